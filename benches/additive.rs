@@ -0,0 +1,38 @@
+// 加算合成のエンジンの1秒分の生成時間。simd フィーチャーの有無で比べる:
+// cargo bench --bench additive と cargo bench --bench additive --features simd
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use synthesizer::{AdditiveEngine, HARMONIC_COUNT};
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+fn engine(harmonics: usize, layers: usize) -> AdditiveEngine {
+    let mut engine = AdditiveEngine::new(SAMPLE_RATE);
+    for i in 0..HARMONIC_COUNT {
+        engine.set_harmonic_amplitude(i, if i < harmonics { 1.0 / (i + 1) as f32 } else { 0.0 });
+    }
+    engine.set_base_frequency(55.0);
+    engine.set_stack(layers, 20.0, 1.0);
+    engine.set_width(0.5);
+    engine
+}
+
+fn additive(c: &mut Criterion) {
+    let mut group = c.benchmark_group("additive_one_second");
+    for (name, harmonics, layers) in [("64_harmonics", 64, 1), ("64_harmonics_7_layers", 64, 7), ("13_harmonics", 13, 1)] {
+        let mut engine = engine(harmonics, layers);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut sum = 0.0;
+                for _ in 0..SAMPLE_RATE as usize {
+                    let [left, right] = engine.next_frame();
+                    sum += left + right;
+                }
+                black_box(sum)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, additive);
+criterion_main!(benches);
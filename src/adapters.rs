@@ -0,0 +1,87 @@
+use crate::event::ScheduledEvent;
+use crate::synth::Synthesizer;
+use std::collections::VecDeque;
+
+// スケジュール済みイベント列を再生しながらサンプルを生成するイテレーター。
+// cpal を使わずに rodio や dasp などのエコシステムへ音声を渡すためのもの。
+pub struct SynthStream {
+    synth: Synthesizer,
+    events: VecDeque<ScheduledEvent>,
+    position: u64,
+}
+
+impl SynthStream {
+    pub fn new(synth: Synthesizer, mut events: Vec<ScheduledEvent>) -> Self {
+        events.sort_by_key(|event| event.sample);
+        Self {
+            synth,
+            events: events.into(),
+            position: 0,
+        }
+    }
+    
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+    
+    pub fn synth(&self) -> &Synthesizer {
+        &self.synth
+    }
+    
+    pub fn synth_mut(&mut self) -> &mut Synthesizer {
+        &mut self.synth
+    }
+    
+    pub fn into_inner(self) -> Synthesizer {
+        self.synth
+    }
+    
+    fn next_sample(&mut self) -> f32 {
+        while let Some(event) = self.events.front() {
+            if event.sample > self.position {
+                break;
+            }
+            self.synth.handle_event(&event.event);
+            self.events.pop_front();
+        }
+        self.position += 1;
+        self.synth.next_sample()
+    }
+}
+
+// 無限に続くモノラルのサンプル列（必要な長さは take などで切り出す）
+impl Iterator for SynthStream {
+    type Item = f32;
+    
+    fn next(&mut self) -> Option<f32> {
+        Some(self.next_sample())
+    }
+}
+
+#[cfg(feature = "rodio")]
+impl rodio::Source for SynthStream {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    
+    fn channels(&self) -> u16 {
+        1
+    }
+    
+    fn sample_rate(&self) -> u32 {
+        self.synth.sample_rate() as u32
+    }
+    
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+#[cfg(feature = "dasp")]
+impl dasp_signal::Signal for SynthStream {
+    type Frame = f32;
+    
+    fn next(&mut self) -> f32 {
+        self.next_sample()
+    }
+}
@@ -0,0 +1,67 @@
+// リアルタイム処理中のメモリの確保と解放を数える計数アロケーター。
+// 回数はスレッドごとに数えるので、オーディオスレッドやレンダリングの区間だけを他のスレッドと混ぜずに測れる。
+// テストでは常にグローバルアロケーターとして組み込み、アプリケーションでは alloc-audit フィーチャーで組み込む
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+pub struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static DEALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+// スレッドの終了中は thread_local に触れられないので数えない
+fn record() {
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+}
+
+fn record_release() {
+    let _ = DEALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record();
+        System.alloc(layout)
+    }
+    
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record();
+        System.alloc_zeroed(layout)
+    }
+    
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record();
+        System.realloc(ptr, layout, new_size)
+    }
+    
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        record_release();
+        System.dealloc(ptr, layout)
+    }
+}
+
+// このスレッドでこれまでに起きたメモリ確保（再確保を含む）の回数
+pub fn thread_allocations() -> u64 {
+    ALLOCATIONS.try_with(Cell::get).unwrap_or(0)
+}
+
+// f の実行中にこのスレッドで起きたメモリ確保の回数を結果と一緒に返す
+pub fn audit<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    let before = thread_allocations();
+    let result = f();
+    (result, thread_allocations() - before)
+}
+
+// このスレッドでこれまでに起きたメモリ解放の回数
+pub fn thread_deallocations() -> u64 {
+    DEALLOCATIONS.try_with(Cell::get).unwrap_or(0)
+}
+
+// f の実行中にこのスレッドで起きたメモリ解放の回数を結果と一緒に返す（外したものを解放せずに渡しているかを確かめる）
+pub fn audit_deallocations<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    let before = thread_deallocations();
+    let result = f();
+    (result, thread_deallocations() - before)
+}
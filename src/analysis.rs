@@ -0,0 +1,112 @@
+// 解析用の信号取り出し口（タップ）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tap {
+    Additive,   // 加算合成エンジン単体（ブレンド・エンベロープ適用後、フィルター前）
+    Fm,         // FMエンジン単体（同上）
+    PostFilter, // ボイスのフィルター通過後、エフェクト前
+}
+
+impl Tap {
+    pub const ALL: [Tap; 3] = [Tap::Additive, Tap::Fm, Tap::PostFilter];
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            Tap::Additive => "additive",
+            Tap::Fm => "fm",
+            Tap::PostFilter => "post-filter",
+        }
+    }
+}
+
+// 1フレーム分の各タップの信号
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TapFrame {
+    pub additive: [f32; 2],
+    pub fm: [f32; 2],
+    pub post_filter: [f32; 2],
+}
+
+impl TapFrame {
+    pub fn get(&self, tap: Tap) -> [f32; 2] {
+        match tap {
+            Tap::Additive => self.additive,
+            Tap::Fm => self.fm,
+            Tap::PostFilter => self.post_filter,
+        }
+    }
+    
+    pub fn add(&mut self, other: &TapFrame) {
+        for (sum, value) in [
+            (&mut self.additive, other.additive),
+            (&mut self.fm, other.fm),
+            (&mut self.post_filter, other.post_filter),
+        ] {
+            sum[0] += value[0];
+            sum[1] += value[1];
+        }
+    }
+    
+    pub fn scale(&mut self, gain: f32) {
+        for frame in [&mut self.additive, &mut self.fm, &mut self.post_filter] {
+            frame[0] *= gain;
+            frame[1] *= gain;
+        }
+    }
+}
+
+// 各タップの直近の信号を保持する解析バッファ（スコープ・スペクトル表示用）
+#[derive(Debug, Clone)]
+pub struct AnalysisTaps {
+    enabled: bool,
+    frames: Vec<TapFrame>, // リングバッファ（確保済み）
+    write_index: usize,
+    filled: bool,
+    last: TapFrame,
+}
+
+impl AnalysisTaps {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            enabled: false,
+            frames: vec![TapFrame::default(); capacity.max(1)],
+            write_index: 0,
+            filled: false,
+            last: TapFrame::default(),
+        }
+    }
+    
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.write_index = 0;
+            self.filled = false;
+            self.last = TapFrame::default();
+        }
+    }
+    
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    
+    pub fn push(&mut self, frame: TapFrame) {
+        self.last = frame;
+        self.frames[self.write_index] = frame;
+        self.write_index += 1;
+        if self.write_index == self.frames.len() {
+            self.write_index = 0;
+            self.filled = true;
+        }
+    }
+    
+    // 最後に生成したフレームのタップ（オフラインでのステム書き出し用）
+    pub fn last(&self) -> &TapFrame {
+        &self.last
+    }
+    
+    // 指定タップの信号を古い順に取り出す
+    pub fn snapshot(&self, tap: Tap) -> Vec<[f32; 2]> {
+        let (newer, older) = self.frames.split_at(self.write_index);
+        let older = if self.filled { older } else { &[] };
+        older.iter().chain(newer).map(|frame| frame.get(tap)).collect()
+    }
+}
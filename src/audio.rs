@@ -1,87 +1,916 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::SampleFormat;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::handle::{AudioSide, SynthHandle};
+use crate::log::LogEvent;
+use crate::surround::ChannelLayout;
 use crate::synth::Synthesizer;
+#[cfg(feature = "alloc-audit")]
+use crate::alloc_audit::thread_allocations;
 
-pub struct AudioOutput {
-    stream: Option<cpal::Stream>,
-    synth: Arc<Mutex<Synthesizer>>,
+const WATCHDOG_POLL: Duration = Duration::from_millis(50);      // 停止の確認間隔
+const WATCHDOG_INTERVAL: Duration = Duration::from_millis(500); // ストリームの故障を確かめて開き直す間隔
+const RETURN_INTERVAL: Duration = Duration::from_secs(2);       // 代わりの機器で鳴らしている間、指定した機器が戻ったか確かめる間隔
+const STATS_SMOOTHING: f32 = 0.05; // 処理時間の移動平均の係数（コールバックごと）
+const JACK_HOST: &str = "jack"; // cpal の JACK ホストの名前
+
+// コールバック間隔からアンダーランを推定する
+struct CallbackTimer {
+    sample_rate: f32,
+    last: Option<(cpal::StreamInstant, usize)>,
 }
 
-impl AudioOutput {
-    pub fn new(synth: Arc<Mutex<Synthesizer>>) -> Result<Self, Box<dyn std::error::Error>> {
-        Ok(Self {
-            stream: None,
-            synth,
-        })
+impl CallbackTimer {
+    fn new(sample_rate: f32) -> Self {
+        Self { sample_rate, last: None }
+    }
+    
+    // 前回のバッファ長より大幅に間隔が空いた場合、その遅れ（ミリ秒）を返す
+    fn check(&mut self, info: &cpal::OutputCallbackInfo, frames: usize) -> Option<f32> {
+        let now = info.timestamp().callback;
+        let late = self.last.and_then(|(last, last_frames)| {
+            let elapsed = now.duration_since(&last)?.as_secs_f32();
+            let expected = last_frames as f32 / self.sample_rate;
+            (elapsed > expected * 1.5).then_some((elapsed - expected) * 1000.0)
+        });
+        self.last = Some((now, frames));
+        late
     }
+}
 
-    pub fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let host = cpal::default_host();
-        let device = host.default_output_device()
-            .ok_or("No output device found")?;
+// オーディオのコールバックの計測値。処理時間はバッファの長さ（次のコールバックまでの締め切り）と比べる
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AudioStats {
+    pub callbacks: u64,
+    pub frames: u64,
+    pub underruns: u64,       // コールバックの間隔がバッファの長さより大幅に空いた回数
+    pub deadline_misses: u64, // 処理に締め切り以上かかった回数
+    pub worst_gap_ms: f32,    // アンダーランで空いた最大の間隔
+    pub last_ms: f32,         // 直近のコールバックの処理時間
+    pub average_ms: f32,      // 処理時間の移動平均
+    pub max_ms: f32,
+    pub deadline_ms: f32,     // 直近のバッファの長さ
+    pub allocations: u64,          // コールバック中のメモリ確保の回数（alloc-audit フィーチャーのときだけ数える）
+    pub allocating_callbacks: u64, // メモリ確保の起きたコールバックの数
+    pub silent_callbacks: u64,     // 制御側が Synthesizer を借りていて無音を出したコールバックの数
+}
 
-        let config = device.default_output_config()?;
-        let sample_rate = config.sample_rate().0 as f32;
+impl AudioStats {
+    // 締め切りに対する処理時間の割合（1.0 で間に合わない）
+    pub fn average_load(&self) -> f32 {
+        if self.deadline_ms > 0.0 { self.average_ms / self.deadline_ms } else { 0.0 }
+    }
+    
+    pub fn peak_load(&self) -> f32 {
+        if self.deadline_ms > 0.0 { self.max_ms / self.deadline_ms } else { 0.0 }
+    }
+    
+    fn record(&mut self, frames: usize, elapsed_ms: f32, deadline_ms: f32, gap_ms: Option<f32>, allocations: u64) {
+        self.average_ms = if self.callbacks == 0 {
+            elapsed_ms
+        } else {
+            self.average_ms + (elapsed_ms - self.average_ms) * STATS_SMOOTHING
+        };
+        self.callbacks += 1;
+        self.frames += frames as u64;
+        self.last_ms = elapsed_ms;
+        self.max_ms = self.max_ms.max(elapsed_ms);
+        self.deadline_ms = deadline_ms;
+        if elapsed_ms >= deadline_ms {
+            self.deadline_misses += 1;
+        }
+        if let Some(gap_ms) = gap_ms {
+            self.underruns += 1;
+            self.worst_gap_ms = self.worst_gap_ms.max(gap_ms);
+        }
+        if allocations > 0 {
+            self.allocations += allocations;
+            self.allocating_callbacks += 1;
+        }
+    }
+}
 
-        let synth_clone = Arc::clone(&self.synth);
-        
-        let stream = match config.sample_format() {
-            SampleFormat::F32 => {
-                device.build_output_stream(
-                    &config.into(),
-                    move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                        let mut synth = synth_clone.lock().unwrap();
-                        for sample in data.iter_mut() {
-                            *sample = synth.next_sample();
-                        }
-                    },
-                    |err| eprintln!("Audio error: {}", err),
-                    None,
-                )?
+// コールバックが計測値を書き込み、ほかのスレッドが読む。コールバックは手元で数え、フィールドごとに原子変数へ写す
+// （フィールドの間で揃っている保証は無い）
+#[derive(Default)]
+pub(crate) struct SharedStats {
+    counts: [AtomicU64; 7], // callbacks, frames, underruns, deadline_misses, allocations, allocating_callbacks, silent_callbacks
+    times: [AtomicU32; 5],  // worst_gap_ms, last_ms, average_ms, max_ms, deadline_ms（f32 のビット列）
+    reset: AtomicBool,      // 次のコールバックで手元の計測値も消す
+}
+
+impl SharedStats {
+    fn store(&self, stats: &AudioStats) {
+        let counts = [
+            stats.callbacks,
+            stats.frames,
+            stats.underruns,
+            stats.deadline_misses,
+            stats.allocations,
+            stats.allocating_callbacks,
+            stats.silent_callbacks,
+        ];
+        let times = [stats.worst_gap_ms, stats.last_ms, stats.average_ms, stats.max_ms, stats.deadline_ms];
+        for (shared, value) in self.counts.iter().zip(counts) {
+            shared.store(value, Ordering::Relaxed);
+        }
+        for (shared, value) in self.times.iter().zip(times) {
+            shared.store(value.to_bits(), Ordering::Relaxed);
+        }
+    }
+    
+    pub(crate) fn load(&self) -> AudioStats {
+        let [callbacks, frames, underruns, deadline_misses, allocations, allocating_callbacks, silent_callbacks] =
+            self.counts.each_ref().map(|count| count.load(Ordering::Relaxed));
+        let [worst_gap_ms, last_ms, average_ms, max_ms, deadline_ms] =
+            self.times.each_ref().map(|time| f32::from_bits(time.load(Ordering::Relaxed)));
+        AudioStats {
+            callbacks,
+            frames,
+            underruns,
+            deadline_misses,
+            worst_gap_ms,
+            last_ms,
+            average_ms,
+            max_ms,
+            deadline_ms,
+            allocations,
+            allocating_callbacks,
+            silent_callbacks,
+        }
+    }
+}
+
+// コールバック中のメモリ確保は alloc-audit フィーチャーのときだけ数える
+#[cfg(not(feature = "alloc-audit"))]
+fn thread_allocations() -> u64 {
+    0
+}
+
+// コールバックの処理時間とバッファの長さの比（1.0 で処理が間に合わない）
+fn dsp_load(started: Instant, frames: usize, sample_rate: f32) -> f32 {
+    let budget = frames as f32 / sample_rate;
+    if budget <= 0.0 {
+        return 0.0;
+    }
+    started.elapsed().as_secs_f32() / budget
+}
+
+// バックエンドが報告したコールバックから再生までの時間（サンプル）
+fn output_latency(info: &cpal::OutputCallbackInfo, sample_rate: f32) -> Option<u32> {
+    let timestamp = info.timestamp();
+    let latency = timestamp.playback.duration_since(&timestamp.callback)?;
+    Some((latency.as_secs_f32() * sample_rate) as u32)
+}
+
+// ステレオフレームを出力チャンネルへ割り当てる（モノラル機器と3ch目以降は中央のミックス）
+fn channel_sample(frame: [f32; 2], channels: usize, channel: usize) -> f32 {
+    match (channels, channel) {
+        (1, _) => (frame[0] + frame[1]) * 0.5,
+        (_, 0) => frame[0],
+        (_, 1) => frame[1],
+        _ => (frame[0] + frame[1]) * 0.5,
+    }
+}
+
+// コールバックをまたいで使う状態（バッファはより大きなものが来たときだけ拡張する）
+pub(crate) struct CallbackState {
+    sample_rate: f32,
+    channels: usize,
+    timer: CallbackTimer,
+    stats: AudioStats,
+    shared_stats: Arc<SharedStats>,
+    front: Vec<[f32; 2]>,
+    rear: Vec<[f32; 2]>,
+}
+
+impl CallbackState {
+    // 計測値は開き直す前から続けて数える
+    pub(crate) fn new(sample_rate: f32, channels: usize, shared_stats: Arc<SharedStats>) -> Self {
+        let stats = shared_stats.load();
+        Self {
+            sample_rate,
+            channels,
+            timer: CallbackTimer::new(sample_rate),
+            stats,
+            shared_stats,
+            front: Vec::with_capacity(4096),
+            rear: Vec::with_capacity(4096),
+        }
+    }
+    
+    // cpal のコールバック。時刻からアンダーランとレイテンシを読み取って render に渡す
+    fn fill<T>(&mut self, side: &mut AudioSide, data: &mut [T], info: &cpal::OutputCallbackInfo, convert: impl Fn(f32) -> T) {
+        let gap_ms = self.timer.check(info, data.len() / self.channels);
+        self.render(side, data, gap_ms, output_latency(info, self.sample_rate), convert);
+    }
+    
+    // 1回分を生成して data に書き込む。制御側が Synthesizer を借りている間は無音を書く。
+    // gap_ms はアンダーランで空いた間隔、latency はバックエンドが報告したレイテンシ（サンプル）
+    pub(crate) fn render<T>(
+        &mut self,
+        side: &mut AudioSide,
+        data: &mut [T],
+        gap_ms: Option<f32>,
+        latency: Option<u32>,
+        convert: impl Fn(f32) -> T,
+    ) {
+        let started = Instant::now();
+        let allocations = thread_allocations();
+        let frames = data.len() / self.channels;
+        match side.synth() {
+            Some(synth) => self.render_synth(synth, data, gap_ms, latency, &convert, started),
+            None => {
+                for sample in data.iter_mut() {
+                    *sample = convert(0.0);
+                }
+                self.stats.silent_callbacks += 1;
             }
-            SampleFormat::I16 => {
-                device.build_output_stream(
-                    &config.into(),
-                    move |data: &mut [i16], _: &cpal::OutputCallbackInfo| {
-                        let mut synth = synth_clone.lock().unwrap();
-                        for sample in data.iter_mut() {
-                            let float_sample = synth.next_sample();
-                            *sample = (float_sample * i16::MAX as f32) as i16;
-                        }
-                    },
-                    |err| eprintln!("Audio error: {}", err),
-                    None,
-                )?
+        }
+        side.lend_if_wanted();
+        self.record_stats(started, frames, gap_ms, thread_allocations() - allocations);
+    }
+    
+    // サラウンドの配置ではスピーカーごとに割り当て、ステレオでは後方を含まないステレオミックスをチャンネルへ割り当てる
+    fn render_synth<T>(
+        &mut self,
+        synth: &mut Synthesizer,
+        data: &mut [T],
+        gap_ms: Option<f32>,
+        latency: Option<u32>,
+        convert: &impl Fn(f32) -> T,
+        started: Instant,
+    ) {
+        let frames = data.len() / self.channels;
+        if let Some(gap_ms) = gap_ms {
+            synth.report(LogEvent::Underrun { gap_ms });
+        }
+        if let Some(latency) = latency {
+            synth.set_output_latency(latency);
+        }
+        if self.front.len() < frames {
+            self.front.resize(frames, [0.0; 2]);
+            self.rear.resize(frames, [0.0; 2]);
+        }
+        
+        let layout = synth.surround_layout();
+        let (front, rear) = (&mut self.front[..frames], &mut self.rear[..frames]);
+        if layout.is_surround() {
+            synth.render_surround_block(front, rear);
+            for ((frame, &front), &rear) in data.chunks_mut(self.channels).zip(front.iter()).zip(rear.iter()) {
+                for (channel, sample) in frame.iter_mut().enumerate() {
+                    *sample = convert(layout.speaker_sample(front, rear, channel));
+                }
             }
-            SampleFormat::U16 => {
-                device.build_output_stream(
-                    &config.into(),
-                    move |data: &mut [u16], _: &cpal::OutputCallbackInfo| {
-                        let mut synth = synth_clone.lock().unwrap();
-                        for sample in data.iter_mut() {
-                            let float_sample = synth.next_sample();
-                            *sample = ((float_sample + 1.0) * 0.5 * u16::MAX as f32) as u16;
-                        }
-                    },
-                    |err| eprintln!("Audio error: {}", err),
-                    None,
-                )?
+        } else {
+            synth.render_block(front);
+            for (frame, &stereo) in data.chunks_mut(self.channels).zip(front.iter()) {
+                for (channel, sample) in frame.iter_mut().enumerate() {
+                    *sample = convert(channel_sample(stereo, self.channels, channel));
+                }
             }
-            _ => {
-                return Err("Unsupported sample format".into());
+        }
+        // サラウンド出力中は前方の2チャンネルを録音する
+        synth.capture_output(front);
+        synth.report_dsp_load(dsp_load(started, frames, self.sample_rate));
+        synth.mark_block_rendered(started);
+        synth.publish_telemetry();
+    }
+    
+    fn record_stats(&mut self, started: Instant, frames: usize, gap_ms: Option<f32>, allocations: u64) {
+        if self.shared_stats.reset.swap(false, Ordering::Relaxed) {
+            self.stats = AudioStats::default();
+        }
+        let deadline_ms = frames as f32 * 1000.0 / self.sample_rate;
+        self.stats.record(frames, started.elapsed().as_secs_f32() * 1000.0, deadline_ms, gap_ms, allocations);
+        self.shared_stats.store(&self.stats);
+    }
+}
+
+// ストリームのエラーを表示し、作り直しが必要なことを記録する（監視スレッドが開き直す）
+fn error_callback(failed: &Arc<AtomicBool>, status: &Arc<Mutex<AudioStatus>>) -> impl FnMut(cpal::StreamError) + Send + 'static {
+    let (failed, status) = (Arc::clone(failed), Arc::clone(status));
+    move |err| {
+        eprintln!("Audio error: {}", err);
+        status.lock().unwrap().last_error = Some(err.to_string());
+        failed.store(true, Ordering::Relaxed);
+    }
+}
+
+// 機器が対応する設定の中から、要求に最も近いサンプルレートを選ぶ（ranges は (最小, 最大)）。
+// 要求が無ければ既定の設定のレートを使う
+fn negotiate_sample_rate(ranges: &[(u32, u32)], default: u32, requested: Option<u32>) -> u32 {
+    let Some(requested) = requested else {
+        return default;
+    };
+    ranges
+        .iter()
+        .map(|&(min, max)| requested.clamp(min, max))
+        .min_by_key(|rate| rate.abs_diff(requested))
+        .unwrap_or(default)
+}
+
+// 要求したバッファの長さを機器の範囲に収める（範囲が分からなければそのまま頼む）
+fn negotiate_buffer_size(supported: &cpal::SupportedBufferSize, requested: Option<u32>) -> cpal::BufferSize {
+    match (requested, supported) {
+        (None, _) => cpal::BufferSize::Default,
+        (Some(frames), cpal::SupportedBufferSize::Range { min, max }) => cpal::BufferSize::Fixed(frames.clamp(*min, *max)),
+        (Some(frames), cpal::SupportedBufferSize::Unknown) => cpal::BufferSize::Fixed(frames),
+    }
+}
+
+// 実際に開いたストリームの設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamSettings {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub buffer_frames: Option<u32>, // None はバックエンドの既定（長さは分からない）
+}
+
+impl StreamSettings {
+    // 1バッファ分の長さ（ミリ秒）。バックエンドが報告する出力レイテンシは Synthesizer::output_latency_seconds
+    pub fn buffer_latency_ms(&self) -> Option<f32> {
+        self.buffer_frames.map(|frames| frames as f32 * 1000.0 / self.sample_rate as f32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AudioState {
+    #[default]
+    Stopped,
+    Running,
+    Recovering, // ストリームが止まり、開き直せる出力機器を探している
+}
+
+impl std::fmt::Display for AudioState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AudioState::Stopped => write!(f, "stopped"),
+            AudioState::Running => write!(f, "running"),
+            AudioState::Recovering => write!(f, "recovering"),
+        }
+    }
+}
+
+// 出力の状態。監視スレッドが機器を開き直すたびに更新する
+#[derive(Debug, Clone, Default)]
+pub struct AudioStatus {
+    pub state: AudioState,
+    pub host: Option<String>,   // 開いているホスト（小文字）
+    pub device: Option<String>, // 開いている出力機器（JACK ではクライアントの名前）
+    pub settings: Option<StreamSettings>,
+    pub fallback: bool,    // 指定した機器が見つからず、既定の機器で代わりに鳴らしている
+    pub recoveries: u32,   // 機器が外れたあと自動で開き直した回数
+    pub last_error: Option<String>,
+}
+
+// ストリームを開くための要求。監視スレッドも同じ要求で開き直すので、複製して渡す
+#[derive(Debug, Clone, Default)]
+struct OutputRequest {
+    host_name: Option<String>,   // オーディオのホスト（"alsa"、"jack" など。None ならプラットフォームの既定）
+    device_name: Option<String>, // 使う出力機器の名前（None なら既定の機器）
+    sample_rate: Option<u32>,    // 要求するサンプルレート（None なら機器の既定）
+    buffer_size: Option<u32>,    // 要求するバッファの長さ（フレーム、None ならバックエンドの既定）
+}
+
+// ホストの名前から開く（このビルドに無いホストなら None）
+fn host_by_name(name: &str) -> Option<cpal::Host> {
+    let id = cpal::available_hosts().into_iter().find(|id| id.name().eq_ignore_ascii_case(name))?;
+    cpal::host_from_id(id).ok()
+}
+
+// ホストを指定しないときに使うホスト。jack フィーチャー付きでは JACK のサーバーに出力できればそれを使い、
+// サーバーが動いていなければプラットフォームの既定に戻す
+fn default_host() -> cpal::Host {
+    if cfg!(feature = "jack") {
+        if let Some(host) = host_by_name(JACK_HOST).filter(|host| host.default_output_device().is_some()) {
+            return host;
+        }
+    }
+    cpal::default_host()
+}
+
+impl OutputRequest {
+    fn host_name(&self) -> String {
+        match &self.host_name {
+            Some(name) => name.clone(),
+            None => default_host().id().name().to_lowercase(),
+        }
+    }
+    
+    fn open_host(&self) -> Result<cpal::Host, Box<dyn std::error::Error>> {
+        let Some(name) = &self.host_name else {
+            return Ok(default_host());
+        };
+        match host_by_name(name) {
+            Some(host) => Ok(host),
+            None if name.eq_ignore_ascii_case(JACK_HOST) => {
+                Err("audio host not available: jack (build with the \"jack\" feature and start the JACK server)".into())
             }
+            None => Err(format!("audio host not available: {} (available: {})", name, AudioOutput::list_hosts().join(", ")).into()),
+        }
+    }
+    
+    fn list_devices(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let host = self.open_host()?;
+        Ok(host.output_devices()?.filter_map(|device| device.name().ok()).collect())
+    }
+    
+    // 名前が完全に一致する機器を優先し、無ければ大文字・小文字を区別せずに名前の一部で探す
+    fn find_device(name: &str, candidates: &[String]) -> Option<usize> {
+        let lowercase = name.to_lowercase();
+        candidates
+            .iter()
+            .position(|candidate| candidate == name)
+            .or_else(|| candidates.iter().position(|candidate| candidate.to_lowercase().contains(&lowercase)))
+    }
+    
+    // 機器と、それを開いたホストの名前（小文字）
+    fn open_device(&self) -> Result<(cpal::Device, String), Box<dyn std::error::Error>> {
+        let host = self.open_host()?;
+        let host_name = host.id().name().to_lowercase();
+        let Some(name) = &self.device_name else {
+            return Ok((host.default_output_device().ok_or("No output device found")?, host_name));
         };
+        let (names, devices): (Vec<String>, Vec<cpal::Device>) = host
+            .output_devices()?
+            .filter_map(|device| Some((device.name().ok()?, device)))
+            .unzip();
+        match Self::find_device(name, &names) {
+            Some(position) => Ok((devices.into_iter().nth(position).ok_or("No output device found")?, host_name)),
+            None => Err(format!("output device not found: {}", name).into()),
+        }
+    }
+    
+    // 指定した機器が今つながっているか（指定が無ければ常に true）
+    fn device_present(&self) -> bool {
+        let Some(name) = &self.device_name else {
+            return true;
+        };
+        self.list_devices().is_ok_and(|names| Self::find_device(name, &names).is_some())
+    }
+    
+    // 指定した機器の代わりに既定の機器を開く要求
+    fn fallback(&self) -> Self {
+        Self { device_name: None, ..self.clone() }
+    }
+}
+
+// 開いたストリームと、その機器の名前・実際の設定
+struct OpenedStream {
+    stream: cpal::Stream,
+    host: String,
+    device: String,
+    settings: StreamSettings,
+}
 
-        stream.play()?;
+fn open_stream(
+    request: &OutputRequest,
+    synth: &SynthHandle,
+    failed: &Arc<AtomicBool>,
+    status: &Arc<Mutex<AudioStatus>>,
+    stats: &Arc<SharedStats>,
+) -> Result<OpenedStream, Box<dyn std::error::Error>> {
+    let (device, host_name) = request.open_device()?;
+    let device_name = device.name().unwrap_or_else(|_| "unknown device".to_string());
+    
+    // チャンネル数とサンプル形式は既定の設定に合わせ、その中からサンプルレートを選ぶ
+    let default_config = device.default_output_config()?;
+    let sample_format = default_config.sample_format();
+    let candidates: Vec<cpal::SupportedStreamConfigRange> = device
+        .supported_output_configs()
+        .map(|configs| {
+            configs
+                .filter(|range| range.channels() == default_config.channels() && range.sample_format() == sample_format)
+                .collect()
+        })
+        .unwrap_or_default();
+    let ranges: Vec<(u32, u32)> = candidates.iter().map(|range| (range.min_sample_rate().0, range.max_sample_rate().0)).collect();
+    let rate = negotiate_sample_rate(&ranges, default_config.sample_rate().0, request.sample_rate);
+    let supported_buffer = candidates
+        .iter()
+        .find(|range| (range.min_sample_rate().0..=range.max_sample_rate().0).contains(&rate))
+        .map_or(default_config.buffer_size(), |range| range.buffer_size());
+    let config = cpal::StreamConfig {
+        channels: default_config.channels(),
+        sample_rate: cpal::SampleRate(rate),
+        buffer_size: negotiate_buffer_size(supported_buffer, request.buffer_size),
+    };
+    if let Some(requested) = request.sample_rate.filter(|requested| *requested != rate) {
+        println!("⚠️  {} does not support {} Hz, using {} Hz", device_name, requested, rate);
+    }
+    
+    let sample_rate = rate as f32;
+    let channels = config.channels as usize;
+    let mut state = CallbackState::new(sample_rate, channels, Arc::clone(stats));
+    let layout = ChannelLayout::for_channels(channels);
+    {
+        // 前のストリームは Supervisor::close で閉じてあるので、すぐに借りられる
+        let mut synth = synth.borrow_or_timeout()?;
+        synth.set_sample_rate(sample_rate);
+        synth.set_output_layout(layout);
+    }
+    
+    // Synthesizer はコールバックが持つ（制御側は SynthHandle::send で送るか、ブロックの合間に SynthHandle::borrow で借りる）
+    let mut side = synth.attach();
+    let settings = StreamSettings {
+        sample_rate: rate,
+        channels: config.channels,
+        buffer_frames: match config.buffer_size {
+            cpal::BufferSize::Fixed(frames) => Some(frames),
+            cpal::BufferSize::Default => None,
+        },
+    };
+    
+    let stream = match sample_format {
+        SampleFormat::F32 => {
+            device.build_output_stream(
+                &config,
+                move |data: &mut [f32], info: &cpal::OutputCallbackInfo| {
+                    state.fill(&mut side, data, info, |sample| sample);
+                },
+                error_callback(failed, status),
+                None,
+            )?
+        }
+        SampleFormat::I16 => {
+            device.build_output_stream(
+                &config,
+                move |data: &mut [i16], info: &cpal::OutputCallbackInfo| {
+                    state.fill(&mut side, data, info, |sample| (sample * i16::MAX as f32) as i16);
+                },
+                error_callback(failed, status),
+                None,
+            )?
+        }
+        SampleFormat::U16 => {
+            device.build_output_stream(
+                &config,
+                move |data: &mut [u16], info: &cpal::OutputCallbackInfo| {
+                    state.fill(&mut side, data, info, |sample| {
+                        ((sample + 1.0) * 0.5 * u16::MAX as f32) as u16
+                    });
+                },
+                error_callback(failed, status),
+                None,
+            )?
+        }
+        _ => {
+            return Err("Unsupported sample format".into());
+        }
+    };
+    
+    stream.play()?;
+    failed.store(false, Ordering::Relaxed);
+    
+    println!("🎵 Audio output started on {} ({}) at {} Hz, {} channels ({})", device_name, host_name, rate, channels, layout);
+    match (settings.buffer_frames, settings.buffer_latency_ms()) {
+        (Some(frames), Some(latency)) => println!("⏱️  Buffer: {} frames ({:.1} ms)", frames, latency),
+        _ => println!("⏱️  Buffer: backend default"),
+    }
+    Ok(OpenedStream { stream, host: host_name, device: device_name, settings })
+}
+
+// 監視スレッドの中でストリームを持つ（cpal::Stream はスレッドをまたげない）
+struct Supervisor {
+    request: OutputRequest,
+    synth: SynthHandle,
+    status: Arc<Mutex<AudioStatus>>,
+    stats: Arc<SharedStats>,
+    failed: Arc<AtomicBool>,
+    stream: Option<cpal::Stream>,
+    fallback: bool,
+}
+
+impl Supervisor {
+    // 指定した機器を開く。allow_fallback なら、開けないときに既定の機器で代わりに鳴らす
+    fn open(&mut self, allow_fallback: bool) -> Result<(), String> {
+        self.close();
+        let opened = match open_stream(&self.request, &self.synth, &self.failed, &self.status, &self.stats) {
+            Ok(opened) => (opened, false),
+            Err(e) if allow_fallback && self.request.device_name.is_some() => {
+                match open_stream(&self.request.fallback(), &self.synth, &self.failed, &self.status, &self.stats) {
+                    Ok(opened) => (opened, true),
+                    Err(_) => return Err(e.to_string()),
+                }
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        let (OpenedStream { stream, host, device, settings }, fallback) = opened;
+        let mut status = self.status.lock().unwrap();
+        status.state = AudioState::Running;
+        status.host = Some(host);
+        status.device = Some(device);
+        status.settings = Some(settings);
+        status.fallback = fallback;
         self.stream = Some(stream);
-        
-        println!("🎵 Audio output started at {} Hz", sample_rate);
+        self.fallback = fallback;
         Ok(())
     }
+    
+    // ストリームを閉じ、コールバックから Synthesizer を取り戻す（開けなかったときも取り戻す）
+    fn close(&mut self) {
+        self.stream = None;
+        self.synth.detach();
+    }
+    
+    // ストリームがエラーを報告していたら機器を数え直して開き直す。開けなければ次の確認でもう一度試す
+    fn recover(&mut self) {
+        if self.stream.is_some() && !self.failed.load(Ordering::Relaxed) {
+            return;
+        }
+        if self.stream.is_some() {
+            self.close();
+            let mut status = self.status.lock().unwrap();
+            status.state = AudioState::Recovering;
+            status.settings = None;
+            println!("🔌 Audio device lost ({}); looking for an output device...", status.device.as_deref().unwrap_or("unknown device"));
+        }
+        match self.open(true) {
+            Ok(()) => {
+                let mut status = self.status.lock().unwrap();
+                status.recoveries += 1;
+                let device = status.device.clone().unwrap_or_default();
+                if status.fallback {
+                    println!("🔁 Audio recovered on fallback device {}", device);
+                } else {
+                    println!("🔁 Audio recovered on {}", device);
+                }
+            }
+            Err(e) => self.status.lock().unwrap().last_error = Some(e),
+        }
+    }
+    
+    // 既定の機器で代わりに鳴らしている間に指定した機器が戻ってきたら、そちらへ戻す
+    fn return_to_preferred(&mut self) {
+        if !self.fallback || self.stream.is_none() || !self.request.device_present() {
+            return;
+        }
+        match self.open(false) {
+            Ok(()) => println!("🔌 {} is back; audio switched to it", self.status.lock().unwrap().device.clone().unwrap_or_default()),
+            Err(e) => {
+                self.status.lock().unwrap().last_error = Some(e);
+                if let Err(e) = self.open(true) {
+                    let mut status = self.status.lock().unwrap();
+                    status.state = AudioState::Recovering;
+                    status.settings = None;
+                    status.last_error = Some(e);
+                }
+            }
+        }
+    }
+}
+
+// ストリームを開いて持ち続けるスレッド。機器が外れたら自動で開き直す
+struct Watchdog {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
 
+impl Watchdog {
+    // 最初のストリームを開けたかどうかはスレッドから受け取って返す
+    fn start(
+        request: OutputRequest,
+        synth: SynthHandle,
+        status: Arc<Mutex<AudioStatus>>,
+        stats: Arc<SharedStats>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (opened_sender, opened) = mpsc::channel();
+        let thread = {
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || {
+                let mut supervisor = Supervisor {
+                    request,
+                    synth,
+                    status: Arc::clone(&status),
+                    stats,
+                    failed: Arc::new(AtomicBool::new(false)),
+                    stream: None,
+                    fallback: false,
+                };
+                let result = supervisor.open(false);
+                let started = result.is_ok();
+                let _ = opened_sender.send(result);
+                if !started {
+                    return;
+                }
+                let mut waited = Duration::ZERO;
+                let mut since_return = Duration::ZERO;
+                while !stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(WATCHDOG_POLL);
+                    waited += WATCHDOG_POLL;
+                    if waited < WATCHDOG_INTERVAL {
+                        continue;
+                    }
+                    since_return += waited;
+                    waited = Duration::ZERO;
+                    supervisor.recover();
+                    if since_return >= RETURN_INTERVAL {
+                        since_return = Duration::ZERO;
+                        supervisor.return_to_preferred();
+                    }
+                }
+                supervisor.close();
+                let mut status = status.lock().unwrap();
+                status.state = AudioState::Stopped;
+                status.host = None;
+                status.device = None;
+                status.settings = None;
+                status.fallback = false;
+            })
+        };
+        match opened.recv() {
+            Ok(Ok(())) => Ok(Self { stop, thread: Some(thread) }),
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(e.into())
+            }
+            Err(_) => {
+                let _ = thread.join();
+                Err("audio thread exited".into())
+            }
+        }
+    }
+    
+    fn halt(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.halt();
+    }
+}
+
+pub struct AudioOutput {
+    synth: SynthHandle,
+    request: OutputRequest,
+    status: Arc<Mutex<AudioStatus>>,
+    stats: Arc<SharedStats>,
+    watchdog: Option<Watchdog>,
+}
+
+impl AudioOutput {
+    pub fn new(synth: SynthHandle) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            synth,
+            request: OutputRequest::default(),
+            status: Arc::new(Mutex::new(AudioStatus::default())),
+            stats: Arc::new(SharedStats::default()),
+            watchdog: None,
+        })
+    }
+    
+    // 要求するサンプルレートとバッファの長さ。機器が対応していなければ最も近い値で開く
+    pub fn with_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.request.sample_rate = Some(sample_rate);
+        self
+    }
+    
+    pub fn with_buffer_size(mut self, frames: u32) -> Self {
+        self.request.buffer_size = Some(frames);
+        self
+    }
+    
+    // 次に開くときの要求（None で既定）。開き直すには restart を呼ぶ
+    pub fn set_sample_rate(&mut self, sample_rate: Option<u32>) {
+        self.request.sample_rate = sample_rate;
+    }
+    
+    pub fn set_buffer_size(&mut self, frames: Option<u32>) {
+        self.request.buffer_size = frames;
+    }
+    
+    pub fn requested_sample_rate(&self) -> Option<u32> {
+        self.request.sample_rate
+    }
+    
+    pub fn requested_buffer_size(&self) -> Option<u32> {
+        self.request.buffer_size
+    }
+    
+    // 開いているストリームの実際の設定
+    pub fn settings(&self) -> Option<StreamSettings> {
+        self.status().settings
+    }
+    
+    // 既定の機器の代わりに名前で選んだ出力機器を使う（start で開くときに探す）
+    pub fn with_device(mut self, name: impl Into<String>) -> Self {
+        self.request.device_name = Some(name.into());
+        self
+    }
+    
+    // 既定の代わりに使うオーディオのホスト（JACK でセッションのグラフにつなぐときなど）
+    pub fn with_host(mut self, name: impl Into<String>) -> Self {
+        self.request.host_name = Some(name.into());
+        self
+    }
+    
+    // このビルドで使えるホストの名前（小文字）。JACK は jack フィーチャー付きでビルドしたときだけ現れる
+    pub fn list_hosts() -> Vec<String> {
+        cpal::available_hosts().iter().map(|id| id.name().to_lowercase()).collect()
+    }
+    
+    // 次に開くときのホスト（None で既定）。機器の名前はホストごとに違うので、機器の指定も既定に戻す
+    pub fn set_host(&mut self, name: Option<String>) {
+        self.request.host_name = name;
+        self.request.device_name = None;
+    }
+    
+    pub fn host_name(&self) -> String {
+        self.request.host_name()
+    }
+    
+    // 既定のホストの出力機器の名前の一覧（ホストが列挙した順）
+    pub fn list_devices() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        OutputRequest::default().list_devices()
+    }
+    
+    pub fn default_device_name() -> Option<String> {
+        default_host().default_output_device().and_then(|device| device.name().ok())
+    }
+    
+    // 選んでいるホストの出力機器の名前の一覧
+    pub fn host_devices(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        self.request.list_devices()
+    }
+    
+    pub fn host_default_device_name(&self) -> Option<String> {
+        self.request.open_host().ok()?.default_output_device().and_then(|device| device.name().ok())
+    }
+    
+    // JACK で鳴らしているときの出力ポートの名前（"クライアント:out_N"）。cpal がシステムの再生ポートへ順につなぐ
+    pub fn output_ports(&self) -> Vec<String> {
+        let status = self.status();
+        match (status.host.as_deref(), status.device, status.settings) {
+            (Some(JACK_HOST), Some(client), Some(settings)) => (0..settings.channels).map(|index| format!("{}:out_{}", client, index)).collect(),
+            _ => Vec::new(),
+        }
+    }
+    
+    // 次に開くときの出力機器（None で既定の機器）。開き直すには restart を呼ぶ
+    pub fn set_device(&mut self, name: Option<String>) {
+        self.request.device_name = name;
+    }
+    
+    pub fn device_name(&self) -> Option<&str> {
+        self.request.device_name.as_deref()
+    }
+    
+    // 指定した機器で開けなければエラーを返す（既定の機器への切り替えは、開いたあとに機器が外れたときだけ）
+    pub fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.watchdog = None;
+        let watchdog = Watchdog::start(
+            self.request.clone(),
+            self.synth.clone(),
+            Arc::clone(&self.status),
+            Arc::clone(&self.stats),
+        );
+        if let Err(e) = &watchdog {
+            self.status.lock().unwrap().last_error = Some(e.to_string());
+        }
+        self.watchdog = Some(watchdog?);
+        Ok(())
+    }
+    
     pub fn stop(&mut self) {
-        self.stream = None;
+        self.watchdog = None;
         println!("🔇 Audio output stopped");
     }
-} 
\ No newline at end of file
+    
+    pub fn status(&self) -> AudioStatus {
+        self.status.lock().unwrap().clone()
+    }
+    
+    // コールバックの計測値（開き直しても続けて数える）
+    pub fn stats(&self) -> AudioStats {
+        self.stats.load()
+    }
+    
+    pub fn reset_stats(&self) {
+        self.stats.store(&AudioStats::default());
+        self.stats.reset.store(true, Ordering::Relaxed);
+    }
+    
+    pub fn is_running(&self) -> bool {
+        self.status().state == AudioState::Running
+    }
+    
+    // ストリームが止まり、監視スレッドが開き直せる機器をまだ見つけていない
+    pub fn has_failed(&self) -> bool {
+        self.status().state == AudioState::Recovering
+    }
+    
+    // ストリームを閉じて開き直す（機器を指定していなければ、その時点の既定の出力機器）
+    pub fn restart(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.start()
+    }
+}
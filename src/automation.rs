@@ -0,0 +1,61 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RampCurve {
+    #[default]
+    Linear,
+    Exponential, // 等比で変化する（始点・終点のどちらかが 0 以下なら直線）
+}
+
+impl fmt::Display for RampCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RampCurve::Linear => "lin",
+            RampCurve::Exponential => "exp",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for RampCurve {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lin" | "linear" => Ok(RampCurve::Linear),
+            "exp" | "exponential" => Ok(RampCurve::Exponential),
+            _ => Err(format!("unknown ramp curve: {}", s)),
+        }
+    }
+}
+
+// パラメータを start のサンプルから length サンプルかけて to へ動かす。
+// from が None なら開始時点の値から動かす
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterRamp {
+    pub parameter: String,
+    pub from: Option<f32>,
+    pub to: f32,
+    pub start: u64,
+    pub length: u64,
+    pub curve: RampCurve,
+}
+
+impl ParameterRamp {
+    // sample の位置での値（開始前は None）と、終点に到達したか
+    pub fn value_at(&self, sample: u64) -> (Option<f32>, bool) {
+        let (Some(from), Some(elapsed)) = (self.from, sample.checked_sub(self.start)) else {
+            return (None, false);
+        };
+        if elapsed >= self.length {
+            return (Some(self.to), true);
+        }
+        let progress = elapsed as f32 / self.length as f32;
+        let value = match self.curve {
+            RampCurve::Exponential if from > 0.0 && self.to > 0.0 => from * (self.to / from).powf(progress),
+            _ => from + (self.to - from) * progress,
+        };
+        (Some(value), false)
+    }
+}
@@ -0,0 +1,245 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::patch::PatchState;
+
+pub const PRESET_EXTENSION: &str = "patch";
+
+// バンク内の1つのプリセット。カテゴリはバンクのディレクトリ直下のサブディレクトリ
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PresetEntry {
+    pub category: Option<String>,
+    pub name: String,
+}
+
+impl PresetEntry {
+    // "カテゴリ/名前" または "名前"
+    pub fn parse(id: &str) -> Result<Self, String> {
+        let (category, name) = match id.split_once('/') {
+            Some((category, name)) => (Some(category), name),
+            None => (None, id),
+        };
+        for part in category.into_iter().chain([name]) {
+            if part.is_empty() || part.starts_with('.') || part.contains(['/', '\\']) {
+                return Err(format!("invalid preset name: {}", id));
+            }
+        }
+        Ok(Self { category: category.map(str::to_string), name: name.to_string() })
+    }
+    
+    pub fn id(&self) -> String {
+        match &self.category {
+            Some(category) => format!("{}/{}", category, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+// プリセット（パッチ差分ファイル）を集めたディレクトリ。
+// 一覧はカテゴリ・名前の順で、next / prev はその順に巡回する
+#[derive(Debug, Clone)]
+pub struct PresetBank {
+    root: PathBuf,
+    presets: Vec<PresetEntry>,
+    current: Option<usize>,
+}
+
+impl PresetBank {
+    // ディレクトリを読まずに空のバンクとして作る
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self { root: root.as_ref().to_path_buf(), presets: Vec::new(), current: None }
+    }
+    
+    // ディレクトリが無ければ空のバンク（最初の保存で作る）
+    pub fn open(root: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let mut bank = Self::new(root);
+        bank.refresh()?;
+        Ok(bank)
+    }
+    
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    
+    // ディレクトリを読み直す（外で追加・削除されたファイルを反映する）
+    pub fn refresh(&mut self) -> Result<(), Box<dyn Error>> {
+        let current = self.current_entry().cloned();
+        self.presets.clear();
+        if self.root.is_dir() {
+            self.scan(None)?;
+            for entry in fs::read_dir(&self.root)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if entry.file_type()?.is_dir() && !name.starts_with('.') {
+                    self.scan(Some(name))?;
+                }
+            }
+        }
+        self.presets.sort();
+        self.current = current.and_then(|entry| self.position(&entry));
+        Ok(())
+    }
+    
+    fn scan(&mut self, category: Option<String>) -> Result<(), Box<dyn Error>> {
+        let directory = category.as_ref().map_or(self.root.clone(), |category| self.root.join(category));
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_file() && path.extension().is_some_and(|extension| extension == PRESET_EXTENSION) {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    self.presets.push(PresetEntry { category: category.clone(), name: name.to_string() });
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    pub fn presets(&self) -> &[PresetEntry] {
+        &self.presets
+    }
+    
+    pub fn categories(&self) -> Vec<&str> {
+        let mut categories: Vec<&str> = self.presets.iter().filter_map(|entry| entry.category.as_deref()).collect();
+        categories.dedup();
+        categories
+    }
+    
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+    
+    pub fn current_entry(&self) -> Option<&PresetEntry> {
+        self.current.and_then(|index| self.presets.get(index))
+    }
+    
+    // "カテゴリ/名前" の完全一致、なければカテゴリを問わず名前が一致する唯一のプリセット
+    pub fn find(&self, id: &str) -> Option<usize> {
+        if let Some(index) = self.presets.iter().position(|entry| entry.id() == id) {
+            return Some(index);
+        }
+        let mut matches = self.presets.iter().enumerate().filter(|(_, entry)| entry.name == id);
+        match (matches.next(), matches.next()) {
+            (Some((index, _)), None) => Some(index),
+            _ => None,
+        }
+    }
+    
+    fn position(&self, entry: &PresetEntry) -> Option<usize> {
+        self.presets.iter().position(|other| other == entry)
+    }
+    
+    fn lookup(&self, id: &str) -> Result<PresetEntry, Box<dyn Error>> {
+        self.find(id)
+            .map(|index| self.presets[index].clone())
+            .ok_or_else(|| format!("unknown preset: {}", id).into())
+    }
+    
+    pub fn path(&self, entry: &PresetEntry) -> PathBuf {
+        let directory = entry.category.as_ref().map_or(self.root.clone(), |category| self.root.join(category));
+        directory.join(format!("{}.{}", entry.name, PRESET_EXTENSION))
+    }
+    
+    // 同じ名前のプリセットは上書きする。保存したプリセットが現在のプリセットになる
+    pub fn save(&mut self, id: &str, patch: &PatchState, base_name: &str) -> Result<PresetEntry, Box<dyn Error>> {
+        let entry = PresetEntry::parse(id)?;
+        let path = self.path(&entry);
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        patch.save_delta(&path, base_name)?;
+        self.refresh()?;
+        self.current = self.position(&entry);
+        Ok(entry)
+    }
+    
+    // 読み込んだプリセットが現在のプリセットになる
+    pub fn load(&mut self, index: usize) -> Result<(PatchState, Vec<String>), Box<dyn Error>> {
+        let entry = self.presets.get(index).ok_or("no such preset")?;
+        let loaded = PatchState::load_delta(self.path(entry))?;
+        self.current = Some(index);
+        Ok(loaded)
+    }
+    
+    // 現在のプリセットを変えずに読む（モーフィングの素材など）
+    pub fn read(&self, id: &str) -> Result<(PatchState, Vec<String>), Box<dyn Error>> {
+        PatchState::load_delta(self.path(&self.lookup(id)?))
+    }
+    
+    // 現在のプリセットから offset 個先（負なら前）。端では反対側へ回る
+    pub fn step(&self, offset: isize) -> Option<usize> {
+        let count = self.presets.len() as isize;
+        if count == 0 {
+            return None;
+        }
+        let index = match self.current {
+            Some(current) => (current as isize + offset).rem_euclid(count),
+            None if offset < 0 => count - 1,
+            None => 0,
+        };
+        Some(index as usize)
+    }
+    
+    // カテゴリを付けずに新しい名前だけを指定すると、元のカテゴリのまま改名する
+    pub fn rename(&mut self, id: &str, new_id: &str) -> Result<PresetEntry, Box<dyn Error>> {
+        let entry = self.lookup(id)?;
+        let mut target = PresetEntry::parse(new_id)?;
+        if !new_id.contains('/') {
+            target.category = entry.category.clone();
+        }
+        self.relocate(&entry, target, false)
+    }
+    
+    // category が None ならカテゴリから外す
+    pub fn categorize(&mut self, id: &str, category: Option<&str>) -> Result<PresetEntry, Box<dyn Error>> {
+        let entry = self.lookup(id)?;
+        let target = match category {
+            Some(category) => PresetEntry::parse(&format!("{}/{}", category, entry.name))?,
+            None => PresetEntry { category: None, name: entry.name.clone() },
+        };
+        self.relocate(&entry, target, false)
+    }
+    
+    pub fn copy(&mut self, id: &str, new_id: &str) -> Result<PresetEntry, Box<dyn Error>> {
+        let entry = self.lookup(id)?;
+        let target = PresetEntry::parse(new_id)?;
+        self.relocate(&entry, target, true)
+    }
+    
+    // 既存のプリセットは上書きしない
+    fn relocate(&mut self, entry: &PresetEntry, target: PresetEntry, keep_source: bool) -> Result<PresetEntry, Box<dyn Error>> {
+        let (from, to) = (self.path(entry), self.path(&target));
+        if to.exists() {
+            return Err(format!("preset already exists: {}", target.id()).into());
+        }
+        if let Some(directory) = to.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let was_current = !keep_source && self.current_entry() == Some(entry);
+        if keep_source {
+            fs::copy(&from, &to)?;
+        } else {
+            fs::rename(&from, &to)?;
+            self.remove_empty_category(entry);
+        }
+        self.refresh()?;
+        if was_current {
+            self.current = self.position(&target);
+        }
+        Ok(target)
+    }
+    
+    pub fn delete(&mut self, id: &str) -> Result<PresetEntry, Box<dyn Error>> {
+        let entry = self.lookup(id)?;
+        fs::remove_file(self.path(&entry))?;
+        self.remove_empty_category(&entry);
+        self.refresh()?;
+        Ok(entry)
+    }
+    
+    // 最後のプリセットを移した・消したカテゴリのディレクトリは片付ける（空でなければ残る）
+    fn remove_empty_category(&self, entry: &PresetEntry) {
+        if let Some(category) = &entry.category {
+            let _ = fs::remove_dir(self.root.join(category));
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+pub const HRIR_TAPS: usize = 32;
+const AZIMUTH_STEP: f32 = 5.0; // 度
+const HEAD_RADIUS: f32 = 0.0875; // m
+const SPEED_OF_SOUND: f32 = 343.0; // m/s
+const VIRTUAL_SPREAD: f32 = 30.0; // ボイスの左右チャンネルを置く仮想スピーカーの開き（度）
+
+// 片耳の頭部インパルス応答。到達の遅れ（サンプル、小数あり）と遅れを除いた応答に分けて持つ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hrir {
+    pub delay: f32,
+    pub taps: [f32; HRIR_TAPS],
+}
+
+impl Hrir {
+    fn lerp(&self, other: &Hrir, t: f32) -> Hrir {
+        let mut taps = self.taps;
+        for (tap, other) in taps.iter_mut().zip(other.taps.iter()) {
+            *tap += (other - *tap) * t;
+        }
+        Hrir { delay: self.delay + (other.delay - self.delay) * t, taps }
+    }
+}
+
+// 水平面の方位ごとの [左耳, 右耳] の HRIR（0° = 正面, 90° = 右, AZIMUTH_STEP 刻みで一周）
+#[derive(Debug, Clone)]
+pub struct HrirSet {
+    entries: Vec<[Hrir; 2]>,
+    max_delay: f32,
+}
+
+impl HrirSet {
+    // 内蔵の球頭モデル（Brown & Duda 1998）。両耳間の時間差は Woodworth の式、
+    // 頭の陰は1次のシェルビングフィルターで近似する
+    pub fn spherical_head(sample_rate: f32) -> Self {
+        let count = (360.0 / AZIMUTH_STEP) as usize;
+        let entries: Vec<[Hrir; 2]> = (0..count)
+            .map(|index| {
+                let azimuth = index as f32 * AZIMUTH_STEP;
+                [-90.0, 90.0].map(|ear| spherical_head_hrir(azimuth - ear, sample_rate))
+            })
+            .collect();
+        let max_delay = entries.iter().flatten().map(|hrir| hrir.delay).fold(0.0, f32::max);
+        Self { entries, max_delay }
+    }
+    
+    // 方位（度）の [左耳, 右耳] の HRIR。隣り合う測定点の間は線形補間する
+    pub fn at(&self, azimuth: f32) -> [Hrir; 2] {
+        let position = azimuth.rem_euclid(360.0) / AZIMUTH_STEP;
+        let index = position as usize % self.entries.len();
+        let next = (index + 1) % self.entries.len();
+        let t = position.fract();
+        [0, 1].map(|ear| self.entries[index][ear].lerp(&self.entries[next][ear], t))
+    }
+    
+    pub fn max_delay(&self) -> f32 {
+        self.max_delay
+    }
+}
+
+// angle は音源の方向と耳の向きの差（度）
+fn spherical_head_hrir(angle: f32, sample_rate: f32) -> Hrir {
+    let incidence = angle.rem_euclid(360.0);
+    let incidence = incidence.min(360.0 - incidence).to_radians(); // 0..π
+    
+    // Woodworth: 耳の正面からの音を 0 として、頭を回り込むほど遅れる
+    let head_time = HEAD_RADIUS / SPEED_OF_SOUND;
+    let delay = if incidence < PI * 0.5 {
+        head_time * (1.0 - incidence.cos())
+    } else {
+        head_time * (1.0 + incidence - PI * 0.5)
+    };
+    
+    // 頭の陰: 高域のゲイン alpha は耳の正面で約 +6 dB、150° 付近で最小（0.1）
+    let (alpha_min, theta_min) = (0.1_f32, 150.0_f32.to_radians());
+    let alpha = (1.0 + alpha_min * 0.5) + (1.0 - alpha_min * 0.5) * (incidence / theta_min * PI).cos();
+    let tau = head_time * 0.5;
+    let k = 2.0 * sample_rate;
+    let b0 = (1.0 + alpha * tau * k) / (1.0 + tau * k);
+    let b1 = (1.0 - alpha * tau * k) / (1.0 + tau * k);
+    let a1 = (1.0 - tau * k) / (1.0 + tau * k);
+    
+    let mut taps = [0.0; HRIR_TAPS];
+    taps[0] = b0;
+    taps[1] = b1 - a1 * b0;
+    for index in 2..HRIR_TAPS {
+        taps[index] = -a1 * taps[index - 1];
+    }
+    // 打ち切りで失われる低域を補い、直流のゲインを 1 にする
+    let sum: f32 = taps.iter().sum();
+    for tap in &mut taps {
+        *tap /= sum;
+    }
+    Hrir { delay: delay * sample_rate, taps }
+}
+
+// 小数の遅れを1サンプル分の係数に畳み込んだ畳み込み核
+#[derive(Debug, Clone, Copy)]
+struct Kernel {
+    offset: usize,
+    taps: [f32; HRIR_TAPS + 1],
+}
+
+impl Kernel {
+    fn new(hrir: &Hrir) -> Self {
+        let offset = hrir.delay.floor();
+        let fraction = hrir.delay - offset;
+        let mut taps = [0.0; HRIR_TAPS + 1];
+        for (index, tap) in hrir.taps.iter().enumerate() {
+            taps[index] += tap * (1.0 - fraction);
+            taps[index + 1] += tap * fraction;
+        }
+        Self { offset: offset as usize, taps }
+    }
+}
+
+// ボイスのステレオ出力を、定位の方向を中心に ±VIRTUAL_SPREAD° に置いた2つの仮想スピーカーとして
+// 両耳に畳み込む。スタックの層の広がりや width はこの2つの間の像として残る
+#[derive(Debug, Clone)]
+pub struct BinauralPanner {
+    hrirs: Arc<HrirSet>,
+    kernels: [[Kernel; 2]; 2], // [入力チャンネル][耳]
+    history: [Vec<f32>; 2],
+    mask: usize,
+    position: usize,
+}
+
+impl BinauralPanner {
+    pub fn new(hrirs: Arc<HrirSet>) -> Self {
+        let length = (hrirs.max_delay().ceil() as usize + HRIR_TAPS + 2).next_power_of_two();
+        let mut panner = Self {
+            hrirs,
+            kernels: [[Kernel { offset: 0, taps: [0.0; HRIR_TAPS + 1] }; 2]; 2],
+            history: [vec![0.0; length], vec![0.0; length]],
+            mask: length - 1,
+            position: 0,
+        };
+        panner.set_pan(0.0);
+        panner
+    }
+    
+    // pan（-1.0 = 左, 1.0 = 右）を -90°..90° の方位に対応させる
+    pub fn set_pan(&mut self, pan: f32) {
+        let center = pan.clamp(-1.0, 1.0) * 90.0;
+        for (channel, offset) in [-VIRTUAL_SPREAD, VIRTUAL_SPREAD].into_iter().enumerate() {
+            let hrirs = self.hrirs.at(center + offset);
+            self.kernels[channel] = [Kernel::new(&hrirs[0]), Kernel::new(&hrirs[1])];
+        }
+    }
+    
+    pub fn hrirs(&self) -> &Arc<HrirSet> {
+        &self.hrirs
+    }
+    
+    pub fn reset(&mut self) {
+        for history in &mut self.history {
+            history.fill(0.0);
+        }
+    }
+    
+    pub fn process(&mut self, frame: [f32; 2]) -> [f32; 2] {
+        self.position = (self.position + 1) & self.mask;
+        let mut output = [0.0; 2];
+        for (channel, history) in self.history.iter_mut().enumerate() {
+            history[self.position] = frame[channel];
+            for (ear, kernel) in self.kernels[channel].iter().enumerate() {
+                let start = self.position.wrapping_sub(kernel.offset);
+                let mut sum = 0.0;
+                for (index, tap) in kernel.taps.iter().enumerate() {
+                    sum += tap * history[start.wrapping_sub(index) & self.mask];
+                }
+                output[ear] += sum;
+            }
+        }
+        // 中央の音が両方の仮想スピーカーから届く分を打ち消し、等パワーのパンと同じ中央の音量にする
+        [output[0] * 0.5, output[1] * 0.5]
+    }
+}
@@ -0,0 +1,161 @@
+use crate::effects::{AnalogFloorSettings, Effect, EffectsChain};
+use crate::mixer::PanLaw;
+use crate::patch::PatchState;
+use crate::quality::QualityPolicy;
+use crate::synth::Synthesizer;
+use crate::tuning::Tuning;
+use crate::velocity::{VelocityCurve, VelocityProfiles};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoiceMode {
+    Poly,
+    Mono, // 新しいノートで他のノートをリリースする
+    Paraphonic(usize), // 最大 n ノートが1つのフィルターとアンプのエンベロープを共有する
+}
+
+// 最大同時発音数を超えたときに奪うボイスの選び方。どの方法でもリリース中のボイスを鍵盤を押さえているボイスより先に奪う
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StealPolicy {
+    #[default]
+    Oldest,   // 最も前に発音したボイス
+    Quietest, // 今のエンベロープとゲインが最も小さいボイス
+    SameNote, // 新しいノートと音名が同じ（オクターブ違いの）ボイス。なければ Oldest と同じ
+}
+
+// シンセサイザーの設定（サンプルレート・同時発音数と奪い方・スレッド数・パンの法則・正弦の計算方法・クロスフィード・バイノーラル・ノイズフロア・ベロシティの変換・ボイスの割り当て方以外は構築後に変更されない）
+#[derive(Debug, Clone)]
+pub struct SynthConfig {
+    pub sample_rate: f32,
+    pub polyphony: usize,
+    pub steal_policy: StealPolicy,
+    pub tuning: Tuning,
+    pub voice_mode: VoiceMode,
+    pub quality_policy: QualityPolicy,
+    pub render_threads: usize, // ボイスのレンダリングに使うスレッド数
+    pub pan_law: PanLaw,
+    pub exact_sine: bool, // 正弦を共有の補間テーブルではなく f32::sin で計算する
+    pub crossfeed: bool, // マスター出力のヘッドホン用クロスフィード
+    pub binaural: bool,  // パンの法則の代わりに HRIR でボイスを定位する
+    pub analog_floor: AnalogFloorSettings, // マスターに足すノイズとハム（パッチには含めない）
+    pub velocity_curve: VelocityCurve,         // すべてのノートオンに掛けるベロシティカーブ
+    pub velocity_profiles: VelocityProfiles,   // 入力機器ごとのベロシティの較正
+}
+
+impl Default for SynthConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44100.0,
+            polyphony: 16,
+            steal_policy: StealPolicy::default(),
+            tuning: Tuning::default(),
+            voice_mode: VoiceMode::Poly,
+            quality_policy: QualityPolicy::default(),
+            render_threads: 1,
+            pan_law: PanLaw::default(),
+            exact_sine: false,
+            crossfeed: false,
+            binaural: false,
+            analog_floor: AnalogFloorSettings::default(),
+            velocity_curve: VelocityCurve::default(),
+            velocity_profiles: VelocityProfiles::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SynthesizerBuilder {
+    config: SynthConfig,
+    patch: PatchState,
+    effects: EffectsChain,
+}
+
+impl SynthesizerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn sample_rate(mut self, sample_rate: f32) -> Self {
+        self.config.sample_rate = sample_rate;
+        self
+    }
+    
+    pub fn polyphony(mut self, polyphony: usize) -> Self {
+        self.config.polyphony = polyphony.max(1);
+        self
+    }
+    
+    pub fn steal_policy(mut self, policy: StealPolicy) -> Self {
+        self.config.steal_policy = policy;
+        self
+    }
+    
+    pub fn patch(mut self, patch: PatchState) -> Self {
+        self.patch = patch;
+        self
+    }
+    
+    pub fn tuning(mut self, tuning: Tuning) -> Self {
+        self.config.tuning = tuning;
+        self
+    }
+    
+    pub fn effect(mut self, effect: impl Effect + 'static) -> Self {
+        self.effects.push(Box::new(effect));
+        self
+    }
+    
+    pub fn voice_mode(mut self, voice_mode: VoiceMode) -> Self {
+        self.config.voice_mode = voice_mode;
+        self
+    }
+    
+    // 負荷が高いときの品質の下げ方
+    pub fn quality_policy(mut self, policy: QualityPolicy) -> Self {
+        self.config.quality_policy = policy;
+        self
+    }
+    
+    pub fn render_threads(mut self, threads: usize) -> Self {
+        self.config.render_threads = threads.max(1);
+        self
+    }
+    
+    pub fn pan_law(mut self, pan_law: PanLaw) -> Self {
+        self.config.pan_law = pan_law;
+        self
+    }
+    
+    pub fn exact_sine(mut self, exact: bool) -> Self {
+        self.config.exact_sine = exact;
+        self
+    }
+    
+    pub fn crossfeed(mut self, enabled: bool) -> Self {
+        self.config.crossfeed = enabled;
+        self
+    }
+    
+    pub fn binaural(mut self, enabled: bool) -> Self {
+        self.config.binaural = enabled;
+        self
+    }
+    
+    pub fn analog_floor(mut self, settings: AnalogFloorSettings) -> Self {
+        self.config.analog_floor = settings;
+        self
+    }
+    
+    pub fn velocity_curve(mut self, curve: VelocityCurve) -> Self {
+        self.config.velocity_curve = curve;
+        self
+    }
+    
+    pub fn velocity_profiles(mut self, profiles: VelocityProfiles) -> Self {
+        self.config.velocity_profiles = profiles;
+        self
+    }
+    
+    pub fn build(self) -> Synthesizer {
+        Synthesizer::from_parts(self.config, self.patch, self.effects)
+    }
+}
@@ -0,0 +1,129 @@
+use crate::params;
+use crate::patch::PatchState;
+use crate::synth::MORPH_PARAMETER;
+
+pub const SUSTAIN_PEDAL_CC: u8 = 64; // 割り当て表を通さずにサステインペダルとして扱う
+pub const ALL_SOUND_OFF_CC: u8 = 120; // 受け取るとパニック（panic）
+pub const ALL_NOTES_OFF_CC: u8 = 123; // 1回でノートオフ、続けて2回でパニック
+
+// MIDI CC 番号からパッチのパラメータ（params の名前）への割り当て
+#[derive(Debug, Clone, PartialEq)]
+pub struct CcMapping {
+    pub controller: u8,
+    pub parameter: String,
+    pub min: f32, // CC 値 0 のときの値
+    pub max: f32, // CC 値 127 のときの値
+}
+
+impl CcMapping {
+    pub fn value(&self, cc_value: u8) -> f32 {
+        self.min + (self.max - self.min) * cc_value.min(127) as f32 / 127.0
+    }
+}
+
+// 範囲を指定しなかったときの既定の範囲
+pub fn default_range(parameter: &str) -> (f32, f32) {
+    match parameter {
+        "env.attack" | "env.decay" => (0.001, 2.0),
+        "env.release" => (0.01, 4.0),
+        "pan" => (-1.0, 1.0),
+        "stack.detune" => (0.0, 50.0),
+        _ => (0.0, 1.0),
+    }
+}
+
+// CC の割り当て表と MIDI ラーン（次に届いた CC を待機中のパラメータに割り当てる）。
+// ラーンの割り当てはオーディオスレッドで行うので、割り当てと表の領域は learn で用意し、置き換えた割り当ては retired に残す
+#[derive(Debug, Clone, Default)]
+pub struct CcMap {
+    mappings: Vec<CcMapping>,
+    learning: Option<CcMapping>, // CC 番号は届いたときに決める
+    retired: Option<CcMapping>,  // ラーンで外した割り当て（解放は次の制御側の変更で行う）
+}
+
+impl CcMap {
+    // 1つの CC には1つのパラメータだけを割り当てる（既存の割り当ては置き換える）
+    pub fn assign(&mut self, controller: u8, parameter: &str, range: Option<(f32, f32)>) -> Result<(), String> {
+        if controller > 127 {
+            return Err(format!("CC number must be 0-127: {}", controller));
+        }
+        if controller == SUSTAIN_PEDAL_CC {
+            return Err(format!("CC {} is reserved for the sustain pedal", controller));
+        }
+        check_parameter(parameter)?;
+        let (min, max) = range.unwrap_or_else(|| default_range(parameter));
+        self.remove(controller);
+        self.insert(CcMapping { controller, parameter: parameter.to_string(), min, max });
+        Ok(())
+    }
+    
+    pub fn learn(&mut self, parameter: &str) -> Result<(), String> {
+        check_parameter(parameter)?;
+        let (min, max) = default_range(parameter);
+        self.retired = None;
+        self.mappings.reserve(1);
+        self.learning = Some(CcMapping { controller: 0, parameter: parameter.to_string(), min, max });
+        Ok(())
+    }
+    
+    pub fn cancel_learn(&mut self) {
+        self.learning = None;
+    }
+    
+    pub fn learning(&self) -> Option<&str> {
+        self.learning.as_ref().map(|mapping| mapping.parameter.as_str())
+    }
+    
+    pub fn remove(&mut self, controller: u8) -> bool {
+        self.retired = None;
+        let before = self.mappings.len();
+        self.mappings.retain(|mapping| mapping.controller != controller);
+        self.mappings.len() != before
+    }
+    
+    pub fn clear(&mut self) {
+        self.mappings.clear();
+        self.learning = None;
+        self.retired = None;
+    }
+    
+    pub fn mappings(&self) -> &[CcMapping] {
+        &self.mappings
+    }
+    
+    // 受け取った CC を処理する。ラーン中ならまずこの CC を割り当てる。
+    // 戻り値は (ラーンで割り当てたか, 動かす割り当て)
+    pub fn handle(&mut self, controller: u8) -> (bool, Option<&CcMapping>) {
+        let learned = match self.learning.take() {
+            Some(mapping) if controller > 127 || controller == SUSTAIN_PEDAL_CC => {
+                self.retired = Some(mapping);
+                false
+            }
+            Some(mut mapping) => {
+                mapping.controller = controller;
+                match self.mappings.iter_mut().find(|mapping| mapping.controller == controller) {
+                    Some(existing) => self.retired = Some(std::mem::replace(existing, mapping)),
+                    None => self.insert(mapping),
+                }
+                true
+            }
+            None => false,
+        };
+        (learned, self.mappings.iter().find(|mapping| mapping.controller == controller))
+    }
+    
+    // CC 番号の順に並べたまま入れる
+    fn insert(&mut self, mapping: CcMapping) {
+        let index = self.mappings.partition_point(|existing| existing.controller < mapping.controller);
+        self.mappings.insert(index, mapping);
+    }
+}
+
+fn check_parameter(parameter: &str) -> Result<(), String> {
+    if parameter == MORPH_PARAMETER {
+        return Ok(());
+    }
+    params::get(&PatchState::default(), parameter)
+        .map(|_| ())
+        .ok_or_else(|| format!("unknown parameter: {}", parameter))
+}
@@ -0,0 +1,961 @@
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use crate::i18n::{fill, text, tr, Text};
+
+// ヘルプでの表示区分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Control,
+    Duration, // 持続時間つきの発音
+}
+
+// REPLのコマンド定義（ヘルプ・使い方・補完はすべてこの表から作る）
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub usage: Text,
+    pub summary: Text,
+    pub category: Category,
+    pub keywords: &'static [&'static str], // 2番目の単語の補完候補
+    pub files: bool,                       // 引数にファイル名をとる
+}
+
+const fn command(name: &'static str, usage: Text, summary: Text) -> CommandSpec {
+    CommandSpec { name, usage, summary, category: Category::Control, keywords: &[], files: false }
+}
+
+const fn timed(name: &'static str, usage: Text, summary: Text) -> CommandSpec {
+    CommandSpec { name, usage, summary, category: Category::Duration, keywords: &[], files: false }
+}
+
+const fn with_keywords(spec: CommandSpec, keywords: &'static [&'static str]) -> CommandSpec {
+    CommandSpec { keywords, ..spec }
+}
+
+const fn with_files(spec: CommandSpec) -> CommandSpec {
+    CommandSpec { files: true, ..spec }
+}
+
+// 引数のないコマンドの使い方
+const fn bare(name: &'static str) -> Text {
+    text(name, name)
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    command(
+        "help",
+        text("help [command]", "help [コマンド]"),
+        text("List commands, or show how to use a command", "コマンド一覧、またはコマンドの使い方を表示"),
+    ),
+    command("c", bare("c"), text("Play middle C", "中央のC音を再生")),
+    command("d", bare("d"), text("Play D", "D音を再生")),
+    command("e", bare("e"), text("Play E", "E音を再生")),
+    command("f", bare("f"), text("Play F", "F音を再生")),
+    command("g", bare("g"), text("Play G", "G音を再生")),
+    command("a", bare("a"), text("Play A", "A音を再生")),
+    command("b", bare("b"), text("Play B", "B音を再生")),
+    command("s", bare("s"), text("Stop all notes", "全ての音を停止")),
+    command("q", bare("q"), text("Quit", "終了")),
+    command("1-9", bare("1-9"), text("Set the blend ratio (1=Additive, 9=FM)", "ブレンド比率変更 (1=Additive, 9=FM)")),
+    with_keywords(
+        command(
+            "env",
+            text(
+                "env [attack|decay|release <time | 1/8> | sustain <0-1> | sync on|off]",
+                "env [attack|decay|release <時間 | 1/8> | sustain <0-1> | sync on|off]",
+            ),
+            text(
+                "Adjust the envelope (times in seconds, or beats with sync on; a note length like 1/8, 1/8. or 1/8t turns sync on)",
+                "エンベロープ調整（時間は秒、sync on では拍。1/8・1/8.・1/8t のような音符の長さで指定すると sync on になる）",
+            ),
+        ),
+        &["attack", "decay", "release", "sustain", "sync", "on", "off"],
+    ),
+    with_keywords(
+        command(
+            "glide",
+            text("glide [<seconds>|off] [last|nearest]", "glide [<秒>|off] [last|nearest]"),
+            text(
+                "Polyphonic glide: each new voice slides from the last released voice (or the nearest held one)",
+                "ポリフォニックのグライド：新しいボイスは直前にリリースしたボイス（または押さえているうち最も近いボイス）の音高から滑る",
+            ),
+        ),
+        &["off", "last", "nearest"],
+    ),
+    with_keywords(
+        command(
+            "voices",
+            text(
+                "voices [poly|mono|para [<notes>] | limit <voices> | steal oldest|quietest|same]",
+                "voices [poly|mono|para [<ノート数>] | limit <ボイス数> | steal oldest|quietest|same]",
+            ),
+            text(
+                "Voice mode: paraphonic notes (4 by default) share one filter and amp envelope, retriggered only by the first note. limit and steal set the maximum polyphony and which voice a new note takes beyond it",
+                "ボイスの割り当て方：パラフォニックでは最大 n ノート（既定は 4）が1つのフィルターとアンプのエンベロープを共有し、最初のノートでだけ掛け直す。limit と steal で最大同時発音数と、超えたときに奪うボイスを設定する",
+            ),
+        ),
+        &["poly", "mono", "para", "limit", "steal"],
+    ),
+    command("filter", bare("filter"), text("Adjust the filter", "フィルター調整")),
+    command("p", bare("p"), text("Show voice state", "ボイスの状態を表示")),
+    command(
+        "bright",
+        bare("bright <0-1>"),
+        text("Set brightness (0.5=neutral, e.g. 'bright 0.8')", "ブライトネス調整 (0.5=標準, 例: 'bright 0.8')"),
+    ),
+    command(
+        "stack",
+        text("stack <1-7> [detune cents] [spread 0-1]", "stack <1-7> [セント] [広がり]"),
+        text("Additive stack (e.g. 'stack 5 15 0.8', 1=off)", "加算合成スタック (例: 'stack 5 15 0.8', 1=無効)"),
+    ),
+    command("pan", bare("pan <-1..1>"), text("Voice pan position (-1 = left, 1 = right)", "ボイスの定位（-1 = 左, 1 = 右）")),
+    command(
+        "depth",
+        bare("depth <0-1>"),
+        text("Front/rear position on surround outputs (0 = front, 1 = rear)", "サラウンド出力での前後の位置（0 = 前, 1 = 後ろ）"),
+    ),
+    with_keywords(
+        command(
+            "surround",
+            bare("surround [on|off]"),
+            text(
+                "Use the rear channels of quad/5.1 devices (off: stereo on every device)",
+                "クアッド・5.1 機器の後方チャンネルを使う（off で常にステレオ）",
+            ),
+        ),
+        &["on", "off"],
+    ),
+    with_keywords(
+        command(
+            "crossfeed",
+            bare("crossfeed [on|off]"),
+            text(
+                "Headphone crossfeed on the master output (delayed, filtered bleed between channels)",
+                "マスター出力のヘッドホン用クロスフィード（反対側のチャンネルを遅らせてローパスを掛けて混ぜる）",
+            ),
+        ),
+        &["on", "off"],
+    ),
+    with_keywords(
+        command(
+            "analog",
+            text(
+                "analog [noise [dB]|off | hum 50|60 [dB] | hum off | off]",
+                "analog [noise [dB]|off | hum 50|60 [dB] | hum off | off]",
+            ),
+            text(
+                "Vintage noise floor and mains hum on the master output (off by default, not saved in patches)",
+                "マスター出力にビンテージ機器風のノイズフロアと電源ハムを足す（既定は無効、パッチには保存しない）",
+            ),
+        ),
+        &["noise", "hum", "off", "50", "60"],
+    ),
+    with_keywords(
+        command(
+            "binaural",
+            bare("binaural [on|off]"),
+            text(
+                "Binaural (HRTF) voice panning for headphones instead of the pan law",
+                "パンの法則の代わりに HRTF でボイスを定位する（ヘッドホン向け）",
+            ),
+        ),
+        &["on", "off"],
+    ),
+    with_keywords(
+        command(
+            "panlaw",
+            bare("panlaw -3|-4.5|-6"),
+            text(
+                "Pan law for voice panning and stack spread (level at center)",
+                "ボイスの定位とスタックの広がりに使うパンの法則（中央での減衰量）",
+            ),
+        ),
+        &["-3", "-4.5", "-6"],
+    ),
+    with_keywords(
+        command(
+            "mono",
+            bare("mono [on|off]"),
+            text(
+                "Mono fold-down check of the master output (no argument: show the level change in mono)",
+                "マスター出力のモノラル互換チェック（引数なしでモノラル時の音量変化を表示）",
+            ),
+        ),
+        &["on", "off"],
+    ),
+    with_keywords(
+        command(
+            "pedal",
+            bare("pedal [on|off]"),
+            text(
+                "Sustain pedal (CC64): released notes keep sounding until the pedal is lifted",
+                "サステインペダル（CC64）: 離した鍵盤もペダルを離すまで鳴り続ける",
+            ),
+        ),
+        &["on", "off"],
+    ),
+    with_keywords(
+        command(
+            "velocity",
+            text(
+                "velocity [curve <exponent> | profile <name> <low> <high> [exponent] | profile <name> remove | device <profile>|off <device>]",
+                "velocity [curve <指数> | profile <名前> <最小> <最大> [指数] | profile <名前> remove | device <プロファイル>|off <機器名>]",
+            ),
+            text(
+                "Velocity curve for all notes, and per-device calibration profiles applied before it",
+                "全ノートに掛けるベロシティカーブと、その前に掛ける入力機器ごとの較正プロファイル",
+            ),
+        ),
+        &["curve", "profile", "remove", "device", "off"],
+    ),
+    with_keywords(
+        command(
+            "hold",
+            bare("hold [on|off]"),
+            text(
+                "Latch the notes sounding now until hold is released (toggles without an argument)",
+                "今鳴っているノートを hold を解除するまで鳴らし続ける（引数なしで切り替え）",
+            ),
+        ),
+        &["on", "off"],
+    ),
+    command(
+        "panic",
+        bare("panic"),
+        text(
+            "Silence everything now: stop the sequencer, cut all voices, clear effect tails and restart a failed audio stream",
+            "すべてを即座に止める：シーケンサーを止め、全ボイスを消し、エフェクトの残響を消し、止まったオーディオ出力を開き直す",
+        ),
+    ),
+    with_keywords(
+        command(
+            "audio",
+            bare("audio [rate <Hz|default>] [buffer <frames|default>]"),
+            text(
+                "Show the output status or request the sample rate and buffer size (reopens the stream; the nearest supported value is used)",
+                "出力の状態の表示と、サンプルレートとバッファの長さの要求（ストリームを開き直す。対応していなければ最も近い値を使う）",
+            ),
+        ),
+        &["rate", "buffer", "default"],
+    ),
+    command(
+        "host",
+        bare("host [<name>|default]"),
+        text(
+            "List the audio hosts or switch to one (e.g. jack) and reopen the stream",
+            "オーディオのホストの一覧と切り替え（jack など。ストリームを開き直す）",
+        ),
+    ),
+    command("devices", bare("devices"), text("List the audio output devices", "オーディオの出力機器を一覧表示")),
+    with_keywords(
+        command(
+            "device",
+            bare("device [<n>|<name>|default]"),
+            text(
+                "Show or switch the audio output device (number from devices, or part of its name)",
+                "オーディオの出力機器の表示と切り替え（devices の番号、または名前の一部）",
+            ),
+        ),
+        &["default"],
+    ),
+    with_keywords(
+        command(
+            "automate",
+            text(
+                "automate [<param> [<from> ->] <to> over <length> [beats|s] [lin|exp] | clear]",
+                "automate [<パラメータ> [<開始> ->] <終了> over <長さ> [beats|s] [lin|exp] | clear]",
+            ),
+            text(
+                "Ramp a parameter sample by sample (e.g. 'automate cutoff 0.2 -> 0.9 over 4 beats')",
+                "パラメータを1サンプルごとに変化させる (例: 'automate cutoff 0.2 -> 0.9 over 4 beats')",
+            ),
+        ),
+        &["over", "beats", "lin", "exp", "clear"],
+    ),
+    with_keywords(
+        command(
+            "cc",
+            text(
+                "cc [<0-127> <param> [min max] | learn <param> | cancel | remove <0-127> | clear | send <0-127> <0-127>]",
+                "cc [<0-127> <パラメータ> [最小 最大] | learn <パラメータ> | cancel | remove <0-127> | clear | send <0-127> <0-127>]",
+            ),
+            text(
+                "MIDI CC mapping (learn: bind the next incoming CC, e.g. 'cc learn cutoff')",
+                "MIDI CC の割り当て（learn で次に届いた CC を割り当てる, 例: 'cc learn cutoff'）",
+            ),
+        ),
+        &["learn", "cancel", "remove", "clear", "send"],
+    ),
+    command(
+        "width",
+        bare("width <0-1>"),
+        text(
+            "Stereo width of a single voice (decorrelated partial phases, detuned FM operators)",
+            "ボイス単体のステレオ幅（倍音の位相を左右でずらし、FMは左右でデチューン）",
+        ),
+    ),
+    with_keywords(
+        command(
+            "spectral",
+            text(
+                "spectral tilt <dB/oct> | low|high <harmonic> <dB> | formant <semitones> | reset",
+                "spectral tilt <dB/oct> | low|high <倍音> <dB> | formant <半音> | reset",
+            ),
+            text("Spectral filter of the additive engine", "加算合成エンジンのスペクトルフィルター"),
+        ),
+        &["tilt", "low", "high", "formant", "reset"],
+    ),
+    with_keywords(
+        command(
+            "antialias",
+            bare("antialias on|off"),
+            text(
+                "Anti-aliasing for high notes (attenuates harmonics near Nyquist)",
+                "高音でのエイリアシング防止（ナイキスト付近の倍音を減衰）",
+            ),
+        ),
+        &["on", "off"],
+    ),
+    command(
+        "bp",
+        text(
+            "bp <1-4> add <time> <level> [curve] | loop <start> <end>|off | sync on|off | clear",
+            "bp <1-4> add <時間> <レベル> [カーブ] | loop <開始> <終了>|off | sync on|off | clear",
+        ),
+        text("Edit a breakpoint envelope", "ブレークポイントエンベロープの編集"),
+    ),
+    with_keywords(
+        command(
+            "lfo",
+            text(
+                "lfo [rate <Hz> | shape sine|triangle|square|up|down | fade <seconds | 1/4> | fadesync on|off | oneshot on|off | sync on|off]",
+                "lfo [rate <Hz> | shape sine|triangle|square|up|down | fade <秒 | 1/4> | fadesync on|off | oneshot on|off | sync on|off]",
+            ),
+            text(
+                "Per-voice LFO (fade: time to reach full depth, fadesync: fade in beats, oneshot: one cycle as a mod envelope, sync: rate in cycles per beat)",
+                "ボイスごとの LFO（fade: 深さが最大になるまでの時間, fadesync: fade を拍で指定, oneshot: 1周期だけ動くモジュレーションエンベロープ, sync: rate を1拍あたりの周期数にする）",
+            ),
+        ),
+        &["rate", "shape", "fade", "fadesync", "oneshot", "sync", "sine", "triangle", "square", "up", "down", "on", "off"],
+    ),
+    with_keywords(
+        command(
+            "route",
+            text(
+                "route <velocity|key|bp1-4|bend|mod|at|lfo|rand|relvel|duck> <pitch|cutoff|blend|brightness|amp|pan|depth|bloom|tilt> <amount> [keys <lo>-<hi>] [vel <lo>-<hi>] | route combine <destination> <sum|mul|max> | route clear | route seed <n>",
+                "route <ソース> <送り先> <量> [keys <下>-<上>] [vel <下>-<上>] | route combine <送り先> <sum|mul|max> | route clear | route seed <n>",
+            ),
+            text("Modulation routing (e.g. 'route bp1 cutoff 0.5')", "モジュレーション (例: 'route bp1 cutoff 0.5')"),
+        ),
+        &["velocity", "key", "bp1", "bp2", "bp3", "bp4", "bend", "mod", "at", "lfo", "rand", "relvel", "duck", "clear", "seed", "keys", "vel", "combine", "sum", "mul", "max"],
+    ),
+    command(
+        "tempo",
+        bare("tempo <BPM>"),
+        text("Set the tempo (for tempo-synced envelopes)", "テンポ設定（テンポ同期エンベロープ用）"),
+    ),
+    with_keywords(
+        command(
+            "clock",
+            bare("clock [internal|midi|link | jitter <ms>]"),
+            text(
+                "Tempo source: the internal tempo, incoming MIDI clock (24 PPQN, also follows Start/Stop/Continue) or Link; jitter delays timestamped events",
+                "テンポの取得元: 内部のテンポ、受信した MIDI クロック（24 PPQN、Start/Stop/Continue にも従う）、Link、プラグインのホストのトランスポート。jitter は時刻指定のイベントを遅らせる",
+            ),
+        ),
+        &["internal", "midi", "link", "jitter"],
+    ),
+    with_keywords(
+        command(
+            "osc",
+            bare("osc [on [port] | off | send <host:port> | unsend <host:port>]"),
+            text(
+                "Control parameters over OSC (/param/<name>, /tempo) and send every change back to control surfaces",
+                "OSC でパラメータを操作し（/param/<名前>, /tempo）、値の変化をコントロールサーフェスへ送り返す",
+            ),
+        ),
+        &["on", "off", "send", "unsend"],
+    ),
+    with_keywords(
+        command(
+            "link",
+            bare("link [on|off]"),
+            text(
+                "Receive only the tempo and start/stop of an Ableton Link session on the network (no beat phase sync)",
+                "ネットワーク上の Ableton Link セッションのテンポと開始・停止だけを受け取る（拍の位相は合わせない）",
+            ),
+        ),
+        &["on", "off"],
+    ),
+    with_files(with_keywords(
+        command(
+            "midi",
+            bare("midi [in <device>|in off | jitter <ms>|off]"),
+            text(
+                "Play from a raw MIDI device (e.g. /dev/snd/midiC1D0); jitter places live input at sample offsets for steadier timing; shows the current input with no argument",
+                "生の MIDI 機器（/dev/snd/midiC1D0 など）からの入力で演奏する。jitter は届いた時刻に合わせてサンプル位置に置き、タイミングを揃える。引数なしで現在の入力を表示",
+            ),
+        ),
+        &["in", "off", "jitter"],
+    )),
+    with_keywords(
+        command(
+            "midimon",
+            bare("midimon [on|off]"),
+            text(
+                "Print incoming MIDI messages with a timestamp and channel while still playing them",
+                "受信した MIDI メッセージを時刻とチャンネル付きで表示する（演奏はそのまま続く）",
+            ),
+        ),
+        &["on", "off"],
+    ),
+    with_files(with_keywords(
+        command(
+            "tuning",
+            text(
+                "tuning edo <n> | scl <file> | offset <note> <cents> | offsets clear | ref <Hz>",
+                "tuning edo <n> | scl <ファイル> | offset <ノート> <セント> | offsets clear | ref <Hz>",
+            ),
+            text("Tuning (e.g. 'tuning edo 19')", "音律 (例: 'tuning edo 19')"),
+        ),
+        &["edo", "scl", "offset", "offsets", "ref"],
+    )),
+    with_keywords(
+        command(
+            "seq",
+            text(
+                "seq add <steps> <division> | notes <track> <note|-> ... | play|stop|clear | mute <track> | prob|ratchet|every <track> <step> <value>",
+                "seq add <ステップ数> <分割> | notes <トラック> <ノート|-> ... | play|stop|clear | mute <トラック> | prob|ratchet|every <トラック> <ステップ> <値>",
+            ),
+            text(
+                "Step sequencer (e.g. 'seq add 16 4', 'seq every 1 5 4 1')",
+                "ステップシーケンサー (例: 'seq add 16 4', 'seq every 1 5 4 1')",
+            ),
+        ),
+        &["add", "notes", "play", "stop", "clear", "mute", "prob", "ratchet", "every"],
+    ),
+    with_keywords(
+        command(
+            "scene",
+            text("scene store <1-8> | scene <1-8> [fade seconds]", "scene store <1-8> | scene <1-8> [秒]"),
+            text(
+                "Store and recall scenes (crossfades when seconds are given)",
+                "シーンの保存と切り替え（秒数指定でクロスフェード）",
+            ),
+        ),
+        &["store"],
+    ),
+    with_keywords(
+        command(
+            "ab",
+            text("ab [a|b | store a|b | clear]", "ab [a|b | store a|b | clear]"),
+            text(
+                "A/B compare two in-memory patches (toggles without an argument; edits stay with the selected slot)",
+                "メモリ上の2つの音色の A/B 比較（引数なしで切り替え、編集は選んでいるスロットに残る）",
+            ),
+        ),
+        &["a", "b", "store", "clear"],
+    ),
+    with_files(with_keywords(
+        command(
+            "patch",
+            text(
+                "patch save <file> [init|additive|fm] | patch load <file>",
+                "patch save <ファイル> [init|additive|fm] | patch load <ファイル>",
+            ),
+            text(
+                "Save and load the difference from a base preset",
+                "基準プリセットとの差分の保存と読み込み",
+            ),
+        ),
+        &["save", "load"],
+    )),
+    with_files(with_keywords(
+        command(
+            "preset",
+            text(
+                "preset [list [category]] | load|delete <preset> | save <[category/]name> [init|additive|fm] | rename|copy <preset> <[category/]name> | move <preset> <category>|- | bank <dir>",
+                "preset [list [カテゴリ]] | load|delete <プリセット> | save <[カテゴリ/]名前> [init|additive|fm] | rename|copy <プリセット> <[カテゴリ/]名前> | move <プリセット> <カテゴリ>|- | bank <ディレクトリ>",
+            ),
+            text(
+                "Manage the preset bank (patch delta files, categories are subdirectories)",
+                "プリセットのバンクの管理（パッチ差分のファイル、カテゴリはサブディレクトリ）",
+            ),
+        ),
+        &["list", "load", "save", "rename", "copy", "move", "delete", "bank"],
+    )),
+    with_files(with_keywords(
+        command(
+            "program",
+            text(
+                "program [<0-127> preset <preset> | <0-127> file <file.patch> | <0-127> off | clear]",
+                "program [<0-127> preset <プリセット> | <0-127> file <ファイル.patch> | <0-127> off | clear]",
+            ),
+            text(
+                "Map incoming MIDI Program Change numbers to presets or patch files (saved in ~/.synthesizer_programs)",
+                "受信した MIDI プログラムチェンジの番号にプリセットやパッチファイルを割り当てる（~/.synthesizer_programs に保存）",
+            ),
+        ),
+        &["preset", "file", "off", "clear"],
+    )),
+    with_keywords(
+        command(
+            "morph",
+            text("morph [<preset A> <preset B> [0-1] | <0-1> | off]", "morph [<プリセット A> <プリセット B> [0-1] | <0-1> | off]"),
+            text(
+                "Morph every continuous parameter between two presets (map a CC to `morph` to move it live)",
+                "2つのプリセットの間ですべての連続値のパラメータを補間する（`cc` で `morph` に割り当てると演奏中に動かせる）",
+            ),
+        ),
+        &["off"],
+    ),
+    with_keywords(
+        command(
+            "randomize",
+            text("randomize [all|harmonics|fm|filter|env] [amount 0-1]", "randomize [all|harmonics|fm|filter|env] [強さ 0-1]"),
+            text(
+                "Perturb the current patch for sound design (keeps the pitch, volume and pan; default amount 0.3)",
+                "音作りのために現在の音色をランダムに揺らす（音高・音量・定位は変えない、既定の強さは 0.3）",
+            ),
+        ),
+        &["all", "harmonics", "fm", "filter", "env"],
+    ),
+    with_files(command(
+        "dx7",
+        text("dx7 <file.syx> [voice]", "dx7 <ファイル.syx> [ボイス番号]"),
+        text(
+            "Import a DX7 voice into the FM engine (lists the voices of a 32-voice bank without a number)",
+            "DX7 のボイスを FM エンジンに読み込む（32 ボイスのバンクは番号を省くと一覧を表示）",
+        ),
+    )),
+    with_files(with_keywords(
+        command(
+            "session",
+            text("session save|load <file>", "session save|load <ファイル>"),
+            text(
+                "Save and load the session (patch, sequencer pattern, tempo, velocity curve, metronome)",
+                "セッション（音色、シーケンサーのパターン、テンポ、ベロシティカーブ、メトロノーム）の保存と読み込み",
+            ),
+        ),
+        &["save", "load"],
+    )),
+    with_keywords(
+        command(
+            "autosave",
+            bare("autosave [on|off]"),
+            text(
+                "Periodically save the session so it can be recovered after a crash",
+                "落ちても復元できるように、セッションを定期的に自動保存する",
+            ),
+        ),
+        &["on", "off"],
+    ),
+    command(
+        "next",
+        bare("next"),
+        text("Load the next preset in the bank", "バンクの次のプリセットを読み込む"),
+    ),
+    command(
+        "prev",
+        bare("prev"),
+        text("Load the previous preset in the bank", "バンクの前のプリセットを読み込む"),
+    ),
+    with_keywords(
+        command(
+            "metro",
+            text("metro on|off | metro bar <beats>", "metro on|off | metro bar <拍数>"),
+            text("Metronome and time signature", "メトロノームと拍子"),
+        ),
+        &["on", "off", "bar"],
+    ),
+    with_keywords(
+        command(
+            "duck",
+            text(
+                "duck [track <n> | notes <note>... | depth <0-1> | attack <ms> | release <beats> | off]",
+                "duck [track <n> | notes <ノート>... | depth <0-1> | attack <ミリ秒> | release <拍> | off]",
+            ),
+            text(
+                "Sidechain ducking triggered by a sequencer track or notes",
+                "シーケンサーのトラックやノートで掛かるサイドチェインのダッキング",
+            ),
+        ),
+        &["track", "notes", "depth", "attack", "release", "off"],
+    ),
+    with_keywords(
+        command(
+            "layer",
+            text(
+                "layer [additive|fm [ch <1-16|all>] [keys <lo>-<hi>] [vel <lo>-<hi>] | reset]",
+                "layer [additive|fm [ch <1-16|all>] [keys <下>-<上>] [vel <下>-<上>] | reset]",
+            ),
+            text(
+                "MIDI channel, key and velocity range each engine layer responds to (splits and layering)",
+                "エンジンのレイヤーごとに受け付ける MIDI チャンネル・鍵盤・ベロシティの範囲（スプリットと重ね）",
+            ),
+        ),
+        &["additive", "fm", "ch", "all", "keys", "vel", "reset"],
+    ),
+    command(
+        "beat",
+        text("beat [bars]", "beat [小節数]"),
+        text("Beat indicator (compensated for output latency)", "拍表示（出力レイテンシを補正）"),
+    ),
+    command(
+        "latency",
+        text("latency [ms]", "latency [ミリ秒]"),
+        text("Show the output latency and set an extra offset", "出力レイテンシの表示と追加補正"),
+    ),
+    with_keywords(
+        command(
+            "taps",
+            bare("taps [on|off]"),
+            text("Toggle per-engine analysis taps and show their levels", "エンジンごとの解析タップの切り替えとレベル表示"),
+        ),
+        &["on", "off"],
+    ),
+    with_files(command(
+        "bounce",
+        text(
+            "bounce <file.wav|.flac|.ogg> <seconds> [16|24|0-10] [--stems] [--normalize <LUFS>]",
+            "bounce <ファイル.wav|.flac|.ogg> <秒> [16|24|0-10] [--stems] [--normalize <LUFS>]",
+        ),
+        text(
+            "Render the sequencer offline and report its loudness (FLAC bit depth defaults to 24, OGG quality 0-10 to 5; --stems also writes per-engine stems; --normalize sets the integrated loudness)",
+            "シーケンサーをオフライン書き出しし、ラウドネスを表示（FLAC のビット深度は既定 24、OGG の品質 0〜10 は既定 5、--stems でエンジン別のステムも、--normalize で統合ラウドネスを合わせる）",
+        ),
+    )),
+    with_files(with_keywords(
+        command(
+            "record",
+            text("record [start <file.wav|.flac|.ogg> [16|24|0-10] | stop]", "record [start <ファイル.wav|.flac|.ogg> [16|24|0-10] | stop]"),
+            text(
+                "Record the audio output to a WAV, FLAC or Ogg Vorbis file while playing",
+                "演奏中のオーディオ出力を WAV / FLAC / Ogg Vorbis ファイルに録音する",
+            ),
+        ),
+        &["start", "stop"],
+    )),
+    with_files(command(
+        "render",
+        text(
+            "render <file.wav|.flac|.ogg> <seconds> [16|24|0-10] [--normalize <LUFS>]",
+            "render <ファイル.wav|.flac|.ogg> <秒> [16|24|0-10] [--normalize <LUFS>]",
+        ),
+        text(
+            "Render what is playing now offline to a WAV, FLAC or Ogg Vorbis file (sounding notes are held, the sequencer plays if running)",
+            "今の演奏をオフラインで WAV / FLAC / Ogg Vorbis に書き出す（鳴っているノートは押さえたまま、再生中のシーケンサーも含む）",
+        ),
+    )),
+    with_keywords(
+        command(
+            "stats",
+            bare("stats [reset]"),
+            text(
+                "Show audio callback timing against the buffer deadline, deadline misses and underruns",
+                "オーディオのコールバックの処理時間とバッファの締め切りの比較、締め切りの超過とアンダーランの回数を表示",
+            ),
+        ),
+        &["reset"],
+    ),
+    with_keywords(
+        command(
+            "quality",
+            bare("quality [auto|off]"),
+            text(
+                "Show DSP load and quality level, toggle load-adaptive quality",
+                "DSP負荷と品質の段を表示、負荷に応じた自動調整の切り替え",
+            ),
+        ),
+        &["auto", "off"],
+    ),
+    command(
+        "threads",
+        bare("threads <n>"),
+        text(
+            "Number of threads rendering voices (1=audio thread only)",
+            "ボイスのレンダリングに使うスレッド数 (1=オーディオスレッドのみ)",
+        ),
+    ),
+    with_files(with_keywords(
+        command(
+            "test",
+            text(
+                "test sweep [start Hz] [end Hz] [seconds] | white | pink | impulse [interval] [--filter] [--wav <file>] | test off",
+                "test sweep [開始Hz] [終了Hz] [秒] | white | pink | impulse [間隔秒] [--filter] [--wav <ファイル>] | test off",
+            ),
+            text("Diagnostic test signals", "診断用テスト信号"),
+        ),
+        &["sweep", "white", "pink", "impulse", "off"],
+    )),
+    command("curve", bare("curve"), text("Show the filter's frequency response", "フィルターの周波数特性を表示")),
+    command("drive", bare("drive <0-1>"), text("Filter drive", "フィルターのドライブ")),
+    command(
+        "saturate",
+        bare("saturate <0-1>"),
+        text("Per-voice output saturation before voices are summed", "ボイスごとの出力の飽和（合算前）"),
+    ),
+    with_keywords(
+        command(
+            "gaincomp",
+            bare("gaincomp on|off"),
+            text("Automatic gain compensation for resonance and drive", "レゾナンス・ドライブの音量自動補正"),
+        ),
+        &["on", "off"],
+    ),
+    command(
+        "trim",
+        text("trim <-12..12 dB>", "trim <-12〜12 dB>"),
+        text("Patch output trim for level-matching patches in a bank", "バンク内で音量を揃えるためのパッチの出力トリム"),
+    ),
+    with_keywords(
+        command(
+            "invert",
+            bare("invert [on|off]"),
+            text("Invert the patch's output polarity (toggles without an argument)", "パッチの出力の極性を反転（引数なしで切り替え）"),
+        ),
+        &["on", "off"],
+    ),
+    with_keywords(
+        command(
+            "bend",
+            text("bend <-1..1> [seconds] | bend range [semitones]", "bend <-1〜1> [秒] | bend range [半音]"),
+            text(
+                "Pitch bend (ramps over the given seconds, range: semitones at full bend, default 2)",
+                "ピッチベンド（秒数指定でその時間をかけて変化, range: 振り切ったときの半音数, 既定 2）",
+            ),
+        ),
+        &["range"],
+    ),
+    command(
+        "mod",
+        text("mod <0..1> [seconds]", "mod <0〜1> [秒]"),
+        text("Mod wheel (ramps over the given seconds)", "モジュレーションホイール（秒数指定でその時間をかけて変化）"),
+    ),
+    command(
+        "at",
+        text("at <0..1> [seconds]", "at <0〜1> [秒]"),
+        text("Aftertouch / channel pressure (ramps over the given seconds)", "アフタータッチ（秒数指定でその時間をかけて変化）"),
+    ),
+    with_keywords(
+        command(
+            "bloom",
+            bare("bloom [mod|at|off]"),
+            text(
+                "Harmonic bloom: the mod wheel or aftertouch opens the partials from the fundamental upwards",
+                "ハーモニック・ブルーム: モジュレーションホイールかアフタータッチで基音から順に倍音を開く",
+            ),
+        ),
+        &["mod", "at", "off"],
+    ),
+    with_keywords(
+        command(
+            "status",
+            bare("status [on|off]"),
+            text(
+                "Status line with meters, position and voice count (updates while waiting for input)",
+                "メーター・再生位置・ボイス数のステータス行（入力待ちの間も更新）の切り替え",
+            ),
+        ),
+        &["on", "off"],
+    ),
+    with_keywords(
+        command("lang", bare("lang [en|ja]"), text("Show or change the display language", "表示言語の表示と切り替え")),
+        &["en", "ja"],
+    ),
+    with_keywords(
+        command(
+            "phrase",
+            text(
+                "phrase spacing <ms|auto> | direction up|down|updown|random | octaves <1-4> | humanize <velocity 0-1> [timing ms] | reset",
+                "phrase spacing <ミリ秒|auto> | direction up|down|updown|random | octaves <1-4> | humanize <ベロシティ 0-1> [タイミング ミリ秒] | reset",
+            ),
+            text(
+                "How SCALE and CHORD lay out notes: spacing, direction, octaves and humanization",
+                "SCALE・CHORD のノートの並べ方（間隔、方向、オクターブ数、揺らぎ）",
+            ),
+        ),
+        &["spacing", "direction", "octaves", "humanize", "reset"],
+    ),
+    command(
+        "demo",
+        bare("demo"),
+        text(
+            "Play the built-in demo (chords, blend sweeps, filter automation); restores the patch afterwards",
+            "内蔵のデモ曲を再生（和音、ブレンドのスイープ、フィルターのオートメーション）。終了後に音色を戻す",
+        ),
+    ),
+    command("log", bare("log"), text("Show the audio thread's event log", "オーディオスレッドのイベントログを表示")),
+    timed("C", text("C <seconds>", "C <秒数>"), text("Play middle C for a duration (e.g. 'C 2.5')", "中央のC音を指定時間再生 (例: 'C 2.5')")),
+    timed("D", text("D <seconds>", "D <秒数>"), text("Play D for a duration (e.g. 'D 1.8')", "D音を指定時間再生 (例: 'D 1.8')")),
+    timed("E", text("E <seconds>", "E <秒数>"), text("Play E for a duration (e.g. 'E 1.8')", "E音を指定時間再生 (例: 'E 1.8')")),
+    timed("F", text("F <seconds>", "F <秒数>"), text("Play F for a duration (e.g. 'F 0.3')", "F音を指定時間再生 (例: 'F 0.3')")),
+    timed("G", text("G <seconds>", "G <秒数>"), text("Play G for a duration (e.g. 'G 0.3')", "G音を指定時間再生 (例: 'G 0.3')")),
+    timed("A", text("A <seconds>", "A <秒数>"), text("Play A for a duration (e.g. 'A 4.2')", "A音を指定時間再生 (例: 'A 4.2')")),
+    timed("B", text("B <seconds>", "B <秒数>"), text("Play B for a duration (e.g. 'B 4.2')", "B音を指定時間再生 (例: 'B 4.2')")),
+    timed("H", text("H <seconds>", "H <秒数>"), text("Play high C for a duration (e.g. 'H 4.2')", "高いC音を指定時間再生 (例: 'H 4.2')")),
+    timed(
+        "CHORD",
+        text("CHORD <seconds>", "CHORD <秒数>"),
+        text(
+            "Play a C-E-G chord for a duration, strummed by 'phrase spacing' (e.g. 'CHORD 5.0')",
+            "C-E-G和音を指定時間再生、'phrase spacing' でストラム (例: 'CHORD 5.0')",
+        ),
+    ),
+    timed(
+        "SCALE",
+        text("SCALE <seconds> [gap seconds]", "SCALE <秒数> [間隔 秒]"),
+        text(
+            "Play the C-D-E-F-G-A-B-C scale one note after another over a duration, with an optional gap (e.g. 'SCALE 8.0 0.1')",
+            "C-D-E-F-G-A-B-C音階を指定時間かけて1音ずつ続けて再生、音の間隔も指定可 (例: 'SCALE 8.0 0.1')",
+        ),
+    ),
+];
+
+// 入力行をコマンド名と残りの引数に分けたもの
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Command<'a> {
+    pub name: &'a str,
+    pub args: &'a str,
+}
+
+impl<'a> Command<'a> {
+    pub fn parse(input: &'a str) -> Option<Self> {
+        let input = input.trim();
+        if input.is_empty() {
+            return None;
+        }
+        let (name, args) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+        Some(Self { name, args: args.trim() })
+    }
+    
+    pub fn spec(&self) -> Option<&'static CommandSpec> {
+        find(self.name)
+    }
+}
+
+pub fn find(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.name == name)
+        .or_else(|| name.parse::<u8>().ok().filter(|n| (1..=9).contains(n)).and_then(|_| find("1-9")))
+}
+
+pub fn print_help() {
+    println!("\n🎮 {}:", tr("Interactive controls", "インタラクティブ制御"));
+    for spec in COMMANDS.iter().filter(|spec| spec.category == Category::Control) {
+        println!("  {:<12} {}", spec.name, spec.summary);
+    }
+    println!("\n⏱️  {}:", tr("Custom durations", "カスタム持続時間"));
+    for spec in COMMANDS.iter().filter(|spec| spec.category == Category::Duration) {
+        println!("  {:<12} {}", spec.name, spec.summary);
+    }
+    println!("\n{}", tr(
+        "'help <command>' shows usage, Tab completes, Up/Down browse history",
+        "'help <コマンド>' で使い方を表示、Tab で補完、↑↓ で履歴",
+    ));
+}
+
+pub fn print_command_help(name: &str) {
+    match find(name) {
+        Some(spec) => {
+            println!("📖 {}", spec.usage);
+            println!("   {}", spec.summary);
+        }
+        None => print_unknown(name),
+    }
+}
+
+// 引数が正しくないときの共通メッセージ
+pub fn print_usage(name: &str) {
+    if let Some(spec) = find(name) {
+        println!("❌ {}: {}", tr("Usage", "使い方"), spec.usage);
+    }
+}
+
+pub fn print_unknown(name: &str) {
+    println!("❓ {}", fill(tr(
+        "Unknown command '{}'. Type 'help' for a list of commands",
+        "不明なコマンド '{}' です。'help' でコマンド一覧を表示します",
+    ), &[&name]));
+}
+
+// rustyline 用の補完（1番目の単語はコマンド名、2番目はサブコマンド、以降はファイル名）
+pub struct CommandHelper {
+    files: FilenameCompleter,
+}
+
+impl CommandHelper {
+    pub fn new() -> Self {
+        Self { files: FilenameCompleter::new() }
+    }
+}
+
+fn candidates<'a>(words: impl Iterator<Item = &'a str>, prefix: &str) -> Vec<Pair> {
+    words.filter(|word| word.starts_with(prefix))
+        .map(|word| Pair { display: word.to_string(), replacement: format!("{} ", word) })
+        .collect()
+}
+
+impl Completer for CommandHelper {
+    type Candidate = Pair;
+    
+    fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |index| index + 1);
+        let word = &before[start..];
+        let previous: Vec<&str> = before[..start].split_whitespace().collect();
+        
+        match previous.as_slice() {
+            [] => Ok((start, candidates(COMMANDS.iter().map(|spec| spec.name).filter(|name| *name != "1-9"), word))),
+            [name, rest @ ..] => {
+                let Some(spec) = find(name) else {
+                    return Ok((start, Vec::new()));
+                };
+                if rest.is_empty() && !spec.keywords.is_empty() {
+                    Ok((start, candidates(spec.keywords.iter().copied(), word)))
+                } else if spec.files {
+                    self.files.complete(line, pos, ctx)
+                } else {
+                    Ok((start, Vec::new()))
+                }
+            }
+        }
+    }
+}
+
+impl Hinter for CommandHelper {
+    type Hint = String;
+}
+
+impl Highlighter for CommandHelper {}
+
+impl Validator for CommandHelper {}
+
+impl Helper for CommandHelper {}
+
+// プリセットのバンクの既定のディレクトリ（ホームディレクトリ、なければカレントディレクトリ）
+pub fn preset_dir() -> std::path::PathBuf {
+    let home = std::env::var_os("HOME").map(std::path::PathBuf::from).unwrap_or_default();
+    home.join(".synthesizer_presets")
+}
+
+// セッションの自動保存のファイル（ホームディレクトリ、なければカレントディレクトリ）
+pub fn autosave_path() -> std::path::PathBuf {
+    let home = std::env::var_os("HOME").map(std::path::PathBuf::from).unwrap_or_default();
+    home.join(".synthesizer_autosave")
+}
+
+// プログラムチェンジの対応表のファイル（ホームディレクトリ、なければカレントディレクトリ）
+pub fn program_map_path() -> std::path::PathBuf {
+    let home = std::env::var_os("HOME").map(std::path::PathBuf::from).unwrap_or_default();
+    home.join(".synthesizer_programs")
+}
+
+// 履歴ファイル（ホームディレクトリ、なければカレントディレクトリ）
+pub fn history_path() -> std::path::PathBuf {
+    let home = std::env::var_os("HOME").map(std::path::PathBuf::from).unwrap_or_default();
+    home.join(".synthesizer_history")
+}
@@ -0,0 +1,84 @@
+use std::time::Instant;
+
+use crate::event::Event;
+use crate::midi::MidiMessage;
+use crate::params::ParamName;
+use crate::ring::{self, Consumer, Producer};
+
+// ほかのスレッドからオーディオスレッドへ送る操作。メモリ確保を伴わない値だけを運ぶ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Event(Event),
+    // 演奏中の MIDI 入力。device は Synthesizer::register_device で登録した番号
+    Midi { device: usize, message: MidiMessage, arrived: Instant },
+    Parameter { index: usize, value: f32 }, // index は synth::DIRECT_PARAMETERS の位置
+    // params の名前で設定するパラメータ（DIRECT_PARAMETERS 以外。名前の確認は送る側で済ませる）
+    NamedParameter { name: ParamName, value: f32 },
+    Tempo(f32),
+    // 長さ付きのノート（Synthesizer::note_on_with_duration）
+    NoteFor { note: u8, velocity: f32, seconds: f32 },
+    // delay 秒後にイベントを発生させる（Synthesizer::schedule）
+    Schedule { delay: f32, event: Event },
+    Scene { slot: usize, fade: f32 }, // Synthesizer::recall_scene
+    Panic,
+    // Link のセッションから受け取ったテンポと開始（true）・停止（false）。テンポの取得元が Link のときだけ反映する
+    Link { tempo: Option<f32>, started: Option<bool> },
+}
+
+const COMMAND_CAPACITY: usize = 1024;
+
+impl Command {
+    // 演奏（ノート・クロックなど）ではなく、音色・テンポなど保存する状態を変えうる操作か（Telemetry::edits に数える）
+    pub fn is_edit(&self) -> bool {
+        match self {
+            Command::Event(event) => matches!(event, Event::ProgramChange { .. } | Event::ControlChange { .. }),
+            Command::Midi { message, .. } => {
+                matches!(message, MidiMessage::ProgramChange { .. } | MidiMessage::ControlChange { .. })
+            }
+            Command::Parameter { .. } | Command::NamedParameter { .. } | Command::Tempo(_) | Command::Scene { .. } => true,
+            Command::NoteFor { .. } | Command::Schedule { .. } | Command::Panic | Command::Link { .. } => false,
+        }
+    }
+}
+
+// 送る側。送るスレッド（MIDI 入力、OSC など）ごとに Synthesizer::command_sender で別のキューを作るので、
+// 送る側もロックを取らない
+pub struct CommandSender {
+    producer: Producer<Command>,
+}
+
+// オーディオスレッド側（Synthesizer が持ち、コントロールレートの境界で取り出す）
+pub struct CommandReceiver {
+    consumer: Consumer<Command>,
+}
+
+pub fn command_queue() -> (CommandSender, CommandReceiver) {
+    let (producer, consumer) = ring::channel(COMMAND_CAPACITY);
+    (CommandSender { producer }, CommandReceiver { consumer })
+}
+
+impl CommandSender {
+    // 満杯なら捨てて false を返す（CommandReceiver::dropped に数える）
+    pub fn send(&mut self, command: Command) -> bool {
+        self.producer.push(command).is_ok()
+    }
+}
+
+impl CommandReceiver {
+    pub fn pop(&mut self) -> Option<Command> {
+        self.consumer.pop()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.consumer.is_empty()
+    }
+    
+    pub fn dropped(&self) -> usize {
+        self.consumer.dropped()
+    }
+    
+    // 送る側が捨てられ、残りも読み終えた
+    pub fn is_closed(&self) -> bool {
+        self.consumer.is_abandoned() && self.consumer.is_empty()
+    }
+}
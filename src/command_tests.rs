@@ -0,0 +1,39 @@
+// ほかのスレッドから送る操作のキューのテスト
+use crate::event::Event;
+use crate::synth::Synthesizer;
+
+// ほかのスレッドの操作はロックを取らずにキューで届き、次のコントロールレートの境界で処理される
+#[test]
+fn commands_arrive_through_the_lock_free_queue() {
+    use crate::command::Command;
+    use crate::midi::MidiMessage;
+    use crate::synth::DIRECT_PARAMETERS;
+    use std::time::Instant;
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    let mut sender = synth.command_sender();
+    let device = synth.register_device("keys");
+    assert_eq!(synth.register_device("keys"), device);
+    let cutoff = DIRECT_PARAMETERS.iter().position(|name| *name == "cutoff").unwrap();
+    
+    let thread = std::thread::spawn(move || {
+        assert!(sender.send(Command::Event(Event::NoteOn { note: 60, velocity: 0.8 })));
+        assert!(sender.send(Command::Midi {
+            device,
+            message: MidiMessage::NoteOn { channel: 0, note: 64, velocity: 100 },
+            arrived: Instant::now(),
+        }));
+        assert!(sender.send(Command::Parameter { index: cutoff, value: 0.3 }));
+        assert!(sender.send(Command::Tempo(90.0)));
+    });
+    thread.join().unwrap();
+    assert!(synth.active_notes().is_empty());
+    
+    synth.next_frame();
+    let mut notes = synth.active_notes();
+    notes.sort();
+    assert_eq!(notes, vec![60, 64]);
+    assert_eq!(synth.patch().cutoff, 0.3);
+    assert_eq!(synth.tempo(), 90.0);
+    assert_eq!(synth.dropped_commands(), 0);
+}
@@ -0,0 +1,247 @@
+use std::time::{Duration, Instant};
+
+use crate::command::Command;
+use crate::effects::StereoDelay;
+use crate::event::Event;
+use crate::handle::{SynthHandle, BORROW_TIMED_OUT};
+use crate::patch::PatchState;
+use crate::synth::{Synthesizer, DIRECT_PARAMETERS};
+
+const DEMO_TEMPO: f32 = 110.0;
+const TAIL_SECONDS: f32 = 2.0; // 最後のノートのリリースとディレイの残響
+
+// デモ曲で使う音色（曲の中でこの順に切り替える）
+pub fn factory_patches() -> Vec<(&'static str, PatchState)> {
+    let mut glass = PatchState { blend: 0.0, brightness: 0.7, cutoff: 0.8, stack_density: 3, ..PatchState::default() };
+    glass.envelope.decay = 0.3;
+    glass.envelope.sustain = 0.6;
+    glass.envelope.release = 0.8;
+    
+    let mut bell = PatchState { blend: 0.85, brightness: 0.6, cutoff: 0.9, ..PatchState::default() };
+    bell.envelope.attack = 0.005;
+    bell.envelope.decay = 0.8;
+    bell.envelope.sustain = 0.2;
+    bell.envelope.release = 1.2;
+    
+    let mut pad = PatchState { blend: 0.4, brightness: 0.4, cutoff: 0.35, resonance: 0.4, stack_density: 5, ..PatchState::default() };
+    pad.envelope.attack = 0.4;
+    pad.envelope.sustain = 0.8;
+    pad.envelope.release = 1.5;
+    
+    vec![("glass", glass), ("bell", bell), ("pad", pad)]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Patch(usize),                // factory_patches の番号
+    Chord(&'static [u8], f32),   // ノート、長さ（拍）
+    BlendSweep(f32, f32, f32),   // 開始、終了、長さ（拍）
+    CutoffSweep(f32, f32, f32),
+}
+
+// 拍位置とアクション（拍順に並べる）
+const SCORE: &[(f32, Action)] = &[
+    (0.0, Action::Patch(0)),
+    (0.0, Action::Chord(&[60, 64, 67], 2.0)),
+    (2.0, Action::Chord(&[57, 60, 64], 2.0)),
+    (4.0, Action::Chord(&[53, 57, 60], 2.0)),
+    (6.0, Action::Chord(&[55, 59, 62], 2.0)),
+    (8.0, Action::Patch(1)),
+    (8.0, Action::BlendSweep(0.85, 0.2, 4.0)),
+    (8.0, Action::Chord(&[72], 1.0)),
+    (9.0, Action::Chord(&[76], 1.0)),
+    (10.0, Action::Chord(&[79], 1.0)),
+    (11.0, Action::Chord(&[76], 1.0)),
+    (12.0, Action::Patch(2)),
+    (12.0, Action::Chord(&[48, 60, 64, 67], 8.0)),
+    (12.0, Action::CutoffSweep(0.2, 0.9, 4.0)),
+    (16.0, Action::CutoffSweep(0.9, 0.3, 4.0)),
+];
+
+const SCORE_BEATS: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy)]
+struct Sweep {
+    blend: bool, // false ならカットオフ
+    from: f32,
+    to: f32,
+    start: f32,    // 秒
+    duration: f32, // 秒
+}
+
+// DemoPlayer::update が行う操作。オフライン描画では Synthesizer に直接行い、出力中は SynthHandle から送る
+#[derive(Debug, Clone, Copy)]
+pub enum DemoStep<'a> {
+    Patch(&'a PatchState),
+    Command(Command),
+}
+
+// 経過時間に合わせて譜面のアクションを実行する（リアルタイム再生とオフライン描画で共通）
+pub struct DemoPlayer {
+    patches: Vec<(&'static str, PatchState)>,
+    next: usize,
+    sweeps: Vec<Sweep>,
+}
+
+impl DemoPlayer {
+    pub fn new() -> Self {
+        Self {
+            patches: factory_patches(),
+            next: 0,
+            sweeps: Vec::new(),
+        }
+    }
+    
+    pub fn length_seconds() -> f32 {
+        beats_to_seconds(SCORE_BEATS) + TAIL_SECONDS
+    }
+    
+    pub fn is_finished(&self, seconds: f32) -> bool {
+        seconds >= Self::length_seconds()
+    }
+    
+    // 開始からの経過秒数までのアクションを実行し、切り替えた音色の名前を返す
+    pub fn update(&mut self, seconds: f32, mut apply: impl FnMut(DemoStep<'_>)) -> Option<&'static str> {
+        let mut section = None;
+        while let Some(&(beat, action)) = SCORE.get(self.next) {
+            let start = beats_to_seconds(beat);
+            if start > seconds {
+                break;
+            }
+            self.next += 1;
+            match action {
+                Action::Patch(index) => {
+                    let (name, patch) = &self.patches[index];
+                    apply(DemoStep::Patch(patch));
+                    section = Some(*name);
+                }
+                Action::Chord(notes, beats) => {
+                    for &note in notes {
+                        apply(DemoStep::Command(Command::NoteFor { note, velocity: 0.7, seconds: beats_to_seconds(beats) }));
+                    }
+                }
+                Action::BlendSweep(from, to, beats) | Action::CutoffSweep(from, to, beats) => {
+                    let blend = matches!(action, Action::BlendSweep(..));
+                    self.sweeps.retain(|sweep| sweep.blend != blend);
+                    self.sweeps.push(Sweep { blend, from, to, start, duration: beats_to_seconds(beats) });
+                }
+            }
+        }
+        
+        for sweep in &self.sweeps {
+            let progress = ((seconds - sweep.start) / sweep.duration).clamp(0.0, 1.0);
+            let value = sweep.from + (sweep.to - sweep.from) * progress;
+            let name = if sweep.blend { "blend" } else { "cutoff" };
+            if let Some(index) = DIRECT_PARAMETERS.iter().position(|direct| *direct == name) {
+                apply(DemoStep::Command(Command::Parameter { index, value }));
+            }
+        }
+        self.sweeps.retain(|sweep| seconds < sweep.start + sweep.duration);
+        section
+    }
+}
+
+impl Default for DemoPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn beats_to_seconds(beats: f32) -> f32 {
+    beats * 60.0 / DEMO_TEMPO
+}
+
+// デモ用の構成（マスターに付点8分のテンポ同期ディレイを掛ける）
+pub fn synthesizer(sample_rate: f32) -> Synthesizer {
+    Synthesizer::builder()
+        .sample_rate(sample_rate)
+        .effect(StereoDelay::tempo_synced(sample_rate, 0.75, 0.35, 0.3))
+        .build()
+}
+
+// オーディオ出力中のシンセサイザーでデモを再生する。終わったら元の音色とテンポに戻す。
+// 借りるのは元の音色を写すときだけで、ノートとパラメータはコマンドで、音色は SynthHandle::queue_patch で送る
+pub fn play(synth: &SynthHandle) {
+    let Some((patch, tempo)) = synth.borrow().map(|synth| (synth.patch().clone(), synth.tempo())) else {
+        println!("⚠️  {}", BORROW_TIMED_OUT);
+        return;
+    };
+    synth.send(Command::Event(Event::AllNotesOff));
+    synth.send(Command::Tempo(DEMO_TEMPO));
+    
+    let mut player = DemoPlayer::new();
+    let start = Instant::now();
+    loop {
+        let seconds = start.elapsed().as_secs_f32();
+        if player.is_finished(seconds) {
+            break;
+        }
+        let section = player.update(seconds, |step| match step {
+            DemoStep::Patch(patch) => {
+                synth.queue_patch(patch.clone());
+            }
+            DemoStep::Command(command) => {
+                synth.send(command);
+            }
+        });
+        if let Some(section) = section {
+            println!("🎼 {}", section);
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    
+    synth.queue_patch(patch);
+    synth.send(Command::Tempo(tempo));
+}
+
+// オフライン描画の結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DemoReport {
+    pub seconds: f32,
+    pub peak: f32,
+    pub rms: f32,
+    pub sections: usize,
+}
+
+// デモを先頭から seconds 秒（None なら最後まで）オフラインで描画し、出力を検査する。
+// 起動時のセルフテストと結合テストを兼ねる。
+pub fn self_test(sample_rate: f32, seconds: Option<f32>) -> Result<DemoReport, String> {
+    let mut synth = synthesizer(sample_rate);
+    synth.set_tempo(DEMO_TEMPO);
+    let mut player = DemoPlayer::new();
+    let seconds = seconds.unwrap_or(DemoPlayer::length_seconds()).min(DemoPlayer::length_seconds());
+    let total_frames = (seconds * sample_rate) as usize;
+    
+    let mut block = vec![[0.0f32; 2]; 256];
+    let (mut frames, mut peak, mut sum_squares, mut sections) = (0, 0.0f32, 0.0f64, 0);
+    while frames < total_frames {
+        let section = player.update(frames as f32 / sample_rate, |step| match step {
+            DemoStep::Patch(patch) => synth.set_patch(patch.clone()),
+            DemoStep::Command(command) => synth.apply_command(command),
+        });
+        if section.is_some() {
+            sections += 1;
+        }
+        let length = block.len().min(total_frames - frames);
+        synth.render_block(&mut block[..length]);
+        for frame in &block[..length] {
+            for sample in frame {
+                if !sample.is_finite() {
+                    return Err(format!("non-finite sample at frame {}", frames));
+                }
+                peak = peak.max(sample.abs());
+                sum_squares += (*sample as f64) * (*sample as f64);
+            }
+        }
+        frames += length;
+    }
+    
+    let rms = (sum_squares / (frames.max(1) * 2) as f64).sqrt() as f32;
+    if rms < 1e-4 {
+        return Err(format!("output is silent (RMS {:.6})", rms));
+    }
+    if peak > 4.0 {
+        return Err(format!("output is far too loud (peak {:.2})", peak));
+    }
+    Ok(DemoReport { seconds, peak, rms, sections })
+}
@@ -0,0 +1,10 @@
+// デモ曲を使った結合テスト
+use crate::demo::{self, DemoPlayer};
+
+#[test]
+fn demo_renders_every_section_without_silence_or_blowups() {
+    let report = demo::self_test(22050.0, None).unwrap();
+    assert_eq!(report.sections, demo::factory_patches().len());
+    assert!((report.seconds - DemoPlayer::length_seconds()).abs() < 1e-3);
+    assert!(report.peak < 2.0, "peak {}", report.peak);
+}
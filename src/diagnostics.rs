@@ -0,0 +1,118 @@
+use std::f32::consts::PI;
+use std::fmt;
+
+use crate::random::Rng;
+use crate::synth::LowPassFilter;
+
+// 機器やフィルターの特性を測るためのテスト信号
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestSignal {
+    Sweep { start: f32, end: f32, seconds: f32 }, // 対数スイープ（繰り返し）
+    WhiteNoise,
+    PinkNoise,
+    Impulse { interval: f32 }, // 秒
+}
+
+impl fmt::Display for TestSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestSignal::Sweep { start, end, seconds } => {
+                write!(f, "sine sweep {:.0}-{:.0} Hz over {:.1} s", start, end, seconds)
+            }
+            TestSignal::WhiteNoise => write!(f, "white noise"),
+            TestSignal::PinkNoise => write!(f, "pink noise"),
+            TestSignal::Impulse { interval } => write!(f, "impulse every {:.2} s", interval),
+        }
+    }
+}
+
+// テスト信号の生成器。ボイスを通さず出力へ直接送られる。
+pub struct TestSignalGenerator {
+    signal: TestSignal,
+    level: f32,
+    sample_rate: f32,
+    elapsed: f32, // 秒（スイープ・インパルスの周期内）
+    phase: f32,
+    rng: Rng,
+    pink: [f32; 7],
+    filter: Option<LowPassFilter>, // フィルターの実際の周波数特性を測る場合
+}
+
+impl TestSignalGenerator {
+    pub fn new(signal: TestSignal, sample_rate: f32) -> Self {
+        Self {
+            signal,
+            level: 0.25,
+            sample_rate,
+            elapsed: 0.0,
+            phase: 0.0,
+            rng: Rng::new(1),
+            pink: [0.0; 7],
+            filter: None,
+        }
+    }
+    
+    pub fn signal(&self) -> TestSignal {
+        self.signal
+    }
+    
+    pub fn set_level(&mut self, level: f32) {
+        self.level = level.clamp(0.0, 1.0);
+    }
+    
+    // 信号をボイスと同じローパスフィルターに通す
+    pub fn set_filter(&mut self, filter: Option<LowPassFilter>) {
+        self.filter = filter;
+    }
+    
+    pub fn through_filter(&self) -> bool {
+        self.filter.is_some()
+    }
+    
+    pub fn next_sample(&mut self) -> f32 {
+        let dt = 1.0 / self.sample_rate;
+        let sample = match self.signal {
+            TestSignal::Sweep { start, end, seconds } => {
+                let progress = self.elapsed / seconds.max(dt);
+                let frequency = start * (end / start).powf(progress);
+                let sample = (self.phase * 2.0 * PI).sin();
+                self.phase = (self.phase + frequency * dt).fract();
+                self.elapsed += dt;
+                if self.elapsed >= seconds {
+                    self.elapsed = 0.0;
+                }
+                sample
+            }
+            TestSignal::WhiteNoise => self.rng.bipolar(),
+            TestSignal::PinkNoise => self.next_pink(),
+            TestSignal::Impulse { interval } => {
+                let sample = if self.elapsed == 0.0 { 1.0 } else { 0.0 };
+                self.elapsed += dt;
+                if self.elapsed >= interval {
+                    self.elapsed = 0.0;
+                }
+                sample
+            }
+        };
+        let sample = sample * self.level;
+        match &mut self.filter {
+            Some(filter) => filter.process(sample),
+            None => sample,
+        }
+    }
+    
+    // Paul Kellet の -3dB/oct フィルターで白色雑音をピンクにする
+    fn next_pink(&mut self) -> f32 {
+        let white = self.rng.bipolar();
+        let b = &mut self.pink;
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.153852;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let pink = b.iter().sum::<f32>() + white * 0.5362;
+        b[6] = white * 0.115926;
+        pink * 0.11
+    }
+}
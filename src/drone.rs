@@ -0,0 +1,54 @@
+use crate::engine::HARMONIC_COUNT;
+use crate::random::Rng;
+
+pub const DEFAULT_DRONE_RATE: f32 = 0.1;
+pub const DEFAULT_DRONE_DEPTH: f32 = 0.5;
+pub const MAX_DRONE_RATE: f32 = 10.0;
+
+// ドローンの倍音のゆらぎ。倍音ごとにランダムな目標を選び直し、係数をその目標へなめらかに近づける。
+// 係数は 1 - depth から 1 の間で、加算合成の倍音の振幅に掛ける。
+// rate（Hz）は目標を選び直す平均の回数で、近づく速さも同じ値にするので、遅くするほど動きもゆっくりになる
+#[derive(Debug, Clone)]
+pub struct DroneWalk {
+    rate: f32,
+    depth: f32, // 0.0-1.0
+    gains: [f32; HARMONIC_COUNT],
+    targets: [f32; HARMONIC_COUNT],
+}
+
+impl DroneWalk {
+    pub fn new(rate: f32, depth: f32) -> Self {
+        let mut walk = Self { rate: 0.0, depth: 0.0, gains: [1.0; HARMONIC_COUNT], targets: [1.0; HARMONIC_COUNT] };
+        walk.set(rate, depth);
+        walk
+    }
+    
+    // 範囲が狭まったときは、今の目標も新しい範囲に収める
+    pub fn set(&mut self, rate: f32, depth: f32) {
+        self.rate = rate.clamp(0.0, MAX_DRONE_RATE);
+        self.depth = depth.clamp(0.0, 1.0);
+        let floor = 1.0 - self.depth;
+        for target in &mut self.targets {
+            *target = target.max(floor);
+        }
+    }
+    
+    // 発音ごとにゆらぎのない状態から始める
+    pub fn reset(&mut self) {
+        self.gains = [1.0; HARMONIC_COUNT];
+        self.targets = [1.0; HARMONIC_COUNT];
+    }
+    
+    // seconds 進めて倍音ごとの係数を返す（コントロールレートで呼ぶ）
+    pub fn advance(&mut self, seconds: f32, rng: &mut Rng) -> &[f32; HARMONIC_COUNT] {
+        let retarget = (self.rate * seconds).min(1.0);
+        let follow = 1.0 - (-self.rate * seconds).exp();
+        for (gain, target) in self.gains.iter_mut().zip(&mut self.targets) {
+            if rng.next_f32() < retarget {
+                *target = 1.0 - self.depth * rng.next_f32();
+            }
+            *gain += (*target - *gain) * follow;
+        }
+        &self.gains
+    }
+}
@@ -0,0 +1,37 @@
+// ドローンのテスト
+use crate::synth::Synthesizer;
+
+// ドローン中はノートオフと持続時間を無視して鳴らし続け、倍音のゆらぎで音量が時間とともに変わる。
+// 解除すると離したノートだけをリリースする（パラメータ名で操作するので OSC の /param/drone.* でも同じ）
+#[test]
+fn drone_sustains_notes_with_drifting_harmonics_until_disabled() {
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    synth.set_parameter("drone.on", 1.0).unwrap();
+    synth.set_parameter("drone.rate", 8.0).unwrap();
+    synth.set_parameter("drone.depth", 1.0).unwrap();
+    synth.set_parameter("blend", 0.0).unwrap(); // ゆらぎは加算合成の倍音だけに掛かる
+    synth.note_on(60, 0.8);
+    synth.note_off(60);
+    assert_eq!(synth.drone_notes(), &[60]);
+    
+    // 最初のブロックはエンベロープのアタックとディケイを含むので測らない
+    let mut block = vec![[0.0; 2]; 5512];
+    synth.render_block(&mut block);
+    let mut levels = Vec::new();
+    for _ in 0..8 {
+        synth.render_block(&mut block);
+        levels.push(block.iter().map(|frame| frame[0] * frame[0]).sum::<f32>().sqrt());
+    }
+    let (quietest, loudest) = levels.iter().fold((f32::MAX, 0.0_f32), |(min, max), level| (min.min(*level), max.max(*level)));
+    assert!(quietest > 0.0 && loudest > quietest * 1.2, "{:?}", levels);
+    
+    synth.note_on_with_duration(64, 0.8, 0.01);
+    synth.note_on(67, 0.8);
+    synth.render_block(&mut block);
+    assert_eq!(synth.active_notes(), vec![60, 64, 67]);
+    
+    synth.set_parameter("drone.on", 0.0).unwrap();
+    synth.render_block(&mut block);
+    assert!(synth.drone_notes().is_empty());
+    assert_eq!(synth.active_notes(), vec![67]);
+}
@@ -0,0 +1,141 @@
+use std::f32::consts::PI;
+use std::fmt;
+
+pub const DEFAULT_DUCK_ATTACK_SECONDS: f32 = 0.005;
+pub const DEFAULT_DUCK_RELEASE_BEATS: f32 = 0.5;
+
+// ダッキングのきっかけ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DuckTrigger {
+    Track(usize),   // シーケンサーのトラックが発音したとき（0 始まり）
+    Notes(Vec<u8>),  // 指定したノートが弾かれたとき（演奏・シーケンサー・MIDI のどれでも）
+}
+
+impl fmt::Display for DuckTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DuckTrigger::Track(track) => write!(f, "track {}", track + 1),
+            DuckTrigger::Notes(notes) => {
+                let notes: Vec<String> = notes.iter().map(u8::to_string).collect();
+                write!(f, "notes {}", notes.join(" "))
+            }
+        }
+    }
+}
+
+// サイドチェインのダッキング（ポンピング）のエンベロープ。
+// きっかけで 0 から 1 へ attack 秒で上がり、テンポに追従する release 拍で滑らかに 0 へ戻る。
+// マスターの音量を 1 - depth × 値 倍にし、値はモジュレーションのソース duck としても使える
+#[derive(Debug, Clone)]
+pub struct Ducker {
+    trigger: Option<DuckTrigger>,
+    depth: f32,         // マスターの音量を下げる量 0.0-1.0（0 でマスターには掛けない）
+    attack: f32,        // 秒
+    release_beats: f32, // 拍
+    level: f32,
+    releasing: Option<f32>, // リリースの進み具合 0.0-1.0（None ならアタック中か停止中）
+    pending: Option<u64>,   // これから掛かるきっかけのサンプル位置
+}
+
+impl Ducker {
+    pub fn new() -> Self {
+        Self {
+            trigger: None,
+            depth: 0.0,
+            attack: DEFAULT_DUCK_ATTACK_SECONDS,
+            release_beats: DEFAULT_DUCK_RELEASE_BEATS,
+            level: 0.0,
+            releasing: Some(1.0),
+            pending: None,
+        }
+    }
+    
+    pub fn set_trigger(&mut self, trigger: Option<DuckTrigger>) {
+        self.trigger = trigger;
+        if self.trigger.is_none() {
+            self.reset();
+        }
+    }
+    
+    pub fn trigger(&self) -> Option<&DuckTrigger> {
+        self.trigger.as_ref()
+    }
+    
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+    
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+    
+    pub fn set_attack(&mut self, seconds: f32) {
+        self.attack = seconds.clamp(0.0, 1.0);
+    }
+    
+    pub fn attack(&self) -> f32 {
+        self.attack
+    }
+    
+    pub fn set_release_beats(&mut self, beats: f32) {
+        self.release_beats = beats.clamp(1.0 / 64.0, 16.0);
+    }
+    
+    pub fn release_beats(&self) -> f32 {
+        self.release_beats
+    }
+    
+    pub fn is_note_trigger(&self, note: u8) -> bool {
+        matches!(&self.trigger, Some(DuckTrigger::Notes(notes)) if notes.contains(&note))
+    }
+    
+    // sample の位置からダッキングを始める（まだ生成していない位置なら、そこに来たときに始める）
+    pub fn trigger_at(&mut self, sample: u64) {
+        self.pending = Some(sample);
+    }
+    
+    pub fn reset(&mut self) {
+        self.level = 0.0;
+        self.releasing = Some(1.0);
+        self.pending = None;
+    }
+    
+    // 現在の値 0.0-1.0
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+    
+    pub fn is_active(&self) -> bool {
+        self.level > 0.0 || self.pending.is_some()
+    }
+    
+    // sample の位置の1サンプル分進め、マスターに掛けるゲインを返す
+    pub fn next_gain(&mut self, sample: u64, sample_rate: f32, tempo_bpm: f32) -> f32 {
+        if self.pending.is_some_and(|pending| sample >= pending) {
+            self.pending = None;
+            self.releasing = None;
+        }
+        match self.releasing.as_mut() {
+            None => {
+                self.level += 1.0 / (self.attack * sample_rate).max(1.0);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.releasing = Some(0.0);
+                }
+            }
+            Some(progress) if *progress < 1.0 => {
+                let seconds = self.release_beats * 60.0 / tempo_bpm.max(1.0);
+                *progress = (*progress + 1.0 / (seconds * sample_rate).max(1.0)).min(1.0);
+                self.level = 0.5 * (1.0 + (PI * *progress).cos());
+            }
+            Some(_) => self.level = 0.0,
+        }
+        1.0 - self.depth * self.level
+    }
+}
+
+impl Default for Ducker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
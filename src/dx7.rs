@@ -0,0 +1,172 @@
+use std::error::Error;
+use std::path::Path;
+
+use crate::engine::{Operator, FM_ALGORITHMS, OPERATOR_COUNT};
+use crate::patch::PatchState;
+use crate::synth::Envelope;
+
+// ヤマハの ID と DX7 のボイスデータのフォーマット番号
+const YAMAHA_ID: u8 = 0x43;
+const FORMAT_SINGLE_VOICE: u8 = 0;
+const FORMAT_BANK: u8 = 9;
+const SINGLE_VOICE_SIZE: usize = 155; // 展開形式
+const PACKED_VOICE_SIZE: usize = 128; // バンク内の圧縮形式
+pub const BANK_VOICE_COUNT: usize = 32;
+
+// エンベロープのレートから時間への近似（レート 99 で約 1ms、0 で約 40 秒）
+const EG_SLOWEST_SECONDS: f32 = 40.0;
+const EG_FASTEST_SECONDS: f32 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Dx7Operator {
+    pub rates: [u8; 4],  // 0-99
+    pub levels: [u8; 4], // 0-99
+    pub output_level: u8, // 0-99
+    pub fixed: bool,     // 周波数固定モード
+    pub coarse: u8,      // 0-31（比率モードでは 0 = 0.5）
+    pub fine: u8,        // 0-99
+    pub detune: u8,      // 0-14（7 = 中央）
+}
+
+impl Dx7Operator {
+    pub fn frequency_ratio(&self) -> f32 {
+        if self.fixed {
+            // 固定周波数は A4（440Hz）に対する比率として近似する
+            let hz = 10.0_f32.powf((self.coarse % 4) as f32 + self.fine as f32 / 100.0);
+            return hz / 440.0;
+        }
+        let coarse = if self.coarse == 0 { 0.5 } else { self.coarse as f32 };
+        coarse * (1.0 + self.fine as f32 / 100.0)
+    }
+    
+    // 出力レベルは対数（1 段で約 0.75dB、99 で 1.0）
+    pub fn amplitude(&self) -> f32 {
+        level_to_amplitude(self.output_level)
+    }
+}
+
+// 1つのボイス。operators[0] が OP1（データ上は OP6 から並ぶ）
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Dx7Voice {
+    pub name: String,
+    pub algorithm: u8, // 1-32
+    pub feedback: u8,  // 0-7
+    pub operators: [Dx7Operator; OPERATOR_COUNT],
+}
+
+impl Dx7Voice {
+    fn from_single(data: &[u8]) -> Self {
+        let mut voice = Dx7Voice::default();
+        for (i, chunk) in data[..126].chunks(21).enumerate() {
+            voice.operators[OPERATOR_COUNT - 1 - i] = Dx7Operator {
+                rates: [chunk[0], chunk[1], chunk[2], chunk[3]],
+                levels: [chunk[4], chunk[5], chunk[6], chunk[7]],
+                output_level: chunk[16],
+                fixed: chunk[17] & 1 != 0,
+                coarse: chunk[18] & 0x1F,
+                fine: chunk[19],
+                detune: chunk[20],
+            };
+        }
+        voice.algorithm = (data[134] & 0x1F) + 1;
+        voice.feedback = data[135] & 0x07;
+        voice.name = voice_name(&data[145..155]);
+        voice
+    }
+    
+    fn from_packed(data: &[u8]) -> Self {
+        let mut voice = Dx7Voice::default();
+        for (i, chunk) in data[..102].chunks(17).enumerate() {
+            voice.operators[OPERATOR_COUNT - 1 - i] = Dx7Operator {
+                rates: [chunk[0], chunk[1], chunk[2], chunk[3]],
+                levels: [chunk[4], chunk[5], chunk[6], chunk[7]],
+                output_level: chunk[14],
+                fixed: chunk[15] & 1 != 0,
+                coarse: (chunk[15] >> 1) & 0x1F,
+                fine: chunk[16],
+                detune: (chunk[12] >> 3) & 0x0F,
+            };
+        }
+        voice.algorithm = (data[110] & 0x1F) + 1;
+        voice.feedback = data[111] & 0x07;
+        voice.name = voice_name(&data[118..128]);
+        voice
+    }
+    
+    // base（通常は現在の音色）に FM エンジンの設定を重ねる。
+    // 音量のエンベロープは OP1（どのアルゴリズムでもキャリア）の EG から近似する。
+    // オペレーターごとのエンベロープ、デチューン、キースケーリング、LFO、ピッチ EG は取り込まない
+    pub fn to_patch(&self, base: &PatchState) -> PatchState {
+        let mut patch = base.clone();
+        patch.blend = 1.0;
+        patch.fm_algorithm = self.algorithm as usize;
+        let feedback_operator = FM_ALGORITHMS[self.algorithm as usize - 1].feedback;
+        patch.operators = self.operators.iter().enumerate()
+            .map(|(i, operator)| Operator {
+                frequency_ratio: operator.frequency_ratio(),
+                amplitude: operator.amplitude(),
+                // DX7 のフィードバックも 1 段で 2 倍（7 で 1.0）
+                feedback: if i + 1 == feedback_operator && self.feedback > 0 {
+                    2.0_f32.powi(self.feedback as i32 - 7)
+                } else {
+                    0.0
+                },
+                enabled: operator.output_level > 0,
+            })
+            .collect();
+        
+        let eg = &self.operators[0];
+        patch.envelope = Envelope {
+            attack: rate_to_seconds(eg.rates[0]),
+            decay: rate_to_seconds(eg.rates[1]) + rate_to_seconds(eg.rates[2]),
+            sustain: level_to_amplitude(eg.levels[2]),
+            release: rate_to_seconds(eg.rates[3]),
+            tempo_sync: false,
+        };
+        patch
+    }
+}
+
+fn voice_name(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| (byte & 0x7F) as char).collect::<String>().trim_end().to_string()
+}
+
+fn level_to_amplitude(level: u8) -> f32 {
+    if level == 0 {
+        0.0
+    } else {
+        2.0_f32.powf((level.min(99) as f32 - 99.0) / 8.0)
+    }
+}
+
+fn rate_to_seconds(rate: u8) -> f32 {
+    let position = rate.min(99) as f32 / 99.0;
+    EG_SLOWEST_SECONDS * (EG_FASTEST_SECONDS / EG_SLOWEST_SECONDS).powf(position)
+}
+
+// 1つのボイス（フォーマット 0）または 32 ボイスのバンク（フォーマット 9）のシステムエクスクルーシブを読む。
+// チェックサムが合わないデータは壊れているとみなす
+pub fn parse_sysex(data: &[u8]) -> Result<Vec<Dx7Voice>, String> {
+    let (format, body) = match data {
+        [0xF0, YAMAHA_ID, _, format, _, _, body @ .., checksum, 0xF7] => {
+            let sum = body.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+            if sum.wrapping_add(*checksum) & 0x7F != 0 {
+                return Err("DX7 sysex checksum mismatch".to_string());
+            }
+            (format, body)
+        }
+        _ => return Err("not a Yamaha DX7 sysex message".to_string()),
+    };
+    match (*format, body) {
+        (FORMAT_SINGLE_VOICE, body) if body.len() == SINGLE_VOICE_SIZE => Ok(vec![Dx7Voice::from_single(body)]),
+        (FORMAT_BANK, body) if body.len() == PACKED_VOICE_SIZE * BANK_VOICE_COUNT => {
+            Ok(body.chunks(PACKED_VOICE_SIZE).map(Dx7Voice::from_packed).collect())
+        }
+        _ => Err("unsupported DX7 sysex format".to_string()),
+    }
+}
+
+pub fn load_sysex(path: impl AsRef<Path>) -> Result<Vec<Dx7Voice>, Box<dyn Error>> {
+    let data = std::fs::read(path)?;
+    Ok(parse_sysex(&data)?)
+}
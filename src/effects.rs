@@ -0,0 +1,264 @@
+use crate::random::Rng;
+
+// マスター出力に直列で掛けるステレオエフェクト
+pub trait Effect: Send {
+    fn process(&mut self, frame: [f32; 2]) -> [f32; 2];
+    
+    // 残響などの内部状態をクリアする
+    fn reset(&mut self) {}
+    
+    // テンポに同期するエフェクトはここで時間を合わせ直す
+    fn set_tempo(&mut self, _tempo_bpm: f32) {}
+    
+    // 出力機器のサンプルレートに合わせて時間と係数を計算し直す（残響は消えてよい）
+    fn set_sample_rate(&mut self, _sample_rate: f32) {}
+}
+
+#[derive(Default)]
+pub struct EffectsChain {
+    effects: Vec<Box<dyn Effect>>,
+}
+
+impl EffectsChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn push(&mut self, effect: Box<dyn Effect>) {
+        self.effects.push(effect);
+    }
+    
+    pub fn process(&mut self, frame: [f32; 2]) -> [f32; 2] {
+        self.effects.iter_mut().fold(frame, |frame, effect| effect.process(frame))
+    }
+    
+    pub fn reset(&mut self) {
+        for effect in &mut self.effects {
+            effect.reset();
+        }
+    }
+    
+    pub fn set_tempo(&mut self, tempo_bpm: f32) {
+        for effect in &mut self.effects {
+            effect.set_tempo(tempo_bpm);
+        }
+    }
+    
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        for effect in &mut self.effects {
+            effect.set_sample_rate(sample_rate);
+        }
+    }
+}
+
+// ピンポンディレイ（フィードバックを左右交互に送る）
+pub struct StereoDelay {
+    buffers: [Vec<f32>; 2],
+    length: usize, // 現在のディレイ時間（サンプル、バッファの長さ以下）
+    position: usize,
+    feedback: f32,
+    mix: f32,
+    sample_rate: f32,
+    beats: Option<f32>, // テンポ同期するときのディレイ時間（拍）
+}
+
+pub const MIN_SYNCED_DELAY_TEMPO: f32 = 20.0; // テンポ同期のバッファはこのテンポで足りる長さを確保する
+
+impl StereoDelay {
+    pub fn new(sample_rate: f32, seconds: f32, feedback: f32, mix: f32) -> Self {
+        let length = ((sample_rate * seconds) as usize).max(1);
+        Self {
+            buffers: [vec![0.0; length], vec![0.0; length]],
+            length,
+            position: 0,
+            feedback: feedback.clamp(0.0, 0.95),
+            mix: mix.clamp(0.0, 1.0),
+            sample_rate,
+            beats: None,
+        }
+    }
+    
+    // ディレイ時間を拍で指定する（シンセサイザーのテンポに追従し、初期値は 120 BPM）
+    pub fn tempo_synced(sample_rate: f32, beats: f32, feedback: f32, mix: f32) -> Self {
+        let beats = beats.max(1.0 / 64.0);
+        let mut delay = Self::new(sample_rate, beats * 60.0 / MIN_SYNCED_DELAY_TEMPO, feedback, mix);
+        delay.beats = Some(beats);
+        delay.set_tempo(120.0);
+        delay
+    }
+}
+
+impl Effect for StereoDelay {
+    fn process(&mut self, frame: [f32; 2]) -> [f32; 2] {
+        let delayed = [self.buffers[0][self.position], self.buffers[1][self.position]];
+        self.buffers[0][self.position] = frame[0] + delayed[1] * self.feedback;
+        self.buffers[1][self.position] = frame[1] + delayed[0] * self.feedback;
+        self.position = (self.position + 1) % self.length;
+        [frame[0] + delayed[0] * self.mix, frame[1] + delayed[1] * self.mix]
+    }
+    
+    fn reset(&mut self) {
+        for buffer in &mut self.buffers {
+            buffer.fill(0.0);
+        }
+    }
+    
+    fn set_tempo(&mut self, tempo_bpm: f32) {
+        if let Some(beats) = self.beats {
+            let seconds = beats * 60.0 / tempo_bpm.max(MIN_SYNCED_DELAY_TEMPO);
+            self.length = ((seconds * self.sample_rate) as usize).clamp(1, self.buffers[0].len());
+            self.position %= self.length;
+        }
+    }
+    
+    // バッファとディレイ時間をサンプルレートの比で伸び縮みさせる（秒・拍で見た長さは変わらない）
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        let ratio = sample_rate / self.sample_rate;
+        let capacity = ((self.buffers[0].len() as f32 * ratio) as usize).max(1);
+        self.buffers = [vec![0.0; capacity], vec![0.0; capacity]];
+        self.length = ((self.length as f32 * ratio) as usize).clamp(1, capacity);
+        self.position = 0;
+        self.sample_rate = sample_rate;
+    }
+}
+
+// ヘッドホン用のクロスフィード: 反対側のチャンネルを少し遅らせ、ローパスを掛けて混ぜる。
+// 左右に大きく広げたスタックがスピーカーで聴くときのように自然に聞こえる。
+pub struct Crossfeed {
+    buffers: [Vec<f32>; 2],
+    position: usize,
+    lowpass: [f32; 2],
+    coefficient: f32, // 1極ローパスの係数
+    level: f32,       // 反対側から混ぜる量（倍率）
+}
+
+pub const CROSSFEED_DELAY_SECONDS: f32 = 0.0003; // 両耳の到達時間差
+pub const CROSSFEED_CUTOFF_HZ: f32 = 700.0;
+pub const CROSSFEED_LEVEL_DB: f32 = -4.5;
+
+impl Crossfeed {
+    pub fn new(sample_rate: f32) -> Self {
+        let length = ((sample_rate * CROSSFEED_DELAY_SECONDS) as usize).max(1);
+        Self {
+            buffers: [vec![0.0; length], vec![0.0; length]],
+            position: 0,
+            lowpass: [0.0; 2],
+            coefficient: (-2.0 * std::f32::consts::PI * CROSSFEED_CUTOFF_HZ / sample_rate).exp(),
+            level: 10.0_f32.powf(CROSSFEED_LEVEL_DB / 20.0),
+        }
+    }
+}
+
+impl Effect for Crossfeed {
+    fn process(&mut self, frame: [f32; 2]) -> [f32; 2] {
+        let delayed = [self.buffers[0][self.position], self.buffers[1][self.position]];
+        self.buffers[0][self.position] = frame[0];
+        self.buffers[1][self.position] = frame[1];
+        self.position = (self.position + 1) % self.buffers[0].len();
+        for (state, input) in self.lowpass.iter_mut().zip(delayed) {
+            *state = input + (*state - input) * self.coefficient;
+        }
+        // 低域は左右が足し合わされるので、その分を下げて中央の音量を保つ
+        let gain = 1.0 / (1.0 + self.level);
+        [
+            (frame[0] + self.lowpass[1] * self.level) * gain,
+            (frame[1] + self.lowpass[0] * self.level) * gain,
+        ]
+    }
+    
+    fn reset(&mut self) {
+        for buffer in &mut self.buffers {
+            buffer.fill(0.0);
+        }
+        self.lowpass = [0.0; 2];
+    }
+    
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        *self = Self::new(sample_rate);
+    }
+}
+
+// マスターに足すアナログ機器風のノイズフロアと電源ハム（どちらも None で無効）
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AnalogFloorSettings {
+    pub noise_db: Option<f32>,       // ノイズのレベル（dBFS RMS）
+    pub hum: Option<(f32, f32)>,     // (電源周波数 50/60 Hz, レベル dBFS)
+}
+
+impl AnalogFloorSettings {
+    pub fn is_enabled(&self) -> bool {
+        self.noise_db.is_some() || self.hum.is_some()
+    }
+}
+
+pub const DEFAULT_NOISE_FLOOR_DB: f32 = -78.0;
+pub const DEFAULT_HUM_DB: f32 = -72.0;
+
+// ハムの倍音（整流で偶数次も出る）の相対振幅。合計の RMS が 1 になるように正規化して使う
+const HUM_HARMONICS: [f32; 3] = [1.0, 0.5, 0.25];
+
+pub struct AnalogFloor {
+    settings: AnalogFloorSettings,
+    sample_rate: f32,
+    rng: Rng,
+    noise_gain: f32,
+    hum_gain: f32,
+    hum_phase: f32,
+    hum_increment: f32,
+}
+
+impl AnalogFloor {
+    pub fn new(sample_rate: f32, settings: AnalogFloorSettings) -> Self {
+        let mut floor = Self {
+            settings: AnalogFloorSettings::default(),
+            sample_rate,
+            rng: Rng::from_entropy(),
+            noise_gain: 0.0,
+            hum_gain: 0.0,
+            hum_phase: 0.0,
+            hum_increment: 0.0,
+        };
+        floor.set_settings(settings);
+        floor
+    }
+    
+    pub fn set_settings(&mut self, settings: AnalogFloorSettings) {
+        self.settings = settings;
+        // 一様分布 [-1, 1) の RMS は 1/√3
+        self.noise_gain = settings.noise_db.map_or(0.0, |db| 10.0_f32.powf(db / 20.0) * 3.0_f32.sqrt());
+        let harmonics_rms = (HUM_HARMONICS.iter().map(|a| a * a).sum::<f32>() * 0.5).sqrt();
+        self.hum_gain = settings.hum.map_or(0.0, |(_, db)| 10.0_f32.powf(db / 20.0) / harmonics_rms);
+        self.hum_increment = settings.hum.map_or(0.0, |(frequency, _)| frequency / self.sample_rate);
+    }
+}
+
+impl Effect for AnalogFloor {
+    fn process(&mut self, frame: [f32; 2]) -> [f32; 2] {
+        let mut hum = 0.0;
+        if self.hum_gain > 0.0 {
+            let angle = self.hum_phase * std::f32::consts::TAU;
+            for (index, amplitude) in HUM_HARMONICS.iter().enumerate() {
+                hum += amplitude * (angle * (index + 1) as f32).sin();
+            }
+            self.hum_phase = (self.hum_phase + self.hum_increment).fract();
+        }
+        // ノイズは左右で無相関、ハムは左右で同じ（電源由来なので）
+        let mut output = frame;
+        for sample in &mut output {
+            *sample += hum * self.hum_gain;
+            if self.noise_gain > 0.0 {
+                *sample += self.rng.bipolar() * self.noise_gain;
+            }
+        }
+        output
+    }
+    
+    fn reset(&mut self) {
+        self.hum_phase = 0.0;
+    }
+    
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.set_settings(self.settings);
+    }
+}
@@ -1,3 +1,11 @@
+use std::sync::LazyLock;
+
+#[cfg(feature = "simd")]
+use wide::{f32x8, CmpGe, CmpGt, CmpLt};
+
+use crate::mixer::PanLaw;
+use crate::random::Rng;
+
 // 基本的なオシレーター
 pub trait Oscillator {
     fn next_sample(&mut self) -> f32;
@@ -10,6 +18,7 @@ pub struct SineOscillator {
     amplitude: f32,
     phase: f32,
     sample_rate: f32,
+    exact: bool, // 共有の正弦波テーブルの代わりに f32::sin で計算する
 }
 
 impl SineOscillator {
@@ -19,17 +28,50 @@ impl SineOscillator {
             amplitude: 1.0,
             phase: 0.0,
             sample_rate,
+            exact: false,
         }
     }
-}
-
-impl Oscillator for SineOscillator {
-    fn next_sample(&mut self) -> f32 {
-        let sample = (self.phase * 2.0 * std::f32::consts::PI).sin() * self.amplitude;
+    
+    pub fn set_exact(&mut self, exact: bool) {
+        self.exact = exact;
+    }
+    
+    pub fn amplitude(&self) -> f32 {
+        self.amplitude
+    }
+    
+    // 位相を 0.0-1.0 で設定
+    pub fn set_phase(&mut self, phase: f32) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+    
+    // 現在の位相と、右チャンネル用に offset（周期）ずらした位相の2つの値を返して進める
+    pub fn next_pair(&mut self, offset: f32) -> (f32, f32) {
+        let left = sine(self.phase, self.exact) * self.amplitude;
+        let right = sine(self.phase + offset, self.exact) * self.amplitude;
+        self.advance();
+        (left, right)
+    }
+    
+    // 現在の位相（ラジアン）を返して進める（位相変調で使う）
+    pub fn next_phase(&mut self) -> f32 {
+        let phase = self.phase * 2.0 * std::f32::consts::PI;
+        self.advance();
+        phase
+    }
+    
+    fn advance(&mut self) {
         self.phase += self.frequency / self.sample_rate;
         if self.phase >= 1.0 {
             self.phase -= 1.0;
         }
+    }
+}
+
+impl Oscillator for SineOscillator {
+    fn next_sample(&mut self) -> f32 {
+        let sample = sine(self.phase, self.exact) * self.amplitude;
+        self.advance();
         sample
     }
     
@@ -42,8 +84,50 @@ impl Oscillator for SineOscillator {
     }
 }
 
+// 正弦波テーブルの1周期分の点数（補間用に先頭の値を末尾にもう1つ持つ）
+pub const SINE_TABLE_SIZE: usize = 4096;
+
+// SineOscillator と FM エンジンで共有する正弦波テーブル
+static SINE_TABLE: LazyLock<[f32; SINE_TABLE_SIZE + 1]> = LazyLock::new(|| {
+    std::array::from_fn(|i| (i as f64 / SINE_TABLE_SIZE as f64 * std::f64::consts::TAU).sin() as f32)
+});
+
+// 位相（周期）の正弦を、テーブルの隣り合う2点の線形補間で求める（f32::sin との差は 1e-6 未満）
+#[inline]
+pub fn table_sin(phase: f32) -> f32 {
+    let position = phase.rem_euclid(1.0) * SINE_TABLE_SIZE as f32;
+    let index = (position as usize).min(SINE_TABLE_SIZE - 1);
+    let fraction = position - index as f32;
+    let table = &*SINE_TABLE;
+    table[index] + (table[index + 1] - table[index]) * fraction
+}
+
+// 位相（周期）の正弦。exact なら f32::sin で計算する
+#[inline]
+fn sine(phase: f32, exact: bool) -> f32 {
+    if exact {
+        (phase * std::f32::consts::TAU).sin()
+    } else {
+        table_sin(phase)
+    }
+}
+
+// ラジアンの正弦（位相変調の計算用）
+#[inline]
+fn sine_radians(angle: f32, exact: bool) -> f32 {
+    if exact {
+        angle.sin()
+    } else {
+        table_sin(angle / std::f32::consts::TAU)
+    }
+}
+
+pub const HARMONIC_COUNT: usize = 64;
+pub const OPERATOR_COUNT: usize = 6;
+const WIDTH_FM_DETUNE_CENTS: f32 = 8.0; // width 1.0 での FM の左右のデチューン幅
+
 // Additive Engine
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Harmonic {
     pub frequency_multiplier: f32,
     pub amplitude: f32,
@@ -51,72 +135,507 @@ pub struct Harmonic {
     pub enabled: bool,
 }
 
+// 初期状態の倍音テーブル（基音のみ有効）
+pub fn default_harmonics() -> Vec<Harmonic> {
+    (1..=HARMONIC_COUNT)
+        .map(|i| Harmonic {
+            frequency_multiplier: i as f32,
+            amplitude: if i == 1 { 1.0 } else { 0.0 },
+            phase: 0.0,
+            enabled: i == 1,
+        })
+        .collect()
+}
+
+pub const MAX_STACK_DENSITY: usize = 7;
+
+// 倍音の振幅に直接掛けるスペクトルフィルター（倍音番号の空間で動作する）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectralFilter {
+    pub tilt: f32,                // dB/oct
+    pub low_shelf_harmonic: f32,  // シェルフの境目（倍音番号）
+    pub low_shelf_gain: f32,      // dB
+    pub high_shelf_harmonic: f32,
+    pub high_shelf_gain: f32,     // dB
+    pub formant_shift: f32,       // 半音。倍音の周波数はそのままで振幅の包絡だけを移動する
+}
+
+impl Default for SpectralFilter {
+    fn default() -> Self {
+        Self {
+            tilt: 0.0,
+            low_shelf_harmonic: 4.0,
+            low_shelf_gain: 0.0,
+            high_shelf_harmonic: 16.0,
+            high_shelf_gain: 0.0,
+            formant_shift: 0.0,
+        }
+    }
+}
+
+impl SpectralFilter {
+    // 第n倍音に掛けるゲイン（倍率）
+    pub fn gain(&self, n: f32) -> f32 {
+        let n = n.max(1.0);
+        let low = (n / self.low_shelf_harmonic.max(1.0)).powi(2);
+        let high = (n / self.high_shelf_harmonic.max(1.0)).powi(2);
+        let db = self.tilt * n.log2()
+            + self.low_shelf_gain / (1.0 + low)
+            + self.high_shelf_gain * high / (1.0 + high);
+        10.0_f32.powf(db / 20.0)
+    }
+    
+    pub fn formant_ratio(&self) -> f32 {
+        2.0_f32.powf(self.formant_shift / 12.0)
+    }
+}
+
+// スタックの1層分: 倍音スペクトル全体のデチューンされたコピー。
+// 倍音の発振器は位相・増分・振幅をそれぞれ連続した配列で持つ（simd フィーチャーでは LANES 個ずつまとめて計算する）
+struct StackLayer {
+    phases: [f32; HARMONIC_COUNT],     // 0.0-1.0（周期）
+    increments: [f32; HARMONIC_COUNT], // 1サンプルあたりの位相の進み
+    amplitudes: [f32; HARMONIC_COUNT],
+    detune_ratio: f32,
+    pan: f32, // -1.0 = 左, 1.0 = 右
+    gains: [f32; 2], // パンの法則を適用した左右のゲイン
+}
+
+impl StackLayer {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            phases: [0.0; HARMONIC_COUNT],
+            increments: [440.0 / sample_rate; HARMONIC_COUNT],
+            amplitudes: [1.0; HARMONIC_COUNT],
+            detune_ratio: 1.0,
+            pan: 0.0,
+            gains: [1.0; 2],
+        }
+    }
+    
+    // 先頭の count 個の倍音を1サンプル分合計して進める。offsets があれば右チャンネルは倍音ごとに位相をずらす
+    #[cfg(feature = "simd")]
+    fn next_frame(&mut self, count: usize, offsets: Option<&[f32; HARMONIC_COUNT]>) -> (f32, f32) {
+        self.next_frame_lanes(count, offsets)
+    }
+    
+    #[cfg(not(feature = "simd"))]
+    fn next_frame(&mut self, count: usize, offsets: Option<&[f32; HARMONIC_COUNT]>) -> (f32, f32) {
+        self.next_frame_scalar(count, offsets, 0)
+    }
+    
+    // 倍音を1つずつ計算する。simd フィーチャーが無いときの経路で、ベクトル命令の経路の基準にもなる
+    fn next_frame_scalar(&mut self, count: usize, offsets: Option<&[f32; HARMONIC_COUNT]>, start: usize) -> (f32, f32) {
+        let (mut left, mut right) = (0.0, 0.0);
+        for i in start..count {
+            let sample = sin_cycles(self.phases[i]) * self.amplitudes[i];
+            left += sample;
+            right += match offsets {
+                Some(offsets) => sin_cycles(wrap_cycle(self.phases[i] + offsets[i])) * self.amplitudes[i],
+                None => sample,
+            };
+            self.phases[i] = wrap_cycle(self.phases[i] + self.increments[i]);
+        }
+        (left, right)
+    }
+    
+    // LANES 個ずつ f32x8 で計算し、塊に満たない残りの倍音は next_frame_scalar で1つずつ計算する
+    #[cfg(feature = "simd")]
+    fn next_frame_lanes(&mut self, count: usize, offsets: Option<&[f32; HARMONIC_COUNT]>) -> (f32, f32) {
+        let whole = count - count % LANES;
+        let (mut left, mut right) = (f32x8::ZERO, f32x8::ZERO);
+        let chunks = self.phases[..whole].chunks_exact_mut(LANES)
+            .zip(self.increments[..whole].chunks_exact(LANES))
+            .zip(self.amplitudes[..whole].chunks_exact(LANES));
+        for (chunk, ((phases, increments), amplitudes)) in chunks.enumerate() {
+            let (phase, amplitude) = (lanes_of(phases), lanes_of(amplitudes));
+            let sample = sin_cycles_lanes(phase) * amplitude;
+            left += sample;
+            right += match offsets {
+                Some(offsets) => {
+                    let offset = lanes_of(&offsets[chunk * LANES..(chunk + 1) * LANES]);
+                    sin_cycles_lanes(wrap_cycle_lanes(phase + offset)) * amplitude
+                }
+                None => sample,
+            };
+            phases.copy_from_slice(&wrap_cycle_lanes(phase + lanes_of(increments)).to_array());
+        }
+        let (rest_left, rest_right) = self.next_frame_scalar(count, offsets, whole);
+        (left.reduce_add() + rest_left, right.reduce_add() + rest_right)
+    }
+}
+
+// 加算合成でまとめて計算する倍音の数（AVX の f32 × 8 に合わせる。SSE では2回に分かれる）
+#[cfg(feature = "simd")]
+const LANES: usize = 8;
+
+#[cfg(feature = "simd")]
+#[inline(always)]
+fn lanes_of(values: &[f32]) -> f32x8 {
+    f32x8::new(values.try_into().unwrap())
+}
+
+// wrap_cycle と sin_cycles の f32x8 版。分岐の代わりに比較のマスクで選ぶ
+#[cfg(feature = "simd")]
+#[inline(always)]
+fn wrap_cycle_lanes(phase: f32x8) -> f32x8 {
+    let one = f32x8::ONE;
+    let wrapped = phase.cmp_ge(one).blend(phase - one, phase);
+    wrapped.cmp_lt(f32x8::ZERO).blend(wrapped + one, wrapped)
+}
+
+#[cfg(feature = "simd")]
+#[inline(always)]
+fn sin_cycles_lanes(phase: f32x8) -> f32x8 {
+    let x = phase - f32x8::splat(0.5);
+    let quarter = f32x8::splat(0.25);
+    let folded = x.cmp_gt(quarter).blend(f32x8::splat(0.5) - x, x.cmp_lt(-quarter).blend(f32x8::splat(-0.5) - x, x));
+    let theta = folded * f32x8::splat(std::f32::consts::TAU);
+    let square = theta * theta;
+    let (one, term) = (f32x8::ONE, |divisor: f32| square / f32x8::splat(divisor));
+    let series = one - term(6.0) * (one - term(20.0) * (one - term(42.0) * (one - term(72.0) * (one - term(110.0)))));
+    -theta * series
+}
+
+// -1.0 以上 2.0 未満の位相を 0.0-1.0 に戻す（位相の進みと左右のずれはそれぞれ1周期未満）
+#[inline(always)]
+fn wrap_cycle(phase: f32) -> f32 {
+    if phase >= 1.0 {
+        phase - 1.0
+    } else if phase < 0.0 {
+        phase + 1.0
+    } else {
+        phase
+    }
+}
+
+// 0.0-1.0 の位相（周期）の正弦。±1/4 周期に折り返してからテイラー展開の 11 次までで求める
+// （誤差は 1e-7 程度。sin_cycles_lanes と同じ計算の並びにしてある）
+#[inline(always)]
+fn sin_cycles(phase: f32) -> f32 {
+    let x = phase - 0.5; // sin(2π·phase) = -sin(2π·x)
+    let folded = if x > 0.25 {
+        0.5 - x
+    } else if x < -0.25 {
+        -0.5 - x
+    } else {
+        x
+    };
+    let theta = folded * std::f32::consts::TAU;
+    let square = theta * theta;
+    let series = 1.0
+        - square / 6.0
+            * (1.0 - square / 20.0 * (1.0 - square / 42.0 * (1.0 - square / 72.0 * (1.0 - square / 110.0))));
+    -theta * series
+}
+
 pub struct AdditiveEngine {
-    pub harmonics: Vec<Harmonic>,
+    harmonics: Vec<Harmonic>,
     base_frequency: f32,
     sample_rate: f32,
-    oscillators: Vec<SineOscillator>,
+    layers: Vec<StackLayer>, // 最大の層数まで確保しておき、先頭の active_layers 個だけを鳴らす（先頭は中央の層）
+    active_layers: usize,
+    spectral_tilt: f32,      // 倍音のオクターブあたりの傾き（指数）
+    stack_detune: f32,       // 最も外側の層のデチューン量（セント）
+    stack_spread: f32,       // 層のステレオの広がり 0.0-1.0
+    spectral_filter: SpectralFilter,
+    anti_alias: bool,        // ナイキスト周波数付近の倍音を減衰させる
+    requested_density: usize, // 負荷による制限前のスタックの層数
+    max_stack: usize,        // 負荷による制限
+    max_harmonics: usize,
+    audible_harmonics: usize, // 振幅が 0 でない最も高い倍音までの数（以降は計算しない）
+    width_offsets: Option<Box<[f32; HARMONIC_COUNT]>>, // 右チャンネルの倍音ごとの位相のずれ（周期、width 0 なら None）
+    pan_law: PanLaw,         // 層を左右に広げるときの法則
+    bloom: Option<f32>,      // 鳴らす倍音の範囲 0.0-1.0（None で制限なし）
+    drift: [f32; HARMONIC_COUNT], // 倍音ごとの振幅の係数（ドローンのゆらぎ、通常は 1.0）
 }
 
 impl AdditiveEngine {
     pub fn new(sample_rate: f32) -> Self {
-        let mut harmonics = Vec::new();
-        let mut oscillators = Vec::new();
-        
         // 64個の倍音を初期化
-        for i in 1..=64 {
-            harmonics.push(Harmonic {
-                frequency_multiplier: i as f32,
-                amplitude: if i == 1 { 1.0 } else { 0.0 },
-                phase: 0.0,
-                enabled: i == 1,
-            });
-            
-            oscillators.push(SineOscillator::new(sample_rate));
-        }
+        let harmonics = default_harmonics();
         
         Self {
             harmonics,
             base_frequency: 440.0,
             sample_rate,
-            oscillators,
+            layers: (0..MAX_STACK_DENSITY).map(|_| StackLayer::new(sample_rate)).collect(),
+            active_layers: 1,
+            spectral_tilt: 0.0,
+            stack_detune: 0.0,
+            stack_spread: 0.0,
+            spectral_filter: SpectralFilter::default(),
+            anti_alias: true,
+            requested_density: 1,
+            max_stack: MAX_STACK_DENSITY,
+            max_harmonics: HARMONIC_COUNT,
+            audible_harmonics: HARMONIC_COUNT,
+            width_offsets: None,
+            pan_law: PanLaw::default(),
+            bloom: None,
+            drift: [1.0; HARMONIC_COUNT],
         }
     }
     
     pub fn set_base_frequency(&mut self, freq: f32) {
         self.base_frequency = freq;
-        for (i, osc) in self.oscillators.iter_mut().enumerate() {
-            let harmonic = &self.harmonics[i];
-            osc.set_frequency(self.base_frequency * harmonic.frequency_multiplier);
-            osc.set_amplitude(if harmonic.enabled { harmonic.amplitude } else { 0.0 });
+        for i in 0..self.harmonics.len() {
+            let frequency = self.base_frequency * self.harmonics[i].frequency_multiplier;
+            for layer in &mut self.layers[..self.active_layers] {
+                // サンプルレートを超える倍音も、1周期を超える分は位相に影響しないので捨てる
+                layer.increments[i] = (frequency * layer.detune_ratio / self.sample_rate).fract();
+            }
+            self.update_oscillator_amplitude(i);
+        }
+        self.update_audible_harmonics();
+    }
+    
+    // アンチエイリアス: 周波数が変わるたびにナイキスト付近の倍音を減衰・ミュートし直す
+    pub fn set_anti_alias(&mut self, enabled: bool) {
+        if enabled != self.anti_alias {
+            self.anti_alias = enabled;
+            self.refresh_amplitudes();
+        }
+    }
+    
+    pub fn anti_alias(&self) -> bool {
+        self.anti_alias
+    }
+    
+    // ナイキストの 80% から 100% にかけて 1.0 から 0.0 へ減衰させる
+    fn alias_gain(&self, frequency: f32) -> f32 {
+        if !self.anti_alias {
+            return 1.0;
         }
+        let nyquist = self.sample_rate * 0.5;
+        ((nyquist - frequency) / (nyquist * 0.2)).clamp(0.0, 1.0)
     }
     
     pub fn set_harmonic_amplitude(&mut self, harmonic_index: usize, amplitude: f32) {
         if harmonic_index < self.harmonics.len() {
             self.harmonics[harmonic_index].amplitude = amplitude;
-            self.oscillators[harmonic_index].set_amplitude(amplitude);
+            self.refresh_amplitude(harmonic_index);
         }
     }
     
     pub fn toggle_harmonic(&mut self, harmonic_index: usize) {
         if harmonic_index < self.harmonics.len() {
             self.harmonics[harmonic_index].enabled = !self.harmonics[harmonic_index].enabled;
-            let amplitude = if self.harmonics[harmonic_index].enabled {
-                self.harmonics[harmonic_index].amplitude
-            } else {
+            self.refresh_amplitude(harmonic_index);
+        }
+    }
+    
+    pub fn set_harmonics(&mut self, harmonics: &[Harmonic]) {
+        for (target, source) in self.harmonics.iter_mut().zip(harmonics) {
+            *target = source.clone();
+        }
+        self.set_base_frequency(self.base_frequency);
+    }
+    
+    // スペクトルの傾き: 第n倍音の振幅に n^tilt を掛ける（1.0 で +6dB/oct）
+    pub fn set_spectral_tilt(&mut self, tilt: f32) {
+        self.spectral_tilt = tilt;
+        self.refresh_amplitudes();
+    }
+    
+    pub fn set_spectral_filter(&mut self, filter: SpectralFilter) {
+        if filter != self.spectral_filter {
+            self.spectral_filter = filter;
+            self.refresh_amplitudes();
+        }
+    }
+    
+    pub fn spectral_filter(&self) -> &SpectralFilter {
+        &self.spectral_filter
+    }
+    
+    // スタック: density 個（1 = 無効）のデチューンしたコピーを左右に広げて重ねる。
+    // 層は最初に最大数まで作ってあるので、演奏中に層数が変わってもメモリは確保しない
+    pub fn set_stack(&mut self, density: usize, detune_cents: f32, spread: f32) {
+        self.requested_density = density.clamp(1, MAX_STACK_DENSITY);
+        let density = self.requested_density.min(self.max_stack);
+        self.stack_detune = detune_cents.max(0.0);
+        self.stack_spread = spread.clamp(0.0, 1.0);
+        
+        self.active_layers = density;
+        
+        // 中央の層から外側へ交互に配置する
+        for (i, layer) in self.layers[..density].iter_mut().enumerate() {
+            let offset = if density == 1 {
                 0.0
+            } else {
+                let step = i.div_ceil(2) as f32 / (density / 2) as f32;
+                if i % 2 == 1 { -step } else { step }
             };
-            self.oscillators[harmonic_index].set_amplitude(amplitude);
+            layer.detune_ratio = 2.0_f32.powf(offset * self.stack_detune / 1200.0);
+            layer.pan = offset * self.stack_spread;
+            layer.gains = self.pan_law.gains(layer.pan);
+        }
+        self.set_base_frequency(self.base_frequency);
+    }
+    
+    pub fn set_pan_law(&mut self, pan_law: PanLaw) {
+        self.pan_law = pan_law;
+        for layer in &mut self.layers[..self.active_layers] {
+            layer.gains = pan_law.gains(layer.pan);
+        }
+    }
+    
+    // ブルーム: amount に応じて低い倍音から順に鳴らす。倍音の番号は 64^amount で指数的に増やすので、
+    // 音色の変化が大きい低次の倍音ほど細かく動く。境目の倍音はフェードさせる。
+    pub fn set_bloom(&mut self, amount: Option<f32>) {
+        let amount = amount.map(|amount| amount.clamp(0.0, 1.0));
+        if amount != self.bloom {
+            self.bloom = amount;
+            self.refresh_amplitudes();
+        }
+    }
+    
+    fn bloom_gain(&self, harmonic_index: usize) -> f32 {
+        match self.bloom {
+            Some(amount) => {
+                let position = (HARMONIC_COUNT as f32).powf(amount) - 1.0;
+                (position - harmonic_index as f32 + 1.0).clamp(0.0, 1.0)
+            }
+            None => 1.0,
+        }
+    }
+    
+    // 倍音ごとの振幅に掛ける係数（None で 1.0 に戻す）。ドローンのボイスがコントロールレートで更新する
+    pub fn set_harmonic_drift(&mut self, gains: Option<&[f32; HARMONIC_COUNT]>) {
+        let gains = gains.copied().unwrap_or([1.0; HARMONIC_COUNT]);
+        if gains != self.drift {
+            self.drift = gains;
+            self.refresh_amplitudes();
+        }
+    }
+    
+    pub fn stack_density(&self) -> usize {
+        self.active_layers
+    }
+    
+    // ステレオ幅: 右チャンネルの倍音の位相を倍音ごとにずらして左右の相関を下げる。
+    // ずれは黄金比の列で決まった値（最大 ±width/2 周期）で、基音はずらさずモノラルでも痩せないようにする。
+    pub fn set_width(&mut self, width: f32) {
+        let width = width.clamp(0.0, 1.0);
+        self.width_offsets = (width > 0.0).then(|| {
+            Box::new(std::array::from_fn(|i| if i == 0 { 0.0 } else { ((i as f32 * 0.618_034).fract() - 0.5) * width }))
+        });
+    }
+    
+    // 負荷に応じた品質の制限（計算する倍音数とスタックの層数）
+    pub fn set_quality_limits(&mut self, max_harmonics: usize, max_stack: usize) {
+        self.max_harmonics = max_harmonics.clamp(1, HARMONIC_COUNT);
+        let max_stack = max_stack.clamp(1, MAX_STACK_DENSITY);
+        if max_stack != self.max_stack {
+            self.max_stack = max_stack;
+            self.set_stack(self.requested_density, self.stack_detune, self.stack_spread);
+        }
+    }
+    
+    // すべての倍音の位相を 0 に戻す（ボイスを使い回すとき）
+    pub fn reset_phases(&mut self) {
+        for layer in &mut self.layers {
+            layer.phases = [0.0; HARMONIC_COUNT];
         }
     }
     
+    // 層ごとに位相をランダム化して、コピー同士が打ち消し合わないようにする
+    pub fn randomize_stack_phases(&mut self, rng: &mut Rng) {
+        for layer in self.layers[1..self.active_layers].iter_mut() {
+            for phase in &mut layer.phases {
+                *phase = rng.next_f32();
+            }
+        }
+    }
+    
+    fn refresh_amplitudes(&mut self) {
+        for i in 0..self.harmonics.len() {
+            self.update_oscillator_amplitude(i);
+        }
+        self.update_audible_harmonics();
+    }
+    
+    fn update_audible_harmonics(&mut self) {
+        self.audible_harmonics = self.layers[..self.active_layers].iter()
+            .filter_map(|layer| layer.amplitudes.iter().rposition(|amplitude| *amplitude != 0.0))
+            .max()
+            .map_or(0, |index| index + 1);
+    }
+    
+    fn refresh_amplitude(&mut self, harmonic_index: usize) {
+        // フォルマントシフト中は他の倍音の振幅も参照するため全体を更新する
+        if self.spectral_filter.formant_shift != 0.0 {
+            self.refresh_amplitudes();
+        } else {
+            self.update_oscillator_amplitude(harmonic_index);
+            self.update_audible_harmonics();
+        }
+    }
+    
+    fn update_oscillator_amplitude(&mut self, harmonic_index: usize) {
+        let amplitude = self.effective_amplitude(harmonic_index);
+        let frequency = self.base_frequency * self.harmonics[harmonic_index].frequency_multiplier;
+        for i in 0..self.active_layers {
+            let gain = self.alias_gain(frequency * self.layers[i].detune_ratio);
+            self.layers[i].amplitudes[harmonic_index] = amplitude * gain;
+        }
+    }
+    
+    fn effective_amplitude(&self, harmonic_index: usize) -> f32 {
+        let n = self.harmonics[harmonic_index].frequency_multiplier.max(1.0);
+        self.shifted_amplitude(harmonic_index) * n.powf(self.spectral_tilt) * self.spectral_filter.gain(n) * self.bloom_gain(harmonic_index) * self.drift[harmonic_index]
+    }
+    
+    fn raw_amplitude(&self, harmonic_index: usize) -> f32 {
+        let harmonic = &self.harmonics[harmonic_index];
+        if harmonic.enabled { harmonic.amplitude } else { 0.0 }
+    }
+    
+    // フォルマントシフト: 倍音番号を比率で割った位置の振幅を線形補間で読む
+    fn shifted_amplitude(&self, harmonic_index: usize) -> f32 {
+        if self.spectral_filter.formant_shift == 0.0 {
+            return self.raw_amplitude(harmonic_index);
+        }
+        let position = ((harmonic_index + 1) as f32 / self.spectral_filter.formant_ratio() - 1.0).max(0.0);
+        let lower = position.floor() as usize;
+        if lower + 1 >= self.harmonics.len() {
+            return if lower < self.harmonics.len() { self.raw_amplitude(lower) } else { 0.0 };
+        }
+        let fraction = position - lower as f32;
+        self.raw_amplitude(lower) * (1.0 - fraction) + self.raw_amplitude(lower + 1) * fraction
+    }
+    
     pub fn next_sample(&mut self) -> f32 {
-        let mut sample = 0.0;
-        for osc in &mut self.oscillators {
-            sample += osc.next_sample();
+        let [left, right] = self.next_frame();
+        (left + right) * 0.5
+    }
+    
+    pub fn next_frame(&mut self) -> [f32; 2] {
+        self.mix_layers(StackLayer::next_frame)
+    }
+    
+    // ベクトル命令を使わない基準の経路（テストで simd フィーチャーの経路と比べる）
+    #[cfg(test)]
+    pub(crate) fn next_frame_scalar(&mut self) -> [f32; 2] {
+        self.mix_layers(|layer, count, offsets| layer.next_frame_scalar(count, offsets, 0))
+    }
+    
+    fn mix_layers(
+        &mut self,
+        mut render: impl FnMut(&mut StackLayer, usize, Option<&[f32; HARMONIC_COUNT]>) -> (f32, f32),
+    ) -> [f32; 2] {
+        let mut frame = [0.0; 2];
+        let harmonic_count = self.audible_harmonics.min(self.max_harmonics);
+        let offsets = self.width_offsets.as_deref();
+        for layer in &mut self.layers[..self.active_layers] {
+            let (left, right) = render(layer, harmonic_count, offsets);
+            frame[0] += left * layer.gains[0];
+            frame[1] += right * layer.gains[1];
         }
-        sample / 64.0 // 正規化
+        // 位相がランダムな層は電力で加算されるので √n で正規化
+        let gain = 1.0 / (HARMONIC_COUNT as f32 * (self.active_layers as f32).sqrt());
+        [frame[0] * gain, frame[1] * gain]
     }
     
     pub fn harmonics(&self) -> &[Harmonic] {
@@ -125,7 +644,7 @@ impl AdditiveEngine {
 }
 
 // FM Engine
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Operator {
     pub frequency_ratio: f32,
     pub amplitude: f32,
@@ -133,50 +652,122 @@ pub struct Operator {
     pub enabled: bool,
 }
 
+// 初期状態のオペレーター（第1オペレーターのみ有効）
+pub fn default_operators() -> Vec<Operator> {
+    (0..OPERATOR_COUNT)
+        .map(|i| Operator {
+            frequency_ratio: if i == 0 { 1.0 } else { 0.0 },
+            amplitude: if i == 0 { 1.0 } else { 0.0 },
+            feedback: 0.0,
+            enabled: i == 0,
+        })
+        .collect()
+}
+
+// DX7 のアルゴリズム（オペレーターの番号は 1 から）。変調は常に番号の大きいオペレーターから小さい方へ流れる。
+// feedback は DX7 でフィードバックが掛かるオペレーター（4・6 番の 3 段・2 段のループはそのオペレーター単体のフィードバックとして扱う）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FmAlgorithm {
+    pub carriers: &'static [usize],
+    pub modulations: &'static [(usize, usize)], // (変調元, 変調先)
+    pub feedback: usize,
+}
+
+const fn algorithm(carriers: &'static [usize], modulations: &'static [(usize, usize)], feedback: usize) -> FmAlgorithm {
+    FmAlgorithm { carriers, modulations, feedback }
+}
+
+pub const FM_ALGORITHMS: [FmAlgorithm; 32] = [
+    algorithm(&[1, 3], &[(2, 1), (4, 3), (5, 4), (6, 5)], 6),
+    algorithm(&[1, 3], &[(2, 1), (4, 3), (5, 4), (6, 5)], 2),
+    algorithm(&[1, 4], &[(2, 1), (3, 2), (5, 4), (6, 5)], 6),
+    algorithm(&[1, 4], &[(2, 1), (3, 2), (5, 4), (6, 5)], 6),
+    algorithm(&[1, 3, 5], &[(2, 1), (4, 3), (6, 5)], 6),
+    algorithm(&[1, 3, 5], &[(2, 1), (4, 3), (6, 5)], 6),
+    algorithm(&[1, 3], &[(2, 1), (4, 3), (5, 3), (6, 5)], 6),
+    algorithm(&[1, 3], &[(2, 1), (4, 3), (5, 3), (6, 5)], 4),
+    algorithm(&[1, 3], &[(2, 1), (4, 3), (5, 3), (6, 5)], 2),
+    algorithm(&[1, 4], &[(2, 1), (3, 2), (5, 4), (6, 4)], 3),
+    algorithm(&[1, 4], &[(2, 1), (3, 2), (5, 4), (6, 4)], 6),
+    algorithm(&[1, 3], &[(2, 1), (4, 3), (5, 3), (6, 3)], 2),
+    algorithm(&[1, 3], &[(2, 1), (4, 3), (5, 3), (6, 3)], 6),
+    algorithm(&[1, 3], &[(2, 1), (4, 3), (5, 4), (6, 4)], 6),
+    algorithm(&[1, 3], &[(2, 1), (4, 3), (5, 4), (6, 4)], 2),
+    algorithm(&[1], &[(2, 1), (3, 1), (4, 3), (5, 1), (6, 5)], 6),
+    algorithm(&[1], &[(2, 1), (3, 1), (4, 3), (5, 1), (6, 5)], 2),
+    algorithm(&[1], &[(2, 1), (3, 1), (4, 1), (5, 4), (6, 5)], 3),
+    algorithm(&[1, 4, 5], &[(2, 1), (3, 2), (6, 4), (6, 5)], 6),
+    algorithm(&[1, 2, 4], &[(3, 1), (3, 2), (5, 4), (6, 4)], 3),
+    algorithm(&[1, 2, 4, 5], &[(3, 1), (3, 2), (6, 4), (6, 5)], 3),
+    algorithm(&[1, 3, 4, 5], &[(2, 1), (6, 3), (6, 4), (6, 5)], 6),
+    algorithm(&[1, 2, 4, 5], &[(3, 2), (6, 4), (6, 5)], 6),
+    algorithm(&[1, 2, 3, 4, 5], &[(6, 3), (6, 4), (6, 5)], 6),
+    algorithm(&[1, 2, 3, 4, 5], &[(6, 4), (6, 5)], 6),
+    algorithm(&[1, 2, 4], &[(3, 2), (5, 4), (6, 4)], 6),
+    algorithm(&[1, 2, 4], &[(3, 2), (5, 4), (6, 4)], 3),
+    algorithm(&[1, 3, 6], &[(2, 1), (4, 3), (5, 4)], 5),
+    algorithm(&[1, 2, 3, 5], &[(4, 3), (6, 5)], 6),
+    algorithm(&[1, 2, 3, 6], &[(4, 3), (5, 4)], 5),
+    algorithm(&[1, 2, 3, 4, 5], &[(6, 5)], 6),
+    algorithm(&[1, 2, 3, 4, 5, 6], &[], 6),
+];
+
+// アルゴリズムでの変調の深さ。ブライトネス 0.5（変調の倍率 1）で、DX7 の出力レベル 99 の
+// モジュレーターのおおよその変調指数（4π）、フィードバック 1.0 で π になる
+const ALGORITHM_MODULATION_DEPTH: f32 = 4.0 * std::f32::consts::PI;
+const ALGORITHM_FEEDBACK_DEPTH: f32 = std::f32::consts::PI;
+
 pub struct FMEngine {
-    pub operators: Vec<Operator>,
+    operators: Vec<Operator>,
+    algorithm: usize, // 0 = 従来の簡易ネットワーク, 1-32 = FM_ALGORITHMS
     base_frequency: f32,
-    sample_rate: f32,
     oscillators: Vec<SineOscillator>,
     feedback_buffer: Vec<f32>,
+    modulation_index: f32, // 変調の深さの倍率（1.0 = 標準）
+    width: f32,            // 0.0 なら右チャンネルは左と同じ
+    right_oscillators: Vec<SineOscillator>, // 右チャンネル用（width > 0 のときだけ計算する）
+    right_feedback: Vec<f32>,
+    exact_sine: bool, // 共有の正弦波テーブルの代わりに f32::sin で計算する
 }
 
 impl FMEngine {
     pub fn new(sample_rate: f32) -> Self {
-        let mut operators = Vec::new();
-        let mut oscillators = Vec::new();
-        let mut feedback_buffer = Vec::new();
-        
         // 6個のオペレーターを初期化
-        for i in 0..6 {
-            operators.push(Operator {
-                frequency_ratio: if i == 0 { 1.0 } else { 0.0 },
-                amplitude: if i == 0 { 1.0 } else { 0.0 },
-                feedback: 0.0,
-                enabled: i == 0,
-            });
-            
-            oscillators.push(SineOscillator::new(sample_rate));
-            feedback_buffer.push(0.0);
-        }
+        let operators = default_operators();
+        let oscillators = (0..OPERATOR_COUNT).map(|_| SineOscillator::new(sample_rate)).collect();
+        let feedback_buffer = vec![0.0; OPERATOR_COUNT];
         
         Self {
             operators,
+            algorithm: 0,
             base_frequency: 440.0,
-            sample_rate,
             oscillators,
             feedback_buffer,
+            modulation_index: 1.0,
+            width: 0.0,
+            right_oscillators: (0..OPERATOR_COUNT).map(|_| SineOscillator::new(sample_rate)).collect(),
+            right_feedback: vec![0.0; OPERATOR_COUNT],
+            exact_sine: false,
         }
     }
     
     pub fn set_base_frequency(&mut self, freq: f32) {
         self.base_frequency = freq;
-        for (i, osc) in self.oscillators.iter_mut().enumerate() {
-            let op = &self.operators[i];
-            osc.set_frequency(self.base_frequency * op.frequency_ratio);
+        // 幅があるときは左右を半分ずつ逆向きにデチューンする
+        let detune = 2.0_f32.powf(self.width * WIDTH_FM_DETUNE_CENTS * 0.5 / 1200.0);
+        for (i, (osc, right)) in self.oscillators.iter_mut().zip(&mut self.right_oscillators).enumerate() {
+            let frequency = self.base_frequency * self.operators[i].frequency_ratio;
+            osc.set_frequency(frequency / detune);
+            right.set_frequency(frequency * detune);
         }
     }
     
+    // ステレオ幅: 右チャンネルを別のオシレーターで少しデチューンして鳴らす
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, 1.0);
+        self.set_base_frequency(self.base_frequency);
+    }
+    
     pub fn set_operator_amplitude(&mut self, operator_index: usize, amplitude: f32) {
         if operator_index < self.operators.len() {
             self.operators[operator_index].amplitude = amplitude;
@@ -186,7 +777,7 @@ impl FMEngine {
     pub fn set_operator_frequency_ratio(&mut self, operator_index: usize, ratio: f32) {
         if operator_index < self.operators.len() {
             self.operators[operator_index].frequency_ratio = ratio;
-            self.oscillators[operator_index].set_frequency(self.base_frequency * ratio);
+            self.set_base_frequency(self.base_frequency);
         }
     }
     
@@ -196,50 +787,153 @@ impl FMEngine {
         }
     }
     
+    pub fn set_operators(&mut self, operators: &[Operator]) {
+        for (target, source) in self.operators.iter_mut().zip(operators) {
+            *target = source.clone();
+        }
+        self.set_base_frequency(self.base_frequency);
+    }
+    
+    pub fn set_modulation_index(&mut self, index: f32) {
+        self.modulation_index = index.max(0.0);
+    }
+    
+    // 範囲外は従来の簡易ネットワーク（0）として扱う
+    pub fn set_algorithm(&mut self, algorithm: usize) {
+        self.algorithm = if algorithm <= FM_ALGORITHMS.len() { algorithm } else { 0 };
+    }
+    
+    pub fn algorithm(&self) -> usize {
+        self.algorithm
+    }
+    
+    // オペレーターの位相とフィードバックを 0 に戻す（ボイスを使い回すとき）
+    pub fn reset(&mut self) {
+        for osc in self.oscillators.iter_mut().chain(&mut self.right_oscillators) {
+            osc.set_phase(0.0);
+        }
+        self.feedback_buffer.fill(0.0);
+        self.right_feedback.fill(0.0);
+    }
+    
+    // 正弦を f32::sin で正確に計算する（既定は共有の正弦波テーブル）
+    pub fn set_exact_sine(&mut self, exact: bool) {
+        self.exact_sine = exact;
+        for osc in self.oscillators.iter_mut().chain(&mut self.right_oscillators) {
+            osc.set_exact(exact);
+        }
+    }
+    
     pub fn next_sample(&mut self) -> f32 {
-        let mut output = 0.0;
+        self.render(false)
+    }
+    
+    fn render(&mut self, right: bool) -> f32 {
+        let (oscillators, feedback_buffer) = if right {
+            (&mut self.right_oscillators, &mut self.right_feedback)
+        } else {
+            (&mut self.oscillators, &mut self.feedback_buffer)
+        };
+        match self.algorithm.checked_sub(1).and_then(|index| FM_ALGORITHMS.get(index)) {
+            Some(algorithm) => render_algorithm(algorithm, &self.operators, oscillators, feedback_buffer, self.modulation_index, self.exact_sine),
+            None => render_operators(&self.operators, oscillators, feedback_buffer, self.modulation_index, self.exact_sine),
+        }
+    }
+    
+    pub fn next_frame(&mut self) -> [f32; 2] {
+        let left = self.next_sample();
+        if self.width == 0.0 {
+            return [left; 2];
+        }
+        [left, self.render(true)]
+    }
+    
+    pub fn operators(&self) -> &[Operator] {
+        &self.operators
+    }
+}
+
+fn render_operators(
+    operators: &[Operator],
+    oscillators: &mut [SineOscillator],
+    feedback_buffer: &mut [f32],
+    modulation_index: f32,
+    exact: bool,
+) -> f32 {
+    let mut output = 0.0;
+    
+    // 各オペレーターの処理
+    for i in 0..operators.len() {
+        if !operators[i].enabled {
+            continue;
+        }
         
-        // 各オペレーターの処理
-        for i in 0..self.operators.len() {
-            if !self.operators[i].enabled {
-                continue;
-            }
-            
-            let mut phase_modulation = 0.0;
-            
-            // フィードバック
-            if self.operators[i].feedback > 0.0 {
-                phase_modulation += self.feedback_buffer[i] * self.operators[i].feedback;
-            }
-            
-            // 他のオペレーターからの変調（簡易版）
-            for j in 0..self.operators.len() {
-                if i != j && self.operators[j].enabled {
-                    phase_modulation += self.feedback_buffer[j] * 0.1; // 簡易変調
-                }
+        let mut phase_modulation = 0.0;
+        
+        // フィードバック
+        if operators[i].feedback > 0.0 {
+            phase_modulation += feedback_buffer[i] * operators[i].feedback;
+        }
+        
+        // 他のオペレーターからの変調（簡易版）
+        for j in 0..operators.len() {
+            if i != j && operators[j].enabled {
+                phase_modulation += feedback_buffer[j] * 0.1; // 簡易変調
             }
-            
-            // オシレーターの位相を変調
-            let sample = (self.oscillators[i].next_sample() + phase_modulation).sin() 
-                * self.operators[i].amplitude;
-            
-            self.feedback_buffer[i] = sample;
-            output += sample;
         }
         
-        output / 6.0 // 正規化
+        phase_modulation *= modulation_index;
+        
+        // オシレーターの位相を変調
+        let sample = sine_radians(oscillators[i].next_sample() + phase_modulation, exact)
+            * operators[i].amplitude;
+        
+        feedback_buffer[i] = sample;
+        output += sample;
     }
     
-    pub fn operators(&self) -> &[Operator] {
-        &self.operators
+    output / OPERATOR_COUNT as f32 // 正規化
+}
+
+// 番号の大きいオペレーターから順に計算し、そのサンプルの出力で番号の小さいオペレーターを変調する。
+// キャリアの和をキャリアの数で正規化する
+fn render_algorithm(
+    algorithm: &FmAlgorithm,
+    operators: &[Operator],
+    oscillators: &mut [SineOscillator],
+    feedback_buffer: &mut [f32],
+    modulation_index: f32,
+    exact: bool,
+) -> f32 {
+    let mut outputs = [0.0; OPERATOR_COUNT];
+    for i in (0..OPERATOR_COUNT).rev() {
+        let phase = oscillators[i].next_phase();
+        if !operators[i].enabled {
+            continue;
+        }
+        let modulation: f32 = algorithm.modulations.iter()
+            .filter(|(_, target)| *target == i + 1)
+            .map(|(source, _)| outputs[source - 1])
+            .sum();
+        let feedback = feedback_buffer[i] * operators[i].feedback * ALGORITHM_FEEDBACK_DEPTH;
+        outputs[i] = sine_radians(phase + (modulation * ALGORITHM_MODULATION_DEPTH + feedback) * modulation_index, exact)
+            * operators[i].amplitude;
+        feedback_buffer[i] = outputs[i];
     }
+    let output: f32 = algorithm.carriers.iter().map(|carrier| outputs[carrier - 1]).sum();
+    output / algorithm.carriers.len() as f32
 }
 
 // エンジンブレンダー
 pub struct EngineBlender {
-    pub additive_engine: AdditiveEngine,
-    pub fm_engine: FMEngine,
+    additive_engine: AdditiveEngine,
+    fm_engine: FMEngine,
     blend_ratio: f32, // 0.0 = Additive only, 1.0 = FM only
+    brightness: f32,  // 0.0 = dark, 0.5 = neutral, 1.0 = bright
+    frequency: f32,   // ピッチベンド前の周波数
+    pitch_bend: f32,  // 半音
+    last_additive: [f32; 2], // 解析タップ用の直前の出力（ブレンド適用後）
+    last_fm: [f32; 2],
 }
 
 impl EngineBlender {
@@ -248,6 +942,11 @@ impl EngineBlender {
             additive_engine: AdditiveEngine::new(sample_rate),
             fm_engine: FMEngine::new(sample_rate),
             blend_ratio: 0.5,
+            brightness: 0.5,
+            frequency: 440.0,
+            pitch_bend: 0.0,
+            last_additive: [0.0; 2],
+            last_fm: [0.0; 2],
         }
     }
     
@@ -255,23 +954,83 @@ impl EngineBlender {
         self.blend_ratio = ratio.clamp(0.0, 1.0);
     }
     
+    // ブライトネス・マクロ: 倍音の傾きとFM変調指数を同時に動かす。
+    // 傾きは ±6dB/oct、変調指数は 1/4〜4 倍で、スペクトル重心の動きがほぼ揃うように対応させている。
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness.clamp(0.0, 1.0);
+        let amount = self.brightness * 2.0 - 1.0;
+        self.additive_engine.set_spectral_tilt(amount);
+        self.fm_engine.set_modulation_index(4.0_f32.powf(amount));
+    }
+    
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+    
     pub fn set_frequency(&mut self, freq: f32) {
+        self.frequency = freq;
+        self.apply_frequency();
+    }
+    
+    pub fn set_pitch_bend(&mut self, semitones: f32) {
+        if semitones != self.pitch_bend {
+            self.pitch_bend = semitones;
+            self.apply_frequency();
+        }
+    }
+    
+    fn apply_frequency(&mut self) {
+        let freq = self.frequency * 2.0_f32.powf(self.pitch_bend / 12.0);
         self.additive_engine.set_base_frequency(freq);
         self.fm_engine.set_base_frequency(freq);
     }
     
+    // ボイス単体のステレオ幅 0.0-1.0（加算合成は倍音の位相、FM は左右のデチューン）
+    pub fn set_width(&mut self, width: f32) {
+        self.additive_engine.set_width(width);
+        self.fm_engine.set_width(width);
+    }
+    
+    // 両エンジンの発振の状態を新しく作ったときと同じにする（音色の設定は残る）
+    pub fn reset(&mut self) {
+        self.additive_engine.reset_phases();
+        self.fm_engine.reset();
+        self.last_additive = [0.0; 2];
+        self.last_fm = [0.0; 2];
+    }
+    
     pub fn next_sample(&mut self) -> f32 {
-        let additive_sample = self.additive_engine.next_sample();
-        let fm_sample = self.fm_engine.next_sample();
+        let [left, right] = self.next_frame();
+        (left + right) * 0.5
+    }
+    
+    pub fn next_frame(&mut self) -> [f32; 2] {
+        let [additive_left, additive_right] = self.additive_engine.next_frame();
+        let [fm_left, fm_right] = self.fm_engine.next_frame();
         
-        // クロスフェード
-        additive_sample * (1.0 - self.blend_ratio) + fm_sample * self.blend_ratio
+        // クロスフェード（FMは幅が 0 なら中央に定位）
+        self.last_additive = [additive_left * (1.0 - self.blend_ratio), additive_right * (1.0 - self.blend_ratio)];
+        self.last_fm = [fm_left * self.blend_ratio, fm_right * self.blend_ratio];
+        [
+            self.last_additive[0] + self.last_fm[0],
+            self.last_additive[1] + self.last_fm[1],
+        ]
+    }
+    
+    // 直前のフレームでの各エンジンの寄与（加算合成の左右, FM の左右）
+    pub fn last_outputs(&self) -> ([f32; 2], [f32; 2]) {
+        (self.last_additive, self.last_fm)
     }
     
     pub fn additive_engine(&mut self) -> &mut AdditiveEngine {
         &mut self.additive_engine
     }
     
+    // 加算合成で鳴らしているスタックの層数（負荷による制限後）
+    pub fn stack_density(&self) -> usize {
+        self.additive_engine.stack_density()
+    }
+    
     pub fn fm_engine(&mut self) -> &mut FMEngine {
         &mut self.fm_engine
     }
@@ -0,0 +1,36 @@
+// リリースベロシティを送らない機器・シーケンサーのノートオフの値（MIDI の規定の 64）
+pub const DEFAULT_RELEASE_VELOCITY: f32 = 64.0 / 127.0;
+
+// シンセサイザーへの演奏イベント
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    NoteOn { note: u8, velocity: f32 },
+    NoteOff { note: u8, velocity: f32 }, // リリースベロシティ 0.0-1.0（無いときは DEFAULT_RELEASE_VELOCITY）
+    MidiNoteOn { channel: u8, note: u8, velocity: f32 }, // MIDI 入力のノートオン（チャンネルはレイヤーの受信フィルターに使う）
+    AllNotesOff,
+    ProgramChange { program: u8 },
+    ControlChange { controller: u8, value: u8 }, // value は 0-127
+    // MIDI のリアルタイムメッセージ（テンポの取得元が MIDI クロックのときにトランスポートを動かす）
+    Clock, // 24 PPQN のパルス
+    Start,
+    Continue,
+    Stop,
+}
+
+// サンプル位置付きのイベント
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduledEvent {
+    pub sample: u64,
+    pub event: Event,
+}
+
+impl ScheduledEvent {
+    pub fn new(sample: u64, event: Event) -> Self {
+        Self { sample, event }
+    }
+    
+    // 秒単位の時刻からサンプル位置を求める
+    pub fn at_seconds(seconds: f32, sample_rate: f32, event: Event) -> Self {
+        Self::new((seconds.max(0.0) * sample_rate) as u64, event)
+    }
+}
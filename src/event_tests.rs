@@ -0,0 +1,174 @@
+// サンプル位置を指定したイベントとコントロールレートの境界での変更のテスト
+use crate::event::Event;
+use crate::synth::Synthesizer;
+
+// 読み込んだ音色は、渡したサンプル位置によらず次のコントロールレートの境界でまとめて差し替わり、
+// 差し替え後の出力はその境界で直接設定したときと同じになる
+#[test]
+fn queued_patch_swaps_at_the_next_control_block() {
+    use crate::modulation::MOD_BLOCK_SIZE;
+    use crate::patch::PatchState;
+    
+    let chord = |synth: &mut Synthesizer| {
+        for note in [48, 55, 60, 64, 67, 72, 76, 79] {
+            synth.note_on(note, 0.8);
+        }
+    };
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    chord(&mut synth);
+    let texts: Vec<String> = [0.2, 0.8]
+        .iter()
+        .map(|blend| PatchState { blend: *blend, ..synth.patch().clone() }.to_delta("init").unwrap())
+        .collect();
+    let parse = |text: &str| PatchState::from_delta(text).unwrap().0;
+    
+    // 境界の途中で渡しても、境界までは元の音色のまま
+    for _ in 0..MOD_BLOCK_SIZE / 2 {
+        synth.next_frame();
+    }
+    synth.queue_patch(parse(&texts[0]));
+    for _ in MOD_BLOCK_SIZE / 2..MOD_BLOCK_SIZE {
+        synth.next_frame();
+    }
+    assert_eq!(synth.patch().blend, 0.5);
+    assert!(synth.has_pending_patch());
+    synth.next_frame();
+    assert_eq!(synth.patch().blend, 0.2);
+    assert!(!synth.has_pending_patch());
+    
+    // どの位置で渡しても、差し替わるまでのサンプル数は次の境界までの距離と同じ
+    for (index, offset) in [1, 7, MOD_BLOCK_SIZE - 1].into_iter().enumerate() {
+        while synth.now_samples() as usize % MOD_BLOCK_SIZE != offset {
+            synth.next_frame();
+        }
+        synth.queue_patch(parse(&texts[(index + 1) % 2]));
+        let mut waited = 0;
+        while synth.has_pending_patch() {
+            synth.next_frame();
+            waited += 1;
+        }
+        assert_eq!(waited, MOD_BLOCK_SIZE - offset + 1, "queued {} samples into a block", offset);
+    }
+    
+    // 差し替わったボイスは、同じ境界で音色を直接設定したボイスと（ボイスを足す順序による丸め誤差の範囲で）同じ音を出す
+    let mut target = PatchState::base_preset("fm").unwrap();
+    target.cutoff = 0.4;
+    target.envelope.release = 0.3;
+    target.harmonics[1].amplitude *= 0.5;
+    let render = |swap: &dyn Fn(&mut Synthesizer)| {
+        let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+        synth.set_note_random_seed(7);
+        chord(&mut synth);
+        let mut block = vec![[0.0; 2]; MOD_BLOCK_SIZE * 4];
+        synth.render_block(&mut block);
+        swap(&mut synth);
+        let mut out = vec![[0.0; 2]; 512];
+        synth.render_block(&mut out);
+        out
+    };
+    let queued = render(&|synth| synth.queue_patch(target.clone()));
+    let direct = render(&|synth| synth.set_patch(target.clone()));
+    let peak = direct.iter().fold(0.0_f32, |peak, frame| peak.max(frame[0].abs()));
+    assert!(peak > 0.01);
+    for (index, (a, b)) in queued.iter().zip(&direct).enumerate() {
+        assert!((a[0] - b[0]).abs() <= 1e-5 * peak && (a[1] - b[1]).abs() <= 1e-5 * peak, "sample {} differs", index);
+    }
+}
+
+// まとめた変更は次の境界で同時に設定され、1つでも設定できなければ何も設定しない
+#[test]
+fn parameter_batch_applies_together_or_not_at_all() {
+    use crate::modulation::MOD_BLOCK_SIZE;
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    synth.note_on(60, 0.8);
+    let before = synth.patch().to_delta("init").unwrap();
+    let batch = |changes: &[(&str, f32)]| changes.iter().map(|(name, value)| (name.to_string(), *value)).collect::<Vec<_>>();
+    
+    assert!(synth.apply_batch(batch(&[("cutoff", 800.0), ("no.such.param", 1.0)])).is_err());
+    assert!(synth.apply_batch(batch(&[("blend", 0.2), ("morph", 0.5)])).is_err());
+    assert!(synth.apply_batch(batch(&[("volume", f32::NAN)])).is_err());
+    assert!(!synth.has_pending_batch());
+    
+    for _ in 0..MOD_BLOCK_SIZE / 2 {
+        synth.next_frame();
+    }
+    synth.apply_batch(batch(&[("cutoff", 0.3), ("stack.density", 3.0), ("blend", 0.2)])).unwrap();
+    synth.apply_batch(batch(&[("env.release", 0.4)])).unwrap();
+    for _ in MOD_BLOCK_SIZE / 2..MOD_BLOCK_SIZE {
+        synth.next_frame();
+    }
+    assert_eq!(synth.patch().to_delta("init").unwrap(), before);
+    assert!(synth.has_pending_batch());
+    
+    synth.next_frame();
+    let patch = synth.patch();
+    assert_eq!((patch.cutoff, patch.stack_density, patch.blend, patch.envelope.release), (0.3, 3, 0.2, 0.4));
+    assert!(!synth.has_pending_batch());
+}
+
+// 時刻指定のノートはそのサンプルで、パラメータは時刻以降のコントロールレートの境界で発生し、遅れて届いたものは数える
+#[test]
+fn timestamped_events_follow_the_sample_clock() {
+    use crate::modulation::MOD_BLOCK_SIZE;
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    for _ in 0..10 {
+        synth.next_frame();
+    }
+    let start = synth.now_samples();
+    assert_eq!(start, 10);
+    synth.note_on_at(start + 50, 60, 0.8);
+    synth.note_off_at(start + 20, 64, 0.5);
+    synth.set_parameter_at(start + 40, "cutoff", 0.3).unwrap();
+    assert!(synth.set_parameter_at(start + 40, "no.such.param", 1.0).is_err());
+    
+    for _ in 0..50 {
+        synth.next_frame();
+    }
+    assert_eq!(synth.voice_count(), 0);
+    assert_ne!(synth.patch().cutoff, 0.3); // 次の境界（64）までは設定しない
+    synth.next_frame();
+    assert_eq!(synth.voice_count(), 1);
+    for _ in 0..4 {
+        synth.next_frame();
+    }
+    assert_eq!(synth.patch().cutoff, 0.3);
+    assert_eq!(synth.late_events(), 0);
+    
+    // 遅れを足した時刻に発生し、それでも過ぎていればすぐに発生させる
+    synth.set_timestamp_latency(MOD_BLOCK_SIZE as f32 / 22050.0);
+    let now = synth.now_samples();
+    synth.note_on_at(now, 67, 0.8);
+    for _ in 0..MOD_BLOCK_SIZE {
+        synth.next_frame();
+    }
+    assert_eq!(synth.voice_count(), 1);
+    synth.next_frame();
+    assert_eq!(synth.voice_count(), 2);
+    synth.set_timestamp_latency(0.0);
+    synth.note_on_at(now, 72, 0.8);
+    synth.next_frame();
+    assert_eq!(synth.voice_count(), 3);
+    assert_eq!(synth.late_events(), 1);
+}
+
+// drive はブロックごとに生成器へ位置と長さを尋ね、返したイベントをそのブロックの先頭で発生させる
+#[test]
+fn drive_pulls_events_from_the_generator_each_block() {
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    let mut out = vec![[0.0; 2]; 1000];
+    let mut requests = Vec::new();
+    synth.drive(&mut out, 256, |block_start, len| {
+        requests.push((block_start, len));
+        match block_start {
+            0 => vec![Event::NoteOn { note: 60, velocity: 0.8 }, Event::NoteOn { note: 64, velocity: 0.8 }],
+            512 => vec![Event::NoteOff { note: 64, velocity: 0.5 }],
+            _ => Vec::new(),
+        }
+    });
+    assert_eq!(requests, vec![(0, 256), (256, 256), (512, 256), (768, 232)]);
+    assert_eq!(synth.now_samples(), 1000);
+    assert_eq!(synth.active_notes(), vec![60]);
+    assert!(out[..256].iter().any(|frame| *frame != [0.0; 2]));
+}
@@ -0,0 +1,88 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::flac::FlacWriter;
+use crate::vorbis::{VorbisWriter, DEFAULT_QUALITY, MAX_QUALITY};
+use crate::wav::WavWriter;
+
+pub const DEFAULT_FLAC_BITS: u32 = 24;
+
+// 書き出すファイルの形式（拡張子で選ぶ）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Wav,               // 32bit float
+    Flac { bits: u32 }, // 16 / 24 ビット整数
+    Vorbis { quality: u32 }, // 0〜10
+}
+
+impl ExportFormat {
+    // bits は FLAC のビット深度（省略時は 24）か OGG の品質（省略時は 5）。WAV は常に 32bit float
+    pub fn from_path(path: impl AsRef<Path>, bits: Option<u32>) -> Result<Self, String> {
+        let extension = path.as_ref().extension().and_then(|extension| extension.to_str()).unwrap_or("").to_ascii_lowercase();
+        let format = match extension.as_str() {
+            "wav" => Self::Wav,
+            "flac" => Self::Flac { bits: bits.unwrap_or(DEFAULT_FLAC_BITS) },
+            "ogg" | "oga" => Self::Vorbis { quality: bits.unwrap_or(DEFAULT_QUALITY) },
+            _ => return Err(format!("unknown audio file type: {} (use .wav, .flac or .ogg)", path.as_ref().display())),
+        };
+        match (format, bits) {
+            (Self::Flac { bits }, _) if bits != 16 && bits != 24 => Err(format!("unsupported FLAC bit depth: {} (use 16 or 24)", bits)),
+            (Self::Vorbis { quality }, _) if quality > MAX_QUALITY => Err(format!("unsupported OGG quality: {} (use 0 to {})", quality, MAX_QUALITY)),
+            (Self::Wav, Some(bits)) if bits != 32 => Err("WAV files are always written as 32-bit float".to_string()),
+            _ => Ok(format),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Wav => write!(f, "WAV 32-bit float"),
+            ExportFormat::Flac { bits } => write!(f, "FLAC {}-bit", bits),
+            ExportFormat::Vorbis { quality } => write!(f, "Ogg Vorbis quality {}", quality),
+        }
+    }
+}
+
+// 形式ごとの書き出し先。長さが決まっていない録音にも使う
+pub enum AudioWriter {
+    Wav(WavWriter),
+    Flac(FlacWriter),
+    Vorbis(Box<VorbisWriter>), // 符号帳と変換の表を持つので大きい
+}
+
+impl AudioWriter {
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32, format: ExportFormat) -> io::Result<Self> {
+        Ok(match format {
+            ExportFormat::Wav => Self::Wav(WavWriter::create(path, sample_rate)?),
+            ExportFormat::Flac { bits } => Self::Flac(FlacWriter::create(path, sample_rate, bits)?),
+            ExportFormat::Vorbis { quality } => Self::Vorbis(Box::new(VorbisWriter::create(path, sample_rate, quality)?)),
+        })
+    }
+    
+    pub fn write(&mut self, frames: &[[f32; 2]]) -> io::Result<()> {
+        match self {
+            Self::Wav(writer) => writer.write(frames),
+            Self::Flac(writer) => writer.write(frames),
+            Self::Vorbis(writer) => writer.write(frames),
+        }
+    }
+    
+    // 書き出したフレーム数を返す
+    pub fn finish(self) -> io::Result<u64> {
+        match self {
+            Self::Wav(writer) => writer.finish().map(u64::from),
+            Self::Flac(writer) => writer.finish(),
+            Self::Vorbis(writer) => writer.finish(),
+        }
+    }
+}
+
+// ステレオのフレーム列を format の形式で書き出す
+pub fn write_audio(path: impl AsRef<Path>, sample_rate: u32, frames: &[[f32; 2]], format: ExportFormat) -> io::Result<()> {
+    let mut writer = AudioWriter::create(path, sample_rate, format)?;
+    writer.write(frames)?;
+    writer.finish()?;
+    Ok(())
+}
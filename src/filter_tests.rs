@@ -0,0 +1,165 @@
+// フィルターの周波数特性のテスト
+use crate::synth::LowPassFilter;
+
+fn filter(sample_rate: f32, cutoff: f32, resonance: f32) -> LowPassFilter {
+    let mut filter = LowPassFilter::new(sample_rate);
+    filter.set_cutoff(cutoff);
+    filter.set_resonance(resonance);
+    filter
+}
+
+fn db(gain: f32) -> f32 {
+    20.0 * gain.log10()
+}
+
+#[test]
+fn passband_is_unity_and_cutoff_gain_matches_q() {
+    for sample_rate in [44100.0, 48000.0, 96000.0] {
+        for resonance in [0.0, 0.3, 1.0] {
+            let filter = filter(sample_rate, 1000.0, resonance);
+            let response = filter.magnitude_response(&[20.0, 1000.0]);
+            assert!(db(response[0]).abs() < 0.1, "passband {:.3} dB", db(response[0]));
+            // RBJ ローパスのカットオフでの振幅は Q に等しい
+            assert!((response[1] - filter.q()).abs() / filter.q() < 0.01, "gain at cutoff {} vs Q {}", response[1], filter.q());
+        }
+    }
+}
+
+#[test]
+fn stopband_falls_at_twelve_db_per_octave() {
+    let filter = filter(96000.0, 500.0, 0.0);
+    let response = filter.magnitude_response(&[4000.0, 8000.0]);
+    let slope = db(response[1]) - db(response[0]);
+    assert!((slope + 12.0).abs() < 0.5, "slope {:.2} dB/oct", slope);
+}
+
+#[test]
+fn response_matches_rendered_sine() {
+    let sample_rate = 48000.0;
+    let mut filter = filter(sample_rate, 2000.0, 0.2);
+    for frequency in [500.0, 2000.0, 6000.0] {
+        let expected = filter.magnitude_response(&[frequency])[0];
+        filter.reset();
+        let mut peak: f32 = 0.0;
+        for n in 0..48000 {
+            let input = (2.0 * std::f32::consts::PI * frequency * n as f32 / sample_rate).sin();
+            let output = filter.process(input);
+            // 過渡応答が落ち着いてから測る
+            if n > 24000 {
+                peak = peak.max(output.abs());
+            }
+        }
+        assert!((db(peak) - db(expected)).abs() < 0.1, "{} Hz: rendered {:.2} dB, computed {:.2} dB", frequency, db(peak), db(expected));
+    }
+}
+
+#[test]
+fn cutoff_is_clamped_below_nyquist() {
+    let filter = filter(22050.0, 20000.0, 0.0);
+    assert!(filter.cutoff() < 11025.0);
+    let response = filter.magnitude_response(&[100.0]);
+    assert!(response[0].is_finite() && db(response[0]).abs() < 0.1);
+}
+
+#[test]
+fn gain_compensation_offsets_resonance_and_drive() {
+    let sample_rate = 48000.0;
+    let mut filter = filter(sample_rate, 1000.0, 1.0);
+    filter.set_gain_compensation(true);
+    let response = filter.magnitude_response(&[1000.0])[0];
+    assert!((response - filter.q().sqrt()).abs() < 0.01, "compensated peak {}", response);
+    
+    // 基準レベルの正弦波はドライブを上げても同じ振幅に戻る
+    let mut filter = filter_with_drive(sample_rate, 1.0);
+    let mut peak: f32 = 0.0;
+    for n in 0..48000 {
+        let input = 0.5 * (2.0 * std::f32::consts::PI * 50.0 * n as f32 / sample_rate).sin();
+        let output = filter.process(input);
+        if n > 24000 {
+            peak = peak.max(output.abs());
+        }
+    }
+    assert!((db(peak) - db(0.5)).abs() < 0.5, "driven peak {:.3}", peak);
+}
+
+// ボイスの飽和は基準レベルを保ち、大きな入力でも上限を超えない
+#[test]
+fn voice_saturation_keeps_reference_level_and_bounds_peaks() {
+    use crate::synth::saturate;
+    
+    assert_eq!(saturate(3.0, 0.0), 3.0);
+    for amount in [0.1, 0.5, 1.0] {
+        assert!((saturate(0.5, amount) - 0.5).abs() < 1e-5);
+        let ceiling = saturate(1000.0, amount);
+        assert!(ceiling < 1.1 && saturate(-1000.0, amount) == -ceiling, "ceiling {}", ceiling);
+        assert!(saturate(0.25, amount) < saturate(0.5, amount));
+    }
+}
+
+fn filter_with_drive(sample_rate: f32, drive: f32) -> LowPassFilter {
+    let mut filter = filter(sample_rate, 8000.0, 0.0);
+    filter.set_drive(drive);
+    filter.set_gain_compensation(true);
+    filter
+}
+
+// カットオフは API 全体で正規化値 0.0-1.0 を使い、Hz への変換は一度だけ行う
+#[test]
+fn cutoff_mapping_is_normalized_everywhere() {
+    use crate::patch::PatchState;
+    use crate::synth::{cutoff_to_hz, hz_to_cutoff, Synthesizer, Voice};
+    
+    assert_eq!(cutoff_to_hz(0.5), 10000.0);
+    assert_eq!(cutoff_to_hz(1.0), 20000.0);
+    assert_eq!(hz_to_cutoff(5000.0), 0.25);
+    
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).build();
+    synth.set_cutoff(0.5);
+    assert_eq!(synth.patch().cutoff, 0.5);
+    assert_eq!(synth.cutoff_hz(), 10000.0);
+    assert_eq!(synth.patch_filter().cutoff(), 10000.0);
+    
+    synth.set_filter_cutoff(0.25);
+    assert_eq!(synth.patch().cutoff, 0.25);
+    
+    synth.set_cutoff_hz(2000.0);
+    assert!((synth.patch().cutoff - 0.1).abs() < 1e-6);
+    
+    // 範囲外の値は Hz に変換される前にクランプされる
+    synth.set_cutoff(20000.0);
+    assert_eq!(synth.patch().cutoff, 1.0);
+    
+    let patch = PatchState { cutoff: 0.1, ..PatchState::default() };
+    let voice = Voice::with_patch(48000.0, &patch);
+    assert!((voice.cutoff_hz() - 2000.0).abs() < 0.01);
+}
+
+// バイノーラル定位: 中央は左右同じで直流のゲインが 1、右に振ると左耳の高域が頭の陰で下がる
+#[test]
+fn binaural_panner_keeps_center_and_shadows_far_ear() {
+    use crate::binaural::{BinauralPanner, HrirSet};
+    use std::sync::Arc;
+    
+    let sample_rate = 48000.0;
+    let hrirs = Arc::new(HrirSet::spherical_head(sample_rate));
+    let mut panner = BinauralPanner::new(hrirs.clone());
+    let mut frame = [0.0; 2];
+    for _ in 0..256 {
+        frame = panner.process([1.0, 1.0]);
+    }
+    assert!((frame[0] - 1.0).abs() < 1e-3 && (frame[1] - 1.0).abs() < 1e-3, "center {:?}", frame);
+    
+    let mut panner = BinauralPanner::new(hrirs);
+    panner.set_pan(1.0);
+    let mut energy = [0.0; 2];
+    for index in 0..4800 {
+        let sample = (2.0 * std::f32::consts::PI * 4000.0 * index as f32 / sample_rate).sin();
+        let [left, right] = panner.process([sample, sample]);
+        if index >= 256 {
+            energy[0] += left * left;
+            energy[1] += right * right;
+        }
+    }
+    let difference = 10.0 * (energy[1] / energy[0]).log10();
+    assert!(difference > 6.0, "interaural level difference {} dB", difference);
+}
@@ -0,0 +1,279 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const CHANNELS: usize = 2;
+const BLOCK_SIZE: usize = 4096; // 1フレームあたりのサンプル数
+const STREAMINFO_OFFSET: u64 = 8; // "fLaC" とメタデータブロックのヘッダーの後
+const MAX_FIXED_ORDER: usize = 4;
+const MAX_RICE_PARAMETER: u32 = 30; // RICE2（5 ビットのパラメータ、31 はエスケープ）
+
+// 16 / 24 ビットの整数ステレオの FLAC を少しずつ書き出す（固定予測とライス符号だけを使う簡易なエンコーダー）。
+// finish で STREAMINFO の長さとフレームの大きさを書き直す
+pub struct FlacWriter {
+    writer: BufWriter<File>,
+    sample_rate: u32,
+    bits: u32,
+    pending: Vec<[i32; 2]>,
+    frames: u64,
+    frame_number: u32,
+    frame_sizes: Option<(u32, u32)>, // 最小・最大のフレームのバイト数
+}
+
+impl FlacWriter {
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32, bits: u32) -> io::Result<Self> {
+        if bits != 16 && bits != 24 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unsupported FLAC bit depth: {} (use 16 or 24)", bits)));
+        }
+        let mut writer = Self {
+            writer: BufWriter::new(File::create(path)?),
+            sample_rate,
+            bits,
+            pending: Vec::with_capacity(BLOCK_SIZE),
+            frames: 0,
+            frame_number: 0,
+            frame_sizes: None,
+        };
+        writer.writer.write_all(b"fLaC")?;
+        // 最後のメタデータブロック（STREAMINFO, 34 バイト）
+        writer.writer.write_all(&[0x80, 0, 0, 34])?;
+        writer.write_streaminfo()?;
+        Ok(writer)
+    }
+    
+    pub fn write(&mut self, frames: &[[f32; 2]]) -> io::Result<()> {
+        let scale = ((1 << (self.bits - 1)) - 1) as f32;
+        for frame in frames {
+            self.pending.push(frame.map(|sample| (sample.clamp(-1.0, 1.0) * scale).round() as i32));
+            if self.pending.len() == BLOCK_SIZE {
+                self.encode_pending()?;
+            }
+        }
+        Ok(())
+    }
+    
+    // 書き出したフレーム数を返す
+    pub fn finish(mut self) -> io::Result<u64> {
+        if !self.pending.is_empty() {
+            self.encode_pending()?;
+        }
+        self.writer.seek(SeekFrom::Start(STREAMINFO_OFFSET))?;
+        self.write_streaminfo()?;
+        self.writer.flush()?;
+        Ok(self.frames)
+    }
+    
+    fn write_streaminfo(&mut self) -> io::Result<()> {
+        let mut bits = BitWriter::default();
+        bits.put(BLOCK_SIZE as u64, 16); // 最小のブロックの長さ（最後のブロックを除く）
+        bits.put(BLOCK_SIZE as u64, 16);
+        let (min_frame, max_frame) = self.frame_sizes.unwrap_or((0, 0)); // 0 は不明
+        bits.put(min_frame as u64, 24);
+        bits.put(max_frame as u64, 24);
+        bits.put(self.sample_rate as u64, 20);
+        bits.put(CHANNELS as u64 - 1, 3);
+        bits.put(self.bits as u64 - 1, 5);
+        bits.put(self.frames, 36);
+        bits.put(0, 64); // MD5（0 は未計算）
+        bits.put(0, 64);
+        self.writer.write_all(&bits.finish())
+    }
+    
+    fn encode_pending(&mut self) -> io::Result<()> {
+        let frame = encode_frame(&self.pending, self.frame_number, self.bits);
+        self.writer.write_all(&frame)?;
+        let size = frame.len() as u32;
+        self.frame_sizes = Some(self.frame_sizes.map_or((size, size), |(min, max)| (min.min(size), max.max(size))));
+        self.frames += self.pending.len() as u64;
+        self.frame_number += 1;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+// 左右を独立に符号化した1フレーム
+fn encode_frame(samples: &[[i32; 2]], frame_number: u32, bits: u32) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    writer.put(0b1111_1111_1111_1000, 16); // 同期コードと固定長ブロック
+    writer.put(0b0111, 4); // ブロックの長さはヘッダーの末尾に 16 ビットで書く
+    writer.put(0b0000, 4); // サンプルレートは STREAMINFO のもの
+    writer.put(0b0001, 4); // 左右独立のステレオ
+    writer.put(if bits == 16 { 0b100 } else { 0b110 }, 3);
+    writer.put(0, 1);
+    writer.put_utf8(frame_number);
+    writer.put(samples.len() as u64 - 1, 16);
+    let crc = crc8(writer.bytes());
+    writer.put(crc as u64, 8);
+    
+    let mut channel = Vec::with_capacity(samples.len());
+    for index in 0..CHANNELS {
+        channel.clear();
+        channel.extend(samples.iter().map(|frame| frame[index] as i64));
+        encode_subframe(&mut writer, &channel, bits);
+    }
+    let mut bytes = writer.finish();
+    let crc = crc16(&bytes);
+    bytes.extend_from_slice(&crc.to_be_bytes());
+    bytes
+}
+
+// 定数・固定予測（次数 0〜4）・無圧縮のうち最も短いものを選ぶ
+fn encode_subframe(writer: &mut BitWriter, samples: &[i64], bits: u32) {
+    if samples.iter().all(|sample| *sample == samples[0]) {
+        writer.put(0b0000_0000, 8); // 定数
+        writer.put_signed(samples[0], bits);
+        return;
+    }
+    let verbatim_bits = samples.len() as u64 * bits as u64;
+    let best = (0..=MAX_FIXED_ORDER.min(samples.len() - 1))
+        .map(|order| {
+            let residual = fixed_residual(samples, order);
+            let parameter = best_rice_parameter(&residual);
+            let size = order as u64 * bits as u64 + 11 + rice_bits(&residual, parameter);
+            (size, order, residual, parameter)
+        })
+        .min_by_key(|(size, ..)| *size);
+    match best {
+        Some((size, order, residual, parameter)) if size < verbatim_bits => {
+            writer.put(0b0001_0000 | (order as u64) << 1, 8); // 固定予測と次数
+            for sample in &samples[..order] {
+                writer.put_signed(*sample, bits);
+            }
+            writer.put(0b01, 2); // RICE2
+            writer.put(0, 4); // 分割しない
+            writer.put(parameter as u64, 5);
+            for value in residual {
+                let folded = zigzag(value);
+                writer.put_unary(folded >> parameter);
+                writer.put(folded & ((1 << parameter) - 1), parameter);
+            }
+        }
+        _ => {
+            writer.put(0b0000_0010, 8); // 無圧縮
+            for sample in samples {
+                writer.put_signed(*sample, bits);
+            }
+        }
+    }
+}
+
+// 固定予測の残差（先頭の order 個は予測に使う）
+fn fixed_residual(samples: &[i64], order: usize) -> Vec<i64> {
+    (order..samples.len())
+        .map(|i| {
+            let s = |back: usize| samples[i - back];
+            let prediction = match order {
+                0 => 0,
+                1 => s(1),
+                2 => 2 * s(1) - s(2),
+                3 => 3 * s(1) - 3 * s(2) + s(3),
+                _ => 4 * s(1) - 6 * s(2) + 4 * s(3) - s(4),
+            };
+            s(0) - prediction
+        })
+        .collect()
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn rice_bits(residual: &[i64], parameter: u32) -> u64 {
+    residual.iter().map(|value| (zigzag(*value) >> parameter) + 1 + parameter as u64).sum()
+}
+
+// 平均の大きさから見積もり、前後の値と比べる
+fn best_rice_parameter(residual: &[i64]) -> u32 {
+    let mean = residual.iter().map(|value| zigzag(*value)).sum::<u64>() / residual.len().max(1) as u64;
+    let estimate = (64 - mean.leading_zeros()).min(MAX_RICE_PARAMETER);
+    (estimate.saturating_sub(1)..=(estimate + 1).min(MAX_RICE_PARAMETER))
+        .min_by_key(|parameter| rice_bits(residual, *parameter))
+        .unwrap_or(0)
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |mut crc, byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { crc << 1 ^ 0x07 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |mut crc, byte| {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { crc << 1 ^ 0x8005 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+// 上位ビットから詰めて書く
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    accumulator: u64,
+    count: u32, // accumulator に溜まっているビット数
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u64, bits: u32) {
+        for shift in (0..bits).rev() {
+            self.accumulator = self.accumulator << 1 | (value >> shift) & 1;
+            self.count += 1;
+            if self.count == 8 {
+                self.bytes.push(self.accumulator as u8);
+                self.accumulator = 0;
+                self.count = 0;
+            }
+        }
+    }
+    
+    fn put_signed(&mut self, value: i64, bits: u32) {
+        self.put(value as u64 & ((1 << bits) - 1), bits);
+    }
+    
+    // value 個の 0 の後に 1
+    fn put_unary(&mut self, value: u64) {
+        for _ in 0..value {
+            self.put(0, 1);
+        }
+        self.put(1, 1);
+    }
+    
+    // フレーム番号の UTF-8 風の可変長表現
+    fn put_utf8(&mut self, value: u32) {
+        if value < 0x80 {
+            self.put(value as u64, 8);
+            return;
+        }
+        let continuation = match value {
+            0..=0x7FF => 1,
+            0x800..=0xFFFF => 2,
+            0x1_0000..=0x1F_FFFF => 3,
+            0x20_0000..=0x3FF_FFFF => 4,
+            _ => 5,
+        };
+        let lead_marker = (0xFF00u32 >> (continuation + 1)) & 0xFF;
+        self.put((lead_marker | value >> (6 * continuation)) as u64, 8);
+        for index in (0..continuation).rev() {
+            self.put((0x80 | (value >> (6 * index)) & 0x3F) as u64, 8);
+        }
+    }
+    
+    // 書き終えたバイト（途中のビットは含まない）
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+    
+    // 最後のバイトの残りを 0 で埋める
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.put(0, 8 - self.count);
+        }
+        self.bytes
+    }
+}
@@ -0,0 +1,54 @@
+// FLAC の書き出しのテスト
+use crate::export::{write_audio, ExportFormat};
+
+fn test_frames(sample_rate: u32, length: usize) -> Vec<[f32; 2]> {
+    (0..length)
+        .map(|i| {
+            let time = i as f32 / sample_rate as f32;
+            let sample = (2.0 * std::f32::consts::PI * 220.0 * time).sin() * 0.5 + (2.0 * std::f32::consts::PI * 1760.0 * time).sin() * 0.1;
+            [sample, -sample * 0.7]
+        })
+        .collect()
+}
+
+// 書き出しの形式は拡張子で決まる
+#[test]
+fn export_format_follows_extension() {
+    assert_eq!(ExportFormat::from_path("mix.WAV", None), Ok(ExportFormat::Wav));
+    assert_eq!(ExportFormat::from_path("mix.flac", None), Ok(ExportFormat::Flac { bits: 24 }));
+    assert_eq!(ExportFormat::from_path("mix.flac", Some(16)), Ok(ExportFormat::Flac { bits: 16 }));
+    assert!(ExportFormat::from_path("mix.flac", Some(20)).is_err());
+    assert!(ExportFormat::from_path("mix.wav", Some(16)).is_err());
+    assert_eq!(ExportFormat::from_path("mix.ogg", None), Ok(ExportFormat::Vorbis { quality: 5 }));
+    assert_eq!(ExportFormat::from_path("mix.oga", Some(9)), Ok(ExportFormat::Vorbis { quality: 9 }));
+    assert!(ExportFormat::from_path("mix.ogg", Some(11)).is_err());
+    assert!(ExportFormat::from_path("mix", None).is_err());
+}
+
+// 復号すると量子化した整数のサンプルがそのまま戻り、STREAMINFO には長さとビット深度が書き戻される
+#[test]
+fn flac_decodes_back_to_the_quantized_samples() {
+    let sample_rate = 48000;
+    // ブロックの長さ（4096）で割り切れない長さにして、短い最後のフレームも確かめる
+    let frames = test_frames(sample_rate, 10_000);
+    for bits in [16, 24] {
+        let path = std::env::temp_dir().join(format!("synthesizer-flac-test-{}-{}.flac", std::process::id(), bits));
+        write_audio(&path, sample_rate, &frames, ExportFormat::Flac { bits }).unwrap();
+        let size = std::fs::metadata(&path).unwrap().len();
+        let mut reader = claxon::FlacReader::open(&path).unwrap();
+        let info = reader.streaminfo();
+        let decoded: Vec<i32> = reader.samples().map(Result::unwrap).collect();
+        std::fs::remove_file(&path).unwrap();
+        
+        assert_eq!(info.sample_rate, sample_rate);
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.bits_per_sample, bits);
+        assert_eq!(info.samples, Some(frames.len() as u64));
+        let scale = ((1 << (bits - 1)) - 1) as f32;
+        let expected: Vec<i32> = frames.iter().flatten().map(|sample| (sample * scale).round() as i32).collect();
+        assert_eq!(decoded.len(), expected.len());
+        let mismatch = decoded.iter().zip(&expected).position(|(a, b)| a != b);
+        assert_eq!(mismatch, None, "{}-bit sample differs", bits);
+        assert!(size < frames.len() as u64 * 2 * bits as u64 / 8, "FLAC should be smaller than raw {}-bit PCM", bits);
+    }
+}
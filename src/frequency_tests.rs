@@ -0,0 +1,454 @@
+// オシレーター・エンジン・音律の周波数精度のテスト。
+// レンダリングした信号の基本周波数を自己相関で推定し、期待値とのずれをセントで比べる。
+use crate::engine::{table_sin, AdditiveEngine, FMEngine, Harmonic, Operator, Oscillator, SineOscillator, HARMONIC_COUNT};
+use crate::synth::Synthesizer;
+use crate::tuning::Tuning;
+
+const SAMPLE_RATES: [f32; 4] = [22050.0, 44100.0, 48000.0, 96000.0];
+const TOLERANCE_CENTS: f32 = 1.0;
+const MIN_FREQUENCY: f32 = 50.0;
+
+fn cents(measured: f32, expected: f32) -> f32 {
+    1200.0 * (measured / expected).log2()
+}
+
+// 平均を引いた正規化自己相関
+fn autocorrelation(samples: &[f32], lag: usize, window: usize) -> f32 {
+    let (mut product, mut energy_a, mut energy_b) = (0.0_f64, 0.0_f64, 0.0_f64);
+    for i in 0..window {
+        let (a, b) = (samples[i] as f64, samples[i + lag] as f64);
+        product += a * b;
+        energy_a += a * a;
+        energy_b += b * b;
+    }
+    (product / (energy_a * energy_b).sqrt().max(1e-12)) as f32
+}
+
+// lag 付近の自己相関の山を放物線補間した位置
+fn refine_peak(samples: &[f32], lag: usize, window: usize) -> f32 {
+    let peak = (lag.saturating_sub(2).max(1)..=lag + 2)
+        .max_by(|&a, &b| autocorrelation(samples, a, window).total_cmp(&autocorrelation(samples, b, window)))
+        .unwrap();
+    let (left, center, right) = (
+        autocorrelation(samples, peak - 1, window),
+        autocorrelation(samples, peak, window),
+        autocorrelation(samples, peak + 1, window),
+    );
+    let denominator = left - 2.0 * center + right;
+    let offset = if denominator.abs() > 1e-12 { 0.5 * (left - right) / denominator } else { 0.0 };
+    peak as f32 + offset
+}
+
+// 基本周期を自己相関で求め、その整数倍の遅れで精密化して周波数を推定する
+fn estimate_frequency(samples: &[f32], sample_rate: f32) -> f32 {
+    let max_lag = (sample_rate / MIN_FREQUENCY) as usize;
+    let window = samples.len() / 2;
+    assert!(window + max_lag * 2 < samples.len(), "not enough samples to analyse");
+    
+    let correlations: Vec<f32> = (0..=max_lag).map(|lag| autocorrelation(samples, lag, window)).collect();
+    let best = correlations[2..].iter().copied().fold(f32::MIN, f32::max);
+    // 倍の周期を選ばないように、最大値に近い最初の山を基本周期とする
+    let period = (2..max_lag)
+        .find(|&lag| {
+            correlations[lag] >= best * 0.95
+                && correlations[lag] >= correlations[lag - 1]
+                && correlations[lag] >= correlations[lag + 1]
+        })
+        .expect("no periodicity found");
+    
+    // 周期の整数倍で山を精密化していき、補間の誤差を周期数で割って小さくする
+    let mut estimate = refine_peak(samples, period, window);
+    let mut periods = 2;
+    while ((periods * 2) as f32 * estimate) as usize + 3 < samples.len() - window {
+        periods *= 2;
+        let lag = refine_peak(samples, (periods as f32 * estimate).round() as usize, window);
+        estimate = lag / periods as f32;
+    }
+    sample_rate / estimate
+}
+
+fn render(seconds: f32, sample_rate: f32, mut next: impl FnMut() -> f32) -> Vec<f32> {
+    (0..(seconds * sample_rate) as usize).map(|_| next()).collect()
+}
+
+fn assert_frequency(samples: &[f32], sample_rate: f32, expected: f32, label: &str) {
+    let measured = estimate_frequency(samples, sample_rate);
+    let error = cents(measured, expected);
+    assert!(
+        error.abs() < TOLERANCE_CENTS,
+        "{}: expected {:.3} Hz at {} Hz, measured {:.3} Hz ({:+.3} cents)",
+        label, expected, sample_rate, measured, error
+    );
+}
+
+fn rich_harmonics() -> Vec<Harmonic> {
+    (1..=HARMONIC_COUNT)
+        .map(|i| Harmonic {
+            frequency_multiplier: i as f32,
+            amplitude: if i <= 8 { 1.0 / i as f32 } else { 0.0 },
+            phase: 0.0,
+            enabled: i <= 8,
+        })
+        .collect()
+}
+
+#[test]
+fn sine_oscillator_frequency() {
+    for sample_rate in SAMPLE_RATES {
+        for frequency in [55.0, 220.0, 440.0, 1000.0, 3520.0] {
+            let mut oscillator = SineOscillator::new(sample_rate);
+            oscillator.set_frequency(frequency);
+            let samples = render(0.25, sample_rate, || oscillator.next_sample());
+            assert_frequency(&samples, sample_rate, frequency, "sine oscillator");
+        }
+    }
+}
+
+#[test]
+fn additive_engine_fundamental() {
+    for sample_rate in SAMPLE_RATES {
+        for frequency in [65.41, 261.63, 987.77] {
+            let mut engine = AdditiveEngine::new(sample_rate);
+            engine.set_harmonics(&rich_harmonics());
+            engine.set_base_frequency(frequency);
+            let samples = render(0.25, sample_rate, || engine.next_sample());
+            assert_frequency(&samples, sample_rate, frequency, "additive engine");
+        }
+    }
+}
+
+// 共有の正弦波テーブルは f32::sin とほぼ一致し、FM エンジンの出力も正確な計算と聞き分けられない程度しか違わない
+#[test]
+fn sine_table_matches_exact_sine() {
+    for i in 0..10_000 {
+        let phase = i as f32 / 10_000.0 * 3.0 - 1.0;
+        let expected = (phase * std::f32::consts::TAU).sin();
+        assert!((table_sin(phase) - expected).abs() < 5e-6, "phase {}", phase);
+    }
+    
+    let mut operators = crate::engine::default_operators();
+    operators[1] = Operator { frequency_ratio: 2.0, amplitude: 0.5, feedback: 0.3, enabled: true };
+    let mut engines = [FMEngine::new(48000.0), FMEngine::new(48000.0)];
+    engines[1].set_exact_sine(true);
+    for engine in &mut engines {
+        engine.set_operators(&operators);
+        engine.set_algorithm(5);
+        engine.set_base_frequency(261.63);
+    }
+    for _ in 0..4800 {
+        let (table, exact) = (engines[0].next_sample(), engines[1].next_sample());
+        assert!((table - exact).abs() < 1e-3, "{} vs {}", table, exact);
+    }
+}
+
+#[test]
+fn fm_engine_fundamental() {
+    let mut operators = crate::engine::default_operators();
+    operators[1] = Operator { frequency_ratio: 2.0, amplitude: 0.5, feedback: 0.3, enabled: true };
+    for sample_rate in SAMPLE_RATES {
+        for frequency in [65.41, 261.63, 987.77] {
+            let mut engine = FMEngine::new(sample_rate);
+            engine.set_operators(&operators);
+            engine.set_base_frequency(frequency);
+            let samples = render(0.25, sample_rate, || engine.next_sample());
+            assert_frequency(&samples, sample_rate, frequency, "FM engine");
+        }
+    }
+}
+
+// DX7 のボイス（アルゴリズム 1、OP2 が OP1 を 1:1 で変調）を読み込んでも基本周波数は変わらない
+#[test]
+fn dx7_voice_keeps_fundamental() {
+    let mut data = vec![0xF0, 0x43, 0x00, 0x00, 0x01, 0x1B];
+    let mut voice = [0u8; 155];
+    for operator in 0..6 {
+        let chunk = &mut voice[operator * 21..operator * 21 + 21];
+        chunk[..8].copy_from_slice(&[99, 50, 50, 60, 99, 90, 80, 0]);
+        chunk[18] = 1; // 比率 1
+        // データは OP6 から並ぶので、OP1 と OP2 は最後の2つ
+        chunk[16] = if operator >= 4 { 85 } else { 0 };
+    }
+    voice[134] = 0; // アルゴリズム 1
+    voice[135] = 3;
+    voice[145..155].copy_from_slice(b"TEST VOICE");
+    let checksum = voice.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)).wrapping_neg() & 0x7F;
+    data.extend_from_slice(&voice);
+    data.extend_from_slice(&[checksum, 0xF7]);
+    
+    let voices = crate::dx7::parse_sysex(&data).unwrap();
+    assert_eq!(voices[0].name, "TEST VOICE");
+    let patch = voices[0].to_patch(&Default::default());
+    assert_eq!(patch.fm_algorithm, 1);
+    assert_eq!(patch.operators.iter().filter(|operator| operator.enabled).count(), 2);
+    assert!(patch.envelope.sustain < 1.0 && patch.envelope.attack < 0.01);
+    
+    for sample_rate in SAMPLE_RATES {
+        let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+        synth.set_patch(patch.clone());
+        let samples = render_note(&mut synth, 57);
+        assert_frequency(&samples, sample_rate, 220.0, "DX7 voice");
+    }
+    data[20] ^= 1;
+    assert!(crate::dx7::parse_sysex(&data).is_err());
+}
+
+// ステレオ幅: 加算合成は左右とも同じ基本周波数、FM は左右に半分ずつデチューンされる
+// randomize で倍音・オペレーターを大きく揺らしても基本周波数は変わらず、対象外のパラメータは動かない
+#[test]
+fn randomized_patches_keep_fundamental() {
+    use crate::mutate::{mutate, MutationScope};
+    use crate::random::Rng;
+    
+    let base = crate::patch::PatchState::default();
+    for (seed, scope) in [(1, MutationScope::Harmonics), (2, MutationScope::Fm), (3, MutationScope::All)] {
+        let mut rng = Rng::new(seed);
+        let patch = mutate(&base, scope, 1.0, &mut rng);
+        assert_eq!(patch.volume, base.volume);
+        if scope != MutationScope::All {
+            assert_eq!(patch.cutoff, base.cutoff);
+        }
+        assert!(patch.harmonics[0].enabled && patch.operators[0].enabled);
+        
+        let mut synth = Synthesizer::builder().sample_rate(44100.0).build();
+        synth.set_patch(patch.clone());
+        synth.set_attack(0.001);
+        let samples = render_note(&mut synth, 57);
+        assert_frequency(&samples, 44100.0, 220.0, "randomized patch");
+        assert_eq!(mutate(&base, scope, 1.0, &mut Rng::new(seed)).to_delta("init"), patch.to_delta("init"));
+    }
+}
+
+// 発音ごとのランダム値はシードで決まり、次の発音で引き直される
+#[test]
+fn per_note_random_offsets_pitch() {
+    use crate::modulation::{ModDestination, ModRouting, ModSource};
+    use crate::random::Rng;
+    
+    let sample_rate = 44100.0;
+    let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+    synth.add_mod_routing(ModRouting::new(ModSource::Random, ModDestination::Pitch, 1.0));
+    synth.set_note_random_seed(7);
+    let mut rng = Rng::new(7);
+    for _ in 0..2 {
+        let semitones = rng.bipolar();
+        let samples = render_note(&mut synth, 57);
+        assert_frequency(&samples, sample_rate, 220.0 * 2.0_f32.powf(semitones / 12.0), "per-note random pitch");
+        synth.all_notes_off();
+    }
+}
+
+// 鍵盤の範囲を指定したルーティングは範囲外のノートに掛からず、条件はパッチ差分に保存される
+#[test]
+fn routing_zone_limits_modulation_to_keys() {
+    use crate::modulation::{ModDestination, ModRouting, ModSource, RoutingZone};
+    use crate::patch::PatchState;
+    
+    let sample_rate = 44100.0;
+    let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+    let zone = RoutingZone::parse(&["keys", "60-127"]).unwrap();
+    synth.add_mod_routing(ModRouting { zone, ..ModRouting::new(ModSource::KeyTrack, ModDestination::Pitch, 12.0) });
+    assert_frequency(&render_note(&mut synth, 57), sample_rate, 220.0, "note below the zone");
+    // 前のノートのリリースが混ざらないように、リリースを待たずに止める
+    synth.panic();
+    // キートラックは C4 から 5 オクターブで 1.0 なので、A4 では 9/60 × 12 半音上がる
+    let expected = 440.0 * 2.0_f32.powf(9.0 / 60.0);
+    assert_frequency(&render_note(&mut synth, 69), sample_rate, expected, "note inside the zone");
+    
+    let delta = synth.patch().to_delta("init").unwrap();
+    assert!(delta.contains("route key pitch 12 keys 60-127"));
+    let (loaded, warnings) = PatchState::from_delta(&delta).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(loaded.mod_routings, synth.patch().mod_routings);
+}
+
+// MIDI ファイルは別のトラックのテンポの変更に従って時刻を決め、その時刻に発音して書き出される
+#[test]
+fn midi_file_renders_at_tempo_map_times() {
+    use crate::midi::MidiMessage;
+    
+    let data = [
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 1, 0, 2, 0, 96,
+        // テンポトラック: 60 BPM（四分音符 = 1 秒）
+        b'M', b'T', b'r', b'k', 0, 0, 0, 11, 0x00, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40, 0x00, 0xFF, 0x2F, 0x00,
+        // 八分音符後に A3 を弾き、ランニングステータスのベロシティ 0 で四分音符後に離す
+        b'M', b'T', b'r', b'k', 0, 0, 0, 11, 0x30, 0x90, 57, 100, 0x60, 57, 0, 0x00, 0xFF, 0x2F, 0x00,
+    ];
+    let file = crate::smf::parse(&data).unwrap();
+    let times: Vec<f32> = file.events.iter().map(|event| event.seconds).collect();
+    assert_eq!(times, vec![0.5, 1.5]);
+    assert_eq!(file.events[1].message, MidiMessage::NoteOn { channel: 0, note: 57, velocity: 0 });
+    
+    let sample_rate = 44100.0;
+    let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+    let frames = crate::render::render_midi(&mut synth, &file);
+    assert!(frames.len() as f32 > 1.5 * sample_rate);
+    let at = |seconds: f32| (seconds * sample_rate) as usize;
+    assert!(frames[..at(0.5)].iter().all(|frame| *frame == [0.0, 0.0]));
+    let left: Vec<f32> = frames[at(0.6)..at(1.4)].iter().map(|frame| frame[0]).collect();
+    assert_frequency(&left, sample_rate, 220.0, "note from the MIDI file");
+}
+
+#[test]
+fn width_keeps_fundamental_per_channel() {
+    let (sample_rate, frequency) = (48000.0, 261.63);
+    let split = |frames: &[[f32; 2]]| -> [Vec<f32>; 2] {
+        [frames.iter().map(|frame| frame[0]).collect(), frames.iter().map(|frame| frame[1]).collect()]
+    };
+    let length = (0.25 * sample_rate) as usize;
+    
+    let mut additive = AdditiveEngine::new(sample_rate);
+    additive.set_harmonics(&rich_harmonics());
+    additive.set_width(1.0);
+    additive.set_base_frequency(frequency);
+    let frames: Vec<[f32; 2]> = (0..length).map(|_| additive.next_frame()).collect();
+    let [left, right] = split(&frames);
+    assert!(left.iter().zip(&right).any(|(l, r)| (l - r).abs() > 1e-3), "additive channels are identical");
+    assert_frequency(&left, sample_rate, frequency, "widened additive left");
+    assert_frequency(&right, sample_rate, frequency, "widened additive right");
+    
+    let mut fm = FMEngine::new(sample_rate);
+    fm.set_width(1.0);
+    fm.set_base_frequency(frequency);
+    let frames: Vec<[f32; 2]> = (0..length).map(|_| fm.next_frame()).collect();
+    let [left, right] = split(&frames);
+    let detune = 2.0_f32.powf(4.0 / 1200.0);
+    assert_frequency(&left, sample_rate, frequency / detune, "widened FM left");
+    assert_frequency(&right, sample_rate, frequency * detune, "widened FM right");
+}
+
+// エンベロープのアタックを避けるため、先頭を捨ててから測る
+fn render_note(synth: &mut Synthesizer, note: u8) -> Vec<f32> {
+    let sample_rate = synth.sample_rate();
+    synth.note_on(note, 0.8);
+    render(0.1, sample_rate, || synth.next_sample());
+    render(0.25, sample_rate, || synth.next_sample())
+}
+
+#[test]
+fn synthesizer_equal_temperament() {
+    for sample_rate in SAMPLE_RATES {
+        for note in [36, 57, 60, 69, 81] {
+            let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+            let samples = render_note(&mut synth, note);
+            let expected = 440.0 * 2.0_f32.powf((note as f32 - 69.0) / 12.0);
+            assert_frequency(&samples, sample_rate, expected, &format!("note {}", note));
+        }
+    }
+}
+
+// 出力機器のレートに切り替えると、ピッチと予約済みイベントの時刻（秒）が保たれる
+#[test]
+fn sample_rate_follows_the_output_device() {
+    let mut synth = Synthesizer::new();
+    synth.note_on(60, 0.8);
+    synth.schedule(0.5, crate::event::Event::NoteOn { note: 69, velocity: 0.8 });
+    synth.set_sample_rate(48000.0);
+    assert_eq!(synth.sample_rate(), 48000.0);
+    assert_eq!(synth.voice_count(), 0);
+    
+    render(0.49, 48000.0, || synth.next_sample());
+    assert_eq!(synth.voice_count(), 0);
+    render(0.02, 48000.0, || synth.next_sample());
+    assert_eq!(synth.voice_count(), 1);
+    synth.note_off(69);
+    
+    let samples = render_note(&mut synth, 57);
+    assert_frequency(&samples, 48000.0, 220.0, "after switching to 48 kHz");
+}
+
+#[test]
+fn tuning_edo_and_offsets() {
+    let edo = Tuning::edo(19);
+    for (steps, note) in [(0, 69), (1, 70), (7, 76), (19, 88), (-19, 50)] {
+        let expected = 440.0 * 2.0_f32.powf(steps as f32 / 19.0);
+        let error = cents(edo.frequency(note), expected);
+        assert!(error.abs() < 0.01, "19-EDO note {}: {:+.4} cents", note, error);
+    }
+    
+    let mut offset = Tuning::default();
+    offset.set_offset(60, -13.7);
+    let expected = 440.0 * 2.0_f32.powf(-9.0 / 12.0) * 2.0_f32.powf(-13.7 / 1200.0);
+    assert!(cents(offset.frequency(60), expected).abs() < 0.01);
+}
+
+#[test]
+fn synthesizer_follows_runtime_tuning() {
+    for sample_rate in [44100.0, 48000.0] {
+        let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+        synth.set_tuning(Tuning::edo(31));
+        let samples = render_note(&mut synth, 74);
+        let expected = 440.0 * 2.0_f32.powf(5.0 / 31.0);
+        assert_frequency(&samples, sample_rate, expected, "31-EDO note 74");
+    }
+}
+
+// ピッチベンドは鳴っているボイスにも後から鳴らすボイスにもベンドレンジ分だけ掛かる
+#[test]
+fn pitch_bend_follows_bend_range() {
+    let sample_rate = 48000.0;
+    let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+    synth.note_on(69, 0.8);
+    synth.set_pitch_bend(1.0);
+    render(0.1, sample_rate, || synth.next_sample());
+    let samples = render(0.25, sample_rate, || synth.next_sample());
+    assert_frequency(&samples, sample_rate, 440.0 * 2.0_f32.powf(2.0 / 12.0), "bend +1 at default range");
+    
+    let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+    synth.set_bend_range(12.0);
+    synth.set_pitch_bend(-0.5);
+    let samples = render_note(&mut synth, 69);
+    assert_frequency(&samples, sample_rate, 440.0 * 2.0_f32.powf(-6.0 / 12.0), "bend -0.5 at 12 semitones");
+}
+
+// パラメータのランプは指定したサンプル数でちょうど終点に届き、指数カーブは中点で幾何平均になる
+#[test]
+fn parameter_ramps_are_sample_accurate() {
+    use crate::automation::RampCurve;
+    
+    let sample_rate = 48000.0;
+    let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+    synth.automate("cutoff", Some(0.2), 0.8, 0.0, 0.01, RampCurve::Exponential).unwrap();
+    // 241 フレーム目はランプの開始から 240 サンプル後（0.01 秒の半分）
+    for _ in 0..241 {
+        synth.next_frame();
+    }
+    assert!((synth.patch().cutoff - 0.4).abs() < 1e-4, "midpoint {}", synth.patch().cutoff);
+    for _ in 0..240 {
+        synth.next_frame();
+    }
+    assert_eq!(synth.patch().cutoff, 0.8);
+    assert!(synth.parameter_ramps().is_empty());
+    
+    assert!(synth.automate("stack.density", None, 3.0, 0.0, 1.0, RampCurve::Linear).is_err());
+}
+
+// ブルームは 64^値 - 1 番目の倍音まで開き、値 0 では基音だけになる
+#[test]
+fn bloom_opens_partials_from_the_fundamental() {
+    let sample_rate = 48000.0;
+    let flat: Vec<Harmonic> = (1..=HARMONIC_COUNT)
+        .map(|i| Harmonic { frequency_multiplier: i as f32, amplitude: 1.0, phase: 0.0, enabled: true })
+        .collect();
+    let energy = |bloom: Option<f32>| {
+        let mut engine = AdditiveEngine::new(sample_rate);
+        engine.set_harmonics(&flat);
+        engine.set_base_frequency(100.0);
+        engine.set_bloom(bloom);
+        render(0.5, sample_rate, || engine.next_sample()).iter().map(|s| s * s).sum::<f32>()
+    };
+    let full = energy(None);
+    for (bloom, partials) in [(0.0, 1.0), (0.5, 8.0), (1.0, 64.0)] {
+        let ratio = energy(Some(bloom)) / full;
+        let expected = partials / HARMONIC_COUNT as f32;
+        assert!((ratio / expected - 1.0).abs() < 0.05, "bloom {}: energy ratio {} (expected {})", bloom, ratio, expected);
+    }
+    
+    let samples = {
+        let mut engine = AdditiveEngine::new(sample_rate);
+        engine.set_harmonics(&flat);
+        engine.set_base_frequency(100.0);
+        engine.set_bloom(Some(0.0));
+        render(0.25, sample_rate, || engine.next_sample())
+    };
+    assert_frequency(&samples, sample_rate, 100.0, "bloom 0");
+}
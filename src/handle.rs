@@ -0,0 +1,313 @@
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::command::{Command, CommandSender};
+use crate::params::{self, ParamName, Params};
+use crate::patch::PatchState;
+use crate::ring::{self, Consumer, Producer};
+use crate::synth::{Synthesizer, DIRECT_PARAMETERS, MORPH_PARAMETER};
+use crate::telemetry::Telemetry;
+
+const BORROW_POLL: Duration = Duration::from_micros(500); // コールバックが貸してくれるのを待つ間隔
+const BORROW_TIMEOUT: Duration = Duration::from_secs(1); // これだけ待っても貸されなければコールバックが止まっている
+pub const BORROW_TIMED_OUT: &str = "the audio callback did not lend the synthesizer";
+const DETACH_POLL: Duration = Duration::from_millis(1);
+const DETACH_TRIES: u32 = 2000; // 閉じたコールバックが Synthesizer を手放すまで待つ回数
+
+// Synthesizer をただ1つの持ち主のまま、制御側のスレッドとオーディオのコールバックの間で受け渡す。
+// 出力中はコールバック（AudioSide）が持ち、制御側が使うときはブロックの合間にリングで貸してもらう。
+// コールバックはロックを取らず、貸している間に来たコールバックは無音を出す（AudioStats::silent_callbacks）。
+// 出力していない間は手元に置き、borrow はすぐに返る。
+// ノート・パラメータ・テンポなどの演奏中の操作は借りずに send / set_parameter でコマンドとして送る
+#[derive(Clone)]
+pub struct SynthHandle {
+    shared: Arc<HandleShared>,
+}
+
+struct HandleShared {
+    state: Mutex<HandleState>, // 制御側のスレッドどうしだけが取る（コールバックは触らない）
+    waiting: Arc<AtomicUsize>, // 貸してもらうのを待っている制御側のスレッドの数
+    telemetry: Arc<Telemetry>,
+    params: Arc<Params>,
+    commands: Mutex<CommandSender>, // 制御側のスレッドが共有する送り口
+}
+
+struct HandleState {
+    home: Option<Box<Synthesizer>>, // コールバックに渡していないときの置き場所
+    link: Option<Link>,
+}
+
+// コールバックに渡している間の制御側の端
+struct Link {
+    lent: Consumer<Handoff>,              // コールバック → 制御側
+    returned: Producer<Box<Synthesizer>>, // 制御側 → コールバック
+}
+
+// コールバックから制御側へ渡すもの
+enum Handoff {
+    Synth(Box<Synthesizer>),
+    // コールバックが閉じた。制御側から返した Synthesizer がまだ届いていなければ、このキューに残っている
+    Closed(Consumer<Box<Synthesizer>>),
+}
+
+impl HandleState {
+    // コールバックから届いたものを受け取り、閉じていればつながりを外す
+    fn collect(&mut self) {
+        let Some(link) = &mut self.link else { return };
+        let mut closed = false;
+        while let Some(handoff) = link.lent.pop() {
+            match handoff {
+                Handoff::Synth(synth) => self.home = Some(synth),
+                Handoff::Closed(mut returned) => {
+                    if let Some(synth) = returned.pop() {
+                        self.home = Some(synth);
+                    }
+                    closed = true;
+                }
+            }
+        }
+        if closed {
+            self.link = None;
+        }
+    }
+    
+    // 出力中なのに手元に残っている Synthesizer を、借りるのを待っているスレッドが無ければコールバックへ返す
+    // （待つのをやめた直後にコールバックが貸してきたとき）
+    fn send_back(&mut self, waiting: &AtomicUsize) {
+        if waiting.load(Ordering::Acquire) > 0 {
+            return;
+        }
+        if let (Some(link), Some(synth)) = (&mut self.link, self.home.take()) {
+            self.home = link.returned.push(synth).err();
+        }
+    }
+}
+
+impl SynthHandle {
+    pub fn new(mut synth: Synthesizer) -> Self {
+        let (telemetry, params, commands) = (synth.telemetry(), synth.params(), synth.command_sender());
+        synth.publish_telemetry();
+        Self {
+            shared: Arc::new(HandleShared {
+                state: Mutex::new(HandleState { home: Some(Box::new(synth)), link: None }),
+                waiting: Arc::new(AtomicUsize::new(0)),
+                telemetry,
+                params,
+                commands: Mutex::new(commands),
+            }),
+        }
+    }
+    
+    // Synthesizer を借りる。出力中なら次のブロックを生成し終えるまで待ち、BORROW_TIMEOUT 待っても
+    // 貸されなければ None を返す。借りている間のコールバックは無音を出すので、演奏中の操作は send で送り、
+    // 借りるのはシーケンサーの編集やファイルの読み書きのように Synthesizer 全体を触る操作だけにする
+    pub fn borrow(&self) -> Option<SynthGuard<'_>> {
+        let deadline = Instant::now() + BORROW_TIMEOUT;
+        let mut waiting = false;
+        loop {
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                state.collect();
+                if let Some(synth) = state.home.take() {
+                    if waiting {
+                        self.shared.waiting.fetch_sub(1, Ordering::AcqRel);
+                    }
+                    self.shared.telemetry.edits.fetch_add(1, Ordering::Relaxed);
+                    return Some(SynthGuard { handle: self, synth: Some(synth) });
+                }
+                if Instant::now() >= deadline {
+                    if waiting {
+                        self.shared.waiting.fetch_sub(1, Ordering::AcqRel);
+                    }
+                    state.collect();
+                    state.send_back(&self.shared.waiting);
+                    return None;
+                }
+                if !waiting {
+                    waiting = true;
+                    self.shared.waiting.fetch_add(1, Ordering::AcqRel);
+                }
+            }
+            std::thread::sleep(BORROW_POLL);
+        }
+    }
+    
+    // borrow と同じだが、待ちきれなければ io::ErrorKind::TimedOut を返す（io::Result を返す側で使う）
+    pub fn borrow_or_timeout(&self) -> io::Result<SynthGuard<'_>> {
+        self.borrow().ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, BORROW_TIMED_OUT))
+    }
+    
+    // 借りずに操作を送る。出力中なら次のコントロールレートの境界で反映し、出力していなければその場で反映する。
+    // キューが満杯なら false
+    pub fn send(&self, command: Command) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        state.collect();
+        if state.link.is_none() {
+            if let Some(synth) = state.home.as_deref_mut() {
+                // 先に送ってまだ反映していない分から順に
+                synth.process_commands();
+                synth.apply_command(command);
+                synth.publish_telemetry();
+                return true;
+            }
+        }
+        state.send_back(&self.shared.waiting);
+        self.shared.commands.lock().unwrap().send(command)
+    }
+    
+    // params の名前でパラメータを送る。名前と値はここで確かめ、オーディオスレッドでは確かめ直さない
+    pub fn set_parameter(&self, name: &str, value: f32) -> Result<(), String> {
+        if !value.is_finite() {
+            return Err(format!("invalid value for {}: {}", name, value));
+        }
+        let command = match DIRECT_PARAMETERS.iter().position(|direct| *direct == name) {
+            Some(index) => Command::Parameter { index, value },
+            None => {
+                let known = name == MORPH_PARAMETER || params::get(&PatchState::default(), name).is_some();
+                match ParamName::new(name).filter(|_| known) {
+                    Some(name) => Command::NamedParameter { name, value },
+                    None => return Err(format!("unknown parameter: {}", name)),
+                }
+            }
+        };
+        match self.send(command) {
+            true => Ok(()),
+            false => Err("command queue is full".to_string()),
+        }
+    }
+    
+    // 読み込み・解釈を済ませた音色に差し替える。ボイスの分の確保を制御側で済ませる間だけ借り、
+    // 出力中は次のコントロールレートの境界で入れ替える（Synthesizer::queue_patch）
+    pub fn queue_patch(&self, patch: PatchState) -> bool {
+        let Some(mut synth) = self.borrow() else { return false };
+        match self.is_attached() {
+            true => synth.queue_patch(patch),
+            false => synth.set_patch(patch),
+        }
+        true
+    }
+    
+    pub fn is_attached(&self) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        state.collect();
+        state.link.is_some()
+    }
+    
+    // 借りずに読める状態と、借りずに書ける共有パラメータ
+    pub fn telemetry(&self) -> Arc<Telemetry> {
+        Arc::clone(&self.shared.telemetry)
+    }
+    
+    pub fn params(&self) -> Arc<Params> {
+        Arc::clone(&self.shared.params)
+    }
+    
+    // オーディオのコールバックに渡す端を作る。前のコールバックが残っていれば先に取り戻す
+    pub(crate) fn attach(&self) -> AudioSide {
+        self.detach();
+        let (lend, lent) = ring::channel(2);
+        let (returned, receive) = ring::channel(1);
+        let mut state = self.shared.state.lock().unwrap();
+        let synth = state.home.take();
+        state.link = Some(Link { lent, returned });
+        AudioSide { synth, lend, receive: Some(receive), waiting: Arc::clone(&self.shared.waiting) }
+    }
+    
+    // ストリームを閉じたあとに呼び、コールバックから Synthesizer を取り戻す
+    pub(crate) fn detach(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        for _ in 0..DETACH_TRIES {
+            state.collect();
+            if state.link.is_none() {
+                return;
+            }
+            std::thread::sleep(DETACH_POLL);
+        }
+    }
+}
+
+// 借りている Synthesizer。手放すとコールバックへ返す（出力していなければ手元に戻す）
+pub struct SynthGuard<'a> {
+    handle: &'a SynthHandle,
+    synth: Option<Box<Synthesizer>>,
+}
+
+impl Deref for SynthGuard<'_> {
+    type Target = Synthesizer;
+    
+    fn deref(&self) -> &Synthesizer {
+        self.synth.as_deref().unwrap()
+    }
+}
+
+impl DerefMut for SynthGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Synthesizer {
+        self.synth.as_deref_mut().unwrap()
+    }
+}
+
+impl Drop for SynthGuard<'_> {
+    fn drop(&mut self) {
+        let Some(synth) = self.synth.take() else { return };
+        // 出力していない間もステータス行などに変更が見えるように書いておく
+        synth.publish_telemetry();
+        let mut state = self.handle.shared.state.lock().unwrap();
+        state.collect();
+        let synth = match &mut state.link {
+            Some(link) => link.returned.push(synth).err(),
+            None => Some(synth),
+        };
+        if let Some(mut synth) = synth {
+            // 出力していなければ、借りている間に送られた操作をここで反映する
+            if state.link.is_none() {
+                synth.process_commands();
+                synth.publish_telemetry();
+            }
+            state.home = Some(synth);
+        }
+    }
+}
+
+// オーディオのコールバックが持つ端。どの操作もロックを取らず、メモリを確保しない
+pub(crate) struct AudioSide {
+    synth: Option<Box<Synthesizer>>,
+    lend: Producer<Handoff>,
+    receive: Option<Consumer<Box<Synthesizer>>>,
+    waiting: Arc<AtomicUsize>,
+}
+
+impl AudioSide {
+    // コールバックの先頭で呼ぶ。制御側から返ってきていれば受け取る（貸している間は None）
+    pub(crate) fn synth(&mut self) -> Option<&mut Synthesizer> {
+        if self.synth.is_none() {
+            self.synth = self.receive.as_mut().and_then(Consumer::pop);
+        }
+        self.synth.as_deref_mut()
+    }
+    
+    // ブロックを生成し終えたら呼ぶ。制御側が待っていれば次のコールバックまでの間に貸す
+    pub(crate) fn lend_if_wanted(&mut self) {
+        if self.waiting.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        if let Some(synth) = self.synth.take() {
+            if let Err(Handoff::Synth(synth)) = self.lend.push(Handoff::Synth(synth)) {
+                self.synth = Some(synth);
+            }
+        }
+    }
+}
+
+impl Drop for AudioSide {
+    fn drop(&mut self) {
+        if let Some(synth) = self.synth.take() {
+            let _ = self.lend.push(Handoff::Synth(synth));
+        }
+        if let Some(receive) = self.receive.take() {
+            let _ = self.lend.push(Handoff::Closed(receive));
+        }
+    }
+}
@@ -0,0 +1,204 @@
+// オーディオのコールバックの経路のテスト（cpal を使わずに CallbackState::render を別スレッドから呼ぶ）
+use crate::alloc_audit::audit;
+use crate::audio::{CallbackState, SharedStats};
+use crate::command::Command;
+use crate::event::Event;
+use crate::handle::SynthHandle;
+use crate::params;
+use crate::synth::Synthesizer;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const CALLBACK_FRAMES: usize = 256;
+
+// 条件が成り立つまで待つ（成り立たなければ false）
+fn wait_until(mut condition: impl FnMut() -> bool) -> bool {
+    for _ in 0..2000 {
+        if condition() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    false
+}
+
+// コールバックは Synthesizer を自分で持って生成し、制御側はブロックの合間に借りるかコマンドで送る。
+// 制御側が借りている間もコールバックは待たずに無音を出し、温まった後のコールバックはメモリを確保しない
+#[test]
+fn callback_renders_and_lends_without_waiting_on_the_control_side() {
+    let handle = SynthHandle::new(Synthesizer::builder().sample_rate(48000.0).build());
+    let telemetry = handle.telemetry();
+    let stats = Arc::new(SharedStats::default());
+    let mut state = CallbackState::new(48000.0, 2, Arc::clone(&stats));
+    let mut side = handle.attach();
+    
+    let stop = Arc::new(AtomicBool::new(false));
+    let callback = {
+        let stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            let mut data = vec![0.0f32; CALLBACK_FRAMES * 2];
+            let (mut peak, mut allocations) = (0.0f32, 0);
+            for count in 0.. {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let ((), allocated) = audit(|| state.render(&mut side, &mut data, None, None, |sample| sample));
+                if count >= 8 {
+                    allocations += allocated;
+                }
+                peak = data.iter().fold(peak, |peak, sample| peak.max(sample.abs()));
+                thread::sleep(Duration::from_millis(1));
+            }
+            (side, peak, allocations)
+        })
+    };
+    
+    handle.borrow().unwrap().note_on(60, 0.8);
+    let mut sender = handle.borrow().unwrap().command_sender();
+    assert!(sender.send(Command::Tempo(90.0)));
+    assert!(wait_until(|| telemetry.tempo() == 90.0 && telemetry.voice_count() == 1 && telemetry.output_meter()[0] > 0.0));
+    assert_eq!(telemetry.active_notes(), [60]);
+    
+    // 借りたまま待つ間もコールバックは進む（ロックを取っていれば止まる）
+    let guard = handle.borrow().unwrap();
+    let silent = stats.load().silent_callbacks;
+    assert!(wait_until(|| stats.load().silent_callbacks > silent + 2));
+    drop(guard);
+    let callbacks = stats.load().callbacks;
+    assert!(wait_until(|| stats.load().callbacks > callbacks + 2));
+    
+    stop.store(true, Ordering::Relaxed);
+    let (side, peak, allocations) = callback.join().unwrap();
+    assert!(peak > 0.01, "peak {}", peak);
+    assert_eq!(allocations, 0);
+    
+    // コールバックを閉じれば Synthesizer は制御側へ戻り、すぐに借りられる
+    drop(side);
+    handle.detach();
+    let synth = handle.borrow().unwrap();
+    assert_eq!(synth.tempo(), 90.0);
+    assert_eq!(synth.voice_count(), 1);
+}
+
+// 借りている間にコールバックが閉じても、あとで返した Synthesizer は失われずに制御側へ戻る
+#[test]
+fn synth_returned_after_the_callback_closed_comes_home() {
+    let handle = SynthHandle::new(Synthesizer::builder().sample_rate(48000.0).build());
+    let mut state = CallbackState::new(48000.0, 2, Arc::new(SharedStats::default()));
+    let mut side = handle.attach();
+    let mut data = vec![0.0f32; CALLBACK_FRAMES * 2];
+    
+    let borrower = {
+        let handle = handle.clone();
+        thread::spawn(move || {
+            let mut synth = handle.borrow().unwrap();
+            synth.set_tempo(75.0);
+            thread::sleep(Duration::from_millis(20));
+        })
+    };
+    // 貸し出すまでコールバックを回し、借りられている間に閉じる
+    while side.synth().is_some() {
+        state.render(&mut side, &mut data, None, None, |sample| sample);
+        thread::sleep(Duration::from_millis(1));
+    }
+    drop(side);
+    borrower.join().unwrap();
+    handle.detach();
+    assert_eq!(handle.borrow().unwrap().tempo(), 75.0);
+}
+
+// 制御側のスレッドがノート・パラメータ・テンポを送り続けても、借りないのでコールバックは無音を出さない
+#[test]
+fn control_thread_edits_do_not_silence_the_output() {
+    let handle = SynthHandle::new(Synthesizer::builder().sample_rate(48000.0).build());
+    let telemetry = handle.telemetry();
+    let stats = Arc::new(SharedStats::default());
+    let mut state = CallbackState::new(48000.0, 2, Arc::clone(&stats));
+    let mut side = handle.attach();
+    
+    let stop = Arc::new(AtomicBool::new(false));
+    let callback = {
+        let stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            let mut data = vec![0.0f32; CALLBACK_FRAMES * 2];
+            let mut peak = 0.0f32;
+            while !stop.load(Ordering::Relaxed) {
+                state.render(&mut side, &mut data, None, None, |sample| sample);
+                peak = data.iter().fold(peak, |peak, sample| peak.max(sample.abs()));
+                thread::sleep(Duration::from_millis(1));
+            }
+            (side, peak)
+        })
+    };
+    
+    assert!(handle.send(Command::Event(Event::NoteOn { note: 60, velocity: 0.8 })));
+    for step in 0..200 {
+        let value = (step + 1) as f32 / 200.0;
+        handle.set_parameter("cutoff", 0.5 + value / 2.0).unwrap();
+        handle.set_parameter("harmonic.3.amp", value).unwrap();
+        assert!(handle.send(Command::NoteFor { note: 64 + (step % 12) as u8, velocity: 0.5, seconds: 0.005 }));
+        assert!(handle.send(Command::Tempo(100.0 + step as f32)));
+        thread::sleep(Duration::from_micros(200));
+    }
+    assert!(handle.set_parameter("no.such.parameter", 0.5).is_err());
+    assert!(handle.set_parameter("cutoff", f32::NAN).is_err());
+    assert!(wait_until(|| telemetry.tempo() == 299.0));
+    let callbacks = stats.load().callbacks;
+    assert!(wait_until(|| stats.load().callbacks > callbacks + 2));
+    assert_eq!(stats.load().silent_callbacks, 0);
+    
+    stop.store(true, Ordering::Relaxed);
+    let (side, peak) = callback.join().unwrap();
+    assert!(peak > 0.0);
+    drop(side);
+    handle.detach();
+    let synth = handle.borrow().unwrap();
+    assert_eq!(synth.patch().cutoff, 1.0);
+    assert_eq!(params::get(synth.patch(), "harmonic.3.amp"), Some(1.0));
+}
+
+// 出力していない間に送った操作はその場で反映するので、続けて借りればすぐ見える
+#[test]
+fn sent_commands_apply_at_once_while_the_stream_is_stopped() {
+    let handle = SynthHandle::new(Synthesizer::builder().sample_rate(48000.0).build());
+    handle.borrow().unwrap().store_scene(2);
+    assert!(handle.telemetry().is_scene_stored(2) && !handle.telemetry().is_scene_stored(3));
+    let edits = handle.telemetry().edits();
+    handle.set_parameter("pan", 0.5).unwrap();
+    handle.set_parameter("stack.detune", 20.0).unwrap();
+    assert!(handle.send(Command::Tempo(90.0)));
+    assert!(handle.send(Command::Event(Event::NoteOn { note: 60, velocity: 0.8 })));
+    assert_eq!(handle.telemetry().voice_count(), 1);
+    assert!(handle.telemetry().edits() > edits);
+    
+    let synth = handle.borrow().unwrap();
+    assert_eq!(synth.patch().pan, 0.5);
+    assert_eq!(synth.patch().stack_detune, 20.0);
+    assert_eq!(synth.tempo(), 90.0);
+    drop(synth);
+    
+    // 保存したときの音色へ戻る
+    assert!(handle.send(Command::Scene { slot: 2, fade: 0.0 }));
+    assert_eq!(handle.borrow().unwrap().patch().pan, 0.0);
+}
+
+// コールバックが止まっていれば借りるのをあきらめ、あとから動き出したコールバックは貸さずに鳴らし続ける
+#[test]
+fn borrow_gives_up_when_the_callback_stalls() {
+    let handle = SynthHandle::new(Synthesizer::builder().sample_rate(48000.0).build());
+    let stats = Arc::new(SharedStats::default());
+    let mut state = CallbackState::new(48000.0, 2, Arc::clone(&stats));
+    let mut side = handle.attach();
+    assert!(handle.borrow().is_none());
+    
+    let mut data = vec![0.0f32; CALLBACK_FRAMES * 2];
+    for _ in 0..4 {
+        state.render(&mut side, &mut data, None, None, |sample| sample);
+    }
+    assert_eq!(stats.load().silent_callbacks, 0);
+    drop(side);
+    handle.detach();
+    assert!(handle.borrow().is_some());
+}
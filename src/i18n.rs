@@ -0,0 +1,107 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// CLI の表示言語
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Japanese,
+}
+
+impl Language {
+    // SYNTH_LANG、なければロケール（LC_ALL, LC_MESSAGES, LANG）から決める。どちらでもなければ日本語。
+    pub fn detect() -> Self {
+        if let Some(language) = std::env::var("SYNTH_LANG").ok().and_then(|value| value.parse().ok()) {
+            return language;
+        }
+        ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|locale| locale.get(..2).and_then(|code| code.parse().ok()))
+            .unwrap_or(Language::Japanese)
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Language::English => write!(f, "en"),
+            Language::Japanese => write!(f, "ja"),
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "en" | "english" => Ok(Language::English),
+            "ja" | "japanese" => Ok(Language::Japanese),
+            _ => Err(format!("unknown language: {}", s)),
+        }
+    }
+}
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(Language::Japanese as u8);
+
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+pub fn language() -> Language {
+    if LANGUAGE.load(Ordering::Relaxed) == Language::English as u8 {
+        Language::English
+    } else {
+        Language::Japanese
+    }
+}
+
+// 言語ごとの文字列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Text {
+    pub en: &'static str,
+    pub ja: &'static str,
+}
+
+impl Text {
+    pub fn get(&self) -> &'static str {
+        match language() {
+            Language::English => self.en,
+            Language::Japanese => self.ja,
+        }
+    }
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.get())
+    }
+}
+
+pub const fn text(en: &'static str, ja: &'static str) -> Text {
+    Text { en, ja }
+}
+
+// 現在の言語の文字列
+pub fn tr(en: &'static str, ja: &'static str) -> &'static str {
+    text(en, ja).get()
+}
+
+// テンプレートの {} を順に args で置き換える（語順が言語で異なるため format! は使えない）
+pub fn fill(template: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(index) = rest.find("{}") {
+        output.push_str(&rest[..index]);
+        match args.next() {
+            Some(arg) => output.push_str(&arg.to_string()),
+            None => output.push_str("{}"),
+        }
+        rest = &rest[index + 2..];
+    }
+    output.push_str(rest);
+    output
+}
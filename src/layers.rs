@@ -0,0 +1,144 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::modulation::RoutingZone;
+
+// 1つの音色の中の音源のレイヤー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    Additive,
+    Fm,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 2] = [Layer::Additive, Layer::Fm];
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            Layer::Additive => "additive",
+            Layer::Fm => "fm",
+        }
+    }
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Layer {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "additive" | "add" => Ok(Layer::Additive),
+            "fm" => Ok(Layer::Fm),
+            _ => Err(format!("unknown layer: {} (use additive or fm)", s)),
+        }
+    }
+}
+
+// レイヤーが受け付ける MIDI チャンネル（None は全チャンネル、0-15）とノート・ベロシティの範囲。
+// ベロシティはベロシティカーブを通した後の値（0.0〜1.0）
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LayerFilter {
+    pub channel: Option<u8>,
+    pub zone: RoutingZone,
+}
+
+impl LayerFilter {
+    pub const ALL: LayerFilter = LayerFilter { channel: None, zone: RoutingZone::ALL };
+    
+    // チャンネルの無いノート（パソコンのキーボードやシーケンサー）はチャンネルの指定を無視する
+    pub fn accepts(&self, channel: Option<u8>, note: u8, velocity: f32) -> bool {
+        let channel_matches = match (self.channel, channel) {
+            (Some(wanted), Some(channel)) => wanted == channel,
+            _ => true,
+        };
+        channel_matches && self.zone.contains(note, velocity)
+    }
+    
+    // "ch <1-16|all>" と、ルーティングと同じ "keys <下>-<上>" / "vel <下>-<上>" の並び（どれも省略可）
+    pub fn parse(parts: &[&str]) -> Result<Self, String> {
+        let mut filter = Self::ALL;
+        let zone_parts = match parts {
+            ["ch", channel, rest @ ..] => {
+                filter.channel = match *channel {
+                    "all" | "omni" => None,
+                    channel => Some(
+                        channel
+                            .parse::<u8>()
+                            .ok()
+                            .filter(|channel| (1..=16).contains(channel))
+                            .ok_or_else(|| format!("invalid MIDI channel: {} (use 1-16 or all)", channel))?
+                            - 1,
+                    ),
+                };
+                rest
+            }
+            rest => rest,
+        };
+        filter.zone = RoutingZone::parse(zone_parts)?;
+        Ok(filter)
+    }
+}
+
+// 全域の条件は何も書かない（先頭に空白を付けて書く）
+impl fmt::Display for LayerFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(channel) = self.channel {
+            write!(f, " ch {}", channel + 1)?;
+        }
+        write!(f, "{}", self.zone)
+    }
+}
+
+// ボイスで鳴らすレイヤーの組（少なくとも一方は鳴らす）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayerMask {
+    pub additive: bool,
+    pub fm: bool,
+}
+
+impl LayerMask {
+    pub const BOTH: LayerMask = LayerMask { additive: true, fm: true };
+    
+    // 片方だけなら、ブレンドに関わらずそのエンジンだけを最大の音量で鳴らす（スプリット）
+    pub fn blend(self, blend: f32) -> f32 {
+        match (self.additive, self.fm) {
+            (true, false) => 0.0,
+            (false, true) => 1.0,
+            _ => blend,
+        }
+    }
+}
+
+impl Default for LayerMask {
+    fn default() -> Self {
+        Self::BOTH
+    }
+}
+
+// レイヤーごとの受信フィルター
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LayerFilters {
+    filters: [LayerFilter; 2],
+}
+
+impl LayerFilters {
+    pub fn get(&self, layer: Layer) -> LayerFilter {
+        self.filters[layer as usize]
+    }
+    
+    pub fn set(&mut self, layer: Layer, filter: LayerFilter) {
+        self.filters[layer as usize] = filter;
+    }
+    
+    // ノートを鳴らすレイヤー。どのレイヤーも受け付けなければ None
+    pub fn mask(&self, channel: Option<u8>, note: u8, velocity: f32) -> Option<LayerMask> {
+        let accepts = |layer: Layer| self.get(layer).accepts(channel, note, velocity);
+        let mask = LayerMask { additive: accepts(Layer::Additive), fm: accepts(Layer::Fm) };
+        (mask.additive || mask.fm).then_some(mask)
+    }
+}
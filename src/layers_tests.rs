@@ -0,0 +1,45 @@
+// レイヤーのキー・チャンネルの振り分けのテスト
+use crate::synth::Synthesizer;
+
+// レイヤーの受信フィルターで鍵盤とチャンネルを分け、片方だけに合うノートはそのエンジンだけで鳴る
+#[test]
+fn layer_filters_split_keys_and_channels() {
+    use crate::analysis::Tap;
+    use crate::layers::{Layer, LayerFilter};
+    use crate::midi::{dispatch, MidiMessage};
+    use crate::session::Session;
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    synth.set_analysis_taps(true);
+    synth.set_layer_filter(Layer::Additive, LayerFilter::parse(&["keys", "0-59"]).unwrap());
+    synth.set_layer_filter(Layer::Fm, LayerFilter::parse(&["ch", "2", "keys", "60-127"]).unwrap());
+    assert!(LayerFilter::parse(&["ch", "17"]).is_err());
+    
+    let energy = |synth: &mut Synthesizer, tap: Tap| {
+        for _ in 0..2205 {
+            synth.next_frame();
+        }
+        synth.analysis_taps().snapshot(tap).iter().map(|[left, right]| left * left + right * right).sum::<f32>()
+    };
+    // チャンネル1の上の鍵盤はどのレイヤーにも合わない
+    dispatch(&mut synth, "", &MidiMessage::NoteOn { channel: 0, note: 72, velocity: 100 });
+    assert!(synth.active_notes().is_empty());
+    dispatch(&mut synth, "", &MidiMessage::NoteOn { channel: 1, note: 72, velocity: 100 });
+    assert_eq!(synth.active_notes(), vec![72]);
+    assert!(energy(&mut synth, Tap::Fm) > 0.0);
+    assert_eq!(energy(&mut synth, Tap::Additive), 0.0);
+    synth.panic();
+    
+    // チャンネルの無いノート（キーボード・シーケンサー）は鍵盤の範囲だけで決まる
+    synth.note_on(48, 0.8);
+    assert!(energy(&mut synth, Tap::Additive) > 0.0);
+    assert_eq!(energy(&mut synth, Tap::Fm), 0.0);
+    
+    let text = Session::capture(&synth).to_text().unwrap();
+    assert!(text.contains("layer additive keys 0-59\nlayer fm ch 2 keys 60-127\n"));
+    let (session, warnings) = Session::from_text(&text).unwrap();
+    assert!(warnings.is_empty());
+    let mut restored = Synthesizer::builder().sample_rate(22050.0).build();
+    session.apply(&mut restored);
+    assert_eq!(restored.layer_filters(), synth.layer_filters());
+}
@@ -0,0 +1,177 @@
+// 加算合成 + FM シンセサイザーのライブラリ。
+// CLI（main.rs）も下で再エクスポートした公開APIだけを使うので、アプリケーションへの組み込みにも同じ型が使える。
+pub(crate) mod adapters;
+#[cfg(any(test, feature = "alloc-audit"))]
+pub(crate) mod alloc_audit;
+pub(crate) mod analysis;
+pub(crate) mod audio;
+pub(crate) mod automation;
+pub(crate) mod bank;
+pub(crate) mod binaural;
+pub(crate) mod builder;
+pub(crate) mod cc;
+pub(crate) mod command;
+pub(crate) mod demo;
+pub(crate) mod diagnostics;
+pub(crate) mod drone;
+pub(crate) mod duck;
+pub(crate) mod dx7;
+pub(crate) mod effects;
+pub(crate) mod engine;
+pub(crate) mod event;
+pub(crate) mod export;
+pub(crate) mod flac;
+pub(crate) mod handle;
+pub(crate) mod layers;
+pub(crate) mod link_listener;
+pub(crate) mod log;
+pub(crate) mod loudness;
+pub(crate) mod metronome;
+pub(crate) mod midi;
+pub(crate) mod mixer;
+pub(crate) mod modulation;
+pub(crate) mod mutate;
+pub(crate) mod ogg;
+pub(crate) mod parallel;
+pub(crate) mod paraphony;
+pub(crate) mod params;
+pub(crate) mod patch;
+pub(crate) mod phrase;
+pub(crate) mod program;
+pub(crate) mod quality;
+pub(crate) mod random;
+pub(crate) mod record;
+pub(crate) mod remote;
+pub(crate) mod render;
+pub(crate) mod ring;
+pub(crate) mod scenes;
+pub(crate) mod sequencer;
+pub(crate) mod session;
+pub(crate) mod smf;
+pub(crate) mod surround;
+pub(crate) mod synth;
+pub(crate) mod theory;
+pub(crate) mod transport;
+pub(crate) mod telemetry;
+pub(crate) mod tuning;
+pub(crate) mod velocity;
+pub(crate) mod vorbis;
+pub(crate) mod wav;
+#[cfg(test)]
+mod engine_tests;
+#[cfg(test)]
+mod frequency_tests;
+#[cfg(test)]
+mod filter_tests;
+#[cfg(test)]
+mod demo_tests;
+#[cfg(test)]
+mod voice_tests;
+#[cfg(test)]
+mod realtime_tests;
+#[cfg(test)]
+mod loudness_tests;
+#[cfg(test)]
+mod parallel_tests;
+#[cfg(test)]
+mod patch_tests;
+#[cfg(test)]
+mod quality_tests;
+#[cfg(test)]
+mod ring_tests;
+#[cfg(test)]
+mod scenes_tests;
+#[cfg(test)]
+mod sequencer_tests;
+#[cfg(test)]
+mod tuning_tests;
+#[cfg(test)]
+mod command_tests;
+#[cfg(test)]
+mod drone_tests;
+#[cfg(test)]
+mod event_tests;
+#[cfg(test)]
+mod layers_tests;
+#[cfg(test)]
+mod link_listener_tests;
+#[cfg(test)]
+mod midi_tests;
+#[cfg(test)]
+mod modulation_tests;
+#[cfg(test)]
+mod paraphony_tests;
+#[cfg(test)]
+mod params_tests;
+#[cfg(test)]
+mod theory_tests;
+#[cfg(test)]
+mod transport_tests;
+#[cfg(test)]
+mod flac_tests;
+#[cfg(test)]
+mod vorbis_tests;
+#[cfg(test)]
+mod handle_tests;
+
+// テストではすべてのメモリ確保を数え、オーディオスレッドで確保していないことを確かめる
+#[cfg(test)]
+#[global_allocator]
+static ALLOCATOR: alloc_audit::CountingAllocator = alloc_audit::CountingAllocator;
+
+// 公開API。モジュールはクレートの中だけで使い、利用者にはここで再エクスポートした型と関数だけを見せる
+pub use adapters::SynthStream;
+#[cfg(feature = "alloc-audit")]
+pub use alloc_audit::{audit, audit_deallocations, thread_allocations, thread_deallocations, CountingAllocator};
+pub use analysis::{AnalysisTaps, Tap, TapFrame};
+pub use audio::{AudioOutput, AudioState, AudioStats, AudioStatus, StreamSettings};
+pub use automation::{ParameterRamp, RampCurve};
+pub use bank::{PresetBank, PresetEntry};
+pub use binaural::{Hrir, HrirSet};
+pub use builder::{StealPolicy, SynthConfig, SynthesizerBuilder, VoiceMode};
+pub use cc::{CcMap, CcMapping};
+pub use command::{Command, CommandSender};
+pub use demo::{factory_patches, play as play_demo, self_test, synthesizer as demo_synthesizer, DemoReport};
+pub use diagnostics::{TestSignal, TestSignalGenerator};
+pub use duck::{DuckTrigger, Ducker};
+pub use dx7::{load_sysex, parse_sysex, Dx7Operator, Dx7Voice};
+pub use effects::{AnalogFloorSettings, Effect, DEFAULT_HUM_DB, DEFAULT_NOISE_FLOOR_DB};
+pub use engine::{
+    AdditiveEngine, EngineBlender, FMEngine, Harmonic, Operator, Oscillator, SineOscillator, SpectralFilter, HARMONIC_COUNT, MAX_STACK_DENSITY,
+};
+pub use event::{Event, ScheduledEvent, DEFAULT_RELEASE_VELOCITY};
+pub use export::{write_audio, ExportFormat};
+pub use handle::{SynthGuard, SynthHandle, BORROW_TIMED_OUT};
+pub use layers::{Layer, LayerFilter, LayerFilters, LayerMask};
+pub use link_listener::LinkTempoListener;
+pub use log::{EventLogReader, LogEntry, LogEvent};
+pub use loudness::LoudnessReport;
+pub use metronome::Metronome;
+pub use midi::{MidiInput, MidiMessage};
+pub use mixer::PanLaw;
+pub use modulation::{
+    parse_division, Breakpoint, BreakpointEnvelope, Controller, Controllers, Lfo, LfoShape, ModCombine, ModCombineModes, ModDestination, ModOffsets,
+    ModRouting, ModSource, RoutingZone,
+};
+pub use mutate::{mutate as mutate_patch, MutationScope, DEFAULT_MUTATION_AMOUNT};
+pub use params::{AtomicParam, ParamName, Params, SHARED_PARAMETERS};
+pub use paraphony::Paraphony;
+pub use patch::{PatchMorph, PatchState, BASE_PRESETS};
+pub use phrase::{build as build_phrase, Direction as PhraseDirection, PhraseKind, PhraseNote, PhraseSettings, CHORD_NOTES, SCALE_NOTES};
+pub use program::{ProgramLoad, ProgramLoader, ProgramMap, ProgramTarget};
+pub use quality::{QualityGovernor, QualityLevel, QualityPolicy};
+pub use random::Rng;
+pub use record::{RecordTap, Recording, RecordingSummary};
+pub use remote::{OscRemote, DEFAULT_OSC_PORT};
+pub use render::{bounce, capture_test_signal, render_frames, render_midi, render_to_file, write_master, BounceOptions, RenderReport};
+pub use scenes::{AbCompare, SceneBank, SceneFade, SCENE_COUNT};
+pub use sequencer::{Sequencer, Step, Track, TrigCondition};
+pub use session::{Autosave, Session, DEFAULT_AUTOSAVE_INTERVAL};
+pub use smf::{load as load_midi_file, parse as parse_midi_file, MidiFile, TimedMessage};
+pub use surround::ChannelLayout;
+pub use synth::{BiquadCoefficients, Envelope, EnvelopeStage, LowPassFilter, Synthesizer, Voice, VoiceInfo, DIRECT_PARAMETERS, MAX_OUTPUT_TRIM_DB};
+pub use telemetry::Telemetry;
+pub use theory::{detect_chord, note_name, Chord};
+pub use transport::{HostTransport, MidiClock, TempoSource};
+pub use tuning::Tuning;
+pub use velocity::{VelocityCurve, VelocityProfiles};
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::command::Command;
+use crate::handle::SynthHandle;
+
+// Ableton Link の探索プロトコル（v1）。参加者はこのマルチキャストへ自分のタイムラインを定期的に送る
+pub const LINK_MULTICAST_ADDRESS: Ipv4Addr = Ipv4Addr::new(224, 76, 78, 75);
+pub const LINK_PORT: u16 = 20808;
+const PROTOCOL_HEADER: &[u8; 8] = b"_asdp_v\x01";
+const MESSAGE_ALIVE: u8 = 1;
+const MESSAGE_RESPONSE: u8 = 2;
+const MESSAGE_BYEBYE: u8 = 3;
+const TIMELINE_KEY: u32 = u32::from_be_bytes(*b"tmln");
+const START_STOP_KEY: u32 = u32::from_be_bytes(*b"stst");
+const SESSION_KEY: u32 = u32::from_be_bytes(*b"sess");
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// 受け取った1つのメッセージから読み取った内容
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkMessage {
+    pub node: [u8; 8],
+    pub ttl: Duration,  // この時間内に次の ALIVE が無ければ参加者から外す
+    pub leaving: bool,  // BYEBYE
+    pub session: Option<[u8; 8]>, // 参加しているセッション（BYEBYE には無い）
+    pub tempo: Option<f32>, // タイムラインのテンポ（BPM）
+    pub playing: Option<bool>, // 開始・停止の状態（対応していない参加者は送らない）
+}
+
+pub fn parse_message(packet: &[u8]) -> Option<LinkMessage> {
+    let rest = packet.strip_prefix(PROTOCOL_HEADER)?;
+    let (header, mut payload) = (rest.get(..12)?, rest.get(12..)?);
+    let (message_type, ttl) = (header[0], header[1]);
+    let node: [u8; 8] = header[4..12].try_into().ok()?;
+    let leaving = match message_type {
+        MESSAGE_ALIVE | MESSAGE_RESPONSE => false,
+        MESSAGE_BYEBYE => true,
+        _ => return None,
+    };
+    
+    let mut message = LinkMessage { node, ttl: Duration::from_secs(ttl as u64), leaving, session: None, tempo: None, playing: None };
+    // 項目は キー（4文字）・長さ・値 の並び。知らないキーは読み飛ばす
+    while payload.len() >= 8 {
+        let key = u32::from_be_bytes(payload[0..4].try_into().ok()?);
+        let size = u32::from_be_bytes(payload[4..8].try_into().ok()?) as usize;
+        let value = payload.get(8..8 + size)?;
+        match key {
+            // 1拍あたりのマイクロ秒・拍の原点・時刻の原点（いずれも i64）
+            TIMELINE_KEY if size >= 8 => {
+                let micros_per_beat = i64::from_be_bytes(value[0..8].try_into().ok()?);
+                if micros_per_beat > 0 {
+                    message.tempo = Some((60_000_000.0 / micros_per_beat as f64) as f32);
+                }
+            }
+            START_STOP_KEY if size >= 1 => message.playing = Some(value[0] != 0),
+            SESSION_KEY if size >= 8 => message.session = value[0..8].try_into().ok(),
+            _ => {}
+        }
+        payload = &payload[8 + size..];
+    }
+    Some(message)
+}
+
+// 1つのセッションの参加者を追い、そのセッションのテンポと開始・停止の変化だけを取り出す。
+// 最初に届いたセッションに従い、その参加者がいなくなれば次に届いたセッションに移る
+#[derive(Debug, Default)]
+pub struct LinkSession {
+    session: Option<[u8; 8]>,
+    peers: HashMap<[u8; 8], Instant>, // 参加者と、次の ALIVE が無ければ外す時刻
+    playing: Option<bool>,
+}
+
+impl LinkSession {
+    // 反映するテンポと開始・停止（どちらも無ければ None）
+    pub fn receive(&mut self, message: &LinkMessage, now: Instant) -> Option<(Option<f32>, Option<bool>)> {
+        if message.leaving {
+            self.peers.remove(&message.node);
+            return None;
+        }
+        let session = message.session?;
+        if self.session.is_some_and(|followed| followed != session) {
+            return None;
+        }
+        self.session = Some(session);
+        self.peers.insert(message.node, now + message.ttl);
+        // 最初に受け取った状態では動かさず、変化したときだけ開始・停止する
+        let started = match (self.playing, message.playing) {
+            (Some(false), Some(true)) => Some(true),
+            (Some(true), Some(false)) => Some(false),
+            _ => None,
+        };
+        self.playing = message.playing.or(self.playing);
+        (message.tempo.is_some() || started.is_some()).then_some((message.tempo, started))
+    }
+    
+    // 期限の切れた参加者を外し、誰もいなくなればセッションを手放す
+    pub fn expire(&mut self, now: Instant) {
+        self.peers.retain(|_, expires| *expires > now);
+        if self.peers.is_empty() {
+            self.session = None;
+            self.playing = None;
+        }
+    }
+    
+    pub fn peers(&self) -> usize {
+        self.peers.len()
+    }
+}
+
+// Link の探索メッセージから1つのセッションのテンポと開始・停止だけを受け取る。
+// 拍の位相は合わせず（時計の同期を行わない）、こちらからは何も送らないので Link の同期ではなくテンポの受信機として使う。
+// 受け取った値はコマンドのキューで送り、シンセサイザーのテンポの取得元が Link のときだけ反映される
+pub struct LinkTempoListener {
+    stop: Arc<AtomicBool>,
+    peers: Arc<AtomicUsize>,
+    tempo: Arc<AtomicU32>, // f32 のビット列（0 = 未受信）
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LinkTempoListener {
+    pub fn start(synth: &SynthHandle) -> io::Result<Self> {
+        let socket = bind_multicast()?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        
+        let stop = Arc::new(AtomicBool::new(false));
+        let peers = Arc::new(AtomicUsize::new(0));
+        let tempo = Arc::new(AtomicU32::new(0));
+        let mut commands = synth.borrow_or_timeout()?.command_sender();
+        let thread = {
+            let (stop, peer_count, shared_tempo) = (Arc::clone(&stop), Arc::clone(&peers), Arc::clone(&tempo));
+            std::thread::spawn(move || {
+                let mut buffer = [0u8; 512];
+                let mut session = LinkSession::default();
+                while !stop.load(Ordering::Relaxed) {
+                    let message = match socket.recv_from(&mut buffer) {
+                        Ok((length, _)) => parse_message(&buffer[..length]),
+                        Err(_) => None, // タイムアウト
+                    };
+                    let now = Instant::now();
+                    if let Some((tempo, started)) = message.and_then(|message| session.receive(&message, now)) {
+                        if let Some(tempo) = tempo {
+                            shared_tempo.store(tempo.to_bits(), Ordering::Relaxed);
+                        }
+                        commands.send(Command::Link { tempo, started });
+                    }
+                    session.expire(now);
+                    peer_count.store(session.peers(), Ordering::Relaxed);
+                }
+            })
+        };
+        Ok(Self { stop, peers, tempo, thread: Some(thread) })
+    }
+    
+    // 従っているセッションで現在 ALIVE を送ってきている参加者の数
+    pub fn peers(&self) -> usize {
+        self.peers.load(Ordering::Relaxed)
+    }
+    
+    // セッションから最後に受け取ったテンポ
+    pub fn tempo(&self) -> Option<f32> {
+        let bits = self.tempo.load(Ordering::Relaxed);
+        (bits != 0).then(|| f32::from_bits(bits))
+    }
+}
+
+impl Drop for LinkTempoListener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// 同じ機器の他の Link アプリもこのポートを使うので、アドレスの再利用を許可してから bind する
+#[cfg(unix)]
+fn bind_multicast() -> io::Result<UdpSocket> {
+    use std::os::fd::FromRawFd;
+    
+    // 作ったソケットは UdpSocket に所有させ、失敗時も閉じられるようにする
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let socket = unsafe { UdpSocket::from_raw_fd(fd) };
+    let enable: libc::c_int = 1;
+    for option in [libc::SO_REUSEADDR, libc::SO_REUSEPORT] {
+        let result = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                option,
+                &enable as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    let address = libc::sockaddr_in {
+        sin_family: libc::AF_INET as libc::sa_family_t,
+        sin_port: LINK_PORT.to_be(),
+        sin_addr: libc::in_addr { s_addr: u32::from(Ipv4Addr::UNSPECIFIED).to_be() },
+        ..unsafe { std::mem::zeroed() }
+    };
+    let result = unsafe {
+        libc::bind(
+            fd,
+            &address as *const libc::sockaddr_in as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    socket.join_multicast_v4(&LINK_MULTICAST_ADDRESS, &Ipv4Addr::UNSPECIFIED)?;
+    Ok(socket)
+}
+
+#[cfg(not(unix))]
+fn bind_multicast() -> io::Result<UdpSocket> {
+    use std::net::SocketAddrV4;
+    
+    let socket = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, LINK_PORT))?;
+    socket.join_multicast_v4(&LINK_MULTICAST_ADDRESS, &Ipv4Addr::UNSPECIFIED)?;
+    Ok(socket)
+}
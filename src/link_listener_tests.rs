@@ -0,0 +1,91 @@
+// Link の探索メッセージの解釈とセッションの追跡のテスト
+use crate::link_listener::{parse_message, LinkMessage, LinkSession};
+use std::time::{Duration, Instant};
+
+const NODE: [u8; 8] = *b"node0001";
+const SESSION: [u8; 8] = *b"session1";
+
+// ヘッダー（種類・TTL・グループ・ノード）と項目（キー・長さ・値）を並べる
+fn packet(message_type: u8, entries: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut packet = b"_asdp_v\x01".to_vec();
+    packet.extend([message_type, 5, 0, 0]);
+    packet.extend(NODE);
+    for (key, value) in entries {
+        packet.extend(*key);
+        packet.extend((value.len() as u32).to_be_bytes());
+        packet.extend(value);
+    }
+    packet
+}
+
+// 120 BPM（1拍 500000 マイクロ秒）と、拍・時刻の原点
+fn timeline() -> Vec<u8> {
+    [500_000i64, 0, 0].iter().flat_map(|value| value.to_be_bytes()).collect()
+}
+
+fn alive(session: [u8; 8], node: [u8; 8], tempo: Option<f32>, playing: Option<bool>) -> LinkMessage {
+    LinkMessage { node, ttl: Duration::from_secs(5), leaving: false, session: Some(session), tempo, playing }
+}
+
+#[test]
+fn alive_message_carries_session_tempo_and_start_stop() {
+    let bytes = packet(1, &[
+        (b"tmln", timeline()),
+        (b"sess", SESSION.to_vec()),
+        (b"mep4", vec![192, 168, 0, 2, 0x51, 0x50]), // 知らないキーは読み飛ばす
+        (b"stst", vec![1, 0, 0, 0, 0, 0, 0, 0, 0]),
+    ]);
+    let message = parse_message(&bytes).unwrap();
+    assert_eq!(message.node, NODE);
+    assert_eq!(message.ttl, Duration::from_secs(5));
+    assert!(!message.leaving);
+    assert_eq!(message.session, Some(SESSION));
+    assert_eq!(message.tempo, Some(120.0));
+    assert_eq!(message.playing, Some(true));
+    
+    let byebye = parse_message(&packet(3, &[])).unwrap();
+    assert!(byebye.leaving && byebye.session.is_none() && byebye.tempo.is_none());
+}
+
+#[test]
+fn malformed_messages_are_rejected() {
+    let bytes = packet(1, &[(b"tmln", timeline())]);
+    // 途中で切れたヘッダーと項目
+    assert!(parse_message(&bytes[..14]).is_none());
+    assert!(parse_message(&bytes[..bytes.len() - 4]).is_none());
+    // 違うヘッダー・版・種類
+    let mut wrong = bytes.clone();
+    wrong[0] = b'x';
+    assert!(parse_message(&wrong).is_none());
+    wrong = bytes.clone();
+    wrong[7] = 2;
+    assert!(parse_message(&wrong).is_none());
+    wrong = bytes.clone();
+    wrong[8] = 9;
+    assert!(parse_message(&wrong).is_none());
+    // 短すぎるタイムラインと正でないテンポは使わない
+    let short = parse_message(&packet(1, &[(b"tmln", vec![0; 4])])).unwrap();
+    assert_eq!(short.tempo, None);
+    let zero = parse_message(&packet(1, &[(b"tmln", vec![0; 24])])).unwrap();
+    assert_eq!(zero.tempo, None);
+}
+
+// 最初のセッションだけに従い、その参加者がいなくなれば別のセッションへ移る
+#[test]
+fn session_filters_other_sessions_and_reports_changes() {
+    let now = Instant::now();
+    let mut session = LinkSession::default();
+    assert_eq!(session.receive(&alive(SESSION, NODE, Some(120.0), Some(false)), now), Some((Some(120.0), None)));
+    assert_eq!(session.receive(&alive(*b"session2", *b"node0002", Some(90.0), Some(true)), now), None);
+    assert_eq!(session.peers(), 1);
+    // 開始・停止は変化したときだけ
+    assert_eq!(session.receive(&alive(SESSION, NODE, None, Some(false)), now), None);
+    assert_eq!(session.receive(&alive(SESSION, *b"node0003", None, Some(true)), now), Some((None, Some(true))));
+    assert_eq!(session.peers(), 2);
+    
+    let leaving = LinkMessage { leaving: true, session: None, ..alive(SESSION, NODE, None, None) };
+    assert_eq!(session.receive(&leaving, now), None);
+    session.expire(now + Duration::from_secs(6));
+    assert_eq!(session.peers(), 0);
+    assert_eq!(session.receive(&alive(*b"session2", *b"node0002", Some(90.0), Some(true)), now), Some((Some(90.0), None)));
+}
@@ -0,0 +1,70 @@
+use crate::ring::{self, Consumer, Producer};
+
+// オーディオスレッドで発生した出来事
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogEvent {
+    VoiceStolen { note: u8 },
+    Clipped { peak: f32 },
+    Underrun { gap_ms: f32 },
+    ParameterClamped { parameter: &'static str, requested: f32, applied: f32 },
+    QualityChanged { level: usize, load: f32 },
+    CcLearned { controller: u8 },
+    Panic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogEntry {
+    pub sample: u64, // 発生時のサンプル位置
+    pub event: LogEvent,
+}
+
+const LOG_CAPACITY: usize = 256;
+
+// オーディオスレッド側（書き込み専用、満杯時は古いものを残して新しいものを捨てる）
+pub struct EventLogWriter {
+    producer: Producer<LogEntry>,
+}
+
+// UIスレッド側（読み出し専用）
+pub struct EventLogReader {
+    consumer: Consumer<LogEntry>,
+}
+
+pub fn event_log() -> (EventLogWriter, EventLogReader) {
+    let (producer, consumer) = ring::channel(LOG_CAPACITY);
+    (EventLogWriter { producer }, EventLogReader { consumer })
+}
+
+impl EventLogWriter {
+    pub fn write(&mut self, sample: u64, event: LogEvent) {
+        let _ = self.producer.push(LogEntry { sample, event });
+    }
+}
+
+impl EventLogReader {
+    pub fn drain(&mut self) -> Vec<LogEntry> {
+        self.consumer.drain().collect()
+    }
+    
+    pub fn dropped(&self) -> usize {
+        self.consumer.dropped()
+    }
+}
+
+impl std::fmt::Display for LogEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogEvent::VoiceStolen { note } => write!(f, "voice stolen: note {}", note),
+            LogEvent::Clipped { peak } => write!(f, "output clipped: peak {:.3}", peak),
+            LogEvent::Underrun { gap_ms } => write!(f, "underrun: callback gap {:.1} ms", gap_ms),
+            LogEvent::ParameterClamped { parameter, requested, applied } => {
+                write!(f, "{} clamped: {:.3} -> {:.3}", parameter, requested, applied)
+            }
+            LogEvent::QualityChanged { level, load } => {
+                write!(f, "quality level changed to {} (DSP load {:.0}%)", level, load * 100.0)
+            }
+            LogEvent::CcLearned { controller } => write!(f, "MIDI learn: assigned CC {}", controller),
+            LogEvent::Panic => write!(f, "panic: all voices and effect tails cleared"),
+        }
+    }
+}
@@ -0,0 +1,216 @@
+// 書き出した音声のラウドネスとピークの測定（ITU-R BS.1770-4 / EBU R128）。
+// ラウドネスは K 特性で重み付けした左右の平均二乗から求め、ラウドネスレンジは EBU Tech 3342 に従う
+use std::f64::consts::PI;
+use std::fmt;
+
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const INTEGRATED_RELATIVE_GATE_LU: f64 = -10.0;
+const RANGE_RELATIVE_GATE_LU: f64 = -20.0;
+const SEGMENT_SECONDS: f64 = 0.1;  // ブロックを進める間隔
+const MOMENTARY_SEGMENTS: usize = 4;   // 400ms のブロック（75% 重ねる）
+const SHORT_TERM_SEGMENTS: usize = 30; // ラウドネスレンジに使う 3 秒の窓
+const OVERSAMPLING: usize = 4;         // トゥルーピークの補間の倍率
+const INTERPOLATION_TAPS: usize = 12;  // 補間フィルターの位相ごとのタップ数
+
+// 測定結果。無音なら integrated と true_peak などは負の無限大になる
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessReport {
+    pub integrated: f32,      // 統合ラウドネス（LUFS）
+    pub loudness_range: f32,  // ラウドネスレンジ（LU）
+    pub true_peak: f32,       // 4 倍オーバーサンプリングで求めたピーク（dBTP）
+    pub sample_peak: f32,     // サンプル値のピーク（dBFS）
+}
+
+impl fmt::Display for LoudnessReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} LUFS integrated, {:.1} LU range, true peak {:.1} dBTP (sample peak {:.1} dBFS)",
+            self.integrated, self.loudness_range, self.true_peak, self.sample_peak
+        )
+    }
+}
+
+// ステレオの frames を測る
+pub fn measure(frames: &[[f32; 2]], sample_rate: f32) -> LoudnessReport {
+    let segments = segment_energies(frames, sample_rate);
+    let blocks = windowed(&segments, MOMENTARY_SEGMENTS);
+    let short_term = windowed(&segments, SHORT_TERM_SEGMENTS);
+    let sample_peak = frames.iter().fold(0.0_f32, |peak, frame| peak.max(frame[0].abs()).max(frame[1].abs()));
+    LoudnessReport {
+        integrated: integrated(&blocks) as f32,
+        loudness_range: loudness_range(&short_term) as f32,
+        true_peak: decibels(true_peak(frames)),
+        sample_peak: decibels(sample_peak),
+    }
+}
+
+// 統合ラウドネスが target（LUFS）になるように frames に掛けたゲイン（dB）を返す。
+// 無音で測れなければ何もしない。トゥルーピークは制限しないので、大きく上げたときは結果のピークを確かめること
+pub fn normalize(frames: &mut [[f32; 2]], sample_rate: f32, target: f32) -> Option<f32> {
+    normalize_all(&mut [frames], sample_rate, target)
+}
+
+// 先頭の信号（マスター）で測ったゲインを、ステムなどの残りにも同じだけ掛ける
+pub fn normalize_all(signals: &mut [&mut [[f32; 2]]], sample_rate: f32, target: f32) -> Option<f32> {
+    let integrated = measure(signals.first()?, sample_rate).integrated;
+    if !integrated.is_finite() {
+        return None;
+    }
+    let gain_db = target - integrated;
+    let gain = 10.0_f32.powf(gain_db / 20.0);
+    for frames in signals.iter_mut() {
+        for frame in frames.iter_mut() {
+            *frame = [frame[0] * gain, frame[1] * gain];
+        }
+    }
+    Some(gain_db)
+}
+
+fn decibels(amplitude: f32) -> f32 {
+    20.0 * amplitude.log10()
+}
+
+fn loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+// K 特性（高域のシェルフと低域のハイパス）で重み付けし、100ms ごとの左右の二乗和の平均を返す
+fn segment_energies(frames: &[[f32; 2]], sample_rate: f32) -> Vec<f64> {
+    let segment = ((sample_rate as f64 * SEGMENT_SECONDS).round() as usize).max(1);
+    let mut filters = [KWeighting::new(sample_rate as f64), KWeighting::new(sample_rate as f64)];
+    frames
+        .chunks_exact(segment)
+        .map(|chunk| {
+            let sum: f64 = chunk
+                .iter()
+                .map(|frame| {
+                    let left = filters[0].process(frame[0] as f64);
+                    let right = filters[1].process(frame[1] as f64);
+                    left * left + right * right
+                })
+                .sum();
+            sum / segment as f64
+        })
+        .collect()
+}
+
+// length 個の区間ずつ 1 区間ずらした窓の平均二乗
+fn windowed(segments: &[f64], length: usize) -> Vec<f64> {
+    segments.windows(length).map(|window| window.iter().sum::<f64>() / length as f64).collect()
+}
+
+// 絶対ゲートを通ったブロックの平均から相対ゲートを決め、両方を通ったブロックの平均のラウドネス
+fn gated(blocks: &[f64], relative_gate: f64) -> Vec<f64> {
+    let audible: Vec<f64> = blocks.iter().copied().filter(|energy| loudness(*energy) > ABSOLUTE_GATE_LUFS).collect();
+    if audible.is_empty() {
+        return audible;
+    }
+    let threshold = loudness(audible.iter().sum::<f64>() / audible.len() as f64) + relative_gate;
+    audible.into_iter().filter(|energy| loudness(*energy) > threshold).collect()
+}
+
+fn integrated(blocks: &[f64]) -> f64 {
+    let blocks = gated(blocks, INTEGRATED_RELATIVE_GATE_LU);
+    if blocks.is_empty() {
+        return f64::NEG_INFINITY;
+    }
+    loudness(blocks.iter().sum::<f64>() / blocks.len() as f64)
+}
+
+// ゲートを通った短時間ラウドネスの 10% 点から 95% 点までの幅
+fn loudness_range(short_term: &[f64]) -> f64 {
+    let mut levels: Vec<f64> = gated(short_term, RANGE_RELATIVE_GATE_LU).into_iter().map(loudness).collect();
+    if levels.len() < 2 {
+        return 0.0;
+    }
+    levels.sort_by(f64::total_cmp);
+    let percentile = |p: f64| levels[((levels.len() - 1) as f64 * p).round() as usize];
+    percentile(0.95) - percentile(0.10)
+}
+
+// 窓付き sinc で 4 倍に補間し、サンプルの間のピークも含めた最大値
+fn true_peak(frames: &[[f32; 2]]) -> f32 {
+    let phases: Vec<[f64; INTERPOLATION_TAPS]> = (1..OVERSAMPLING).map(interpolation_phase).collect();
+    let before = INTERPOLATION_TAPS / 2 - 1;
+    let mut peak = 0.0_f64;
+    for channel in 0..2 {
+        // 前後を 0 で埋め、i と i + 1 の間の点を i - 5 から i + 6 の 12 サンプルから求める
+        let mut samples = vec![0.0; before];
+        samples.extend(frames.iter().map(|frame| frame[channel] as f64));
+        samples.resize(samples.len() + INTERPOLATION_TAPS - before, 0.0);
+        for window in samples.windows(INTERPOLATION_TAPS) {
+            peak = peak.max(window[before].abs());
+            for phase in &phases {
+                let value: f64 = window.iter().zip(phase).map(|(sample, coefficient)| sample * coefficient).sum();
+                peak = peak.max(value.abs());
+            }
+        }
+    }
+    peak as f32
+}
+
+// サンプルの間の位置 phase / OVERSAMPLING を求める係数（Hann 窓の sinc）
+fn interpolation_phase(phase: usize) -> [f64; INTERPOLATION_TAPS] {
+    let offset = phase as f64 / OVERSAMPLING as f64;
+    let half = INTERPOLATION_TAPS as f64 / 2.0;
+    std::array::from_fn(|tap| {
+        // tap の位置から補間する点までの距離（サンプル）
+        let distance = tap as f64 - (half - 1.0) - offset;
+        let sinc = if distance == 0.0 { 1.0 } else { (PI * distance).sin() / (PI * distance) };
+        let window = 0.5 + 0.5 * (PI * distance / half).cos();
+        sinc * window
+    })
+}
+
+// 2 次の IIR フィルター（直接形 I）
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, x: [0.0; 2], y: [0.0; 2] }
+    }
+    
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.x[0] + self.b[2] * self.x[1] - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+// BS.1770 の K 特性。48kHz で規格の係数になるよう、任意のサンプルレートでアナログの原型から求める
+#[derive(Debug, Clone, Copy)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(sample_rate: f64) -> Self {
+        let k = (PI * 1_681.974_450_955_533 / sample_rate).tan();
+        let (q, gain) = (0.707_175_236_955_419_6, 10.0_f64.powf(3.999_843_853_973_347 / 20.0));
+        let band = gain.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [(gain + band * k / q + k * k) / a0, 2.0 * (k * k - gain) / a0, (gain - band * k / q + k * k) / a0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+        
+        let k = (PI * 38.135_470_876_024_44 / sample_rate).tan();
+        let q = 0.500_327_037_323_877_3;
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new([1.0, -2.0, 1.0], [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0]);
+        Self { shelf, high_pass }
+    }
+    
+    fn process(&mut self, input: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(input))
+    }
+}
@@ -0,0 +1,45 @@
+// ラウドネス・トゥルーピークの測定と書き出しの正規化のテスト
+use crate::loudness::{measure, normalize};
+use std::f64::consts::PI;
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+// 左右に同じ正弦波（振幅は dBFS）
+fn sine(frequency: f32, level: f32, seconds: f32, phase: f32) -> Vec<[f32; 2]> {
+    let amplitude = 10.0_f64.powf(level as f64 / 20.0);
+    let step = 2.0 * PI * frequency as f64 / SAMPLE_RATE as f64;
+    (0..(seconds * SAMPLE_RATE) as usize)
+        .map(|i| [(amplitude * (step * i as f64 + phase as f64).sin()) as f32; 2])
+        .collect()
+}
+
+// 1kHz 付近の正弦波は K 特性でほぼ変わらないので、両チャンネルに -20dBFS なら -20 LUFS
+#[test]
+fn sine_loudness_and_true_peak_match_the_reference() {
+    let report = measure(&sine(997.0, -20.0, 5.0, 0.0), SAMPLE_RATE);
+    assert!((report.integrated + 20.0).abs() < 0.1, "{}", report);
+    assert!(report.loudness_range.abs() < 0.1, "{}", report);
+    
+    // サンプルレートの 1/4 の正弦波を 45° ずらすと、サンプル値はピークの 1/√2 にしかならない
+    let report = measure(&sine(SAMPLE_RATE / 4.0, -6.0, 1.0, std::f32::consts::FRAC_PI_4), SAMPLE_RATE);
+    assert!((report.sample_peak + 9.01).abs() < 0.05, "{}", report);
+    assert!((report.true_peak + 6.0).abs() < 0.2, "{}", report);
+    
+    let silence = measure(&vec![[0.0; 2]; 48000], SAMPLE_RATE);
+    assert_eq!(silence.integrated, f32::NEG_INFINITY);
+}
+
+// 10 秒ずつ -30 と -20 dBFS が続くとラウドネスレンジは 10 LU。正規化は統合ラウドネスを目標に合わせる
+#[test]
+fn loudness_range_and_normalization() {
+    let mut frames = sine(997.0, -30.0, 10.0, 0.0);
+    frames.extend(sine(997.0, -20.0, 10.0, 0.0));
+    let report = measure(&frames, SAMPLE_RATE);
+    assert!((report.loudness_range - 10.0).abs() < 0.5, "{}", report);
+    
+    let gain = normalize(&mut frames, SAMPLE_RATE, -16.0).unwrap();
+    let normalized = measure(&frames, SAMPLE_RATE);
+    assert!((normalized.integrated + 16.0).abs() < 0.01, "{}", normalized);
+    assert!((gain - (-16.0 - report.integrated)).abs() < 1e-4);
+    assert!(normalize(&mut vec![[0.0; 2]; 48000], SAMPLE_RATE, -16.0).is_none());
+}
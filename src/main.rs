@@ -1,224 +1,1221 @@
-mod engine;
-mod synth;
-mod audio;
+// CLI。シンセサイザー本体はライブラリ（lib.rs）の公開APIだけを使う
+mod cli;
+mod i18n;
+mod status;
 
-use std::sync::{Arc, Mutex};
 use std::io::{self, Write};
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
+use synthesizer::{
+    bounce, build_phrase, capture_test_signal, demo_synthesizer, detect_chord, load_midi_file, load_sysex, mutate_patch, note_name, parse_division,
+    play_demo, render_midi, render_to_file, self_test, write_master, AbCompare, AudioOutput, AudioState, Autosave, BounceOptions, Command, Controller,
+    DuckTrigger, Event, EventLogReader, ExportFormat, Layer, LayerFilter, LinkTempoListener, MidiInput, ModCombine, ModDestination, ModRouting,
+    ModSource, MutationScope, OscRemote, PanLaw, PatchState, PhraseDirection, PhraseKind, PhraseNote, PhraseSettings, PresetBank, ProgramLoad,
+    ProgramLoader, ProgramMap, ProgramTarget, RampCurve, Recording, RenderReport, Rng, RoutingZone, Session, SpectralFilter, SynthConfig, SynthGuard,
+    SynthHandle, Synthesizer, Tap, TempoSource, TestSignal, TestSignalGenerator, Track, TrigCondition, Tuning, VelocityCurve, CHORD_NOTES,
+    DEFAULT_AUTOSAVE_INTERVAL, DEFAULT_HUM_DB, DEFAULT_MUTATION_AMOUNT, DEFAULT_NOISE_FLOOR_DB, DEFAULT_OSC_PORT, DEFAULT_RELEASE_VELOCITY,
+    HARMONIC_COUNT, MAX_OUTPUT_TRIM_DB, MAX_STACK_DENSITY, SCALE_NOTES, SCENE_COUNT,
+};
+
+// オーディオのコールバック中のメモリ確保を stats で数える
+#[cfg(feature = "alloc-audit")]
+#[global_allocator]
+static ALLOCATOR: synthesizer::CountingAllocator = synthesizer::CountingAllocator;
 
 fn main() {
+    i18n::set_language(i18n::Language::detect());
+    
+    // 対話せずに MIDI ファイルを WAV に書き出すモード
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("render") {
+        if let Err(e) = render_midi_file(&args[1..]) {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    
     println!("🎹 Additive + FM Synthesizer");
     println!("================================");
     
+    let demo_mode = std::env::args().skip(1).any(|arg| arg == "--demo");
+    
     // Initialize synthesizer
-    let mut synth = synth::Synthesizer::new();
+    let mut synth = if demo_mode {
+        demo_synthesizer(SynthConfig::default().sample_rate)
+    } else {
+        Synthesizer::new()
+    };
     println!("✅ Synthesizer initialized successfully!");
     
     // Test synthesizer functionality
-    test_synthesizer(&mut synth);
+    test_synthesizer(&synth);
+    
+    let event_log = synth.take_event_log();
     
-    // Create thread-safe synthesizer for audio
-    let synth_arc = Arc::new(Mutex::new(synth));
+    // オーディオのコールバックと制御側のスレッドで受け渡すハンドル
+    let synth_handle = SynthHandle::new(synth);
     
     // Initialize audio output
-    match audio::AudioOutput::new(Arc::clone(&synth_arc)) {
+    match AudioOutput::new(synth_handle.clone()) {
         Ok(mut audio) => {
+            // --host <ホスト> / --rate <Hz> / --buffer <フレーム> で機器に要求する設定（開けた値は start が表示する）
+            for (flag, value) in args.iter().zip(args.iter().skip(1)) {
+                match (flag.as_str(), value.parse::<u32>()) {
+                    ("--host", _) => audio.set_host(Some(value.clone())),
+                    ("--rate", Ok(rate)) if rate > 0 => audio.set_sample_rate(Some(rate)),
+                    ("--buffer", Ok(frames)) if frames > 0 => audio.set_buffer_size(Some(frames)),
+                    ("--rate" | "--buffer", _) => println!("⚠️  {}", i18n::fill(i18n::tr("Ignoring {} {} (expected a positive number)", "{} {} を無視します（正の数で指定してください）"), &[flag, value])),
+                    _ => {}
+                }
+            }
             println!("\n🎵 Starting audio output...");
             if let Err(e) = audio.start() {
-                eprintln!("❌ Failed to start audio: {}", e);
+                eprintln!("❌ {}", i18n::fill(i18n::tr("Failed to start audio: {}", "オーディオを開始できませんでした: {}"), &[&e]));
                 return;
             }
             
+            if demo_mode {
+                println!("\n🎬 Playing the demo...");
+                play_demo(&synth_handle);
+            }
+            
             // Interactive control loop
-            interactive_control(Arc::clone(&synth_arc), &mut audio);
+            interactive_control(synth_handle, &mut audio, event_log);
         }
         Err(e) => {
-            eprintln!("❌ Failed to initialize audio: {}", e);
+            eprintln!("❌ {}", i18n::fill(i18n::tr("Failed to initialize audio: {}", "オーディオを初期化できませんでした: {}"), &[&e]));
             println!("🔧 Audio output not available, running in test mode only");
         }
     }
 }
 
-fn test_synthesizer(synth: &mut synth::Synthesizer) {
-    println!("📊 Additive Engine: 64 harmonics available");
-    println!("🎛️  FM Engine: 6 operators available");
-    println!("🎚️  Envelope: ADSR controls");
-    println!("🔊 Filter: Low-pass with resonance");
+// audio [rate <Hz|default>] [buffer <フレーム|default>]。変えたら開き直し、開けなければ元の設定に戻す
+fn handle_audio_command(audio: &mut AudioOutput, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    if !parts.is_empty() {
+        let (previous_rate, previous_buffer) = (audio.requested_sample_rate(), audio.requested_buffer_size());
+        for pair in parts.chunks(2) {
+            let value = match pair {
+                [_, "default"] => None,
+                [_, value] => match value.parse::<u32>() {
+                    Ok(value) if value > 0 => Some(value),
+                    _ => {
+                        println!("❌ {}", i18n::fill(i18n::tr("Expected a positive number: {}", "正の数値で指定してください: {}"), &[value]));
+                        return;
+                    }
+                },
+                _ => {
+                    cli::print_usage("audio");
+                    return;
+                }
+            };
+            match pair[0] {
+                "rate" => audio.set_sample_rate(value),
+                "buffer" => audio.set_buffer_size(value),
+                _ => {
+                    cli::print_usage("audio");
+                    return;
+                }
+            }
+        }
+        if let Err(e) = audio.restart() {
+            println!("❌ {}", i18n::fill(i18n::tr("Failed to reopen audio: {}", "オーディオを開き直せませんでした: {}"), &[&e]));
+            audio.set_sample_rate(previous_rate);
+            audio.set_buffer_size(previous_buffer);
+            if let Err(e) = audio.restart() {
+                eprintln!("❌ {}", i18n::fill(i18n::tr("Failed to rebuild audio: {}", "オーディオを開き直せませんでした: {}"), &[&e]));
+            }
+        }
+        return;
+    }
+    let requested = |value: Option<u32>, unit: &str| value.map_or("default".to_string(), |value| format!("{} {}", value, unit));
+    println!("🔈 Requested: rate {}, buffer {}",
+        requested(audio.requested_sample_rate(), "Hz"),
+        requested(audio.requested_buffer_size(), "frames"));
+    let status = audio.status();
+    match (status.state, status.settings) {
+        (AudioState::Running, Some(settings)) => {
+            let buffer = match (settings.buffer_frames, settings.buffer_latency_ms()) {
+                (Some(frames), Some(latency)) => format!("{} frames ({:.1} ms)", frames, latency),
+                _ => "backend default".to_string(),
+            };
+            println!("🎵 Running: {} Hz, {} channels, buffer {}", settings.sample_rate, settings.channels, buffer);
+            let device = status.device.unwrap_or_default();
+            if status.fallback {
+                println!("🔌 Device: {} (fallback, waiting for {})", device, audio.device_name().unwrap_or("default"));
+            } else {
+                println!("🔌 Device: {}", device);
+            }
+            let ports = audio.output_ports();
+            if !ports.is_empty() {
+                println!("🔌 JACK ports: {}", ports.join(" "));
+            }
+        }
+        (AudioState::Recovering, _) => {
+            println!("🔌 {}", i18n::tr("Audio device lost; looking for an output device", "出力機器が外れました。開き直せる機器を探しています"));
+        }
+        _ => println!("🔇 {}", i18n::tr("Audio output is not running", "オーディオ出力は止まっています")),
+    }
+    if status.recoveries > 0 {
+        println!("🔁 Recovered {} times", status.recoveries);
+    }
+    if let Some(error) = status.last_error {
+        println!("⚠️  {}: {}", i18n::tr("Last error", "最後のエラー"), error);
+    }
+}
+
+// stats [reset]。コールバックの計測値を表示する
+fn handle_stats_command(audio: &AudioOutput, args: &str) {
+    match args.trim() {
+        "" => {}
+        "reset" => {
+            audio.reset_stats();
+            println!("📊 Audio stats reset");
+            return;
+        }
+        _ => {
+            cli::print_usage("stats");
+            return;
+        }
+    }
+    let stats = audio.stats();
+    if stats.callbacks == 0 {
+        println!("📊 {}", i18n::tr("No audio callbacks yet", "オーディオのコールバックはまだありません"));
+        return;
+    }
+    println!("📊 Callbacks: {} ({} frames), deadline {:.2} ms", stats.callbacks, stats.frames, stats.deadline_ms);
+    println!("⏱️  Callback time: last {:.3} ms, average {:.3} ms ({:.0}%), max {:.3} ms ({:.0}%)",
+        stats.last_ms,
+        stats.average_ms,
+        stats.average_load() * 100.0,
+        stats.max_ms,
+        stats.peak_load() * 100.0);
+    println!("⚠️  Deadline misses: {} | Underruns: {} (worst gap {:.1} ms)", stats.deadline_misses, stats.underruns, stats.worst_gap_ms);
+    if cfg!(feature = "alloc-audit") {
+        println!("🧮 Allocations in the callback: {} ({} callbacks)", stats.allocations, stats.allocating_callbacks);
+    }
+    if stats.silent_callbacks > 0 {
+        println!("🔇 Silent callbacks while the synth was borrowed: {}", stats.silent_callbacks);
+    }
+}
+
+// host [<名前>|default]。引数なしで使えるホストを一覧表示する。開けなければ元のホストに戻す
+fn handle_host_command(audio: &mut AudioOutput, args: &str) {
+    let args = args.trim();
+    if args.is_empty() {
+        let current = audio.host_name();
+        println!("🔌 Audio hosts:");
+        for host in AudioOutput::list_hosts() {
+            let marker = if host == current { "▶" } else { " " };
+            println!("  {} {}", marker, host);
+        }
+        if !AudioOutput::list_hosts().iter().any(|host| host == "jack") {
+            println!("💡 {}", i18n::tr(
+                "JACK is not available in this build (build with the \"jack\" feature)",
+                "このビルドでは JACK を使えません（\"jack\" フィーチャー付きでビルドしてください）",
+            ));
+        }
+        return;
+    }
+    let previous = (audio.host_name(), audio.device_name().map(str::to_string));
+    audio.set_host((args != "default").then(|| args.to_string()));
+    if let Err(e) = audio.restart() {
+        println!("❌ {}", i18n::fill(i18n::tr("Failed to open the audio host: {}", "オーディオのホストを開けませんでした: {}"), &[&e]));
+        audio.set_host(Some(previous.0));
+        audio.set_device(previous.1);
+        if let Err(e) = audio.restart() {
+            eprintln!("❌ {}", i18n::fill(i18n::tr("Failed to rebuild audio: {}", "オーディオを開き直せませんでした: {}"), &[&e]));
+        }
+    }
+}
+
+// 出力機器の一覧。使っている機器に印を付ける（機器を指定していなければ既定の機器）
+fn list_audio_devices(audio: &AudioOutput) {
+    let devices = match audio.host_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            println!("❌ {}", i18n::fill(i18n::tr("Failed to list audio devices: {}", "出力機器を一覧できませんでした: {}"), &[&e]));
+            return;
+        }
+    };
+    if devices.is_empty() {
+        println!("🔇 {}", i18n::tr("No audio output devices found", "出力機器が見つかりません"));
+        return;
+    }
+    let default = audio.host_default_device_name();
+    let current = audio.device_name().map(str::to_string).or_else(|| default.clone());
+    println!("🔈 Audio output devices:");
+    for (index, name) in devices.iter().enumerate() {
+        let marker = if current.as_deref() == Some(name.as_str()) { "▶" } else { " " };
+        let note = if default.as_deref() == Some(name.as_str()) { " (default)" } else { "" };
+        println!("  {} {:>2}. {}{}", marker, index + 1, name, note);
+    }
+}
+
+// device <番号|名前|default>。開けなければ元の機器に戻す
+fn handle_device_command(audio: &mut AudioOutput, args: &str) {
+    let args = args.trim();
+    if args.is_empty() {
+        match audio.device_name() {
+            Some(name) => println!("🔈 Audio device: {}", name),
+            None => println!("🔈 Audio device: default ({})", audio.host_default_device_name().unwrap_or_else(|| "none".to_string())),
+        }
+        return;
+    }
+    let device = if args == "default" {
+        None
+    } else if let Ok(index) = args.parse::<usize>() {
+        let devices = audio.host_devices().unwrap_or_default();
+        match index.checked_sub(1).and_then(|index| devices.get(index)) {
+            Some(name) => Some(name.clone()),
+            None => {
+                println!("❌ {}", i18n::fill(i18n::tr("No audio device numbered {} (see devices)", "番号 {} の出力機器はありません（devices を参照）"), &[&index]));
+                return;
+            }
+        }
+    } else {
+        Some(args.to_string())
+    };
+    let previous = audio.device_name().map(str::to_string);
+    audio.set_device(device);
+    if let Err(e) = audio.restart() {
+        println!("❌ {}", i18n::fill(i18n::tr("Failed to open the audio device: {}", "出力機器を開けませんでした: {}"), &[&e]));
+        audio.set_device(previous);
+        if let Err(e) = audio.restart() {
+            eprintln!("❌ {}", i18n::fill(i18n::tr("Failed to rebuild audio: {}", "オーディオを開き直せませんでした: {}"), &[&e]));
+        }
+    }
+}
+
+// synthesizer render --midi <in.mid> [--preset <プリセット>] --out <out.wav> [--normalize <LUFS>]
+// プリセットはパッチ差分ファイルのパス、またはプリセットのバンクの名前
+fn render_midi_file(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "usage: synthesizer render --midi <in.mid> [--preset <file.patch | bank preset>] --out <out.wav|out.flac|out.ogg> [--bits 16|24 | 0-10 for .ogg] [--normalize <LUFS>]";
+    let (mut midi_path, mut preset, mut out_path, mut bits, mut normalize) = (None, None, None, None, None);
+    for pair in args.chunks(2) {
+        match pair {
+            [flag, value] if flag == "--midi" => midi_path = Some(value),
+            [flag, value] if flag == "--preset" => preset = Some(value),
+            [flag, value] if flag == "--out" => out_path = Some(value),
+            [flag, value] if flag == "--bits" => bits = Some(value.parse::<u32>().map_err(|_| USAGE)?),
+            [flag, value] if flag == "--normalize" => normalize = Some(value.parse::<f32>().map_err(|_| USAGE)?),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let (Some(midi_path), Some(out_path)) = (midi_path, out_path) else {
+        return Err(USAGE.into());
+    };
+    let format = ExportFormat::from_path(out_path, bits)?;
     
+    let mut synth = Synthesizer::new();
+    if let Some(preset) = preset {
+        let (patch, warnings) = if std::path::Path::new(preset).is_file() {
+            PatchState::load_delta(preset)?
+        } else {
+            PresetBank::open(cli::preset_dir())?.read(preset)?
+        };
+        for warning in &warnings {
+            eprintln!("⚠️  {}", i18n::fill(i18n::tr("Skipped: {}", "読み飛ばしました: {}"), &[warning]));
+        }
+        synth.set_patch(patch);
+    }
+    let file = load_midi_file(midi_path).map_err(|e| i18n::fill(i18n::tr("Failed to read {}: {}", "{} を読み込めませんでした: {}"), &[midi_path, &e]))?;
+    let mut frames = render_midi(&mut synth, &file);
+    let report = write_master(out_path, synth.sample_rate(), &mut frames, format, normalize)?;
+    println!(
+        "💾 Rendered {} ({} MIDI events) to {} ({}, {:.1} s)",
+        midi_path,
+        file.events.len(),
+        out_path,
+        format,
+        report.seconds
+    );
+    print_loudness(&report);
+    Ok(())
+}
+
+// 起動時のセルフテスト（デモ曲の冒頭をオフラインで描画して出力を検査する）
+fn test_synthesizer(synth: &Synthesizer) {
+    println!("📊 Additive Engine: {} harmonics, FM Engine: {} operators", synth.harmonics_count(), synth.operators_count());
     println!("\n🎵 Testing synthesizer...");
+    match self_test(synth.sample_rate(), Some(SELF_TEST_SECONDS)) {
+        Ok(report) => println!(
+            "✅ Self-test passed: {:.1} s rendered, peak {:.3}, RMS {:.4}",
+            report.seconds, report.peak, report.rms
+        ),
+        Err(message) => println!("❌ {}", i18n::fill(i18n::tr("Self-test failed: {}", "セルフテストに失敗しました: {}"), &[&message])),
+    }
+}
+
+// 起動時のセルフテストで描画する長さ（秒）
+const SELF_TEST_SECONDS: f32 = 1.5;
+
+// ステータス行の更新間隔
+const STATUS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+
+fn interactive_control(
+    synth: SynthHandle,
+    audio: &mut AudioOutput,
+    mut event_log: Option<EventLogReader>,
+) {
+    cli::print_help();
     
-    // Test sample generation
-    for i in 0..100 {
-        if i % 20 == 0 {
-            let sample = synth.next_sample();
-            println!("Sample {}: {:.6}", i, sample);
-        } else {
-            synth.next_sample();
-        }
-    }
-    
-    println!("✅ Test completed successfully!");
-    println!("Is playing: {}", synth.is_playing());
-    println!("Harmonics count: {}", synth.harmonics_count());
-    println!("Operators count: {}", synth.operators_count());
-}
-
-fn interactive_control(synth: Arc<Mutex<synth::Synthesizer>>, _audio: &mut audio::AudioOutput) {
-    println!("\n🎮 インタラクティブ制御:");
-    println!("'c' + Enter で中央のC音を再生");
-    println!("'e' + Enter でE音を再生");
-    println!("'g' + Enter でG音を再生");
-    println!("'d' + Enter でD音を再生");
-    println!("'f' + Enter でF音を再生");
-    println!("'a' + Enter でA音を再生");
-    println!("'b' + Enter でB音を再生");
-    println!("'s' + Enter で全ての音を停止");
-    println!("'q' + Enter で終了");
-    println!("'1-9' + Enter でブレンド比率変更 (1=Additive, 9=FM)");
-    println!("'a' + Enter でエンベロープ調整");
-    println!("'f' + Enter でフィルター調整");
-    println!("'p' + Enter でアクティブな音を表示");
-    println!("\n⏱️  カスタム持続時間:");
-    println!("'C <秒数>' で中央のC音を指定時間再生 (例: 'C 2.5')");
-    println!("'D <秒数>' でD音を指定時間再生 (例: 'D 1.8')");
-    println!("'E <秒数>' でE音を指定時間再生 (例: 'E 1.8')");
-    println!("'F <秒数>' でF音を指定時間再生 (例: 'F 0.3')");
-    println!("'G <秒数>' でG音を指定時間再生 (例: 'G 0.3')");
-    println!("'A <秒数>' でA音を指定時間再生 (例: 'A 4.2')");
-    println!("'B <秒数>' でB音を指定時間再生 (例: 'B 4.2')");
-    println!("'H <秒数>' で高いC音を指定時間再生 (例: 'H 4.2')");
-    println!("'CHORD <秒数>' でC-E-G和音を指定時間再生 (例: 'CHORD 5.0')");
-    println!("'SCALE <秒数>' でC-D-E-F-G-A-B-C音階を指定時間再生 (例: 'SCALE 8.0')");
+    let mut editor = match Editor::<cli::CommandHelper, DefaultHistory>::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("❌ {}", i18n::fill(i18n::tr("Failed to initialize the command line: {}", "コマンドラインを初期化できませんでした: {}"), &[&e]));
+            return;
+        }
+    };
+    editor.set_helper(Some(cli::CommandHelper::new()));
+    let history_path = cli::history_path();
+    let mut phrase_settings = PhraseSettings::default();
+    let mut phrase_rng = Rng::from_entropy();
+    let _ = editor.load_history(&history_path);
+    let mut status_line = status::StatusLine::start(&synth, STATUS_INTERVAL);
+    let mut tempo_listener: Option<LinkTempoListener> = None;
+    let mut midi_input: Option<MidiInput> = None;
+    let mut osc_remote: Option<OscRemote> = None;
+    offer_recovery(&mut editor, &synth);
+    let mut autosave = Some(Autosave::start(cli::autosave_path(), synth.clone(), DEFAULT_AUTOSAVE_INTERVAL));
+    let mut preset_bank = PresetBank::open(cli::preset_dir()).unwrap_or_else(|e| {
+        eprintln!("⚠️  {}: {}", i18n::tr("Failed to read the preset bank", "プリセットのバンクを読めませんでした"), e);
+        PresetBank::new(cli::preset_dir())
+    });
+    let mut ab_compare = AbCompare::default();
+    let mut recording: Option<Recording> = None;
+    match ProgramMap::load(cli::program_map_path()) {
+        Ok((map, warnings)) => {
+            for warning in &warnings {
+                eprintln!("⚠️  {}", i18n::fill(i18n::tr("Skipped: {}", "読み飛ばしました: {}"), &[warning]));
+            }
+            if let Some(mut synth) = borrow_synth(&synth) {
+                *synth.program_map_mut() = map;
+            }
+        }
+        Err(e) => eprintln!("⚠️  {}: {}", i18n::tr("Failed to read the program change table", "プログラムチェンジの対応表を読めませんでした"), e),
+    }
+    let _program_loader = ProgramLoader::start(synth.clone(), cli::preset_dir(), report_program_load);
     
     loop {
-        print!("> ");
-        io::stdout().flush().unwrap();
-        
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => {
+                println!("👋 Goodbye!");
+                break;
+            }
+            Err(e) => {
+                eprintln!("❌ {}", i18n::fill(i18n::tr("Failed to read input: {}", "入力を読み取れませんでした: {}"), &[&e]));
+                break;
+            }
+        };
+        let Some(command) = cli::Command::parse(&line) else {
+            continue;
+        };
+        let _ = editor.add_history_entry(line.trim());
+        let args = command.args;
         
-        // カスタム持続時間の処理
-        if let Some((note, duration_str)) = parse_custom_duration(input) {
-            match duration_str.parse::<f32>() {
+        match command.name {
+            "help" => match args {
+                "" => cli::print_help(),
+                name => cli::print_command_help(name),
+            },
+            "bright" => match args.parse::<f32>() {
+                Ok(brightness) if (0.0..=1.0).contains(&brightness) => {
+                    if send_parameter(&synth, "brightness", brightness) {
+                        println!("✨ Brightness set to: {:.2}", brightness);
+                    }
+                }
+                _ => {
+                    println!("❌ {}", i18n::tr("Brightness must be a number between 0.0 and 1.0", "ブライトネスは0.0〜1.0の数値で指定してください"));
+                }
+            },
+            "stack" => {
+                let values: Vec<&str> = args.split_whitespace().collect();
+                let density = values.first().and_then(|v| v.parse::<usize>().ok());
+                let detune = values.get(1).map_or(Some(12.0), |v| v.parse::<f32>().ok());
+                let spread = values.get(2).map_or(Some(0.5), |v| v.parse::<f32>().ok());
+                match (density, detune, spread) {
+                    (Some(density @ 1..=7), Some(detune), Some(spread)) => {
+                        let Some(mut synth) = borrow_synth(&synth) else { continue };
+                        synth.set_stack(density, detune, spread);
+                        let patch = synth.patch();
+                        println!(
+                            "🎚️  Stack: {} layers, ±{:.1} cents, spread {:.2}",
+                            patch.stack_density, patch.stack_detune, patch.stack_spread
+                        );
+                    }
+                    _ => {
+                        cli::print_usage("stack");
+                    }
+                }
+            }
+            "pan" => match args.parse::<f32>() {
+                Ok(pan) => {
+                    if send_parameter(&synth, "pan", pan) {
+                        println!("↔️  Pan set to: {:+.2}", pan.clamp(-1.0, 1.0));
+                    }
+                }
+                Err(_) => println!("❌ {}", i18n::tr("Pan must be a number between -1 and 1", "パンは-1〜1の数値で指定してください")),
+            },
+            "panlaw" => match args.parse::<PanLaw>() {
+                Ok(pan_law) => {
+                    let Some(mut synth) = borrow_synth(&synth) else { continue };
+                    synth.set_pan_law(pan_law);
+                    println!("↔️  Pan law set to: {} at center", pan_law);
+                }
+                Err(_) if args.is_empty() => {
+                    let Some(synth) = borrow_synth(&synth) else { continue };
+                    println!("↔️  Pan law: {} at center", synth.pan_law());
+                }
+                Err(_) => cli::print_usage("panlaw"),
+            },
+            "mono" => with_synth(&synth, |synth| handle_mono_command(synth, args)),
+            "cc" => with_synth(&synth, |synth| handle_cc_command(synth, args)),
+            "clock" => {
+                let Some(mut synth) = borrow_synth(&synth) else { continue };
+                match args.parse::<TempoSource>() {
+                    // ホストのトランスポートはプラグインのラッパーが渡すもので、CLI には届かない
+                    Ok(TempoSource::Host) => {
+                        cli::print_usage("clock");
+                        continue;
+                    }
+                    Ok(source) => synth.set_tempo_source(source),
+                    Err(_) if args.is_empty() => {}
+                    Err(_) if args.starts_with("jitter") => match args["jitter".len()..].trim().parse::<f32>() {
+                        Ok(milliseconds) if milliseconds >= 0.0 => synth.set_timestamp_latency(milliseconds / 1000.0),
+                        _ => {
+                            cli::print_usage("clock");
+                            continue;
+                        }
+                    },
+                    Err(_) => {
+                        cli::print_usage("clock");
+                        continue;
+                    }
+                }
+                let received = match synth.midi_clock_tempo() {
+                    Some(tempo_bpm) => format!("{:.1} BPM", tempo_bpm),
+                    None => i18n::tr("not received", "受信なし").to_string(),
+                };
+                let transport = if synth.midi_clock().is_running() { "running" } else { "stopped" };
+                println!(
+                    "⏱️  Tempo source: {} ({:.1} BPM) | MIDI clock: {}, {}",
+                    synth.tempo_source(), synth.tempo(), received, transport,
+                );
+                println!(
+                    "🕰️  Sample clock: {} ({:.3} s) | Timestamp latency: {:.1} ms | Late events: {}",
+                    synth.now_samples(),
+                    synth.now_samples() as f64 / synth.config().sample_rate as f64,
+                    synth.timestamp_latency() * 1000.0,
+                    synth.late_events(),
+                );
+            }
+            "link" => match args {
+                "on" => {
+                    if tempo_listener.is_none() {
+                        match LinkTempoListener::start(&synth) {
+                            Ok(listener) => tempo_listener = Some(listener),
+                            Err(e) => {
+                                println!("❌ {}: {}", i18n::tr("Failed to listen to the Link session", "Link のセッションを受信できませんでした"), e);
+                                continue;
+                            }
+                        }
+                    }
+                    let Some(mut synth) = borrow_synth(&synth) else { continue };
+                    synth.set_tempo_source(TempoSource::Link);
+                    println!("🔗 Link on (receiving the session tempo and start/stop only)");
+                }
+                "off" => {
+                    tempo_listener = None;
+                    let Some(mut synth) = borrow_synth(&synth) else { continue };
+                    if synth.tempo_source() == TempoSource::Link {
+                        synth.set_tempo_source(TempoSource::Internal);
+                    }
+                    println!("🔗 Link off");
+                }
+                "" => match &tempo_listener {
+                    Some(listener) => {
+                        let session = listener.tempo().map_or("-".to_string(), |tempo_bpm| format!("{:.2} BPM", tempo_bpm));
+                        println!(
+                            "🔗 Link on: {} peers, session tempo {} (synth tempo {:.2} BPM)",
+                            listener.peers(), session, synth.telemetry().tempo(),
+                        );
+                    }
+                    None => println!("🔗 Link off"),
+                },
+                _ => cli::print_usage("link"),
+            },
+            "midi" => match args.split_once(' ').map_or((args, ""), |(sub, rest)| (sub, rest.trim())) {
+                ("in", "off") => {
+                    midi_input = None;
+                    println!("🎹 MIDI input off");
+                }
+                ("in", path) if !path.is_empty() => {
+                    // 切り替えてもモニターの状態は引き継ぐ
+                    let monitor = midi_input.as_ref().is_some_and(|input| input.monitor());
+                    match MidiInput::open(path, &synth) {
+                        Ok(input) => {
+                            input.set_monitor(monitor);
+                            println!("🎹 MIDI input: {}", path);
+                            midi_input = Some(input);
+                        }
+                        Err(e) => println!("❌ {}: {}", i18n::tr("Failed to open the MIDI device", "MIDI 機器を開けませんでした"), e),
+                    }
+                }
+                ("jitter", value) => {
+                    let milliseconds = match value {
+                        "off" => Some(0.0),
+                        value => value.parse::<f32>().ok().filter(|milliseconds| *milliseconds >= 0.0),
+                    };
+                    let Some(milliseconds) = milliseconds else {
+                        cli::print_usage("midi");
+                        continue;
+                    };
+                    let Some(mut synth) = borrow_synth(&synth) else { continue };
+                    synth.set_live_lookahead(milliseconds / 1000.0);
+                    if synth.live_lookahead() > 0.0 {
+                        println!("🎹 MIDI jitter buffer: {:.1} ms", synth.live_lookahead() * 1000.0);
+                    } else {
+                        println!("🎹 MIDI jitter buffer off");
+                    }
+                }
+                ("", "") => {
+                    match &midi_input {
+                        Some(input) => println!(
+                            "🎹 MIDI input: {} (monitor {})",
+                            input.path().display(), if input.monitor() { "on" } else { "off" },
+                        ),
+                        None => println!("🎹 MIDI input off"),
+                    }
+                    let Some(synth) = borrow_synth(&synth) else { continue };
+                    let lookahead = synth.live_lookahead();
+                    if lookahead > 0.0 {
+                        println!("🎹 MIDI jitter buffer: {:.1} ms", lookahead * 1000.0);
+                    }
+                }
+                _ => cli::print_usage("midi"),
+            },
+            "midimon" => {
+                let Some(input) = &midi_input else {
+                    println!("❌ {}", i18n::tr("No MIDI input is open (use `midi in <device>`)", "MIDI 入力が開かれていません（`midi in <機器>` で開く）"));
+                    continue;
+                };
+                let enabled = match args {
+                    "on" => true,
+                    "off" => false,
+                    "" => !input.monitor(),
+                    _ => {
+                        cli::print_usage("midimon");
+                        continue;
+                    }
+                };
+                input.set_monitor(enabled);
+                println!("🎹 MIDI monitor {}", if enabled { "on" } else { "off" });
+            }
+            "osc" => match args.split_once(' ').map_or((args, ""), |(sub, rest)| (sub, rest.trim())) {
+                ("on", port) => {
+                    let port = match port {
+                        "" => DEFAULT_OSC_PORT,
+                        port => match port.parse() {
+                            Ok(port) => port,
+                            Err(_) => {
+                                cli::print_usage("osc");
+                                continue;
+                            }
+                        },
+                    };
+                    // 開き直すと送り先は引き継ぐ
+                    let targets = osc_remote.take().map(|remote| remote.targets()).unwrap_or_default();
+                    match OscRemote::start(port, synth.clone()) {
+                        Ok(remote) => {
+                            targets.into_iter().for_each(|target| remote.add_target(target));
+                            println!("📡 OSC on: listening on port {}", remote.port());
+                            osc_remote = Some(remote);
+                        }
+                        Err(e) => println!("❌ {}: {}", i18n::tr("Failed to open the OSC port", "OSC のポートを開けませんでした"), e),
+                    }
+                }
+                ("off", "") => {
+                    osc_remote = None;
+                    println!("📡 OSC off");
+                }
+                ("send" | "unsend", target) => {
+                    let Some(remote) = &osc_remote else {
+                        println!("❌ {}", i18n::tr("OSC is off (use `osc on`)", "OSC が無効です（`osc on` で有効にする）"));
+                        continue;
+                    };
+                    let address = match std::net::ToSocketAddrs::to_socket_addrs(target).ok().and_then(|mut addresses| addresses.next()) {
+                        Some(address) => address,
+                        None => {
+                            println!("❌ {}: {}", i18n::tr("Invalid address", "アドレスが正しくありません"), target);
+                            continue;
+                        }
+                    };
+                    if args.starts_with("send") {
+                        remote.add_target(address);
+                        println!("📡 Sending parameter feedback to {}", address);
+                    } else if remote.remove_target(address) {
+                        println!("📡 Stopped sending to {}", address);
+                    } else {
+                        println!("❌ {}: {}", i18n::tr("Not a feedback target", "送り先ではありません"), address);
+                    }
+                }
+                ("", "") => match &osc_remote {
+                    Some(remote) => {
+                        let targets: Vec<String> = remote.targets().iter().map(|target| target.to_string()).collect();
+                        let targets = if targets.is_empty() { "-".to_string() } else { targets.join(", ") };
+                        println!("📡 OSC on: port {}, feedback to {}", remote.port(), targets);
+                    }
+                    None => println!("📡 OSC off"),
+                },
+                _ => cli::print_usage("osc"),
+            },
+            "analog" => with_synth(&synth, |synth| handle_analog_command(synth, args)),
+            "velocity" => with_synth(&synth, |synth| handle_velocity_command(synth, args)),
+            "automate" => with_synth(&synth, |synth| handle_automate_command(synth, args)),
+            "pedal" => {
+                let Some(mut synth) = borrow_synth(&synth) else { continue };
+                match args {
+                    "on" | "off" => synth.set_sustain_pedal(args == "on"),
+                    "" => {}
+                    _ => {
+                        cli::print_usage("pedal");
+                        continue;
+                    }
+                }
+                let state = if synth.sustain_pedal() { "down" } else { "up" };
+                println!("🦶 Sustain pedal {} ({} notes sustained)", state, synth.sustained_notes().len());
+            }
+            "hold" => {
+                let Some(mut synth) = borrow_synth(&synth) else { continue };
+                let hold = match args {
+                    "on" => true,
+                    "off" => false,
+                    "" => !synth.hold(),
+                    _ => {
+                        cli::print_usage("hold");
+                        continue;
+                    }
+                };
+                synth.set_hold(hold);
+                if hold {
+                    let notes: Vec<String> = synth.held_notes().iter().map(|note| note.to_string()).collect();
+                    println!("🧊 Hold on ({} notes latched: {})", notes.len(), notes.join(" "));
+                } else {
+                    println!("🧊 Hold off");
+                }
+            }
+            "panic" => {
+                // 止まったストリームへ送った分は、開き直したあとの最初のブロックで反映する
+                send(&synth, Command::Panic);
+                println!("🛑 Panic: all voices, effect tails and meters cleared");
+                if audio.has_failed() {
+                    match audio.restart() {
+                        Ok(()) => println!("🔁 Audio stream rebuilt"),
+                        Err(e) => eprintln!("❌ {}", i18n::fill(i18n::tr("Failed to rebuild audio: {}", "オーディオを開き直せませんでした: {}"), &[&e])),
+                    }
+                }
+            }
+            "devices" => list_audio_devices(audio),
+            "host" => handle_host_command(audio, args),
+            "audio" => handle_audio_command(audio, args),
+            "stats" => handle_stats_command(audio, args),
+            "device" => handle_device_command(audio, args),
+            "depth" => match args.parse::<f32>() {
+                Ok(depth) => {
+                    if !send_parameter(&synth, "depth", depth) {
+                        continue;
+                    }
+                    println!("🔊 Depth set to: {:.2}", depth.clamp(0.0, 1.0));
+                    if !synth.telemetry().is_surround() {
+                        println!("💡 {}", i18n::tr(
+                            "Depth only applies to quad/5.1 outputs",
+                            "奥行きはクアッド・5.1 出力でのみ使われます",
+                        ));
+                    }
+                }
+                Err(_) => println!("❌ {}", i18n::tr("Depth must be a number between 0 and 1", "奥行きは0〜1の数値で指定してください")),
+            },
+            "crossfeed" => {
+                let Some(mut synth) = borrow_synth(&synth) else { continue };
+                match args {
+                    "on" | "off" => synth.set_crossfeed(args == "on"),
+                    "" => {}
+                    _ => {
+                        cli::print_usage("crossfeed");
+                        continue;
+                    }
+                }
+                println!("🎧 Crossfeed {}", if synth.crossfeed() { "on" } else { "off" });
+            }
+            "binaural" => {
+                let Some(mut synth) = borrow_synth(&synth) else { continue };
+                match args {
+                    "on" | "off" => synth.set_binaural(args == "on"),
+                    "" => {}
+                    _ => {
+                        cli::print_usage("binaural");
+                        continue;
+                    }
+                }
+                println!("🎧 Binaural panning {}", if synth.binaural() { "on" } else { "off" });
+            }
+            "surround" => {
+                let Some(mut synth) = borrow_synth(&synth) else { continue };
+                match args {
+                    "on" | "off" => synth.set_surround(args == "on"),
+                    "" => {}
+                    _ => {
+                        cli::print_usage("surround");
+                        continue;
+                    }
+                }
+                println!("🔊 Output: {} device, mixing as {}", synth.output_layout(), synth.surround_layout());
+            }
+            "width" => match args.parse::<f32>() {
+                Ok(width) => {
+                    if send_parameter(&synth, "width", width) {
+                        println!("↔️  Voice width set to: {:.2}", width.clamp(0.0, 1.0));
+                    }
+                }
+                Err(_) => println!("❌ {}", i18n::tr("Width must be a number between 0 and 1", "幅は0〜1の数値で指定してください")),
+            },
+            "antialias" => {
+                let enabled = match args {
+                    "on" => true,
+                    "off" => false,
+                    _ => {
+                        cli::print_usage("antialias");
+                        continue;
+                    }
+                };
+                let Some(mut synth) = borrow_synth(&synth) else { continue };
+                synth.set_anti_alias(enabled);
+                println!("🛡️  Anti-alias {}", if enabled { "on" } else { "off" });
+            }
+            "spectral" => with_synth(&synth, |synth| handle_spectral_command(synth, args)),
+            "bp" => with_synth(&synth, |synth| handle_breakpoint_command(synth, args)),
+            "lfo" => with_synth(&synth, |synth| handle_lfo_command(synth, args)),
+            "route" => with_synth(&synth, |synth| handle_route_command(synth, args)),
+            "tuning" => with_synth(&synth, |synth| handle_tuning_command(synth, args)),
+            "seq" => with_synth(&synth, |synth| handle_sequencer_command(synth, args)),
+            "patch" => with_synth(&synth, |synth| handle_patch_command(synth, args)),
+            "dx7" => with_synth(&synth, |synth| handle_dx7_command(synth, args)),
+            "session" => with_synth(&synth, |synth| handle_session_command(synth, args)),
+            "autosave" => match args {
+                "on" => {
+                    if autosave.is_none() {
+                        autosave = Some(Autosave::start(cli::autosave_path(), synth.clone(), DEFAULT_AUTOSAVE_INTERVAL));
+                    }
+                    println!("💾 Autosave on ({}, every {} s)", cli::autosave_path().display(), DEFAULT_AUTOSAVE_INTERVAL.as_secs());
+                }
+                "off" => {
+                    // 止めるときはファイルも消す（残すと次の起動で古い状態の復元を勧めてしまう）
+                    if let Some(autosave) = autosave.take() {
+                        autosave.finish();
+                    }
+                    println!("💾 Autosave off");
+                }
+                "" => match &autosave {
+                    Some(autosave) => println!("💾 Autosave on ({})", autosave.path().display()),
+                    None => println!("💾 Autosave off"),
+                },
+                _ => cli::print_usage("autosave"),
+            },
+            "program" => with_synth(&synth, |synth| handle_program_command(synth, args)),
+            "preset" => handle_preset_command(&synth, &mut preset_bank, args),
+            "morph" => with_synth(&synth, |synth| handle_morph_command(synth, &preset_bank, args)),
+            "ab" => with_synth(&synth, |synth| handle_ab_command(synth, &mut ab_compare, args)),
+            "randomize" => with_synth(&synth, |synth| handle_randomize_command(synth, args)),
+            "next" | "prev" => {
+                let offset = if command.name == "next" { 1 } else { -1 };
+                match preset_bank.step(offset) {
+                    Some(index) => load_preset(&synth, &mut preset_bank, index),
+                    None => println!("❌ {}", i18n::tr("The preset bank is empty", "プリセットのバンクは空です")),
+                }
+            }
+            "scene" => handle_scene_command(&synth, args),
+            "drive" => match args.parse::<f32>() {
+                Ok(drive) => {
+                    if send_parameter(&synth, "filter.drive", drive) {
+                        println!("🔥 Filter drive set to: {:.2}", drive.clamp(0.0, 1.0));
+                    }
+                }
+                Err(_) => println!("❌ {}", i18n::tr("Drive must be a number between 0 and 1", "ドライブは0〜1の数値で指定してください")),
+            },
+            "saturate" => match args.parse::<f32>() {
+                Ok(amount) => {
+                    if send_parameter(&synth, "voice.saturation", amount) {
+                        println!("🔥 Voice saturation set to: {:.2}", amount.clamp(0.0, 1.0));
+                    }
+                }
+                Err(_) => println!("❌ {}", i18n::tr("Saturation must be a number between 0 and 1", "飽和量は0〜1の数値で指定してください")),
+            },
+            "gaincomp" => {
+                let enabled = match args {
+                    "on" => true,
+                    "off" => false,
+                    _ => {
+                        cli::print_usage("gaincomp");
+                        continue;
+                    }
+                };
+                let Some(mut synth) = borrow_synth(&synth) else { continue };
+                synth.set_filter_compensation(enabled);
+                println!("🎚️  Filter gain compensation {}", if enabled { "on" } else { "off" });
+            }
+            "trim" => match args.parse::<f32>() {
+                Ok(trim_db) => {
+                    if send_parameter(&synth, "output.trim", trim_db) {
+                        println!("🎚️  Patch output trim set to: {:+.1} dB", trim_db.clamp(-MAX_OUTPUT_TRIM_DB, MAX_OUTPUT_TRIM_DB));
+                    }
+                }
+                Err(_) => println!(
+                    "❌ {}",
+                    i18n::tr("Trim must be a number of dB between -12 and 12", "トリムは-12〜12の dB の数値で指定してください")
+                ),
+            },
+            "invert" => {
+                let Some(mut synth) = borrow_synth(&synth) else { continue };
+                let invert = match args {
+                    "on" => true,
+                    "off" => false,
+                    "" => !synth.patch().output_invert,
+                    _ => {
+                        cli::print_usage("invert");
+                        continue;
+                    }
+                };
+                synth.set_output_invert(invert);
+                println!("🔃 Patch output polarity {}", if invert { "inverted" } else { "normal" });
+            }
+            "status" => match args {
+                "on" if status_line.is_none() => {
+                    status_line = status::StatusLine::start(&synth, STATUS_INTERVAL);
+                    if status_line.is_none() {
+                        println!("❌ {}", i18n::tr("Status line needs an interactive terminal", "ステータス行は対話型の端末でのみ使えます"));
+                    }
+                }
+                "on" => {}
+                "off" => status_line = None,
+                "" => println!("{}", status::StatusSnapshot::capture(&synth.telemetry()).format()),
+                _ => cli::print_usage("status"),
+            },
+            "lang" => match args {
+                "" => println!("🌐 {}", i18n::language()),
+                language => match language.parse::<i18n::Language>() {
+                    Ok(language) => {
+                        i18n::set_language(language);
+                        println!("🌐 {}", language);
+                    }
+                    Err(_) => cli::print_usage("lang"),
+                },
+            },
+            "phrase" => handle_phrase_command(&mut phrase_settings, args),
+            "demo" => {
+                println!("🎬 Playing the demo...");
+                play_demo(&synth);
+            }
+            "bend" => match args.strip_prefix("range") {
+                Some(range) => with_synth(&synth, |synth| handle_bend_range_command(synth, range.trim())),
+                None => with_synth(&synth, |synth| handle_controller_command(synth, Controller::PitchBend, args)),
+            },
+            "mod" => with_synth(&synth, |synth| handle_controller_command(synth, Controller::ModWheel, args)),
+            "at" => with_synth(&synth, |synth| handle_controller_command(synth, Controller::Aftertouch, args)),
+            "bloom" => {
+                let Some(mut synth) = borrow_synth(&synth) else { continue };
+                match args {
+                    "mod" => synth.set_bloom_source(Some(ModSource::ModWheel)),
+                    "at" => synth.set_bloom_source(Some(ModSource::Aftertouch)),
+                    "off" => synth.set_bloom_source(None),
+                    "" => {}
+                    _ => {
+                        cli::print_usage("bloom");
+                        continue;
+                    }
+                }
+                match synth.bloom_source() {
+                    Some(source) => println!("🌸 Harmonic bloom on '{}'", source),
+                    None => println!("🌸 Harmonic bloom off"),
+                }
+            }
+            "curve" => with_synth(&synth, |synth| show_filter_curve(synth)),
+            "test" => with_synth(&synth, |synth| handle_test_signal_command(synth, args)),
+            // 書き出しはコピーで行い、その間は Synthesizer を借りたままにしない
+            "bounce" => {
+                let Some(copy) = borrow_synth(&synth).map(|synth| synth.offline_copy()) else { continue };
+                handle_bounce_command(&copy, args);
+            }
+            "record" => match args.split_whitespace().collect::<Vec<_>>().as_slice() {
+                ["start", path, bits @ ..] if bits.len() <= 1 => {
+                    let Some(format) = export_format(path, bits.first().copied()) else {
+                        continue;
+                    };
+                    // 録音中なら先に今のファイルを閉じる
+                    if let Some(previous) = recording.take() {
+                        let _ = previous.stop();
+                    }
+                    match Recording::start(path, synth.clone(), format) {
+                        Ok(started) => {
+                            println!("⏺️  Recording to {} ({})", started.path().display(), format);
+                            recording = Some(started);
+                        }
+                        Err(e) => println!("❌ {}", i18n::fill(i18n::tr("Failed to start recording: {}", "録音を開始できませんでした: {}"), &[&e])),
+                    }
+                }
+                ["stop"] => match recording.take().map(|recording| (recording.path().to_path_buf(), recording.stop())) {
+                    Some((path, Ok(summary))) => {
+                        println!("⏹️  Wrote {} ({:.1} s)", path.display(), summary.seconds);
+                        if summary.dropped > 0 {
+                            println!("⚠️  {}", i18n::fill(i18n::tr("{} frames were dropped (the disk could not keep up)", "{} フレームを落としました（ディスクの書き込みが追いつきませんでした）"), &[&summary.dropped]));
+                        }
+                    }
+                    Some((path, Err(e))) => println!("❌ {}", i18n::fill(i18n::tr("Failed to write {}: {}", "{} を書き出せませんでした: {}"), &[&path.display(), &e])),
+                    None => println!("⏹️  Not recording"),
+                },
+                [] => match &recording {
+                    Some(recording) => println!("⏺️  Recording to {}", recording.path().display()),
+                    None => println!("⏹️  Not recording"),
+                },
+                _ => cli::print_usage("record"),
+            },
+            "render" => {
+                let mut parts: Vec<&str> = args.split_whitespace().collect();
+                let Ok(normalize) = take_normalize(&mut parts) else {
+                    cli::print_usage("render");
+                    continue;
+                };
+                match parts.as_slice() {
+                    [path, seconds, bits @ ..] if bits.len() <= 1 => match seconds.parse::<f32>() {
+                        Ok(seconds) if seconds > 0.0 => {
+                            let Some(format) = export_format(path, bits.first().copied()) else {
+                                continue;
+                            };
+                            // 書き出しはオフラインのコピーで行うので、その間も演奏は止まらない
+                            let Some(mut copy) = borrow_synth(&synth).map(|synth| synth.offline_snapshot()) else { continue };
+                            match render_to_file(&mut copy, path, seconds, format, normalize) {
+                                Ok(report) => {
+                                    println!("💾 Wrote {} ({}, {:.2} s)", path, format, report.seconds);
+                                    print_loudness(&report);
+                                }
+                                Err(e) => println!("❌ {}", i18n::fill(i18n::tr("Render failed: {}", "書き出しに失敗しました: {}"), &[&e])),
+                            }
+                        }
+                        _ => println!("❌ {}", i18n::tr("Duration must be greater than 0", "持続時間は0より大きくしてください")),
+                    },
+                    _ => cli::print_usage("render"),
+                }
+            }
+            "threads" => match args.parse::<usize>() {
+                Ok(threads) if (1..=16).contains(&threads) => {
+                    let Some(mut synth) = borrow_synth(&synth) else { continue };
+                    synth.set_render_threads(threads);
+                    println!("🧵 Rendering voices on {} thread(s)", synth.render_threads());
+                }
+                _ => println!("❌ {}", i18n::tr("Thread count must be 1-16", "スレッド数は1〜16で指定してください")),
+            },
+            "quality" => {
+                let Some(mut synth) = borrow_synth(&synth) else { continue };
+                match args {
+                    "" => {}
+                    "auto" => synth.set_adaptive_quality(true),
+                    "off" => synth.set_adaptive_quality(false),
+                    _ => {
+                        cli::print_usage("quality");
+                        continue;
+                    }
+                }
+                let quality = synth.quality();
+                let level = quality.level();
+                println!("⚙️  DSP load {:.0}%, quality level {}/{} ({}), harmonics {}, stack {}, polyphony x{:.2}",
+                    quality.load() * 100.0,
+                    quality.level_index(),
+                    quality.policy().levels.len().saturating_sub(1),
+                    if quality.policy().enabled { "auto" } else { "fixed" },
+                    level.max_harmonics.min(HARMONIC_COUNT),
+                    level.max_stack.min(MAX_STACK_DENSITY),
+                    level.polyphony_scale);
+            }
+            "taps" => with_synth(&synth, |synth| handle_taps_command(synth, args)),
+            "metro" => with_synth(&synth, |synth| handle_metronome_command(synth, args)),
+            "duck" => with_synth(&synth, |synth| handle_duck_command(synth, args)),
+            "layer" => with_synth(&synth, |synth| handle_layer_command(synth, args)),
+            "latency" => {
+                let Some(mut synth) = borrow_synth(&synth) else { continue };
+                if !args.is_empty() {
+                    match args.parse::<f32>() {
+                        Ok(milliseconds) => synth.set_latency_offset(milliseconds),
+                        Err(_) => println!("❌ {}", i18n::tr("Latency offset must be a number of milliseconds", "レイテンシの補正はミリ秒の数値で指定してください")),
+                    }
+                }
+                println!("⏱️  Output latency: {:.1} ms (including offset)", synth.output_latency_seconds() * 1000.0);
+            }
+            "beat" => match args {
+                "" => show_beat_indicator(&synth, 2),
+                bars => match bars.parse::<u32>() {
+                    Ok(bars) if bars > 0 => show_beat_indicator(&synth, bars),
+                    _ => cli::print_usage("beat"),
+                },
+            },
+            "tempo" => match args.parse::<f32>() {
+                Ok(bpm) if bpm > 0.0 => {
+                    if send(&synth, Command::Tempo(bpm)) {
+                        println!("🥁 Tempo set to: {:.1} BPM", bpm.clamp(20.0, 300.0));
+                    }
+                }
+                _ => println!("❌ {}", i18n::tr("Tempo must be a positive number", "テンポは正の数で指定してください")),
+            },
+            // カスタム持続時間の処理
+            note @ ("C" | "D" | "E" | "F" | "G" | "A" | "B" | "H" | "CHORD") => match args.parse::<f32>() {
                 Ok(duration) if duration > 0.0 => {
-                    let mut synth = synth.lock().unwrap();
+                    let note_for = |note, velocity| send(&synth, Command::NoteFor { note, velocity, seconds: duration });
                     match note {
                         "C" => {
-                            synth.note_on_with_duration(60, 0.8, duration);
+                            note_for(60, 0.8);
                             println!("🎵 Note ON: Middle C (60) for {:.1} seconds", duration);
                         }
                         "D" => {
-                            synth.note_on_with_duration(62, 0.75, duration);
+                            note_for(62, 0.75);
                             println!("🎵 Note ON: D (62) for {:.1} seconds", duration);
                         }
                         "E" => {
-                            synth.note_on_with_duration(64, 0.7, duration);
+                            note_for(64, 0.7);
                             println!("🎵 Note ON: E (64) for {:.1} seconds", duration);
                         }
                         "F" => {
-                            synth.note_on_with_duration(65, 0.65, duration);
+                            note_for(65, 0.65);
                             println!("🎵 Note ON: F (65) for {:.1} seconds", duration);
                         }
                         "G" => {
-                            synth.note_on_with_duration(67, 0.6, duration);
+                            note_for(67, 0.6);
                             println!("🎵 Note ON: G (67) for {:.1} seconds", duration);
                         }
                         "A" => {
-                            synth.note_on_with_duration(69, 0.55, duration);
+                            note_for(69, 0.55);
                             println!("🎵 Note ON: A (69) for {:.1} seconds", duration);
                         }
                         "B" => {
-                            synth.note_on_with_duration(71, 0.5, duration);
+                            note_for(71, 0.5);
                             println!("🎵 Note ON: B (71) for {:.1} seconds", duration);
                         }
                         "H" => {
-                            synth.note_on_with_duration(72, 0.5, duration);
+                            note_for(72, 0.5);
                             println!("🎵 Note ON: High C (72) for {:.1} seconds", duration);
                         }
-                        "CHORD" => {
-                            synth.note_on_with_duration(60, 0.8, duration);
-                            synth.note_on_with_duration(64, 0.7, duration);
-                            synth.note_on_with_duration(67, 0.6, duration);
-                            println!("🎵 Chord ON: C-E-G for {:.1} seconds", duration);
-                        }
-                        "SCALE" => {
-                            let notes = [60, 62, 64, 65, 67, 69, 71, 72]; // C-D-E-F-G-A-B-C
-                            let velocities = [0.8, 0.75, 0.7, 0.65, 0.6, 0.55, 0.5, 0.5];
-                            for (note, velocity) in notes.iter().zip(velocities.iter()) {
-                                synth.note_on_with_duration(*note, *velocity, duration);
-                            }
-                            println!("🎵 Scale ON: C-D-E-F-G-A-B-C for {:.1} seconds", duration);
-                        }
                         _ => {
-                            println!("❓ Unknown note: {}", note);
+                            let notes = build_phrase(PhraseKind::Chord, &CHORD_NOTES, duration, &phrase_settings, &mut phrase_rng);
+                            play_phrase(&synth, &notes);
+                            println!("🎵 Chord ON: C-E-G for {:.1} seconds ({} notes, {})", duration, notes.len(), describe_phrase(&phrase_settings));
                         }
                     }
                 }
                 Ok(_) => {
-                    println!("❌ Duration must be greater than 0");
+                    println!("❌ {}", i18n::tr("Duration must be greater than 0", "持続時間は0より大きくしてください"));
                 }
+                Err(_) if args.is_empty() => cli::print_command_help(note),
                 Err(_) => {
-                    println!("❌ Invalid duration format. Use numbers like 2.5, 1.8, etc.");
+                    println!("❌ {}", i18n::tr(
+                        "Invalid duration format. Use numbers like 2.5, 1.8, etc.",
+                        "持続時間の形式が正しくありません。2.5 や 1.8 のような数値を使ってください",
+                    ));
                 }
-            }
-            continue;
-        }
-        
-        match input {
+            },
+            "SCALE" => handle_scale_command(&synth, args, &phrase_settings, &mut phrase_rng),
             "c" => {
-                let mut synth = synth.lock().unwrap();
-                synth.note_on(60, 0.8); // Middle C
-                println!("🎵 Note ON: Middle C (60)");
+                if send(&synth, Command::Event(Event::NoteOn { note: 60, velocity: 0.8 })) {
+                    println!("🎵 Note ON: Middle C (60)");
+                }
             }
             "d" => {
-                let mut synth = synth.lock().unwrap();
-                synth.note_on(62, 0.75); // D
-                println!("🎵 Note ON: D (62)");
+                if send(&synth, Command::Event(Event::NoteOn { note: 62, velocity: 0.75 })) {
+                    println!("🎵 Note ON: D (62)");
+                }
             }
             "e" => {
-                let mut synth = synth.lock().unwrap();
-                synth.note_on(64, 0.7); // E
-                println!("🎵 Note ON: E (64)");
+                if send(&synth, Command::Event(Event::NoteOn { note: 64, velocity: 0.7 })) {
+                    println!("🎵 Note ON: E (64)");
+                }
             }
             "f" => {
-                let mut synth = synth.lock().unwrap();
-                synth.note_on(65, 0.65); // F
-                println!("🎵 Note ON: F (65)");
+                if send(&synth, Command::Event(Event::NoteOn { note: 65, velocity: 0.65 })) {
+                    println!("🎵 Note ON: F (65)");
+                }
             }
             "g" => {
-                let mut synth = synth.lock().unwrap();
-                synth.note_on(67, 0.6); // G
-                println!("🎵 Note ON: G (67)");
+                if send(&synth, Command::Event(Event::NoteOn { note: 67, velocity: 0.6 })) {
+                    println!("🎵 Note ON: G (67)");
+                }
             }
             "a" => {
-                let mut synth = synth.lock().unwrap();
-                synth.note_on(69, 0.55); // A
-                println!("🎵 Note ON: A (69)");
+                if send(&synth, Command::Event(Event::NoteOn { note: 69, velocity: 0.55 })) {
+                    println!("🎵 Note ON: A (69)");
+                }
             }
             "b" => {
-                let mut synth = synth.lock().unwrap();
-                synth.note_on(71, 0.5); // B
-                println!("🎵 Note ON: B (71)");
+                if send(&synth, Command::Event(Event::NoteOn { note: 71, velocity: 0.5 })) {
+                    println!("🎵 Note ON: B (71)");
+                }
             }
             "s" => {
-                let mut synth = synth.lock().unwrap();
-                // Stop all active notes
-                let active_notes: Vec<u8> = synth.voices.keys().cloned().collect();
-                for note in active_notes {
-                    synth.note_off(note);
+                if send(&synth, Command::Event(Event::AllNotesOff)) {
+                    println!("🔇 All notes stopped");
                 }
-                println!("🔇 All notes stopped");
             }
             "p" => {
-                let synth = synth.lock().unwrap();
-                let active_voices: Vec<u8> = synth.voices.iter()
-                    .filter(|(_, voice)| voice.is_active())
-                    .map(|(note, _)| *note)
-                    .collect();
-                if active_voices.is_empty() {
+                let (voices, held) = {
+                    let Some(synth) = borrow_synth(&synth) else { continue };
+                    (synth.voice_info(), synth.active_notes())
+                };
+                if voices.is_empty() {
                     println!("📊 No active voices");
                 } else {
-                    println!("📊 Active voices: {:?}", active_voices);
+                    let names: Vec<String> = held.iter().map(|note| note_name(*note)).collect();
+                    match detect_chord(&held) {
+                        Some(chord) => println!("🎼 {} ({})", chord, names.join(" ")),
+                        None if !held.is_empty() => println!("🎼 {}", names.join(" ")),
+                        None => {}
+                    }
+                    println!("📊 Voices:");
+                    for voice in voices {
+                        println!(
+                            "   {:3} {:8.2} Hz  {:?} env={:.2} age={} peak={:.3}",
+                            voice.note,
+                            voice.frequency,
+                            voice.stage,
+                            voice.envelope_level,
+                            voice.age_samples,
+                            voice.peak_output
+                        );
+                        // 合わせた後のモジュレーション値（0 でない送り先だけ）
+                        let modulation: Vec<String> = ModDestination::ALL.into_iter()
+                            .filter(|destination| voice.modulation.get(*destination) != 0.0)
+                            .map(|destination| format!("{}={:+.3}", destination, voice.modulation.get(destination)))
+                            .collect();
+                        if !modulation.is_empty() {
+                            println!("       mod {}", modulation.join(" "));
+                        }
+                    }
+                }
+            }
+            "log" => {
+                match event_log.as_mut() {
+                    Some(reader) => {
+                        let entries = reader.drain();
+                        if entries.is_empty() {
+                            println!("📜 No new audio events");
+                        }
+                        for entry in entries {
+                            println!("📜 [{:>10}] {}", entry.sample, entry.event);
+                        }
+                        if reader.dropped() > 0 {
+                            println!("⚠️  {} events dropped (log full)", reader.dropped());
+                        }
+                    }
+                    None => println!("❌ {}", i18n::tr("Event log not available", "イベントログを利用できません")),
                 }
             }
             "q" => {
@@ -226,38 +1223,1801 @@ fn interactive_control(synth: Arc<Mutex<synth::Synthesizer>>, _audio: &mut audio
                 break;
             }
             "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9" => {
-                let blend = (input.parse::<f32>().unwrap() - 1.0) / 8.0;
-                let mut synth = synth.lock().unwrap();
-                synth.set_blend(blend);
-                println!("🎛️  Blend set to: {:.2}", blend);
+                let blend = (command.name.parse::<f32>().unwrap() - 1.0) / 8.0;
+                if send_parameter(&synth, "blend", blend) {
+                    println!("🎛️  Blend set to: {:.2}", blend);
+                }
             }
+            "env" if !args.is_empty() => with_synth(&synth, |synth| handle_envelope_command(synth, args)),
             "env" => {
-                let mut synth = synth.lock().unwrap();
-                synth.set_attack(0.1);
-                synth.set_decay(0.2);
-                synth.set_sustain(0.7);
-                synth.set_release(0.3);
-                println!("🎚️  Envelope adjusted");
+                let values = [("env.attack", 0.1), ("env.decay", 0.2), ("env.sustain", 0.7), ("env.release", 0.3)];
+                if values.into_iter().all(|(name, value)| send_parameter(&synth, name, value)) {
+                    println!("🎚️  Envelope adjusted");
+                }
             }
+            "glide" => with_synth(&synth, |synth| handle_glide_command(synth, args)),
+            "voices" => with_synth(&synth, |synth| handle_voices_command(synth, args)),
             "filter" => {
-                let mut synth = synth.lock().unwrap();
-                synth.set_cutoff(0.5);
-                synth.set_resonance(0.3);
-                println!("🔊 Filter adjusted");
+                if send_parameter(&synth, "cutoff", 0.5) && send_parameter(&synth, "resonance", 0.3) {
+                    println!("🔊 Filter adjusted");
+                }
             }
             _ => {
-                println!("❓ Unknown command. Type 'c', 'd', 'e', 'f', 'g', 'a', 'b', 's', 'p', 'q', '1-9', 'env', 'filter', or custom duration like 'C 2.5'");
+                match command.spec() {
+                    Some(_) => cli::print_usage(command.name),
+                    None => cli::print_unknown(command.name),
+                }
             }
         }
     }
+    
+    drop(status_line);
+    if let Some(autosave) = autosave {
+        autosave.finish();
+    }
+    if let Err(e) = editor.save_history(&history_path) {
+        eprintln!("⚠️  Failed to save command history: {}", e);
+    }
 }
 
-// カスタム持続時間のパース関数
-fn parse_custom_duration(input: &str) -> Option<(&str, &str)> {
-    let parts: Vec<&str> = input.split_whitespace().collect();
-    if parts.len() == 2 {
-        Some((parts[0], parts[1]))
+fn handle_lfo_command(synth: &mut Synthesizer, args: &str) {
+    let mut lfo = synth.lfo();
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let valid = match parts.as_slice() {
+        [] => true,
+        ["rate", rate] => rate.parse().map(|rate| lfo.rate = rate).is_ok(),
+        ["shape", shape] => shape.parse().map(|shape| lfo.shape = shape).is_ok(),
+        // 音符の長さで指定すると拍に切り替える（数値は今の単位のまま）
+        ["fade", fade] => match parse_division(fade) {
+            Some(beats) => {
+                lfo.fade = beats;
+                lfo.fade_sync = true;
+                true
+            }
+            None => fade.parse().map(|fade| lfo.fade = fade).is_ok(),
+        },
+        ["fadesync", "on" | "off"] => {
+            let fade_sync = parts[1] == "on";
+            if fade_sync != lfo.fade_sync {
+                lfo.fade = if fade_sync { lfo.fade * synth.tempo() / 60.0 } else { lfo.fade_seconds(synth.tempo()) };
+                lfo.fade_sync = fade_sync;
+            }
+            true
+        }
+        ["oneshot", "on" | "off"] => {
+            lfo.one_shot = parts[1] == "on";
+            true
+        }
+        ["sync", "on" | "off"] => {
+            lfo.sync = parts[1] == "on";
+            true
+        }
+        _ => false,
+    };
+    if !valid {
+        cli::print_usage("lfo");
+        return;
+    }
+    if !parts.is_empty() {
+        synth.set_lfo(lfo);
+    }
+    
+    let lfo = synth.lfo();
+    let mode = if lfo.one_shot { "one-shot" } else { "free" };
+    let rate = if lfo.sync {
+        format!("{:.2} per beat ({:.2} Hz)", lfo.rate, lfo.frequency(synth.tempo()))
     } else {
-        None
+        format!("{:.2} Hz", lfo.rate)
+    };
+    let fade = if lfo.fade_sync {
+        format!("{:.2} beats ({:.2} s)", lfo.fade, lfo.fade_seconds(synth.tempo()))
+    } else {
+        format!("{:.2} s", lfo.fade)
+    };
+    println!("🌊 LFO: {} {}, fade {}, {}", lfo.shape, rate, fade, mode);
+    if !synth.mod_routings().iter().any(|routing| routing.source == ModSource::Lfo) {
+        println!("ℹ️  {}", i18n::tr(
+            "No routing uses 'lfo' yet (e.g. 'route lfo pitch 0.3')",
+            "'lfo' を使うルーティングがまだありません (例: 'route lfo pitch 0.3')",
+        ));
+    }
+}
+
+// env attack|decay|release <時間> | sustain <0-1> | sync on|off。
+// 時間は今の単位（秒または拍）の数値か音符の長さ（"1/8" など、指定するとテンポ同期に切り替える）
+fn handle_envelope_command(synth: &mut Synthesizer, args: &str) {
+    let tempo = synth.tempo();
+    let mut envelope = synth.patch().envelope;
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let valid = match parts.as_slice() {
+        [stage @ ("attack" | "decay" | "release"), time] => {
+            let time = match parse_division(time) {
+                Some(beats) => {
+                    envelope = envelope.with_tempo_sync(true, tempo);
+                    Some(beats)
+                }
+                None => time.parse::<f32>().ok().filter(|time| *time >= 0.0),
+            };
+            match (*stage, time) {
+                ("attack", Some(time)) => envelope.attack = time,
+                ("decay", Some(time)) => envelope.decay = time,
+                ("release", Some(time)) => envelope.release = time,
+                _ => {}
+            }
+            time.is_some()
+        }
+        ["sustain", level] => level.parse::<f32>().map(|level| envelope.sustain = level.clamp(0.0, 1.0)).is_ok(),
+        ["sync", "on" | "off"] => {
+            envelope = envelope.with_tempo_sync(parts[1] == "on", tempo);
+            true
+        }
+        _ => false,
+    };
+    if !valid {
+        cli::print_usage("env");
+        return;
+    }
+    synth.set_envelope(envelope);
+    
+    let unit = if envelope.tempo_sync { "beats" } else { "s" };
+    println!("🎚️  Envelope: attack {:.3} {unit}, decay {:.3} {unit}, sustain {:.2}, release {:.3} {unit}",
+        envelope.attack, envelope.decay, envelope.sustain, envelope.release);
+    if envelope.tempo_sync {
+        println!("⏱️  At {:.1} BPM: release {:.3} s", tempo, envelope.release_seconds(tempo));
+    }
+}
+
+// glide [<秒>|off] [last|nearest]。引数なしで現在の設定を表示する
+fn handle_glide_command(synth: &mut Synthesizer, args: &str) {
+    let (mut time, mut nearest) = (synth.patch().glide_time, synth.patch().glide_nearest);
+    for part in args.split_whitespace() {
+        match part {
+            "off" => time = 0.0,
+            "last" => nearest = false,
+            "nearest" => nearest = true,
+            value => match value.parse::<f32>() {
+                Ok(seconds) if seconds >= 0.0 => time = seconds,
+                _ => {
+                    cli::print_usage("glide");
+                    return;
+                }
+            },
+        }
+    }
+    synth.set_glide(time, nearest);
+    let patch = synth.patch();
+    if patch.glide_time > 0.0 {
+        let source = if patch.glide_nearest { "nearest held voice" } else { "last released voice" };
+        println!("🛝 Glide: {:.3} s from the {}", patch.glide_time, source);
+    } else {
+        println!("🛝 Glide off");
+    }
+}
+
+// パラフォニックで指定が無いときに共有するノート数
+const DEFAULT_PARAPHONIC_NOTES: usize = 4;
+
+fn handle_voices_command(synth: &mut Synthesizer, args: &str) {
+    use synthesizer::{StealPolicy, VoiceMode};
+    
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let mode = match parts.as_slice() {
+        [] => None,
+        ["limit", voices] => match voices.parse::<usize>() {
+            Ok(voices) if voices > 0 => {
+                synth.set_polyphony(voices);
+                None
+            }
+            _ => {
+                cli::print_usage("voices");
+                return;
+            }
+        },
+        ["steal", policy] => {
+            let policy = match *policy {
+                "oldest" => StealPolicy::Oldest,
+                "quietest" => StealPolicy::Quietest,
+                "same" => StealPolicy::SameNote,
+                _ => {
+                    cli::print_usage("voices");
+                    return;
+                }
+            };
+            synth.set_steal_policy(policy);
+            None
+        }
+        ["poly"] => Some(VoiceMode::Poly),
+        ["mono"] => Some(VoiceMode::Mono),
+        ["para"] => Some(VoiceMode::Paraphonic(DEFAULT_PARAPHONIC_NOTES)),
+        ["para", notes] => match notes.parse::<usize>() {
+            Ok(notes) if notes > 0 => Some(VoiceMode::Paraphonic(notes)),
+            _ => {
+                cli::print_usage("voices");
+                return;
+            }
+        },
+        _ => {
+            cli::print_usage("voices");
+            return;
+        }
+    };
+    if let Some(mode) = mode {
+        synth.set_voice_mode(mode);
+    }
+    match synth.voice_mode() {
+        VoiceMode::Poly => println!("🎹 Voice mode: poly"),
+        VoiceMode::Mono => println!("🎹 Voice mode: mono"),
+        VoiceMode::Paraphonic(notes) => {
+            println!("🎹 Voice mode: paraphonic, up to {} notes through one filter and amp envelope", notes);
+        }
+    }
+    let policy = match synth.steal_policy() {
+        StealPolicy::Oldest => "oldest voice",
+        StealPolicy::Quietest => "quietest voice",
+        StealPolicy::SameNote => "voice with the same note name (or the oldest)",
+    };
+    println!("🎹 Up to {} voices; a new note beyond that steals the {}, releasing voices first", synth.polyphony(), policy);
+}
+
+// ブレークポイントエンベロープの編集コマンド
+fn handle_breakpoint_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let index = match parts.first().and_then(|n| n.parse::<usize>().ok()) {
+        Some(n @ 1..=4) => n - 1,
+        _ => {
+            cli::print_usage("bp");
+            return;
+        }
+    };
+    
+    let result = match parts.get(1..).unwrap_or_default() {
+        [] => Ok(()),
+        ["add", time, level, rest @ ..] => {
+            let curve = rest.first().map_or(Ok(0.0), |c| c.parse::<f32>());
+            match (time.parse::<f32>(), level.parse::<f32>(), curve) {
+                (Ok(time), Ok(level), Ok(curve)) => {
+                    synth.edit_breakpoint_envelope(index, |env| env.add_point(time, level, curve));
+                    Ok(())
+                }
+                _ => Err(i18n::tr("time, level and curve must be numbers", "時間・レベル・カーブは数値で指定してください")),
+            }
+        }
+        ["loop", "off"] => {
+            synth.edit_breakpoint_envelope(index, |env| env.loop_points = None);
+            Ok(())
+        }
+        ["loop", start, end] => match (start.parse::<usize>(), end.parse::<usize>()) {
+            (Ok(start), Ok(end)) => {
+                if synth.edit_breakpoint_envelope(index, |env| env.set_loop(start, end)) == Some(true) {
+                    Ok(())
+                } else {
+                    Err(i18n::tr(
+                        "loop needs start < end within the points and a non-zero length",
+                        "ループは開始 < 終了 をポイントの範囲内で、長さが0にならないように指定してください",
+                    ))
+                }
+            }
+            _ => Err(i18n::tr("loop points must be point indices", "ループ位置はポイントの番号で指定してください")),
+        },
+        ["sync", "on"] => {
+            synth.edit_breakpoint_envelope(index, |env| env.tempo_sync = true);
+            Ok(())
+        }
+        ["sync", "off"] => {
+            synth.edit_breakpoint_envelope(index, |env| env.tempo_sync = false);
+            Ok(())
+        }
+        ["clear"] => {
+            synth.edit_breakpoint_envelope(index, |env| env.clear());
+            Ok(())
+        }
+        _ => {
+            cli::print_usage("bp");
+            return;
+        }
+    };
+    
+    match result {
+        Ok(()) => {
+            if let Some(env) = synth.breakpoint_envelope(index) {
+                let unit = if env.tempo_sync { "beats" } else { "s" };
+                println!("📈 bp{} ({} points, loop {:?}):", index + 1, env.points.len(), env.loop_points);
+                for (i, point) in env.points.iter().enumerate() {
+                    println!("   {}: +{:.3}{} -> {:.3} (curve {:.2})", i, point.time, unit, point.level, point.curve);
+                }
+            }
+        }
+        Err(message) => println!("❌ {}", message),
+    }
+}
+
+// モジュレーションマトリクスのコマンド
+fn handle_route_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    match parts.as_slice() {
+        [] => {}
+        ["clear"] => synth.clear_mod_routings(),
+        ["combine", destination, mode] => match destination.parse::<ModDestination>().and_then(|destination| Ok((destination, mode.parse::<ModCombine>()?))) {
+            Ok((destination, mode)) => synth.set_mod_combine(destination, mode),
+            Err(message) => {
+                println!("❌ {}", message);
+                return;
+            }
+        },
+        ["seed", seed] => match seed.parse::<u64>() {
+            Ok(seed) => {
+                synth.set_note_random_seed(seed);
+                println!("🎲 Per-note random seed {}", seed);
+                return;
+            }
+            Err(_) => {
+                cli::print_usage("route");
+                return;
+            }
+        },
+        [source, destination, amount, zone @ ..] => {
+            let routing = source.parse::<ModSource>().and_then(|source| {
+                let destination = destination.parse::<ModDestination>()?;
+                let amount = amount.parse::<f32>().map_err(|_| i18n::fill(i18n::tr("invalid amount: {}", "量が正しくありません: {}"), &[amount]))?;
+                let zone = RoutingZone::parse(zone)?;
+                Ok(ModRouting { source, destination, amount, zone })
+            });
+            match routing {
+                Ok(routing) => synth.add_mod_routing(routing),
+                Err(message) => {
+                    println!("❌ {}", message);
+                    return;
+                }
+            }
+        }
+        _ => {
+            cli::print_usage("route");
+            return;
+        }
+    }
+    
+    let routings = synth.mod_routings();
+    if routings.is_empty() {
+        println!("🔀 No modulation routings");
+    }
+    for routing in routings {
+        println!("🔀 {} -> {} ({:+.3}){}", routing.source, routing.destination, routing.amount, routing.zone);
+    }
+    for destination in ModDestination::ALL {
+        let mode = synth.mod_combine(destination);
+        if mode != ModCombine::Sum {
+            println!("🔀 {} combines by {}", destination, mode);
+        }
+    }
+}
+
+// 音律のコマンド
+fn handle_tuning_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let current = synth.tuning().clone();
+    let result = match parts.as_slice() {
+        [] => Ok(current),
+        ["edo", divisions] => match divisions.parse::<usize>() {
+            Ok(divisions) if divisions > 0 => {
+                let mut tuning = Tuning::edo(divisions);
+                tuning.reference_frequency = current.reference_frequency;
+                tuning.offsets = current.offsets;
+                Ok(tuning)
+            }
+            _ => Err(i18n::tr("EDO divisions must be a positive integer", "平均律の分割数は正の整数で指定してください").to_string()),
+        },
+        ["scl", path] => Tuning::load_scala(path)
+            .map(|mut tuning| {
+                tuning.reference_frequency = current.reference_frequency;
+                tuning.offsets = current.offsets;
+                tuning
+            })
+            .map_err(|e| i18n::fill(i18n::tr("Failed to load {}: {}", "{} を読み込めませんでした: {}"), &[path, &e])),
+        ["offset", note, cents] => match (note.parse::<u8>(), cents.parse::<f32>()) {
+            (Ok(note), Ok(cents)) if note < 128 => {
+                let mut tuning = current;
+                tuning.set_offset(note, cents);
+                Ok(tuning)
+            }
+            _ => Err(i18n::tr("Usage: tuning offset <note 0-127> <cents>", "使い方: tuning offset <ノート 0-127> <セント>").to_string()),
+        },
+        ["offsets", "clear"] => {
+            let mut tuning = current;
+            tuning.clear_offsets();
+            Ok(tuning)
+        }
+        ["ref", hz] => match hz.parse::<f32>() {
+            Ok(hz) if hz > 0.0 => {
+                let mut tuning = current;
+                tuning.reference_frequency = hz;
+                Ok(tuning)
+            }
+            _ => Err(i18n::tr("Reference frequency must be positive", "基準周波数は正の数で指定してください").to_string()),
+        },
+        _ => {
+            cli::print_usage("tuning");
+            return;
+        }
+    };
+    
+    match result {
+        Ok(tuning) => {
+            synth.set_tuning(tuning);
+            let tuning = synth.tuning();
+            let offsets = tuning.offsets.iter().filter(|cents| **cents != 0.0).count();
+            println!(
+                "🎼 Tuning: {} notes per period ({:.1} cents), A4 = {:.2} Hz, {} note offsets",
+                tuning.scale.len(),
+                tuning.scale.last().copied().unwrap_or(1200.0),
+                tuning.frequency(69),
+                offsets
+            );
+        }
+        Err(message) => println!("❌ {}", message),
+    }
+}
+
+// シーケンサーのコマンド
+fn handle_sequencer_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let track_index = |value: &str| value.parse::<usize>().ok().and_then(|n| n.checked_sub(1));
+    
+    let result: Result<(), String> = match parts.as_slice() {
+        [] => Ok(()),
+        ["play"] => {
+            synth.play_sequencer();
+            Ok(())
+        }
+        ["stop"] => {
+            synth.stop_sequencer();
+            Ok(())
+        }
+        ["clear"] => {
+            synth.clear_sequencer();
+            Ok(())
+        }
+        ["add", length, division] => match (length.parse::<usize>(), division.parse::<u32>()) {
+            (Ok(length), Ok(division)) if length > 0 && division > 0 => {
+                synth.sequencer_mut().add_track(Track::new(length, division));
+                Ok(())
+            }
+            _ => Err(i18n::tr("Usage: seq add <steps> <steps per beat>", "使い方: seq add <ステップ数> <拍あたりの分割>").to_string()),
+        },
+        ["notes", track, notes @ ..] => {
+            let steps: Result<Vec<Option<u8>>, String> = notes.iter()
+                .map(|note| match *note {
+                    "-" => Ok(None),
+                    note => note.parse::<u8>().ok().filter(|n| *n < 128).map(Some)
+                        .ok_or_else(|| i18n::fill(i18n::tr("invalid note: {}", "ノートが正しくありません: {}"), &[&note])),
+                })
+                .collect();
+            match (track_index(track).and_then(|i| synth.sequencer_mut().track_mut(i)), steps) {
+                (Some(track), Ok(steps)) => {
+                    track.set_length(steps.len());
+                    for (index, note) in steps.into_iter().enumerate() {
+                        if let Some(step) = track.step_mut(index) {
+                            step.note = note;
+                        }
+                    }
+                    Ok(())
+                }
+                (None, _) => Err(i18n::fill(i18n::tr("no such track: {}", "トラック {} はありません"), &[track])),
+                (_, Err(message)) => Err(message),
+            }
+        }
+        [command @ ("prob" | "ratchet" | "every"), track, step, values @ ..] => {
+            let step_index = step.parse::<usize>().ok().and_then(|n| n.checked_sub(1));
+            let target = track_index(track)
+                .and_then(|i| synth.sequencer_mut().track_mut(i))
+                .and_then(|track| step_index.and_then(|i| track.step_mut(i)));
+            match (target, *command, values) {
+                (None, _, _) => Err(i18n::fill(i18n::tr("no such step: track {} step {}", "トラック {} のステップ {} はありません"), &[track, step])),
+                (Some(step), "prob", [value]) => match value.parse::<f32>() {
+                    Ok(p) if (0.0..=1.0).contains(&p) => {
+                        step.probability = p;
+                        Ok(())
+                    }
+                    _ => Err(i18n::tr("probability must be between 0.0 and 1.0", "確率は0.0〜1.0で指定してください").to_string()),
+                },
+                (Some(step), "ratchet", [value]) => match value.parse::<u8>() {
+                    Ok(n @ 1..=8) => {
+                        step.ratchet = n;
+                        Ok(())
+                    }
+                    _ => Err(i18n::tr("ratchet must be between 1 and 8", "連打数は1〜8で指定してください").to_string()),
+                },
+                (Some(step), "every", [cycle, rest @ ..]) => {
+                    let offset = rest.first().map_or(Ok(1), |v| v.parse::<u32>());
+                    match (cycle.parse::<u32>(), offset) {
+                        (Ok(1), _) => {
+                            step.condition = TrigCondition::Always;
+                            Ok(())
+                        }
+                        (Ok(cycle), Ok(offset)) if cycle > 1 && (1..=cycle).contains(&offset) => {
+                            step.condition = TrigCondition::Every { cycle, offset: offset - 1 };
+                            Ok(())
+                        }
+                        _ => Err(i18n::tr(
+                            "Usage: seq every <track> <step> <cycle> [1..cycle]",
+                            "使い方: seq every <トラック> <ステップ> <周期> [1〜周期]",
+                        ).to_string()),
+                    }
+                }
+                _ => Err(i18n::fill(i18n::tr("Usage: seq {} <track> <step> <value>", "使い方: seq {} <トラック> <ステップ> <値>"), &[command])),
+            }
+        }
+        ["mute", track] => match track_index(track).and_then(|i| synth.sequencer_mut().track_mut(i)) {
+            Some(track) => {
+                track.muted = !track.muted;
+                Ok(())
+            }
+            None => Err(i18n::fill(i18n::tr("no such track: {}", "トラック {} はありません"), &[track])),
+        },
+        _ => {
+            cli::print_usage("seq");
+            return;
+        }
+    };
+    
+    if let Err(message) = result {
+        println!("❌ {}", message);
+        return;
+    }
+    
+    let sequencer = synth.sequencer();
+    println!("🥁 Sequencer {} at {:.1} BPM", if sequencer.is_playing() { "playing" } else { "stopped" }, synth.tempo());
+    for (index, track) in sequencer.tracks().iter().enumerate() {
+        let notes: Vec<String> = track.steps.iter()
+            .map(|step| {
+                let mut text = step.note.map_or("-".to_string(), |note| note.to_string());
+                if step.probability < 1.0 {
+                    text += &format!("?{:.0}%", step.probability * 100.0);
+                }
+                if step.ratchet > 1 {
+                    text += &format!("x{}", step.ratchet);
+                }
+                if let TrigCondition::Every { cycle, offset } = step.condition {
+                    text += &format!("@{}:{}", offset + 1, cycle);
+                }
+                text
+            })
+            .collect();
+        println!(
+            "   track {}: {} steps / {} per beat{} [{}]",
+            index + 1,
+            track.steps.len(),
+            track.division,
+            if track.muted { " (muted)" } else { "" },
+            notes.join(" ")
+        );
+    }
+}
+
+// シーンのコマンド
+// 呼び出しは借りずに送る（保存の有無は Telemetry で確かめる）。一覧と保存だけ借りる
+fn handle_scene_command(synth: &SynthHandle, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let slot_index = |value: &str| value.parse::<usize>().ok()
+        .filter(|n| (1..=SCENE_COUNT).contains(n))
+        .map(|n| n - 1);
+    
+    match parts.as_slice() {
+        [] => {
+            let Some(synth) = borrow_synth(synth) else { return };
+            let scenes = synth.scenes();
+            let slots: Vec<String> = (0..SCENE_COUNT)
+                .map(|slot| {
+                    let mark = if synth.current_scene() == Some(slot) { "*" } else { "" };
+                    if scenes.is_stored(slot) { format!("[{}{}]", slot + 1, mark) } else { format!(" {} ", slot + 1) }
+                })
+                .collect();
+            println!("🎬 Scenes: {}", slots.join(" "));
+        }
+        ["store", slot] => match slot_index(slot) {
+            Some(slot) => {
+                if let Some(mut synth) = borrow_synth(synth) {
+                    synth.store_scene(slot);
+                    println!("🎬 Scene {} stored", slot + 1);
+                }
+            }
+            _ => println!("❌ {}", i18n::fill(i18n::tr("Scene slot must be 1-{}", "シーンの番号は1〜{}で指定してください"), &[&SCENE_COUNT])),
+        },
+        [slot, rest @ ..] => {
+            let fade = rest.first().map_or(Ok(0.0), |v| v.parse::<f32>());
+            match (slot_index(slot), fade) {
+                (Some(slot), Ok(fade)) if fade >= 0.0 => {
+                    if !synth.telemetry().is_scene_stored(slot) {
+                        println!("❌ {}", i18n::fill(i18n::tr("Scene {} is empty", "シーン{}は空です"), &[&(slot + 1)]));
+                    } else if send(synth, Command::Scene { slot, fade }) {
+                        if fade > 0.0 {
+                            println!("🎬 Fading to scene {} over {:.1} seconds", slot + 1, fade);
+                        } else {
+                            println!("🎬 Scene {} recalled", slot + 1);
+                        }
+                    }
+                }
+                _ => cli::print_usage("scene"),
+            }
+        }
+    }
+}
+
+// パッチ差分の保存・読み込みのコマンド
+fn handle_patch_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    match parts.as_slice() {
+        ["save", path, base @ ..] if base.len() <= 1 => {
+            let base = base.first().copied().unwrap_or("init");
+            match synth.patch().save_delta(path, base) {
+                Ok(()) => println!("💾 Patch delta saved to {} (base: {})", path, base),
+                Err(e) => println!("❌ {}", i18n::fill(i18n::tr("Failed to save patch: {}", "パッチを保存できませんでした: {}"), &[&e])),
+            }
+        }
+        ["load", path] => match PatchState::load_delta(path) {
+            Ok((patch, warnings)) => {
+                for warning in &warnings {
+                    println!("⚠️  {}", i18n::fill(i18n::tr("Skipped: {}", "読み飛ばしました: {}"), &[warning]));
+                }
+                synth.set_patch(patch);
+                println!("📂 Patch loaded from {}", path);
+            }
+            Err(e) => println!("❌ {}", i18n::fill(i18n::tr("Failed to load patch: {}", "パッチを読み込めませんでした: {}"), &[&e])),
+        },
+        _ => cli::print_usage("patch"),
+    }
+}
+
+// 前回の自動保存が残っていれば（正常に終了しなかった）、復元するか尋ねる
+fn offer_recovery(editor: &mut Editor<cli::CommandHelper, DefaultHistory>, synth: &SynthHandle) {
+    let path = cli::autosave_path();
+    if !path.exists() {
+        return;
+    }
+    println!(
+        "🩹 {}",
+        i18n::fill(
+            i18n::tr("The previous session did not exit cleanly (autosave: {}).", "前回のセッションは正常に終了しませんでした（自動保存: {}）。"),
+            &[&path.display()],
+        )
+    );
+    let answer = editor.readline(i18n::tr("Recover it? [y/N] ", "復元しますか? [y/N] ")).unwrap_or_default();
+    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+        println!("🗑️  {}", i18n::tr("Starting fresh (the autosave will be overwritten)", "新しく始めます（自動保存は上書きされます）"));
+        return;
+    }
+    match Session::load(&path) {
+        Ok((session, warnings)) => {
+            for warning in &warnings {
+                println!("⚠️  {}", i18n::fill(i18n::tr("Skipped: {}", "読み飛ばしました: {}"), &[warning]));
+            }
+            let Some(mut synth) = borrow_synth(synth) else { return };
+            session.apply(&mut synth);
+            println!("🩹 Session recovered");
+        }
+        Err(e) => println!("❌ {}", i18n::fill(i18n::tr("Failed to recover the session: {}", "セッションを復元できませんでした: {}"), &[&e])),
+    }
+}
+
+// DX7 のシステムエクスクルーシブの読み込み。バンクで番号が無ければボイスの一覧を表示する
+fn handle_dx7_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let (path, number) = match parts.as_slice() {
+        [path] => (*path, None),
+        [path, number] => match number.parse::<usize>() {
+            Ok(number) if number >= 1 => (*path, Some(number)),
+            _ => {
+                cli::print_usage("dx7");
+                return;
+            }
+        },
+        _ => {
+            cli::print_usage("dx7");
+            return;
+        }
+    };
+    let voices = match load_sysex(path) {
+        Ok(voices) => voices,
+        Err(e) => {
+            println!("❌ {}", i18n::fill(i18n::tr("Failed to load DX7 sysex: {}", "DX7 のシステムエクスクルーシブを読み込めませんでした: {}"), &[&e]));
+            return;
+        }
+    };
+    let index = match (number, voices.len()) {
+        (None, 1) => 0,
+        (None, _) => {
+            println!("🎹 {} ({} voices)", path, voices.len());
+            for (i, voice) in voices.iter().enumerate() {
+                println!("  {:>2}. {:<10}  algorithm {:>2}", i + 1, voice.name, voice.algorithm);
+            }
+            return;
+        }
+        (Some(number), count) if number <= count => number - 1,
+        (Some(_), count) => {
+            println!("❌ {}", i18n::fill(i18n::tr("Voice number must be 1-{}", "ボイスの番号は1〜{}で指定してください"), &[&count]));
+            return;
+        }
+    };
+    let voice = &voices[index];
+    synth.set_patch(voice.to_patch(synth.patch()));
+    println!("🎹 DX7 voice loaded: {} (algorithm {}, feedback {})", voice.name, voice.algorithm, voice.feedback);
+}
+
+// セッションの保存・読み込みのコマンド
+fn handle_session_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    match parts.as_slice() {
+        ["save", path] => match Session::capture(synth).save(path) {
+            Ok(()) => println!("💾 Session saved to {}", path),
+            Err(e) => println!("❌ {}", i18n::fill(i18n::tr("Failed to save session: {}", "セッションを保存できませんでした: {}"), &[&e])),
+        },
+        ["load", path] => match Session::load(path) {
+            Ok((session, warnings)) => {
+                for warning in &warnings {
+                    println!("⚠️  {}", i18n::fill(i18n::tr("Skipped: {}", "読み飛ばしました: {}"), &[warning]));
+                }
+                session.apply(synth);
+                println!("📂 Session loaded from {}", path);
+            }
+            Err(e) => println!("❌ {}", i18n::fill(i18n::tr("Failed to load session: {}", "セッションを読み込めませんでした: {}"), &[&e])),
+        },
+        _ => cli::print_usage("session"),
+    }
+}
+
+// プリセットのバンクの管理のコマンド（ファイルの読み書きの間はシンセサイザーを借りない）
+fn handle_preset_command(synth: &SynthHandle, bank: &mut PresetBank, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let result = match parts.as_slice() {
+        [] | ["list"] => bank.refresh().map(|()| print_presets(bank, None)),
+        ["list", category] => bank.refresh().map(|()| print_presets(bank, Some(category))),
+        ["load", id] => match bank.find(id) {
+            Some(index) => {
+                load_preset(synth, bank, index);
+                Ok(())
+            }
+            None => Err(i18n::fill(i18n::tr("Unknown preset: {}", "プリセットがありません: {}"), &[id]).into()),
+        },
+        ["save", id, base @ ..] if base.len() <= 1 => {
+            let base = base.first().copied().unwrap_or("init");
+            let Some(patch) = borrow_synth(synth).map(|synth| synth.patch().clone()) else { return };
+            bank.save(id, &patch, base).map(|entry| println!("💾 Preset saved: {} (base: {})", entry.id(), base))
+        }
+        ["rename", id, new_id] => bank.rename(id, new_id).map(|entry| println!("✏️  Preset renamed to {}", entry.id())),
+        ["copy", id, new_id] => bank.copy(id, new_id).map(|entry| println!("📄 Preset copied to {}", entry.id())),
+        ["move", id, category] => {
+            let category = (*category != "-").then_some(*category);
+            bank.categorize(id, category).map(|entry| println!("🗂️  Preset moved to {}", entry.id()))
+        }
+        ["delete", id] => bank.delete(id).map(|entry| println!("🗑️  Preset deleted: {}", entry.id())),
+        ["bank", directory] => PresetBank::open(directory).map(|opened| {
+            *bank = opened;
+            println!("📚 Preset bank: {} ({} presets)", bank.root().display(), bank.presets().len());
+        }),
+        _ => {
+            cli::print_usage("preset");
+            return;
+        }
+    };
+    if let Err(e) = result {
+        println!("❌ {}", i18n::fill(i18n::tr("Preset error: {}", "プリセットのエラー: {}"), &[&e]));
+    }
+}
+
+// A・B の位置は 0 で A、1 で B。位置を省くと A から始める
+fn handle_morph_command(synth: &mut Synthesizer, bank: &PresetBank, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    match parts.as_slice() {
+        [] => match synth.morph() {
+            Some(morph) => println!("🔀 Morph position {:.2}", morph.position),
+            None => println!("🔀 Morph off"),
+        },
+        ["off"] => {
+            synth.stop_morph();
+            println!("🔀 Morph off (current patch kept)");
+        }
+        [position] => match position.parse::<f32>() {
+            Ok(position) if synth.set_morph_position(position) => {
+                println!("🔀 Morph position {:.2}", synth.morph().map_or(position, |morph| morph.position));
+            }
+            Ok(_) => println!("❌ {}", i18n::tr("No morph in progress (start one with `morph <A> <B>`)", "モーフィング中ではありません（`morph <A> <B>` で始める）")),
+            Err(_) => cli::print_usage("morph"),
+        },
+        [from_id, to_id, position @ ..] if position.len() <= 1 => {
+            let position = match position.first().map(|position| position.parse::<f32>()) {
+                None => 0.0,
+                Some(Ok(position)) => position,
+                Some(Err(_)) => return cli::print_usage("morph"),
+            };
+            let mut patches = Vec::with_capacity(2);
+            for id in [from_id, to_id] {
+                match bank.read(id) {
+                    Ok((patch, warnings)) => {
+                        for warning in &warnings {
+                            println!("⚠️  {}", i18n::fill(i18n::tr("Skipped: {}", "読み飛ばしました: {}"), &[warning]));
+                        }
+                        patches.push(patch);
+                    }
+                    Err(e) => {
+                        println!("❌ {}", i18n::fill(i18n::tr("Failed to load preset {}: {}", "プリセット {} を読み込めませんでした: {}"), &[id, &e]));
+                        return;
+                    }
+                }
+            }
+            let to = patches.pop().unwrap_or_default();
+            let from = patches.pop().unwrap_or_default();
+            synth.start_morph(from, to, position);
+            println!("🔀 Morphing {} -> {} at {:.2}", from_id, to_id, position.clamp(0.0, 1.0));
+        }
+        _ => cli::print_usage("morph"),
+    }
+}
+
+fn handle_ab_command(synth: &mut Synthesizer, compare: &mut AbCompare, args: &str) {
+    const NAMES: [&str; 2] = ["A", "B"];
+    let slot = |name: &str| match name {
+        "a" | "A" => Some(0),
+        "b" | "B" => Some(1),
+        _ => None,
+    };
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let selected = match parts.as_slice() {
+        [] => match compare.toggle(synth.patch()) {
+            Some((slot, patch)) => (slot, patch.clone()),
+            None => {
+                println!("❌ {}", i18n::tr("Nothing to switch to (store with `ab store a|b`)", "切り替える先がありません（`ab store a|b` で保存する）"));
+                return;
+            }
+        },
+        ["store", name] => match slot(name) {
+            Some(slot) => {
+                compare.store(slot, synth.patch());
+                println!("💾 Stored the current patch to {}", NAMES[slot]);
+                return;
+            }
+            None => return cli::print_usage("ab"),
+        },
+        ["clear"] => {
+            compare.clear();
+            println!("🅰️  A/B slots cleared");
+            return;
+        }
+        [name] => match slot(name) {
+            Some(slot) => match compare.select(slot, synth.patch()) {
+                Some(patch) => (slot, patch.clone()),
+                None => {
+                    println!("❌ {}", i18n::fill(i18n::tr("Slot {} is empty", "スロット {} は空です"), &[&NAMES[slot]]));
+                    return;
+                }
+            },
+            None => return cli::print_usage("ab"),
+        },
+        _ => return cli::print_usage("ab"),
+    };
+    let (slot, patch) = selected;
+    synth.set_patch(patch);
+    let other = if compare.is_stored(1 - slot) { "" } else { " (other slot empty)" };
+    println!("🅰️  Listening to {}{}", NAMES[slot], other);
+}
+
+fn handle_randomize_command(synth: &mut Synthesizer, args: &str) {
+    let mut parts = args.split_whitespace().peekable();
+    let scope = match parts.peek().and_then(|name| MutationScope::parse(name)) {
+        Some(scope) => {
+            parts.next();
+            scope
+        }
+        None => MutationScope::All,
+    };
+    let amount = match (parts.next().map(str::parse::<f32>), parts.next()) {
+        (None, None) => DEFAULT_MUTATION_AMOUNT,
+        (Some(Ok(amount)), None) => amount.clamp(0.0, 1.0),
+        _ => return cli::print_usage("randomize"),
+    };
+    let mut rng = Rng::from_entropy();
+    let patch = mutate_patch(synth.patch(), scope, amount, &mut rng);
+    synth.set_patch(patch);
+    println!("🎲 Randomized {:?} by {:.2}", scope, amount);
+}
+
+// プログラムチェンジの対応表のコマンド。変更するたびにファイルへ保存する
+fn handle_program_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.splitn(3, char::is_whitespace).map(str::trim).collect();
+    let map = synth.program_map_mut();
+    let changed = match parts.as_slice() {
+        [] | [""] => false,
+        ["clear"] => {
+            map.clear();
+            true
+        }
+        [number, rest @ ..] => {
+            let Ok(program) = number.parse::<u8>() else {
+                cli::print_usage("program");
+                return;
+            };
+            let result = match rest {
+                ["off"] => match map.remove(program) {
+                    true => Ok(()),
+                    false => Err(i18n::fill(i18n::tr("Program {} is not mapped", "プログラム {} は対応表にありません"), &[&program])),
+                },
+                ["preset", id] if !id.is_empty() => map.set(program, ProgramTarget::Preset(id.to_string())),
+                ["file", path] if !path.is_empty() => map.set(program, ProgramTarget::File(path.into())),
+                _ => {
+                    cli::print_usage("program");
+                    return;
+                }
+            };
+            if let Err(e) = result {
+                println!("❌ {}", e);
+                return;
+            }
+            true
+        }
+    };
+    if changed {
+        if let Err(e) = synth.program_map().save(cli::program_map_path()) {
+            println!("❌ {}", i18n::fill(i18n::tr("Failed to write {}: {}", "{} を書き出せませんでした: {}"), &[&cli::program_map_path().display(), &e]));
+        }
+    }
+    let mut entries = synth.program_map().entries().peekable();
+    if entries.peek().is_none() {
+        println!("🎛️  No program changes mapped (program changes recall scenes)");
+    }
+    for (program, target) in entries {
+        println!("🎛️  Program {:3} -> {}", program, target);
+    }
+}
+
+// プログラムチェンジで読み込んだ結果を表示する（ProgramLoader のスレッドから呼ばれる）
+fn report_program_load(load: ProgramLoad<'_>) {
+    match load {
+        ProgramLoad::Loaded { program, target, warnings } => {
+            for warning in warnings {
+                println!("⚠️  {}", i18n::fill(i18n::tr("Skipped: {}", "読み飛ばしました: {}"), &[warning]));
+            }
+            println!("🎛️  Program {}: {}", program, target);
+        }
+        ProgramLoad::Failed { program, target, error } => {
+            println!("❌ {}", i18n::fill(i18n::tr("Program {}: failed to load {}: {}", "プログラム {}: {} を読み込めませんでした: {}"), &[&program, target, &error]));
+        }
+    }
+}
+
+// 読み込みと解釈はこのスレッドで済ませ、差し替えはオーディオスレッドが次の境界で行う
+fn load_preset(synth: &SynthHandle, bank: &mut PresetBank, index: usize) {
+    let id = bank.presets()[index].id();
+    match bank.load(index) {
+        Ok((patch, warnings)) => {
+            for warning in &warnings {
+                println!("⚠️  {}", i18n::fill(i18n::tr("Skipped: {}", "読み飛ばしました: {}"), &[warning]));
+            }
+            if !synth.queue_patch(patch) {
+                println!("❌ {}", i18n::tr(BORROW_FAILED.0, BORROW_FAILED.1));
+                return;
+            }
+            println!("📂 Preset {}/{}: {}", index + 1, bank.presets().len(), id);
+        }
+        Err(e) => println!("❌ {}", i18n::fill(i18n::tr("Failed to load preset {}: {}", "プリセット {} を読み込めませんでした: {}"), &[&id, &e])),
+    }
+}
+
+// カテゴリごとに一覧を表示する（現在のプリセットに印を付ける）
+fn print_presets(bank: &PresetBank, category: Option<&str>) {
+    println!("📚 Preset bank: {}", bank.root().display());
+    let mut heading = None;
+    for (index, entry) in bank.presets().iter().enumerate() {
+        if category.is_some_and(|category| entry.category.as_deref() != Some(category)) {
+            continue;
+        }
+        if heading != Some(entry.category.as_deref()) {
+            heading = Some(entry.category.as_deref());
+            println!("  [{}]", entry.category.as_deref().unwrap_or("-"));
+        }
+        let marker = if bank.current() == Some(index) { "▶" } else { " " };
+        println!("  {} {:>3}. {}", marker, index + 1, entry.name);
+    }
+    if heading.is_none() {
+        println!("  (empty)");
+    }
+}
+
+// メトロノームのコマンド
+fn handle_metronome_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    match parts.as_slice() {
+        ["on"] => synth.metronome_mut().set_enabled(true),
+        ["off"] => synth.metronome_mut().set_enabled(false),
+        ["bar", beats] => match beats.parse::<u32>() {
+            Ok(beats) if beats > 0 => synth.metronome_mut().set_beats_per_bar(beats),
+            _ => {
+                println!("❌ {}", i18n::tr("Beats per bar must be a positive integer", "1小節の拍数は正の整数で指定してください"));
+                return;
+            }
+        },
+        _ => {
+            cli::print_usage("metro");
+            return;
+        }
+    }
+    let metronome = synth.metronome();
+    println!("🥁 Metronome {} ({}/4, {:.1} BPM)",
+        if metronome.is_enabled() { "on" } else { "off" }, metronome.beats_per_bar(), synth.tempo());
+}
+
+// サイドチェインのダッキングのコマンド
+fn handle_duck_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let ducker = synth.ducker_mut();
+    match parts.as_slice() {
+        [] => {}
+        ["off"] => ducker.set_trigger(None),
+        ["track", track] => match track.parse::<usize>() {
+            Ok(track) if track > 0 => ducker.set_trigger(Some(DuckTrigger::Track(track - 1))),
+            _ => {
+                println!("❌ {}", i18n::tr("Track numbers start at 1", "トラック番号は1から指定してください"));
+                return;
+            }
+        },
+        ["notes", notes @ ..] if !notes.is_empty() => match notes.iter().map(|note| note.parse::<u8>().ok().filter(|note| *note <= 127)).collect::<Option<Vec<_>>>() {
+            Some(notes) => ducker.set_trigger(Some(DuckTrigger::Notes(notes))),
+            None => {
+                println!("❌ {}", i18n::tr("Notes must be MIDI note numbers (0-127)", "ノートは MIDI のノート番号（0〜127）で指定してください"));
+                return;
+            }
+        },
+        [setting @ ("depth" | "attack" | "release"), value] => match (*setting, value.parse::<f32>()) {
+            ("depth", Ok(depth)) => ducker.set_depth(depth),
+            ("attack", Ok(ms)) => ducker.set_attack(ms / 1000.0),
+            ("release", Ok(beats)) => ducker.set_release_beats(beats),
+            _ => {
+                cli::print_usage("duck");
+                return;
+            }
+        },
+        _ => {
+            cli::print_usage("duck");
+            return;
+        }
+    }
+    let ducker = synth.ducker();
+    match ducker.trigger() {
+        Some(trigger) => println!(
+            "🦆 Ducking on {}: master depth {:.2}, attack {:.0} ms, release {} beats",
+            trigger, ducker.depth(), ducker.attack() * 1000.0, ducker.release_beats()
+        ),
+        None => println!("🦆 Ducking off"),
+    }
+}
+
+// レイヤーの受信フィルターのコマンド
+fn handle_layer_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    match parts.as_slice() {
+        [] => {}
+        ["reset"] => {
+            for layer in Layer::ALL {
+                synth.set_layer_filter(layer, LayerFilter::ALL);
+            }
+        }
+        [layer, filter @ ..] => {
+            let parsed = layer.parse::<Layer>().and_then(|layer| Ok((layer, LayerFilter::parse(filter)?)));
+            match parsed {
+                Ok((layer, filter)) => synth.set_layer_filter(layer, filter),
+                Err(e) => {
+                    println!("❌ {}", e);
+                    cli::print_usage("layer");
+                    return;
+                }
+            }
+        }
+    }
+    for layer in Layer::ALL {
+        let filter = synth.layer_filter(layer);
+        if filter == LayerFilter::ALL {
+            println!("🎛️  {:<8} all channels, all keys and velocities", layer);
+        } else {
+            println!("🎛️  {:<8}{}", layer, filter);
+        }
+    }
+}
+
+// 聞こえている拍に合わせて拍表示を指定小節数だけ更新する
+fn show_beat_indicator(synth: &SynthHandle, bars: u32) {
+    let telemetry = synth.telemetry();
+    let (start, beats_per_bar) = (telemetry.audible_beats(), telemetry.beats_per_bar());
+    let end = start.floor() + (bars * beats_per_bar) as f64;
+    let mut last = (start, std::time::Instant::now());
+    loop {
+        let beats = telemetry.audible_beats();
+        if beats >= end {
+            break;
+        }
+        // オーディオが止まっている場合は待ち続けない
+        if beats != last.0 {
+            last = (beats, std::time::Instant::now());
+        } else if last.1.elapsed() > std::time::Duration::from_millis(500) {
+            println!("\n❌ {}", i18n::tr("Audio is not running", "オーディオが動いていません"));
+            return;
+        }
+        let beat = beats.floor() as u64;
+        let position = (beat % beats_per_bar as u64) as u32;
+        let indicator: String = (0..beats_per_bar)
+            .map(|i| if i == position { "● " } else { "○ " })
+            .collect();
+        print!("\r🥁 {} bar {}", indicator, beat / beats_per_bar as u64 + 1);
+        io::stdout().flush().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    println!();
+}
+
+// 解析タップのコマンド
+fn handle_taps_command(synth: &mut Synthesizer, args: &str) {
+    match args {
+        "on" => synth.set_analysis_taps(true),
+        "off" => synth.set_analysis_taps(false),
+        "" => {}
+        _ => {
+            cli::print_usage("taps");
+            return;
+        }
+    }
+    
+    let taps = synth.analysis_taps();
+    if !taps.is_enabled() {
+        println!("🔬 Analysis taps off");
+        return;
+    }
+    for tap in Tap::ALL {
+        let frames = taps.snapshot(tap);
+        let peak = frames.iter().fold(0.0_f32, |peak, [l, r]| peak.max(l.abs()).max(r.abs()));
+        let rms = (frames.iter().map(|[l, r]| (l * l + r * r) * 0.5).sum::<f32>() / frames.len().max(1) as f32).sqrt();
+        println!("🔬 {:<12} rms {:.4}  peak {:.4}", tap.name(), rms, peak);
+    }
+}
+
+// 書き出しのコマンドから `--normalize <LUFS>` を取り除き、目標のラウドネスを返す（値が数でなければ Err）
+fn take_normalize(parts: &mut Vec<&str>) -> Result<Option<f32>, ()> {
+    let Some(index) = parts.iter().position(|part| *part == "--normalize") else {
+        return Ok(None);
+    };
+    let target = parts.get(index + 1).and_then(|value| value.parse::<f32>().ok()).ok_or(())?;
+    parts.drain(index..index + 2);
+    Ok(Some(target))
+}
+
+// 書き出したマスターのラウドネスとピーク（正規化したときはそのゲインも）
+fn print_loudness(report: &RenderReport) {
+    if let Some(gain) = report.gain_db {
+        println!("🔊 Normalized by {:+.1} dB", gain);
+    }
+    println!("📏 {}", report.loudness);
+    if report.loudness.true_peak > 0.0 {
+        println!("⚠️  {}", i18n::tr(
+            "True peak is above 0 dBTP; the file may clip on playback",
+            "トゥルーピークが 0 dBTP を超えています。再生時にクリップするおそれがあります",
+        ));
+    }
+}
+
+// 書き出すファイルの拡張子とビット深度の指定から形式を決める（不正ならエラーを表示して None）
+fn export_format(path: &str, bits: Option<&str>) -> Option<ExportFormat> {
+    let bits = match bits.map(str::parse::<u32>) {
+        None => None,
+        Some(Ok(bits)) => Some(bits),
+        Some(Err(_)) => {
+            println!("❌ {}", i18n::tr("Bit depth must be 16 or 24 (quality 0-10 for .ogg)", "ビット深度は16か24で指定してください（.ogg は品質 0〜10）"));
+            return None;
+        }
+    };
+    match ExportFormat::from_path(path, bits) {
+        Ok(format) => Some(format),
+        Err(e) => {
+            println!("❌ {}", e);
+            None
+        }
+    }
+}
+
+// オフライン書き出しのコマンド
+fn handle_bounce_command(synth: &Synthesizer, args: &str) {
+    let mut parts: Vec<&str> = args.split_whitespace().collect();
+    let Ok(normalize) = take_normalize(&mut parts) else {
+        cli::print_usage("bounce");
+        return;
+    };
+    let (stems, parts): (Vec<&str>, Vec<&str>) = parts.into_iter().partition(|part| *part == "--stems");
+    let (path, seconds, bits) = match parts.as_slice() {
+        [path, seconds, bits @ ..] if bits.len() <= 1 => match seconds.parse::<f32>() {
+            Ok(seconds) if seconds > 0.0 => (*path, seconds, bits.first().copied()),
+            _ => {
+                println!("❌ {}", i18n::tr("Duration must be greater than 0", "持続時間は0より大きくしてください"));
+                return;
+            }
+        },
+        _ => {
+            cli::print_usage("bounce");
+            return;
+        }
+    };
+    let Some(format) = export_format(path, bits) else {
+        return;
+    };
+    
+    let options = BounceOptions { seconds, stems: !stems.is_empty(), format, normalize };
+    match bounce(synth, path, &options) {
+        Ok(report) => {
+            for file in &report.files {
+                println!("💾 Wrote {}", file.display());
+            }
+            print_loudness(&report);
+        }
+        Err(e) => println!("❌ {}", i18n::fill(i18n::tr("Bounce failed: {}", "書き出しに失敗しました: {}"), &[&e])),
+    }
+}
+
+// スペクトルフィルターのコマンド
+fn handle_spectral_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let mut filter = synth.patch().spectral_filter;
+    let values: Option<Vec<f32>> = parts.iter().skip(1).map(|v| v.parse::<f32>().ok()).collect();
+    match (parts.first().copied(), values.as_deref()) {
+        (None, _) => {}
+        (Some("reset"), Some([])) => filter = SpectralFilter::default(),
+        (Some("tilt"), Some([db])) => filter.tilt = *db,
+        (Some("low"), Some([harmonic, db])) => {
+            filter.low_shelf_harmonic = *harmonic;
+            filter.low_shelf_gain = *db;
+        }
+        (Some("high"), Some([harmonic, db])) => {
+            filter.high_shelf_harmonic = *harmonic;
+            filter.high_shelf_gain = *db;
+        }
+        (Some("formant"), Some([semitones])) => filter.formant_shift = *semitones,
+        _ => {
+            cli::print_usage("spectral");
+            return;
+        }
+    }
+    synth.set_spectral_filter(filter);
+    
+    let filter = synth.patch().spectral_filter;
+    println!("🌈 Spectral filter: tilt {:+.1} dB/oct, low shelf {:+.1} dB @ {:.0}, high shelf {:+.1} dB @ {:.0}, formant {:+.1} st",
+        filter.tilt, filter.low_shelf_gain, filter.low_shelf_harmonic,
+        filter.high_shelf_gain, filter.high_shelf_harmonic, filter.formant_shift);
+}
+
+// 診断用テスト信号のコマンド
+fn handle_test_signal_command(synth: &mut Synthesizer, args: &str) {
+    let mut parts: Vec<&str> = args.split_whitespace().collect();
+    let through_filter = parts.contains(&"--filter");
+    parts.retain(|part| *part != "--filter");
+    let wav = parts.iter().position(|part| *part == "--wav").map(|index| {
+        let path = parts.get(index + 1).map(|path| path.to_string());
+        parts.drain(index..(index + 2).min(parts.len()));
+        path
+    });
+    
+    let numbers: Option<Vec<f32>> = parts.iter().skip(1).map(|v| v.parse::<f32>().ok()).collect();
+    let signal = match (parts.first().copied(), numbers.as_deref()) {
+        (Some("off"), Some([])) => {
+            synth.stop_test_signal();
+            println!("🔇 Test signal stopped");
+            return;
+        }
+        (Some("sweep"), Some(values)) if values.len() <= 3 => {
+            let start = values.first().copied().unwrap_or(20.0);
+            let end = values.get(1).copied().unwrap_or(20000.0);
+            let seconds = values.get(2).copied().unwrap_or(10.0);
+            if start <= 0.0 || end <= 0.0 || seconds <= 0.0 {
+                println!("❌ {}", i18n::tr("Sweep frequencies and duration must be positive", "スイープの周波数と長さは正の数で指定してください"));
+                return;
+            }
+            TestSignal::Sweep { start, end, seconds }
+        }
+        (Some("white"), Some([])) => TestSignal::WhiteNoise,
+        (Some("pink"), Some([])) => TestSignal::PinkNoise,
+        (Some("impulse"), Some(values)) if values.len() <= 1 => {
+            let interval = values.first().copied().unwrap_or(1.0);
+            if interval <= 0.0 {
+                println!("❌ {}", i18n::tr("Impulse interval must be positive", "インパルスの間隔は正の数で指定してください"));
+                return;
+            }
+            TestSignal::Impulse { interval }
+        }
+        _ => {
+            cli::print_usage("test");
+            return;
+        }
+    };
+    
+    if let Some(path) = wav {
+        let Some(path) = path else {
+            println!("❌ {}", i18n::tr("--wav needs a file name", "--wav にはファイル名が必要です"));
+            return;
+        };
+        let sample_rate = synth.sample_rate();
+        let mut generator = TestSignalGenerator::new(signal, sample_rate);
+        if through_filter {
+            generator.set_filter(Some(synth.patch_filter()));
+        }
+        let seconds = match signal {
+            TestSignal::Sweep { seconds, .. } => seconds,
+            TestSignal::Impulse { interval } => interval,
+            _ => 10.0,
+        };
+        match capture_test_signal(generator, sample_rate, seconds, &path) {
+            Ok(()) => println!("💾 Wrote {} ({:.1} s)", path, seconds),
+            Err(e) => println!("❌ {}", i18n::fill(i18n::tr("Failed to write {}: {}", "{} に書き込めませんでした: {}"), &[&path, &e])),
+        }
+    }
+    
+    synth.start_test_signal(signal, through_filter);
+    println!("📈 Test signal: {}{}", signal, if through_filter { " through the filter" } else { "" });
+}
+
+// フィルターの周波数特性をオクターブごとに表示する
+fn show_filter_curve(synth: &Synthesizer) {
+    let freqs: Vec<f32> = (0..10).map(|octave| 31.25 * 2.0_f32.powi(octave)).collect();
+    for (freq, gain) in freqs.iter().zip(synth.filter_response(&freqs)) {
+        let db = 20.0 * gain.max(1e-6).log10();
+        let bar = "█".repeat(((db + 48.0).max(0.0) / 2.0) as usize);
+        println!("{:>7.0} Hz {:>+6.1} dB {}", freq, db, bar);
+    }
+}
+
+// ピッチベンド・モジュレーションホイールをMIDI機器なしで操作する
+fn handle_controller_command(synth: &mut Synthesizer, controller: Controller, args: &str) {
+    let name = controller.source().to_string();
+    let (min, max) = controller.range();
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let parsed = match parts.as_slice() {
+        [value] => value.parse::<f32>().ok().map(|value| (value, 0.0)),
+        [value, seconds] => value.parse::<f32>().ok().zip(seconds.parse::<f32>().ok()),
+        _ => None,
+    };
+    let Some((value, seconds)) = parsed else {
+        cli::print_usage(&name);
+        return;
+    };
+    
+    synth.set_controller(controller, value, seconds.max(0.0));
+    let target = value.clamp(min, max);
+    if seconds > 0.0 {
+        println!("🎛️  {} -> {:+.2} over {:.2} s", name, target, seconds);
+    } else {
+        println!("🎛️  {} = {:+.2}", name, target);
+    }
+    // ルーティングがなければ音は変わらない（ピッチベンドはベンドレンジが 0 のときだけ）
+    if controller == Controller::PitchBend && synth.bend_range() > 0.0 {
+        return;
+    }
+    if !synth.mod_routings().iter().any(|routing| routing.source == controller.source()) {
+        println!("ℹ️  {}", i18n::fill(i18n::tr(
+            "No routing uses '{}' yet (e.g. 'route {} pitch 2')",
+            "'{}' を使うルーティングがまだありません (例: 'route {} pitch 2')",
+        ), &[&name, &name]));
+    }
+}
+
+fn handle_bend_range_command(synth: &mut Synthesizer, args: &str) {
+    if !args.is_empty() {
+        match args.parse::<f32>() {
+            Ok(semitones) => synth.set_bend_range(semitones),
+            Err(_) => {
+                cli::print_usage("bend");
+                return;
+            }
+        }
+    }
+    println!("🎛️  Bend range: ±{} semitones", synth.bend_range());
+}
+
+// フレーズのノートを予約する（ノートオフも時刻指定で送る）
+fn play_phrase(synth: &SynthHandle, notes: &[PhraseNote]) {
+    for note in notes {
+        send(synth, Command::Schedule { delay: note.offset, event: Event::NoteOn { note: note.note, velocity: note.velocity } });
+        let release = Event::NoteOff { note: note.note, velocity: DEFAULT_RELEASE_VELOCITY };
+        send(synth, Command::Schedule { delay: note.offset + note.duration, event: release });
+    }
+}
+
+const BORROW_FAILED: (&str, &str) = (
+    "The audio callback did not hand over the synthesizer; try again",
+    "オーディオのコールバックがシンセサイザーを渡しませんでした。もう一度試してください",
+);
+
+// Synthesizer を借りる。借りている間のコールバックは無音になるので、ノート・パラメータ・テンポは
+// send / send_parameter で送り、借りるのは Synthesizer 全体を触る操作だけにする。借りられなければ知らせて None
+fn borrow_synth(synth: &SynthHandle) -> Option<SynthGuard<'_>> {
+    let guard = synth.borrow();
+    if guard.is_none() {
+        println!("❌ {}", i18n::tr(BORROW_FAILED.0, BORROW_FAILED.1));
+    }
+    guard
+}
+
+fn with_synth(synth: &SynthHandle, f: impl FnOnce(&mut Synthesizer)) {
+    if let Some(mut synth) = borrow_synth(synth) {
+        f(&mut synth);
+    }
+}
+
+// 借りずに送る（出力中は次のコントロールレートの境界で反映する）。キューが満杯なら知らせて false
+fn send(synth: &SynthHandle, command: Command) -> bool {
+    let sent = synth.send(command);
+    if !sent {
+        println!("❌ {}", i18n::tr("The command queue is full; try again", "コマンドのキューが満杯です。もう一度試してください"));
+    }
+    sent
+}
+
+// 範囲への丸めはオーディオスレッドで行うので、表示する値は呼び出し側で同じ範囲に丸める
+fn send_parameter(synth: &SynthHandle, name: &str, value: f32) -> bool {
+    match synth.set_parameter(name, value) {
+        Ok(()) => true,
+        Err(e) => {
+            println!("❌ {}", e);
+            false
+        }
+    }
+}
+
+fn describe_phrase(settings: &PhraseSettings) -> String {
+    let spacing = settings.spacing.map_or("auto".to_string(), |seconds| format!("{:.0} ms", seconds * 1000.0));
+    format!(
+        "{}, {} octave(s), spacing {}, humanize {:.0}% / {:.0} ms",
+        settings.direction,
+        settings.octaves,
+        spacing,
+        settings.velocity_humanize * 100.0,
+        settings.timing_humanize * 1000.0
+    )
+}
+
+// mono on|off: マスター出力のモノラル互換チェック。引数なしでモノラルにしたときの音量の変化を表示する
+fn handle_mono_command(synth: &mut Synthesizer, args: &str) {
+    match args {
+        "on" | "off" => {
+            synth.set_mono_check(args == "on");
+            println!("🔈 Mono fold-down check {}", args);
+        }
+        "" => {
+            let state = if synth.mono_check() { "on" } else { "off" };
+            match synth.fold_down_loss_db() {
+                Some(loss) => println!("🔈 Mono fold-down {}: {:+.1} dB when summed to mono", state, loss),
+                None => println!("🔈 Mono fold-down {}: {}", state, i18n::tr("no output to measure", "測定する出力がありません")),
+            }
+            if synth.fold_down_loss_db().is_some_and(|loss| loss < -6.0) {
+                println!("⚠️  {}", i18n::tr(
+                    "Large level drop in mono: check wide stacks, width and phase",
+                    "モノラルで大きく音量が下がります。スタック・幅・位相を確認してください",
+                ));
+            }
+        }
+        _ => cli::print_usage("mono"),
+    }
+}
+
+// cc: CC 番号とパラメータの割り当て。send で CC を送ってコントローラーなしでも試せる
+fn handle_cc_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let result = match parts.as_slice() {
+        [] => {
+            print_cc_map(synth);
+            Ok(())
+        }
+        ["learn", parameter] => synth.cc_map_mut().learn(parameter).map(|_| {
+            println!("🎛️  {}", i18n::fill(i18n::tr("Move a controller to assign it to {}", "{} に割り当てるコントローラーを動かしてください"), &[parameter]));
+        }),
+        ["cancel"] => {
+            synth.cc_map_mut().cancel_learn();
+            println!("🎛️  {}", i18n::tr("MIDI learn cancelled", "MIDI ラーンを取り消しました"));
+            Ok(())
+        }
+        ["clear"] => {
+            synth.cc_map_mut().clear();
+            println!("🎛️  {}", i18n::tr("All CC mappings cleared", "CC の割り当てをすべて解除しました"));
+            Ok(())
+        }
+        ["remove", controller] => match controller.parse::<u8>() {
+            Ok(controller) if synth.cc_map_mut().remove(controller) => {
+                println!("🎛️  CC {} {}", controller, i18n::tr("removed", "の割り当てを解除しました"));
+                Ok(())
+            }
+            Ok(controller) => Err(i18n::fill(i18n::tr("CC {} is not assigned", "CC {} は割り当てられていません"), &[&controller])),
+            Err(_) => Err(String::new()),
+        },
+        ["send", controller, value] => match (controller.parse::<u8>(), value.parse::<u8>()) {
+            (Ok(controller), Ok(value)) if controller < 128 && value < 128 => {
+                synth.handle_event(&Event::ControlChange { controller, value });
+                Ok(())
+            }
+            _ => Err(String::new()),
+        },
+        [controller, parameter, range @ ..] => {
+            let range = match range {
+                [] => Some(None),
+                [min, max] => min.parse::<f32>().ok().zip(max.parse::<f32>().ok()).map(Some),
+                _ => None,
+            };
+            match (controller.parse::<u8>(), range) {
+                (Ok(controller), Some(range)) => synth.cc_map_mut().assign(controller, parameter, range).map(|_| {
+                    if let Some(mapping) = synth.cc_map().mappings().iter().find(|mapping| mapping.controller == controller) {
+                        println!("🎛️  CC {} → {} ({} .. {})", mapping.controller, mapping.parameter, mapping.min, mapping.max);
+                    }
+                }),
+                _ => Err(String::new()),
+            }
+        }
+        _ => Err(String::new()),
+    };
+    match result {
+        Ok(()) => {}
+        Err(message) if message.is_empty() => cli::print_usage("cc"),
+        Err(message) => println!("❌ {}", message),
+    }
+}
+
+// velocity curve <指数> | profile <名前> <最小> <最大> [指数] | profile <名前> remove | device <プロファイル>|off <機器名>
+fn handle_velocity_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let midi = |value: &str| value.parse::<u8>().ok().filter(|value| *value <= 127).map(|value| value as f32 / 127.0);
+    let result = match parts.as_slice() {
+        [] => Ok(()),
+        ["curve", exponent] => match exponent.parse::<f32>() {
+            Ok(exponent) if exponent > 0.0 => {
+                synth.set_velocity_curve(VelocityCurve { exponent, ..VelocityCurve::default() });
+                Ok(())
+            }
+            _ => Err(String::new()),
+        },
+        ["profile", name, "remove"] => match synth.velocity_profiles_mut().remove_profile(name) {
+            true => Ok(()),
+            false => Err(i18n::fill(i18n::tr("Unknown velocity profile: {}", "ベロシティのプロファイルがありません: {}"), &[name])),
+        },
+        ["profile", name, low, high, rest @ ..] if rest.len() <= 1 => {
+            let exponent = rest.first().map_or(Ok(1.0), |exponent| exponent.parse::<f32>());
+            match (midi(low), midi(high), exponent) {
+                (Some(low), Some(high), Ok(exponent)) if low < high && exponent > 0.0 => {
+                    synth.velocity_profiles_mut().set_profile(name, VelocityCurve::new(low, high, exponent));
+                    Ok(())
+                }
+                _ => Err(String::new()),
+            }
+        }
+        ["device", "off", device @ ..] if !device.is_empty() => {
+            synth.velocity_profiles_mut().unassign(&device.join(" "));
+            Ok(())
+        }
+        ["device", profile, device @ ..] if !device.is_empty() => synth.velocity_profiles_mut().assign(&device.join(" "), profile),
+        _ => Err(String::new()),
+    };
+    match result {
+        Ok(()) => print_velocity_settings(synth),
+        Err(message) if message.is_empty() => cli::print_usage("velocity"),
+        Err(message) => println!("❌ {}", message),
+    }
+}
+
+fn print_velocity_settings(synth: &Synthesizer) {
+    let curve = synth.velocity_curve();
+    println!("🎹 Velocity curve: exponent {:.2}", curve.exponent);
+    let profiles = synth.velocity_profiles();
+    for (name, curve) in profiles.profiles() {
+        println!(
+            "   profile {}: {:.0}-{:.0} -> full range, exponent {:.2}",
+            name, curve.low * 127.0, curve.high * 127.0, curve.exponent,
+        );
+    }
+    for (device, profile) in profiles.devices() {
+        println!("   device \"{}\" -> {}", device, profile);
+    }
+}
+
+fn handle_analog_command(synth: &mut Synthesizer, args: &str) {
+    let mut settings = synth.analog_floor();
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let number = |value: Option<&&str>, default: f32| value.map_or(Ok(default), |value| value.parse::<f32>());
+    let valid = match parts.as_slice() {
+        [] => true,
+        ["off"] => {
+            settings = Default::default();
+            true
+        }
+        ["noise", "off"] => {
+            settings.noise_db = None;
+            true
+        }
+        ["noise", rest @ ..] if rest.len() <= 1 => number(rest.first(), DEFAULT_NOISE_FLOOR_DB)
+            .map(|db| settings.noise_db = Some(db.min(-30.0)))
+            .is_ok(),
+        ["hum", "off"] => {
+            settings.hum = None;
+            true
+        }
+        ["hum", frequency @ ("50" | "60"), rest @ ..] if rest.len() <= 1 => number(rest.first(), DEFAULT_HUM_DB)
+            .map(|db| settings.hum = Some((if *frequency == "50" { 50.0 } else { 60.0 }, db.min(-30.0))))
+            .is_ok(),
+        _ => false,
+    };
+    if !valid {
+        cli::print_usage("analog");
+        return;
+    }
+    synth.set_analog_floor(settings);
+    
+    let settings = synth.analog_floor();
+    let noise = settings.noise_db.map_or("off".to_string(), |db| format!("{:.0} dBFS", db));
+    let hum = settings.hum.map_or("off".to_string(), |(frequency, db)| format!("{:.0} Hz at {:.0} dBFS", frequency, db));
+    println!("📼 Noise floor {}, hum {}", noise, hum);
+}
+
+// automate <パラメータ> [<開始> ->] <終了> over <長さ> [beats|s] [lin|exp]
+fn handle_automate_command(synth: &mut Synthesizer, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    match parts.as_slice() {
+        [] => {
+            let sample_rate = synth.sample_rate();
+            if synth.parameter_ramps().is_empty() {
+                println!("📈 {}", i18n::tr("No parameter ramps", "パラメータのランプはありません"));
+            }
+            for ramp in synth.parameter_ramps() {
+                let from = ramp.from.map_or("current".to_string(), |from| format!("{:.3}", from));
+                let seconds = ramp.length as f32 / sample_rate;
+                println!("📈 {} {} -> {:.3} over {:.2} s ({})", ramp.parameter, from, ramp.to, seconds, ramp.curve);
+            }
+            return;
+        }
+        ["clear"] => {
+            synth.clear_parameter_ramps();
+            println!("📈 {}", i18n::tr("Parameter ramps cleared", "パラメータのランプを解除しました"));
+            return;
+        }
+        _ => {}
+    }
+    
+    let Some(ramp) = parse_automation(&parts, synth.tempo()) else {
+        cli::print_usage("automate");
+        return;
+    };
+    let (parameter, from, to, seconds, curve) = ramp;
+    match synth.automate(parameter, from, to, 0.0, seconds, curve) {
+        Ok(()) => println!("📈 {} -> {} over {:.2} s ({})", parameter, to, seconds, curve),
+        Err(message) => println!("❌ {}", message),
+    }
+}
+
+fn parse_automation<'a>(parts: &[&'a str], tempo: f32) -> Option<(&'a str, Option<f32>, f32, f32, RampCurve)> {
+    let (parameter, from, to, rest) = match parts {
+        [parameter, from, "->", to, rest @ ..] => (*parameter, Some(from.parse::<f32>().ok()?), to.parse::<f32>().ok()?, rest),
+        [parameter, to, rest @ ..] => (*parameter, None, to.parse::<f32>().ok()?, rest),
+        _ => return None,
+    };
+    let (length, rest) = match rest {
+        ["over", length, rest @ ..] => (length.parse::<f32>().ok().filter(|length| *length >= 0.0)?, rest),
+        _ => return None,
+    };
+    let (seconds, rest) = match rest {
+        ["beats" | "beat", rest @ ..] => (length * 60.0 / tempo, rest),
+        ["s", rest @ ..] => (length, rest),
+        _ => (length * 60.0 / tempo, rest),
+    };
+    let curve = match rest {
+        [] => RampCurve::default(),
+        [curve] => curve.parse().ok()?,
+        _ => return None,
+    };
+    Some((parameter, from, to, seconds, curve))
+}
+
+fn print_cc_map(synth: &Synthesizer) {
+    let cc_map = synth.cc_map();
+    if cc_map.mappings().is_empty() {
+        println!("🎛️  {}", i18n::tr("No CC mappings", "CC の割り当てはありません"));
+    }
+    for mapping in cc_map.mappings() {
+        println!("🎛️  CC {:>3} → {} ({} .. {})", mapping.controller, mapping.parameter, mapping.min, mapping.max);
+    }
+    if let Some(parameter) = cc_map.learning() {
+        println!("🎛️  {}", i18n::fill(i18n::tr("Learning: waiting for a CC for {}", "ラーン中: {} に割り当てる CC を待っています"), &[&parameter]));
+    }
+}
+
+// SCALE <秒数> [間隔]: 音階を前の音が終わってから次の音を鳴らす形で並べる
+fn handle_scale_command(synth: &SynthHandle, args: &str, settings: &PhraseSettings, rng: &mut Rng) {
+    if args.is_empty() {
+        cli::print_command_help("SCALE");
+        return;
+    }
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let (length, gap) = match parts.as_slice() {
+        [length] => (length.parse::<f32>(), Ok(settings.gap)),
+        [length, gap] => (length.parse::<f32>(), gap.parse::<f32>()),
+        _ => {
+            cli::print_usage("SCALE");
+            return;
+        }
+    };
+    let (length, gap) = match (length, gap) {
+        (Ok(length), Ok(gap)) if length > 0.0 && gap >= 0.0 => (length, gap),
+        (Ok(_), Ok(_)) => {
+            println!("❌ {}", i18n::tr(
+                "Duration must be greater than 0 and the gap must not be negative",
+                "持続時間は0より大きく、間隔は0以上にしてください",
+            ));
+            return;
+        }
+        _ => {
+            println!("❌ {}", i18n::tr(
+                "Invalid duration format. Use numbers like 2.5, 1.8, etc.",
+                "持続時間の形式が正しくありません。2.5 や 1.8 のような数値を使ってください",
+            ));
+            return;
+        }
+    };
+    
+    let settings = PhraseSettings { gap, ..*settings };
+    let notes = build_phrase(PhraseKind::Run, &SCALE_NOTES, length, &settings, rng);
+    play_phrase(synth, &notes);
+    println!(
+        "🎵 Scale ON: C-D-E-F-G-A-B-C over {:.1} seconds ({} notes, gap {:.0} ms, {})",
+        length,
+        notes.len(),
+        gap * 1000.0,
+        describe_phrase(&settings)
+    );
+}
+
+// SCALE / CHORD のフレーズ設定
+fn handle_phrase_command(settings: &mut PhraseSettings, args: &str) {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let valid = match parts.as_slice() {
+        [] => true,
+        ["reset"] => {
+            *settings = PhraseSettings::default();
+            true
+        }
+        ["spacing", "auto"] => {
+            settings.spacing = None;
+            true
+        }
+        ["spacing", milliseconds] => match milliseconds.parse::<f32>() {
+            Ok(milliseconds) if milliseconds >= 0.0 => {
+                settings.spacing = Some(milliseconds / 1000.0);
+                true
+            }
+            _ => false,
+        },
+        ["direction", direction] => match direction.parse::<PhraseDirection>() {
+            Ok(direction) => {
+                settings.direction = direction;
+                true
+            }
+            Err(_) => false,
+        },
+        ["octaves", octaves] => match octaves.parse::<u8>() {
+            Ok(octaves @ 1..=4) => {
+                settings.octaves = octaves;
+                true
+            }
+            _ => false,
+        },
+        ["humanize", velocity, rest @ ..] => {
+            let timing = rest.first().map_or(Ok(settings.timing_humanize * 1000.0), |ms| ms.parse::<f32>());
+            match (velocity.parse::<f32>(), timing) {
+                (Ok(velocity), Ok(milliseconds)) if (0.0..=1.0).contains(&velocity) && milliseconds >= 0.0 => {
+                    settings.velocity_humanize = velocity;
+                    settings.timing_humanize = milliseconds / 1000.0;
+                    true
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    };
+    if !valid {
+        cli::print_usage("phrase");
+        return;
     }
+    println!("🎼 Phrase: {}", describe_phrase(settings));
 }
@@ -0,0 +1,90 @@
+use std::f32::consts::PI;
+
+const CLICK_SECONDS: f32 = 0.03;
+
+// テンポに追従する拍カウンターとクリック音。
+// 拍の位置はクリックが無効でも常に進み、拍表示に使われる。
+#[derive(Debug, Clone)]
+pub struct Metronome {
+    enabled: bool,
+    level: f32,
+    beats_per_bar: u32,
+    beats: f64, // 生成済みのサンプルにおける拍位置
+    click_phase: f32,
+    click_frequency: f32,
+    click_remaining: f32, // 秒
+}
+
+impl Metronome {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            level: 0.3,
+            beats_per_bar: 4,
+            beats: 0.0,
+            click_phase: 0.0,
+            click_frequency: 0.0,
+            click_remaining: 0.0,
+        }
+    }
+    
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+    
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    
+    pub fn set_beats_per_bar(&mut self, beats_per_bar: u32) {
+        self.beats_per_bar = beats_per_bar.max(1);
+    }
+    
+    pub fn beats_per_bar(&self) -> u32 {
+        self.beats_per_bar
+    }
+    
+    // 拍位置を先頭に戻す（シーケンサーの再生開始時など）
+    pub fn reset(&mut self) {
+        self.beats = 0.0;
+        self.click_remaining = 0.0;
+    }
+    
+    pub fn beats(&self) -> f64 {
+        self.beats
+    }
+    
+    // 拍位置を移す（プラグインのホストの位置に合わせるときなど）。クリックは次の拍から鳴らす
+    pub fn locate(&mut self, beats: f64) {
+        self.beats = beats.max(0.0);
+        self.click_remaining = 0.0;
+    }
+    
+    // 1サンプル進め、クリック音を返す
+    pub fn next_sample(&mut self, sample_rate: f32, tempo_bpm: f32) -> f32 {
+        let previous = self.beats;
+        self.beats += tempo_bpm as f64 / 60.0 / sample_rate as f64;
+        if previous == 0.0 || self.beats.floor() > previous.floor() {
+            let beat = if previous == 0.0 { 0 } else { self.beats.floor() as u64 };
+            // 小節の頭は高い音でアクセントを付ける
+            self.click_frequency = if beat.is_multiple_of(self.beats_per_bar as u64) { 1500.0 } else { 1000.0 };
+            self.click_phase = 0.0;
+            self.click_remaining = CLICK_SECONDS;
+        }
+        
+        if !self.enabled || self.click_remaining <= 0.0 {
+            return 0.0;
+        }
+        let envelope = self.click_remaining / CLICK_SECONDS;
+        let sample = (self.click_phase * 2.0 * PI).sin() * envelope * envelope * self.level;
+        self.click_phase = (self.click_phase + self.click_frequency / sample_rate).fract();
+        self.click_remaining -= 1.0 / sample_rate;
+        sample
+    }
+}
+
+impl Default for Metronome {
+    fn default() -> Self {
+        Self::new()
+    }
+}
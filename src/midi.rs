@@ -0,0 +1,246 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use crate::command::Command;
+use crate::event::{Event, DEFAULT_RELEASE_VELOCITY};
+use crate::handle::SynthHandle;
+use crate::modulation::Controller;
+use crate::synth::Synthesizer;
+
+// 解釈した MIDI メッセージ。channel は 0-15（表示は 1-16）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    NoteOff { channel: u8, note: u8, velocity: u8 },
+    PolyPressure { channel: u8, note: u8, value: u8 },
+    ControlChange { channel: u8, controller: u8, value: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    ChannelPressure { channel: u8, value: u8 },
+    PitchBend { channel: u8, value: u16 }, // 0-16383（8192 = 中央）
+    Clock,
+    Start,
+    Continue,
+    Stop,
+}
+
+impl MidiMessage {
+    pub fn channel(&self) -> Option<u8> {
+        match *self {
+            MidiMessage::NoteOn { channel, .. }
+            | MidiMessage::NoteOff { channel, .. }
+            | MidiMessage::PolyPressure { channel, .. }
+            | MidiMessage::ControlChange { channel, .. }
+            | MidiMessage::ProgramChange { channel, .. }
+            | MidiMessage::ChannelPressure { channel, .. }
+            | MidiMessage::PitchBend { channel, .. } => Some(channel),
+            MidiMessage::Clock | MidiMessage::Start | MidiMessage::Continue | MidiMessage::Stop => None,
+        }
+    }
+    
+    // シンセサイザーのイベントにする。ベロシティ 0 のノートオンはノートオフ。
+    // ピッチベンドとプレッシャーはイベントではなくコントローラーとして扱う（dispatch を参照）
+    pub fn to_event(&self) -> Option<Event> {
+        match *self {
+            MidiMessage::NoteOn { note, velocity: 0, .. } => Some(Event::NoteOff { note, velocity: DEFAULT_RELEASE_VELOCITY }),
+            MidiMessage::NoteOff { note, velocity, .. } => Some(Event::NoteOff { note, velocity: velocity as f32 / 127.0 }),
+            MidiMessage::NoteOn { note, velocity, .. } => Some(Event::NoteOn { note, velocity: velocity as f32 / 127.0 }),
+            MidiMessage::ControlChange { controller, value, .. } => Some(Event::ControlChange { controller, value }),
+            MidiMessage::ProgramChange { program, .. } => Some(Event::ProgramChange { program }),
+            MidiMessage::Clock => Some(Event::Clock),
+            MidiMessage::Start => Some(Event::Start),
+            MidiMessage::Continue => Some(Event::Continue),
+            MidiMessage::Stop => Some(Event::Stop),
+            MidiMessage::PolyPressure { .. } | MidiMessage::ChannelPressure { .. } | MidiMessage::PitchBend { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for MidiMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(channel) = self.channel() {
+            write!(f, "ch{:<2} ", channel + 1)?;
+        }
+        match *self {
+            MidiMessage::NoteOn { note, velocity, .. } => write!(f, "note on    {:3} vel {:3}", note, velocity),
+            MidiMessage::NoteOff { note, velocity, .. } => write!(f, "note off   {:3} vel {:3}", note, velocity),
+            MidiMessage::PolyPressure { note, value, .. } => write!(f, "poly press {:3} val {:3}", note, value),
+            MidiMessage::ControlChange { controller, value, .. } => write!(f, "cc         {:3} val {:3}", controller, value),
+            MidiMessage::ProgramChange { program, .. } => write!(f, "program    {:3}", program),
+            MidiMessage::ChannelPressure { value, .. } => write!(f, "pressure   {:3}", value),
+            MidiMessage::PitchBend { value, .. } => write!(f, "pitch bend {:+5}", value as i32 - 8192),
+            MidiMessage::Clock => write!(f, "clock"),
+            MidiMessage::Start => write!(f, "start"),
+            MidiMessage::Continue => write!(f, "continue"),
+            MidiMessage::Stop => write!(f, "stop"),
+        }
+    }
+}
+
+// バイト列から MIDI メッセージを取り出す。ランニングステータスに対応し、
+// システムエクスクルーシブと未対応のシステムメッセージは読み飛ばす。
+// リアルタイムメッセージは他のメッセージの途中に挟まっていても取り出す
+#[derive(Debug, Clone, Default)]
+pub struct MidiParser {
+    status: Option<u8>,
+    data: [u8; 2],
+    length: usize,
+    in_sysex: bool,
+}
+
+impl MidiParser {
+    pub fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        if byte >= 0xF8 {
+            return match byte {
+                0xF8 => Some(MidiMessage::Clock),
+                0xFA => Some(MidiMessage::Start),
+                0xFB => Some(MidiMessage::Continue),
+                0xFC => Some(MidiMessage::Stop),
+                _ => None,
+            };
+        }
+        if byte >= 0x80 {
+            self.length = 0;
+            self.in_sysex = byte == 0xF0;
+            // システムコモンはランニングステータスを取り消す
+            self.status = (byte < 0xF0).then_some(byte);
+            return None;
+        }
+        if self.in_sysex {
+            return None;
+        }
+        let status = self.status?;
+        self.data[self.length] = byte;
+        self.length += 1;
+        let needed = match status & 0xF0 {
+            0xC0 | 0xD0 => 1,
+            _ => 2,
+        };
+        if self.length < needed {
+            return None;
+        }
+        self.length = 0;
+        let channel = status & 0x0F;
+        let [first, second] = self.data;
+        Some(match status & 0xF0 {
+            0x80 => MidiMessage::NoteOff { channel, note: first, velocity: second },
+            0x90 => MidiMessage::NoteOn { channel, note: first, velocity: second },
+            0xA0 => MidiMessage::PolyPressure { channel, note: first, value: second },
+            0xB0 => MidiMessage::ControlChange { channel, controller: first, value: second },
+            0xC0 => MidiMessage::ProgramChange { channel, program: first },
+            0xD0 => MidiMessage::ChannelPressure { channel, value: first },
+            _ => MidiMessage::PitchBend { channel, value: first as u16 | (second as u16) << 7 },
+        })
+    }
+}
+
+// メッセージをシンセサイザーに渡す。ノートのベロシティは device の較正プロファイルを通り、
+// チャンネルはレイヤーの受信フィルターで使う
+pub fn dispatch(synth: &mut Synthesizer, device: &str, message: &MidiMessage) {
+    match *message {
+        MidiMessage::PitchBend { value, .. } => {
+            synth.set_controller(Controller::PitchBend, (value as f32 - 8192.0) / 8192.0, 0.0);
+        }
+        MidiMessage::ChannelPressure { value, .. } => {
+            synth.set_controller(Controller::Aftertouch, value as f32 / 127.0, 0.0);
+        }
+        MidiMessage::NoteOn { channel, note, velocity } if velocity > 0 => {
+            synth.handle_midi_note_on(device, channel, note, velocity as f32 / 127.0);
+        }
+        _ => {
+            if let Some(event) = message.to_event() {
+                synth.handle_device_event(device, &event);
+            }
+        }
+    }
+}
+
+// 演奏中の入力を届いた時刻（arrived）に合わせてブロックの中に置く（Synthesizer::set_live_lookahead が有効なとき）。
+// ピッチベンドとプレッシャーは先読みせずにすぐ渡す
+pub fn dispatch_live(synth: &mut Synthesizer, device: &str, message: &MidiMessage, arrived: Instant) {
+    let event = match *message {
+        MidiMessage::NoteOn { channel, note, velocity } if velocity > 0 => {
+            Some(Event::MidiNoteOn { channel, note, velocity: velocity as f32 / 127.0 })
+        }
+        _ => message.to_event(),
+    };
+    if let Some(event) = event {
+        let event = synth.calibrate_event(device, &event);
+        if synth.schedule_live(arrived, event) {
+            return;
+        }
+    }
+    dispatch(synth, device, message);
+}
+
+// 生の MIDI バイト列を読める機器（Linux の /dev/snd/midiC*D* など）から受け取って演奏する。
+// 受け取ったメッセージはロックを取らずにコマンドのキューで渡し、オーディオスレッドで dispatch_live に通す。
+// モニターを有効にすると、受け取ったメッセージを時刻とチャンネル付きで表示する
+pub struct MidiInput {
+    path: PathBuf,
+    monitor: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MidiInput {
+    pub fn open(path: impl AsRef<Path>, synth: &SynthHandle) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = File::open(&path)?;
+        let (mut commands, device) = {
+            let mut synth = synth.borrow_or_timeout()?;
+            (synth.command_sender(), synth.register_device(&path.display().to_string()))
+        };
+        let monitor = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (monitor, stop) = (Arc::clone(&monitor), Arc::clone(&stop));
+            std::thread::spawn(move || {
+                let start = Instant::now();
+                let mut parser = MidiParser::default();
+                let mut buffer = [0u8; 256];
+                // 読み取りは次のバイトが届くまで戻らないので、停止はその後に反映される
+                while !stop.load(Ordering::Relaxed) {
+                    let count = match file.read(&mut buffer) {
+                        Ok(0) | Err(_) => break,
+                        Ok(count) => count,
+                    };
+                    let arrived = Instant::now();
+                    for message in buffer[..count].iter().filter_map(|byte| parser.push(*byte)) {
+                        commands.send(Command::Midi { device, message, arrived });
+                        // クロックは毎秒数十回届くので表示しない
+                        if monitor.load(Ordering::Relaxed) && message != MidiMessage::Clock {
+                            println!("🎹 {:9.3}  {}", start.elapsed().as_secs_f64(), message);
+                        }
+                    }
+                }
+            })
+        };
+        Ok(Self { path, monitor, stop, thread: Some(thread) })
+    }
+    
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    pub fn set_monitor(&self, enabled: bool) {
+        self.monitor.store(enabled, Ordering::Relaxed);
+    }
+    
+    pub fn monitor(&self) -> bool {
+        self.monitor.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for MidiInput {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // 読み取り中のスレッドは待たない（機器が次のバイトを送るか閉じられると終わる）
+        self.thread.take();
+    }
+}
@@ -0,0 +1,205 @@
+// MIDI 入力の解釈と演奏への割り当て（ベロシティの較正・CC・プログラムチェンジ）のテスト
+use crate::event::Event;
+use crate::synth::Synthesizer;
+use crate::voice_tests::releasing;
+
+// 機器の較正プロファイルはシンセサイザーのベロシティカーブより前に掛かる
+#[test]
+fn device_velocity_profile_applies_before_synth_curve() {
+    use crate::velocity::{VelocityCurve, VelocityProfiles};
+    
+    let mut profiles = VelocityProfiles::default();
+    profiles.set_profile("stiff", VelocityCurve::new(0.2, 0.6, 1.0));
+    profiles.assign("Keystation", "stiff").unwrap();
+    assert!(profiles.assign("Keystation", "missing").is_err());
+    let mut synth = Synthesizer::builder()
+        .sample_rate(22050.0)
+        .velocity_curve(VelocityCurve { exponent: 2.0, ..VelocityCurve::default() })
+        .velocity_profiles(profiles)
+        .build();
+    
+    synth.handle_device_event("Keystation", &Event::NoteOn { note: 60, velocity: 0.4 });
+    assert!((synth.current_velocity().unwrap() - 0.25).abs() < 1e-6);
+    synth.handle_device_event("Keystation", &Event::NoteOn { note: 62, velocity: 0.9 });
+    assert_eq!(synth.current_velocity(), Some(1.0));
+    // 割り当てのない機器はベロシティカーブだけ
+    synth.handle_device_event("Other", &Event::NoteOn { note: 64, velocity: 0.5 });
+    assert_eq!(synth.current_velocity(), Some(0.25));
+    
+    synth.velocity_profiles_mut().remove_profile("stiff");
+    assert!(synth.velocity_profiles().curve_for("Keystation").is_none());
+}
+
+// MIDI のバイト列はランニングステータスと途中に挟まったクロックを含めて解釈し、そのまま演奏に渡す
+#[test]
+fn midi_bytes_are_decoded_and_played() {
+    use crate::midi::{self, MidiMessage, MidiParser};
+    
+    let bytes = [0x91, 60, 100, 0xF8, 64, 90, 0xF0, 0x43, 0x10, 0xF7, 0x81, 60, 0, 0xE1, 0, 0x60, 0x90, 67, 0];
+    let mut parser = MidiParser::default();
+    let messages: Vec<MidiMessage> = bytes.iter().filter_map(|byte| parser.push(*byte)).collect();
+    assert_eq!(messages, vec![
+        MidiMessage::NoteOn { channel: 1, note: 60, velocity: 100 },
+        MidiMessage::Clock,
+        MidiMessage::NoteOn { channel: 1, note: 64, velocity: 90 },
+        MidiMessage::NoteOff { channel: 1, note: 60, velocity: 0 },
+        MidiMessage::PitchBend { channel: 1, value: 0x3000 },
+        MidiMessage::NoteOn { channel: 0, note: 67, velocity: 0 },
+    ]);
+    assert_eq!(messages[0].to_string(), "ch2  note on     60 vel 100");
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    synth.note_on(67, 0.8);
+    for message in &messages {
+        midi::dispatch(&mut synth, "test", message);
+    }
+    // ベロシティ 0 のノートオンはノートオフ
+    assert_eq!(synth.active_notes(), vec![64]);
+    assert_eq!(synth.controllers().pitch_bend, 0.5);
+}
+
+// 先読みを有効にすると、演奏中の入力は届いた時刻に合わせて次のブロックの中のサンプル位置で鳴る
+#[test]
+fn live_midi_lands_at_its_arrival_offset() {
+    use crate::midi::{self, MidiMessage};
+    use std::time::{Duration, Instant};
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    let note = MidiMessage::NoteOn { channel: 0, note: 60, velocity: 100 };
+    let started = Instant::now();
+    for _ in 0..64 {
+        synth.next_frame();
+    }
+    synth.mark_block_rendered(started);
+    midi::dispatch_live(&mut synth, "test", &note, started + Duration::from_millis(1));
+    assert_eq!(synth.active_notes(), vec![60]); // 先読みが無効ならすぐに鳴る
+    
+    synth.all_notes_off();
+    synth.set_live_lookahead(0.001);
+    for _ in 0..64 {
+        synth.next_frame();
+    }
+    synth.mark_block_rendered(started);
+    let arrival = synth.now_samples() + 2 * 22; // 1 ms 後に届き、1 ms 遅らせる
+    midi::dispatch_live(&mut synth, "test", &note, started + Duration::from_millis(1));
+    assert!(synth.active_notes().is_empty());
+    while synth.now_samples() < arrival {
+        synth.next_frame();
+    }
+    assert!(synth.active_notes().is_empty());
+    synth.next_frame();
+    assert_eq!(synth.active_notes(), vec![60]);
+    
+    // オーディオが止まっていればすぐに処理する
+    synth.all_notes_off();
+    midi::dispatch_live(&mut synth, "test", &note, started + Duration::from_secs(1));
+    assert_eq!(synth.active_notes(), vec![60]);
+}
+
+// CC を morph に割り当てると、2つのパッチの間を演奏中のボイスごと補間する
+#[test]
+fn mapped_cc_moves_the_morph_position() {
+    use crate::patch::PatchState;
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    let mut from = PatchState { cutoff: 0.2, ..PatchState::default() };
+    from.envelope.release = 0.1;
+    let mut to = from.clone();
+    to.cutoff = 0.8;
+    to.envelope.release = 0.5;
+    assert!(synth.set_parameter("morph", 0.5).is_err());
+    
+    synth.start_morph(from, to, 0.0);
+    synth.cc_map_mut().assign(1, "morph", None).unwrap();
+    synth.note_on(60, 0.8);
+    synth.handle_event(&Event::ControlChange { controller: 1, value: 127 });
+    assert_eq!(synth.patch().cutoff, 0.8);
+    synth.set_parameter("morph", 0.25).unwrap();
+    assert!((synth.patch().cutoff - 0.35).abs() < 1e-6);
+    assert!((synth.patch().envelope.release - 0.2).abs() < 1e-6);
+    
+    synth.stop_morph();
+    synth.handle_event(&Event::ControlChange { controller: 1, value: 0 });
+    assert!((synth.patch().cutoff - 0.35).abs() < 1e-6);
+}
+
+// 対応表にあるプログラムチェンジは音色の読み込みを待ち、無い番号は従来どおりシーンを呼び出す
+#[test]
+fn program_changes_follow_the_mapping_table() {
+    use crate::program::{ProgramMap, ProgramTarget};
+    
+    let (map, warnings) = ProgramMap::from_text("# synthesizer program map v1\n5 preset pads/warm\n9 file /tmp/My Patches/lead.patch\n200 preset x\n");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(map.get(5), Some(&ProgramTarget::Preset("pads/warm".to_string())));
+    assert_eq!(map.get(9), Some(&ProgramTarget::File("/tmp/My Patches/lead.patch".into())));
+    assert_eq!(ProgramMap::from_text(&map.to_text()), (map.clone(), Vec::new()));
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    *synth.program_map_mut() = map;
+    assert!(synth.store_scene(3));
+    synth.handle_event(&Event::ProgramChange { program: 3 });
+    assert_eq!(synth.current_scene(), Some(3));
+    assert_eq!(synth.take_program_request(), None);
+    synth.handle_event(&Event::ProgramChange { program: 9 });
+    synth.handle_event(&Event::ProgramChange { program: 5 });
+    assert_eq!(synth.current_scene(), Some(3));
+    assert_eq!(synth.take_program_request(), Some((5, ProgramTarget::Preset("pads/warm".to_string()))));
+    assert_eq!(synth.take_program_request(), None);
+}
+
+// CC123 は1回なら通常の全ノートオフ（リリースが鳴る）、続けて2回でボイスを即座に消してコントローラーを戻す
+#[test]
+fn double_all_notes_off_silences_voices_at_once() {
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    synth.note_on(60, 0.8);
+    synth.note_on(64, 0.8);
+    synth.set_pitch_bend(0.7);
+    synth.handle_event(&Event::ControlChange { controller: 123, value: 0 });
+    assert_eq!(releasing(&synth), vec![60, 64]);
+    assert_eq!(synth.voice_count(), 2);
+    
+    synth.handle_event(&Event::ControlChange { controller: 123, value: 0 });
+    assert_eq!(synth.voice_count(), 0);
+    assert_eq!(synth.controllers().pitch_bend, 0.0);
+    assert_eq!(synth.next_frame(), [0.0, 0.0]);
+}
+
+// 2回目の CC123 が間隔より遅ければ通常の全ノートオフで、コントローラーはそのまま
+#[test]
+fn slow_second_all_notes_off_only_releases() {
+    use crate::synth::PANIC_DOUBLE_TAP;
+    
+    let sample_rate = 22050.0;
+    let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+    synth.set_pitch_bend(0.7);
+    synth.handle_event(&Event::ControlChange { controller: 123, value: 0 });
+    for _ in 0..((PANIC_DOUBLE_TAP.as_secs_f32() * sample_rate) as usize + 64) {
+        synth.next_frame();
+    }
+    synth.note_on(60, 0.8);
+    synth.handle_event(&Event::ControlChange { controller: 123, value: 0 });
+    assert_eq!(releasing(&synth), vec![60]);
+    assert_eq!(synth.controllers().pitch_bend, 0.7);
+}
+
+// オフラインの書き出しは実時間より速く進むが、曲の中で離れた CC123 はパニックにならない
+#[test]
+fn offline_render_keeps_distant_all_notes_off_apart() {
+    use crate::midi::MidiMessage;
+    use crate::render::render_midi;
+    use crate::smf::{MidiFile, TimedMessage};
+    
+    let all_notes_off = MidiMessage::ControlChange { channel: 0, controller: 123, value: 0 };
+    let events = vec![
+        TimedMessage { seconds: 0.0, message: MidiMessage::PitchBend { channel: 0, value: 12288 } },
+        TimedMessage { seconds: 0.0, message: MidiMessage::NoteOn { channel: 0, note: 60, velocity: 100 } },
+        TimedMessage { seconds: 0.5, message: all_notes_off },
+        TimedMessage { seconds: 1.0, message: MidiMessage::NoteOn { channel: 0, note: 64, velocity: 100 } },
+        TimedMessage { seconds: 2.0, message: all_notes_off },
+    ];
+    let mut synth = Synthesizer::builder().sample_rate(8000.0).build();
+    let frames = render_midi(&mut synth, &MidiFile { events });
+    assert_eq!(synth.controllers().pitch_bend, 0.5);
+    // 2回目のノートオフの後もリリースが鳴っている（パニックなら即座に無音になる）
+    assert!(frames[16001..16400].iter().any(|frame| frame[0].abs() > 1e-3));
+}
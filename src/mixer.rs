@@ -0,0 +1,98 @@
+use std::fmt;
+use std::str::FromStr;
+
+// パンの法則: 中央に置いたときの減衰量（左右どちらかに振り切った状態との比）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanLaw {
+    #[default]
+    ConstantPower, // -3 dB（等パワー）
+    Compromise,    // -4.5 dB（-3 dB と -6 dB の中間）
+    Linear,        // -6 dB（振幅が一定、モノラルにしたときに音量が変わらない）
+}
+
+impl PanLaw {
+    // pan（-1.0 = 左, 1.0 = 右）での左右のゲイン。中央で 1.0 になるように正規化しているので、
+    // 法則を変えても中央に置いた音の音量は変わらず、振り切ったときの持ち上がり方だけが変わる
+    pub fn gains(self, pan: f32) -> [f32; 2] {
+        let pan = pan.clamp(-1.0, 1.0);
+        let [left, right] = self.raw_gains(pan);
+        let [center, _] = self.raw_gains(0.0);
+        [left / center, right / center]
+    }
+    
+    fn raw_gains(self, pan: f32) -> [f32; 2] {
+        let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+        let power = [angle.cos(), angle.sin()];
+        let linear = [(1.0 - pan) * 0.5, (1.0 + pan) * 0.5];
+        match self {
+            PanLaw::ConstantPower => power,
+            PanLaw::Compromise => [(power[0] * linear[0]).sqrt(), (power[1] * linear[1]).sqrt()],
+            PanLaw::Linear => linear,
+        }
+    }
+    
+    pub fn center_db(self) -> f32 {
+        match self {
+            PanLaw::ConstantPower => -3.0,
+            PanLaw::Compromise => -4.5,
+            PanLaw::Linear => -6.0,
+        }
+    }
+}
+
+impl fmt::Display for PanLaw {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} dB", self.center_db())
+    }
+}
+
+impl FromStr for PanLaw {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim().trim_end_matches("dB").trim_end_matches("db").trim();
+        match value.trim_start_matches('-') {
+            "3" => Ok(PanLaw::ConstantPower),
+            "4.5" => Ok(PanLaw::Compromise),
+            "6" => Ok(PanLaw::Linear),
+            _ => Err(format!("unknown pan law: {} (use -3, -4.5 or -6)", s)),
+        }
+    }
+}
+
+// マスター出力をモノラルにしたときの音量の落ち込みを測る。
+// 左右の相関が高いと 0 dB、無相関だと約 -3 dB、逆相の成分が多いほど大きく下がる。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FoldDownMeter {
+    stereo_energy: f32,
+    mono_energy: f32,
+    decay: f32,
+}
+
+impl FoldDownMeter {
+    pub fn new(sample_rate: f32, seconds: f32) -> Self {
+        Self {
+            stereo_energy: 0.0,
+            mono_energy: 0.0,
+            decay: 0.001_f32.powf(1.0 / (seconds * sample_rate)),
+        }
+    }
+    
+    pub fn process(&mut self, frame: [f32; 2]) {
+        let [left, right] = frame;
+        let mono = (left + right) * 0.5;
+        self.stereo_energy = self.stereo_energy * self.decay + (left * left + right * right) * 0.5 * (1.0 - self.decay);
+        self.mono_energy = self.mono_energy * self.decay + mono * mono * (1.0 - self.decay);
+    }
+    
+    // モノラルにしたときの音量の変化（dB）。無音なら None
+    pub fn loss_db(&self) -> Option<f32> {
+        (self.stereo_energy > 1e-10).then(|| 10.0 * (self.mono_energy.max(1e-12) / self.stereo_energy).log10())
+    }
+}
+
+// モノラル互換チェック用に左右を足し合わせる
+pub fn fold_down(frame: [f32; 2]) -> [f32; 2] {
+    let mono = (frame[0] + frame[1]) * 0.5;
+    [mono, mono]
+}
@@ -0,0 +1,796 @@
+use std::fmt;
+use std::str::FromStr;
+
+// モジュレーションはこのサンプル数ごとにまとめて計算する（コントロールレート）
+pub const MOD_BLOCK_SIZE: usize = 32;
+pub const BREAKPOINT_ENVELOPE_COUNT: usize = 4;
+
+// ブレークポイント: 前のポイントから time かけて level へ向かう
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Breakpoint {
+    pub time: f32,  // 秒（テンポ同期時は拍）
+    pub level: f32,
+    pub curve: f32, // 0.0 = 直線, 正 = ゆっくり立ち上がる, 負 = 素早く立ち上がる
+}
+
+// 任意の時間・レベル・カーブのポイントからなるエンベロープ
+#[derive(Debug, PartialEq, Default)]
+pub struct BreakpointEnvelope {
+    pub points: Vec<Breakpoint>,
+    pub loop_points: Option<(usize, usize)>, // ゲート中に end から start へ戻る
+    pub tempo_sync: bool,
+}
+
+// 導出した clone_from は作り直すので、ポイントは確保済みの領域に写す（シーンの補間などオーディオスレッドで使う）
+impl Clone for BreakpointEnvelope {
+    fn clone(&self) -> Self {
+        Self { points: self.points.clone(), loop_points: self.loop_points, tempo_sync: self.tempo_sync }
+    }
+    
+    fn clone_from(&mut self, source: &Self) {
+        self.points.clone_from(&source.points);
+        self.loop_points = source.loop_points;
+        self.tempo_sync = source.tempo_sync;
+    }
+}
+
+impl BreakpointEnvelope {
+    pub fn add_point(&mut self, time: f32, level: f32, curve: f32) {
+        self.points.push(Breakpoint { time: time.max(0.0), level, curve });
+    }
+    
+    pub fn clear(&mut self) {
+        self.points.clear();
+        self.loop_points = None;
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+    
+    // ループ区間の長さが 0 の場合は設定しない
+    pub fn set_loop(&mut self, start: usize, end: usize) -> bool {
+        let valid = start < end
+            && end < self.points.len()
+            && self.points[start + 1..=end].iter().any(|point| point.time > 0.0);
+        if valid {
+            self.loop_points = Some((start, end));
+        }
+        valid
+    }
+    
+    fn segment_seconds(&self, index: usize, tempo_bpm: f32) -> f32 {
+        let time = self.points[index].time;
+        if self.tempo_sync {
+            time * 60.0 / tempo_bpm.max(1.0)
+        } else {
+            time
+        }
+    }
+}
+
+// ボイスごとのブレークポイントエンベロープの再生状態
+#[derive(Debug, Clone, Default)]
+pub struct BreakpointState {
+    position: usize, // 向かっているポイント
+    time_in_segment: f32,
+    start_level: f32,
+    level: f32,
+    gate: bool,
+    finished: bool,
+}
+
+impl BreakpointState {
+    pub fn trigger(&mut self) {
+        *self = Self {
+            gate: true,
+            ..Self::default()
+        };
+    }
+    
+    pub fn release(&mut self) {
+        self.gate = false;
+    }
+    
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+    
+    pub fn advance(&mut self, envelope: &BreakpointEnvelope, seconds: f32, tempo_bpm: f32) -> f32 {
+        // 再生中にポイントが減らされた場合はそこで終了する
+        if self.finished || self.position >= envelope.points.len() {
+            self.finished = true;
+            return self.level;
+        }
+        
+        self.time_in_segment += seconds;
+        loop {
+            let target = envelope.points[self.position];
+            let length = envelope.segment_seconds(self.position, tempo_bpm);
+            if self.time_in_segment < length {
+                let progress = (self.time_in_segment / length).powf(2.0_f32.powf(target.curve));
+                self.level = self.start_level + (target.level - self.start_level) * progress;
+                break;
+            }
+            
+            self.time_in_segment -= length;
+            self.level = target.level;
+            self.start_level = target.level;
+            match envelope.loop_points {
+                Some((start, end)) if self.gate && self.position == end => {
+                    self.position = start + 1;
+                    self.start_level = envelope.points[start].level;
+                }
+                _ => self.position += 1,
+            }
+            if self.position >= envelope.points.len() {
+                self.finished = true;
+                break;
+            }
+        }
+        self.level
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LfoShape {
+    #[default]
+    Sine,     // -1.0〜1.0
+    Triangle, // -1.0〜1.0
+    Square,   // -1.0〜1.0
+    RampUp,   // 0.0〜1.0
+    RampDown, // 1.0〜0.0（ワンショットで減衰するモジュレーションエンベロープになる）
+}
+
+impl LfoShape {
+    // phase は 0.0〜1.0
+    pub fn value(self, phase: f32) -> f32 {
+        match self {
+            LfoShape::Sine => (phase * std::f32::consts::TAU).sin(),
+            LfoShape::Triangle => {
+                let shifted = phase + 0.25;
+                1.0 - 4.0 * (shifted - shifted.floor() - 0.5).abs()
+            }
+            LfoShape::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            LfoShape::RampUp => phase,
+            LfoShape::RampDown => 1.0 - phase,
+        }
+    }
+}
+
+impl fmt::Display for LfoShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LfoShape::Sine => "sine",
+            LfoShape::Triangle => "triangle",
+            LfoShape::Square => "square",
+            LfoShape::RampUp => "up",
+            LfoShape::RampDown => "down",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for LfoShape {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sine" => Ok(LfoShape::Sine),
+            "triangle" | "tri" => Ok(LfoShape::Triangle),
+            "square" => Ok(LfoShape::Square),
+            "up" => Ok(LfoShape::RampUp),
+            "down" => Ok(LfoShape::RampDown),
+            _ => Err(format!("unknown LFO shape: {}", s)),
+        }
+    }
+}
+
+// ボイスごとに発音時から位相を始める LFO
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lfo {
+    pub rate: f32, // Hz（sync では1拍あたりの周期数）
+    pub shape: LfoShape,
+    pub fade: f32,      // 発音から深さが最大になるまでの秒数（0 で即座に。fade_sync では拍）
+    pub fade_sync: bool, // fade を拍で指定する
+    pub one_shot: bool, // 1周期だけ動いて最後の値で止まる（簡易モジュレーションエンベロープ）
+    pub sync: bool,     // rate を1拍あたりの周期数としてテンポに同期する
+}
+
+impl Lfo {
+    pub fn frequency(&self, tempo_bpm: f32) -> f32 {
+        if self.sync {
+            self.rate * tempo_bpm / 60.0
+        } else {
+            self.rate
+        }
+    }
+    
+    pub fn fade_seconds(&self, tempo_bpm: f32) -> f32 {
+        if self.fade_sync {
+            self.fade * 60.0 / tempo_bpm.max(1.0)
+        } else {
+            self.fade
+        }
+    }
+}
+
+impl Default for Lfo {
+    fn default() -> Self {
+        Self { rate: 5.0, shape: LfoShape::Sine, fade: 0.0, fade_sync: false, one_shot: false, sync: false }
+    }
+}
+
+// 音符の長さ（"1/4" が1拍、付点は "1/8."、3連符は "1/8t"）を拍数に直す
+pub fn parse_division(text: &str) -> Option<f32> {
+    let (body, factor) = match text.strip_suffix('.') {
+        Some(body) => (body, 1.5),
+        None => match text.strip_suffix('t') {
+            Some(body) => (body, 2.0 / 3.0),
+            None => (text, 1.0),
+        },
+    };
+    let (count, division) = body.split_once('/')?;
+    let count = count.parse::<f32>().ok().filter(|count| *count > 0.0)?;
+    let division = division.parse::<f32>().ok().filter(|division| *division > 0.0)?;
+    Some(count * 4.0 / division * factor)
+}
+
+// ボイスごとの LFO の再生状態
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LfoState {
+    phase: f32,
+    elapsed: f32,
+    finished: bool,
+}
+
+impl LfoState {
+    pub fn trigger(&mut self) {
+        *self = Self::default();
+    }
+    
+    pub fn advance(&mut self, lfo: &Lfo, seconds: f32, tempo_bpm: f32) -> f32 {
+        self.elapsed += seconds;
+        if !self.finished {
+            self.phase += lfo.frequency(tempo_bpm).max(0.0) * seconds;
+            if self.phase >= 1.0 {
+                if lfo.one_shot {
+                    self.phase = 1.0;
+                    self.finished = true;
+                } else {
+                    self.phase -= self.phase.floor();
+                }
+            }
+        }
+        let fade_seconds = lfo.fade_seconds(tempo_bpm);
+        let fade = if fade_seconds > 0.0 { (self.elapsed / fade_seconds).min(1.0) } else { 1.0 };
+        // ワンショットの終点は周期の終わりの値（RampUp なら 1.0）
+        let value = if self.finished { lfo.shape.value(1.0 - f32::EPSILON) } else { lfo.shape.value(self.phase) };
+        value * fade
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModSource {
+    Velocity,
+    KeyTrack,          // C4 を 0 として ±5 オクターブで -1.0〜1.0
+    Breakpoint(usize), // ブレークポイントエンベロープ 1〜4
+    PitchBend,         // -1.0〜1.0
+    ModWheel,          // 0.0〜1.0
+    Aftertouch,        // 0.0〜1.0（チャンネルプレッシャー）
+    Lfo,
+    Random,            // 発音ごとに引き直す -1.0〜1.0（サンプル＆ホールド）
+    ReleaseVelocity,   // ノートオフのベロシティ 0.0〜1.0（離鍵までは 0）
+    Duck,              // サイドチェインのダッキング 0.0〜1.0（全ボイス共通）
+}
+
+// 全ボイス共通の演奏コントローラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Controller {
+    PitchBend,
+    ModWheel,
+    Aftertouch,
+}
+
+impl Controller {
+    pub fn range(&self) -> (f32, f32) {
+        match self {
+            Controller::PitchBend => (-1.0, 1.0),
+            Controller::ModWheel | Controller::Aftertouch => (0.0, 1.0),
+        }
+    }
+    
+    pub fn source(&self) -> ModSource {
+        match self {
+            Controller::PitchBend => ModSource::PitchBend,
+            Controller::ModWheel => ModSource::ModWheel,
+            Controller::Aftertouch => ModSource::Aftertouch,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Controllers {
+    pub pitch_bend: f32,
+    pub mod_wheel: f32,
+    pub aftertouch: f32,
+    pub duck: f32, // ダッキングのエンベロープ（シンセサイザーが進める）
+}
+
+impl Controllers {
+    pub fn get(&self, controller: Controller) -> f32 {
+        match controller {
+            Controller::PitchBend => self.pitch_bend,
+            Controller::ModWheel => self.mod_wheel,
+            Controller::Aftertouch => self.aftertouch,
+        }
+    }
+    
+    pub fn set(&mut self, controller: Controller, value: f32) {
+        let (min, max) = controller.range();
+        let value = value.clamp(min, max);
+        match controller {
+            Controller::PitchBend => self.pitch_bend = value,
+            Controller::ModWheel => self.mod_wheel = value,
+            Controller::Aftertouch => self.aftertouch = value,
+        }
+    }
+}
+
+// コントローラーを一定時間かけて目標値へ動かす
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControllerRamp {
+    pub controller: Controller,
+    pub from: f32,
+    pub to: f32,
+    pub elapsed: f32,
+    pub duration: f32,
+}
+
+impl ControllerRamp {
+    // 進めた後の値と、目標に到達したかを返す
+    pub fn advance(&mut self, seconds: f32) -> (f32, bool) {
+        self.elapsed += seconds;
+        let progress = (self.elapsed / self.duration.max(f32::EPSILON)).min(1.0);
+        (self.from + (self.to - self.from) * progress, progress >= 1.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModDestination {
+    Pitch,      // 半音
+    Cutoff,     // 正規化カットオフへの加算
+    Blend,
+    Brightness,
+    Amplitude,  // 1.0 + 値 を掛ける
+    Pan,        // 定位への加算（-1.0 = 左, 1.0 = 右）
+    Depth,      // 奥行きへの加算（0.0 = 前, 1.0 = 後ろ、サラウンド出力のみ）
+    Bloom,      // 鳴らす倍音の範囲（0.0 = 基音のみ, 1.0 = 全倍音、割り当てたときだけ有効）
+    Tilt,       // 倍音のスペクトルの傾きへの加算（dB/oct、加算合成のみ）
+}
+
+impl ModDestination {
+    pub const ALL: [ModDestination; 9] = [
+        ModDestination::Pitch,
+        ModDestination::Cutoff,
+        ModDestination::Blend,
+        ModDestination::Brightness,
+        ModDestination::Amplitude,
+        ModDestination::Pan,
+        ModDestination::Depth,
+        ModDestination::Bloom,
+        ModDestination::Tilt,
+    ];
+    
+    fn index(self) -> usize {
+        self as usize
+    }
+    
+    // ルーティングを合成した後の値の範囲（これを超える分は切り捨てる）
+    pub fn range(self) -> (f32, f32) {
+        match self {
+            ModDestination::Pitch => (-48.0, 48.0),
+            ModDestination::Cutoff | ModDestination::Blend | ModDestination::Brightness | ModDestination::Depth => (-1.0, 1.0),
+            ModDestination::Amplitude => (-1.0, 3.0),
+            ModDestination::Pan => (-2.0, 2.0),
+            ModDestination::Bloom => (0.0, 1.0),
+            ModDestination::Tilt => (-24.0, 24.0),
+        }
+    }
+}
+
+// 同じ送り先への複数のルーティングの合わせ方。ルーティングは追加した順に合わせる
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ModCombine {
+    #[default]
+    Sum,      // 足し合わせる
+    Multiply, // 掛け合わせる（例: LFO × モジュレーションホイールで深さを操作）
+    Max,      // 最も大きい値を使う
+}
+
+impl ModCombine {
+    fn combine(self, current: f32, value: f32) -> f32 {
+        match self {
+            ModCombine::Sum => current + value,
+            ModCombine::Multiply => current * value,
+            ModCombine::Max => current.max(value),
+        }
+    }
+}
+
+// 送り先ごとの合わせ方（パッチに保存する）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ModCombineModes {
+    modes: [ModCombine; ModDestination::ALL.len()],
+}
+
+impl ModCombineModes {
+    pub fn get(&self, destination: ModDestination) -> ModCombine {
+        self.modes[destination.index()]
+    }
+    
+    pub fn set(&mut self, destination: ModDestination, mode: ModCombine) {
+        self.modes[destination.index()] = mode;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModRouting {
+    pub source: ModSource,
+    pub destination: ModDestination,
+    pub amount: f32,
+    pub zone: RoutingZone,
+}
+
+impl ModRouting {
+    // 鍵盤・ベロシティの全域で掛かるルーティング
+    pub fn new(source: ModSource, destination: ModDestination, amount: f32) -> Self {
+        Self { source, destination, amount, zone: RoutingZone::ALL }
+    }
+}
+
+// ルーティングを掛けるノート番号とベロシティ（0.0〜1.0、カーブ適用後）の範囲。両端を含む
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoutingZone {
+    pub keys: (u8, u8),
+    pub velocities: (f32, f32),
+}
+
+impl Default for RoutingZone {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl RoutingZone {
+    pub const ALL: RoutingZone = RoutingZone { keys: (0, 127), velocities: (0.0, 1.0) };
+    
+    pub fn contains(&self, note: u8, velocity: f32) -> bool {
+        (self.keys.0..=self.keys.1).contains(&note) && (self.velocities.0..=self.velocities.1).contains(&velocity)
+    }
+    
+    // "keys <下>-<上>" と "vel <下>-<上>" の並び（どちらも省略可）
+    pub fn parse(parts: &[&str]) -> Result<Self, String> {
+        let mut zone = Self::ALL;
+        for pair in parts.chunks(2) {
+            let range = match pair {
+                [_, range] => range.split_once('-').ok_or_else(|| format!("invalid range: {}", range))?,
+                _ => return Err(format!("missing range after {}", pair[0])),
+            };
+            match pair[0] {
+                "keys" => {
+                    let key = |value: &str| value.parse::<u8>().ok().filter(|key| *key <= 127).ok_or_else(|| format!("invalid key: {}", value));
+                    zone.keys = (key(range.0)?, key(range.1)?);
+                }
+                "vel" => {
+                    let velocity = |value: &str| value.parse::<f32>().ok().filter(|v| (0.0..=1.0).contains(v)).ok_or_else(|| format!("invalid velocity: {}", value));
+                    zone.velocities = (velocity(range.0)?, velocity(range.1)?);
+                }
+                other => return Err(format!("unknown routing condition: {}", other)),
+            }
+        }
+        Ok(zone)
+    }
+}
+
+// 全域の条件は何も書かない（先頭に空白を付けて書く）
+impl fmt::Display for RoutingZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.keys != Self::ALL.keys {
+            write!(f, " keys {}-{}", self.keys.0, self.keys.1)?;
+        }
+        if self.velocities != Self::ALL.velocities {
+            write!(f, " vel {}-{}", self.velocities.0, self.velocities.1)?;
+        }
+        Ok(())
+    }
+}
+
+// 1ブロック分のモジュレーション値（各送り先で合わせて範囲に収めたもの）
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ModOffsets {
+    pub pitch: f32,
+    pub cutoff: f32,
+    pub blend: f32,
+    pub brightness: f32,
+    pub amplitude: f32,
+    pub pan: f32,
+    pub depth: f32,
+    pub bloom: f32,
+    pub tilt: f32,
+}
+
+impl ModOffsets {
+    pub fn get(&self, destination: ModDestination) -> f32 {
+        match destination {
+            ModDestination::Pitch => self.pitch,
+            ModDestination::Cutoff => self.cutoff,
+            ModDestination::Blend => self.blend,
+            ModDestination::Brightness => self.brightness,
+            ModDestination::Amplitude => self.amplitude,
+            ModDestination::Pan => self.pan,
+            ModDestination::Depth => self.depth,
+            ModDestination::Bloom => self.bloom,
+            ModDestination::Tilt => self.tilt,
+        }
+    }
+    
+    pub fn set(&mut self, destination: ModDestination, value: f32) {
+        match destination {
+            ModDestination::Pitch => self.pitch = value,
+            ModDestination::Cutoff => self.cutoff = value,
+            ModDestination::Blend => self.blend = value,
+            ModDestination::Brightness => self.brightness = value,
+            ModDestination::Amplitude => self.amplitude = value,
+            ModDestination::Pan => self.pan = value,
+            ModDestination::Depth => self.depth = value,
+            ModDestination::Bloom => self.bloom = value,
+            ModDestination::Tilt => self.tilt = value,
+        }
+    }
+}
+
+impl fmt::Display for ModCombine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModCombine::Sum => write!(f, "sum"),
+            ModCombine::Multiply => write!(f, "mul"),
+            ModCombine::Max => write!(f, "max"),
+        }
+    }
+}
+
+impl FromStr for ModCombine {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sum" | "add" => Ok(ModCombine::Sum),
+            "mul" | "multiply" => Ok(ModCombine::Multiply),
+            "max" => Ok(ModCombine::Max),
+            _ => Err(format!("unknown modulation combine mode: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ModSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModSource::Velocity => write!(f, "velocity"),
+            ModSource::KeyTrack => write!(f, "key"),
+            ModSource::Breakpoint(index) => write!(f, "bp{}", index + 1),
+            ModSource::PitchBend => write!(f, "bend"),
+            ModSource::ModWheel => write!(f, "mod"),
+            ModSource::Aftertouch => write!(f, "at"),
+            ModSource::Lfo => write!(f, "lfo"),
+            ModSource::Random => write!(f, "rand"),
+            ModSource::ReleaseVelocity => write!(f, "relvel"),
+            ModSource::Duck => write!(f, "duck"),
+        }
+    }
+}
+
+impl FromStr for ModSource {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "velocity" | "vel" => Ok(ModSource::Velocity),
+            "key" => Ok(ModSource::KeyTrack),
+            "bend" => Ok(ModSource::PitchBend),
+            "mod" | "modwheel" => Ok(ModSource::ModWheel),
+            "at" | "aftertouch" => Ok(ModSource::Aftertouch),
+            "lfo" => Ok(ModSource::Lfo),
+            "rand" | "random" => Ok(ModSource::Random),
+            "relvel" | "release" => Ok(ModSource::ReleaseVelocity),
+            "duck" => Ok(ModSource::Duck),
+            _ => s.strip_prefix("bp")
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|n| (1..=BREAKPOINT_ENVELOPE_COUNT).contains(n))
+                .map(|n| ModSource::Breakpoint(n - 1))
+                .ok_or_else(|| format!("unknown modulation source: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ModDestination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ModDestination::Pitch => "pitch",
+            ModDestination::Cutoff => "cutoff",
+            ModDestination::Blend => "blend",
+            ModDestination::Brightness => "brightness",
+            ModDestination::Amplitude => "amp",
+            ModDestination::Pan => "pan",
+            ModDestination::Depth => "depth",
+            ModDestination::Bloom => "bloom",
+            ModDestination::Tilt => "tilt",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ModDestination {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pitch" => Ok(ModDestination::Pitch),
+            "cutoff" => Ok(ModDestination::Cutoff),
+            "blend" => Ok(ModDestination::Blend),
+            "brightness" | "bright" => Ok(ModDestination::Brightness),
+            "amp" | "amplitude" => Ok(ModDestination::Amplitude),
+            "pan" => Ok(ModDestination::Pan),
+            "depth" => Ok(ModDestination::Depth),
+            "bloom" => Ok(ModDestination::Bloom),
+            "tilt" => Ok(ModDestination::Tilt),
+            _ => Err(format!("unknown modulation destination: {}", s)),
+        }
+    }
+}
+
+// ボイスごとのモジュレーション状態（ルーティングとエンベロープのコピーを保持する）
+pub struct VoiceModulation {
+    routings: Vec<ModRouting>,
+    combine: ModCombineModes,
+    envelopes: Vec<BreakpointEnvelope>,
+    states: Vec<BreakpointState>,
+    lfo: Lfo,
+    lfo_state: LfoState,
+    controllers: Controllers,
+    counter: usize,
+    tempo_bpm: f32,
+    random: f32, // 発音時に決めた ModSource::Random の値
+    release_velocity: f32, // ModSource::ReleaseVelocity（発音時に 0 に戻す）
+}
+
+impl Default for VoiceModulation {
+    fn default() -> Self {
+        Self {
+            routings: Vec::new(),
+            combine: ModCombineModes::default(),
+            envelopes: vec![BreakpointEnvelope::default(); BREAKPOINT_ENVELOPE_COUNT],
+            states: vec![BreakpointState::default(); BREAKPOINT_ENVELOPE_COUNT],
+            lfo: Lfo::default(),
+            lfo_state: LfoState::default(),
+            controllers: Controllers::default(),
+            counter: 0,
+            tempo_bpm: 120.0,
+            random: 0.0,
+            release_velocity: 0.0,
+        }
+    }
+}
+
+impl VoiceModulation {
+    pub fn set_routings(&mut self, routings: &[ModRouting]) {
+        self.routings.clear();
+        self.routings.extend_from_slice(routings);
+    }
+    
+    pub fn set_combine(&mut self, combine: ModCombineModes) {
+        self.combine = combine;
+    }
+    
+    // 後で set_routings・set_envelope に渡す音色の分だけ先に確保しておく（オーディオスレッドで確保しないように）
+    pub fn reserve(&mut self, routings: &[ModRouting], envelopes: &[BreakpointEnvelope]) {
+        self.routings.reserve(routings.len().saturating_sub(self.routings.len()));
+        for (target, envelope) in self.envelopes.iter_mut().zip(envelopes) {
+            target.points.reserve(envelope.points.len().saturating_sub(target.points.len()));
+        }
+    }
+    
+    pub fn set_envelope(&mut self, index: usize, envelope: &BreakpointEnvelope) {
+        if let Some(target) = self.envelopes.get_mut(index) {
+            target.clone_from(envelope);
+        }
+    }
+    
+    pub fn set_lfo(&mut self, lfo: Lfo) {
+        self.lfo = lfo;
+    }
+    
+    pub fn set_tempo(&mut self, tempo_bpm: f32) {
+        self.tempo_bpm = tempo_bpm;
+    }
+    
+    // 次のブロックの計算から反映される
+    pub fn set_controllers(&mut self, controllers: Controllers) {
+        self.controllers = controllers;
+    }
+    
+    pub fn is_active(&self) -> bool {
+        !self.routings.is_empty()
+    }
+    
+    pub fn routes_to(&self, destination: ModDestination) -> bool {
+        self.routings.iter().any(|routing| routing.destination == destination)
+    }
+    
+    // random は発音ごとのランダム値（-1.0〜1.0）。次の発音まで保持する
+    pub fn note_on(&mut self, random: f32) {
+        self.random = random;
+        self.release_velocity = 0.0;
+        for state in &mut self.states {
+            state.trigger();
+        }
+        self.lfo_state.trigger();
+        self.counter = 0;
+    }
+    
+    pub fn note_off(&mut self, release_velocity: f32) {
+        self.release_velocity = release_velocity;
+        for state in &mut self.states {
+            state.release();
+        }
+    }
+    
+    // ブロックの先頭で新しいモジュレーション値を返す
+    pub fn tick(&mut self, velocity: f32, note: u8, sample_rate: f32) -> Option<ModOffsets> {
+        if self.routings.is_empty() {
+            return None;
+        }
+        let block_start = self.counter == 0;
+        self.counter = (self.counter + 1) % MOD_BLOCK_SIZE;
+        if !block_start {
+            return None;
+        }
+        
+        let seconds = MOD_BLOCK_SIZE as f32 / sample_rate;
+        for (state, envelope) in self.states.iter_mut().zip(&self.envelopes) {
+            state.advance(envelope, seconds, self.tempo_bpm);
+        }
+        let lfo = self.lfo_state.advance(&self.lfo, seconds, self.tempo_bpm);
+        
+        // 送り先ごとに、最初のルーティングの値から順に合わせていく
+        let mut combined = [None::<f32>; ModDestination::ALL.len()];
+        for routing in self.routings.iter().filter(|routing| routing.zone.contains(note, velocity)) {
+            let value = match routing.source {
+                ModSource::Velocity => velocity,
+                ModSource::KeyTrack => ((note as f32 - 60.0) / 60.0).clamp(-1.0, 1.0),
+                ModSource::Breakpoint(index) => self.states.get(index).map_or(0.0, |state| state.level()),
+                ModSource::PitchBend => self.controllers.pitch_bend,
+                ModSource::ModWheel => self.controllers.mod_wheel,
+                ModSource::Aftertouch => self.controllers.aftertouch,
+                ModSource::Lfo => lfo,
+                ModSource::Random => self.random,
+                ModSource::ReleaseVelocity => self.release_velocity,
+                ModSource::Duck => self.controllers.duck,
+            };
+            let value = value * routing.amount;
+            let slot = &mut combined[routing.destination.index()];
+            *slot = Some(match *slot {
+                Some(current) => self.combine.get(routing.destination).combine(current, value),
+                None => value,
+            });
+        }
+        let mut offsets = ModOffsets::default();
+        for (destination, value) in ModDestination::ALL.into_iter().zip(combined) {
+            if let Some(value) = value {
+                let (min, max) = destination.range();
+                offsets.set(destination, value.clamp(min, max));
+            }
+        }
+        Some(offsets)
+    }
+}
@@ -0,0 +1,99 @@
+// LFO とモジュレーションのルーティングのテスト
+use crate::event::Event;
+use crate::synth::Synthesizer;
+use crate::voice_tests::releasing;
+
+// LFO のフェードインは深さを直線的に上げ、ワンショットは1周期で最後の値に止まる
+#[test]
+fn lfo_fades_in_and_one_shot_holds_end_value() {
+    use crate::modulation::{Lfo, LfoShape, LfoState};
+    
+    let fading = Lfo { rate: 1.0, shape: LfoShape::Square, fade: 1.0, fade_sync: false, one_shot: false, sync: false };
+    let mut state = LfoState::default();
+    state.trigger();
+    assert!((state.advance(&fading, 0.25, 120.0) - 0.25).abs() < 1e-6);
+    assert!((state.advance(&fading, 0.5, 120.0) + 0.75).abs() < 1e-6);
+    assert_eq!(state.advance(&fading, 0.5, 120.0), 1.0);
+    
+    let envelope = Lfo { rate: 2.0, shape: LfoShape::RampDown, fade: 0.0, fade_sync: false, one_shot: true, sync: false };
+    state.trigger();
+    assert!((state.advance(&envelope, 0.25, 120.0) - 0.5).abs() < 1e-6);
+    for _ in 0..10 {
+        assert!(state.advance(&envelope, 0.25, 120.0).abs() < 1e-6);
+    }
+    state.trigger();
+    assert_eq!(state.advance(&envelope, 0.0, 120.0), 1.0);
+}
+
+// 同じ送り先への複数のルーティングは合わせ方に従って合わせ、送り先の範囲に収めてからボイスの診断情報に出る
+#[test]
+fn routings_to_one_destination_combine_and_clamp() {
+    use crate::modulation::{ModCombine, ModDestination, ModRouting, ModSource};
+    use crate::patch::PatchState;
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    // キートラックは A4 で 9/60 = 0.15
+    synth.add_mod_routing(ModRouting::new(ModSource::KeyTrack, ModDestination::Pitch, 10.0));
+    synth.add_mod_routing(ModRouting::new(ModSource::KeyTrack, ModDestination::Pitch, 40.0));
+    let pitch_after = |synth: &mut Synthesizer, mode: ModCombine| {
+        synth.set_mod_combine(ModDestination::Pitch, mode);
+        synth.note_on(69, 0.8);
+        synth.next_frame();
+        let pitch = synth.voice_info()[0].modulation.pitch;
+        synth.panic();
+        pitch
+    };
+    assert!((pitch_after(&mut synth, ModCombine::Sum) - 7.5).abs() < 1e-4);
+    assert!((pitch_after(&mut synth, ModCombine::Multiply) - 9.0).abs() < 1e-4);
+    assert!((pitch_after(&mut synth, ModCombine::Max) - 6.0).abs() < 1e-4);
+    
+    synth.add_mod_routing(ModRouting::new(ModSource::KeyTrack, ModDestination::Pitch, 1000.0));
+    assert_eq!(pitch_after(&mut synth, ModCombine::Sum), ModDestination::Pitch.range().1);
+    
+    synth.set_mod_combine(ModDestination::Pitch, ModCombine::Max);
+    let (loaded, warnings) = PatchState::from_delta(&synth.patch().to_delta("init").unwrap()).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(loaded.mod_combine, synth.patch().mod_combine);
+    synth.clear_mod_routings();
+    assert_eq!(synth.mod_combine(ModDestination::Pitch), ModCombine::Sum);
+}
+
+// ノートオフのベロシティはリリース中のモジュレーションのソースになり、ペダルで保留しても離鍵時の値が残る
+#[test]
+fn release_velocity_reaches_modulation() {
+    use crate::event::DEFAULT_RELEASE_VELOCITY;
+    use crate::midi::MidiMessage;
+    use crate::modulation::{ModDestination, ModRouting, ModSource, RoutingZone, VoiceModulation};
+    use crate::synth::Voice;
+    
+    assert_eq!(
+        MidiMessage::NoteOff { channel: 0, note: 60, velocity: 127 }.to_event(),
+        Some(Event::NoteOff { note: 60, velocity: 1.0 }),
+    );
+    assert_eq!(
+        MidiMessage::NoteOn { channel: 0, note: 60, velocity: 0 }.to_event(),
+        Some(Event::NoteOff { note: 60, velocity: DEFAULT_RELEASE_VELOCITY }),
+    );
+    
+    let routing = ModRouting { source: ModSource::ReleaseVelocity, destination: ModDestination::Cutoff, amount: 1.0, zone: RoutingZone::ALL };
+    let mut modulation = VoiceModulation::default();
+    modulation.set_routings(&[routing]);
+    modulation.note_on(0.0);
+    assert_eq!(modulation.tick(0.8, 60, 48000.0).unwrap().cutoff, 0.0);
+    modulation.note_off(0.9);
+    let offsets = (0..crate::modulation::MOD_BLOCK_SIZE).find_map(|_| modulation.tick(0.8, 60, 48000.0)).unwrap();
+    assert_eq!(offsets.cutoff, 0.9);
+    
+    let mut voice = Voice::new(48000.0);
+    voice.note_on_with_random(60, 0.8, 0.0);
+    voice.note_off(0.25);
+    assert_eq!(voice.release_velocity(), 0.25);
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    synth.note_on(60, 0.8);
+    synth.handle_event(&Event::ControlChange { controller: 64, value: 127 });
+    synth.handle_event(&Event::NoteOff { note: 60, velocity: 0.1 });
+    synth.handle_event(&Event::ControlChange { controller: 64, value: 0 });
+    assert_eq!(releasing(&synth), vec![60]);
+    assert_eq!(synth.voice_info()[0].release_velocity, 0.1);
+}
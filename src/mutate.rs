@@ -0,0 +1,102 @@
+use crate::patch::PatchState;
+use crate::random::Rng;
+
+pub const DEFAULT_MUTATION_AMOUNT: f32 = 0.3;
+
+// モジュレーターとして音楽的に使いやすい周波数比（整数比なら基本周波数は変わらない）
+const FM_RATIOS: [f32; 8] = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+const MAX_MUTATED_FEEDBACK: f32 = 0.6; // これ以上はほぼノイズになる
+
+// どのパラメータを動かすか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationScope {
+    All,
+    Harmonics,
+    Fm,
+    Filter,
+    Envelope,
+}
+
+impl MutationScope {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "all" => Some(MutationScope::All),
+            "harmonics" => Some(MutationScope::Harmonics),
+            "fm" => Some(MutationScope::Fm),
+            "filter" => Some(MutationScope::Filter),
+            "env" | "envelope" => Some(MutationScope::Envelope),
+            _ => None,
+        }
+    }
+    
+    fn includes(self, scope: MutationScope) -> bool {
+        self == MutationScope::All || self == scope
+    }
+}
+
+// 現在の状態を amount（0.0-1.0）の強さで揺らした新しいパッチを返す。
+// 倍音は整数倍のまま高次ほど弱く、FM の比は整数比から選び、基音（1 番目の倍音・オペレーター）は残すので音高は変わらない。
+// 音量・定位・トリムは動かさない
+pub fn mutate(patch: &PatchState, scope: MutationScope, amount: f32, rng: &mut Rng) -> PatchState {
+    let amount = amount.clamp(0.0, 1.0);
+    let mut mutated = patch.clone();
+    // 範囲の幅に対する割合で動かす
+    let nudge = |value: f32, min: f32, max: f32, rng: &mut Rng| {
+        (value + rng.bipolar() * amount * (max - min) * 0.5).clamp(min, max)
+    };
+    
+    if scope == MutationScope::All {
+        mutated.blend = nudge(patch.blend, 0.0, 1.0, rng);
+        mutated.brightness = nudge(patch.brightness, 0.0, 1.0, rng);
+    }
+    if scope.includes(MutationScope::Harmonics) {
+        for (i, harmonic) in mutated.harmonics.iter_mut().enumerate() {
+            let rolloff = 1.0 / (i + 1) as f32;
+            if i > 0 && rng.next_f32() < amount * 0.5 {
+                harmonic.enabled = !harmonic.enabled;
+                harmonic.amplitude = rng.range(0.2, 1.0) * rolloff;
+            }
+            if harmonic.enabled {
+                harmonic.amplitude = nudge(harmonic.amplitude, 0.0, rolloff, rng);
+                harmonic.phase = nudge(harmonic.phase, 0.0, 1.0, rng);
+            }
+        }
+        if let Some(fundamental) = mutated.harmonics.first_mut() {
+            fundamental.enabled = true;
+            fundamental.amplitude = fundamental.amplitude.max(0.5);
+        }
+    }
+    if scope.includes(MutationScope::Fm) {
+        for (i, operator) in mutated.operators.iter_mut().enumerate() {
+            if i > 0 && rng.next_f32() < amount * 0.5 {
+                operator.enabled = !operator.enabled;
+                operator.frequency_ratio = FM_RATIOS[(rng.next_u64() % FM_RATIOS.len() as u64) as usize];
+            }
+            if operator.enabled {
+                operator.amplitude = nudge(operator.amplitude, 0.0, 1.0, rng);
+                operator.feedback = nudge(operator.feedback, 0.0, MAX_MUTATED_FEEDBACK, rng);
+            }
+        }
+        if let Some(carrier) = mutated.operators.first_mut() {
+            carrier.enabled = true;
+            carrier.amplitude = carrier.amplitude.max(0.5);
+        }
+    }
+    if scope.includes(MutationScope::Filter) {
+        mutated.cutoff = nudge(patch.cutoff, 0.2, 1.0, rng);
+        mutated.resonance = nudge(patch.resonance, 0.0, 0.7, rng);
+        mutated.filter_drive = nudge(patch.filter_drive, 0.0, 0.5, rng);
+    }
+    if scope.includes(MutationScope::Envelope) {
+        // 時間は比で揺らす（amount 1 で 1/8〜8 倍）
+        let scale = |seconds: f32, min: f32, max: f32, rng: &mut Rng| {
+            (seconds * 8.0_f32.powf(rng.bipolar() * amount)).clamp(min, max)
+        };
+        let envelope = &mut mutated.envelope;
+        envelope.attack = scale(envelope.attack, 0.001, 2.0, rng);
+        envelope.decay = scale(envelope.decay, 0.01, 3.0, rng);
+        envelope.sustain = nudge(envelope.sustain, 0.0, 1.0, rng);
+        envelope.release = scale(envelope.release, 0.01, 4.0, rng);
+    }
+    mutated
+}
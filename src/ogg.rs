@@ -0,0 +1,113 @@
+use std::io::{self, Write};
+
+const MAX_SEGMENTS: usize = 255;
+const PAGE_TARGET: usize = 4096; // これを超えたら次のパケットの前でページを閉じる
+const FLAG_CONTINUED: u8 = 0x01;
+const FLAG_FIRST: u8 = 0x02;
+const FLAG_LAST: u8 = 0x04;
+
+// 1本の論理ストリームのパケットを Ogg のページに詰めて書き出す
+pub struct OggWriter<W: Write> {
+    writer: W,
+    serial: u32,
+    sequence: u32,
+    lacing: Vec<u8>,
+    body: Vec<u8>,
+    granule: Option<u64>, // このページで終わった最後のパケットの位置（無ければ -1 を書く）
+    continued: bool,      // ページの先頭が前のページから続くパケットの途中
+}
+
+impl<W: Write> OggWriter<W> {
+    pub fn new(writer: W, serial: u32) -> Self {
+        Self {
+            writer,
+            serial,
+            sequence: 0,
+            lacing: Vec::with_capacity(MAX_SEGMENTS),
+            body: Vec::with_capacity(PAGE_TARGET * 2),
+            granule: None,
+            continued: false,
+        }
+    }
+    
+    // granule はこのパケットまでを復号したときの位置（Vorbis ではサンプル数）。
+    // 最後のパケットは finish で閉じるページに入るよう、ページは次のパケットを足す前に閉じる
+    pub fn write_packet(&mut self, packet: &[u8], granule: u64) -> io::Result<()> {
+        if self.body.len() >= PAGE_TARGET {
+            self.flush()?;
+        }
+        // 255 バイトずつの区切りに分け、255 未満の区切りでパケットの終わりを示す
+        let mut rest = packet;
+        loop {
+            if self.lacing.len() == MAX_SEGMENTS {
+                self.write_page(0)?;
+                self.continued = true;
+            }
+            let size = rest.len().min(255);
+            self.lacing.push(size as u8);
+            self.body.extend_from_slice(&rest[..size]);
+            rest = &rest[size..];
+            if size < 255 {
+                break;
+            }
+        }
+        self.granule = Some(granule);
+        Ok(())
+    }
+    
+    // 書きかけのページを閉じる（ヘッダーのパケットは音声と別のページに置く）
+    pub fn flush(&mut self) -> io::Result<()> {
+        if !self.lacing.is_empty() {
+            self.write_page(0)?;
+        }
+        Ok(())
+    }
+    
+    // 残りをストリームの最後のページとして書き出す
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_page(FLAG_LAST)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+    
+    fn write_page(&mut self, flags: u8) -> io::Result<()> {
+        let mut flags = flags;
+        if self.continued {
+            flags |= FLAG_CONTINUED;
+        }
+        if self.sequence == 0 {
+            flags |= FLAG_FIRST;
+        }
+        let mut page = Vec::with_capacity(27 + self.lacing.len() + self.body.len());
+        page.extend_from_slice(b"OggS");
+        page.push(0); // 版
+        page.push(flags);
+        page.extend_from_slice(&self.granule.unwrap_or(u64::MAX).to_le_bytes());
+        page.extend_from_slice(&self.serial.to_le_bytes());
+        page.extend_from_slice(&self.sequence.to_le_bytes());
+        page.extend_from_slice(&[0; 4]); // CRC は 0 として計算してから埋める
+        page.push(self.lacing.len() as u8);
+        page.extend_from_slice(&self.lacing);
+        page.extend_from_slice(&self.body);
+        let crc = crc32(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        self.writer.write_all(&page)?;
+        
+        self.sequence += 1;
+        self.lacing.clear();
+        self.body.clear();
+        self.granule = None;
+        self.continued = false;
+        Ok(())
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0u32, |mut crc, byte| {
+        crc ^= (*byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 { crc << 1 ^ 0x04C1_1DB7 } else { crc << 1 };
+        }
+        crc
+    })
+}
@@ -0,0 +1,265 @@
+use std::cell::UnsafeCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::modulation::MOD_BLOCK_SIZE;
+use crate::synth::Voice;
+
+// 1回の並列レンダリングで扱う最大フレーム数（ワーカーのバッファは固定長）
+pub const MAX_PARALLEL_FRAMES: usize = MOD_BLOCK_SIZE;
+
+// 自分の分を生成し終えてからワーカーを待つ長さ。過ぎればまだ手を付けていないボイスをオーディオスレッドで生成する
+const WORKER_TIMEOUT: Duration = Duration::from_millis(1);
+
+const IDLE: u8 = 0;
+const START: u8 = 1;
+const RUNNING: u8 = 2;
+const DONE: u8 = 3;
+const FAILED: u8 = 4; // ワーカーがパニックした（以後は使わない）
+const EXIT: u8 = 5;
+
+// テストでワーカーの不調を起こす（次のジョブだけ）
+#[cfg(test)]
+pub(crate) const FAULT_STALL: u8 = 1; // ジョブを受け取る前に止まる
+#[cfg(test)]
+pub(crate) const FAULT_PANIC: u8 = 2; // ジョブの途中でパニックする
+#[cfg(test)]
+pub(crate) const STALL: Duration = Duration::from_millis(200);
+
+// ワーカーに渡すボイスへのポインタ（各ボイスは1つのワーカーだけが触る）
+#[derive(Clone, Copy)]
+struct VoicePtr(*mut Voice);
+
+// SAFETY: ポインタは render の間だけ使い、その間 &mut self.voices の借用でボイスは他から触られない。
+// 各ボイスは1つのスレッドだけが扱うので、Voice を別のスレッドへ送るのと同じ
+unsafe impl Send for VoicePtr {}
+
+struct Job {
+    voices: *const VoicePtr,
+    count: usize,
+    frames: usize,
+}
+
+// state が RUNNING の間はワーカーだけが buffer に書き、job はどちらも読むだけ。
+// それ以外はオーディオスレッドだけがアクセスする。ボイスは next で1つずつ取り合い、取った側だけが触る
+struct WorkerShared {
+    state: AtomicU8,
+    job: UnsafeCell<Job>,
+    next: AtomicUsize, // 次に取る job.voices の番号
+    buffer: UnsafeCell<[[f32; 2]; MAX_PARALLEL_FRAMES]>,
+    #[cfg(test)]
+    fault: AtomicU8,
+}
+
+// SAFETY: UnsafeCell の中身は state の受け渡し（上のとおり）でどちらか一方のスレッドだけが触る。
+// job の voices は VoicePtr と同じ理由で別のスレッドから使ってよい
+unsafe impl Send for WorkerShared {}
+// SAFETY: Send と同じく、job と buffer の書き込みは state の START / DONE の Release・Acquire で交互になる
+unsafe impl Sync for WorkerShared {}
+
+struct Worker {
+    shared: Arc<WorkerShared>,
+    handle: Option<JoinHandle<()>>,
+    started: bool, // 今回の render でジョブを渡した
+    failed: bool,  // パニックしたワーカーにはジョブを渡さない
+}
+
+// ボイスのレンダリングを分担する常駐ワーカースレッド。
+// レンダリング中はメモリ確保もロックも行わない（ワーカーの起床のみ）。
+pub struct VoiceWorkers {
+    workers: Vec<Worker>,
+    voices: Vec<VoicePtr>, // 確保済み
+}
+
+impl VoiceWorkers {
+    // threads はオーディオスレッド自身を含むスレッド数
+    pub fn new(threads: usize, max_voices: usize) -> Self {
+        let workers = (1..threads.max(1))
+            .map(|_| {
+                let shared = Arc::new(WorkerShared {
+                    state: AtomicU8::new(IDLE),
+                    job: UnsafeCell::new(Job { voices: std::ptr::null(), count: 0, frames: 0 }),
+                    next: AtomicUsize::new(0),
+                    buffer: UnsafeCell::new([[0.0; 2]; MAX_PARALLEL_FRAMES]),
+                    #[cfg(test)]
+                    fault: AtomicU8::new(0),
+                });
+                let worker_shared = Arc::clone(&shared);
+                let handle = thread::Builder::new()
+                    .name("voice-worker".to_string())
+                    .spawn(move || worker_loop(&worker_shared))
+                    .expect("failed to spawn voice worker");
+                Worker { shared, handle: Some(handle), started: false, failed: false }
+            })
+            .collect();
+        Self {
+            workers,
+            voices: Vec::with_capacity(max_voices),
+        }
+    }
+    
+    // 同時発音数を増やしたときに、レンダリング中に確保しないよう先に広げておく
+    pub fn reserve(&mut self, max_voices: usize) {
+        self.voices.reserve(max_voices.saturating_sub(self.voices.len()));
+    }
+    
+    pub fn threads(&self) -> usize {
+        self.workers.len() + 1
+    }
+    
+    // voices を分担してレンダリングし、全ボイスの合計を out に加算する。
+    // 待ち時間は WORKER_TIMEOUT までで、過ぎればワーカーがまだ取っていないボイスをこのスレッドで生成する
+    // （ワーカーが生成中の1つのボイスだけは終わるまで待つ）
+    pub fn render<'a>(&mut self, voices: impl Iterator<Item = &'a mut Voice>, out: &mut [[f32; 2]]) {
+        let frames = out.len().min(MAX_PARALLEL_FRAMES);
+        let out = &mut out[..frames];
+        self.voices.clear();
+        self.voices.extend(voices.map(|voice| VoicePtr(voice as *mut Voice)));
+        
+        let chunk = self.voices.len().div_ceil(self.threads());
+        let mut chunks = self.voices.chunks(chunk.max(1));
+        let own = chunks.next().unwrap_or(&[]);
+        
+        for worker in &mut self.workers {
+            worker.started = false;
+            if worker.failed {
+                continue;
+            }
+            let Some(voices) = chunks.next() else { break };
+            // SAFETY: ワーカーは IDLE（前回の DONE を読んだ後に戻した）なので job には触らない。
+            // voices は self.voices の中を指し、ワーカーが DONE にするまで self.voices は変えない
+            unsafe {
+                *worker.shared.job.get() = Job { voices: voices.as_ptr(), count: voices.len(), frames };
+            }
+            worker.shared.next.store(0, Ordering::Relaxed);
+            worker.shared.state.store(START, Ordering::Release);
+            if let Some(handle) = &worker.handle {
+                handle.thread().unpark();
+            }
+            worker.started = true;
+        }
+        
+        // 自分の分と、パニックしたワーカーの代わりの分
+        for voice in own.iter().chain(chunks.flatten()) {
+            // SAFETY: voice は呼び出し元から借りた &mut Voice で、ワーカーには渡していない
+            unsafe { render_voice(&mut *voice.0, out) };
+        }
+        
+        let deadline = Instant::now() + WORKER_TIMEOUT;
+        for worker in self.workers.iter_mut().filter(|worker| worker.started) {
+            let shared = &*worker.shared;
+            let mut state = shared.state.load(Ordering::Acquire);
+            while state != DONE && state != FAILED && Instant::now() < deadline {
+                std::hint::spin_loop();
+                state = shared.state.load(Ordering::Acquire);
+            }
+            if state != DONE && state != FAILED {
+                // まだ受け取っていなければジョブごと取り戻し、受け取っていれば残りのボイスを取る
+                if shared.state.compare_exchange(START, IDLE, Ordering::Acquire, Ordering::Acquire).is_ok() {
+                    state = IDLE;
+                }
+                // SAFETY: job はワーカーも読むだけで、書くのは次の START の前（このスレッド）だけ
+                let job = unsafe { &*shared.job.get() };
+                take_voices(shared, job, out);
+                while state == START || state == RUNNING {
+                    std::hint::spin_loop();
+                    state = shared.state.load(Ordering::Acquire);
+                }
+            }
+            match state {
+                DONE => {
+                    // SAFETY: DONE を Acquire で読んだので、ワーカーの書き込みは済んでいて、次の START までは触られない
+                    let buffer = unsafe { &*shared.buffer.get() };
+                    for (sum, frame) in out.iter_mut().zip(buffer) {
+                        sum[0] += frame[0];
+                        sum[1] += frame[1];
+                    }
+                    shared.state.store(IDLE, Ordering::Relaxed);
+                }
+                FAILED => {
+                    // 途中までの出力は捨て、まだ誰も取っていないボイスを生成する
+                    // SAFETY: FAILED を Acquire で読んだので、ワーカーはもうボイスに触らない
+                    let job = unsafe { &*shared.job.get() };
+                    take_voices(shared, job, out);
+                    worker.failed = true;
+                }
+                _ => {}
+            }
+        }
+    }
+    
+    // テスト用: 次のジョブでワーカーを止めるかパニックさせる
+    #[cfg(test)]
+    pub(crate) fn inject_fault(&self, worker: usize, fault: u8) {
+        self.workers[worker].shared.fault.store(fault, Ordering::Relaxed);
+    }
+}
+
+impl Drop for VoiceWorkers {
+    fn drop(&mut self) {
+        for worker in &mut self.workers {
+            worker.shared.state.store(EXIT, Ordering::Release);
+            if let Some(handle) = worker.handle.take() {
+                handle.thread().unpark();
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+fn worker_loop(shared: &WorkerShared) {
+    loop {
+        match shared.state.load(Ordering::Acquire) {
+            START => {
+                #[cfg(test)]
+                if shared.fault.compare_exchange(FAULT_STALL, 0, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+                    thread::sleep(STALL);
+                }
+                // 待ちきれなかったオーディオスレッドがジョブを取り戻していれば何もしない
+                if shared.state.compare_exchange(START, RUNNING, Ordering::Acquire, Ordering::Acquire).is_err() {
+                    continue;
+                }
+                // パニックしても FAILED にして、オーディオスレッドが待ち続けないようにする
+                let rendered = panic::catch_unwind(AssertUnwindSafe(|| {
+                    #[cfg(test)]
+                    if shared.fault.compare_exchange(FAULT_PANIC, 0, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+                        panic!("injected voice worker panic");
+                    }
+                    // SAFETY: START を Acquire で読んだので job は書き込み済みで、DONE にするまでオーディオスレッドは
+                    // buffer に触らない。job.voices の各ボイスは render の呼び出し中も有効で、next で取ったものだけを触る
+                    unsafe {
+                        let job = &*shared.job.get();
+                        let buffer = &mut *shared.buffer.get();
+                        let out = &mut buffer[..job.frames];
+                        out.fill([0.0; 2]);
+                        take_voices(shared, job, out);
+                    }
+                }));
+                shared.state.store(if rendered.is_ok() { DONE } else { FAILED }, Ordering::Release);
+            }
+            EXIT => return,
+            _ => thread::park(),
+        }
+    }
+}
+
+// job のボイスを next で1つずつ取り、out に加算する（ワーカーとオーディオスレッドが取り合う）
+fn take_voices(shared: &WorkerShared, job: &Job, out: &mut [[f32; 2]]) {
+    // SAFETY: job.voices は render の間は有効な self.voices の一部を指す
+    let voices = unsafe { std::slice::from_raw_parts(job.voices, job.count) };
+    while let Some(voice) = voices.get(shared.next.fetch_add(1, Ordering::Relaxed)) {
+        // SAFETY: fetch_add で取った番号のボイスはほかのスレッドは触らない
+        unsafe { render_voice(&mut *voice.0, out) };
+    }
+}
+
+fn render_voice(voice: &mut Voice, out: &mut [[f32; 2]]) {
+    for frame in out {
+        let [left, right] = voice.next_frame();
+        frame[0] += left;
+        frame[1] += right;
+    }
+}
@@ -0,0 +1,113 @@
+// ワーカースレッドでのボイスの並列レンダリングのテスト
+use crate::builder::VoiceMode;
+use crate::diagnostics::TestSignal;
+use crate::automation::RampCurve;
+use crate::parallel::{VoiceWorkers, FAULT_PANIC, FAULT_STALL, MAX_PARALLEL_FRAMES, STALL};
+use crate::patch::PatchState;
+use crate::surround::ChannelLayout;
+use crate::synth::{Synthesizer, Voice};
+use std::thread;
+use std::time::Instant;
+
+const CHORD: [u8; 7] = [36, 48, 55, 60, 64, 67, 71];
+
+type Setup<'a> = &'a dyn Fn(&mut Synthesizer);
+
+// 和音を鳴らし、256 フレームずつ render_block で生成する（スタックは使わないので発音ごとの乱数に依存しない）
+fn render(threads: usize, mode: VoiceMode, setup: Setup) -> Vec<[f32; 2]> {
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).render_threads(threads).voice_mode(mode).build();
+    synth.set_note_random_seed(7);
+    synth.set_harmonic_amplitude(1, 0.5);
+    synth.set_harmonic_amplitude(4, 0.3);
+    for note in CHORD {
+        synth.note_on(note, 0.7);
+    }
+    setup(&mut synth);
+    let mut out = vec![[0.0; 2]; 4096];
+    for (index, block) in out.chunks_mut(256).enumerate() {
+        if index == 8 {
+            synth.note_off(60);
+        }
+        synth.render_block(block);
+    }
+    out
+}
+
+// ボイスを足す順序だけが違うので、丸め誤差の範囲で 1 サンプルずつ一致する
+fn assert_same(parallel: &[[f32; 2]], serial: &[[f32; 2]], case: &str) {
+    let peak = serial.iter().fold(0.0_f32, |peak, frame| peak.max(frame[0].abs()).max(frame[1].abs()));
+    assert!(peak > 0.01, "{}: silent", case);
+    for (index, (a, b)) in parallel.iter().zip(serial).enumerate() {
+        for channel in 0..2 {
+            assert!((a[channel] - b[channel]).abs() <= 1e-5 * peak, "{}: sample {} differs ({} vs {})", case, index, a[channel], b[channel]);
+        }
+    }
+}
+
+#[test]
+fn parallel_rendering_matches_serial_output() {
+    let serial = render(1, VoiceMode::Poly, &|_| {});
+    for threads in [2, 3, 8] {
+        assert_same(&render(threads, VoiceMode::Poly, &|_| {}), &serial, &format!("{} threads", threads));
+    }
+}
+
+// render_block が1サンプルずつの生成に戻る条件ごとに、ワーカーを使う設定でも同じ出力になる
+#[test]
+fn serial_fallbacks_match_serial_output() {
+    let cases: [(&str, VoiceMode, Setup); 6] = [
+        ("analysis taps", VoiceMode::Poly, &|synth| synth.set_analysis_taps(true)),
+        ("test signal", VoiceMode::Poly, &|synth| synth.start_test_signal(TestSignal::Impulse { interval: 0.01 }, false)),
+        ("surround", VoiceMode::Poly, &|synth| {
+            synth.set_output_layout(ChannelLayout::Quad);
+            synth.set_surround(true);
+        }),
+        ("parameter ramp", VoiceMode::Poly, &|synth| {
+            synth.automate("cutoff", Some(0.9), 0.2, 0.01, 0.05, RampCurve::Linear).unwrap();
+        }),
+        ("shared parameter smoothing", VoiceMode::Poly, &|synth| {
+            synth.params().cutoff.set(0.3);
+        }),
+        // 和音が同時発音数に収まるようにして、スチールするボイスの選び方に依存しないようにする
+        ("paraphony", VoiceMode::Paraphonic(8), &|_| {}),
+    ];
+    for (case, mode, setup) in cases {
+        assert_same(&render(3, mode, setup), &render(1, mode, setup), case);
+    }
+}
+
+
+// ワーカーが止まっても、パニックしても、オーディオスレッドは待ち続けずに残りのボイスを自分で生成する
+#[test]
+fn stalled_or_panicking_worker_falls_back_to_the_audio_thread() {
+    let chord = || -> Vec<Voice> {
+        CHORD.iter().map(|&note| {
+            let mut voice = Voice::with_patch(48000.0, &PatchState::default());
+            voice.note_on(note, 0.7);
+            voice
+        }).collect()
+    };
+    let (mut parallel, mut serial) = (chord(), chord());
+    let mut workers = VoiceWorkers::new(3, CHORD.len());
+    for fault in [None, Some(FAULT_STALL), None, Some(FAULT_PANIC), None, None] {
+        if let Some(fault) = fault {
+            // 前のジョブで止めたワーカーが起きてから仕掛ける
+            thread::sleep(STALL);
+            workers.inject_fault(0, fault);
+        }
+        let mut out = [[0.0; 2]; MAX_PARALLEL_FRAMES];
+        let started = Instant::now();
+        workers.render(parallel.iter_mut(), &mut out);
+        assert!(started.elapsed() < STALL / 2, "waited {:?}", started.elapsed());
+        
+        let mut expected = [[0.0; 2]; MAX_PARALLEL_FRAMES];
+        for voice in &mut serial {
+            for frame in &mut expected {
+                let [left, right] = voice.next_frame();
+                frame[0] += left;
+                frame[1] += right;
+            }
+        }
+        assert_same(&out, &expected, &format!("{:?}", fault));
+    }
+}
@@ -0,0 +1,286 @@
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::engine::{FM_ALGORITHMS, MAX_STACK_DENSITY};
+use crate::patch::PatchState;
+
+// パッチ内の数値パラメータへの参照
+pub enum ParamRef<'a> {
+    Float(&'a mut f32),
+    Toggle(&'a mut bool),
+    Count(&'a mut usize, usize, usize), // 値, 最小, 最大
+}
+
+impl ParamRef<'_> {
+    pub fn get(&self) -> f32 {
+        match self {
+            ParamRef::Float(value) => **value,
+            ParamRef::Toggle(value) => if **value { 1.0 } else { 0.0 },
+            ParamRef::Count(value, _, _) => **value as f32,
+        }
+    }
+    
+    pub fn set(&mut self, value: f32) {
+        match self {
+            ParamRef::Float(target) => **target = value,
+            ParamRef::Toggle(target) => **target = value >= 0.5,
+            ParamRef::Count(target, min, max) => {
+                **target = (value.round().max(0.0) as usize).clamp(*min, *max);
+            }
+        }
+    }
+}
+
+// パラメータレジストリ: パッチの全数値パラメータを安定した名前で巡回する。
+// 名前は保存形式などで使われるため、既存の名前は変更しないこと。
+pub fn visit(patch: &mut PatchState, mut f: impl FnMut(&str, ParamRef)) {
+    f("blend", ParamRef::Float(&mut patch.blend));
+    f("brightness", ParamRef::Float(&mut patch.brightness));
+    f("cutoff", ParamRef::Float(&mut patch.cutoff));
+    f("resonance", ParamRef::Float(&mut patch.resonance));
+    f("filter.drive", ParamRef::Float(&mut patch.filter_drive));
+    f("filter.compensation", ParamRef::Toggle(&mut patch.filter_compensation));
+    f("volume", ParamRef::Float(&mut patch.volume));
+    f("pan", ParamRef::Float(&mut patch.pan));
+    f("depth", ParamRef::Float(&mut patch.depth));
+    f("voice.saturation", ParamRef::Float(&mut patch.voice_saturation));
+    f("output.trim", ParamRef::Float(&mut patch.output_trim));
+    f("output.invert", ParamRef::Toggle(&mut patch.output_invert));
+    f("env.attack", ParamRef::Float(&mut patch.envelope.attack));
+    f("env.decay", ParamRef::Float(&mut patch.envelope.decay));
+    f("env.sustain", ParamRef::Float(&mut patch.envelope.sustain));
+    f("env.release", ParamRef::Float(&mut patch.envelope.release));
+    f("env.sync", ParamRef::Toggle(&mut patch.envelope.tempo_sync));
+    f("glide.time", ParamRef::Float(&mut patch.glide_time));
+    f("glide.nearest", ParamRef::Toggle(&mut patch.glide_nearest));
+    f("drone.on", ParamRef::Toggle(&mut patch.drone));
+    f("drone.rate", ParamRef::Float(&mut patch.drone_rate));
+    f("drone.depth", ParamRef::Float(&mut patch.drone_depth));
+    f("stack.density", ParamRef::Count(&mut patch.stack_density, 1, MAX_STACK_DENSITY));
+    f("stack.detune", ParamRef::Float(&mut patch.stack_detune));
+    f("stack.spread", ParamRef::Float(&mut patch.stack_spread));
+    f("width", ParamRef::Float(&mut patch.width));
+    f("spectral.tilt", ParamRef::Float(&mut patch.spectral_filter.tilt));
+    f("spectral.low.harmonic", ParamRef::Float(&mut patch.spectral_filter.low_shelf_harmonic));
+    f("spectral.low.gain", ParamRef::Float(&mut patch.spectral_filter.low_shelf_gain));
+    f("spectral.high.harmonic", ParamRef::Float(&mut patch.spectral_filter.high_shelf_harmonic));
+    f("spectral.high.gain", ParamRef::Float(&mut patch.spectral_filter.high_shelf_gain));
+    f("spectral.formant", ParamRef::Float(&mut patch.spectral_filter.formant_shift));
+    f("antialias", ParamRef::Toggle(&mut patch.anti_alias));
+    f("lfo.rate", ParamRef::Float(&mut patch.lfo.rate));
+    f("lfo.fade", ParamRef::Float(&mut patch.lfo.fade));
+    f("lfo.oneshot", ParamRef::Toggle(&mut patch.lfo.one_shot));
+    f("lfo.sync", ParamRef::Toggle(&mut patch.lfo.sync));
+    f("lfo.fade.sync", ParamRef::Toggle(&mut patch.lfo.fade_sync));
+    
+    // 番号付きの名前はスタック上で組み立てる（set はオーディオスレッドからも呼ばれるので、メモリを確保しない）
+    let mut name = ParamName::default();
+    for (i, harmonic) in patch.harmonics.iter_mut().enumerate() {
+        let n = i + 1;
+        f(name.format(format_args!("harmonic.{}.ratio", n)), ParamRef::Float(&mut harmonic.frequency_multiplier));
+        f(name.format(format_args!("harmonic.{}.amp", n)), ParamRef::Float(&mut harmonic.amplitude));
+        f(name.format(format_args!("harmonic.{}.phase", n)), ParamRef::Float(&mut harmonic.phase));
+        f(name.format(format_args!("harmonic.{}.on", n)), ParamRef::Toggle(&mut harmonic.enabled));
+    }
+    f("fm.algorithm", ParamRef::Count(&mut patch.fm_algorithm, 0, FM_ALGORITHMS.len()));
+    for (i, operator) in patch.operators.iter_mut().enumerate() {
+        let n = i + 1;
+        f(name.format(format_args!("op.{}.ratio", n)), ParamRef::Float(&mut operator.frequency_ratio));
+        f(name.format(format_args!("op.{}.amp", n)), ParamRef::Float(&mut operator.amplitude));
+        f(name.format(format_args!("op.{}.feedback", n)), ParamRef::Float(&mut operator.feedback));
+        f(name.format(format_args!("op.{}.on", n)), ParamRef::Toggle(&mut operator.enabled));
+    }
+}
+
+// 固定長に収めたパラメータの名前（"harmonic.<番号>.phase" が収まる長さ）。
+// メモリを確保せずに組み立てたり、command::Command でオーディオスレッドへ運んだりできる
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParamName {
+    bytes: [u8; 32],
+    len: usize,
+}
+
+impl ParamName {
+    // 収まらない長さなら None
+    pub fn new(name: &str) -> Option<Self> {
+        let mut buffer = Self::default();
+        buffer.write_str(name).ok()?;
+        Some(buffer)
+    }
+    
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+    
+    fn format(&mut self, args: fmt::Arguments<'_>) -> &str {
+        self.len = 0;
+        let _ = self.write_fmt(args);
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for ParamName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ParamName").field(&self.as_str()).finish()
+    }
+}
+
+impl Write for ParamName {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        let target = self.bytes.get_mut(self.len..end).ok_or(fmt::Error)?;
+        target.copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+// 全パラメータの (名前, 値) の一覧
+pub fn values(patch: &PatchState) -> Vec<(String, f32)> {
+    let mut copy = patch.clone();
+    let mut values = Vec::new();
+    visit(&mut copy, |name, param| values.push((name.to_string(), param.get())));
+    values
+}
+
+pub fn get(patch: &PatchState, name: &str) -> Option<f32> {
+    values(patch).into_iter().find(|(n, _)| n == name).map(|(_, value)| value)
+}
+
+pub fn set(patch: &mut PatchState, name: &str, value: f32) -> Result<(), String> {
+    let mut found = false;
+    visit(patch, |n, mut param| {
+        if !found && n == name {
+            param.set(value);
+            found = true;
+        }
+    });
+    if found {
+        Ok(())
+    } else {
+        Err(format!("unknown parameter: {}", name))
+    }
+}
+
+// 共有パラメータの値を変えたとき、この長さをかけて直線で動かす（ジッパーノイズを防ぐ）
+pub const PARAM_SMOOTHING_SECONDS: f32 = 0.02;
+
+// 制御スレッドとオーディオスレッドで共有するパラメータ（名前, 最小, 最大, 動かす秒数）。
+// エンベロープの時間は途中で変えても段差にならないので、動かさずにそのまま設定する
+pub const SHARED_PARAMETERS: [(&str, f32, f32, f32); 8] = [
+    ("cutoff", 0.0, 1.0, PARAM_SMOOTHING_SECONDS),
+    ("resonance", 0.0, 1.0, PARAM_SMOOTHING_SECONDS),
+    ("blend", 0.0, 1.0, PARAM_SMOOTHING_SECONDS),
+    ("volume", 0.0, 1.0, PARAM_SMOOTHING_SECONDS),
+    ("env.attack", 0.001, f32::MAX, 0.0),
+    ("env.decay", 0.001, f32::MAX, 0.0),
+    ("env.sustain", 0.0, 1.0, PARAM_SMOOTHING_SECONDS),
+    ("env.release", 0.001, f32::MAX, 0.0),
+];
+
+// ロックを取らずに読み書きできる f32。上位32ビットは書き込みの回数で、
+// 同じ値を書き直しても（その間にほかの経路で値が変わっていれば）変更として届く
+pub struct AtomicParam(AtomicU64);
+
+impl AtomicParam {
+    pub fn new(value: f32) -> Self {
+        Self(AtomicU64::new(value.to_bits() as u64))
+    }
+    
+    pub fn get(&self) -> f32 {
+        self.load().1
+    }
+    
+    pub fn set(&self, value: f32) {
+        let _ = self.0.fetch_update(Ordering::AcqRel, Ordering::Acquire, |old| {
+            let writes = ((old >> 32) as u32).wrapping_add(1);
+            Some(((writes as u64) << 32) | value.to_bits() as u64)
+        });
+    }
+    
+    // (書き込みの回数, 値)
+    pub fn load(&self) -> (u32, f32) {
+        let bits = self.0.load(Ordering::Acquire);
+        ((bits >> 32) as u32, f32::from_bits(bits as u32))
+    }
+}
+
+// よく動かすパラメータの共有の置き場所（Synthesizer::params で受け取り、どのスレッドからでも書ける）。
+// オーディオスレッドは次のコントロールレートの境界で書き込みを読み、SHARED_PARAMETERS の秒数かけて動かす。
+// 値はここへ書いた最後の値で、CLI などほかの経路で変えた値は反映しない（今の値は Synthesizer::patch で読む）
+pub struct Params {
+    pub cutoff: AtomicParam,
+    pub resonance: AtomicParam,
+    pub blend: AtomicParam,
+    pub volume: AtomicParam,
+    pub attack: AtomicParam,
+    pub decay: AtomicParam,
+    pub sustain: AtomicParam,
+    pub release: AtomicParam,
+}
+
+impl Params {
+    pub fn new(patch: &PatchState) -> Self {
+        let [cutoff, resonance, blend, volume, attack, decay, sustain, release] = shared_values(patch).map(AtomicParam::new);
+        Self { cutoff, resonance, blend, volume, attack, decay, sustain, release }
+    }
+    
+    // SHARED_PARAMETERS と同じ順
+    pub fn fields(&self) -> [&AtomicParam; 8] {
+        [&self.cutoff, &self.resonance, &self.blend, &self.volume, &self.attack, &self.decay, &self.sustain, &self.release]
+    }
+    
+    // params の名前で設定する（共有していない名前なら false）
+    pub fn set(&self, name: &str, value: f32) -> bool {
+        match SHARED_PARAMETERS.iter().position(|(shared, ..)| *shared == name) {
+            Some(index) => {
+                self.fields()[index].set(value);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    pub fn get(&self, name: &str) -> Option<f32> {
+        let index = SHARED_PARAMETERS.iter().position(|(shared, ..)| *shared == name)?;
+        Some(self.fields()[index].get())
+    }
+}
+
+// パッチでの共有パラメータの値（SHARED_PARAMETERS と同じ順）
+pub fn shared_values(patch: &PatchState) -> [f32; 8] {
+    let envelope = &patch.envelope;
+    [patch.cutoff, patch.resonance, patch.blend, patch.volume, envelope.attack, envelope.decay, envelope.sustain, envelope.release]
+}
+
+// 1つのパラメータを目標の値まで1サンプルずつ直線で動かす
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Smoother {
+    value: f32,
+    target: f32,
+    step: f32,
+    remaining: u32,
+}
+
+impl Smoother {
+    pub fn start(&mut self, from: f32, to: f32, samples: u32) {
+        self.value = from;
+        self.target = to;
+        self.remaining = samples;
+        self.step = if samples > 0 { (to - from) / samples as f32 } else { 0.0 };
+    }
+    
+    // 次のサンプルの値（動いていなければ None）。最後のサンプルで目標の値ちょうどになる
+    pub fn next_value(&mut self) -> Option<f32> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        self.value = if self.remaining == 0 { self.target } else { self.value + self.step };
+        Some(self.value)
+    }
+    
+    pub fn is_active(&self) -> bool {
+        self.remaining > 0
+    }
+}
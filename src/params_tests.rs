@@ -0,0 +1,50 @@
+// スレッド間で共有するパラメータのテスト
+use crate::synth::Synthesizer;
+
+// 共有パラメータは次の境界で読まれ、カットオフなどは直線でなめらかに、エンベロープの時間はそのまま設定される
+#[test]
+fn shared_params_are_smoothed_on_the_audio_thread() {
+    use crate::modulation::MOD_BLOCK_SIZE;
+    use crate::params::PARAM_SMOOTHING_SECONDS;
+    use std::sync::Arc;
+    
+    let sample_rate = 22050.0;
+    let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+    synth.set_cutoff(0.8);
+    synth.note_on(60, 0.8);
+    let params = synth.params();
+    
+    let writer = {
+        let params = Arc::clone(&params);
+        std::thread::spawn(move || {
+            params.cutoff.set(0.2);
+            params.attack.set(0.5);
+        })
+    };
+    writer.join().unwrap();
+    assert_eq!(synth.patch().cutoff, 0.8);
+    
+    let smoothing = (PARAM_SMOOTHING_SECONDS * sample_rate) as usize;
+    synth.next_frame();
+    assert_eq!(synth.patch().envelope.attack, 0.5);
+    let first = synth.patch().cutoff;
+    assert!(first < 0.8 && first > 0.79, "first step {}", first);
+    for _ in 1..smoothing / 2 {
+        synth.next_frame();
+    }
+    assert!((synth.patch().cutoff - 0.5).abs() < 0.01, "halfway {}", synth.patch().cutoff);
+    for _ in 0..smoothing {
+        synth.next_frame();
+    }
+    assert_eq!(synth.patch().cutoff, 0.2);
+    
+    // ほかの経路で変えた後に同じ値を書き直しても届く
+    synth.set_cutoff(0.6);
+    params.cutoff.set(0.2);
+    for _ in 0..smoothing + 2 * MOD_BLOCK_SIZE {
+        synth.next_frame();
+    }
+    assert_eq!(synth.patch().cutoff, 0.2);
+    assert!(params.set("volume", 0.4));
+    assert!(!params.set("pan", 0.4));
+}
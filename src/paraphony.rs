@@ -0,0 +1,91 @@
+use crate::patch::PatchState;
+use crate::synth::{cutoff_to_hz, EnvelopeGenerator, EnvelopeStage, LowPassFilter};
+
+// パラフォニックの共有部分: 鳴っているボイスの合計に1つのフィルターとアンプのエンベロープを掛ける
+// （ストリングマシンのように、ボイスごとのフィルターとエンベロープを持たない分だけ軽い）。
+// エンベロープは押さえているノートが無い状態からの最初のノートでだけ掛け直す
+pub struct Paraphony {
+    envelope: EnvelopeGenerator,
+    filters: [LowPassFilter; 4], // 前方の左右、後方の左右
+    cutoff: f32,
+    resonance: f32,
+    drive: f32,
+    compensation: bool,
+}
+
+impl Paraphony {
+    pub fn new(sample_rate: f32, patch: &PatchState, tempo_bpm: f32) -> Self {
+        let mut paraphony = Self {
+            envelope: EnvelopeGenerator::new(sample_rate),
+            filters: std::array::from_fn(|_| LowPassFilter::new(sample_rate)),
+            cutoff: f32::NAN,
+            resonance: f32::NAN,
+            drive: f32::NAN,
+            compensation: false,
+        };
+        paraphony.envelope.set_tempo(tempo_bpm);
+        paraphony.sync(patch);
+        paraphony
+    }
+    
+    pub fn trigger(&mut self) {
+        self.envelope.note_on();
+    }
+    
+    pub fn release(&mut self) {
+        self.envelope.note_off();
+    }
+    
+    // 押さえているノートがある（リリースしていない）か
+    pub fn is_gated(&self) -> bool {
+        self.envelope.is_gated()
+    }
+    
+    // リリースが終わったか
+    pub fn is_idle(&self) -> bool {
+        self.envelope.stage() == EnvelopeStage::Idle
+    }
+    
+    pub fn stage(&self) -> EnvelopeStage {
+        self.envelope.stage()
+    }
+    
+    pub fn level(&self) -> f32 {
+        self.envelope.level()
+    }
+    
+    pub fn set_tempo(&mut self, tempo_bpm: f32) {
+        self.envelope.set_tempo(tempo_bpm);
+    }
+    
+    // パッチのフィルターとエンベロープに合わせる（変わった値だけ係数を計算し直す）
+    pub fn sync(&mut self, patch: &PatchState) {
+        self.envelope.set_envelope(patch.envelope);
+        if patch.cutoff != self.cutoff {
+            self.cutoff = patch.cutoff;
+            let hz = cutoff_to_hz(patch.cutoff);
+            self.filters.iter_mut().for_each(|filter| filter.set_cutoff(hz));
+        }
+        if patch.resonance != self.resonance {
+            self.resonance = patch.resonance;
+            self.filters.iter_mut().for_each(|filter| filter.set_resonance(patch.resonance));
+        }
+        if patch.filter_drive != self.drive {
+            self.drive = patch.filter_drive;
+            self.filters.iter_mut().for_each(|filter| filter.set_drive(patch.filter_drive));
+        }
+        if patch.filter_compensation != self.compensation {
+            self.compensation = patch.filter_compensation;
+            self.filters.iter_mut().for_each(|filter| filter.set_gain_compensation(patch.filter_compensation));
+        }
+    }
+    
+    pub fn process(&mut self, front: [f32; 2], rear: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        let gain = self.envelope.next_sample();
+        let [front_left, front_right, rear_left, rear_right] = &mut self.filters;
+        (
+            [front_left.process(front[0]) * gain, front_right.process(front[1]) * gain],
+            [rear_left.process(rear[0]) * gain, rear_right.process(rear[1]) * gain],
+        )
+    }
+}
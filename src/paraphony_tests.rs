@@ -0,0 +1,52 @@
+// パラフォニックの発音のテスト
+use crate::synth::{EnvelopeStage, Synthesizer};
+
+// パラフォニックモードでは最初のノートでだけ共有のエンベロープを掛け直し、すべて離すと最後のノートを共有のリリースで鳴らす
+#[test]
+fn paraphonic_notes_share_one_envelope() {
+    use crate::builder::VoiceMode;
+    use crate::synth::Envelope;
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).voice_mode(VoiceMode::Paraphonic(2)).build();
+    synth.set_envelope(Envelope { attack: 0.01, decay: 0.01, sustain: 0.5, release: 0.05, tempo_sync: false });
+    synth.note_on(60, 0.8);
+    for _ in 0..2205 {
+        synth.next_frame();
+    }
+    let shared = |synth: &Synthesizer| synth.paraphony().map(|paraphony| (paraphony.stage(), paraphony.level())).unwrap();
+    assert_eq!(shared(&synth), (EnvelopeStage::Sustain, 0.5));
+    
+    // 2つ目のノートでは掛け直さず、上限を超えると古いノートを奪う
+    synth.note_on(64, 0.8);
+    synth.next_frame();
+    assert_eq!(shared(&synth), (EnvelopeStage::Sustain, 0.5));
+    synth.note_on(67, 0.8);
+    synth.next_frame();
+    assert_eq!(synth.voice_count(), 2);
+    
+    // 押さえているノートがあれば、離したノートはすぐに止まる
+    synth.note_off(64);
+    for _ in 0..64 {
+        synth.next_frame();
+    }
+    assert_eq!(synth.voice_count(), 1);
+    assert_eq!(shared(&synth).0, EnvelopeStage::Sustain);
+    
+    // すべて離すと、最後のノートは共有のリリースが終わるまで鳴る
+    synth.note_off(67);
+    for _ in 0..64 {
+        synth.next_frame();
+    }
+    assert_eq!(synth.voice_count(), 1);
+    assert_eq!(shared(&synth).0, EnvelopeStage::Release);
+    assert!(synth.next_frame() != [0.0; 2]);
+    for _ in 0..2205 {
+        synth.next_frame();
+    }
+    assert_eq!(synth.voice_count(), 0);
+    
+    synth.note_on(62, 0.8);
+    assert_eq!(shared(&synth).0, EnvelopeStage::Attack);
+    synth.set_voice_mode(VoiceMode::Poly);
+    assert!(synth.paraphony().is_none());
+}
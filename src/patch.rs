@@ -0,0 +1,397 @@
+use std::path::Path;
+
+use crate::drone::{DEFAULT_DRONE_DEPTH, DEFAULT_DRONE_RATE};
+use crate::engine::{default_harmonics, default_operators, Harmonic, Operator, SpectralFilter};
+use crate::modulation::{BreakpointEnvelope, Lfo, ModCombineModes, ModDestination, ModRouting, RoutingZone, BREAKPOINT_ENVELOPE_COUNT};
+use crate::params;
+use crate::synth::Envelope;
+
+// 全ボイスで共有される音色パラメータ。
+// ボイスは確保時にこの状態で初期化され、変更時には全ボイスへ反映される。
+#[derive(Debug, PartialEq)]
+pub struct PatchState {
+    pub blend: f32,       // 0.0 = Additive, 1.0 = FM
+    pub brightness: f32,  // 0.5 = 標準
+    pub cutoff: f32,      // 正規化カットオフ 0.0-1.0（synth::cutoff_to_hz で Hz に変換）
+    pub resonance: f32,
+    pub filter_drive: f32,         // 0.0-1.0
+    pub filter_compensation: bool, // レゾナンス・ドライブによる音量変化を補正する
+    pub volume: f32,
+    pub pan: f32,              // -1.0 = 左, 1.0 = 右
+    pub depth: f32,            // 0.0 = 前, 1.0 = 後ろ（サラウンド出力のみ）
+    pub voice_saturation: f32, // 0.0-1.0 ボイスごとの出力の飽和（tanh、0 で無効）
+    pub output_trim: f32,      // dB（±synth::MAX_OUTPUT_TRIM_DB）バンク内で音量を揃えるためのボイス出力のトリム
+    pub output_invert: bool,   // ボイス出力の極性を反転する
+    pub envelope: Envelope,
+    pub glide_time: f32,     // 新しいボイスが前のボイスの音高から滑る時間（秒、0 で無効）
+    pub glide_nearest: bool, // 直前にリリースしたボイスの代わりに、押さえているボイスのうち最も近い音高から滑る
+    pub drone: bool,         // ノートオフを無視して鳴らし続け、倍音の振幅をゆっくりランダムに揺らす
+    pub drone_rate: f32,     // ゆらぎの速さ（Hz、drone::MAX_DRONE_RATE まで）
+    pub drone_depth: f32,    // ゆらぎの深さ 0.0-1.0（倍音の振幅を最大でこの割合だけ下げる）
+    pub harmonics: Vec<Harmonic>,
+    pub operators: Vec<Operator>,
+    pub fm_algorithm: usize,  // 0 = 従来の簡易ネットワーク, 1-32 = DX7 のアルゴリズム（engine::FM_ALGORITHMS）
+    pub stack_density: usize, // 加算合成スタックの層数（1 = 無効）
+    pub stack_detune: f32,    // セント
+    pub stack_spread: f32,    // 0.0-1.0
+    pub width: f32,           // 0.0-1.0 ボイス単体のステレオ幅
+    pub spectral_filter: SpectralFilter,
+    pub anti_alias: bool,
+    pub breakpoint_envelopes: Vec<BreakpointEnvelope>,
+    pub lfo: Lfo,
+    pub mod_routings: Vec<ModRouting>,
+    pub mod_combine: ModCombineModes, // 同じ送り先への複数のルーティングの合わせ方
+}
+
+impl Default for PatchState {
+    fn default() -> Self {
+        Self {
+            blend: 0.5,
+            brightness: 0.5,
+            cutoff: 1.0,
+            resonance: 0.0,
+            filter_drive: 0.0,
+            filter_compensation: true,
+            volume: 1.0,
+            pan: 0.0,
+            depth: 0.0,
+            voice_saturation: 0.0,
+            output_trim: 0.0,
+            output_invert: false,
+            envelope: Envelope::default(),
+            glide_time: 0.0,
+            glide_nearest: false,
+            drone: false,
+            drone_rate: DEFAULT_DRONE_RATE,
+            drone_depth: DEFAULT_DRONE_DEPTH,
+            harmonics: default_harmonics(),
+            operators: default_operators(),
+            fm_algorithm: 0,
+            stack_density: 1,
+            stack_detune: 12.0,
+            stack_spread: 0.5,
+            width: 0.0,
+            spectral_filter: SpectralFilter::default(),
+            anti_alias: true,
+            breakpoint_envelopes: vec![BreakpointEnvelope::default(); BREAKPOINT_ENVELOPE_COUNT],
+            lfo: Lfo::default(),
+            mod_routings: Vec::new(),
+            mod_combine: ModCombineModes::default(),
+        }
+    }
+}
+
+// clone_from は配列を確保済みの領域に写す。オーディオスレッドでシーンを呼び出すときは、
+// 写し先に reserve_for で先に領域を確保しておけばメモリを確保しない
+impl Clone for PatchState {
+    fn clone(&self) -> Self {
+        Self {
+            harmonics: self.harmonics.clone(),
+            operators: self.operators.clone(),
+            breakpoint_envelopes: self.breakpoint_envelopes.clone(),
+            mod_routings: self.mod_routings.clone(),
+            ..*self
+        }
+    }
+    
+    fn clone_from(&mut self, source: &Self) {
+        let mut harmonics = std::mem::take(&mut self.harmonics);
+        let mut operators = std::mem::take(&mut self.operators);
+        let mut breakpoint_envelopes = std::mem::take(&mut self.breakpoint_envelopes);
+        let mut mod_routings = std::mem::take(&mut self.mod_routings);
+        harmonics.clone_from(&source.harmonics);
+        operators.clone_from(&source.operators);
+        breakpoint_envelopes.clone_from(&source.breakpoint_envelopes);
+        mod_routings.clone_from(&source.mod_routings);
+        *self = Self { harmonics, operators, breakpoint_envelopes, mod_routings, ..*source };
+    }
+}
+
+impl PatchState {
+    // other を clone_from で写せるだけの領域を先に確保する
+    pub fn reserve_for(&mut self, other: &PatchState) {
+        self.harmonics.reserve(other.harmonics.len().saturating_sub(self.harmonics.len()));
+        self.operators.reserve(other.operators.len().saturating_sub(self.operators.len()));
+        self.mod_routings.reserve(other.mod_routings.len().saturating_sub(self.mod_routings.len()));
+        // エンベロープの数はどのパッチも BREAKPOINT_ENVELOPE_COUNT
+        for (target, envelope) in self.breakpoint_envelopes.iter_mut().zip(&other.breakpoint_envelopes) {
+            target.points.reserve(envelope.points.len().saturating_sub(target.points.len()));
+        }
+    }
+    
+    // from と to の間を t (0.0-1.0) で補間した状態にする。
+    // 連続値は線形補間し、離散的な値（層数、ルーティングなど）は中間点で切り替える。
+    pub fn interpolate(&mut self, from: &PatchState, to: &PatchState, t: f32) {
+        let t = t.clamp(0.0, 1.0);
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let discrete = if t < 0.5 { from } else { to };
+        
+        self.blend = lerp(from.blend, to.blend);
+        self.brightness = lerp(from.brightness, to.brightness);
+        self.cutoff = lerp(from.cutoff, to.cutoff);
+        self.resonance = lerp(from.resonance, to.resonance);
+        self.filter_drive = lerp(from.filter_drive, to.filter_drive);
+        self.filter_compensation = discrete.filter_compensation;
+        self.volume = lerp(from.volume, to.volume);
+        self.pan = lerp(from.pan, to.pan);
+        self.depth = lerp(from.depth, to.depth);
+        self.voice_saturation = lerp(from.voice_saturation, to.voice_saturation);
+        self.output_trim = lerp(from.output_trim, to.output_trim);
+        self.output_invert = discrete.output_invert;
+        self.envelope = Envelope {
+            attack: lerp(from.envelope.attack, to.envelope.attack),
+            decay: lerp(from.envelope.decay, to.envelope.decay),
+            sustain: lerp(from.envelope.sustain, to.envelope.sustain),
+            release: lerp(from.envelope.release, to.envelope.release),
+            tempo_sync: discrete.envelope.tempo_sync,
+        };
+        self.glide_time = lerp(from.glide_time, to.glide_time);
+        self.glide_nearest = discrete.glide_nearest;
+        self.drone = discrete.drone;
+        self.drone_rate = lerp(from.drone_rate, to.drone_rate);
+        self.drone_depth = lerp(from.drone_depth, to.drone_depth);
+        
+        // 片方だけ有効な倍音・オペレーターは振幅 0 との間でフェードさせる
+        let effective = |enabled: bool, amplitude: f32| if enabled { amplitude } else { 0.0 };
+        for ((harmonic, a), b) in self.harmonics.iter_mut().zip(&from.harmonics).zip(&to.harmonics) {
+            harmonic.frequency_multiplier = lerp(a.frequency_multiplier, b.frequency_multiplier);
+            harmonic.amplitude = lerp(effective(a.enabled, a.amplitude), effective(b.enabled, b.amplitude));
+            harmonic.phase = lerp(a.phase, b.phase);
+            harmonic.enabled = a.enabled || b.enabled;
+        }
+        for ((operator, a), b) in self.operators.iter_mut().zip(&from.operators).zip(&to.operators) {
+            operator.frequency_ratio = lerp(a.frequency_ratio, b.frequency_ratio);
+            operator.amplitude = lerp(effective(a.enabled, a.amplitude), effective(b.enabled, b.amplitude));
+            operator.feedback = lerp(a.feedback, b.feedback);
+            operator.enabled = a.enabled || b.enabled;
+        }
+        self.fm_algorithm = discrete.fm_algorithm;
+        
+        self.stack_density = discrete.stack_density;
+        self.stack_detune = lerp(from.stack_detune, to.stack_detune);
+        self.stack_spread = lerp(from.stack_spread, to.stack_spread);
+        self.width = lerp(from.width, to.width);
+        let (a, b) = (&from.spectral_filter, &to.spectral_filter);
+        self.spectral_filter = SpectralFilter {
+            tilt: lerp(a.tilt, b.tilt),
+            low_shelf_harmonic: lerp(a.low_shelf_harmonic, b.low_shelf_harmonic),
+            low_shelf_gain: lerp(a.low_shelf_gain, b.low_shelf_gain),
+            high_shelf_harmonic: lerp(a.high_shelf_harmonic, b.high_shelf_harmonic),
+            high_shelf_gain: lerp(a.high_shelf_gain, b.high_shelf_gain),
+            formant_shift: lerp(a.formant_shift, b.formant_shift),
+        };
+        self.anti_alias = discrete.anti_alias;
+        self.breakpoint_envelopes.clone_from(&discrete.breakpoint_envelopes);
+        self.lfo = Lfo {
+            rate: lerp(from.lfo.rate, to.lfo.rate),
+            shape: discrete.lfo.shape,
+            fade: lerp(from.lfo.fade, to.lfo.fade),
+            fade_sync: discrete.lfo.fade_sync,
+            one_shot: discrete.lfo.one_shot,
+            sync: discrete.lfo.sync,
+        };
+        self.mod_routings.clone_from(&discrete.mod_routings);
+        self.mod_combine = discrete.mod_combine;
+    }
+}
+
+// 2つのパッチの間のモーフィング。position（0.0 = from, 1.0 = to）は演奏中に動かせる
+#[derive(Debug, Clone)]
+pub struct PatchMorph {
+    pub from: PatchState,
+    pub to: PatchState,
+    pub position: f32,
+}
+
+// パッチ差分形式の版と先頭行。パラメータの名前や既定値を変えたときは版を上げ、PATCH_MIGRATIONS に移行を足す
+pub const PATCH_DELTA_VERSION: u32 = 2;
+pub const PATCH_DELTA_HEADER: &str = "# synthesizer patch delta v2";
+const PATCH_DELTA_HEADER_PREFIX: &str = "# synthesizer patch delta v";
+
+// 1つ前の版の差分を読むための移行
+struct Migration {
+    renames: &'static [(&'static str, &'static str)], // 旧名 → 新名
+    defaults: fn(&mut PatchState), // 前の版に無かった値を、前の版での振る舞いに戻す（基準プリセットに適用する）
+}
+
+// 添え字 i は v(i+1) → v(i+2) の移行
+const PATCH_MIGRATIONS: [Migration; PATCH_DELTA_VERSION as usize - 1] = [
+    // v2: anti_alias を CLI のコマンドに合わせて antialias にした
+    Migration { renames: &[("anti_alias", "antialias")], defaults: |_| {} },
+];
+
+// "<prefix><版>" の先頭行から版を読む（先頭行の無いテキストは v1 として扱う）
+pub fn format_version(text: &str, prefix: &str) -> Result<u32, String> {
+    match text.lines().map(str::trim).find_map(|line| line.strip_prefix(prefix)) {
+        Some(version) => version.parse::<u32>().ok().filter(|version| *version >= 1)
+            .ok_or_else(|| format!("invalid format version: {}", version)),
+        None => Ok(1),
+    }
+}
+
+// 版 version で保存されたパラメータ名を現在の名前に移す
+fn migrate_param_name(name: &str, version: u32) -> &str {
+    PATCH_MIGRATIONS.iter()
+        .skip(version.saturating_sub(1) as usize)
+        .fold(name, |name, migration| {
+            migration.renames.iter().find(|(old, _)| *old == name).map_or(name, |(_, new)| new)
+        })
+}
+
+// 差分の基準として使える名前付きプリセット
+pub const BASE_PRESETS: [&str; 3] = ["init", "additive", "fm"];
+
+impl PatchState {
+    pub fn base_preset(name: &str) -> Option<PatchState> {
+        let mut patch = PatchState::default();
+        match name {
+            "init" => {}
+            "additive" => patch.blend = 0.0,
+            "fm" => patch.blend = 1.0,
+            _ => return None,
+        }
+        Some(patch)
+    }
+    
+    // 基準プリセットと異なるパラメータだけを記録した差分テキストを作る
+    pub fn to_delta(&self, base_name: &str) -> Result<String, String> {
+        let base = Self::base_preset(base_name)
+            .ok_or_else(|| format!("unknown base preset: {}", base_name))?;
+        
+        let mut text = format!("{}\nbase {}\n", PATCH_DELTA_HEADER, base_name);
+        let base_values = params::values(&base);
+        for ((name, value), (_, base_value)) in params::values(self).iter().zip(&base_values) {
+            if value != base_value {
+                text.push_str(&format!("set {} {}\n", name, value));
+            }
+        }
+        
+        if self.lfo.shape != base.lfo.shape {
+            text.push_str(&format!("lfo shape {}\n", self.lfo.shape));
+        }
+        
+        if self.mod_routings != base.mod_routings {
+            text.push_str("route clear\n");
+            for routing in &self.mod_routings {
+                text.push_str(&format!("route {} {} {}{}\n", routing.source, routing.destination, routing.amount, routing.zone));
+            }
+        }
+        for destination in ModDestination::ALL {
+            let mode = self.mod_combine.get(destination);
+            if mode != base.mod_combine.get(destination) {
+                text.push_str(&format!("combine {} {}\n", destination, mode));
+            }
+        }
+        
+        for (i, (envelope, base_envelope)) in self.breakpoint_envelopes.iter().zip(&base.breakpoint_envelopes).enumerate() {
+            if envelope == base_envelope {
+                continue;
+            }
+            let n = i + 1;
+            text.push_str(&format!("bp {} clear\n", n));
+            for point in &envelope.points {
+                text.push_str(&format!("bp {} add {} {} {}\n", n, point.time, point.level, point.curve));
+            }
+            if let Some((start, end)) = envelope.loop_points {
+                text.push_str(&format!("bp {} loop {} {}\n", n, start, end));
+            }
+            text.push_str(&format!("bp {} sync {}\n", n, if envelope.tempo_sync { "on" } else { "off" }));
+        }
+        
+        Ok(text)
+    }
+    
+    // 差分テキストを基準プリセットに重ねて読み込む。
+    // 古い版の差分は名前と既定値を移行してから読み、未知のパラメータや行は読み飛ばして警告として返す。
+    // このビルドより新しい版は、名前や既定値の意味が分からないのでエラーにする
+    pub fn from_delta(text: &str) -> Result<(PatchState, Vec<String>), String> {
+        let version = format_version(text, PATCH_DELTA_HEADER_PREFIX)?;
+        if version > PATCH_DELTA_VERSION {
+            return Err(format!("unsupported patch delta version: v{} (this build reads up to v{})", version, PATCH_DELTA_VERSION));
+        }
+        let mut lines = text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        
+        let base_name = match lines.next().map(|line| line.split_whitespace().collect::<Vec<_>>()) {
+            Some(parts) if parts.len() == 2 && parts[0] == "base" => parts[1].to_string(),
+            _ => return Err("patch delta must start with 'base <preset>'".to_string()),
+        };
+        let mut patch = Self::base_preset(&base_name)
+            .ok_or_else(|| format!("unknown base preset: {}", base_name))?;
+        // 古い版ほど後に適用して、同じ値を何度か変えていても保存した版での既定値が残るようにする
+        for migration in PATCH_MIGRATIONS.iter().skip(version as usize - 1).rev() {
+            (migration.defaults)(&mut patch);
+        }
+        
+        let mut warnings = Vec::new();
+        for line in lines {
+            if let Err(message) = patch.apply_delta_line(line, version) {
+                warnings.push(format!("{} ({})", message, line));
+            }
+        }
+        Ok((patch, warnings))
+    }
+    
+    fn apply_delta_line(&mut self, line: &str, version: u32) -> Result<(), String> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let number = |value: &str| value.parse::<f32>().map_err(|_| format!("invalid number: {}", value));
+        
+        match parts.as_slice() {
+            ["set", name, value] => params::set(self, migrate_param_name(name, version), number(value)?),
+            ["lfo", "shape", shape] => {
+                self.lfo.shape = shape.parse()?;
+                Ok(())
+            }
+            ["route", "clear"] => {
+                self.mod_routings.clear();
+                Ok(())
+            }
+            ["route", source, destination, amount, zone @ ..] => {
+                self.mod_routings.push(ModRouting {
+                    source: source.parse()?,
+                    destination: destination.parse()?,
+                    amount: number(amount)?,
+                    zone: RoutingZone::parse(zone)?,
+                });
+                Ok(())
+            }
+            ["combine", destination, mode] => {
+                self.mod_combine.set(destination.parse()?, mode.parse()?);
+                Ok(())
+            }
+            ["bp", index, command, args @ ..] => {
+                let envelope = index.parse::<usize>().ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .and_then(|i| self.breakpoint_envelopes.get_mut(i))
+                    .ok_or_else(|| format!("unknown breakpoint envelope: {}", index))?;
+                match (*command, args) {
+                    ("clear", []) => envelope.clear(),
+                    ("add", [time, level, curve]) => envelope.add_point(number(time)?, number(level)?, number(curve)?),
+                    ("loop", [start, end]) => {
+                        let start = start.parse::<usize>().map_err(|_| format!("invalid loop start: {}", start))?;
+                        let end = end.parse::<usize>().map_err(|_| format!("invalid loop end: {}", end))?;
+                        if !envelope.set_loop(start, end) {
+                            return Err("invalid loop points".to_string());
+                        }
+                    }
+                    ("sync", ["on"]) => envelope.tempo_sync = true,
+                    ("sync", ["off"]) => envelope.tempo_sync = false,
+                    _ => return Err("unknown breakpoint command".to_string()),
+                }
+                Ok(())
+            }
+            _ => Err("unknown line".to_string()),
+        }
+    }
+    
+    pub fn save_delta(&self, path: impl AsRef<Path>, base_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(path, self.to_delta(base_name)?)?;
+        Ok(())
+    }
+    
+    pub fn load_delta(path: impl AsRef<Path>) -> Result<(PatchState, Vec<String>), Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::from_delta(&text)?)
+    }
+}
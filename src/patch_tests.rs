@@ -0,0 +1,73 @@
+// パッチ差分形式の書き出し・読み込みのテスト
+use crate::modulation::{LfoShape, ModCombine, ModDestination, ModRouting, ModSource, RoutingZone};
+use crate::patch::{PatchState, PATCH_DELTA_HEADER};
+use crate::session::Session;
+
+// 基準プリセットからの差分を書き出して読み込むと、元のパッチと同じになる
+#[test]
+fn delta_round_trips_to_the_same_patch() {
+    let mut target = PatchState::base_preset("fm").unwrap();
+    target.cutoff = 0.3125;
+    target.resonance = 0.7;
+    target.envelope.attack = 0.015;
+    target.envelope.tempo_sync = true;
+    target.stack_density = 5;
+    target.spectral_filter.formant_shift = -3.5;
+    target.harmonics[2].amplitude = 0.45;
+    target.harmonics[2].enabled = true;
+    target.operators[1].frequency_ratio = 3.5;
+    target.operators[1].enabled = true;
+    target.fm_algorithm = 5;
+    target.lfo.shape = LfoShape::Triangle;
+    target.lfo.rate = 6.5;
+    target.mod_routings.push(ModRouting {
+        source: ModSource::Lfo,
+        destination: ModDestination::Cutoff,
+        amount: 0.25,
+        zone: RoutingZone { keys: (48, 72), velocities: (0.0, 0.5) },
+    });
+    target.mod_combine.set(ModDestination::Cutoff, ModCombine::Max);
+    target.breakpoint_envelopes[1].add_point(0.0, 0.0, 0.0);
+    target.breakpoint_envelopes[1].add_point(0.25, 1.0, 2.0);
+    target.breakpoint_envelopes[1].add_point(0.5, 0.2, -1.0);
+    assert!(target.breakpoint_envelopes[1].set_loop(1, 2));
+    
+    let delta = target.to_delta("fm").unwrap();
+    assert!(delta.starts_with(PATCH_DELTA_HEADER), "{}", delta);
+    let (loaded, warnings) = PatchState::from_delta(&delta).unwrap();
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(loaded, target, "{}", delta);
+    
+    // 別の基準から書き出しても同じパッチになる
+    let (loaded, _) = PatchState::from_delta(&target.to_delta("init").unwrap()).unwrap();
+    assert_eq!(loaded, target);
+}
+
+// 古い版の差分は名前を移行して読み、未知の名前は警告を出して読み飛ばす。新しい版はエラーにする
+#[test]
+fn older_patch_deltas_migrate_to_the_current_format() {
+    let v1 = "# synthesizer patch delta v1\nbase init\nset anti_alias 0\nset cutoff 0.25\n";
+    let (patch, warnings) = PatchState::from_delta(v1).unwrap();
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!((patch.anti_alias, patch.cutoff), (false, 0.25));
+    
+    let saved = patch.to_delta("init").unwrap();
+    assert!(saved.starts_with(PATCH_DELTA_HEADER) && saved.contains("set antialias 0\n"), "{}", saved);
+    assert_eq!(PatchState::from_delta(&saved).unwrap().1, Vec::<String>::new());
+    
+    // 現在の版では旧名は未知のパラメータ
+    let (patch, warnings) = PatchState::from_delta(&format!("{}\nbase init\nset anti_alias 0\n", PATCH_DELTA_HEADER)).unwrap();
+    assert!(patch.anti_alias);
+    assert_eq!(warnings.len(), 1);
+    
+    let error = PatchState::from_delta("# synthesizer patch delta v99\nbase fm\nset blend 0.5\n").unwrap_err();
+    assert!(error.contains("v99"), "{}", error);
+    assert!(PatchState::from_delta("# synthesizer patch delta vx\nbase init\n").is_err());
+    assert!(Session::from_text("# synthesizer session v99\npatch\nbase init\n").is_err());
+    
+    // セッションに埋め込まれた古い差分も移行される
+    let session = format!("# synthesizer session v1\ntempo 90\npatch\n{}", v1);
+    let (session, warnings) = Session::from_text(&session).unwrap();
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!((session.tempo_bpm, session.patch.anti_alias), (90.0, false));
+}
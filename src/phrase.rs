@@ -0,0 +1,131 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::random::Rng;
+
+pub const CHORD_NOTES: [u8; 3] = [60, 64, 67]; // C-E-G
+pub const SCALE_NOTES: [u8; 8] = [60, 62, 64, 65, 67, 69, 71, 72]; // C-D-E-F-G-A-B-C
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhraseKind {
+    Chord, // 全ノートがフレーズの終わりまで伸びる（間隔はストラム）
+    Run,   // 1ノートずつ順に鳴らす
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    UpDown, // 折り返しの音は繰り返さない
+    Random,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::UpDown => "updown",
+            Direction::Random => "random",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "up" => Ok(Direction::Up),
+            "down" => Ok(Direction::Down),
+            "updown" => Ok(Direction::UpDown),
+            "random" => Ok(Direction::Random),
+            _ => Err(format!("unknown direction: {}", s)),
+        }
+    }
+}
+
+// フレーズの並べ方と人間らしい揺らぎ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhraseSettings {
+    pub spacing: Option<f32>, // ノートの間隔（秒）。None なら Run は長さに均等、Chord は同時
+    pub direction: Direction,
+    pub octaves: u8,
+    pub velocity: f32,
+    pub velocity_humanize: f32, // ベロシティの揺れ幅（割合）
+    pub timing_humanize: f32,   // 発音タイミングの揺れ幅（秒）
+    pub gap: f32,               // Run で前のノートの終わりから次のノートまでの無音（秒）
+}
+
+impl Default for PhraseSettings {
+    fn default() -> Self {
+        Self {
+            spacing: None,
+            direction: Direction::Up,
+            octaves: 1,
+            velocity: 0.7,
+            velocity_humanize: 0.0,
+            timing_humanize: 0.0,
+            gap: 0.0,
+        }
+    }
+}
+
+// フレーズの先頭からの時刻と長さ（秒）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhraseNote {
+    pub offset: f32,
+    pub note: u8,
+    pub velocity: f32,
+    pub duration: f32,
+}
+
+// notes をオクターブ方向に広げて並べ替え、length 秒のフレーズにする
+pub fn build(kind: PhraseKind, notes: &[u8], length: f32, settings: &PhraseSettings, rng: &mut Rng) -> Vec<PhraseNote> {
+    let mut pitches: Vec<u8> = Vec::new();
+    for octave in 0..settings.octaves.max(1) {
+        for &note in notes {
+            let Some(note) = note.checked_add(octave * 12).filter(|note| *note < 128) else {
+                continue;
+            };
+            // 1オクターブ上の主音が次のオクターブの最初の音と重なる場合は1回にする
+            if pitches.last() != Some(&note) {
+                pitches.push(note);
+            }
+        }
+    }
+    
+    match settings.direction {
+        Direction::Up => {}
+        Direction::Down => pitches.reverse(),
+        Direction::UpDown => {
+            let descending: Vec<u8> = pitches.iter().rev().skip(1).copied().collect();
+            pitches.extend(descending);
+        }
+        Direction::Random => {
+            for index in (1..pitches.len()).rev() {
+                let other = (rng.next_u64() % (index as u64 + 1)) as usize;
+                pitches.swap(index, other);
+            }
+        }
+    }
+    
+    // Run の自動間隔は最後のノートが length ちょうどで終わるように決める
+    // （ノート i は前のノートが終わって gap 秒後に始まる）
+    let count = pitches.len().max(1) as f32;
+    let spacing = settings.spacing.unwrap_or(match kind {
+        PhraseKind::Chord => 0.0,
+        PhraseKind::Run => (length + settings.gap) / count,
+    });
+    
+    pitches.iter().enumerate().map(|(index, &note)| {
+        let offset = (index as f32 * spacing + settings.timing_humanize * rng.bipolar()).max(0.0);
+        let duration = match kind {
+            PhraseKind::Chord => (length - offset).max(0.05),
+            PhraseKind::Run => (spacing - settings.gap).max(0.05),
+        };
+        let velocity = settings.velocity * (1.0 + settings.velocity_humanize * rng.bipolar());
+        PhraseNote { offset, note, velocity: velocity.clamp(0.05, 1.0), duration }
+    }).collect()
+}
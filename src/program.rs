@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::bank::PresetBank;
+use crate::handle::SynthHandle;
+use crate::patch::PatchState;
+
+// 対応表のファイルの先頭行
+pub const PROGRAM_MAP_HEADER: &str = "# synthesizer program map v1";
+const LOADER_POLL: Duration = Duration::from_millis(10);
+
+// プログラムチェンジで読み込む音色
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgramTarget {
+    Preset(String), // プリセットのバンクの "カテゴリ/名前"
+    File(PathBuf),  // パッチ差分ファイル
+}
+
+impl ProgramTarget {
+    // bank_root のバンクからプリセットを探す（バンクはその都度読み直す）
+    pub fn load(&self, bank_root: &Path) -> Result<(PatchState, Vec<String>), Box<dyn Error>> {
+        match self {
+            ProgramTarget::Preset(id) => PresetBank::open(bank_root)?.read(id),
+            ProgramTarget::File(path) => PatchState::load_delta(path),
+        }
+    }
+}
+
+impl fmt::Display for ProgramTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramTarget::Preset(id) => write!(f, "preset {}", id),
+            ProgramTarget::File(path) => write!(f, "file {}", path.display()),
+        }
+    }
+}
+
+// 受信したプログラムチェンジの番号（0-127）と音色の対応表。
+// 表に無い番号は従来どおりシーンを呼び出す
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgramMap {
+    entries: BTreeMap<u8, ProgramTarget>,
+}
+
+impl ProgramMap {
+    pub fn get(&self, program: u8) -> Option<&ProgramTarget> {
+        self.entries.get(&program)
+    }
+    
+    pub fn set(&mut self, program: u8, target: ProgramTarget) -> Result<(), String> {
+        if program > 127 {
+            return Err(format!("program number out of range: {} (use 0-127)", program));
+        }
+        self.entries.insert(program, target);
+        Ok(())
+    }
+    
+    pub fn remove(&mut self, program: u8) -> bool {
+        self.entries.remove(&program).is_some()
+    }
+    
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    
+    pub fn entries(&self) -> impl Iterator<Item = (u8, &ProgramTarget)> {
+        self.entries.iter().map(|(program, target)| (*program, target))
+    }
+    
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n", PROGRAM_MAP_HEADER);
+        for (program, target) in self.entries() {
+            text.push_str(&format!("{} {}\n", program, target));
+        }
+        text
+    }
+    
+    // 未知の行は読み飛ばし、警告として返す
+    pub fn from_text(text: &str) -> (ProgramMap, Vec<String>) {
+        let mut map = ProgramMap::default();
+        let mut warnings = Vec::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            if let Err(message) = map.apply_line(line) {
+                warnings.push(format!("{} ({})", message, line));
+            }
+        }
+        (map, warnings)
+    }
+    
+    // "<番号> preset <カテゴリ/名前>" または "<番号> file <パス>"（パスは行末まで）
+    fn apply_line(&mut self, line: &str) -> Result<(), String> {
+        let mut parts = line.splitn(3, char::is_whitespace);
+        let (Some(program), Some(kind), Some(value)) = (parts.next(), parts.next(), parts.next().map(str::trim)) else {
+            return Err("incomplete program mapping".to_string());
+        };
+        let program = program.parse::<u8>().map_err(|_| format!("invalid program number: {}", program))?;
+        let target = match kind {
+            "preset" => ProgramTarget::Preset(value.to_string()),
+            "file" => ProgramTarget::File(PathBuf::from(value)),
+            _ => return Err(format!("unknown program target: {}", kind)),
+        };
+        self.set(program, target)
+    }
+    
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_text())?;
+        Ok(())
+    }
+    
+    // ファイルが無ければ空の表
+    pub fn load(path: impl AsRef<Path>) -> Result<(ProgramMap, Vec<String>), Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Self::from_text(&text)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok((ProgramMap::default(), Vec::new())),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// 読み込みの結果（表示は呼び出し側が言語に合わせて行う）
+pub enum ProgramLoad<'a> {
+    Loaded { program: u8, target: &'a ProgramTarget, warnings: &'a [String] },
+    Failed { program: u8, target: &'a ProgramTarget, error: &'a dyn Error },
+}
+
+// 表にあるプログラムチェンジを受け取ったら、ファイルを読んで音色を切り替える。
+// MIDI の受信やオーディオのスレッドではファイルを読まず、このスレッドが Synthesizer を借りずに読んで解釈し、
+// 差し替えは Synthesizer::queue_patch で次のコントロールレートの境界に任せる。
+// 要求は Telemetry で待ち、届いたときだけ借りる
+pub struct ProgramLoader {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProgramLoader {
+    // report は読み込みのたびにこのスレッドから呼ばれる
+    pub fn start(synth: SynthHandle, bank_root: impl AsRef<Path>, report: impl Fn(ProgramLoad<'_>) + Send + 'static) -> Self {
+        let bank_root = bank_root.as_ref().to_path_buf();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            let telemetry = synth.telemetry();
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let request = match telemetry.has_program_request() {
+                        true => synth.borrow().and_then(|mut synth| synth.take_program_request()),
+                        false => None,
+                    };
+                    let Some((program, target)) = request else {
+                        std::thread::sleep(LOADER_POLL);
+                        continue;
+                    };
+                    match target.load(&bank_root) {
+                        Ok((patch, warnings)) => {
+                            synth.queue_patch(patch);
+                            report(ProgramLoad::Loaded { program, target: &target, warnings: &warnings });
+                        }
+                        Err(e) => report(ProgramLoad::Failed { program, target: &target, error: e.as_ref() }),
+                    }
+                }
+            })
+        };
+        Self { stop, thread: Some(thread) }
+    }
+}
+
+impl Drop for ProgramLoader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
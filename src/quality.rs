@@ -0,0 +1,118 @@
+// 負荷が高いときに段階的に下げる品質の1段分
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityLevel {
+    pub max_harmonics: usize,  // 加算合成で計算する倍音数の上限（高次から省く）
+    pub max_stack: usize,      // 加算合成スタックの層数の上限
+    pub polyphony_scale: f32,  // 同時発音数の上限に掛ける係数（早めにボイスを奪う）
+}
+
+impl QualityLevel {
+    pub const FULL: QualityLevel = QualityLevel {
+        max_harmonics: usize::MAX,
+        max_stack: usize::MAX,
+        polyphony_scale: 1.0,
+    };
+}
+
+// 負荷に応じた品質調整の方針
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityPolicy {
+    pub enabled: bool,
+    pub overload: f32,          // DSP負荷（コールバック処理時間 / バッファ長）がこれを超えたら1段下げる
+    pub recover: f32,           // これを下回る状態が続いたら1段戻す
+    pub recover_callbacks: u32, // 戻すまでに必要な連続コールバック数
+    pub levels: Vec<QualityLevel>, // 先頭が最高品質
+}
+
+impl Default for QualityPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            overload: 0.85,
+            recover: 0.5,
+            recover_callbacks: 200,
+            levels: vec![
+                QualityLevel::FULL,
+                QualityLevel { max_harmonics: 32, max_stack: 3, polyphony_scale: 1.0 },
+                QualityLevel { max_harmonics: 16, max_stack: 1, polyphony_scale: 0.75 },
+                QualityLevel { max_harmonics: 8, max_stack: 1, polyphony_scale: 0.5 },
+            ],
+        }
+    }
+}
+
+// DSP負荷を監視して品質の段を決める
+#[derive(Debug, Clone)]
+pub struct QualityGovernor {
+    policy: QualityPolicy,
+    level: usize,
+    load: f32,           // 平滑化した負荷
+    calm_callbacks: u32, // recover を下回り続けているコールバック数
+}
+
+impl QualityGovernor {
+    pub fn new(policy: QualityPolicy) -> Self {
+        Self {
+            policy,
+            level: 0,
+            load: 0.0,
+            calm_callbacks: 0,
+        }
+    }
+    
+    pub fn policy(&self) -> &QualityPolicy {
+        &self.policy
+    }
+    
+    pub fn load(&self) -> f32 {
+        self.load
+    }
+    
+    pub fn level_index(&self) -> usize {
+        self.level
+    }
+    
+    pub fn level(&self) -> QualityLevel {
+        self.policy.levels.get(self.level).copied().unwrap_or(QualityLevel::FULL)
+    }
+    
+    // 無効にしたときは最高品質に戻す。段が変わった場合は新しい段を返す
+    pub fn set_enabled(&mut self, enabled: bool) -> Option<usize> {
+        self.policy.enabled = enabled;
+        self.calm_callbacks = 0;
+        if !enabled && self.level != 0 {
+            self.level = 0;
+            return Some(0);
+        }
+        None
+    }
+    
+    // コールバックごとの負荷を反映し、段が変わった場合は新しい段を返す
+    pub fn update(&mut self, load: f32) -> Option<usize> {
+        // 一瞬のスパイクで段を変えないように平滑化する
+        self.load += (load - self.load) * 0.2;
+        if !self.policy.enabled {
+            return None;
+        }
+        
+        if self.load > self.policy.overload && self.level + 1 < self.policy.levels.len() {
+            self.level += 1;
+            self.calm_callbacks = 0;
+            // 下げた直後の負荷は新しい段で測り直す
+            self.load = self.policy.recover;
+            return Some(self.level);
+        }
+        
+        if self.load < self.policy.recover && self.level > 0 {
+            self.calm_callbacks += 1;
+            if self.calm_callbacks >= self.policy.recover_callbacks {
+                self.level -= 1;
+                self.calm_callbacks = 0;
+                return Some(self.level);
+            }
+        } else {
+            self.calm_callbacks = 0;
+        }
+        None
+    }
+}
@@ -0,0 +1,29 @@
+// 負荷に応じた品質調整のテスト
+use crate::log::LogEvent;
+use crate::synth::Synthesizer;
+
+// 過負荷で段を下げると、そのときの負荷をログに残し、鳴っているボイスのスタックの層を減らす
+#[test]
+fn overload_steps_quality_down_and_logs_the_load() {
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).build();
+    let mut log = synth.take_event_log().unwrap();
+    synth.set_stack(7, 20.0, 1.0);
+    synth.note_on(60, 0.8);
+    
+    let mut reports = 0;
+    while synth.quality().level_index() == 0 {
+        synth.report_dsp_load(1.5);
+        reports += 1;
+        assert!(reports < 100);
+    }
+    let changes: Vec<(usize, f32)> = log.drain().into_iter()
+        .filter_map(|entry| match entry.event {
+            LogEvent::QualityChanged { level, load } => Some((level, load)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(changes, [(1, 1.5)]);
+    let voices = synth.voice_info();
+    assert_eq!(voices.len(), 1);
+    assert_eq!(voices[0].stack_density, synth.quality().level().max_stack);
+}
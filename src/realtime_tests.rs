@@ -0,0 +1,297 @@
+// オーディオスレッドのリアルタイム安全性のテスト（定常状態の生成中にメモリを確保しない）
+use crate::alloc_audit::{audit, audit_deallocations};
+use crate::command::Command;
+use crate::engine::AdditiveEngine;
+use crate::event::Event;
+use crate::midi::MidiMessage;
+use crate::modulation::{ModDestination, ModRouting, ModSource};
+use crate::patch::PatchState;
+use crate::synth::Synthesizer;
+use std::time::Instant;
+
+const CHORD: [u8; 5] = [48, 55, 60, 64, 67];
+
+// 和音を押さえたまま、最初のブロックで準備が済んだ後のブロックを測る
+fn steady_state_allocations(synth: &mut Synthesizer) -> u64 {
+    for note in CHORD {
+        synth.note_on(note, 0.6);
+    }
+    let mut block = vec![[0.0; 2]; 256];
+    synth.render_block(&mut block);
+    let ((), allocations) = audit(|| {
+        for _ in 0..40 {
+            synth.render_block(&mut block);
+        }
+    });
+    allocations
+}
+
+#[test]
+fn steady_state_rendering_does_not_allocate() {
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).build();
+    assert_eq!(steady_state_allocations(&mut synth), 0);
+    
+    // 1フレームずつの生成（サラウンド・ランプ中などの経路）も同じ
+    let ((), allocations) = audit(|| {
+        for _ in 0..2048 {
+            synth.next_frame();
+        }
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn parallel_rendering_does_not_allocate() {
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).render_threads(2).build();
+    assert_eq!(steady_state_allocations(&mut synth), 0);
+}
+
+// 起動時に作ったボイスを使い回すので、同時発音数以内のノートオン・ボイスの奪取・回収ではメモリを確保しない
+#[test]
+fn note_on_reuses_pooled_voices_without_allocating() {
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).polyphony(8).build();
+    let mut block = vec![[0.0; 2]; 256];
+    let ((), allocations) = audit(|| {
+        for transpose in 0..3 {
+            // 前の和音のリリース中に弾くので、同時発音数を超えた分はリリース中のボイスを奪う
+            for note in CHORD {
+                synth.note_on(note + transpose, 0.6);
+            }
+            synth.render_block(&mut block);
+            for note in CHORD {
+                synth.note_off(note + transpose);
+            }
+            synth.render_block(&mut block);
+        }
+        for _ in 0..50 {
+            synth.render_block(&mut block);
+        }
+    });
+    assert_eq!(allocations, 0);
+    assert_eq!(synth.voice_count(), 0);
+}
+
+// 計数アロケーターが組み込まれていなければ、上のテストは何も測らずに通ってしまう
+#[test]
+fn audit_counts_allocations_on_this_thread() {
+    let (values, allocations) = audit(|| vec![1.0_f32; 64]);
+    assert_eq!(values.len(), 64);
+    assert_eq!(allocations, 1);
+}
+
+// スタックの層は最大数まで確保してあるので、演奏中に層数を増やしても負荷で減らしてもメモリを確保しない
+#[test]
+fn stack_density_changes_do_not_allocate() {
+    let mut engine = AdditiveEngine::new(48000.0);
+    let ((), allocations) = audit(|| {
+        for density in [1, 7, 3, 5, 1, 7] {
+            engine.set_stack(density, 20.0, 1.0);
+            engine.next_frame();
+        }
+        engine.set_quality_limits(16, 2);
+        engine.next_frame();
+        engine.set_quality_limits(64, 7);
+        engine.next_frame();
+    });
+    assert_eq!(allocations, 0);
+    assert_eq!(engine.stack_density(), 7);
+}
+
+// プリセットの差し替え（Synthesizer::queue_patch）は、オーディオスレッドで鳴っているボイスに反映してもメモリを確保しない。
+// ルーティングやブレークポイントが増える音色でも、ボイスの分は queue_patch を呼んだスレッドで確保しておく
+#[test]
+fn queued_patch_swap_does_not_allocate() {
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).build();
+    for note in CHORD {
+        synth.note_on(note, 0.6);
+    }
+    let mut block = vec![[0.0; 2]; 256];
+    synth.render_block(&mut block);
+    
+    let mut rich = PatchState::base_preset("fm").unwrap();
+    rich.cutoff = 0.3;
+    rich.stack_density = 5;
+    rich.harmonics[2].amplitude = 0.8;
+    rich.operators[1].frequency_ratio = 3.0;
+    rich.fm_algorithm = 5;
+    for destination in [ModDestination::Cutoff, ModDestination::Pitch, ModDestination::Amplitude] {
+        rich.mod_routings.push(ModRouting::new(ModSource::Breakpoint(0), destination, 0.3));
+    }
+    for step in 0..6 {
+        rich.breakpoint_envelopes[0].add_point(0.05 * step as f32, 1.0 - 0.1 * step as f32, 0.0);
+    }
+    let patches = [rich, PatchState::base_preset("additive").unwrap(), PatchState::default()];
+    
+    let mut allocations = 0;
+    for patch in patches.iter().cycle().take(6) {
+        synth.queue_patch(patch.clone());
+        let ((), swapped) = audit(|| synth.render_block(&mut block));
+        assert!(!synth.has_pending_patch());
+        allocations += swapped;
+    }
+    assert_eq!(allocations, 0);
+}
+
+
+// ほかのスレッドから送った操作（ノート・MIDI・パラメータ・テンポ）は、コントロールレートの境界で取り出して処理してもメモリを確保しない
+#[test]
+fn draining_commands_does_not_allocate() {
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).build();
+    let mut sender = synth.command_sender();
+    let mut block = vec![[0.0; 2]; 256];
+    synth.render_block(&mut block);
+    let device = synth.register_device("test");
+    
+    let mut allocations = 0;
+    for round in 0..4 {
+        for note in CHORD {
+            assert!(sender.send(Command::Event(Event::NoteOn { note: note + round, velocity: 0.6 })));
+        }
+        let message = MidiMessage::ControlChange { channel: 0, controller: 1, value: 32 * round };
+        assert!(sender.send(Command::Midi { device, message, arrived: Instant::now() }));
+        assert!(sender.send(Command::Parameter { index: 0, value: 0.2 * round as f32 }));
+        assert!(sender.send(Command::Tempo(100.0 + round as f32)));
+        for note in CHORD {
+            assert!(sender.send(Command::Event(Event::NoteOff { note: note + round, velocity: 0.5 })));
+        }
+        let ((), drained) = audit(|| synth.render_block(&mut block));
+        allocations += drained;
+    }
+    assert_eq!(allocations, 0);
+    assert_eq!(synth.tempo(), 103.0);
+    assert_eq!(synth.dropped_commands(), 0);
+}
+
+// ルーティングとブレークポイントを持つ、既定の音色と形の違うパッチ
+fn rich_patch() -> PatchState {
+    let mut rich = PatchState::base_preset("fm").unwrap();
+    rich.cutoff = 0.3;
+    rich.stack_density = 5;
+    rich.harmonics[2].amplitude = 0.8;
+    for destination in [ModDestination::Cutoff, ModDestination::Pitch, ModDestination::Amplitude] {
+        rich.mod_routings.push(ModRouting::new(ModSource::Breakpoint(0), destination, 0.3));
+    }
+    for step in 0..6 {
+        rich.breakpoint_envelopes[0].add_point(0.05 * step as f32, 1.0 - 0.1 * step as f32, 0.0);
+    }
+    rich
+}
+
+// シーン 0 に既定の音色、1 に rich_patch を保存して和音を押さえる
+fn synth_with_scenes() -> (Synthesizer, Vec<[f32; 2]>) {
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).build();
+    synth.store_scene(0);
+    synth.set_patch(rich_patch());
+    synth.store_scene(1);
+    for note in CHORD {
+        synth.note_on(note, 0.6);
+    }
+    let mut block = vec![[0.0; 2]; 256];
+    synth.render_block(&mut block);
+    (synth, block)
+}
+
+// シーンのクロスフェードは、鳴っているボイスへブロックごとに補間したパッチを反映してもメモリを確保せず、
+// 終わったときにも外したパッチを解放しない
+#[test]
+fn scene_fade_does_not_allocate() {
+    let (mut synth, mut block) = synth_with_scenes();
+    
+    let (mut allocations, mut deallocations) = (0, 0);
+    for slot in [0, 1, 0] {
+        let (((), allocated), freed) = audit_deallocations(|| {
+            audit(|| {
+                assert!(synth.recall_scene(slot, 0.05));
+                for _ in 0..12 {
+                    synth.render_block(&mut block);
+                }
+            })
+        });
+        assert!(synth.scene_fade().is_none());
+        allocations += allocated;
+        deallocations += freed;
+    }
+    assert_eq!(allocations, 0);
+    assert_eq!(deallocations, 0);
+    assert_eq!(synth.patch().mod_routings.len(), 0);
+}
+
+// 割り当て表に無いプログラムチェンジはオーディオスレッドでシーンを呼び出す。
+// シーンは確保済みの領域に写すので、形の違うシーンの間を行き来してもメモリを確保・解放しない
+#[test]
+fn queued_program_change_does_not_allocate() {
+    let (mut synth, mut block) = synth_with_scenes();
+    let mut sender = synth.command_sender();
+    let device = synth.register_device("test");
+    
+    let (mut allocations, mut deallocations) = (0, 0);
+    for program in [0, 1, 0, 1] {
+        let message = MidiMessage::ProgramChange { channel: 0, program };
+        assert!(sender.send(Command::Midi { device, message, arrived: Instant::now() }));
+        let (((), allocated), freed) = audit_deallocations(|| audit(|| synth.render_block(&mut block)));
+        assert_eq!(synth.current_scene(), Some(program as usize));
+        assert_eq!(synth.patch().mod_routings.len(), 3 * program as usize);
+        allocations += allocated;
+        deallocations += freed;
+    }
+    assert_eq!(allocations, 0);
+    assert_eq!(deallocations, 0);
+}
+
+// 直接設定できないパラメータ（倍音・層数など）に割り当てた CC と、MIDI ラーンで割り当てる CC も、
+// パッチを複製せずにその場で書き換えるのでメモリを確保・解放しない
+#[test]
+fn mapped_cc_to_patch_parameter_does_not_allocate() {
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).build();
+    synth.cc_map_mut().assign(20, "harmonic.3.amp", None).unwrap();
+    synth.cc_map_mut().assign(21, "stack.density", Some((1.0, 7.0))).unwrap();
+    synth.cc_map_mut().learn("op.2.ratio").unwrap();
+    let mut sender = synth.command_sender();
+    let device = synth.register_device("test");
+    for note in CHORD {
+        synth.note_on(note, 0.6);
+    }
+    let mut block = vec![[0.0; 2]; 256];
+    synth.render_block(&mut block);
+    
+    let (mut allocations, mut deallocations) = (0, 0);
+    for (controller, value) in [(22, 64), (20, 127), (21, 127), (20, 0), (22, 127)] {
+        let message = MidiMessage::ControlChange { channel: 0, controller, value };
+        assert!(sender.send(Command::Midi { device, message, arrived: Instant::now() }));
+        let (((), allocated), freed) = audit_deallocations(|| audit(|| synth.render_block(&mut block)));
+        allocations += allocated;
+        deallocations += freed;
+    }
+    assert_eq!(allocations, 0);
+    assert_eq!(deallocations, 0);
+    assert_eq!(synth.patch().harmonics[2].amplitude, 0.0);
+    assert_eq!(synth.patch().stack_density, 7);
+    assert_eq!(synth.cc_map().mappings().iter().find(|mapping| mapping.controller == 22).unwrap().parameter, "op.2.ratio");
+    assert_eq!(synth.patch().operators[1].frequency_ratio, 1.0);
+}
+
+// 負荷で品質の段を下げ（倍音・スタック・同時発音数を減らし）、戻すときもメモリを確保しない
+#[test]
+fn quality_steps_do_not_allocate() {
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).polyphony(8).build();
+    synth.set_patch(PatchState { stack_density: 7, ..PatchState::default() });
+    let mut block = vec![[0.0; 2]; 256];
+    for note in CHORD.iter().chain(&[72, 76, 79]) {
+        synth.note_on(*note, 0.6);
+    }
+    synth.render_block(&mut block);
+    
+    let levels = synth.quality().policy().levels.len();
+    let ((), allocations) = audit(|| {
+        while synth.quality().level_index() + 1 < levels {
+            synth.report_dsp_load(5.0);
+            synth.render_block(&mut block);
+        }
+        while synth.quality().level_index() > 0 {
+            synth.report_dsp_load(0.0);
+        }
+        synth.render_block(&mut block);
+    });
+    assert_eq!(allocations, 0);
+    assert!(synth.voice_count() <= 8);
+}
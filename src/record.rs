@@ -0,0 +1,106 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::export::{AudioWriter, ExportFormat};
+use crate::handle::SynthHandle;
+use crate::ring::{self, Producer};
+
+const RECORD_BUFFER_SECONDS: f32 = 2.0; // 書き込みスレッドが遅れても取りこぼさない長さ
+const WRITE_INTERVAL: Duration = Duration::from_millis(50);
+
+// オーディオスレッド側の録音の入口（確保もロックもしない。満杯なら捨てる）
+pub struct RecordTap {
+    producer: Producer<[f32; 2]>,
+}
+
+impl RecordTap {
+    pub fn push(&mut self, frames: &[[f32; 2]]) {
+        for frame in frames {
+            let _ = self.producer.push(*frame);
+        }
+    }
+}
+
+// 録音の結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordingSummary {
+    pub frames: u64,
+    pub seconds: f32,
+    pub dropped: usize, // バッファが満杯で捨てたフレーム数
+}
+
+// オーディオ出力を WAV / FLAC ファイルへ録音する。
+// 出力のコールバックはリングバッファへ入れるだけで、ファイルへの書き込みは専用のスレッドで行う
+pub struct Recording {
+    path: PathBuf,
+    synth: SynthHandle,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<io::Result<RecordingSummary>>>,
+}
+
+impl Recording {
+    pub fn start(path: impl AsRef<Path>, synth: SynthHandle, format: ExportFormat) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let sample_rate = synth.borrow_or_timeout()?.sample_rate();
+        let mut writer = AudioWriter::create(&path, sample_rate as u32, format)?;
+        let (producer, mut consumer) = ring::channel((sample_rate * RECORD_BUFFER_SECONDS) as usize);
+        synth.borrow_or_timeout()?.set_record_tap(Some(RecordTap { producer }));
+        
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || {
+                let mut frames = Vec::with_capacity(consumer.capacity());
+                loop {
+                    // 止めた後も、入口を外すまでに入ったフレームは書き切る
+                    let stopping = stop.load(Ordering::Acquire);
+                    frames.clear();
+                    frames.extend(consumer.drain());
+                    writer.write(&frames)?;
+                    if stopping {
+                        break;
+                    }
+                    std::thread::sleep(WRITE_INTERVAL);
+                }
+                let dropped = consumer.dropped();
+                let frames = writer.finish()?;
+                Ok(RecordingSummary { frames, seconds: frames as f32 / sample_rate, dropped })
+            })
+        };
+        Ok(Self { path, synth, stop, thread: Some(thread) })
+    }
+    
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    pub fn stop(mut self) -> io::Result<RecordingSummary> {
+        self.finish()
+    }
+    
+    fn finish(&mut self) -> io::Result<RecordingSummary> {
+        // 外せなければ、書き込みのスレッドが止まったあとは入口のリングが満杯になって捨てるだけになる
+        if let Some(mut synth) = self.synth.borrow() {
+            synth.set_record_tap(None);
+        }
+        self.stop.store(true, Ordering::Release);
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(io::Error::other("recording thread panicked")),
+            None => Err(io::Error::other("recording already stopped")),
+        }
+    }
+}
+
+// 止めずに終了してもファイルのヘッダーを書き直して閉じる
+impl Drop for Recording {
+    fn drop(&mut self) {
+        if self.thread.is_some() {
+            let _ = self.finish();
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::command::{Command, CommandSender};
+use crate::handle::SynthHandle;
+use crate::params::{self, Params};
+use crate::synth::DIRECT_PARAMETERS;
+
+pub const DEFAULT_OSC_PORT: u16 = 9000;
+pub const PARAM_PREFIX: &str = "/param/"; // /param/<params の名前> f
+pub const TEMPO_ADDRESS: &str = "/tempo";
+pub const SYNC_ADDRESS: &str = "/sync"; // 受け取ると送り主へ全パラメータを送る
+const FEEDBACK_INTERVAL: Duration = Duration::from_millis(50);
+
+// 引数が float 1つの OSC メッセージ
+pub fn encode_message(address: &str, value: f32) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(address.len() + 12);
+    push_padded(&mut packet, address.as_bytes());
+    push_padded(&mut packet, b",f");
+    packet.extend_from_slice(&value.to_be_bytes());
+    packet
+}
+
+// (アドレス, 最初の数値の引数)。int と true/false も数値として読む。バンドルは未対応
+pub fn parse_message(packet: &[u8]) -> Option<(String, Option<f32>)> {
+    let (address, rest) = read_padded(packet)?;
+    if !address.starts_with('/') {
+        return None;
+    }
+    let value = match read_padded(rest) {
+        Some((tags, arguments)) if tags.starts_with(',') => match tags.as_bytes().get(1) {
+            Some(b'f') => Some(f32::from_be_bytes(arguments.get(..4)?.try_into().ok()?)),
+            Some(b'i') => Some(i32::from_be_bytes(arguments.get(..4)?.try_into().ok()?) as f32),
+            Some(b'T') => Some(1.0),
+            Some(b'F') => Some(0.0),
+            _ => None,
+        },
+        _ => None,
+    };
+    Some((address.to_string(), value))
+}
+
+// OSC の文字列は NUL で終え、4バイト境界まで NUL で埋める
+fn push_padded(packet: &mut Vec<u8>, bytes: &[u8]) {
+    packet.extend_from_slice(bytes);
+    packet.resize((packet.len() / 4 + 1) * 4, 0);
+}
+
+fn read_padded(packet: &[u8]) -> Option<(&str, &[u8])> {
+    let end = packet.iter().position(|byte| *byte == 0)?;
+    let text = std::str::from_utf8(&packet[..end]).ok()?;
+    Some((text, packet.get((end / 4 + 1) * 4..)?))
+}
+
+// 送り先ごとに最後に送った値を覚えておき、変わったものだけ送る
+struct FeedbackTarget {
+    address: SocketAddr,
+    sent: HashMap<String, f32>,
+}
+
+// OSC でパラメータを受け取り、値の変化を登録した送り先（コントロールサーフェスなど）へ送り返す。
+// 変化は定期的に値を見比べて見つけるので、CLI・MIDI・オートメーションのどこで変えても送られる
+pub struct OscRemote {
+    port: u16,
+    targets: Arc<Mutex<Vec<SocketAddr>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl OscRemote {
+    pub fn start(port: u16, synth: SynthHandle) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        socket.set_read_timeout(Some(FEEDBACK_INTERVAL))?;
+        let port = socket.local_addr()?.port();
+        
+        let targets = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let commands = synth.borrow_or_timeout()?.command_sender();
+        let mut senders = Senders { commands, params: synth.params() };
+        let thread = {
+            let (shared_targets, stop) = (Arc::clone(&targets), Arc::clone(&stop));
+            std::thread::spawn(move || {
+                let mut buffer = [0u8; 1024];
+                let mut feedback = Feedback::default();
+                let mut last_feedback = Instant::now();
+                while !stop.load(Ordering::Relaxed) {
+                    if let Ok((length, sender)) = socket.recv_from(&mut buffer) {
+                        if let Some((address, value)) = parse_message(&buffer[..length]) {
+                            receive(&socket, &synth, &mut senders, &mut feedback, sender, &address, value);
+                        }
+                    }
+                    if last_feedback.elapsed() < FEEDBACK_INTERVAL {
+                        continue;
+                    }
+                    last_feedback = Instant::now();
+                    
+                    // 追加された送り先は何も送っていない状態から始まるので、全パラメータが送られる
+                    let addresses = shared_targets.lock().unwrap().clone();
+                    let Feedback { targets, values } = &mut feedback;
+                    targets.retain(|target| addresses.contains(&target.address));
+                    for address in addresses {
+                        if !targets.iter().any(|target| target.address == address) {
+                            targets.push(FeedbackTarget { address, sent: HashMap::new() });
+                        }
+                    }
+                    if targets.is_empty() {
+                        continue;
+                    }
+                    let values = values.current(&synth);
+                    for target in targets.iter_mut() {
+                        for (name, value) in values {
+                            if target.sent.get(name) != Some(value) {
+                                let _ = socket.send_to(&encode_message(name, *value), target.address);
+                                target.sent.insert(name.clone(), *value);
+                            }
+                        }
+                    }
+                }
+            })
+        };
+        Ok(Self { port, targets, stop, thread: Some(thread) })
+    }
+    
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+    
+    pub fn add_target(&self, address: SocketAddr) {
+        let mut targets = self.targets.lock().unwrap();
+        if !targets.contains(&address) {
+            targets.push(address);
+        }
+    }
+    
+    pub fn remove_target(&self, address: SocketAddr) -> bool {
+        let mut targets = self.targets.lock().unwrap();
+        let count = targets.len();
+        targets.retain(|target| *target != address);
+        targets.len() != count
+    }
+    
+    pub fn targets(&self) -> Vec<SocketAddr> {
+        self.targets.lock().unwrap().clone()
+    }
+}
+
+impl Drop for OscRemote {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// 送り返す値の一覧（OSC のアドレス, 値）。パッチの値は Telemetry::edits が変わったときだけ
+// Synthesizer を借りて写し直す（借りている間のコールバックは無音になるので、定期的には借りない）
+#[derive(Default)]
+struct Feedback {
+    targets: Vec<FeedbackTarget>,
+    values: FeedbackValues,
+}
+
+#[derive(Default)]
+struct FeedbackValues {
+    values: Vec<(String, f32)>,
+    edits: Option<u64>,
+}
+
+impl FeedbackValues {
+    fn current(&mut self, synth: &SynthHandle) -> &[(String, f32)] {
+        let telemetry = synth.telemetry();
+        if self.edits != Some(telemetry.edits()) {
+            if let Some(guard) = synth.borrow() {
+                // 借りたことも数えられるので、借りてから読む
+                self.edits = Some(telemetry.edits());
+                let patch = guard.patch().clone();
+                drop(guard);
+                self.values = params::values(&patch)
+                    .into_iter()
+                    .map(|(name, value)| (format!("{}{}", PARAM_PREFIX, name), value))
+                    .collect();
+                self.values.push((TEMPO_ADDRESS.to_string(), 0.0));
+            }
+        }
+        // テンポは Link・MIDI クロックからも変わるので毎回読む
+        if let Some((_, tempo)) = self.values.last_mut() {
+            *tempo = telemetry.tempo();
+        }
+        &self.values
+    }
+}
+
+// ロックを取らずにオーディオスレッドへ渡す経路
+struct Senders {
+    commands: CommandSender,
+    params: Arc<Params>,
+}
+
+// 共有パラメータ（params::SHARED_PARAMETERS）は Params に書いてなめらかに動かし、
+// テンポとほかのよく動かすパラメータ（DIRECT_PARAMETERS）はロックを取らずにコマンドで送る。
+// パッチ全体を適用し直すものも SynthHandle::set_parameter で送り、Synthesizer は借りない
+fn receive(
+    socket: &UdpSocket,
+    synth: &SynthHandle,
+    senders: &mut Senders,
+    feedback: &mut Feedback,
+    sender: SocketAddr,
+    address: &str,
+    value: Option<f32>,
+) {
+    if address == SYNC_ADDRESS {
+        for (name, value) in feedback.values.current(synth) {
+            let _ = socket.send_to(&encode_message(name, *value), sender);
+        }
+        return;
+    }
+    let Some(value) = value else { return };
+    let applied = if address == TEMPO_ADDRESS {
+        senders.commands.send(Command::Tempo(value))
+    } else if let Some(name) = address.strip_prefix(PARAM_PREFIX) {
+        if senders.params.set(name, value) {
+            true
+        } else {
+            match DIRECT_PARAMETERS.iter().position(|direct| *direct == name) {
+                Some(index) => senders.commands.send(Command::Parameter { index, value }),
+                None => synth.set_parameter(name, value).is_ok(),
+            }
+        }
+    } else {
+        false
+    };
+    // 動かしている本人へは同じ値を送り返さない（モーターフェーダーが遅れた値で引き戻されないように）。
+    // 範囲外で丸められたときは値が違うので送られる。
+    // サーフェスは送信元と受信のポートが違うことが多いので、同じホストの送り先を本人とみなす
+    if applied {
+        for target in feedback.targets.iter_mut().filter(|target| target.address.ip() == sender.ip()) {
+            target.sent.insert(address.to_string(), value);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+
+use crate::analysis::Tap;
+use crate::diagnostics::TestSignalGenerator;
+use crate::export::{write_audio, ExportFormat};
+use crate::loudness::{self, LoudnessReport};
+use crate::midi;
+use crate::smf::MidiFile;
+use crate::synth::Synthesizer;
+use crate::wav::write_wav;
+
+// オフラインでのバウンス（リアルタイムより速く書き出す）
+#[derive(Debug, Clone)]
+pub struct BounceOptions {
+    pub seconds: f32,
+    pub stems: bool, // マスターとは別にタップごとのステムを書き出す
+    pub format: ExportFormat,
+    pub normalize: Option<f32>, // 統合ラウドネスをこの値（LUFS）に合わせる（ステムにも同じゲインを掛ける）
+}
+
+// 書き出しの結果。ラウドネスは正規化した後のマスターを測ったもの
+#[derive(Debug, Clone)]
+pub struct RenderReport {
+    pub files: Vec<PathBuf>,
+    pub seconds: f32,
+    pub loudness: LoudnessReport,
+    pub gain_db: Option<f32>, // 正規化で掛けたゲイン（正規化しないか、無音で測れなければ None）
+}
+
+// シーケンサーを先頭から再生して書き出し、書き出したファイルの一覧とラウドネスを返す。
+// 演奏時間の後にリリースの余韻を含める。
+pub fn bounce(source: &Synthesizer, path: impl AsRef<Path>, options: &BounceOptions) -> Result<RenderReport, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    if source.sequencer().tracks().is_empty() {
+        return Err("nothing to render: the sequencer has no tracks".into());
+    }
+    
+    let mut synth = source.offline_copy();
+    synth.set_analysis_taps(options.stems);
+    let sample_rate = synth.sample_rate();
+    let play_frames = (options.seconds.max(0.0) * sample_rate) as usize;
+    let tail_frames = ((synth.patch().envelope.release_seconds(synth.tempo()) + 0.1) * sample_rate) as usize;
+    
+    let mut master = Vec::with_capacity(play_frames + tail_frames);
+    let mut stems: Vec<Vec<[f32; 2]>> = if options.stems {
+        Tap::ALL.iter().map(|_| Vec::with_capacity(play_frames + tail_frames)).collect()
+    } else {
+        Vec::new()
+    };
+    
+    synth.play_sequencer();
+    for frame in 0..play_frames + tail_frames {
+        if frame == play_frames {
+            synth.stop_sequencer();
+        }
+        master.push(synth.next_frame());
+        let taps = synth.analysis_taps().last();
+        for (stem, tap) in stems.iter_mut().zip(Tap::ALL) {
+            stem.push(taps.get(tap));
+        }
+    }
+    
+    let gain_db = options.normalize.and_then(|target| {
+        let mut signals: Vec<&mut [[f32; 2]]> = std::iter::once(&mut master[..]).chain(stems.iter_mut().map(|stem| &mut stem[..])).collect();
+        loudness::normalize_all(&mut signals, sample_rate, target)
+    });
+    write_audio(path, sample_rate as u32, &master, options.format)?;
+    let mut files = vec![path.to_path_buf()];
+    for (stem, tap) in stems.iter().zip(Tap::ALL) {
+        let stem_path = stem_path(path, tap.name());
+        write_audio(&stem_path, sample_rate as u32, stem, options.format)?;
+        files.push(stem_path);
+    }
+    Ok(RenderReport {
+        files,
+        seconds: master.len() as f32 / sample_rate,
+        loudness: loudness::measure(&master, sample_rate),
+        gain_db,
+    })
+}
+
+// synth をそのまま seconds 秒進めて、出力のフレームを返す（テストや書き出し用、リアルタイムより速い）
+pub fn render_frames(synth: &mut Synthesizer, seconds: f32) -> Vec<[f32; 2]> {
+    let frames = (seconds.max(0.0) * synth.sample_rate()) as usize;
+    (0..frames).map(|_| synth.next_frame()).collect()
+}
+
+// synth を seconds 秒進めて format の形式で書き出す（normalize は統合ラウドネスの目標、LUFS）
+pub fn render_to_file(
+    synth: &mut Synthesizer,
+    path: impl AsRef<Path>,
+    seconds: f32,
+    format: ExportFormat,
+    normalize: Option<f32>,
+) -> Result<RenderReport, Box<dyn std::error::Error>> {
+    let mut frames = render_frames(synth, seconds);
+    write_master(path, synth.sample_rate(), &mut frames, format, normalize)
+}
+
+// 書き出したフレームを必要なら正規化してから書き出し、ラウドネスを測る
+pub fn write_master(
+    path: impl AsRef<Path>,
+    sample_rate: f32,
+    frames: &mut [[f32; 2]],
+    format: ExportFormat,
+    normalize: Option<f32>,
+) -> Result<RenderReport, Box<dyn std::error::Error>> {
+    let gain_db = normalize.and_then(|target| loudness::normalize(frames, sample_rate, target));
+    write_audio(path.as_ref(), sample_rate as u32, frames, format)?;
+    Ok(RenderReport {
+        files: vec![path.as_ref().to_path_buf()],
+        seconds: frames.len() as f32 / sample_rate,
+        loudness: loudness::measure(frames, sample_rate),
+        gain_db,
+    })
+}
+
+// MIDI ファイルを先頭から演奏して書き出す。最後のメッセージの後は残ったノートを止め、リリースの余韻を含める
+pub fn render_midi(synth: &mut Synthesizer, file: &MidiFile) -> Vec<[f32; 2]> {
+    let sample_rate = synth.sample_rate();
+    let mut frames = Vec::with_capacity(((file.duration() + 1.0) * sample_rate) as usize);
+    for event in &file.events {
+        let frame = (event.seconds * sample_rate) as usize;
+        while frames.len() < frame {
+            frames.push(synth.next_frame());
+        }
+        midi::dispatch(synth, "", &event.message);
+    }
+    synth.all_notes_off();
+    let tail_frames = ((synth.patch().envelope.release_seconds(synth.tempo()) + 0.1) * sample_rate) as usize;
+    frames.extend((0..tail_frames).map(|_| synth.next_frame()));
+    frames
+}
+
+// mix.wav -> mix.additive.wav（拡張子はマスターと同じ）
+fn stem_path(path: &Path, name: &str) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("bounce");
+    let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("wav");
+    path.with_file_name(format!("{}.{}.{}", stem, name, extension))
+}
+
+// テスト信号を指定秒数だけWAVに書き出す（出力機器へ送るものと同じ信号）
+pub fn capture_test_signal(mut generator: TestSignalGenerator, sample_rate: f32, seconds: f32, path: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let frames: Vec<[f32; 2]> = (0..(seconds.max(0.0) * sample_rate) as usize)
+        .map(|_| [generator.next_sample(); 2])
+        .collect();
+    write_wav(path, sample_rate as u32, &frames)?;
+    Ok(())
+}
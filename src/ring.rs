@@ -0,0 +1,119 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// 固定長のロックフリー SPSC リングバッファ。
+// 書き込み側・読み出し側ともにロックもメモリ確保も行わない。
+struct Shared<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    head: AtomicUsize,    // 次に読む位置
+    tail: AtomicUsize,    // 次に書く位置
+    dropped: AtomicUsize, // 満杯で捨てた要素数
+}
+
+// SAFETY: スロットの中身は Producer から Consumer へ T ごと受け渡すだけなので、T が Send なら送れる
+unsafe impl<T: Send> Send for Shared<T> {}
+// SAFETY: 共有されるのは Producer と Consumer の 1 つずつだけ（どちらも Clone できない）。
+// head..tail のスロットは Consumer だけが、それ以外は Producer だけが触り、
+// 境界の移動は Release / Acquire で相手に公開するので、同じスロットに同時にアクセスすることはない
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        let mut head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        while head != tail {
+            let index = head % self.slots.len();
+            // SAFETY: &mut self なので他に参照はなく、head..tail のスロットは書き込み済みでまだ読まれていない
+            unsafe { (*self.slots[index].get()).assume_init_drop() };
+            head = head.wrapping_add(1);
+        }
+    }
+}
+
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub fn channel<T: Send>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    let capacity = capacity.max(1);
+    let slots = (0..capacity).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect();
+    let shared = Arc::new(Shared {
+        slots,
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        dropped: AtomicUsize::new(0),
+    });
+    (
+        Producer { shared: Arc::clone(&shared) },
+        Consumer { shared },
+    )
+}
+
+impl<T> Producer<T> {
+    // 満杯のときは値を返して捨てた数を数える
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let shared = &*self.shared;
+        let tail = shared.tail.load(Ordering::Relaxed);
+        let head = shared.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == shared.slots.len() {
+            shared.dropped.fetch_add(1, Ordering::Relaxed);
+            return Err(value);
+        }
+        let index = tail % shared.slots.len();
+        // SAFETY: 満杯でないので tail のスロットは head..tail の外にあり、Consumer は触らない。
+        // 中身は未初期化か読み出し済み（assume_init_read でムーブ済み）なので、上書きしても何も失われない
+        unsafe { (*shared.slots[index].get()).write(value) };
+        shared.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+}
+
+impl<T> Consumer<T> {
+    pub fn pop(&mut self) -> Option<T> {
+        let shared = &*self.shared;
+        let head = shared.head.load(Ordering::Relaxed);
+        let tail = shared.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let index = head % shared.slots.len();
+        // SAFETY: head != tail なので head のスロットは Producer が書き込み済みで、
+        // その書き込みは tail の Acquire で見えている。読んだ後は head を進めるので二度は読まない
+        let value = unsafe { (*shared.slots[index].get()).assume_init_read() };
+        shared.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+    
+    pub fn len(&self) -> usize {
+        let tail = self.shared.tail.load(Ordering::Acquire);
+        let head = self.shared.head.load(Ordering::Relaxed);
+        tail.wrapping_sub(head)
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    pub fn dropped(&self) -> usize {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+    
+    pub fn capacity(&self) -> usize {
+        self.shared.slots.len()
+    }
+    
+    // Producer が捨てられ、これ以上は届かない（残りは pop で読める）
+    pub fn is_abandoned(&self) -> bool {
+        Arc::strong_count(&self.shared) == 1
+    }
+    
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.pop())
+    }
+}
@@ -0,0 +1,91 @@
+// ロックフリー SPSC リングバッファのテスト
+use crate::ring::channel;
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn empty_ring_pops_nothing() {
+    let (_producer, mut consumer) = channel::<u32>(4);
+    assert!(consumer.is_empty());
+    assert_eq!(consumer.pop(), None);
+    assert_eq!(consumer.dropped(), 0);
+}
+
+#[test]
+fn full_ring_rejects_and_counts_the_value() {
+    let (mut producer, mut consumer) = channel(3);
+    for value in 0..3 {
+        assert_eq!(producer.push(value), Ok(()));
+    }
+    assert_eq!(consumer.len(), 3);
+    assert_eq!(producer.push(3), Err(3));
+    assert_eq!(producer.push(4), Err(4));
+    assert_eq!(consumer.dropped(), 2);
+    
+    // 1 つ読めば 1 つ書ける
+    assert_eq!(consumer.pop(), Some(0));
+    assert_eq!(producer.push(5), Ok(()));
+    assert_eq!(consumer.drain().collect::<Vec<_>>(), [1, 2, 5]);
+    assert!(consumer.is_empty());
+}
+
+// 容量を何周しても順序が保たれる
+#[test]
+fn wraps_around_the_slots_in_order() {
+    let (mut producer, mut consumer) = channel(4);
+    let mut expected = 0;
+    for round in 0..50 {
+        let count = round % 4 + 1;
+        for value in 0..count {
+            producer.push(expected + value).unwrap();
+        }
+        for _ in 0..count {
+            assert_eq!(consumer.pop(), Some(expected));
+            expected += 1;
+        }
+        assert!(consumer.is_empty());
+    }
+}
+
+// 読まれずに残った要素はリングと一緒に破棄される
+#[test]
+fn drops_values_left_in_the_ring() {
+    let value = Arc::new(());
+    {
+        let (mut producer, mut consumer) = channel(4);
+        for _ in 0..4 {
+            producer.push(Arc::clone(&value)).unwrap();
+        }
+        drop(consumer.pop());
+        assert_eq!(Arc::strong_count(&value), 4);
+    }
+    assert_eq!(Arc::strong_count(&value), 1);
+}
+
+// 書き込みと読み出しを別のスレッドで同時に回し、欠けも重複もなく順に届くことを確かめる
+#[test]
+fn two_threads_pass_every_value_in_order() {
+    const COUNT: u64 = 200_000;
+    let (mut producer, mut consumer) = channel(64);
+    let writer = thread::spawn(move || {
+        for value in 0..COUNT {
+            let mut value = Box::new(value);
+            while let Err(rejected) = producer.push(value) {
+                value = rejected;
+                thread::yield_now();
+            }
+        }
+    });
+    let mut expected = 0;
+    while expected < COUNT {
+        match consumer.pop() {
+            Some(value) => {
+                assert_eq!(*value, expected);
+                expected += 1;
+            }
+            None => thread::yield_now(),
+        }
+    }
+    writer.join().unwrap();
+    assert!(consumer.is_empty());
+}
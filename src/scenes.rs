@@ -0,0 +1,92 @@
+use crate::patch::PatchState;
+
+pub const SCENE_COUNT: usize = 8;
+
+// 演奏中に即座に切り替えられるパラメータのスナップショット（プリセットファイルとは独立）
+#[derive(Debug, Clone, Default)]
+pub struct SceneBank {
+    slots: [Option<PatchState>; SCENE_COUNT],
+}
+
+impl SceneBank {
+    pub fn store(&mut self, slot: usize, patch: &PatchState) -> bool {
+        match self.slots.get_mut(slot) {
+            Some(target) => {
+                *target = Some(patch.clone());
+                true
+            }
+            None => false,
+        }
+    }
+    
+    pub fn get(&self, slot: usize) -> Option<&PatchState> {
+        self.slots.get(slot).and_then(Option::as_ref)
+    }
+    
+    pub fn is_stored(&self, slot: usize) -> bool {
+        self.get(slot).is_some()
+    }
+}
+
+// 音作り用の A/B 比較。選んでいるスロットは編集中の音色として扱い、
+// 切り替えるときにそれまでの編集を書き戻すので、両方を調整しながら聴き比べられる
+#[derive(Debug, Clone, Default)]
+pub struct AbCompare {
+    slots: [Option<PatchState>; 2], // 0 = A, 1 = B
+    active: Option<usize>,
+}
+
+impl AbCompare {
+    pub fn store(&mut self, slot: usize, patch: &PatchState) {
+        self.slots[slot] = Some(patch.clone());
+        self.active = Some(slot);
+    }
+    
+    // current（今の音色）を選んでいるスロットへ書き戻してから slot を取り出す
+    pub fn select(&mut self, slot: usize, current: &PatchState) -> Option<&PatchState> {
+        self.slots[slot].as_ref()?;
+        if let Some(active) = self.active {
+            self.slots[active] = Some(current.clone());
+        }
+        self.active = Some(slot);
+        self.slots[slot].as_ref()
+    }
+    
+    // もう一方のスロットへ切り替える（選んでいなければ A）
+    pub fn toggle(&mut self, current: &PatchState) -> Option<(usize, &PatchState)> {
+        let slot = self.active.map_or(0, |active| 1 - active);
+        self.select(slot, current)?;
+        Some((slot, self.slots[slot].as_ref()?))
+    }
+    
+    pub fn active(&self) -> Option<usize> {
+        self.active
+    }
+    
+    pub fn is_stored(&self, slot: usize) -> bool {
+        self.slots[slot].is_some()
+    }
+    
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+// シーン間のクロスフェードの進行状態
+#[derive(Debug, Clone)]
+pub struct SceneFade {
+    pub from: PatchState,
+    pub to: PatchState,
+    pub elapsed: f32,  // 秒
+    pub duration: f32, // 秒
+}
+
+impl SceneFade {
+    pub fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            1.0
+        } else {
+            (self.elapsed / self.duration).min(1.0)
+        }
+    }
+}
@@ -0,0 +1,50 @@
+// シーンの保存・呼び出しとクロスフェードのテスト
+use crate::synth::Synthesizer;
+
+// 低い音の加算合成だけを鳴らし、明るさ・カットオフ・音量・倍音が異なる 2 つのシーンを保存する
+fn synth_with_two_scenes() -> Synthesizer {
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).build();
+    synth.set_blend(0.0);
+    synth.set_harmonic_amplitude(1, 0.5);
+    synth.set_brightness(0.3);
+    synth.set_cutoff(0.4);
+    synth.set_volume(0.5);
+    synth.store_scene(0);
+    synth.set_harmonic_amplitude(1, 0.1);
+    synth.set_harmonic_amplitude(2, 0.4);
+    synth.set_brightness(0.7);
+    synth.set_cutoff(0.8);
+    synth.set_volume(1.0);
+    synth.store_scene(1);
+    synth.recall_scene(0, 0.0);
+    synth
+}
+
+// クロスフェード中の出力は、パラメータを更新するブロックの境目でも段差なくつながる
+#[test]
+fn scene_fade_is_continuous_across_control_blocks() {
+    let mut synth = synth_with_two_scenes();
+    synth.note_on(45, 0.8);
+    let mut warmup = vec![[0.0; 2]; 4800];
+    synth.render_block(&mut warmup);
+    
+    synth.recall_scene(1, 0.2);
+    let mut frames = vec![[0.0; 2]; 12000];
+    synth.render_block(&mut frames);
+    assert!(synth.scene_fade().is_none());
+    
+    // 隣り合うサンプルの差が、前後の差より飛び抜けて大きいところ（段差）がない
+    let signal: Vec<f32> = frames.iter().map(|frame| frame[0]).collect();
+    let peak = signal.iter().fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+    let steps: Vec<f32> = signal.windows(2).map(|pair| (pair[1] - pair[0]).abs()).collect();
+    for (index, window) in steps.windows(3).enumerate() {
+        let neighbours = window[0].max(window[2]);
+        assert!(window[1] <= neighbours * 2.0 + peak * 1e-3, "step of {} at sample {} (neighbours {})", window[1], index + 2, neighbours);
+    }
+    
+    // フェードの後はシーン 1 と同じ状態
+    let patch = synth.patch();
+    assert_eq!(patch.cutoff, 0.8);
+    assert_eq!(patch.volume, 1.0);
+    assert_eq!(patch.harmonics[2].amplitude, 0.4);
+}
@@ -0,0 +1,287 @@
+use crate::event::{Event, DEFAULT_RELEASE_VELOCITY};
+use crate::random::Rng;
+
+// ループ回数による発音条件
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TrigCondition {
+    #[default]
+    Always,
+    Every { cycle: u32, offset: u32 }, // loop % cycle == offset のループだけ鳴らす
+}
+
+impl TrigCondition {
+    pub fn matches(&self, loop_index: u64) -> bool {
+        match *self {
+            TrigCondition::Always => true,
+            TrigCondition::Every { cycle, offset } => {
+                let cycle = cycle.max(1) as u64;
+                loop_index % cycle == offset as u64 % cycle
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    pub note: Option<u8>, // None = 休符
+    pub velocity: f32,
+    pub gate: f32,        // ステップ長（ラチェット時は1打分）に対する発音の長さ 0.0-1.0
+    pub probability: f32, // 発音する確率 0.0-1.0
+    pub ratchet: u8,      // ステップ内の連打数
+    pub condition: TrigCondition,
+}
+
+impl Default for Step {
+    fn default() -> Self {
+        Self {
+            note: None,
+            velocity: 0.8,
+            gate: 0.5,
+            probability: 1.0,
+            ratchet: 1,
+            condition: TrigCondition::Always,
+        }
+    }
+}
+
+// トラックごとに長さと分割数が独立しているので、16ステップ対12ステップのようなポリリズムが組める
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub steps: Vec<Step>,
+    pub division: u32, // 1拍あたりのステップ数（4 = 16分音符, 3 = 8分3連）
+    pub muted: bool,
+    position: f64,             // 再生開始からの経過ステップ数
+    current_step: Option<u64>, // 再生中のステップの通し番号
+    current_hit: Option<u32>,  // 再生中のラチェットの打数
+    triggered: bool,           // 現在のステップが条件・確率を満たしたか
+    sounding: Option<u8>,
+}
+
+impl Track {
+    pub fn new(length: usize, division: u32) -> Self {
+        Self {
+            steps: vec![Step::default(); length.max(1)],
+            division: division.max(1),
+            muted: false,
+            position: 0.0,
+            current_step: None,
+            current_hit: None,
+            triggered: false,
+            sounding: None,
+        }
+    }
+    
+    pub fn set_step(&mut self, index: usize, step: Step) {
+        if let Some(target) = self.steps.get_mut(index) {
+            *target = step;
+        }
+    }
+    
+    pub fn step_mut(&mut self, index: usize) -> Option<&mut Step> {
+        self.steps.get_mut(index)
+    }
+    
+    pub fn set_length(&mut self, length: usize) {
+        self.steps.resize(length.max(1), Step::default());
+    }
+    
+    // 再生中のステップ位置
+    pub fn playhead(&self) -> usize {
+        self.current_step.map_or(0, |step| (step % self.steps.len() as u64) as usize)
+    }
+    
+    fn reset(&mut self) {
+        self.position = 0.0;
+        self.current_step = None;
+        self.current_hit = None;
+        self.triggered = false;
+    }
+    
+    // 発音したら true を返す
+    fn advance(&mut self, beats: f64, rng: &mut Rng, events: &mut Vec<Event>) -> bool {
+        let length = self.steps.len() as u64;
+        let step_number = self.position.floor() as u64;
+        let step = self.steps[(step_number % length) as usize];
+        
+        if self.current_step != Some(step_number) {
+            self.current_step = Some(step_number);
+            self.current_hit = None;
+            if let Some(note) = self.sounding.take() {
+                events.push(Event::NoteOff { note, velocity: DEFAULT_RELEASE_VELOCITY });
+            }
+            self.triggered = !self.muted
+                && step.note.is_some()
+                && step.condition.matches(step_number / length)
+                && rng.next_f32() < step.probability;
+        }
+        
+        let ratchet = step.ratchet.max(1) as f64;
+        let hit_position = self.position.fract() * ratchet;
+        let hit = hit_position.floor() as u32;
+        let mut sounded = false;
+        if self.triggered && self.current_hit != Some(hit) {
+            self.current_hit = Some(hit);
+            if let Some(note) = self.sounding.take() {
+                events.push(Event::NoteOff { note, velocity: DEFAULT_RELEASE_VELOCITY });
+            }
+            if let Some(note) = step.note {
+                events.push(Event::NoteOn { note, velocity: step.velocity });
+                self.sounding = Some(note);
+                sounded = true;
+            }
+        } else if hit_position.fract() as f32 >= step.gate {
+            if let Some(note) = self.sounding.take() {
+                events.push(Event::NoteOff { note, velocity: DEFAULT_RELEASE_VELOCITY });
+            }
+        }
+        
+        self.position += beats * self.division as f64;
+        sounded
+    }
+    
+    fn stop(&mut self, events: &mut Vec<Event>) {
+        if let Some(note) = self.sounding.take() {
+            events.push(Event::NoteOff { note, velocity: DEFAULT_RELEASE_VELOCITY });
+        }
+        self.reset();
+    }
+}
+
+// マルチトラックのステップシーケンサー。シンセサイザー内でサンプル単位に進める。
+// ボイスはノート番号ごとなので、複数のトラックが同じノートを鳴らしているあいだは、
+// 最後のトラックが離すまでノートオフを送らない
+#[derive(Debug, Clone)]
+pub struct Sequencer {
+    tracks: Vec<Track>,
+    playing: bool,
+    rng: Rng, // 確率ステップ用
+    hits: Vec<bool>, // 直前の advance で各トラックが発音したか
+    beat: f64,       // 再生開始からの拍数
+    holders: [u8; 128], // ノートごとに鳴らしているトラックの数
+}
+
+impl Default for Sequencer {
+    fn default() -> Self {
+        Self {
+            tracks: Vec::new(),
+            playing: false,
+            rng: Rng::from_entropy(),
+            hits: Vec::new(),
+            beat: 0.0,
+            holders: [0; 128],
+        }
+    }
+}
+
+impl Sequencer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    // 確率ステップの結果を再現可能にする
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+    
+    pub fn add_track(&mut self, track: Track) -> usize {
+        self.tracks.push(track);
+        self.tracks.len() - 1
+    }
+    
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+    
+    pub fn track_mut(&mut self, index: usize) -> Option<&mut Track> {
+        self.tracks.get_mut(index)
+    }
+    
+    pub fn clear(&mut self, events: &mut Vec<Event>) {
+        self.stop(events);
+        self.tracks.clear();
+    }
+    
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+    
+    pub fn play(&mut self) {
+        for track in &mut self.tracks {
+            track.reset();
+        }
+        self.beat = 0.0;
+        self.playing = true;
+    }
+    
+    // 再生位置（拍）
+    pub fn beat(&self) -> f64 {
+        self.beat
+    }
+    
+    // 再生位置を拍で移す（ホストのトランスポートに合わせる）。移った先のステップが変われば、そのステップから鳴らす
+    pub fn locate(&mut self, beat: f64) {
+        self.beat = beat.max(0.0);
+        for track in &mut self.tracks {
+            track.position = self.beat * track.division as f64;
+        }
+    }
+    
+    // 位置を戻さずに再開する（MIDI の Continue）
+    pub fn resume(&mut self) {
+        self.playing = true;
+    }
+    
+    pub fn stop(&mut self, events: &mut Vec<Event>) {
+        let start = events.len();
+        for track in &mut self.tracks {
+            track.stop(events);
+        }
+        self.merge_note_offs(events, start);
+        self.playing = false;
+    }
+    
+    // 1サンプル分進め、発生したイベントを events に追加する
+    pub fn advance(&mut self, sample_rate: f32, tempo_bpm: f32, events: &mut Vec<Event>) {
+        if !self.playing {
+            return;
+        }
+        let beats = tempo_bpm as f64 / 60.0 / sample_rate as f64;
+        self.beat += beats;
+        self.hits.resize(self.tracks.len(), false);
+        let start = events.len();
+        for (track, hit) in self.tracks.iter_mut().zip(&mut self.hits) {
+            *hit = track.advance(beats, &mut self.rng, events);
+        }
+        self.merge_note_offs(events, start);
+    }
+    
+    // start 以降に追加したイベントで鳴らしているトラックの数を数え、まだ他のトラックが鳴らしているノートのノートオフを取り除く
+    fn merge_note_offs(&mut self, events: &mut Vec<Event>, start: usize) {
+        let mut kept = start;
+        for index in start..events.len() {
+            let keep = match events[index] {
+                Event::NoteOn { note, .. } => {
+                    let holders = &mut self.holders[note as usize & 0x7F];
+                    *holders = holders.saturating_add(1);
+                    true
+                }
+                Event::NoteOff { note, .. } => {
+                    let holders = &mut self.holders[note as usize & 0x7F];
+                    *holders = holders.saturating_sub(1);
+                    *holders == 0
+                }
+                _ => true,
+            };
+            if keep {
+                events.swap(kept, index);
+                kept += 1;
+            }
+        }
+        events.truncate(kept);
+    }
+    
+    // 直前の advance でトラックが発音したか（サイドチェインのきっかけ）
+    pub fn hit(&self, track: usize) -> bool {
+        self.hits.get(track).copied().unwrap_or(false)
+    }
+}
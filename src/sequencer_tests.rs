@@ -0,0 +1,91 @@
+// ステップシーケンサーのテスト
+use crate::event::Event;
+use crate::sequencer::{Sequencer, Step, Track};
+use crate::synth::Synthesizer;
+
+const SAMPLE_RATE: f32 = 1000.0;
+const TEMPO: f32 = 120.0; // 1拍 = 500 サンプル
+
+// samples サンプル進め、各サンプルでノートが鳴っているか（シンセサイザーと同じくノート番号ごとに1つ）を返す
+fn sounding(sequencer: &mut Sequencer, note: u8, samples: usize) -> Vec<bool> {
+    let mut events = Vec::new();
+    let mut on = false;
+    (0..samples)
+        .map(|_| {
+            sequencer.advance(SAMPLE_RATE, TEMPO, &mut events);
+            for event in events.drain(..) {
+                match event {
+                    Event::NoteOn { note: n, .. } if n == note => on = true,
+                    Event::NoteOff { note: n, .. } if n == note => on = false,
+                    _ => {}
+                }
+            }
+            on
+        })
+        .collect()
+}
+
+fn step(note: u8, gate: f32) -> Step {
+    Step { note: Some(note), gate, ..Step::default() }
+}
+
+// 同じノートを短く鳴らすトラックのノートオフで、長く鳴らしている別のトラックのノートが切れない
+#[test]
+fn tracks_sharing_a_note_release_it_after_the_last_one() {
+    let mut sequencer = Sequencer::new();
+    let mut long = Track::new(1, 1);
+    long.set_step(0, step(60, 0.9)); // 0-450 サンプル
+    let mut short = Track::new(4, 4);
+    short.set_step(0, step(60, 0.5)); // 0-62 サンプル
+    sequencer.add_track(long);
+    sequencer.add_track(short);
+    sequencer.play();
+    
+    let on = sounding(&mut sequencer, 60, 500);
+    assert!(on[..440].iter().all(|on| *on), "cut off at {:?}", on.iter().position(|on| !on));
+    assert!(!on[460]);
+    
+    // 止めたときは両方のトラックを離してからノートオフを1回だけ送る
+    let mut events = Vec::new();
+    sequencer.advance(SAMPLE_RATE, TEMPO, &mut events);
+    sequencer.stop(&mut events);
+    let offs = events.iter().filter(|event| matches!(event, Event::NoteOff { note: 60, .. })).count();
+    assert_eq!(offs, 1, "{:?}", events);
+}
+
+// シーケンサーのトラックが発音した位置でマスターが下がり、テンポに合わせた長さで戻る。
+// 値はモジュレーションのソースとしてもボイスに届く
+#[test]
+fn sequencer_track_ducks_master_in_time() {
+    use crate::duck::DuckTrigger;
+    use crate::modulation::{ModDestination, ModRouting, ModSource};
+    
+    let sample_rate = 22050.0;
+    let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+    synth.set_tempo(120.0);
+    let mut track = Track::new(4, 1);
+    track.set_step(0, Step { note: Some(36), ..Step::default() });
+    synth.sequencer_mut().add_track(track);
+    let ducker = synth.ducker_mut();
+    ducker.set_trigger(Some(DuckTrigger::Track(0)));
+    ducker.set_depth(1.0);
+    ducker.set_attack(0.0);
+    ducker.set_release_beats(0.25); // 120 BPM で 0.125 秒
+    synth.add_mod_routing(ModRouting::new(ModSource::Duck, ModDestination::Pitch, 1.0));
+    
+    synth.note_on(57, 0.8);
+    let peak = |synth: &mut Synthesizer, seconds: f32| {
+        (0..(seconds * sample_rate) as usize)
+            .map(|_| synth.next_frame()[0].abs())
+            .fold(0.0, f32::max)
+    };
+    let settled = peak(&mut synth, 0.3);
+    synth.play_sequencer();
+    let ducked = peak(&mut synth, 0.005);
+    assert!(synth.ducker().level() > 0.9);
+    assert!(synth.voice_info().iter().any(|voice| voice.modulation.pitch > 0.9));
+    assert!(ducked < settled * 0.1, "ducked {} vs {}", ducked, settled);
+    peak(&mut synth, 0.15);
+    assert_eq!(synth.ducker().level(), 0.0);
+    assert!(peak(&mut synth, 0.1) > settled * 0.5);
+}
@@ -0,0 +1,268 @@
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::handle::SynthHandle;
+use crate::layers::{Layer, LayerFilter, LayerFilters};
+use crate::patch::{format_version, PatchState};
+use crate::sequencer::{Step, Track, TrigCondition};
+use crate::synth::Synthesizer;
+use crate::velocity::VelocityCurve;
+
+// セッション形式の版と先頭行（音色の部分はパッチ差分の版で移行する）
+pub const SESSION_VERSION: u32 = 1;
+pub const SESSION_HEADER: &str = "# synthesizer session v1";
+const SESSION_HEADER_PREFIX: &str = "# synthesizer session v";
+pub const DEFAULT_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+const AUTOSAVE_POLL: Duration = Duration::from_millis(200); // 停止の確認間隔
+
+// 作業の状態のスナップショット（音色・シーケンサーのパターン・演奏の設定）。
+// チューニングやシーンなど、ファイルや機器に由来する状態は含めない
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub patch: PatchState,
+    pub tracks: Vec<Track>,
+    pub tempo_bpm: f32,
+    pub velocity_curve: VelocityCurve,
+    pub metronome: bool,
+    pub beats_per_bar: u32,
+    pub layer_filters: LayerFilters,
+}
+
+impl Session {
+    pub fn capture(synth: &Synthesizer) -> Self {
+        Self {
+            patch: synth.patch().clone(),
+            tracks: synth.sequencer().tracks().to_vec(),
+            tempo_bpm: synth.tempo(),
+            velocity_curve: synth.velocity_curve(),
+            metronome: synth.metronome().is_enabled(),
+            beats_per_bar: synth.metronome().beats_per_bar(),
+            layer_filters: *synth.layer_filters(),
+        }
+    }
+    
+    // シーケンサーは止めてからパターンを置き換える
+    pub fn apply(&self, synth: &mut Synthesizer) {
+        synth.set_patch(self.patch.clone());
+        synth.set_tempo(self.tempo_bpm);
+        synth.set_velocity_curve(self.velocity_curve);
+        synth.metronome_mut().set_enabled(self.metronome);
+        synth.metronome_mut().set_beats_per_bar(self.beats_per_bar);
+        for layer in Layer::ALL {
+            synth.set_layer_filter(layer, self.layer_filters.get(layer));
+        }
+        synth.clear_sequencer();
+        for track in &self.tracks {
+            let mut restored = Track::new(track.steps.len(), track.division);
+            restored.steps.clone_from(&track.steps);
+            restored.muted = track.muted;
+            synth.sequencer_mut().add_track(restored);
+        }
+    }
+    
+    // 設定とトラックを1行ずつ書き、最後の patch 行の後にパッチ差分を続ける
+    pub fn to_text(&self) -> Result<String, String> {
+        let curve = self.velocity_curve;
+        let mut text = format!(
+            "{}\ntempo {}\nvelocity {} {} {}\nmetronome {} {}\n",
+            SESSION_HEADER, self.tempo_bpm, curve.low, curve.high, curve.exponent,
+            if self.metronome { "on" } else { "off" }, self.beats_per_bar,
+        );
+        // 全域のままのレイヤーは書かない
+        for layer in Layer::ALL {
+            let filter = self.layer_filters.get(layer);
+            if filter != LayerFilter::ALL {
+                text.push_str(&format!("layer {}{}\n", layer, filter));
+            }
+        }
+        for track in &self.tracks {
+            text.push_str(&format!("track {} {}{}\n", track.steps.len(), track.division, if track.muted { " muted" } else { "" }));
+            // 既定のままのステップは書かない
+            for (i, step) in track.steps.iter().enumerate().filter(|(_, step)| **step != Step::default()) {
+                let note = step.note.map_or("-".to_string(), |note| note.to_string());
+                text.push_str(&format!(
+                    "step {} {} {} {} {} {}",
+                    i + 1, note, step.velocity, step.gate, step.probability, step.ratchet,
+                ));
+                if let TrigCondition::Every { cycle, offset } = step.condition {
+                    text.push_str(&format!(" every {} {}", cycle, offset));
+                }
+                text.push('\n');
+            }
+        }
+        text.push_str("patch\n");
+        text.push_str(&self.patch.to_delta("init")?);
+        Ok(text)
+    }
+    
+    // 未知の行は読み飛ばし、警告として返す
+    pub fn from_text(text: &str) -> Result<(Session, Vec<String>), String> {
+        let (settings, delta) = match text.split_once("\npatch\n") {
+            Some((settings, delta)) => (settings, delta),
+            None => return Err("session has no patch section".to_string()),
+        };
+        let version = format_version(settings, SESSION_HEADER_PREFIX)?;
+        if version > SESSION_VERSION {
+            return Err(format!("unsupported session version: v{} (this build reads up to v{})", version, SESSION_VERSION));
+        }
+        let (patch, mut warnings) = PatchState::from_delta(delta)?;
+        let mut session = Session {
+            patch,
+            tracks: Vec::new(),
+            tempo_bpm: 120.0,
+            velocity_curve: VelocityCurve::default(),
+            metronome: false,
+            beats_per_bar: 4,
+            layer_filters: LayerFilters::default(),
+        };
+        for line in settings.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            if let Err(message) = session.apply_line(line) {
+                warnings.push(format!("{} ({})", message, line));
+            }
+        }
+        Ok((session, warnings))
+    }
+    
+    fn apply_line(&mut self, line: &str) -> Result<(), String> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let number = |value: &str| value.parse::<f32>().map_err(|_| format!("invalid number: {}", value));
+        let integer = |value: &str| value.parse::<u32>().map_err(|_| format!("invalid integer: {}", value));
+        
+        match parts.as_slice() {
+            ["tempo", tempo] => self.tempo_bpm = number(tempo)?,
+            ["velocity", low, high, exponent] => {
+                self.velocity_curve = VelocityCurve::new(number(low)?, number(high)?, number(exponent)?);
+            }
+            ["metronome", enabled, beats] => {
+                self.metronome = *enabled == "on";
+                self.beats_per_bar = integer(beats)?.max(1);
+            }
+            ["layer", layer, filter @ ..] => {
+                let layer: Layer = layer.parse()?;
+                self.layer_filters.set(layer, LayerFilter::parse(filter)?);
+            }
+            ["track", length, division, rest @ ..] => {
+                let mut track = Track::new(integer(length)? as usize, integer(division)?);
+                track.muted = rest == ["muted"];
+                self.tracks.push(track);
+            }
+            ["step", index, note, velocity, gate, probability, ratchet, condition @ ..] => {
+                let track = self.tracks.last_mut().ok_or("step before any track")?;
+                let condition = match condition {
+                    [] => TrigCondition::Always,
+                    ["every", cycle, offset] => TrigCondition::Every { cycle: integer(cycle)?, offset: integer(offset)? },
+                    _ => return Err("unknown step condition".to_string()),
+                };
+                let step = Step {
+                    note: match *note {
+                        "-" => None,
+                        note => Some(note.parse().map_err(|_| format!("invalid note: {}", note))?),
+                    },
+                    velocity: number(velocity)?,
+                    gate: number(gate)?,
+                    probability: number(probability)?,
+                    ratchet: integer(ratchet)?.clamp(1, u8::MAX as u32) as u8,
+                    condition,
+                };
+                let index = (integer(index)? as usize).checked_sub(1).filter(|i| *i < track.steps.len());
+                track.set_step(index.ok_or("step out of range")?, step);
+            }
+            _ => return Err("unknown session line".to_string()),
+        }
+        Ok(())
+    }
+    
+    // 一時ファイルに書いてから置き換えるので、書き込み中に落ちても前の内容が残る
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        write_atomically(path.as_ref(), &self.to_text()?)?;
+        Ok(())
+    }
+    
+    pub fn load(path: impl AsRef<Path>) -> Result<(Session, Vec<String>), Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        Ok(Self::from_text(&text)?)
+    }
+}
+
+// 停電に備えて、置き換える前に一時ファイルの内容をディスクまで書き出す
+fn write_atomically(path: &Path, text: &str) -> std::io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    let mut file = fs::File::create(&temporary)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+// 一定間隔でセッションを自動保存する（変化が無ければ書かない）。
+// オーディオスレッドではなく専用のスレッドで書き、Synthesizer はスナップショットを取る間だけ借りる
+pub struct Autosave {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Autosave {
+    pub fn start(path: impl AsRef<Path>, synth: SynthHandle, interval: Duration) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (path, stop) = (path.clone(), Arc::clone(&stop));
+            std::thread::spawn(move || {
+                let mut saved: Option<String> = None;
+                let mut captured = None;
+                let mut waited = interval;
+                let telemetry = synth.telemetry();
+                while !stop.load(Ordering::Relaxed) {
+                    // 借りている間のコールバックは無音になるので、前回から何か変わったときだけ借りる
+                    if waited >= interval && captured != Some(telemetry.edits()) {
+                        waited = Duration::ZERO;
+                        let Some(guard) = synth.borrow() else { continue };
+                        // 借りたことも数えられるので、借りてから読む
+                        captured = Some(telemetry.edits());
+                        let session = Session::capture(&guard);
+                        drop(guard);
+                        if let Ok(text) = session.to_text() {
+                            if saved.as_ref() != Some(&text) && write_atomically(&path, &text).is_ok() {
+                                saved = Some(text);
+                            }
+                        }
+                    }
+                    std::thread::sleep(AUTOSAVE_POLL);
+                    waited += AUTOSAVE_POLL;
+                }
+            })
+        };
+        Self { path, stop, thread: Some(thread) }
+    }
+    
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    // 正常に終了するときに呼ぶ。自動保存を止めてファイルを消すので、次の起動で復元を勧めない
+    pub fn finish(mut self) {
+        self.halt();
+        let _ = fs::remove_file(&self.path);
+    }
+    
+    fn halt(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Autosave {
+    fn drop(&mut self) {
+        self.halt();
+    }
+}
@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::path::Path;
+
+use crate::midi::MidiMessage;
+
+const DEFAULT_TEMPO: u32 = 500_000; // 四分音符あたりのマイクロ秒（120 BPM）
+const META_TEMPO: u8 = 0x51;
+
+// スタンダード MIDI ファイルの1つのメッセージと、ファイル先頭からの時刻（秒）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimedMessage {
+    pub seconds: f32,
+    pub message: MidiMessage,
+}
+
+// 全トラックを時刻順にまとめたスタンダード MIDI ファイル（フォーマット 0/1）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MidiFile {
+    pub events: Vec<TimedMessage>,
+}
+
+impl MidiFile {
+    // 最後のメッセージの時刻（秒）
+    pub fn duration(&self) -> f32 {
+        self.events.last().map_or(0.0, |event| event.seconds)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TrackEvent {
+    Tempo(u32),
+    Message(MidiMessage),
+}
+
+// テンポの変更（メタイベント）はどのトラックにあっても全体に効く。
+// システムエクスクルーシブと他のメタイベントは読み飛ばす
+pub fn parse(data: &[u8]) -> Result<MidiFile, String> {
+    let mut reader = Reader { data, position: 0 };
+    if reader.take(4)? != b"MThd" {
+        return Err("not a standard MIDI file".to_string());
+    }
+    let length = reader.u32()? as usize;
+    let header = reader.take(length)?;
+    let [_, _, _, _, division_high, division_low, ..] = *header else {
+        return Err("MIDI file header is too short".to_string());
+    };
+    let division = u16::from_be_bytes([division_high, division_low]);
+    
+    // (tick, トラック内の順序を保つための通し番号, イベント)
+    let mut events: Vec<(u64, usize, TrackEvent)> = Vec::new();
+    while reader.position < data.len() {
+        let id = reader.take(4)?;
+        let length = reader.u32()? as usize;
+        let chunk = reader.take(length)?;
+        if id == b"MTrk" {
+            parse_track(chunk, &mut events)?;
+        }
+    }
+    events.sort_by_key(|(tick, order, _)| (*tick, *order));
+    
+    // tick を秒に直す（SMPTE 形式では1秒あたりの tick 数が固定でテンポに依らない）
+    let smpte = division & 0x8000 != 0;
+    let quarter_seconds_per_tick = |tempo: u32| tempo as f64 / 1e6 / division.max(1) as f64;
+    let mut seconds_per_tick = if smpte {
+        let frames_per_second = -((division >> 8) as i8) as f64;
+        let ticks_per_frame = (division & 0xFF) as f64;
+        1.0 / (frames_per_second * ticks_per_frame).max(1.0)
+    } else {
+        quarter_seconds_per_tick(DEFAULT_TEMPO)
+    };
+    let (mut last_tick, mut seconds) = (0, 0.0f64);
+    let mut file = MidiFile::default();
+    for (tick, _, event) in events {
+        seconds += (tick - last_tick) as f64 * seconds_per_tick;
+        last_tick = tick;
+        match event {
+            TrackEvent::Tempo(tempo) if !smpte => seconds_per_tick = quarter_seconds_per_tick(tempo),
+            TrackEvent::Tempo(_) => {}
+            TrackEvent::Message(message) => file.events.push(TimedMessage { seconds: seconds as f32, message }),
+        }
+    }
+    Ok(file)
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<MidiFile, Box<dyn Error>> {
+    let data = std::fs::read(path)?;
+    Ok(parse(&data)?)
+}
+
+fn parse_track(chunk: &[u8], events: &mut Vec<(u64, usize, TrackEvent)>) -> Result<(), String> {
+    let mut reader = Reader { data: chunk, position: 0 };
+    let (mut tick, mut running_status) = (0u64, None);
+    while reader.position < chunk.len() {
+        tick += reader.variable_length()? as u64;
+        let order = events.len();
+        let status = match reader.peek()? {
+            byte if byte & 0x80 != 0 => {
+                reader.position += 1;
+                byte
+            }
+            _ => running_status.ok_or("MIDI data without a status byte")?,
+        };
+        match status {
+            0xFF => {
+                let kind = reader.take(1)?[0];
+                let length = reader.variable_length()? as usize;
+                let data = reader.take(length)?;
+                if let (META_TEMPO, [a, b, c]) = (kind, data) {
+                    events.push((tick, order, TrackEvent::Tempo(u32::from_be_bytes([0, *a, *b, *c]))));
+                }
+                running_status = None;
+            }
+            0xF0 | 0xF7 => {
+                let length = reader.variable_length()? as usize;
+                reader.take(length)?;
+                running_status = None;
+            }
+            _ => {
+                running_status = Some(status);
+                let length = match status & 0xF0 {
+                    0xC0 | 0xD0 => 1,
+                    _ => 2,
+                };
+                let channel = status & 0x0F;
+                let data = reader.take(length)?;
+                let (first, second) = (data[0] & 0x7F, data.get(1).map_or(0, |byte| byte & 0x7F));
+                let message = match status & 0xF0 {
+                    0x80 => MidiMessage::NoteOff { channel, note: first, velocity: second },
+                    0x90 => MidiMessage::NoteOn { channel, note: first, velocity: second },
+                    0xA0 => MidiMessage::PolyPressure { channel, note: first, value: second },
+                    0xB0 => MidiMessage::ControlChange { channel, controller: first, value: second },
+                    0xC0 => MidiMessage::ProgramChange { channel, program: first },
+                    0xD0 => MidiMessage::ChannelPressure { channel, value: first },
+                    _ => MidiMessage::PitchBend { channel, value: first as u16 | (second as u16) << 7 },
+                };
+                events.push((tick, order, TrackEvent::Message(message)));
+            }
+        }
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let bytes = self.data.get(self.position..self.position + length).ok_or("unexpected end of MIDI file")?;
+        self.position += length;
+        Ok(bytes)
+    }
+    
+    fn peek(&self) -> Result<u8, String> {
+        self.data.get(self.position).copied().ok_or_else(|| "unexpected end of MIDI file".to_string())
+    }
+    
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    
+    // 可変長数値（7 ビットずつ、最大 4 バイト）
+    fn variable_length(&mut self) -> Result<u32, String> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.take(1)?[0];
+            value = value << 7 | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("invalid variable-length value in MIDI file".to_string())
+    }
+}
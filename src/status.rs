@@ -0,0 +1,148 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use synthesizer::{detect_chord, Chord, SynthHandle, Telemetry};
+
+const METER_WIDTH: usize = 8;
+const METER_FLOOR_DB: f32 = -48.0;
+
+// ステータス行に表示する値
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatusSnapshot {
+    pub meter: [f32; 2],
+    pub playing: bool,
+    pub beats: f64, // 聞こえている拍位置
+    pub beats_per_bar: u32,
+    pub tempo_bpm: f32,
+    pub voices: usize,
+    pub dsp_load: f32,
+    pub chord: Option<Chord>, // 押さえているノートから判定した和音
+}
+
+impl StatusSnapshot {
+    // Synthesizer を借りずに、最後に書かれた状態を読む
+    pub fn capture(telemetry: &Telemetry) -> Self {
+        Self {
+            meter: telemetry.output_meter(),
+            playing: telemetry.is_sequencer_playing(),
+            beats: telemetry.audible_beats(),
+            beats_per_bar: telemetry.beats_per_bar(),
+            tempo_bpm: telemetry.tempo(),
+            voices: telemetry.voice_count(),
+            dsp_load: telemetry.dsp_load(),
+            chord: detect_chord(&telemetry.active_notes()),
+        }
+    }
+    
+    pub fn format(&self) -> String {
+        let transport = if self.playing {
+            let beat = self.beats.floor() as u64;
+            let beats_per_bar = self.beats_per_bar.max(1) as u64;
+            format!("> {:>3}.{}", beat / beats_per_bar + 1, beat % beats_per_bar + 1)
+        } else {
+            "- stop".to_string()
+        };
+        let chord = self.chord.map(|chord| format!(" | {}", chord)).unwrap_or_default();
+        format!(
+            "L {} R {} | {} {:.0} BPM | {} voices | DSP {:>3.0}%{}",
+            meter_bar(self.meter[0]),
+            meter_bar(self.meter[1]),
+            transport,
+            self.tempo_bpm,
+            self.voices,
+            self.dsp_load * 100.0,
+            chord
+        )
+    }
+}
+
+fn meter_bar(peak: f32) -> String {
+    let db = 20.0 * peak.max(1e-6).log10();
+    let filled = (((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0) * METER_WIDTH as f32).round() as usize;
+    let clip = if peak > 1.0 { '!' } else { ' ' };
+    format!("[{}{}]{}", "#".repeat(filled), ".".repeat(METER_WIDTH - filled), clip)
+}
+
+// 端末の最下行を入力のスクロール範囲から外し、そこへ一定間隔でステータスを描画する。
+// 入力待ち（rustyline の読み取り）の間も別スレッドで更新が続く。
+pub struct StatusLine {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StatusLine {
+    // 端末でない場合や大きさが分からない場合は None
+    pub fn start(synth: &SynthHandle, interval: Duration) -> Option<Self> {
+        if !io::stdout().is_terminal() {
+            return None;
+        }
+        let (rows, _) = terminal_size()?;
+        // 最下行を空けてからスクロール範囲を設定する
+        print!("\n\x1b[1A");
+        set_scroll_region(rows);
+        
+        let stop = Arc::new(AtomicBool::new(false));
+        let telemetry = synth.telemetry();
+        let thread = {
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || {
+                let mut rows = rows;
+                while !stop.load(Ordering::Relaxed) {
+                    let snapshot = StatusSnapshot::capture(&telemetry);
+                    if let Some((current_rows, columns)) = terminal_size() {
+                        if current_rows != rows {
+                            rows = current_rows;
+                            set_scroll_region(rows);
+                        }
+                        draw(rows, columns, &snapshot.format());
+                    }
+                    std::thread::sleep(interval);
+                }
+            })
+        };
+        Some(Self { stop, thread: Some(thread) })
+    }
+}
+
+impl Drop for StatusLine {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        if let Some((rows, _)) = terminal_size() {
+            draw(rows, 0, "");
+        }
+        print!("\x1b7\x1b[r\x1b8");
+        let _ = io::stdout().flush();
+    }
+}
+
+fn set_scroll_region(rows: u16) {
+    print!("\x1b7\x1b[1;{}r\x1b8", rows - 1);
+    let _ = io::stdout().flush();
+}
+
+// カーソル位置を保存して最下行に描画し、元に戻す
+fn draw(rows: u16, columns: u16, text: &str) {
+    let text: String = text.chars().take(columns as usize).collect();
+    let mut stdout = io::stdout().lock();
+    let _ = write!(stdout, "\x1b7\x1b[{};1H\x1b[2K{}\x1b8", rows, text);
+    let _ = stdout.flush();
+}
+
+#[cfg(unix)]
+fn terminal_size() -> Option<(u16, u16)> {
+    let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    // TIOCGWINSZ は渡した winsize に書き込むだけ
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_row > 2).then_some((size.ws_row, size.ws_col))
+}
+
+#[cfg(not(unix))]
+fn terminal_size() -> Option<(u16, u16)> {
+    None
+}
@@ -0,0 +1,83 @@
+use std::fmt;
+use std::str::FromStr;
+
+// 出力機器のチャンネル配置。シンセサイザー内部は前方と後方のステレオペアでミックスし、
+// 配置に合わせてスピーカーへ割り当てる（ステレオでは後方ペアを作らない）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelLayout {
+    #[default]
+    Stereo,
+    Quad,       // FL FR RL RR
+    Surround51, // FL FR C LFE SL SR（WAVE / cpal の既定の並び）
+}
+
+impl ChannelLayout {
+    // 機器のチャンネル数から決める（3ch 以下はステレオとして扱う）
+    pub fn for_channels(channels: usize) -> Self {
+        match channels {
+            0..=3 => ChannelLayout::Stereo,
+            4 | 5 => ChannelLayout::Quad,
+            _ => ChannelLayout::Surround51,
+        }
+    }
+    
+    pub fn channels(self) -> usize {
+        match self {
+            ChannelLayout::Stereo => 2,
+            ChannelLayout::Quad => 4,
+            ChannelLayout::Surround51 => 6,
+        }
+    }
+    
+    pub fn is_surround(self) -> bool {
+        self != ChannelLayout::Stereo
+    }
+    
+    // 前方・後方のペアから出力チャンネル channel の値を求める。
+    // センターと LFE は使わない（前方の定位はファントムセンターのまま）。配置外のチャンネルは無音。
+    pub fn speaker_sample(self, front: [f32; 2], rear: [f32; 2], channel: usize) -> f32 {
+        match (self, channel) {
+            (_, 0) => front[0],
+            (_, 1) => front[1],
+            (ChannelLayout::Quad, 2) | (ChannelLayout::Surround51, 4) => rear[0],
+            (ChannelLayout::Quad, 3) | (ChannelLayout::Surround51, 5) => rear[1],
+            _ => 0.0,
+        }
+    }
+}
+
+impl fmt::Display for ChannelLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ChannelLayout::Stereo => "stereo",
+            ChannelLayout::Quad => "quad",
+            ChannelLayout::Surround51 => "5.1",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ChannelLayout {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stereo" => Ok(ChannelLayout::Stereo),
+            "quad" => Ok(ChannelLayout::Quad),
+            "5.1" => Ok(ChannelLayout::Surround51),
+            _ => Err(format!("unknown channel layout: {}", s)),
+        }
+    }
+}
+
+// 奥行き（0.0 = 前, 1.0 = 後ろ）の等パワーのゲイン [前, 後]
+pub fn depth_gains(depth: f32) -> [f32; 2] {
+    let angle = depth.clamp(0.0, 1.0) * std::f32::consts::FRAC_PI_2;
+    [angle.cos(), angle.sin()]
+}
+
+// 後方ペアをステレオへ畳み込む（ITU-R BS.775 のサラウンド係数 1/√2）
+pub fn downmix(front: [f32; 2], rear: [f32; 2]) -> [f32; 2] {
+    let gain = std::f32::consts::FRAC_1_SQRT_2;
+    [front[0] + rear[0] * gain, front[1] + rear[1] * gain]
+}
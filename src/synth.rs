@@ -1,13 +1,79 @@
-use crate::engine::{EngineBlender, Harmonic, Operator};
-use std::collections::HashMap;
+use crate::automation::{ParameterRamp, RampCurve};
+use crate::binaural::{BinauralPanner, HrirSet};
+use crate::builder::{StealPolicy, SynthConfig, SynthesizerBuilder, VoiceMode};
+use crate::cc::{CcMap, ALL_NOTES_OFF_CC, ALL_SOUND_OFF_CC, SUSTAIN_PEDAL_CC};
+use crate::command::{command_queue, Command, CommandReceiver, CommandSender};
+use crate::telemetry::Telemetry;
+use crate::effects::{AnalogFloor, AnalogFloorSettings, Crossfeed, Effect, EffectsChain};
+use crate::engine::{EngineBlender, Harmonic, Operator, SpectralFilter, MAX_STACK_DENSITY};
+use crate::event::{Event, ScheduledEvent, DEFAULT_RELEASE_VELOCITY};
+use crate::modulation::{
+    BreakpointEnvelope, Controller, ControllerRamp, Controllers, Lfo, ModCombine, ModCombineModes, ModDestination, ModOffsets,
+    ModRouting, ModSource, VoiceModulation, MOD_BLOCK_SIZE,
+};
+use crate::layers::{Layer, LayerFilter, LayerFilters, LayerMask};
+use crate::log::{event_log, EventLogReader, EventLogWriter, LogEvent};
+use crate::paraphony::Paraphony;
+use crate::params::{self, Params, Smoother, SHARED_PARAMETERS};
+use crate::patch::{PatchMorph, PatchState};
+use crate::program::{ProgramMap, ProgramTarget};
+use crate::analysis::{AnalysisTaps, TapFrame};
+use crate::diagnostics::{TestSignal, TestSignalGenerator};
+use crate::drone::DroneWalk;
+use crate::duck::{DuckTrigger, Ducker};
+use crate::metronome::Metronome;
+use crate::mixer::{fold_down, FoldDownMeter, PanLaw};
+use crate::surround::{depth_gains, downmix, ChannelLayout};
+use crate::parallel::{VoiceWorkers, MAX_PARALLEL_FRAMES};
+use crate::quality::{QualityGovernor, QualityLevel};
+use crate::random::Rng;
+use crate::record::RecordTap;
+use crate::scenes::{SceneBank, SceneFade};
+use crate::sequencer::Sequencer;
+use crate::transport::{HostTransport, MidiClock, TempoSource, HOST_TIMEOUT_SECONDS};
+use crate::tuning::Tuning;
+use crate::velocity::{VelocityCurve, VelocityProfiles};
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 // エンベロープ
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Envelope {
-    pub attack: f32,   // 秒
-    pub decay: f32,    // 秒
+    pub attack: f32,   // 秒（テンポ同期時は拍）
+    pub decay: f32,    // 秒（テンポ同期時は拍）
     pub sustain: f32,  // 0.0-1.0
-    pub release: f32,  // 秒
+    pub release: f32,  // 秒（テンポ同期時は拍）
+    pub tempo_sync: bool, // 時間を拍で指定し、テンポが変わると長さも変わる
+}
+
+impl Envelope {
+    // 時間の単位（秒または拍）から秒への倍率
+    pub fn time_scale(&self, tempo_bpm: f32) -> f32 {
+        if self.tempo_sync {
+            60.0 / tempo_bpm.max(1.0)
+        } else {
+            1.0
+        }
+    }
+    
+    pub fn release_seconds(&self, tempo_bpm: f32) -> f32 {
+        self.release * self.time_scale(tempo_bpm)
+    }
+    
+    // 今のテンポで同じ長さのまま単位を切り替える
+    pub fn with_tempo_sync(self, tempo_sync: bool, tempo_bpm: f32) -> Self {
+        let scale = self.time_scale(tempo_bpm) / Envelope { tempo_sync, ..self }.time_scale(tempo_bpm);
+        Self {
+            attack: self.attack * scale,
+            decay: self.decay * scale,
+            release: self.release * scale,
+            tempo_sync,
+            ..self
+        }
+    }
 }
 
 impl Default for Envelope {
@@ -17,6 +83,7 @@ impl Default for Envelope {
             decay: 0.1,
             sustain: 0.7,
             release: 0.2,
+            tempo_sync: false,
         }
     }
 }
@@ -24,14 +91,17 @@ impl Default for Envelope {
 pub struct EnvelopeGenerator {
     envelope: Envelope,
     sample_rate: f32,
+    tempo_bpm: f32,
+    time_scale: f32, // envelope の時間から秒への倍率（テンポ同期時はテンポから求める）
     current_stage: EnvelopeStage,
     current_time: f32,
     current_value: f32,
+    release_level: f32, // リリースを始めたときの値（ここから 0 へ下げる）
     gate: bool,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum EnvelopeStage {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnvelopeStage {
     Attack,
     Decay,
     Sustain,
@@ -44,15 +114,25 @@ impl EnvelopeGenerator {
         Self {
             envelope: Envelope::default(),
             sample_rate,
+            tempo_bpm: 120.0,
+            time_scale: 1.0,
             current_stage: EnvelopeStage::Idle,
             current_time: 0.0,
             current_value: 0.0,
+            release_level: 0.0,
             gate: false,
         }
     }
     
     pub fn set_envelope(&mut self, envelope: Envelope) {
         self.envelope = envelope;
+        self.time_scale = envelope.time_scale(self.tempo_bpm);
+    }
+    
+    // テンポ同期のエンベロープは進行中の段も新しいテンポでの長さに合わせる
+    pub fn set_tempo(&mut self, tempo_bpm: f32) {
+        self.tempo_bpm = tempo_bpm;
+        self.time_scale = self.envelope.time_scale(tempo_bpm);
     }
     
     pub fn note_on(&mut self) {
@@ -61,31 +141,53 @@ impl EnvelopeGenerator {
         self.current_time = 0.0;
     }
     
+    // アタック・ディケイの途中で離しても、その時点の値から下げる（サステインの値へ飛ばない）
     pub fn note_off(&mut self) {
         self.gate = false;
         self.current_stage = EnvelopeStage::Release;
         self.current_time = 0.0;
+        self.release_level = self.current_value;
+    }
+    
+    pub fn reset(&mut self) {
+        self.gate = false;
+        self.current_stage = EnvelopeStage::Idle;
+        self.current_time = 0.0;
+        self.current_value = 0.0;
+        self.release_level = 0.0;
+    }
+    
+    pub fn stage(&self) -> EnvelopeStage {
+        self.current_stage
+    }
+    
+    pub fn is_gated(&self) -> bool {
+        self.gate
+    }
+    
+    pub fn level(&self) -> f32 {
+        self.current_value
     }
     
     pub fn next_sample(&mut self) -> f32 {
         match self.current_stage {
             EnvelopeStage::Attack => {
                 self.current_time += 1.0 / self.sample_rate;
-                if self.current_time >= self.envelope.attack {
+                if self.current_time >= self.envelope.attack * self.time_scale {
                     self.current_stage = EnvelopeStage::Decay;
                     self.current_time = 0.0;
                     self.current_value = 1.0;
                 } else {
-                    self.current_value = self.current_time / self.envelope.attack;
+                    self.current_value = self.current_time / (self.envelope.attack * self.time_scale);
                 }
             }
             EnvelopeStage::Decay => {
                 self.current_time += 1.0 / self.sample_rate;
-                if self.current_time >= self.envelope.decay {
+                if self.current_time >= self.envelope.decay * self.time_scale {
                     self.current_stage = EnvelopeStage::Sustain;
                     self.current_value = self.envelope.sustain;
                 } else {
-                    let decay_progress = self.current_time / self.envelope.decay;
+                    let decay_progress = self.current_time / (self.envelope.decay * self.time_scale);
                     self.current_value = 1.0 - (1.0 - self.envelope.sustain) * decay_progress;
                 }
             }
@@ -93,17 +195,18 @@ impl EnvelopeGenerator {
                 if !self.gate {
                     self.current_stage = EnvelopeStage::Release;
                     self.current_time = 0.0;
+                    self.release_level = self.envelope.sustain;
                 }
                 self.current_value = self.envelope.sustain;
             }
             EnvelopeStage::Release => {
                 self.current_time += 1.0 / self.sample_rate;
-                if self.current_time >= self.envelope.release {
+                if self.current_time >= self.envelope.release * self.time_scale {
                     self.current_stage = EnvelopeStage::Idle;
                     self.current_value = 0.0;
                 } else {
-                    let release_progress = self.current_time / self.envelope.release;
-                    self.current_value = self.envelope.sustain * (1.0 - release_progress);
+                    let release_progress = self.current_time / (self.envelope.release * self.time_scale);
+                    self.current_value = self.release_level * (1.0 - release_progress);
                 }
             }
             EnvelopeStage::Idle => {
@@ -115,68 +218,229 @@ impl EnvelopeGenerator {
     }
 }
 
-// フィルター
+// カットオフは API 全体で 0.0-1.0 の正規化値で扱い、フィルターに渡す直前にだけ Hz に変換する
+pub const MAX_CUTOFF_HZ: f32 = 20000.0;
+
+pub fn cutoff_to_hz(cutoff: f32) -> f32 {
+    cutoff.clamp(0.0, 1.0) * MAX_CUTOFF_HZ
+}
+
+pub fn hz_to_cutoff(hz: f32) -> f32 {
+    (hz / MAX_CUTOFF_HZ).clamp(0.0, 1.0)
+}
+
+// フィルター（2次のローパス・バイクアッド）
 pub struct LowPassFilter {
     cutoff_frequency: f32,
     resonance: f32,
     sample_rate: f32,
-    buffer: [f32; 2],
+    coefficients: BiquadCoefficients,
+    inputs: [f32; 2],  // x[n-1], x[n-2]
+    outputs: [f32; 2], // y[n-1], y[n-2]
+    drive: f32,          // 0.0-1.0 入力の飽和
+    compensation: bool,  // レゾナンスとドライブによる音量変化を打ち消す
+    output_gain: f32,    // 補正用の出力ゲイン（キャッシュ）
+}
+
+// a0 で正規化した係数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiquadCoefficients {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
 }
 
 impl LowPassFilter {
     pub fn new(sample_rate: f32) -> Self {
-        Self {
+        let mut filter = Self {
             cutoff_frequency: 20000.0,
             resonance: 0.0,
             sample_rate,
-            buffer: [0.0; 2],
-        }
+            coefficients: BiquadCoefficients { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0 },
+            inputs: [0.0; 2],
+            outputs: [0.0; 2],
+            drive: 0.0,
+            compensation: false,
+            output_gain: 1.0,
+        };
+        filter.set_cutoff(filter.cutoff_frequency);
+        filter
     }
     
     pub fn set_cutoff(&mut self, cutoff: f32) {
-        self.cutoff_frequency = cutoff.clamp(20.0, self.sample_rate / 2.0);
+        // ナイキスト周波数ちょうどでは係数が退化して発振するため、少し下で止める
+        self.cutoff_frequency = cutoff.clamp(20.0, self.sample_rate * 0.45);
+        self.update_coefficients();
     }
     
     pub fn set_resonance(&mut self, resonance: f32) {
         self.resonance = resonance.clamp(0.0, 1.0);
+        self.update_coefficients();
     }
     
-    pub fn process(&mut self, input: f32) -> f32 {
-        let freq = self.cutoff_frequency / self.sample_rate;
-        let q = 1.0 + self.resonance * 10.0;
-        
-        let w0 = 2.0 * std::f32::consts::PI * freq;
-        let alpha = w0.sin() / (2.0 * q);
-        
-        let b0 = (1.0 - alpha.cos()) / 2.0;
-        let b1 = 1.0 - alpha.cos();
-        let b2 = (1.0 - alpha.cos()) / 2.0;
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive.clamp(0.0, 1.0);
+        self.update_output_gain();
+    }
+    
+    pub fn set_gain_compensation(&mut self, enabled: bool) {
+        self.compensation = enabled;
+        self.update_output_gain();
+    }
+    
+    pub fn cutoff(&self) -> f32 {
+        self.cutoff_frequency
+    }
+    
+    // カットオフでの振幅（RBJ ローパスでは Q と等しい）
+    pub fn gain_at_cutoff(&self) -> f32 {
+        self.biquad_response(self.cutoff_frequency)
+    }
+    
+    pub fn output_gain(&self) -> f32 {
+        self.output_gain
+    }
+    
+    pub fn q(&self) -> f32 {
+        1.0 + self.resonance * 10.0
+    }
+    
+    pub fn coefficients(&self) -> BiquadCoefficients {
+        self.coefficients
+    }
+    
+    // 係数はカットオフ・レゾナンスの変更時にだけ計算する（RBJ のローパス）
+    fn update_coefficients(&mut self) {
+        let w0 = 2.0 * std::f32::consts::PI * self.cutoff_frequency / self.sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * self.q());
         let a0 = 1.0 + alpha;
-        let a1 = -2.0 * alpha.cos();
-        let a2 = 1.0 - alpha;
-        
-        let output = (b0 * input + b1 * self.buffer[0] + b2 * self.buffer[1] 
-                     - a1 * self.buffer[0] - a2 * self.buffer[1]) / a0;
-        
-        self.buffer[1] = self.buffer[0];
-        self.buffer[0] = output;
+        self.coefficients = BiquadCoefficients {
+            b0: (1.0 - cos) / 2.0 / a0,
+            b1: (1.0 - cos) / a0,
+            b2: (1.0 - cos) / 2.0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+        };
+        self.update_output_gain();
+    }
+    
+    fn drive_gain(&self) -> f32 {
+        1.0 + self.drive * 9.0
+    }
+    
+    // 補正ゲイン: レゾナンスのピークは聴感上の妥協として 1/√(カットオフでのゲイン)、
+    // ドライブは基準レベル 0.5 の正弦波が同じ振幅になるように戻す
+    fn update_output_gain(&mut self) {
+        self.output_gain = if self.compensation {
+            let resonance = 1.0 / self.gain_at_cutoff().max(1.0).sqrt();
+            let drive = if self.drive > 0.0 { 0.5 / (0.5 * self.drive_gain()).tanh() } else { 1.0 };
+            resonance * drive
+        } else {
+            1.0
+        };
+    }
+    
+    // 現在の係数から求めた各周波数（Hz）での振幅特性（倍率、補正ゲインを含みドライブの飽和は含まない）
+    pub fn magnitude_response(&self, freqs: &[f32]) -> Vec<f32> {
+        freqs.iter().map(|&freq| self.biquad_response(freq) * self.output_gain).collect()
+    }
+    
+    fn biquad_response(&self, freq: f32) -> f32 {
+        let c = self.coefficients;
+        let w = 2.0 * std::f32::consts::PI * freq / self.sample_rate;
+        let (sin1, cos1) = w.sin_cos();
+        let (sin2, cos2) = (2.0 * w).sin_cos();
+        let numerator = (c.b0 + c.b1 * cos1 + c.b2 * cos2, -(c.b1 * sin1 + c.b2 * sin2));
+        let denominator = (1.0 + c.a1 * cos1 + c.a2 * cos2, -(c.a1 * sin1 + c.a2 * sin2));
+        numerator.0.hypot(numerator.1) / denominator.0.hypot(denominator.1).max(1e-12)
+    }
+    
+    pub fn reset(&mut self) {
+        self.inputs = [0.0; 2];
+        self.outputs = [0.0; 2];
+    }
+    
+    pub fn process(&mut self, input: f32) -> f32 {
+        let input = if self.drive > 0.0 { (input * self.drive_gain()).tanh() } else { input };
+        let c = self.coefficients;
+        let output = c.b0 * input + c.b1 * self.inputs[0] + c.b2 * self.inputs[1]
+            - c.a1 * self.outputs[0] - c.a2 * self.outputs[1];
         
-        output
+        self.inputs = [input, self.inputs[0]];
+        self.outputs = [output, self.outputs[0]];
+        output * self.output_gain
     }
 }
 
+// ボイス出力の tanh 飽和。フィルターのドライブと同じく、基準レベル 0.5 の正弦波が
+// 同じ振幅になるように戻すので、出力は 0.5 / tanh(0.5 * drive) を超えない
+pub fn saturate(input: f32, amount: f32) -> f32 {
+    if amount <= 0.0 {
+        return input;
+    }
+    let drive = 1.0 + amount * 9.0;
+    (input * drive).tanh() * 0.5 / (0.5 * drive).tanh()
+}
+
+// ボイスの診断情報
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceInfo {
+    pub note: u8,
+    pub frequency: f32,
+    pub stage: EnvelopeStage,
+    pub envelope_level: f32,
+    pub age_samples: u64,  // note_on からのサンプル数
+    pub peak_output: f32,  // note_on 以降の最大出力（絶対値）
+    pub release_velocity: f32, // 最後のノートオフのリリースベロシティ
+    pub glide: f32,            // グライドで残っている音高のずれ（半音）
+    pub modulation: ModOffsets, // ルーティングを合わせた最終的なモジュレーション値
+    pub stack_density: usize,   // 鳴らしているスタックの層数（負荷による制限後）
+}
+
 // 個別の音声（ボイス）
 pub struct Voice {
     engine_blender: EngineBlender,
     envelope: EnvelopeGenerator,
-    filter: LowPassFilter,
+    filters: [LowPassFilter; 2], // 左右チャンネル
     frequency: f32,
+    glide_time: f32,   // 前のボイスの音高から滑る時間（秒、0 で無効）
+    glide_offset: f32, // 残りの音高のずれ（半音）
+    glide_rate: f32,   // 1秒あたりに戻す半音
     velocity: f32,
+    release_velocity: f32, // 最後の note_off のリリースベロシティ（ペダルで保留中は離鍵時の値）
+    volume: f32,
+    target_volume: f32, // set_volume の値。volume は 1 ブロックかけてここへ直線で近づく
+    volume_step: f32,   // 1サンプルあたりの volume の変化量
     note: u8,
     is_active: bool,
     duration: Option<f32>,  // 持続時間（秒）
     elapsed_time: f32,      // 経過時間
     sample_rate: f32,       // サンプルレート
+    age_samples: u64,
+    peak_output: f32,
+    rng: Rng,
+    modulation: VoiceModulation,
+    mod_offsets: ModOffsets,  // 現在適用中のモジュレーション値
+    base_cutoff: f32,         // モジュレーション前の値
+    base_blend: f32,
+    base_brightness: f32,
+    base_spectral_filter: SpectralFilter,
+    saturation: f32,          // 0.0-1.0 出力の飽和（0 で無効）
+    output_gain: f32,         // パッチのトリムと極性から求めた出力のゲイン（反転時は負）
+    pan: f32,                 // -1.0 = 左, 1.0 = 右（モジュレーション前）
+    pan_law: PanLaw,
+    pan_gains: [f32; 2],      // pan と pan_law から求めた左右のゲイン
+    depth: f32,               // 0.0 = 前, 1.0 = 後ろ（モジュレーション前）
+    depth_gains: [f32; 2],    // サラウンド出力での前方・後方のゲイン
+    binaural: Option<BinauralPanner>, // 有効なら pan_gains の代わりに HRIR で定位する
+    layers: LayerMask,        // このノートで鳴らすエンジン（レイヤーの受信フィルターで決まる）
+    last_gain: f32,           // 直前のフレームのエンベロープとゲイン（解析タップ用）
+    last_output: [f32; 2],
+    paraphonic: bool,         // フィルターとエンベロープを掛けずに出し、リリース後も取り除かれるまで鳴らす
+    drone: Option<DroneWalk>, // ドローン中なら倍音の振幅をゆっくりランダムに揺らし、持続時間の指定を無視する
 }
 
 impl Voice {
@@ -184,70 +448,426 @@ impl Voice {
         Self {
             engine_blender: EngineBlender::new(sample_rate),
             envelope: EnvelopeGenerator::new(sample_rate),
-            filter: LowPassFilter::new(sample_rate),
+            filters: [LowPassFilter::new(sample_rate), LowPassFilter::new(sample_rate)],
             frequency: 440.0,
+            glide_time: 0.0,
+            glide_offset: 0.0,
+            glide_rate: 0.0,
             velocity: 0.5,
+            release_velocity: DEFAULT_RELEASE_VELOCITY,
+            volume: 1.0,
+            target_volume: 1.0,
+            volume_step: 0.0,
             note: 60,
             is_active: false,
             duration: None,
             elapsed_time: 0.0,
             sample_rate,
+            age_samples: 0,
+            peak_output: 0.0,
+            rng: Rng::from_entropy(),
+            modulation: VoiceModulation::default(),
+            mod_offsets: ModOffsets::default(),
+            base_cutoff: 1.0,
+            base_blend: 0.5,
+            base_brightness: 0.5,
+            base_spectral_filter: SpectralFilter::default(),
+            saturation: 0.0,
+            output_gain: 1.0,
+            pan: 0.0,
+            pan_law: PanLaw::default(),
+            pan_gains: [1.0; 2],
+            depth: 0.0,
+            depth_gains: [1.0, 0.0],
+            binaural: None,
+            layers: LayerMask::BOTH,
+            last_gain: 0.0,
+            last_output: [0.0; 2],
+            paraphonic: false,
+            drone: None,
+        }
+    }
+    
+    // 使い終わったボイスを新しく作ったときと同じ発音前の状態に戻す。
+    // 音色と演奏の設定はプールから確保するときに上書きされるので、ここでは前のノートの状態だけを消す
+    pub fn reset(&mut self) {
+        self.envelope.reset();
+        for filter in &mut self.filters {
+            filter.reset();
+        }
+        self.engine_blender.reset();
+        self.glide_offset = 0.0;
+        self.glide_rate = 0.0;
+        self.release_velocity = DEFAULT_RELEASE_VELOCITY;
+        self.is_active = false;
+        self.duration = None;
+        self.elapsed_time = 0.0;
+        self.age_samples = 0;
+        self.peak_output = 0.0;
+        // モジュレーションで動かした音高・カットオフ・定位なども元の値に戻す
+        self.apply_modulation(ModOffsets::default());
+        self.volume = self.target_volume;
+        self.layers = LayerMask::BOTH;
+        self.last_gain = 0.0;
+        self.last_output = [0.0; 2];
+        if let Some(panner) = &mut self.binaural {
+            panner.reset();
+        }
+        if let Some(walk) = &mut self.drone {
+            walk.reset();
+            self.engine_blender.additive_engine().set_harmonic_drift(None);
+        }
+    }
+    
+    pub fn with_patch(sample_rate: f32, patch: &PatchState) -> Self {
+        let mut voice = Self::new(sample_rate);
+        voice.apply_patch(patch);
+        voice
+    }
+    
+    pub fn apply_patch(&mut self, patch: &PatchState) {
+        self.set_blend(patch.blend);
+        self.set_brightness(patch.brightness);
+        self.set_cutoff(patch.cutoff);
+        self.set_resonance(patch.resonance);
+        self.set_filter_drive(patch.filter_drive);
+        self.set_filter_compensation(patch.filter_compensation);
+        self.set_volume(patch.volume);
+        self.set_saturation(patch.voice_saturation);
+        self.set_output(patch.output_trim, patch.output_invert);
+        self.set_pan(patch.pan);
+        self.set_depth(patch.depth);
+        self.set_envelope(patch.envelope);
+        self.set_glide_time(patch.glide_time);
+        self.set_drone(patch.drone, patch.drone_rate, patch.drone_depth);
+        self.engine_blender.additive_engine().set_harmonics(&patch.harmonics);
+        self.engine_blender.fm_engine().set_operators(&patch.operators);
+        self.engine_blender.fm_engine().set_algorithm(patch.fm_algorithm);
+        self.set_stack(patch.stack_density, patch.stack_detune, patch.stack_spread);
+        self.set_width(patch.width);
+        self.set_spectral_filter(patch.spectral_filter);
+        self.set_anti_alias(patch.anti_alias);
+        for (index, envelope) in patch.breakpoint_envelopes.iter().enumerate() {
+            self.modulation.set_envelope(index, envelope);
+        }
+        self.modulation.set_lfo(patch.lfo);
+        self.modulation.set_combine(patch.mod_combine);
+        self.set_mod_routings(&patch.mod_routings);
+    }
+    
+    // patch を反映するときに要るメモリを先に確保する
+    pub fn reserve_patch(&mut self, patch: &PatchState) {
+        self.modulation.reserve(&patch.mod_routings, &patch.breakpoint_envelopes);
+    }
+    
+    // from と to で異なるパラメータだけを patch の値にする（クロスフェードや差し替えで、変わらない設定を毎回計算し直さない）
+    pub fn apply_patch_changes(&mut self, patch: &PatchState, from: &PatchState, to: &PatchState) {
+        if from.blend != to.blend {
+            self.set_blend(patch.blend);
+        }
+        if from.brightness != to.brightness {
+            self.set_brightness(patch.brightness);
+        }
+        if from.cutoff != to.cutoff {
+            self.set_cutoff(patch.cutoff);
+        }
+        if from.resonance != to.resonance {
+            self.set_resonance(patch.resonance);
+        }
+        if from.filter_drive != to.filter_drive {
+            self.set_filter_drive(patch.filter_drive);
+        }
+        if from.filter_compensation != to.filter_compensation {
+            self.set_filter_compensation(patch.filter_compensation);
+        }
+        if from.volume != to.volume {
+            self.set_volume(patch.volume);
+        }
+        if from.voice_saturation != to.voice_saturation {
+            self.set_saturation(patch.voice_saturation);
+        }
+        if (from.output_trim, from.output_invert) != (to.output_trim, to.output_invert) {
+            self.set_output(patch.output_trim, patch.output_invert);
+        }
+        if from.pan != to.pan {
+            self.set_pan(patch.pan);
+        }
+        if from.depth != to.depth {
+            self.set_depth(patch.depth);
+        }
+        if from.envelope != to.envelope {
+            self.set_envelope(patch.envelope);
+        }
+        if from.glide_time != to.glide_time {
+            self.set_glide_time(patch.glide_time);
+        }
+        if (from.drone, from.drone_rate, from.drone_depth) != (to.drone, to.drone_rate, to.drone_depth) {
+            self.set_drone(patch.drone, patch.drone_rate, patch.drone_depth);
+        }
+        if from.harmonics != to.harmonics {
+            self.engine_blender.additive_engine().set_harmonics(&patch.harmonics);
+        }
+        if from.operators != to.operators {
+            self.engine_blender.fm_engine().set_operators(&patch.operators);
+        }
+        if from.fm_algorithm != to.fm_algorithm {
+            self.engine_blender.fm_engine().set_algorithm(patch.fm_algorithm);
+        }
+        if (from.stack_density, from.stack_detune, from.stack_spread) != (to.stack_density, to.stack_detune, to.stack_spread) {
+            self.set_stack(patch.stack_density, patch.stack_detune, patch.stack_spread);
+        }
+        if from.width != to.width {
+            self.set_width(patch.width);
+        }
+        if from.spectral_filter != to.spectral_filter {
+            self.set_spectral_filter(patch.spectral_filter);
+        }
+        if from.anti_alias != to.anti_alias {
+            self.set_anti_alias(patch.anti_alias);
+        }
+        for (index, (a, b)) in from.breakpoint_envelopes.iter().zip(&to.breakpoint_envelopes).enumerate() {
+            if a != b {
+                self.modulation.set_envelope(index, &patch.breakpoint_envelopes[index]);
+            }
+        }
+        if from.lfo != to.lfo {
+            self.modulation.set_lfo(patch.lfo);
+        }
+        if from.mod_combine != to.mod_combine {
+            self.modulation.set_combine(patch.mod_combine);
+        }
+        if from.mod_routings != to.mod_routings {
+            self.set_mod_routings(&patch.mod_routings);
         }
     }
     
     pub fn note_on(&mut self, note: u8, velocity: f32) {
-        let frequency = 440.0 * 2.0_f32.powf((note as f32 - 69.0) / 12.0);
-        self.frequency = frequency;
+        self.note_on_with_random(note, velocity, 0.0);
+    }
+    
+    // random は ModSource::Random として発音中保持する値（-1.0〜1.0）
+    pub fn note_on_with_random(&mut self, note: u8, velocity: f32, random: f32) {
+        self.frequency = 440.0 * 2.0_f32.powf((note as f32 - 69.0) / 12.0);
         self.note = note;
         self.velocity = velocity.clamp(0.0, 1.0);
-        self.engine_blender.set_frequency(frequency);
+        self.duration = None;
+        self.glide_offset = 0.0;
+        self.apply_pitch();
+        self.volume = self.target_volume;
         self.envelope.note_on();
+        self.modulation.note_on(random);
+        self.engine_blender.additive_engine().randomize_stack_phases(&mut self.rng);
+        if let Some(panner) = &mut self.binaural {
+            panner.reset();
+        }
         self.is_active = true;
         self.elapsed_time = 0.0;
+        self.age_samples = 0;
+        self.peak_output = 0.0;
     }
     
-    pub fn note_on_with_duration(&mut self, note: u8, velocity: f32, duration: f32) {
-        let frequency = 440.0 * 2.0_f32.powf((note as f32 - 69.0) / 12.0);
-        self.frequency = frequency;
-        self.note = note;
-        self.velocity = velocity.clamp(0.0, 1.0);
+    pub fn note_on_with_duration(&mut self, note: u8, velocity: f32, duration: f32, random: f32) {
+        self.note_on_with_random(note, velocity, random);
         self.duration = Some(duration);
-        self.engine_blender.set_frequency(frequency);
-        self.envelope.note_on();
-        self.is_active = true;
-        self.elapsed_time = 0.0;
     }
     
-    pub fn note_off(&mut self) {
+    // 持続時間の指定を外し、note_off まで鳴らし続ける
+    pub fn clear_duration(&mut self) {
+        self.duration = None;
+    }
+    
+    // velocity はリリースベロシティ（0.0-1.0）。ModSource::ReleaseVelocity としてリリース中に使える
+    pub fn note_off(&mut self, velocity: f32) {
+        self.release_velocity = velocity.clamp(0.0, 1.0);
         self.envelope.note_off();
+        self.modulation.note_off(self.release_velocity);
         self.is_active = false;
     }
     
+    // ペダルでリリースを保留するときに離鍵時の値を覚えておく
+    pub fn set_release_velocity(&mut self, velocity: f32) {
+        self.release_velocity = velocity.clamp(0.0, 1.0);
+    }
+    
+    pub fn release_velocity(&self) -> f32 {
+        self.release_velocity
+    }
+    
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency;
+        self.apply_pitch();
+    }
+    
+    fn apply_pitch(&mut self) {
+        let frequency = self.frequency * 2.0_f32.powf((self.mod_offsets.pitch + self.glide_offset) / 12.0);
+        self.engine_blender.set_frequency(frequency);
+    }
+    
+    // ドローン: 持続時間の指定を無視して鳴らし続け、倍音の振幅を rate（Hz）の速さで 1 - depth から 1 の間で揺らす。
+    // 鳴っている間に rate・depth を変えてもゆらぎは途切れず、無効にすると倍音の振幅を元に戻す
+    pub fn set_drone(&mut self, enabled: bool, rate: f32, depth: f32) {
+        if enabled {
+            self.drone.get_or_insert_with(|| DroneWalk::new(rate, depth)).set(rate, depth);
+        } else if self.drone.take().is_some() {
+            self.engine_blender.additive_engine().set_harmonic_drift(None);
+        }
+    }
+    
+    pub fn set_glide_time(&mut self, seconds: f32) {
+        self.glide_time = seconds.clamp(0.0, MAX_GLIDE_TIME);
+    }
+    
+    // from_frequency から今の音高まで glide_time で滑る（note_on と set_frequency の後に呼ぶ）
+    pub fn start_glide(&mut self, from_frequency: f32) {
+        if self.glide_time <= 0.0 || from_frequency <= 0.0 || self.frequency <= 0.0 {
+            return;
+        }
+        self.glide_offset = 12.0 * (from_frequency / self.frequency).log2();
+        self.glide_rate = self.glide_offset.abs() / self.glide_time;
+        self.apply_pitch();
+    }
+    
+    // 音高のずれをコントロールレートで 0 へ近づける
+    fn advance_glide(&mut self, seconds: f32) {
+        let step = self.glide_rate * seconds;
+        self.glide_offset = if self.glide_offset.abs() <= step { 0.0 } else { self.glide_offset - step.copysign(self.glide_offset) };
+        self.apply_pitch();
+    }
+    
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+    
+    // 変化した送り先だけを更新する
+    fn apply_modulation(&mut self, offsets: ModOffsets) {
+        let previous = std::mem::replace(&mut self.mod_offsets, offsets);
+        if offsets.pitch != previous.pitch {
+            self.apply_pitch();
+        }
+        if offsets.cutoff != previous.cutoff {
+            self.set_cutoff(self.base_cutoff);
+        }
+        if offsets.blend != previous.blend {
+            self.set_blend(self.base_blend);
+        }
+        if offsets.brightness != previous.brightness {
+            self.set_brightness(self.base_brightness);
+        }
+        if offsets.pan != previous.pan || offsets.depth != previous.depth {
+            self.update_placement();
+        }
+        if offsets.bloom != previous.bloom && self.modulation.routes_to(ModDestination::Bloom) {
+            self.engine_blender.additive_engine().set_bloom(Some(offsets.bloom));
+        }
+        if offsets.tilt != previous.tilt {
+            self.set_spectral_filter(self.base_spectral_filter);
+        }
+    }
+    
     pub fn next_sample(&mut self) -> f32 {
-        if !self.is_active {
-            return 0.0;
+        let [left, right] = self.next_frame();
+        (left + right) * 0.5
+    }
+    
+    // ノートオフの後もエンベロープのリリースが終わるまで鳴らす
+    pub fn next_frame(&mut self) -> [f32; 2] {
+        if self.is_released() && !self.paraphonic {
+            return [0.0; 2];
         }
         
-        // 持続時間のチェック
-        if let Some(duration) = self.duration {
+        // 持続時間のチェック（ドローン中は鳴らし続ける）。過ぎたらリリースに入る
+        if let Some(duration) = self.duration.filter(|_| self.is_active && self.drone.is_none()) {
             self.elapsed_time += 1.0 / self.sample_rate;
             if self.elapsed_time >= duration {
-                self.note_off();
-                return 0.0;
+                self.note_off(DEFAULT_RELEASE_VELOCITY);
+            }
+        }
+        
+        if let Some(offsets) = self.modulation.tick(self.velocity, self.note, self.sample_rate) {
+            self.apply_modulation(offsets);
+        }
+        if self.glide_offset != 0.0 && self.age_samples > 0 && self.age_samples.is_multiple_of(MOD_BLOCK_SIZE as u64) {
+            self.advance_glide(MOD_BLOCK_SIZE as f32 / self.sample_rate);
+        }
+        if let Some(walk) = &mut self.drone {
+            if self.age_samples.is_multiple_of(MOD_BLOCK_SIZE as u64) {
+                let gains = walk.advance(MOD_BLOCK_SIZE as f32 / self.sample_rate, &mut self.rng);
+                self.engine_blender.additive_engine().set_harmonic_drift(Some(gains));
             }
         }
         
-        let raw_sample = self.engine_blender.next_sample();
-        let envelope_value = self.envelope.next_sample();
-        let filtered_sample = self.filter.process(raw_sample * envelope_value);
+        if self.volume != self.target_volume {
+            let remaining = self.target_volume - self.volume;
+            self.volume = if remaining.abs() <= self.volume_step { self.target_volume } else { self.volume + self.volume_step.copysign(remaining) };
+        }
+        
+        let raw_frame = self.engine_blender.next_frame();
+        let gain = self.velocity * self.volume * (1.0 + self.mod_offsets.amplitude).max(0.0);
+        let mut output = [0.0; 2];
+        let envelope_value = if self.paraphonic {
+            for (channel, sample) in output.iter_mut().enumerate() {
+                *sample = saturate(raw_frame[channel] * gain, self.saturation) * self.output_gain;
+            }
+            1.0
+        } else {
+            let envelope_value = self.envelope.next_sample();
+            for (channel, filter) in self.filters.iter_mut().enumerate() {
+                output[channel] = saturate(filter.process(raw_frame[channel] * envelope_value) * gain, self.saturation) * self.output_gain;
+            }
+            envelope_value
+        };
+        output = match &mut self.binaural {
+            Some(panner) => panner.process(output),
+            None => [output[0] * self.pan_gains[0], output[1] * self.pan_gains[1]],
+        };
+        self.last_gain = envelope_value * gain;
+        self.last_output = output;
         
-        filtered_sample * self.velocity
+        self.age_samples += 1;
+        self.peak_output = self.peak_output.max(output[0].abs()).max(output[1].abs());
+        output
+    }
+    
+    // 直前のフレームの解析タップ（エンジン単体の信号はフィルター前）
+    pub fn taps(&self) -> TapFrame {
+        let ([additive_left, additive_right], fm) = self.engine_blender.last_outputs();
+        TapFrame {
+            additive: [additive_left * self.last_gain, additive_right * self.last_gain],
+            fm: [fm[0] * self.last_gain, fm[1] * self.last_gain],
+            post_filter: self.last_output,
+        }
+    }
+    
+    pub fn info(&self) -> VoiceInfo {
+        VoiceInfo {
+            note: self.note,
+            frequency: self.frequency,
+            stage: self.envelope.stage(),
+            envelope_level: self.envelope.level(),
+            age_samples: self.age_samples,
+            peak_output: self.peak_output,
+            release_velocity: self.release_velocity,
+            glide: self.glide_offset,
+            modulation: self.mod_offsets,
+            stack_density: self.engine_blender.stack_density(),
+        }
     }
     
     pub fn is_active(&self) -> bool {
         self.is_active
     }
     
+    // 直前のフレームのエンベロープとゲイン（ボイスを奪うときの音量の目安）
+    pub fn level(&self) -> f32 {
+        self.last_gain.abs()
+    }
+    
+    // パラフォニックのボイス（フィルターとアンプのエンベロープは Synthesizer がまとめて掛ける）
+    pub fn set_paraphonic(&mut self, paraphonic: bool) {
+        self.paraphonic = paraphonic;
+    }
+    
+    // ノートオフの後にリリースが終わった（Synthesizer が回収してよい）
     pub fn is_released(&self) -> bool {
         !self.is_active && self.envelope.current_stage == EnvelopeStage::Idle
     }
@@ -258,15 +878,165 @@ impl Voice {
     
     // パラメータ設定
     pub fn set_blend(&mut self, blend: f32) {
-        self.engine_blender.set_blend_ratio(blend);
+        self.base_blend = blend;
+        self.engine_blender.set_blend_ratio(self.layers.blend(blend + self.mod_offsets.blend));
+    }
+    
+    pub fn set_layers(&mut self, layers: LayerMask) {
+        self.layers = layers;
+        self.set_blend(self.base_blend);
+    }
+    
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.base_brightness = brightness;
+        self.engine_blender.set_brightness(brightness + self.mod_offsets.brightness);
+    }
+    
+    pub fn set_mod_routings(&mut self, routings: &[ModRouting]) {
+        self.modulation.set_routings(routings);
+        if !self.modulation.is_active() {
+            self.apply_modulation(ModOffsets::default());
+        }
+        // ブルームは割り当てたときだけ倍音を制限する（ソースが 0 のままでも基音だけにする）
+        let bloom = self.modulation.routes_to(ModDestination::Bloom).then_some(self.mod_offsets.bloom);
+        self.engine_blender.additive_engine().set_bloom(bloom);
+    }
+    
+    pub fn set_breakpoint_envelope(&mut self, index: usize, envelope: &BreakpointEnvelope) {
+        self.modulation.set_envelope(index, envelope);
+    }
+    
+    // 鳴っているボイスでは次の発音を待たずに新しい設定で動き続ける
+    pub fn set_lfo(&mut self, lfo: Lfo) {
+        self.modulation.set_lfo(lfo);
+    }
+    
+    pub fn set_controllers(&mut self, controllers: Controllers) {
+        self.modulation.set_controllers(controllers);
+    }
+    
+    pub fn set_tempo(&mut self, tempo_bpm: f32) {
+        self.modulation.set_tempo(tempo_bpm);
+        self.envelope.set_tempo(tempo_bpm);
+    }
+    
+    // ベンドレンジを掛けた後のピッチベンド（半音）。ルーティングによるピッチの変化とは別に足される
+    pub fn set_pitch_bend(&mut self, semitones: f32) {
+        self.engine_blender.set_pitch_bend(semitones);
+    }
+    
+    pub fn set_stack(&mut self, density: usize, detune_cents: f32, spread: f32) {
+        self.engine_blender.additive_engine().set_stack(density, detune_cents, spread);
+    }
+    
+    // 傾きのモジュレーションはこの値に加算される
+    pub fn set_spectral_filter(&mut self, filter: SpectralFilter) {
+        self.base_spectral_filter = filter;
+        let tilt = filter.tilt + self.mod_offsets.tilt;
+        self.engine_blender.additive_engine().set_spectral_filter(SpectralFilter { tilt, ..filter });
+    }
+    
+    pub fn set_width(&mut self, width: f32) {
+        self.engine_blender.set_width(width);
+    }
+    
+    pub fn set_anti_alias(&mut self, enabled: bool) {
+        self.engine_blender.additive_engine().set_anti_alias(enabled);
+    }
+    
+    pub fn set_quality(&mut self, level: QualityLevel) {
+        self.engine_blender.additive_engine().set_quality_limits(level.max_harmonics, level.max_stack);
+    }
+    
+    // FM エンジンの正弦を共有のテーブルではなく f32::sin で計算する
+    pub fn set_exact_sine(&mut self, exact: bool) {
+        self.engine_blender.fm_engine().set_exact_sine(exact);
     }
     
+    // 正規化カットオフ（モジュレーションはこの値に加算される）
     pub fn set_cutoff(&mut self, cutoff: f32) {
-        self.filter.set_cutoff(cutoff * 20000.0);
+        self.base_cutoff = cutoff;
+        let hz = cutoff_to_hz(cutoff + self.mod_offsets.cutoff);
+        for filter in &mut self.filters {
+            filter.set_cutoff(hz);
+        }
+    }
+    
+    pub fn cutoff_hz(&self) -> f32 {
+        self.filters[0].cutoff()
     }
     
     pub fn set_resonance(&mut self, resonance: f32) {
-        self.filter.set_resonance(resonance);
+        for filter in &mut self.filters {
+            filter.set_resonance(resonance);
+        }
+    }
+    
+    pub fn set_filter_drive(&mut self, drive: f32) {
+        for filter in &mut self.filters {
+            filter.set_drive(drive);
+        }
+    }
+    
+    pub fn set_saturation(&mut self, amount: f32) {
+        self.saturation = amount.clamp(0.0, 1.0);
+    }
+    
+    // 飽和の後、定位の前に掛ける（トリムで飽和の掛かり方は変わらない）
+    pub fn set_output(&mut self, trim_db: f32, invert: bool) {
+        let gain = 10.0_f32.powf(trim_db.clamp(-MAX_OUTPUT_TRIM_DB, MAX_OUTPUT_TRIM_DB) / 20.0);
+        self.output_gain = if invert { -gain } else { gain };
+    }
+    
+    pub fn set_pan(&mut self, pan: f32) {
+        self.pan = pan.clamp(-1.0, 1.0);
+        self.update_placement();
+    }
+    
+    // サラウンド出力での前後の位置（ステレオ出力では使わない）
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+        self.update_placement();
+    }
+    
+    // ボイスの定位とスタックの層の広がりの両方に使う
+    pub fn set_pan_law(&mut self, pan_law: PanLaw) {
+        self.pan_law = pan_law;
+        self.update_placement();
+        self.engine_blender.additive_engine().set_pan_law(pan_law);
+    }
+    
+    // ヘッドホン向けのバイノーラル定位（None で pan_law に戻す）
+    // 同じ HRIR セットの定位が既にあれば作り直さない（プールのボイスを確保するとき）
+    pub fn set_binaural(&mut self, hrirs: Option<Arc<HrirSet>>) {
+        let unchanged = match (&self.binaural, &hrirs) {
+            (Some(panner), Some(hrirs)) => Arc::ptr_eq(panner.hrirs(), hrirs),
+            (None, None) => true,
+            _ => false,
+        };
+        if !unchanged {
+            self.binaural = hrirs.map(BinauralPanner::new);
+        }
+        self.update_placement();
+    }
+    
+    fn update_placement(&mut self) {
+        self.pan_gains = self.pan_law.gains(self.pan + self.mod_offsets.pan);
+        if let Some(panner) = &mut self.binaural {
+            panner.set_pan(self.pan + self.mod_offsets.pan);
+        }
+        self.depth_gains = depth_gains(self.depth + self.mod_offsets.depth);
+    }
+    
+    // 直前のフレームでの前方・後方のゲイン（サラウンド出力のミックス用）
+    pub fn depth_gains(&self) -> [f32; 2] {
+        self.depth_gains
+    }
+    
+    pub fn set_filter_compensation(&mut self, enabled: bool) {
+        for filter in &mut self.filters {
+            filter.set_gain_compensation(enabled);
+        }
     }
     
     pub fn set_attack(&mut self, attack: f32) {
@@ -308,8 +1078,10 @@ impl Voice {
     }
     
     // Volume control
+    // 鳴っている間の変化は段差にならないよう 1 ブロック（MOD_BLOCK_SIZE サンプル）かけて移る
     pub fn set_volume(&mut self, volume: f32) {
-        self.velocity = volume.clamp(0.0, 1.0);
+        self.target_volume = volume.clamp(0.0, 1.0);
+        self.volume_step = (self.target_volume - self.volume).abs() / MOD_BLOCK_SIZE as f32;
     }
     
     // Envelope control
@@ -318,204 +1090,2603 @@ impl Voice {
     }
 }
 
+// 解析バッファの長さ（フレーム）
+const ANALYSIS_BUFFER_FRAMES: usize = 2048;
+const METER_RELEASE_SECONDS: f32 = 0.3;
+const FOLD_DOWN_WINDOW_SECONDS: f32 = 1.0;
+const LIVE_ANCHOR_TIMEOUT_SECONDS: f32 = 0.1; // これより長くブロックを生成していなければ、オーディオは止まっているとみなす
+pub const MAX_LIVE_LOOKAHEAD: f32 = 0.02;
+pub const DEFAULT_BEND_RANGE: f32 = 2.0;
+pub const MAX_LFO_RATE: f32 = 50.0;
+pub const MAX_LFO_FADE: f32 = 10.0;
+pub const MAX_GLIDE_TIME: f32 = 10.0;
+const HOST_LOCATE_TOLERANCE_BEATS: f64 = 1.0 / 256.0; // ホストの位置とこれ以上ずれたらシーケンサーの位置を移す
+
+// set_parameter がパッチ全体を適用し直さずに設定できるパラメータ
+pub const DIRECT_PARAMETERS: [&str; 15] = [
+    "blend", "brightness", "cutoff", "resonance", "filter.drive", "volume", "voice.saturation",
+    "output.trim", "pan", "depth", "width", "env.attack", "env.decay", "env.sustain", "env.release",
+];
+pub const MAX_BEND_RANGE: f32 = 24.0;
+pub const MAX_OUTPUT_TRIM_DB: f32 = 12.0;
+// CC123 をこの間隔以内に2回受け取るとパニック。
+// サンプル位置で測るので、実時間より速いオフラインの書き出しでも曲の中の間隔で判定する
+pub const PANIC_DOUBLE_TAP: Duration = Duration::from_millis(500);
+// モーフィングの位置を set_parameter（CC・OSC の割り当て）から動かすときの名前
+pub const MORPH_PARAMETER: &str = "morph";
+
 // メインシンセサイザー
 pub struct Synthesizer {
-    pub voices: HashMap<u8, Voice>,
-    sample_rate: f32,
+    voices: HashMap<u8, Voice>,
+    voice_pool: Vec<Voice>, // 発音していないボイス。起動時に最大同時発音数分を作り、note_on で取り出して取り除いたものを戻す
+    config: SynthConfig,
+    effects: EffectsChain,
     current_note: Option<u8>,
     current_velocity: Option<f32>,
+    last_note_frequency: Option<f32>,     // 直前に弾いたノートの周波数（グライドの始点）
+    last_released_frequency: Option<f32>, // 直前にリリースしたボイスの周波数（グライドの始点）
+    patch: PatchState, // ボイスとは独立したグローバルな音色状態
+    log: EventLogWriter,
+    log_reader: Option<EventLogReader>,
+    commands: Vec<CommandReceiver>, // ほかのスレッドからロックを取らずに届く操作（送る側ごとに1つ）
+    closed_command_drops: usize,    // 閉じて外したキューで捨てられていた操作の数
+    devices: Vec<Arc<str>>,         // Command::Midi の機器の番号と名前
+    sample_clock: u64,
+    control_block_done: bool, // render_block が現在の境界のコントロールブロックを先に進めた
+    clipping: bool,
+    output_meter: [f32; 2], // ピークメーター（ゆっくり下がる）
+    meter_decay: f32,
+    mono_check: bool, // マスター出力をモノラルにして確認する
+    output_layout: ChannelLayout,
+    crossfeed: Crossfeed, // config.crossfeed が有効なときだけ掛ける
+    hrirs: Option<Arc<HrirSet>>, // 一度作った HRIR セットはボイス間で共有する
+    analog_floor: AnalogFloor, // config.analog_floor が有効なときだけ足す
+    surround: bool,   // 機器がサラウンドのときに後方チャンネルを使う
+    fold_down: FoldDownMeter,
+    tempo_bpm: f32,
+    tempo_source: TempoSource,
+    midi_clock: MidiClock,
+    host_transport: Option<(HostTransport, u64)>, // ホストから最後に届いたトランスポートと、届いたときの時計
+    bend_range: f32, // ピッチベンドを振り切ったときの半音数
+    tuning: Tuning, // 実行中に変更できる音律（初期値は設定から）
+    sequencer: Sequencer,
+    pending_events: Vec<Event>, // シーケンサーなどが生成したイベント（確保済みバッファを再利用）
+    scenes: SceneBank,
+    scene_fade: SceneFade, // 進行中でないときも写し先として残しておく（オーディオスレッドで確保・解放しない）
+    scene_fading: bool,
+    morph: Option<PatchMorph>,
+    retired_morph: Option<PatchMorph>, // オーディオスレッドで止めたモーフィング（解放は次の start_morph・queue_patch で行う）
+    current_scene: Option<usize>,
+    metronome: Metronome,
+    ducker: Ducker,
+    layer_filters: LayerFilters,
+    program_map: ProgramMap,
+    pending_patch: Option<Box<PatchState>>, // 次のコントロールレートの境界で差し替える音色
+    retired_patch: Option<Box<PatchState>>, // 差し替えで外した音色（解放は次の queue_patch で行う）
+    pending_batch: Vec<(String, f32)>, // 次のコントロールレートの境界でまとめて設定するパラメータ
+    retired_batch: Vec<(String, f32)>, // 設定し終えた変更（解放は次の apply_batch で行う）
+    output_latency: u32,  // 出力機器が報告したレイテンシ（サンプル）
+    latency_offset: f32,  // ユーザーによる追加補正（ミリ秒、Bluetooth機器など）
+    taps: AnalysisTaps,
+    quality: QualityGovernor,
+    workers: Option<VoiceWorkers>, // 複数スレッドでのボイスのレンダリング
+    event_offsets: Vec<(usize, usize)>, // ブロック内のイベント位置と pending_events の終端
+    test_signal: Option<TestSignalGenerator>, // 診断モード（ボイスの代わりに出力する）
+    controllers: Controllers, // ピッチベンド・モジュレーションホイール
+    controller_ramps: Vec<ControllerRamp>,
+    scheduled: VecDeque<ScheduledEvent>, // 時刻指定のイベント（サンプル位置順）
+    scheduled_batches: VecDeque<(u64, Vec<(String, f32)>)>, // 時刻指定のパラメータの変更（サンプル位置順）
+    timestamp_latency: f32, // 時刻指定のイベントに足す遅れ（秒）。ネットワーク越しに届く時刻の揺れを吸収する
+    late_events: u64,       // 指定の時刻を過ぎてから届いた時刻指定のイベント
+    live_lookahead: f32,    // 演奏中の MIDI 入力を遅らせてブロックの中に置く先読み（秒、0 で無効）
+    block_anchor: Option<(Instant, u64)>, // 最後のコールバックの開始時刻と、そのブロックを生成し終えた時計
+    cc_map: CcMap,
+    parameter_ramps: Vec<ParameterRamp>, // 時刻指定のパラメータのランプ（サンプル単位で進める）
+    shared_params: Arc<Params>,          // ほかのスレッドがロックを取らずに書くパラメータ
+    telemetry: Arc<Telemetry>,           // ほかのスレッドがロックを取らずに読む状態
+    shared_writes: [u32; SHARED_PARAMETERS.len()], // 読み終えた書き込みの回数
+    smoothers: [Smoother; SHARED_PARAMETERS.len()],
+    paraphony: Option<Paraphony>, // パラフォニックモードで全ボイスが共有するフィルターとエンベロープ
+    sustain_pedal: bool,
+    sustained_notes: Vec<u8>, // 鍵盤は離されたがペダルでリリースを待っているノート
+    hold: bool,
+    held_notes: Vec<u8>,      // hold で固定したノート（note_off を無視する）
+    drone_notes: Vec<u8>,     // ドローン中に離されたノート（ドローンを解除するとリリースする）
+    last_all_notes_off: Option<u64>, // CC123 のダブルタップの判定用（受け取ったサンプル位置）
+    note_rng: Rng, // 発音ごとのランダム値（ModSource::Random）
+    record_tap: Option<RecordTap>, // 録音中ならオーディオ出力をここへ流す
+}
+
+impl Default for Synthesizer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Synthesizer {
     pub fn new() -> Self {
-        let sample_rate = 44100.0;
-        
-        Self {
-            voices: HashMap::new(),
-            sample_rate,
-            current_note: None,
-            current_velocity: None,
-        }
+        SynthesizerBuilder::new().build()
+    }
+    
+    pub fn builder() -> SynthesizerBuilder {
+        SynthesizerBuilder::new()
+    }
+    
+    pub(crate) fn from_parts(config: SynthConfig, patch: PatchState, effects: EffectsChain) -> Self {
+        let (log, log_reader) = event_log();
+        let tuning = config.tuning.clone();
+        let quality = QualityGovernor::new(config.quality_policy.clone());
+        let render_threads = config.render_threads;
+        let polyphony = config.polyphony;
+        let fold_down = FoldDownMeter::new(config.sample_rate, FOLD_DOWN_WINDOW_SECONDS);
+        let crossfeed = Crossfeed::new(config.sample_rate);
+        let analog_floor = AnalogFloor::new(config.sample_rate, config.analog_floor);
+        let hrirs = config.binaural.then(|| Arc::new(HrirSet::spherical_head(config.sample_rate)));
+        let shared_params = Arc::new(Params::new(&patch));
+        let shared_writes = shared_params.fields().map(|field| field.load().0);
+        let scene_fade = SceneFade { from: patch.clone(), to: patch.clone(), elapsed: 0.0, duration: 0.0 };
+        let mut synth = Self {
+            voices: HashMap::with_capacity(polyphony + 1),
+            voice_pool: Vec::new(),
+            config,
+            effects,
+            current_note: None,
+            last_note_frequency: None,
+            last_released_frequency: None,
+            current_velocity: None,
+            patch,
+            log,
+            log_reader: Some(log_reader),
+            commands: Vec::new(),
+            closed_command_drops: 0,
+            devices: Vec::new(),
+            sample_clock: 0,
+            control_block_done: false,
+            clipping: false,
+            output_meter: [0.0; 2],
+            meter_decay: 0.0,
+            mono_check: false,
+            output_layout: ChannelLayout::Stereo,
+            crossfeed,
+            hrirs,
+            analog_floor,
+            surround: true,
+            fold_down,
+            tempo_bpm: 120.0,
+            tempo_source: TempoSource::Internal,
+            midi_clock: MidiClock::default(),
+            host_transport: None,
+            bend_range: DEFAULT_BEND_RANGE,
+            tuning,
+            sequencer: Sequencer::new(),
+            pending_events: Vec::with_capacity(64),
+            scenes: SceneBank::default(),
+            scene_fade,
+            scene_fading: false,
+            morph: None,
+            retired_morph: None,
+            current_scene: None,
+            metronome: Metronome::new(),
+            ducker: Ducker::new(),
+            layer_filters: LayerFilters::default(),
+            program_map: ProgramMap::default(),
+            pending_patch: None,
+            retired_patch: None,
+            pending_batch: Vec::new(),
+            retired_batch: Vec::new(),
+            output_latency: 0,
+            latency_offset: 0.0,
+            taps: AnalysisTaps::new(ANALYSIS_BUFFER_FRAMES),
+            quality,
+            workers: None,
+            event_offsets: Vec::with_capacity(MAX_PARALLEL_FRAMES),
+            test_signal: None,
+            controllers: Controllers::default(),
+            controller_ramps: Vec::with_capacity(2),
+            scheduled: VecDeque::with_capacity(256),
+            scheduled_batches: VecDeque::new(),
+            timestamp_latency: 0.0,
+            late_events: 0,
+            live_lookahead: 0.0,
+            block_anchor: None,
+            cc_map: CcMap::default(),
+            parameter_ramps: Vec::with_capacity(8),
+            shared_params,
+            telemetry: Arc::new(Telemetry::default()),
+            shared_writes,
+            smoothers: Default::default(),
+            paraphony: None,
+            sustain_pedal: false,
+            sustained_notes: Vec::with_capacity(128),
+            hold: false,
+            held_notes: Vec::with_capacity(128),
+            drone_notes: Vec::with_capacity(128),
+            last_all_notes_off: None,
+            note_rng: Rng::from_entropy(),
+            record_tap: None,
+        };
+        synth.set_render_threads(render_threads);
+        synth.fill_voice_pool();
+        synth.set_voice_mode(synth.config.voice_mode);
+        // メーターは約300msで -60dB まで下がる
+        synth.meter_decay = 0.001_f32.powf(1.0 / (METER_RELEASE_SECONDS * synth.config.sample_rate));
+        synth
+    }
+    
+    // オフラインレンダリング用に音色・音律・テンポ・シーケンサーを引き継いだ別インスタンスを作る。
+    // エフェクトは複製できないため引き継がない。
+    pub fn offline_copy(&self) -> Synthesizer {
+        let mut copy = Synthesizer::from_parts(self.config.clone(), self.patch.clone(), EffectsChain::new());
+        copy.tuning = self.tuning.clone();
+        copy.set_tempo(self.tempo_bpm);
+        copy.bend_range = self.bend_range;
+        copy.sequencer = self.sequencer.clone();
+        copy.scenes = self.scenes.clone();
+        copy.controllers = self.controllers;
+        copy.cc_map = self.cc_map.clone();
+        copy.ducker = self.ducker.clone();
+        copy.ducker.reset();
+        copy.layer_filters = self.layer_filters;
+        copy
+    }
+    
+    // offline_copy に加えて、今鳴っているノートを同じベロシティで先頭から弾き直し、
+    // 再生中のシーケンサーを先頭から再生した状態にする（演奏中の音をそのまま書き出すため）
+    pub fn offline_snapshot(&self) -> Synthesizer {
+        let mut copy = self.offline_copy();
+        for (note, voice) in self.voices.iter().filter(|(_, voice)| voice.is_active()) {
+            let (velocity, layers) = (voice.velocity, voice.layers);
+            let frequency = copy.tuning.frequency(*note);
+            let random = copy.note_rng.bipolar();
+            let voice = copy.allocate_voice(*note);
+            voice.set_layers(layers);
+            voice.note_on_with_random(*note, velocity, random);
+            voice.set_frequency(frequency);
+        }
+        if self.sequencer.is_playing() {
+            copy.play_sequencer();
+        }
+        copy
+    }
+    
+    // オーディオ出力のコールバックが capture_output で渡すフレームの送り先（record::Recording が設定する）
+    pub fn set_record_tap(&mut self, tap: Option<RecordTap>) {
+        self.record_tap = tap;
+    }
+    
+    pub fn is_recording(&self) -> bool {
+        self.record_tap.is_some()
+    }
+    
+    // 機器へ送ったステレオのフレームを録音する（録音中でなければ何もしない）
+    pub fn capture_output(&mut self, frames: &[[f32; 2]]) {
+        if let Some(tap) = &mut self.record_tap {
+            tap.push(frames);
+        }
+    }
+    
+    // オーディオスレッドのイベントログの読み出し側（最初の1回のみ取得できる）
+    pub fn take_event_log(&mut self) -> Option<EventLogReader> {
+        self.log_reader.take()
+    }
+    
+    pub fn report(&mut self, event: LogEvent) {
+        self.log.write(self.sample_clock, event);
+    }
+    
+    fn clamp_parameter(&mut self, parameter: &'static str, value: f32, min: f32, max: f32) -> f32 {
+        let applied = value.clamp(min, max);
+        if applied != value {
+            self.report(LogEvent::ParameterClamped { parameter, requested: value, applied });
+        }
+        applied
+    }
+    
+    pub fn config(&self) -> &SynthConfig {
+        &self.config
+    }
+    
+    pub fn sample_rate(&self) -> f32 {
+        self.config.sample_rate
+    }
+    
+    // 出力機器が実際に開いたサンプルレートに切り替える。鳴っているボイスは消し（新しいレートで作り直す）、
+    // 予約済みのイベントとオートメーションは秒で見た位置が変わらないようにサンプル位置を換算する
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        let sample_rate = sample_rate.max(1.0);
+        let previous = self.config.sample_rate;
+        if sample_rate == previous {
+            return;
+        }
+        let clock = self.sample_clock;
+        let rescale = |samples: u64| (samples as f64 * sample_rate as f64 / previous as f64) as u64;
+        for scheduled in &mut self.scheduled {
+            scheduled.sample = clock + rescale(scheduled.sample.saturating_sub(clock));
+        }
+        for (sample, _) in &mut self.scheduled_batches {
+            *sample = clock + rescale(sample.saturating_sub(clock));
+        }
+        for ramp in &mut self.parameter_ramps {
+            ramp.start = clock + rescale(ramp.start.saturating_sub(clock));
+            ramp.length = rescale(ramp.length).max(1);
+        }
+        self.config.sample_rate = sample_rate;
+        self.block_anchor = None;
+        self.voices.clear();
+        self.fill_voice_pool();
+        self.sustained_notes.clear();
+        self.held_notes.clear();
+        self.drone_notes.clear();
+        if self.paraphony.is_some() {
+            self.paraphony = Some(Paraphony::new(sample_rate, &self.patch, self.tempo_bpm));
+        }
+        self.effects.set_sample_rate(sample_rate);
+        self.crossfeed.set_sample_rate(sample_rate);
+        self.analog_floor.set_sample_rate(sample_rate);
+        self.fold_down = FoldDownMeter::new(sample_rate, FOLD_DOWN_WINDOW_SECONDS);
+        if self.hrirs.is_some() {
+            self.hrirs = Some(Arc::new(HrirSet::spherical_head(sample_rate)));
+        }
+        self.meter_decay = 0.001_f32.powf(1.0 / (METER_RELEASE_SECONDS * sample_rate));
+        // 出力レイテンシはサンプル単位なので、次のコールバックが報告し直すまで 0 にしておく
+        self.output_latency = 0;
+    }
+    
+    // ノートの操作の組み合わせ:
+    // - サステインペダル: 踏んでいる間の note_off は sustained_notes に保留し、ペダルを離すとリリースする
+    // - hold: 有効にした時点で鳴っているノートを held_notes に固定し、note_off を無視する
+    //   （ペダルで保留中のノートも hold に移る）。同じノートを弾き直すと保留・固定は外れる
+    // - ドローン（音色の drone.on）: 有効な間の note_off は drone_notes に記録するだけで、解除するとリリースする
+    // - モノモード: 新しいノートはペダル・hold に関係なく他のボイスをリリースし、保留・固定からも外す
+    // - ボイスの奪取: 同時発音数を超えたら StealPolicy で選んだボイスを奪い、保留・固定からも外す
+    // - 回収: リリースが終わったボイスは制御ブロックごとに取り除く（保留・固定中のボイスは発音中なので残る）
+    // このため、すべての鍵盤を離してペダル・hold・ドローンを解除し、リリースが終われば
+    // pending_release_count() と voice_count() は必ず 0 になる
+    
+    // ノート用のボイスを確保する（モノモード・最大同時発音数を考慮）
+    fn allocate_voice(&mut self, note: u8) -> &mut Voice {
+        if self.config.voice_mode == VoiceMode::Mono {
+            for (&other, voice) in self.voices.iter_mut() {
+                if other != note && voice.is_active() {
+                    voice.note_off(DEFAULT_RELEASE_VELOCITY);
+                    self.sustained_notes.retain(|sustained| *sustained != other);
+                    self.held_notes.retain(|held| *held != other);
+                    self.drone_notes.retain(|drone| *drone != other);
+                }
+            }
+        }
+        
+        // 品質の段で同時発音数を減らした直後は、超えている分をまとめて奪う
+        if !self.voices.contains_key(&note) {
+            self.steal_voices(self.polyphony_limit() - 1, Some(note));
+        }
+        
+        let sample_rate = self.config.sample_rate;
+        let patch = &self.patch;
+        let tempo_bpm = self.tempo_bpm;
+        let quality = self.quality.level();
+        let controllers = self.controllers;
+        let bend = controllers.pitch_bend * self.bend_range;
+        let pan_law = self.config.pan_law;
+        let exact_sine = self.config.exact_sine;
+        let hrirs = self.hrirs.clone();
+        let paraphonic = self.paraphony.is_some();
+        let pool = &mut self.voice_pool;
+        self.voices.entry(note).or_insert_with(|| {
+            // プールが空になるのは、同時発音数を超えて作ったボイスがまだ戻っていないときだけ
+            let mut voice = pool.pop().unwrap_or_else(|| Voice::new(sample_rate));
+            voice.set_paraphonic(paraphonic);
+            voice.set_pan_law(pan_law);
+            voice.set_exact_sine(exact_sine);
+            voice.set_binaural(hrirs);
+            voice.apply_patch(patch);
+            voice.set_tempo(tempo_bpm);
+            voice.set_quality(quality);
+            voice.set_controllers(controllers);
+            voice.set_pitch_bend(bend);
+            voice
+        })
+    }
+    
+    // ボイスが keep 個になるまで奪い方に従って奪い、プールへ戻す
+    fn steal_voices(&mut self, keep: usize, note: Option<u8>) {
+        while self.voices.len() > keep {
+            let Some(victim) = self.steal_victim(note) else {
+                break;
+            };
+            if let Some(mut voice) = self.voices.remove(&victim) {
+                if voice.is_active() {
+                    self.report(LogEvent::VoiceStolen { note: victim });
+                }
+                voice.reset();
+                self.voice_pool.push(voice);
+            }
+            self.sustained_notes.retain(|sustained| *sustained != victim);
+            self.held_notes.retain(|held| *held != victim);
+            self.drone_notes.retain(|drone| *drone != victim);
+        }
+    }
+    
+    // note のために奪うボイスのノート（リリース中のボイスを優先し、その中で奪い方に従う）。
+    // note が無ければ SameNote は Oldest と同じ順に奪う
+    fn steal_victim(&self, note: Option<u8>) -> Option<u8> {
+        let voices = self.voices.iter();
+        let victim = match self.config.steal_policy {
+            StealPolicy::Oldest => voices.min_by_key(|(_, voice)| (voice.is_active(), Reverse(voice.info().age_samples))),
+            StealPolicy::Quietest => voices.min_by(|(_, a), (_, b)| {
+                a.is_active().cmp(&b.is_active()).then(a.level().total_cmp(&b.level()))
+            }),
+            StealPolicy::SameNote => voices.min_by_key(|(other, voice)| {
+                let same_name = note.is_some_and(|note| **other % 12 == note % 12);
+                (voice.is_active(), !same_name, Reverse(voice.info().age_samples))
+            }),
+        };
+        victim.map(|(note, _)| *note)
     }
     
     pub fn note_on(&mut self, note: u8, velocity: f32) {
-        let voice = self.voices.entry(note).or_insert_with(|| Voice::new(self.sample_rate));
-        voice.note_on(note, velocity);
+        self.note_on_from(None, note, velocity);
+    }
+    
+    // channel は MIDI 入力のチャンネル（0-15）。どのレイヤーの受信フィルターにも合わなければ発音しない
+    pub fn note_on_from(&mut self, channel: Option<u8>, note: u8, velocity: f32) {
+        let velocity = self.config.velocity_curve.apply(velocity);
+        let Some(layers) = self.layer_filters.mask(channel, note, velocity) else {
+            return;
+        };
+        let frequency = self.tuning.frequency(note);
+        let glide_origin = self.glide_origin(note, frequency);
+        self.sustained_notes.retain(|sustained| *sustained != note);
+        self.held_notes.retain(|held| *held != note);
+        self.drone_notes.retain(|drone| *drone != note);
+        let random = self.note_rng.bipolar();
+        self.trigger_paraphony();
+        let voice = self.allocate_voice(note);
+        voice.set_layers(layers);
+        voice.note_on_with_random(note, velocity, random);
+        voice.set_frequency(frequency);
+        if let Some(origin) = glide_origin {
+            voice.start_glide(origin);
+        }
+        self.last_note_frequency = Some(frequency);
+        self.trigger_duck(note);
+        self.current_note = Some(note);
+        self.current_velocity = Some(velocity);
+    }
+    
+    pub fn note_on_with_duration(&mut self, note: u8, velocity: f32, duration: f32) {
+        let velocity = self.config.velocity_curve.apply(velocity);
+        let Some(layers) = self.layer_filters.mask(None, note, velocity) else {
+            return;
+        };
+        let frequency = self.tuning.frequency(note);
+        let glide_origin = self.glide_origin(note, frequency);
+        self.sustained_notes.retain(|sustained| *sustained != note);
+        self.held_notes.retain(|held| *held != note);
+        self.drone_notes.retain(|drone| *drone != note);
+        let random = self.note_rng.bipolar();
+        self.trigger_paraphony();
+        let voice = self.allocate_voice(note);
+        voice.set_layers(layers);
+        voice.note_on_with_duration(note, velocity, duration, random);
+        voice.set_frequency(frequency);
+        if let Some(origin) = glide_origin {
+            voice.start_glide(origin);
+        }
+        self.last_note_frequency = Some(frequency);
+        self.trigger_duck(note);
         self.current_note = Some(note);
         self.current_velocity = Some(velocity);
     }
     
-    pub fn note_on_with_duration(&mut self, note: u8, velocity: f32, duration: f32) {
-        let voice = self.voices.entry(note).or_insert_with(|| Voice::new(self.sample_rate));
-        voice.note_on_with_duration(note, velocity, duration);
-        self.current_note = Some(note);
-        self.current_velocity = Some(velocity);
+    // 新しいノートが滑り始める音高（グライドが無効なら None）。直前にリリースしたボイス（無ければ直前に弾いたノート）、
+    // glide.nearest なら押さえているほかのボイスのうち最も近い音高（無ければ同じく直前のもの）
+    fn glide_origin(&self, note: u8, frequency: f32) -> Option<f32> {
+        if self.patch.glide_time <= 0.0 {
+            return None;
+        }
+        if self.patch.glide_nearest {
+            let distance = |other: f32| (other / frequency).log2().abs();
+            let nearest = self
+                .voices
+                .iter()
+                .filter(|(other, voice)| **other != note && voice.is_active())
+                .map(|(_, voice)| voice.frequency())
+                .min_by(|a, b| distance(*a).total_cmp(&distance(*b)));
+            if nearest.is_some() {
+                return nearest;
+            }
+        }
+        self.last_released_frequency.or(self.last_note_frequency)
+    }
+    
+    // ダッキングのきっかけのノートなら今の位置から掛ける
+    // （ブロック単位の生成ではイベントをブロックの先頭で受け取るので、最大でその区間分早くなる）
+    fn trigger_duck(&mut self, note: u8) {
+        if self.ducker.is_note_trigger(note) {
+            self.ducker.trigger_at(self.sample_clock);
+        }
+    }
+    
+    // サステインペダルを踏んでいる間はリリースをペダルを離すまで、ドローン中は解除するまで遅らせる。hold で固定したノートは無視する
+    pub fn note_off(&mut self, note: u8) {
+        self.note_off_with_velocity(note, DEFAULT_RELEASE_VELOCITY);
+    }
+    
+    // velocity は MIDI のノートオフのリリースベロシティ（0.0-1.0）。ペダルで保留したノートは離鍵時の値でリリースする
+    pub fn note_off_with_velocity(&mut self, note: u8, velocity: f32) {
+        if self.held_notes.contains(&note) {
+            return;
+        }
+        if let Some(voice) = self.voices.get_mut(&note) {
+            if self.patch.drone {
+                if voice.is_active() && !self.drone_notes.contains(&note) {
+                    voice.set_release_velocity(velocity);
+                    self.drone_notes.push(note);
+                }
+            } else if !self.sustain_pedal {
+                voice.note_off(velocity);
+                self.last_released_frequency = Some(voice.frequency());
+            } else if voice.is_active() && !self.sustained_notes.contains(&note) {
+                voice.set_release_velocity(velocity);
+                self.sustained_notes.push(note);
+            }
+        }
+        self.current_note = None;
+        self.current_velocity = None;
+    }
+    
+    pub fn set_sustain_pedal(&mut self, down: bool) {
+        self.sustain_pedal = down;
+        if !down {
+            let mut notes = std::mem::take(&mut self.sustained_notes);
+            for note in notes.drain(..) {
+                if let Some(voice) = self.voices.get_mut(&note) {
+                    voice.note_off(voice.release_velocity());
+                    self.last_released_frequency = Some(voice.frequency());
+                }
+            }
+            self.sustained_notes = notes;
+        }
+    }
+    
+    // ドローンが解除されたら、その間に離されたノートを離鍵時の値でリリースする（ペダルを踏んでいればペダルに移す）。
+    // 音色の変更はどの経路でも次のコントロールレートの境界で確かめる
+    fn release_drone_notes(&mut self) {
+        let mut notes = std::mem::take(&mut self.drone_notes);
+        for note in notes.drain(..) {
+            let Some(voice) = self.voices.get_mut(&note) else {
+                continue;
+            };
+            if self.sustain_pedal {
+                if !self.sustained_notes.contains(&note) {
+                    self.sustained_notes.push(note);
+                }
+            } else {
+                voice.note_off(voice.release_velocity());
+                self.last_released_frequency = Some(voice.frequency());
+            }
+        }
+        self.drone_notes = notes;
+    }
+    
+    // ドローン中に離され、解除を待っているノート
+    pub fn drone_notes(&self) -> &[u8] {
+        &self.drone_notes
+    }
+    
+    pub fn sustain_pedal(&self) -> bool {
+        self.sustain_pedal
+    }
+    
+    // 有効にした時点で鳴っているノートを、持続時間の指定も含めて無視して鳴らし続ける。
+    // 解除すると固定したノートをリリースする（後から弾いたノートは通常どおり）
+    pub fn set_hold(&mut self, hold: bool) {
+        if hold == self.hold {
+            return;
+        }
+        self.hold = hold;
+        if hold {
+            for (&note, voice) in self.voices.iter_mut().filter(|(_, voice)| voice.is_active()) {
+                voice.clear_duration();
+                self.held_notes.push(note);
+            }
+            self.held_notes.sort_unstable();
+            let held = &self.held_notes;
+            self.sustained_notes.retain(|note| !held.contains(note));
+        } else {
+            let mut notes = std::mem::take(&mut self.held_notes);
+            for note in notes.drain(..) {
+                if let Some(voice) = self.voices.get_mut(&note) {
+                    voice.note_off(DEFAULT_RELEASE_VELOCITY);
+                }
+            }
+            self.held_notes = notes;
+        }
+    }
+    
+    pub fn hold(&self) -> bool {
+        self.hold
+    }
+    
+    pub fn held_notes(&self) -> &[u8] {
+        &self.held_notes
+    }
+    
+    // リリースが済んでいないボイスの数: ペダル・hold・ドローンで保留しているノートと、リリース中でまだ回収されていないボイス。
+    // 押さえている鍵盤のボイスは含まないので、voice_count() との差が押さえている鍵盤の数になる
+    pub fn pending_release_count(&self) -> usize {
+        let deferred = self.sustained_notes.iter()
+            .chain(&self.held_notes)
+            .chain(&self.drone_notes)
+            .filter(|note| self.voices.get(note).is_some_and(|voice| voice.is_active()))
+            .count();
+        deferred + self.voices.values().filter(|voice| !voice.is_active()).count()
+    }
+    
+    // ペダルでリリースを待っているノート（鍵盤を押さえているノートは含まない）
+    pub fn sustained_notes(&self) -> &[u8] {
+        &self.sustained_notes
+    }
+    
+    // 予約済みのフレーズやペダル・hold で伸ばしているノートも取り消す
+    pub fn all_notes_off(&mut self) {
+        self.scheduled.clear();
+        self.sustained_notes.clear();
+        self.hold = false;
+        self.held_notes.clear();
+        self.drone_notes.clear();
+        for voice in self.voices.values_mut() {
+            voice.note_off(DEFAULT_RELEASE_VELOCITY);
+        }
+        self.current_note = None;
+        self.current_velocity = None;
+    }
+    
+    // ほかのスレッドからロックを取らずに操作を送る。届いた操作は次のコントロールレートの境界で処理する。
+    // 送るスレッドごとに呼んで別のキューを受け取る（送る側を捨てたキューは次にここで外す）
+    pub fn command_sender(&mut self) -> CommandSender {
+        let closed: usize = self.commands.iter().filter(|commands| commands.is_closed()).map(CommandReceiver::dropped).sum();
+        self.closed_command_drops += closed;
+        self.commands.retain(|commands| !commands.is_closed());
+        let (sender, receiver) = command_queue();
+        self.commands.push(receiver);
+        sender
+    }
+    
+    // Command::Midi で使う機器の番号（同じ名前なら同じ番号）。ベロシティの較正は機器の名前で引く
+    pub fn register_device(&mut self, name: &str) -> usize {
+        if let Some(index) = self.devices.iter().position(|device| &**device == name) {
+            return index;
+        }
+        self.devices.push(Arc::from(name));
+        self.devices.len() - 1
+    }
+    
+    // キューが満杯で捨てた操作の数
+    pub fn dropped_commands(&self) -> usize {
+        self.closed_command_drops + self.commands.iter().map(CommandReceiver::dropped).sum::<usize>()
+    }
+    
+    pub(crate) fn process_commands(&mut self) {
+        for index in 0..self.commands.len() {
+            while let Some(command) = self.commands[index].pop() {
+                self.apply_command(command);
+            }
+        }
+    }
+    
+    pub(crate) fn apply_command(&mut self, command: Command) {
+        if command.is_edit() {
+            self.telemetry.edits.fetch_add(1, Ordering::Relaxed);
+        }
+        match command {
+            Command::Event(event) => self.handle_event(&event),
+            Command::Midi { device, message, arrived } => {
+                match self.devices.get(device).cloned() {
+                    Some(device) => crate::midi::dispatch_live(self, &device, &message, arrived),
+                    None => crate::midi::dispatch_live(self, "", &message, arrived),
+                }
+            }
+            Command::Parameter { index, value } => {
+                if let Some(name) = DIRECT_PARAMETERS.get(index) {
+                    self.set_direct_parameter(name, value);
+                }
+            }
+            Command::NamedParameter { name, value } => {
+                // モーフしていないときの Err はメモリを確保するので、モーフ位置は先に分ける
+                if name.as_str() == MORPH_PARAMETER {
+                    self.set_morph_position(value);
+                } else {
+                    let _ = self.set_parameter(name.as_str(), value);
+                }
+            }
+            Command::Tempo(tempo_bpm) => self.set_tempo(tempo_bpm),
+            Command::NoteFor { note, velocity, seconds } => self.note_on_with_duration(note, velocity, seconds),
+            Command::Schedule { delay, event } => self.schedule(delay, event),
+            Command::Scene { slot, fade } => {
+                self.recall_scene(slot, fade);
+            }
+            Command::Panic => self.panic(),
+            Command::Link { tempo, started } => self.apply_link(tempo, started),
+        }
+    }
+    
+    // ほかのスレッドと共有するパラメータ（書いた値は次のコントロールレートの境界からなめらかに反映する）
+    pub fn params(&self) -> Arc<Params> {
+        self.shared_params.clone()
+    }
+    
+    // ほかのスレッドがロックを取らずに読む状態（publish_telemetry で書いた時点の値）
+    pub fn telemetry(&self) -> Arc<Telemetry> {
+        self.telemetry.clone()
+    }
+    
+    // メーターや拍位置などを telemetry に書く。オーディオのコールバックがブロックごとに呼ぶ（メモリは確保しない）
+    pub fn publish_telemetry(&self) {
+        let telemetry = &*self.telemetry;
+        for (meter, peak) in telemetry.meter.iter().zip(self.output_meter) {
+            meter.store(peak.to_bits(), Ordering::Relaxed);
+        }
+        telemetry.playing.store(self.sequencer.is_playing(), Ordering::Relaxed);
+        telemetry.beats.store(self.audible_beats().to_bits(), Ordering::Relaxed);
+        telemetry.beats_per_bar.store(self.metronome.beats_per_bar(), Ordering::Relaxed);
+        telemetry.tempo.store(self.tempo_bpm.to_bits(), Ordering::Relaxed);
+        telemetry.voices.store(self.voices.len(), Ordering::Relaxed);
+        telemetry.surround.store(self.surround_layout().is_surround(), Ordering::Relaxed);
+        let scenes = (0..crate::scenes::SCENE_COUNT).filter(|slot| self.scenes.is_stored(*slot)).fold(0, |bits, slot| bits | 1 << slot);
+        telemetry.scenes.store(scenes, Ordering::Relaxed);
+        telemetry.dsp_load.store(self.quality.load().to_bits(), Ordering::Relaxed);
+        let mut notes = [0u64; 2];
+        for (note, _) in self.voices.iter().filter(|(_, voice)| voice.is_active()) {
+            notes[*note as usize / 64 % 2] |= 1 << (note % 64);
+        }
+        for (word, bits) in telemetry.notes.iter().zip(notes) {
+            word.store(bits, Ordering::Relaxed);
+        }
+    }
+    
+    // 前の境界から書かれた共有パラメータを読み、目標の値へ動かし始める。
+    // 同じパラメータのオートメーションは止める（両方が動かして取り合わないように）
+    fn read_shared_params(&mut self) {
+        let params = self.shared_params.clone();
+        for (index, field) in params.fields().into_iter().enumerate() {
+            let (writes, value) = field.load();
+            if writes == self.shared_writes[index] {
+                continue;
+            }
+            self.shared_writes[index] = writes;
+            if !value.is_finite() {
+                continue;
+            }
+            let (name, min, max, seconds) = SHARED_PARAMETERS[index];
+            let target = self.clamp_parameter(name, value, min, max);
+            self.parameter_ramps.retain(|ramp| ramp.parameter != name);
+            let samples = (seconds * self.config.sample_rate) as u32;
+            if samples == 0 {
+                self.smoothers[index] = Smoother::default();
+                self.set_direct_parameter(name, target);
+            } else {
+                self.smoothers[index].start(params::shared_values(&self.patch)[index], target, samples);
+            }
+        }
+    }
+    
+    fn is_smoothing(&self) -> bool {
+        self.smoothers.iter().any(Smoother::is_active)
+    }
+    
+    fn advance_smoothers(&mut self) {
+        for (index, (name, ..)) in SHARED_PARAMETERS.iter().enumerate() {
+            if let Some(value) = self.smoothers[index].next_value() {
+                self.set_direct_parameter(name, value);
+            }
+        }
+    }
+    
+    // 緊急停止。リリースを待たずにすべてのボイスを消し、シーケンサーと進行中のランプ・シーンのフェードを止め、
+    // コントローラーを中央に戻し、エフェクトの残響とマスター段の状態（メーター・クリップ表示）を初期化する。
+    // パッチは変えない
+    pub fn panic(&mut self) {
+        self.stop_sequencer();
+        self.all_notes_off();
+        self.recycle_voices(|_| true);
+        if self.paraphony.is_some() {
+            self.paraphony = Some(Paraphony::new(self.config.sample_rate, &self.patch, self.tempo_bpm));
+        }
+        self.scene_fading = false;
+        self.parameter_ramps.clear();
+        self.smoothers = Default::default();
+        self.scheduled_batches.clear();
+        self.controller_ramps.clear();
+        self.controllers = Controllers::default();
+        self.sustain_pedal = false;
+        self.ducker.reset();
+        self.effects.reset();
+        self.crossfeed.reset();
+        self.clipping = false;
+        self.output_meter = [0.0; 2];
+        self.last_all_notes_off = None;
+        self.report(LogEvent::Panic);
+    }
+    
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::NoteOn { note, velocity } => self.note_on(note, velocity),
+            Event::MidiNoteOn { channel, note, velocity } => self.note_on_from(Some(channel), note, velocity),
+            Event::NoteOff { note, velocity } => self.note_off_with_velocity(note, velocity),
+            Event::AllNotesOff => self.all_notes_off(),
+            // 対応表にある番号は読み込みを program::ProgramLoader に任せ、無ければシーンを呼び出す
+            Event::ProgramChange { program } if self.program_map.get(program).is_some() => {
+                self.telemetry.request_program(program);
+            }
+            Event::ProgramChange { program } => {
+                self.recall_scene(program as usize % crate::scenes::SCENE_COUNT, 0.0);
+            }
+            Event::ControlChange { controller, value } => self.control_change(controller, value),
+            Event::Clock => self.clock_tick(),
+            Event::Start => {
+                self.midi_clock.start();
+                if self.tempo_source == TempoSource::MidiClock {
+                    self.play_sequencer();
+                }
+            }
+            Event::Continue => {
+                self.midi_clock.resume();
+                if self.tempo_source == TempoSource::MidiClock {
+                    self.sequencer.resume();
+                }
+            }
+            Event::Stop => {
+                self.midi_clock.stop();
+                if self.tempo_source == TempoSource::MidiClock {
+                    self.stop_sequencer();
+                }
+            }
+        }
+    }
+    
+    // 入力機器から届いたイベント。ノートオンのベロシティは機器の較正プロファイルを通してから
+    // シンセサイザーのベロシティカーブに渡す
+    pub fn handle_device_event(&mut self, device: &str, event: &Event) {
+        self.handle_event(&self.calibrate_event(device, event));
+    }
+    
+    // MIDI 入力のノートオン。handle_device_event と同じくベロシティを較正し、チャンネルはレイヤーの受信フィルターに使う
+    pub fn handle_midi_note_on(&mut self, device: &str, channel: u8, note: u8, velocity: f32) {
+        let velocity = self.config.velocity_profiles.apply(device, velocity);
+        self.note_on_from(Some(channel), note, velocity);
+    }
+    
+    // ノートオンのベロシティを device の較正プロファイルに通す
+    pub fn calibrate_event(&self, device: &str, event: &Event) -> Event {
+        match *event {
+            Event::NoteOn { note, velocity } => Event::NoteOn { note, velocity: self.config.velocity_profiles.apply(device, velocity) },
+            Event::MidiNoteOn { channel, note, velocity } => {
+                Event::MidiNoteOn { channel, note, velocity: self.config.velocity_profiles.apply(device, velocity) }
+            }
+            _ => *event,
+        }
+    }
+    
+    // 演奏中の入力を、届いた時刻に合わせて次のブロックの中のサンプル位置に置く。
+    // 直前のコールバックの開始から届くまでの時間を次のブロックの先頭からのずれとし、先読みの分だけ遅らせる。
+    // 先読みが無効か、オーディオが止まっていれば false を返す（呼び出し側ですぐに処理する）
+    pub fn schedule_live(&mut self, arrived: Instant, event: Event) -> bool {
+        if self.live_lookahead <= 0.0 {
+            return false;
+        }
+        let Some((started, clock)) = self.block_anchor else {
+            return false;
+        };
+        let elapsed = arrived.saturating_duration_since(started).as_secs_f32();
+        if elapsed > LIVE_ANCHOR_TIMEOUT_SECONDS {
+            return false;
+        }
+        let sample = clock + ((elapsed + self.live_lookahead) * self.config.sample_rate) as u64;
+        self.insert_scheduled(ScheduledEvent::new(sample.max(self.sample_clock), event));
+        true
+    }
+    
+    // オーディオのコールバックが1ブロック生成し終えたときに呼ぶ（started はコールバックの開始時刻）
+    pub fn mark_block_rendered(&mut self, started: Instant) {
+        self.block_anchor = Some((started, self.sample_clock));
+    }
+    
+    // 演奏中の MIDI 入力の揺れを吸収する先読み（秒、0 で無効）。出力のバッファ1つ分にこの長さを足しただけ遅れる
+    pub fn set_live_lookahead(&mut self, seconds: f32) {
+        self.live_lookahead = seconds.clamp(0.0, MAX_LIVE_LOOKAHEAD);
+    }
+    
+    pub fn live_lookahead(&self) -> f32 {
+        self.live_lookahead
+    }
+    
+    // 直前のノートオンのベロシティ（ベロシティカーブを通した後）
+    pub fn current_velocity(&self) -> Option<f32> {
+        self.current_velocity
+    }
+    
+    pub fn set_velocity_curve(&mut self, curve: VelocityCurve) {
+        self.config.velocity_curve = curve;
+    }
+    
+    pub fn velocity_curve(&self) -> VelocityCurve {
+        self.config.velocity_curve
+    }
+    
+    pub fn velocity_profiles(&self) -> &VelocityProfiles {
+        &self.config.velocity_profiles
+    }
+    
+    pub fn velocity_profiles_mut(&mut self) -> &mut VelocityProfiles {
+        &mut self.config.velocity_profiles
+    }
+    
+    // クロックの間隔から求めたテンポは、取得元が MIDI クロックのときだけ反映する
+    fn clock_tick(&mut self) {
+        self.midi_clock.tick(self.sample_clock, self.config.sample_rate);
+        if self.tempo_source != TempoSource::MidiClock {
+            return;
+        }
+        if let Some(tempo_bpm) = self.midi_clock.tempo(self.sample_clock, self.config.sample_rate) {
+            // 推定値の細かな揺れで毎パルス全ボイスを更新しない
+            if (tempo_bpm - self.tempo_bpm).abs() >= 0.05 {
+                self.set_tempo(tempo_bpm);
+            }
+        }
+    }
+    
+    // プラグインのホストから処理のブロックの先頭で呼ぶ。取得元が Host のときだけ、テンポと再生・停止を反映し、
+    // 再生中はシーケンサーとメトロノームの位置をホストの位置に合わせる（ループやシークで位置が飛んだときだけ移す）。
+    // メトロノームの1小節の拍数は拍子から求める（4分音符単位）。届かなくなればそのまま内部の時計で進む
+    pub fn set_host_transport(&mut self, transport: HostTransport) {
+        self.host_transport = Some((transport, self.sample_clock));
+        if self.tempo_source != TempoSource::Host {
+            return;
+        }
+        if transport.tempo.is_finite() && transport.tempo > 0.0 && (transport.tempo - self.tempo_bpm).abs() >= 0.01 {
+            self.set_tempo(transport.tempo);
+        }
+        match (transport.playing, self.sequencer.is_playing()) {
+            (true, false) => {
+                self.play_sequencer();
+                self.sequencer.locate(transport.beat);
+            }
+            (true, true) if (self.sequencer.beat() - transport.beat).abs() > HOST_LOCATE_TOLERANCE_BEATS => {
+                self.sequencer.locate(transport.beat);
+            }
+            (false, true) => self.stop_sequencer(),
+            _ => {}
+        }
+        self.metronome.set_beats_per_bar(transport.bar_length().round() as u32);
+        if (self.metronome.beats() - transport.beat).abs() > HOST_LOCATE_TOLERANCE_BEATS {
+            self.metronome.locate(transport.beat);
+        }
+    }
+    
+    // Link のセッションのテンポと開始・停止。取得元が Link のときだけ反映する
+    fn apply_link(&mut self, tempo: Option<f32>, started: Option<bool>) {
+        if self.tempo_source != TempoSource::Link {
+            return;
+        }
+        if let Some(tempo) = tempo.filter(|tempo| (tempo - self.tempo_bpm).abs() >= 0.01) {
+            self.set_tempo(tempo);
+        }
+        match started {
+            Some(true) => self.play_sequencer(),
+            Some(false) => self.stop_sequencer(),
+            None => {}
+        }
+    }
+    
+    // ホストから最後に届いたトランスポート（HOST_TIMEOUT_SECONDS より古ければ None）
+    pub fn host_transport(&self) -> Option<HostTransport> {
+        let (transport, received) = self.host_transport?;
+        let age = self.sample_clock.saturating_sub(received) as f32 / self.config.sample_rate;
+        (age <= HOST_TIMEOUT_SECONDS).then_some(transport)
+    }
+    
+    pub fn set_tempo_source(&mut self, source: TempoSource) {
+        self.tempo_source = source;
+    }
+    
+    pub fn tempo_source(&self) -> TempoSource {
+        self.tempo_source
+    }
+    
+    // 受け取った MIDI クロックの状態（取得元が内部でも追跡する）
+    pub fn midi_clock(&self) -> &MidiClock {
+        &self.midi_clock
+    }
+    
+    // 受け取った MIDI クロックから推定したテンポ（パルスが途切れていれば None）
+    pub fn midi_clock_tempo(&self) -> Option<f32> {
+        self.midi_clock.tempo(self.sample_clock, self.config.sample_rate)
+    }
+    
+    // CC64 はサステインペダル、CC120 と CC123 は発音の停止、それ以外は割り当て表に従ってパラメータを動かす
+    fn control_change(&mut self, controller: u8, value: u8) {
+        match controller {
+            SUSTAIN_PEDAL_CC => return self.set_sustain_pedal(value >= 64),
+            ALL_SOUND_OFF_CC => return self.panic(),
+            ALL_NOTES_OFF_CC => {
+                let window = (PANIC_DOUBLE_TAP.as_secs_f32() * self.config.sample_rate) as u64;
+                let double_tap = self.last_all_notes_off.is_some_and(|last| self.sample_clock - last <= window);
+                if double_tap {
+                    self.panic();
+                } else {
+                    self.all_notes_off();
+                    self.last_all_notes_off = Some(self.sample_clock);
+                }
+                return;
+            }
+            _ => {}
+        }
+        // set_parameter が self を借用するため、割り当て表は一時的に取り出す
+        let mut cc_map = std::mem::take(&mut self.cc_map);
+        let (learned, mapping) = cc_map.handle(controller);
+        if learned {
+            self.report(LogEvent::CcLearned { controller });
+        }
+        if let Some(mapping) = mapping {
+            let _ = self.set_parameter(&mapping.parameter, mapping.value(value));
+        }
+        self.cc_map = cc_map;
+    }
+    
+    pub fn cc_map(&self) -> &CcMap {
+        &self.cc_map
+    }
+    
+    pub fn cc_map_mut(&mut self) -> &mut CcMap {
+        &mut self.cc_map
+    }
+    
+    // params の名前でパラメータを設定する。よく動かすもの（DIRECT_PARAMETERS）は専用の設定関数で
+    // 該当するボイスの値だけを更新し、それ以外はパッチ全体を適用し直す
+    pub fn set_parameter(&mut self, name: &str, value: f32) -> Result<(), String> {
+        if name == MORPH_PARAMETER {
+            return match self.set_morph_position(value) {
+                true => Ok(()),
+                false => Err("no morph in progress".to_string()),
+            };
+        }
+        if !self.set_direct_parameter(name, value) {
+            // CC の割り当てからオーディオスレッドで呼ばれるので、パッチを複製せずにその場で書き換える
+            params::set(&mut self.patch, name, value)?;
+            self.apply_patch_to_voices();
+            self.telemetry.edits.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+    
+    // 複数のパラメータを次のコントロールレートの境界でまとめて設定する（途中まで設定された状態は鳴らない）。
+    // 名前は set_parameter と同じで、1つでも設定できないものがあれば何も設定せずにエラーを返す。
+    // 境界の前に続けて呼ぶと、前の変更の後に続けて設定する
+    pub fn apply_batch(&mut self, changes: Vec<(String, f32)>) -> Result<(), String> {
+        self.validate_batch(&changes)?;
+        self.retired_batch = Vec::new();
+        if self.pending_batch.is_empty() {
+            self.pending_batch = changes;
+        } else {
+            self.pending_batch.extend(changes);
+        }
+        Ok(())
+    }
+    
+    // apply_batch を時刻（now_samples の時計）を指定して予約する。指定の時刻以降の最初のコントロールレートの境界で設定する
+    pub fn apply_batch_at(&mut self, timestamp: u64, changes: Vec<(String, f32)>) -> Result<(), String> {
+        self.validate_batch(&changes)?;
+        let sample = self.compensate_timestamp(timestamp);
+        let index = self.scheduled_batches.partition_point(|(scheduled, _)| *scheduled <= sample);
+        self.scheduled_batches.insert(index, (sample, changes));
+        Ok(())
+    }
+    
+    pub fn set_parameter_at(&mut self, timestamp: u64, name: &str, value: f32) -> Result<(), String> {
+        self.apply_batch_at(timestamp, vec![(name.to_string(), value)])
+    }
+    
+    fn validate_batch(&self, changes: &[(String, f32)]) -> Result<(), String> {
+        let mut probe = None;
+        for (name, value) in changes {
+            if !value.is_finite() {
+                return Err(format!("invalid value for {}: {}", name, value));
+            }
+            if name == MORPH_PARAMETER {
+                if self.morph.is_none() {
+                    return Err("no morph in progress".to_string());
+                }
+                continue;
+            }
+            params::set(probe.get_or_insert_with(|| self.patch.clone()), name, *value)?;
+        }
+        Ok(())
+    }
+    
+    // 時刻になった予約済みの変更を、まとめて設定する変更の後ろに移す
+    fn release_scheduled_batches(&mut self) {
+        while self.scheduled_batches.front().is_some_and(|(sample, _)| *sample <= self.sample_clock) {
+            if let Some((_, changes)) = self.scheduled_batches.pop_front() {
+                if self.pending_batch.is_empty() {
+                    self.pending_batch = changes;
+                } else {
+                    self.pending_batch.extend(changes);
+                }
+            }
+        }
+    }
+    
+    // まとめて設定するのを待っている変更があるか
+    pub fn has_pending_batch(&self) -> bool {
+        !self.pending_batch.is_empty()
+    }
+    
+    // パッチ全体を適用し直す変更を1回の反映にまとめてから、直接設定できるものを順に設定する。
+    // 設定し終えた変更は retired_batch に移し、オーディオスレッドでは解放しない
+    fn apply_pending_batch(&mut self) {
+        let batch = std::mem::take(&mut self.pending_batch);
+        let is_direct = |name: &str| name == MORPH_PARAMETER || DIRECT_PARAMETERS.contains(&name);
+        if batch.iter().any(|(name, _)| !is_direct(name)) {
+            for (name, value) in batch.iter().filter(|(name, _)| !is_direct(name)) {
+                let _ = params::set(&mut self.patch, name, *value);
+            }
+            self.apply_patch_to_voices();
+        }
+        for (name, value) in batch.iter().filter(|(name, _)| is_direct(name)) {
+            if name == MORPH_PARAMETER {
+                // 境界までにモーフィングが終わっていれば、位置の変更だけを捨てる
+                self.set_morph_position(*value);
+            } else {
+                self.set_direct_parameter(name, *value);
+            }
+        }
+        self.retired_batch = batch;
+    }
+    
+    fn set_direct_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "blend" => self.set_blend(value),
+            "brightness" => self.set_brightness(value),
+            "cutoff" => self.set_cutoff(value),
+            "resonance" => self.set_resonance(value),
+            "filter.drive" => self.set_filter_drive(value),
+            "volume" => self.set_volume(value),
+            "voice.saturation" => self.set_voice_saturation(value),
+            "output.trim" => self.set_output_trim(value),
+            "pan" => self.set_pan(value),
+            "depth" => self.set_depth(value),
+            "width" => self.set_width(value),
+            "env.attack" => self.set_attack(value.max(0.001)),
+            "env.decay" => self.set_decay(value.max(0.001)),
+            "env.sustain" => self.set_sustain(value.clamp(0.0, 1.0)),
+            "env.release" => self.set_release(value.max(0.001)),
+            _ => return false,
+        }
+        self.telemetry.edits.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+    
+    // delay_seconds 後から seconds かけてパラメータを to へ動かす（from が None なら開始時点の値から）。
+    // オーディオスレッドで1サンプルごとに値を更新するため、DIRECT_PARAMETERS だけを対象にする。
+    // 同じパラメータのランプは置き換える
+    pub fn automate(
+        &mut self,
+        parameter: &str,
+        from: Option<f32>,
+        to: f32,
+        delay_seconds: f32,
+        seconds: f32,
+        curve: RampCurve,
+    ) -> Result<(), String> {
+        if !DIRECT_PARAMETERS.contains(&parameter) {
+            return Err(format!("parameter cannot be automated: {}", parameter));
+        }
+        let sample_rate = self.config.sample_rate;
+        self.parameter_ramps.retain(|ramp| ramp.parameter != parameter);
+        self.parameter_ramps.push(ParameterRamp {
+            parameter: parameter.to_string(),
+            from,
+            to,
+            start: self.sample_clock + (delay_seconds.max(0.0) * sample_rate) as u64,
+            length: ((seconds.max(0.0) * sample_rate) as u64).max(1),
+            curve,
+        });
+        Ok(())
+    }
+    
+    pub fn parameter_ramps(&self) -> &[ParameterRamp] {
+        &self.parameter_ramps
+    }
+    
+    pub fn clear_parameter_ramps(&mut self) {
+        self.parameter_ramps.clear();
+    }
+    
+    fn advance_parameter_ramps(&mut self) {
+        let mut ramps = std::mem::take(&mut self.parameter_ramps);
+        let sample = self.sample_clock;
+        ramps.retain_mut(|ramp| {
+            if ramp.from.is_none() && sample >= ramp.start {
+                ramp.from = params::get(&self.patch, &ramp.parameter);
+            }
+            let (value, finished) = ramp.value_at(sample);
+            if let Some(value) = value {
+                self.set_direct_parameter(&ramp.parameter, value);
+            }
+            !finished
+        });
+        self.parameter_ramps = ramps;
+    }
+    
+    // sample の位置で発生するイベントを pending_events に集める
+    fn collect_events(&mut self, sample: u64) {
+        if self.sequencer.is_playing() {
+            self.sequencer.advance(self.config.sample_rate, self.tempo_bpm, &mut self.pending_events);
+            if let Some(DuckTrigger::Track(track)) = self.ducker.trigger() {
+                if self.sequencer.hit(*track) {
+                    self.ducker.trigger_at(sample);
+                }
+            }
+        }
+        while self.scheduled.front().is_some_and(|event| event.sample <= sample) {
+            if let Some(scheduled) = self.scheduled.pop_front() {
+                self.pending_events.push(scheduled.event);
+            }
+        }
+    }
+    
+    // delay_seconds 後にイベントを発生させる（同じ時刻のイベントは追加した順）
+    pub fn schedule(&mut self, delay_seconds: f32, event: Event) {
+        self.insert_scheduled(ScheduledEvent::new(
+            self.sample_clock + (delay_seconds.max(0.0) * self.config.sample_rate) as u64,
+            event,
+        ));
+    }
+    
+    // サンプル位置（now_samples の時計）を指定してイベントを発生させる。
+    // 外部のシーケンサーやネットワーク越しの操作が先の時刻を予約するのに使う
+    pub fn schedule_at(&mut self, timestamp: u64, event: Event) {
+        let sample = self.compensate_timestamp(timestamp);
+        self.insert_scheduled(ScheduledEvent::new(sample, event));
+    }
+    
+    pub fn note_on_at(&mut self, timestamp: u64, note: u8, velocity: f32) {
+        self.schedule_at(timestamp, Event::NoteOn { note, velocity });
+    }
+    
+    pub fn note_off_at(&mut self, timestamp: u64, note: u8, velocity: f32) {
+        self.schedule_at(timestamp, Event::NoteOff { note, velocity });
+    }
+    
+    fn insert_scheduled(&mut self, event: ScheduledEvent) {
+        let index = self.scheduled.partition_point(|scheduled| scheduled.sample <= event.sample);
+        self.scheduled.insert(index, event);
+    }
+    
+    // 指定の時刻に timestamp_latency を足す。それでも過ぎていれば今の位置にして遅れとして数える
+    fn compensate_timestamp(&mut self, timestamp: u64) -> u64 {
+        let sample = timestamp.saturating_add((self.timestamp_latency * self.config.sample_rate) as u64);
+        if sample < self.sample_clock {
+            self.late_events += 1;
+            return self.sample_clock;
+        }
+        sample
+    }
+    
+    // オーディオスレッドが1フレームごとに進めるサンプル単位の時計（単調に増え、サンプルレートを変えても戻らない）
+    pub fn now_samples(&self) -> u64 {
+        self.sample_clock
+    }
+    
+    // 時刻指定のイベントをすべて一定の秒数だけ遅らせ、届くまでの時間の揺れを吸収する
+    pub fn set_timestamp_latency(&mut self, seconds: f32) {
+        self.timestamp_latency = seconds.max(0.0);
+    }
+    
+    pub fn timestamp_latency(&self) -> f32 {
+        self.timestamp_latency
+    }
+    
+    // 遅れて届き、指定の時刻より後に発生させた時刻指定のイベントの数
+    pub fn late_events(&self) -> u64 {
+        self.late_events
+    }
+    
+    pub fn scheduled_events(&self) -> usize {
+        self.scheduled.len()
+    }
+    
+    pub fn clear_scheduled(&mut self) {
+        self.scheduled.clear();
+        self.scheduled_batches.clear();
+    }
+    
+    fn dispatch_pending_events(&mut self) {
+        let mut events = std::mem::take(&mut self.pending_events);
+        for event in &events {
+            self.handle_event(event);
+        }
+        events.clear();
+        self.pending_events = events;
+    }
+    
+    // 診断用のテスト信号（ボイスとエフェクトを通さずに出力する）
+    pub fn start_test_signal(&mut self, signal: TestSignal, through_filter: bool) {
+        let mut generator = TestSignalGenerator::new(signal, self.config.sample_rate);
+        if through_filter {
+            generator.set_filter(Some(self.patch_filter()));
+        }
+        self.test_signal = Some(generator);
+    }
+    
+    pub fn stop_test_signal(&mut self) {
+        self.test_signal = None;
+    }
+    
+    pub fn test_signal(&self) -> Option<&TestSignalGenerator> {
+        self.test_signal.as_ref()
+    }
+    
+    // 現在のパッチの設定をしたフィルター（モジュレーションは含まない）
+    pub fn patch_filter(&self) -> LowPassFilter {
+        let mut filter = LowPassFilter::new(self.config.sample_rate);
+        filter.set_cutoff(cutoff_to_hz(self.patch.cutoff));
+        filter.set_resonance(self.patch.resonance);
+        filter.set_drive(self.patch.filter_drive);
+        filter.set_gain_compensation(self.patch.filter_compensation);
+        filter
+    }
+    
+    // パッチのフィルターの振幅特性（UIでの曲線表示用）
+    pub fn filter_response(&self, freqs: &[f32]) -> Vec<f32> {
+        self.patch_filter().magnitude_response(freqs)
+    }
+    
+    // 負荷に応じた品質調整。オーディオコールバックが処理時間 / バッファ長 を報告する
+    // ログには段を変えたときのコールバックの負荷を残す（update は段を下げると平滑化した負荷を測り直すため）
+    pub fn report_dsp_load(&mut self, load: f32) {
+        if let Some(level) = self.quality.update(load) {
+            self.apply_quality(level, load);
+        }
+    }
+    
+    pub fn set_adaptive_quality(&mut self, enabled: bool) {
+        let load = self.quality.load();
+        if let Some(level) = self.quality.set_enabled(enabled) {
+            self.apply_quality(level, load);
+        }
+    }
+    
+    pub fn quality(&self) -> &QualityGovernor {
+        &self.quality
+    }
+    
+    fn apply_quality(&mut self, level: usize, load: f32) {
+        self.report(LogEvent::QualityChanged { level, load });
+        let quality = self.quality.level();
+        self.for_each_voice(|voice| voice.set_quality(quality));
+    }
+    
+    fn polyphony_limit(&self) -> usize {
+        let scale = self.quality.level().polyphony_scale;
+        let polyphony = match self.config.voice_mode {
+            VoiceMode::Paraphonic(notes) => self.config.polyphony.min(notes),
+            _ => self.config.polyphony,
+        };
+        ((polyphony as f32 * scale).ceil() as usize).max(1)
+    }
+    
+    // 解析タップ（無効時は各エンジンの信号を集計しない）
+    pub fn set_analysis_taps(&mut self, enabled: bool) {
+        self.taps.set_enabled(enabled);
+    }
+    
+    pub fn analysis_taps(&self) -> &AnalysisTaps {
+        &self.taps
+    }
+    
+    // メトロノームと出力レイテンシ
+    pub fn metronome(&self) -> &Metronome {
+        &self.metronome
+    }
+    
+    pub fn metronome_mut(&mut self) -> &mut Metronome {
+        &mut self.metronome
+    }
+    
+    // シーケンサーのトラックや特定のノートで掛かるサイドチェインのダッキング
+    pub fn ducker(&self) -> &Ducker {
+        &self.ducker
+    }
+    
+    pub fn ducker_mut(&mut self) -> &mut Ducker {
+        &mut self.ducker
+    }
+    
+    // レイヤー（加算合成・FM）ごとに受け付ける MIDI チャンネルとノート・ベロシティの範囲。
+    // 変更はこれから弾くノートに効く
+    pub fn layer_filters(&self) -> &LayerFilters {
+        &self.layer_filters
+    }
+    
+    pub fn layer_filter(&self, layer: Layer) -> LayerFilter {
+        self.layer_filters.get(layer)
+    }
+    
+    pub fn set_layer_filter(&mut self, layer: Layer, filter: LayerFilter) {
+        self.layer_filters.set(layer, filter);
+    }
+    
+    // プログラムチェンジの番号と音色の対応表
+    pub fn program_map(&self) -> &ProgramMap {
+        &self.program_map
+    }
+    
+    pub fn program_map_mut(&mut self) -> &mut ProgramMap {
+        &mut self.program_map
+    }
+    
+    // 読み込みを待っているプログラムチェンジ（続けて届いたときは最後のものだけ）
+    pub fn take_program_request(&mut self) -> Option<(u8, ProgramTarget)> {
+        let program = self.telemetry.take_program_request()?;
+        self.program_map.get(program).map(|target| (program, target.clone()))
+    }
+    
+    // オーディオバックエンドが報告したレイテンシ（コールバック時刻から再生時刻まで）
+    pub fn set_output_latency(&mut self, samples: u32) {
+        self.output_latency = samples;
+    }
+    
+    pub fn set_latency_offset(&mut self, milliseconds: f32) {
+        self.latency_offset = self.clamp_parameter("latency offset", milliseconds, -500.0, 500.0);
+    }
+    
+    // 生成済みの音が実際に聞こえるまでの合計レイテンシ（秒）
+    pub fn output_latency_seconds(&self) -> f32 {
+        (self.output_latency as f32 / self.config.sample_rate + self.latency_offset / 1000.0).max(0.0)
+    }
+    
+    // 今スピーカーから聞こえている拍位置（レイテンシ分だけ生成位置から遅らせる）
+    pub fn audible_beats(&self) -> f64 {
+        let latency_beats = self.output_latency_seconds() as f64 * self.tempo_bpm as f64 / 60.0;
+        (self.metronome.beats() - latency_beats).max(0.0)
+    }
+    
+    // シーン
+    // 呼び出しで写す先（今のパッチとクロスフェードの置き場所）の領域はここで確保しておく
+    pub fn store_scene(&mut self, slot: usize) -> bool {
+        let stored = self.scenes.store(slot, &self.patch);
+        if stored {
+            self.current_scene = Some(slot);
+            self.scene_fade.from.reserve_for(&self.patch);
+            self.scene_fade.to.reserve_for(&self.patch);
+            if let Some(pending) = &mut self.pending_patch {
+                pending.reserve_for(&self.patch);
+            }
+        }
+        stored
+    }
+    
+    // fade_seconds が 0 なら即座に、それ以外はクロスフェードで切り替える。
+    // プログラムチェンジからオーディオスレッドで呼ばれるので、シーンは確保済みの領域に写し、モーフィングは解放せずに退ける
+    pub fn recall_scene(&mut self, slot: usize, fade_seconds: f32) -> bool {
+        let Some(scene) = self.scenes.get(slot) else {
+            return false;
+        };
+        self.current_scene = Some(slot);
+        if self.morph.is_some() {
+            self.retired_morph = self.morph.take();
+        }
+        if fade_seconds <= 0.0 {
+            self.scene_fading = false;
+            self.patch.clone_from(scene);
+            self.apply_patch_to_voices();
+        } else {
+            self.scene_fade.from.clone_from(&self.patch);
+            self.scene_fade.to.clone_from(scene);
+            self.scene_fade.elapsed = 0.0;
+            self.scene_fade.duration = fade_seconds;
+            self.scene_fading = true;
+        }
+        true
+    }
+    
+    // from と to の間のモーフィングを始める（シーンのクロスフェードは止める）
+    pub fn start_morph(&mut self, from: PatchState, to: PatchState, position: f32) {
+        self.scene_fading = false;
+        self.retired_morph = None;
+        for patch in [&from, &to] {
+            self.patch.reserve_for(patch);
+            self.reserve_patch(patch);
+        }
+        self.morph = Some(PatchMorph { from, to, position: 0.0 });
+        self.set_morph_position(position);
+    }
+    
+    // 連続値のパラメータを補間して鳴っているボイスにも反映する。モーフィング中でなければ false
+    pub fn set_morph_position(&mut self, position: f32) -> bool {
+        let Some(morph) = self.morph.as_mut() else {
+            return false;
+        };
+        morph.position = position.clamp(0.0, 1.0);
+        self.patch.interpolate(&morph.from, &morph.to, morph.position);
+        let patch = &self.patch;
+        for voice in self.voices.values_mut() {
+            voice.apply_patch(patch);
+        }
+        true
+    }
+    
+    pub fn morph(&self) -> Option<&PatchMorph> {
+        self.morph.as_ref()
+    }
+    
+    // モーフィングをやめる（パッチはその時点の補間結果のまま）
+    pub fn stop_morph(&mut self) {
+        self.morph = None;
+    }
+    
+    pub fn scenes(&self) -> &SceneBank {
+        &self.scenes
+    }
+    
+    pub fn current_scene(&self) -> Option<usize> {
+        self.current_scene
+    }
+    
+    // 進行中のシーンのクロスフェード
+    pub fn scene_fade(&self) -> Option<&SceneFade> {
+        self.scene_fading.then_some(&self.scene_fade)
+    }
+    
+    // コントロールレートで進める処理（シーンのクロスフェード、コントローラーのランプ）
+    fn advance_control_block(&mut self, seconds: f32) {
+        if self.commands.iter().any(|commands| !commands.is_empty()) {
+            self.process_commands();
+        }
+        self.read_shared_params();
+        self.reap_voices();
+        if self.paraphony.is_some() {
+            self.advance_paraphony();
+        }
+        if self.pending_patch.is_some() {
+            self.swap_pending_patch();
+        }
+        if !self.drone_notes.is_empty() && !self.patch.drone {
+            self.release_drone_notes();
+        }
+        if !self.scheduled_batches.is_empty() {
+            self.release_scheduled_batches();
+        }
+        if !self.pending_batch.is_empty() {
+            self.apply_pending_batch();
+        }
+        if self.scene_fading {
+            self.advance_scene_fade(seconds);
+        }
+        if !self.controller_ramps.is_empty() {
+            self.advance_controller_ramps(seconds);
+        }
+        // ダッキングの値をモジュレーションのソースとしてボイスへ渡す
+        if self.ducker.is_active() || self.controllers.duck != 0.0 {
+            self.controllers.duck = self.ducker.level();
+            let controllers = self.controllers;
+            self.for_each_voice(|voice| voice.set_controllers(controllers));
+        }
+    }
+    
+    // リリースが終わったボイスを取り除き、残っていた保留・固定の記録も消す
+    fn reap_voices(&mut self) {
+        let before = self.voices.len();
+        self.recycle_voices(Voice::is_released);
+        if self.voices.len() != before {
+            let voices = &self.voices;
+            self.sustained_notes.retain(|note| voices.contains_key(note));
+            self.held_notes.retain(|note| voices.contains_key(note));
+            self.drone_notes.retain(|note| voices.contains_key(note));
+        }
+    }
+    
+    // 条件に合うボイスを取り除き、発音前の状態に戻してプールへ返す
+    fn recycle_voices(&mut self, mut remove: impl FnMut(&Voice) -> bool) {
+        for (_, mut voice) in self.voices.extract_if(|_, voice| remove(voice)) {
+            voice.reset();
+            self.voice_pool.push(voice);
+        }
+    }
+    
+    // 最大同時発音数分のボイスを今のサンプルレートで作り直してプールに置く（構築時とサンプルレートの変更時）
+    fn fill_voice_pool(&mut self) {
+        let sample_rate = self.config.sample_rate;
+        self.voice_pool.clear();
+        self.voice_pool.extend((0..self.config.polyphony).map(|_| Voice::new(sample_rate)));
+    }
+    
+    // 押さえているノートが無い状態からの最初のノートで共有のエンベロープを掛け直し、前のリリースの残りを止める
+    fn trigger_paraphony(&mut self) {
+        let Some(paraphony) = self.paraphony.as_mut() else {
+            return;
+        };
+        if !paraphony.is_gated() {
+            paraphony.trigger();
+            self.recycle_voices(|voice| !voice.is_active());
+        }
+    }
+    
+    // 押さえているノートがあれば、離したノートはすぐに止める。すべて離したら最後に鳴っていたノートを
+    // 共有のエンベロープのリリースで鳴らし、リリースが終わったら取り除く
+    fn advance_paraphony(&mut self) {
+        let held = self.voices.values().any(Voice::is_active);
+        let Some(paraphony) = self.paraphony.as_mut() else {
+            return;
+        };
+        let before = self.voices.len();
+        if held {
+            self.recycle_voices(|voice| !voice.is_active());
+        } else if paraphony.is_gated() {
+            paraphony.release();
+        } else if paraphony.is_idle() {
+            self.recycle_voices(|_| true);
+        }
+        if self.voices.len() != before {
+            let voices = &self.voices;
+            self.sustained_notes.retain(|note| voices.contains_key(note));
+            self.held_notes.retain(|note| voices.contains_key(note));
+            self.drone_notes.retain(|note| voices.contains_key(note));
+        }
+    }
+    
+    // ボイスの割り当て方。切り替えると鳴っているボイスは消える
+    pub fn set_voice_mode(&mut self, mode: VoiceMode) {
+        let mode = match mode {
+            VoiceMode::Paraphonic(notes) => VoiceMode::Paraphonic(notes.max(1)),
+            mode => mode,
+        };
+        self.config.voice_mode = mode;
+        self.recycle_voices(|_| true);
+        self.sustained_notes.clear();
+        self.held_notes.clear();
+        self.drone_notes.clear();
+        self.paraphony = match mode {
+            VoiceMode::Paraphonic(_) => Some(Paraphony::new(self.config.sample_rate, &self.patch, self.tempo_bpm)),
+            _ => None,
+        };
+    }
+    
+    pub fn voice_mode(&self) -> VoiceMode {
+        self.config.voice_mode
+    }
+    
+    // 最大同時発音数。増やした分のボイスはここで作ってプールに置き、減らしたときに超えている分は
+    // 次のノートオンで奪う（リリース中のボイスはそれまで鳴らし続ける）
+    // 鳴っているボイスが新しい同時発音数を超えていれば、その場で奪い方に従って奪う
+    pub fn set_polyphony(&mut self, polyphony: usize) {
+        let polyphony = polyphony.max(1);
+        self.config.polyphony = polyphony;
+        self.steal_voices(self.polyphony_limit(), None);
+        let sample_rate = self.config.sample_rate;
+        let allocated = self.voices.len() + self.voice_pool.len();
+        if polyphony > allocated {
+            self.voice_pool.extend((allocated..polyphony).map(|_| Voice::new(sample_rate)));
+        } else {
+            self.voice_pool.truncate(polyphony.saturating_sub(self.voices.len()));
+        }
+        self.voices.reserve((polyphony + 1).saturating_sub(self.voices.len()));
+        if let Some(workers) = &mut self.workers {
+            workers.reserve(polyphony + 1);
+        }
+    }
+    
+    pub fn polyphony(&self) -> usize {
+        self.config.polyphony
+    }
+    
+    pub fn set_steal_policy(&mut self, policy: StealPolicy) {
+        self.config.steal_policy = policy;
+    }
+    
+    pub fn steal_policy(&self) -> StealPolicy {
+        self.config.steal_policy
+    }
+    
+    // パラフォニックモードの共有のフィルターとエンベロープ（ほかのモードでは None）
+    pub fn paraphony(&self) -> Option<&Paraphony> {
+        self.paraphony.as_ref()
+    }
+    
+    // 2 つのシーンで異なるパラメータだけを補間してボイスに反映する
+    fn advance_scene_fade(&mut self, seconds: f32) {
+        let fade = &mut self.scene_fade;
+        fade.elapsed += seconds;
+        let finished = fade.progress() >= 1.0;
+        if finished {
+            // 複製すると倍音などの配列を確保し直すので、行き先のパッチと入れ替える
+            std::mem::swap(&mut self.patch, &mut fade.to);
+        } else {
+            self.patch.interpolate(&fade.from, &fade.to, fade.progress());
+        }
+        let patch = &self.patch;
+        let to = if finished { patch } else { &fade.to };
+        for voice in self.voices.values_mut() {
+            voice.apply_patch_changes(patch, &fade.from, to);
+        }
+        // 終えたフェードの from・to は次のフェードの写し先に使う
+        self.scene_fading = !finished;
+    }
+    
+    // ピッチベンド・モジュレーションホイール。ramp_seconds が 0 なら即座に、それ以外は直線的に動かす。
+    pub fn set_controller(&mut self, controller: Controller, value: f32, ramp_seconds: f32) {
+        let (min, max) = controller.range();
+        let value = value.clamp(min, max);
+        self.controller_ramps.retain(|ramp| ramp.controller != controller);
+        if ramp_seconds <= 0.0 {
+            self.controllers.set(controller, value);
+            self.apply_controllers();
+        } else {
+            self.controller_ramps.push(ControllerRamp {
+                controller,
+                from: self.controllers.get(controller),
+                to: value,
+                elapsed: 0.0,
+                duration: ramp_seconds,
+            });
+        }
+    }
+    
+    pub fn set_pitch_bend(&mut self, value: f32) {
+        self.set_controller(Controller::PitchBend, value, 0.0);
+    }
+    
+    pub fn set_mod_wheel(&mut self, value: f32) {
+        self.set_controller(Controller::ModWheel, value, 0.0);
+    }
+    
+    pub fn controllers(&self) -> Controllers {
+        self.controllers
+    }
+    
+    pub fn is_controller_ramping(&self, controller: Controller) -> bool {
+        self.controller_ramps.iter().any(|ramp| ramp.controller == controller)
+    }
+    
+    fn advance_controller_ramps(&mut self, seconds: f32) {
+        let controllers = &mut self.controllers;
+        self.controller_ramps.retain_mut(|ramp| {
+            let (value, finished) = ramp.advance(seconds);
+            controllers.set(ramp.controller, value);
+            !finished
+        });
+        self.apply_controllers();
+    }
+    
+    fn apply_controllers(&mut self) {
+        let controllers = self.controllers;
+        let bend = controllers.pitch_bend * self.bend_range;
+        self.for_each_voice(|voice| {
+            voice.set_controllers(controllers);
+            voice.set_pitch_bend(bend);
+        });
+    }
+    
+    // ピッチベンドを振り切ったときの変化（半音, 0 でルーティングだけになる）
+    pub fn set_bend_range(&mut self, semitones: f32) {
+        self.bend_range = self.clamp_parameter("bend.range", semitones, 0.0, MAX_BEND_RANGE);
+        self.apply_controllers();
     }
     
-    pub fn note_off(&mut self, note: u8) {
-        if let Some(voice) = self.voices.get_mut(&note) {
-            voice.note_off();
-        }
-        self.current_note = None;
-        self.current_velocity = None;
+    pub fn bend_range(&self) -> f32 {
+        self.bend_range
+    }
+    
+    // シーケンサー
+    pub fn sequencer(&self) -> &Sequencer {
+        &self.sequencer
+    }
+    
+    pub fn sequencer_mut(&mut self) -> &mut Sequencer {
+        &mut self.sequencer
+    }
+    
+    pub fn play_sequencer(&mut self) {
+        self.sequencer.play();
+        self.metronome.reset();
+    }
+    
+    pub fn stop_sequencer(&mut self) {
+        self.sequencer.stop(&mut self.pending_events);
+        self.dispatch_pending_events();
+    }
+    
+    pub fn clear_sequencer(&mut self) {
+        self.sequencer.clear(&mut self.pending_events);
+        self.dispatch_pending_events();
     }
     
     pub fn next_sample(&mut self) -> f32 {
-        let mut sample = 0.0;
-        for voice in self.voices.values_mut() {
-            sample += voice.next_sample();
+        let [left, right] = self.next_frame();
+        (left + right) * 0.5
+    }
+    
+    // ステレオ1フレーム分を生成する（サラウンド出力中は後方をステレオへ畳み込む）
+    pub fn next_frame(&mut self) -> [f32; 2] {
+        let (front, rear) = self.next_surround_frame();
+        if self.surround_layout().is_surround() {
+            downmix(front, rear)
+        } else {
+            front
         }
-        sample / self.voices.len() as f32 // Average voices for polyphony
     }
     
-    // パラメータ設定
-    pub fn set_blend_ratio(&mut self, ratio: f32) {
+    // 前方・後方のステレオペアで1フレーム分を生成する（ステレオ出力では後方は常に無音）
+    pub fn next_surround_frame(&mut self) -> ([f32; 2], [f32; 2]) {
+        let control_block_done = std::mem::take(&mut self.control_block_done);
+        if let Some(generator) = self.test_signal.as_mut() {
+            let sample = generator.next_sample();
+            self.sample_clock += 1;
+            return ([sample; 2], [0.0; 2]);
+        }
+        if self.sample_clock.is_multiple_of(MOD_BLOCK_SIZE as u64) && !control_block_done {
+            self.advance_control_block(MOD_BLOCK_SIZE as f32 / self.config.sample_rate);
+        }
+        self.collect_events(self.sample_clock);
+        if !self.pending_events.is_empty() {
+            self.dispatch_pending_events();
+        }
+        if !self.parameter_ramps.is_empty() {
+            self.advance_parameter_ramps();
+        }
+        if self.is_smoothing() {
+            self.advance_smoothers();
+        }
+        
+        let mut frame = [0.0; 2];
+        let mut rear = [0.0; 2];
+        let surround = self.surround_layout().is_surround();
+        let mut taps = TapFrame::default();
+        let tapping = self.taps.is_enabled();
         for voice in self.voices.values_mut() {
-            voice.set_blend(ratio);
+            let [left, right] = voice.next_frame();
+            if surround {
+                let [front_gain, rear_gain] = voice.depth_gains();
+                frame = [frame[0] + left * front_gain, frame[1] + right * front_gain];
+                rear = [rear[0] + left * rear_gain, rear[1] + right * rear_gain];
+            } else {
+                frame[0] += left;
+                frame[1] += right;
+            }
+            if tapping {
+                taps.add(&voice.taps());
+            }
         }
+        if !self.voices.is_empty() {
+            // Average voices for polyphony
+            let count = self.voices.len() as f32;
+            frame = [frame[0] / count, frame[1] / count];
+            rear = [rear[0] / count, rear[1] / count];
+            taps.scale(1.0 / count);
+        }
+        if tapping {
+            self.taps.push(taps);
+        }
+        if let Some(paraphony) = self.paraphony.as_mut() {
+            paraphony.sync(&self.patch);
+            (frame, rear) = paraphony.process(frame, rear);
+        }
+        self.finish_frame(frame, rear)
     }
     
-    pub fn set_blend(&mut self, blend: f32) {
-        for voice in self.voices.values_mut() {
-            voice.set_blend(blend);
+    // ボイスのミックス後の処理（エフェクト、メトロノーム、クリップ検出）。
+    // エフェクトとメトロノームは前方のペアだけに掛け、メーターは後方を畳み込んだ値で測る
+    fn finish_frame(&mut self, frame: [f32; 2], rear: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        let mut output = self.effects.process(frame);
+        let mut rear = rear;
+        if self.ducker.is_active() {
+            let gain = self.ducker.next_gain(self.sample_clock, self.config.sample_rate, self.tempo_bpm);
+            output = [output[0] * gain, output[1] * gain];
+            rear = [rear[0] * gain, rear[1] * gain];
+        }
+        let click = self.metronome.next_sample(self.config.sample_rate, self.tempo_bpm);
+        output = [output[0] + click, output[1] + click];
+        if self.config.analog_floor.is_enabled() {
+            output = self.analog_floor.process(output);
+        }
+        if self.config.crossfeed && !self.surround_layout().is_surround() {
+            output = self.crossfeed.process(output);
+        }
+        let mix = downmix(output, rear);
+        self.fold_down.process(mix);
+        if self.mono_check {
+            output = fold_down(mix);
+            rear = [0.0; 2];
         }
+        
+        // クリップは連続区間の先頭だけ記録する
+        let peak = output[0].abs().max(output[1].abs()).max(rear[0].abs()).max(rear[1].abs());
+        let clipping = peak > 1.0;
+        if clipping && !self.clipping {
+            self.report(LogEvent::Clipped { peak });
+        }
+        self.clipping = clipping;
+        for (meter, sample) in self.output_meter.iter_mut().zip(mix) {
+            *meter = sample.abs().max(*meter * self.meter_decay);
+        }
+        self.sample_clock += 1;
+        (output, rear)
     }
     
-    pub fn set_volume(&mut self, volume: f32) {
-        for voice in self.voices.values_mut() {
-            voice.set_volume(volume); // Assuming set_volume exists on Voice
+    // ブロック単位で生成する。ワーカーがある場合はボイスを複数スレッドで分担し、
+    // イベントの位置でブロックを区切ってサンプル単位のタイミングを保つ。
+    // サラウンド出力中（ボイスごとに前後へ振り分ける）とパラメータのランプ中（1サンプルごとに値を更新する）は
+    // オーディオスレッドだけで生成する。
+    pub fn render_block(&mut self, out: &mut [[f32; 2]]) {
+        if self.workers.is_none() || self.needs_serial_rendering() {
+            for frame in out.iter_mut() {
+                *frame = self.next_frame();
+            }
+            return;
+        }
+        
+        for block in out.chunks_mut(MAX_PARALLEL_FRAMES) {
+            let mut offset = 0;
+            while offset < block.len() {
+                // コントロールレートの境界までを1区間とする
+                let until_boundary = MOD_BLOCK_SIZE - (self.sample_clock % MOD_BLOCK_SIZE as u64) as usize;
+                let length = until_boundary.min(block.len() - offset);
+                // 境界のコントロールブロックで共有パラメータのスムージングやランプが始まることがあるので、
+                // 先に進めてから生成方法を決める
+                if self.sample_clock.is_multiple_of(MOD_BLOCK_SIZE as u64) {
+                    self.advance_control_block(MOD_BLOCK_SIZE as f32 / self.config.sample_rate);
+                    self.control_block_done = true;
+                }
+                if self.needs_serial_rendering() {
+                    for frame in &mut block[offset..offset + length] {
+                        *frame = self.next_frame();
+                    }
+                } else {
+                    self.control_block_done = false;
+                    self.render_segment(&mut block[offset..offset + length]);
+                }
+                offset += length;
+            }
         }
     }
     
-    pub fn set_filter_cutoff(&mut self, cutoff: f32) {
-        for voice in self.voices.values_mut() {
-            voice.set_cutoff(cutoff);
+    fn needs_serial_rendering(&self) -> bool {
+        self.taps.is_enabled()
+            || self.test_signal.is_some()
+            || self.surround_layout().is_surround()
+            || !self.parameter_ramps.is_empty()
+            || self.is_smoothing()
+            || self.paraphony.is_some()
+    }
+    
+    fn render_segment(&mut self, out: &mut [[f32; 2]]) {
+        // 先にシーケンサーと時刻指定のイベントからイベントの位置を集める（どちらもボイスの状態に依存しない）
+        self.event_offsets.clear();
+        if self.sequencer.is_playing() || !self.scheduled.is_empty() {
+            for offset in 0..out.len() {
+                let before = self.pending_events.len();
+                self.collect_events(self.sample_clock + offset as u64);
+                if self.pending_events.len() > before {
+                    self.event_offsets.push((offset, self.pending_events.len()));
+                }
+            }
+        }
+        
+        out.fill([0.0; 2]);
+        let mut events = std::mem::take(&mut self.pending_events);
+        let offsets = std::mem::take(&mut self.event_offsets);
+        let (mut cursor, mut dispatched) = (0, 0);
+        for &(offset, end) in &offsets {
+            self.mix_voices(&mut out[cursor..offset]);
+            for event in &events[dispatched..end] {
+                self.handle_event(event);
+            }
+            cursor = offset;
+            dispatched = end;
+        }
+        self.mix_voices(&mut out[cursor..]);
+        events.clear();
+        self.pending_events = events;
+        self.event_offsets = offsets;
+        
+        for frame in out.iter_mut() {
+            *frame = self.finish_frame(*frame, [0.0; 2]).0;
         }
     }
     
-    pub fn set_cutoff(&mut self, cutoff: f32) {
-        for voice in self.voices.values_mut() {
-            voice.set_cutoff(cutoff * 20000.0);
+    // 前方・後方のペアでブロック単位で生成する（サラウンド出力用）
+    pub fn render_surround_block(&mut self, front: &mut [[f32; 2]], rear: &mut [[f32; 2]]) {
+        if !self.surround_layout().is_surround() {
+            self.render_block(front);
+            rear.fill([0.0; 2]);
+            return;
+        }
+        for (front, rear) in front.iter_mut().zip(rear.iter_mut()) {
+            (*front, *rear) = self.next_surround_frame();
         }
     }
     
-    pub fn set_filter_resonance(&mut self, resonance: f32) {
+    // 外部の生成器に block_size サンプルごとに次のイベントを尋ねながら out を生成する。
+    // generator はブロックの先頭のサンプル位置（now_samples の時計）と長さを受け取り、返したイベントはそのブロックの先頭で発生する。
+    // CLI やシーケンサーを使わずに、ライブラリの上で生成的な音楽を作るためのもの
+    pub fn drive(&mut self, out: &mut [[f32; 2]], block_size: usize, mut generator: impl FnMut(u64, usize) -> Vec<Event>) {
+        for block in out.chunks_mut(block_size.max(1)) {
+            for event in generator(self.sample_clock, block.len()) {
+                self.handle_event(&event);
+            }
+            self.render_block(block);
+        }
+    }
+    
+    // ヘッドホン用のクロスフィード（サラウンド出力中は掛けない）
+    pub fn set_crossfeed(&mut self, enabled: bool) {
+        if enabled && !self.config.crossfeed {
+            self.crossfeed.reset();
+        }
+        self.config.crossfeed = enabled;
+    }
+    
+    pub fn crossfeed(&self) -> bool {
+        self.config.crossfeed
+    }
+    
+    // ビンテージ機器風のノイズフロアと電源ハム（セッションの設定でパッチには保存しない）
+    pub fn set_analog_floor(&mut self, settings: AnalogFloorSettings) {
+        self.config.analog_floor = settings;
+        self.analog_floor.set_settings(settings);
+    }
+    
+    pub fn analog_floor(&self) -> AnalogFloorSettings {
+        self.config.analog_floor
+    }
+    
+    // 出力機器のチャンネル配置（オーディオ出力の開始時に設定される）
+    pub fn set_output_layout(&mut self, layout: ChannelLayout) {
+        self.output_layout = layout;
+    }
+    
+    pub fn output_layout(&self) -> ChannelLayout {
+        self.output_layout
+    }
+    
+    // サラウンド出力の有効・無効（無効にすると機器のチャンネル数によらずステレオで出力する）
+    pub fn set_surround(&mut self, enabled: bool) {
+        self.surround = enabled;
+    }
+    
+    // 実際にミックスに使う配置
+    pub fn surround_layout(&self) -> ChannelLayout {
+        if self.surround {
+            self.output_layout
+        } else {
+            ChannelLayout::Stereo
+        }
+    }
+    
+    // 全ボイスを out に加算し、ボイス数で平均する
+    fn mix_voices(&mut self, out: &mut [[f32; 2]]) {
+        if out.is_empty() || self.voices.is_empty() {
+            return;
+        }
+        let count = self.voices.len() as f32;
+        match self.workers.as_mut() {
+            Some(workers) => workers.render(self.voices.values_mut(), out),
+            None => {
+                for voice in self.voices.values_mut() {
+                    for frame in out.iter_mut() {
+                        let [left, right] = voice.next_frame();
+                        frame[0] += left;
+                        frame[1] += right;
+                    }
+                }
+            }
+        }
+        for frame in out.iter_mut() {
+            *frame = [frame[0] / count, frame[1] / count];
+        }
+    }
+    
+    // ボイスのレンダリングに使うスレッド数（1 = オーディオスレッドのみ）
+    pub fn set_render_threads(&mut self, threads: usize) {
+        let threads = threads.max(1);
+        self.config.render_threads = threads;
+        self.workers = (threads > 1).then(|| VoiceWorkers::new(threads, self.config.polyphony + 1));
+    }
+    
+    pub fn render_threads(&self) -> usize {
+        self.config.render_threads
+    }
+    
+    fn for_each_voice(&mut self, mut f: impl FnMut(&mut Voice)) {
         for voice in self.voices.values_mut() {
-            voice.set_resonance(resonance);
+            f(voice);
         }
     }
     
-    pub fn set_resonance(&mut self, resonance: f32) {
+    // パッチ全体の取得と置き換え
+    pub fn patch(&self) -> &PatchState {
+        &self.patch
+    }
+    
+    // ポリフォニックのグライド。新しいボイスは直前にリリースしたボイス（nearest なら押さえているうち最も近いボイス）の音高から滑る
+    pub fn set_glide(&mut self, seconds: f32, nearest: bool) {
+        self.patch.glide_time = seconds.clamp(0.0, MAX_GLIDE_TIME);
+        self.patch.glide_nearest = nearest;
+        let time = self.patch.glide_time;
+        self.for_each_voice(|voice| voice.set_glide_time(time));
+    }
+    
+    pub fn set_patch(&mut self, mut patch: PatchState) {
+        self.prepare_patch(&mut patch);
+        self.patch = patch;
+        self.apply_patch_to_voices();
+    }
+    
+    fn apply_patch_to_voices(&mut self) {
+        let patch = &self.patch;
         for voice in self.voices.values_mut() {
-            voice.set_resonance(resonance);
+            voice.apply_patch(patch);
         }
     }
     
-    pub fn set_envelope(&mut self, envelope: Envelope) {
+    // patch を今のパッチにする前に、オーディオスレッドで写したり反映したりするときに要るメモリを確保する
+    // （保存済みのシーンを写せる領域と、ボイス・クロスフェードの置き場所の領域）
+    fn prepare_patch(&mut self, patch: &mut PatchState) {
+        for slot in 0..crate::scenes::SCENE_COUNT {
+            if let Some(scene) = self.scenes.get(slot) {
+                patch.reserve_for(scene);
+            }
+        }
+        self.reserve_patch(patch);
+    }
+    
+    fn reserve_patch(&mut self, patch: &PatchState) {
+        self.scene_fade.from.reserve_for(patch);
+        self.scene_fade.to.reserve_for(patch);
+        for voice in self.voices.values_mut().chain(self.voice_pool.iter_mut()) {
+            voice.reserve_patch(patch);
+        }
+    }
+    
+    // 読み込み・解釈を済ませた音色を、次のコントロールレートの境界（MOD_BLOCK_SIZE サンプルごと）で差し替える。
+    // ボイスが反映に使うメモリの確保と外した音色の解放はここ（呼び出し側のスレッド）で済ませ、
+    // オーディオスレッドでは中身の交換と異なるパラメータのボイスへの反映だけを行う。差し替え前に続けて呼ぶと最後のものだけが残る
+    pub fn queue_patch(&mut self, mut patch: PatchState) {
+        self.retired_patch = None;
+        self.retired_morph = None;
+        self.prepare_patch(&mut patch);
+        self.pending_patch = Some(Box::new(patch));
+    }
+    
+    // 差し替えを待っている音色があるか
+    pub fn has_pending_patch(&self) -> bool {
+        self.pending_patch.is_some()
+    }
+    
+    fn swap_pending_patch(&mut self) {
+        let Some(mut patch) = self.pending_patch.take() else {
+            return;
+        };
+        std::mem::swap(&mut self.patch, &mut patch);
+        if self.morph.is_some() {
+            self.retired_morph = self.morph.take();
+        }
+        self.scene_fading = false;
+        // ボイスは外した音色の値のままなので、異なるパラメータだけを設定し直す
+        let retired = self.retired_patch.insert(patch);
+        let patch = &self.patch;
         for voice in self.voices.values_mut() {
-            voice.set_envelope(envelope);
+            voice.apply_patch_changes(patch, retired, patch);
         }
     }
     
-    pub fn set_attack(&mut self, attack: f32) {
+    // パラメータ設定
+    pub fn set_blend_ratio(&mut self, ratio: f32) {
+        self.set_blend(ratio);
+    }
+    
+    pub fn set_blend(&mut self, blend: f32) {
+        let blend = self.clamp_parameter("blend", blend, 0.0, 1.0);
+        self.patch.blend = blend;
+        self.for_each_voice(|voice| voice.set_blend(blend));
+    }
+    
+    pub fn set_brightness(&mut self, brightness: f32) {
+        let brightness = self.clamp_parameter("brightness", brightness, 0.0, 1.0);
+        self.patch.brightness = brightness;
+        self.for_each_voice(|voice| voice.set_brightness(brightness));
+    }
+    
+    pub fn set_stack(&mut self, density: usize, detune_cents: f32, spread: f32) {
+        self.patch.stack_density = density.clamp(1, MAX_STACK_DENSITY);
+        self.patch.stack_detune = detune_cents.max(0.0);
+        self.patch.stack_spread = spread.clamp(0.0, 1.0);
+        let (density, detune, spread) = (self.patch.stack_density, self.patch.stack_detune, self.patch.stack_spread);
+        self.for_each_voice(|voice| voice.set_stack(density, detune, spread));
+    }
+    
+    pub fn set_pan(&mut self, pan: f32) {
+        let pan = self.clamp_parameter("pan", pan, -1.0, 1.0);
+        self.patch.pan = pan;
+        self.for_each_voice(|voice| voice.set_pan(pan));
+    }
+    
+    // サラウンド出力での前後の位置（ボイスごとの違いは route の depth で付ける）
+    pub fn set_depth(&mut self, depth: f32) {
+        let depth = self.clamp_parameter("depth", depth, 0.0, 1.0);
+        self.patch.depth = depth;
+        self.for_each_voice(|voice| voice.set_depth(depth));
+    }
+    
+    // ボイス単体のステレオ幅（ユニゾンのスタックと違いボイスあたりの計算量はほぼ増えない）
+    pub fn set_width(&mut self, width: f32) {
+        let width = self.clamp_parameter("width", width, 0.0, 1.0);
+        self.patch.width = width;
+        self.for_each_voice(|voice| voice.set_width(width));
+    }
+    
+    // 加算合成エンジンのスペクトルフィルター
+    pub fn set_spectral_filter(&mut self, filter: SpectralFilter) {
+        let filter = SpectralFilter {
+            tilt: self.clamp_parameter("spectral tilt", filter.tilt, -24.0, 24.0),
+            low_shelf_harmonic: self.clamp_parameter("low shelf harmonic", filter.low_shelf_harmonic, 1.0, 64.0),
+            low_shelf_gain: self.clamp_parameter("low shelf gain", filter.low_shelf_gain, -48.0, 24.0),
+            high_shelf_harmonic: self.clamp_parameter("high shelf harmonic", filter.high_shelf_harmonic, 1.0, 64.0),
+            high_shelf_gain: self.clamp_parameter("high shelf gain", filter.high_shelf_gain, -48.0, 24.0),
+            formant_shift: self.clamp_parameter("formant shift", filter.formant_shift, -24.0, 24.0),
+        };
+        self.patch.spectral_filter = filter;
+        self.for_each_voice(|voice| voice.set_spectral_filter(filter));
+    }
+    
+    // ナイキスト周波数を超える倍音の自動減衰
+    pub fn set_anti_alias(&mut self, enabled: bool) {
+        self.patch.anti_alias = enabled;
+        self.for_each_voice(|voice| voice.set_anti_alias(enabled));
+    }
+    
+    // 音律の変更（発音中のボイスも再チューニングする）
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        self.tuning = tuning;
+        let tuning = &self.tuning;
         for voice in self.voices.values_mut() {
-            voice.set_attack(attack);
+            voice.set_frequency(tuning.frequency(voice.get_note()));
         }
     }
     
-    pub fn set_decay(&mut self, decay: f32) {
+    pub fn tuning(&self) -> &Tuning {
+        &self.tuning
+    }
+    
+    pub fn set_note_tuning_offset(&mut self, note: u8, cents: f32) {
+        let mut tuning = self.tuning.clone();
+        tuning.set_offset(note, cents);
+        self.set_tuning(tuning);
+    }
+    
+    pub fn set_tempo(&mut self, tempo_bpm: f32) {
+        self.tempo_bpm = self.clamp_parameter("tempo", tempo_bpm, 20.0, 300.0);
+        let tempo_bpm = self.tempo_bpm;
+        self.for_each_voice(|voice| voice.set_tempo(tempo_bpm));
+        self.effects.set_tempo(tempo_bpm);
+        if let Some(paraphony) = self.paraphony.as_mut() {
+            paraphony.set_tempo(tempo_bpm);
+        }
+    }
+    
+    pub fn tempo(&self) -> f32 {
+        self.tempo_bpm
+    }
+    
+    // モジュレーションマトリクス
+    pub fn add_mod_routing(&mut self, routing: ModRouting) {
+        self.patch.mod_routings.push(routing);
+        let routings = &self.patch.mod_routings;
         for voice in self.voices.values_mut() {
-            voice.set_decay(decay);
+            voice.set_mod_routings(routings);
         }
     }
     
-    pub fn set_sustain(&mut self, sustain: f32) {
+    // ブルームのテンプレート: source（モジュレーションホイール、アフタータッチなど）で倍音を低い方から順に開く。
+    // 既存のブルームへのルーティングは置き換え、None なら外す
+    pub fn set_bloom_source(&mut self, source: Option<ModSource>) {
+        self.patch.mod_routings.retain(|routing| routing.destination != ModDestination::Bloom);
+        if let Some(source) = source {
+            self.patch.mod_routings.push(ModRouting::new(source, ModDestination::Bloom, 1.0));
+        }
+        let routings = &self.patch.mod_routings;
         for voice in self.voices.values_mut() {
-            voice.set_sustain(sustain);
+            voice.set_mod_routings(routings);
         }
     }
     
-    pub fn set_release(&mut self, release: f32) {
+    pub fn bloom_source(&self) -> Option<ModSource> {
+        self.patch.mod_routings.iter()
+            .find(|routing| routing.destination == ModDestination::Bloom)
+            .map(|routing| routing.source)
+    }
+    
+    pub fn clear_mod_routings(&mut self) {
+        self.patch.mod_routings.clear();
+        self.patch.mod_combine = ModCombineModes::default();
+        self.for_each_voice(|voice| {
+            voice.modulation.set_combine(ModCombineModes::default());
+            voice.set_mod_routings(&[]);
+        });
+    }
+    
+    // 同じ送り先への複数のルーティングの合わせ方（次のブロックから反映される）
+    pub fn set_mod_combine(&mut self, destination: ModDestination, mode: ModCombine) {
+        self.patch.mod_combine.set(destination, mode);
+        let combine = self.patch.mod_combine;
+        self.for_each_voice(|voice| voice.modulation.set_combine(combine));
+    }
+    
+    pub fn mod_combine(&self, destination: ModDestination) -> ModCombine {
+        self.patch.mod_combine.get(destination)
+    }
+    
+    // 発音ごとのランダム値の乱数列を固定する（同じ演奏で同じ値が出る）
+    pub fn set_note_random_seed(&mut self, seed: u64) {
+        self.note_rng = Rng::new(seed);
+    }
+    
+    pub fn mod_routings(&self) -> &[ModRouting] {
+        &self.patch.mod_routings
+    }
+    
+    pub fn breakpoint_envelope(&self, index: usize) -> Option<&BreakpointEnvelope> {
+        self.patch.breakpoint_envelopes.get(index)
+    }
+    
+    // エンベロープを編集して全ボイスへ反映する
+    pub fn edit_breakpoint_envelope<R>(&mut self, index: usize, edit: impl FnOnce(&mut BreakpointEnvelope) -> R) -> Option<R> {
+        let envelope = self.patch.breakpoint_envelopes.get_mut(index)?;
+        let result = edit(envelope);
+        let envelope = &self.patch.breakpoint_envelopes[index];
         for voice in self.voices.values_mut() {
-            voice.set_release(release);
+            voice.set_breakpoint_envelope(index, envelope);
         }
+        Some(result)
+    }
+    
+    pub fn lfo(&self) -> Lfo {
+        self.patch.lfo
+    }
+    
+    pub fn set_lfo(&mut self, lfo: Lfo) {
+        let rate = self.clamp_parameter("lfo.rate", lfo.rate, 0.0, MAX_LFO_RATE);
+        let fade = self.clamp_parameter("lfo.fade", lfo.fade, 0.0, MAX_LFO_FADE);
+        let lfo = Lfo { rate, fade, ..lfo };
+        self.patch.lfo = lfo;
+        self.for_each_voice(|voice| voice.set_lfo(lfo));
+    }
+    
+    pub fn set_volume(&mut self, volume: f32) {
+        let volume = self.clamp_parameter("volume", volume, 0.0, 1.0);
+        self.patch.volume = volume;
+        self.for_each_voice(|voice| voice.set_volume(volume));
+    }
+    
+    pub fn set_filter_cutoff(&mut self, cutoff: f32) {
+        self.set_cutoff(cutoff);
+    }
+    
+    // 正規化カットオフ 0.0-1.0（cutoff_to_hz で Hz に対応）
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        let cutoff = self.clamp_parameter("cutoff", cutoff, 0.0, 1.0);
+        self.patch.cutoff = cutoff;
+        self.for_each_voice(|voice| voice.set_cutoff(cutoff));
+    }
+    
+    pub fn set_cutoff_hz(&mut self, hz: f32) {
+        self.set_cutoff(hz_to_cutoff(hz));
+    }
+    
+    pub fn cutoff_hz(&self) -> f32 {
+        cutoff_to_hz(self.patch.cutoff)
+    }
+    
+    pub fn set_filter_resonance(&mut self, resonance: f32) {
+        self.set_resonance(resonance);
+    }
+    
+    pub fn set_resonance(&mut self, resonance: f32) {
+        let resonance = self.clamp_parameter("resonance", resonance, 0.0, 1.0);
+        self.patch.resonance = resonance;
+        self.for_each_voice(|voice| voice.set_resonance(resonance));
+    }
+    
+    pub fn set_filter_drive(&mut self, drive: f32) {
+        let drive = self.clamp_parameter("filter drive", drive, 0.0, 1.0);
+        self.patch.filter_drive = drive;
+        self.for_each_voice(|voice| voice.set_filter_drive(drive));
+    }
+    
+    // ボイスごとの出力の飽和（合算前に掛けるので、大きな FM ボイスが全体を占有しない）
+    pub fn set_voice_saturation(&mut self, amount: f32) {
+        let amount = self.clamp_parameter("voice saturation", amount, 0.0, 1.0);
+        self.patch.voice_saturation = amount;
+        self.for_each_voice(|voice| voice.set_saturation(amount));
+    }
+    
+    // パッチの出力トリム（dB）。バンク内のパッチの音量を揃えるためのもの
+    pub fn set_output_trim(&mut self, trim_db: f32) {
+        let trim_db = self.clamp_parameter("output trim", trim_db, -MAX_OUTPUT_TRIM_DB, MAX_OUTPUT_TRIM_DB);
+        self.patch.output_trim = trim_db;
+        let invert = self.patch.output_invert;
+        self.for_each_voice(|voice| voice.set_output(trim_db, invert));
+    }
+    
+    pub fn set_output_invert(&mut self, invert: bool) {
+        self.patch.output_invert = invert;
+        let trim_db = self.patch.output_trim;
+        self.for_each_voice(|voice| voice.set_output(trim_db, invert));
+    }
+    
+    // レゾナンスとドライブによる音量変化の自動補正（パッチごとの設定）
+    pub fn set_filter_compensation(&mut self, enabled: bool) {
+        self.patch.filter_compensation = enabled;
+        self.for_each_voice(|voice| voice.set_filter_compensation(enabled));
+    }
+    
+    pub fn set_envelope(&mut self, envelope: Envelope) {
+        self.patch.envelope = envelope;
+        self.for_each_voice(|voice| voice.set_envelope(envelope));
+    }
+    
+    pub fn set_attack(&mut self, attack: f32) {
+        self.patch.envelope.attack = attack;
+        self.for_each_voice(|voice| voice.set_attack(attack));
+    }
+    
+    pub fn set_decay(&mut self, decay: f32) {
+        self.patch.envelope.decay = decay;
+        self.for_each_voice(|voice| voice.set_decay(decay));
+    }
+    
+    pub fn set_sustain(&mut self, sustain: f32) {
+        self.patch.envelope.sustain = sustain;
+        self.for_each_voice(|voice| voice.set_sustain(sustain));
+    }
+    
+    pub fn set_release(&mut self, release: f32) {
+        self.patch.envelope.release = release;
+        self.for_each_voice(|voice| voice.set_release(release));
     }
     
     // Additive Engine パラメータ
     pub fn set_harmonic_amplitude(&mut self, harmonic_index: usize, amplitude: f32) {
-        for voice in self.voices.values_mut() {
-            voice.set_harmonic_amplitude(harmonic_index, amplitude);
+        if let Some(harmonic) = self.patch.harmonics.get_mut(harmonic_index) {
+            harmonic.amplitude = amplitude;
         }
+        self.for_each_voice(|voice| voice.set_harmonic_amplitude(harmonic_index, amplitude));
     }
     
     pub fn toggle_harmonic(&mut self, harmonic_index: usize) {
-        for voice in self.voices.values_mut() {
-            voice.toggle_harmonic(harmonic_index);
+        if let Some(harmonic) = self.patch.harmonics.get_mut(harmonic_index) {
+            harmonic.enabled = !harmonic.enabled;
         }
+        self.for_each_voice(|voice| voice.toggle_harmonic(harmonic_index));
     }
     
     // FM Engine パラメータ
     pub fn set_operator_amplitude(&mut self, operator_index: usize, amplitude: f32) {
-        for voice in self.voices.values_mut() {
-            voice.set_operator_amplitude(operator_index, amplitude);
+        if let Some(operator) = self.patch.operators.get_mut(operator_index) {
+            operator.amplitude = amplitude;
         }
+        self.for_each_voice(|voice| voice.set_operator_amplitude(operator_index, amplitude));
     }
     
     pub fn set_operator_frequency_ratio(&mut self, operator_index: usize, ratio: f32) {
-        for voice in self.voices.values_mut() {
-            voice.set_operator_frequency_ratio(operator_index, ratio);
+        if let Some(operator) = self.patch.operators.get_mut(operator_index) {
+            operator.frequency_ratio = ratio;
         }
+        self.for_each_voice(|voice| voice.set_operator_frequency_ratio(operator_index, ratio));
     }
     
     pub fn set_operator_feedback(&mut self, operator_index: usize, feedback: f32) {
-        for voice in self.voices.values_mut() {
-            voice.set_operator_feedback(operator_index, feedback);
+        if let Some(operator) = self.patch.operators.get_mut(operator_index) {
+            operator.feedback = feedback;
         }
+        self.for_each_voice(|voice| voice.set_operator_feedback(operator_index, feedback));
     }
     
     // ゲッター
     pub fn harmonics(&self) -> &[Harmonic] {
-        // This needs to be adapted to return harmonics from all voices
-        // For now, it will return the harmonics of the first voice
-        if let Some(voice) = self.voices.values().next() {
-            &voice.engine_blender.additive_engine.harmonics
-        } else {
-            &[]
-        }
+        &self.patch.harmonics
     }
     
     pub fn harmonics_count(&self) -> usize {
-        // This needs to be adapted to return the total count of harmonics across all voices
-        // For now, it will return the count of harmonics from the first voice
-        if let Some(voice) = self.voices.values().next() {
-            voice.engine_blender.additive_engine.harmonics.len()
-        } else {
-            0
-        }
+        self.patch.harmonics.len()
     }
     
     pub fn operators(&self) -> &[Operator] {
-        // This needs to be adapted to return operators from all voices
-        // For now, it will return the operators of the first voice
-        if let Some(voice) = self.voices.values().next() {
-            &voice.engine_blender.fm_engine.operators
-        } else {
-            &[]
-        }
+        &self.patch.operators
     }
     
     pub fn operators_count(&self) -> usize {
-        // This needs to be adapted to return the total count of operators across all voices
-        // For now, it will return the count of operators from the first voice
-        if let Some(voice) = self.voices.values().next() {
-            voice.engine_blender.fm_engine.operators.len()
-        } else {
-            0
+        self.patch.operators.len()
+    }
+    
+    // 発音中（リリース前）のノート番号
+    pub fn active_notes(&self) -> Vec<u8> {
+        let mut notes: Vec<u8> = self.voices.iter()
+            .filter(|(_, voice)| voice.is_active())
+            .map(|(note, _)| *note)
+            .collect();
+        notes.sort_unstable();
+        notes
+    }
+    
+    // ボイスの定位とスタックの広がりに使うパンの法則
+    pub fn set_pan_law(&mut self, pan_law: PanLaw) {
+        self.config.pan_law = pan_law;
+        self.for_each_voice(|voice| voice.set_pan_law(pan_law));
+    }
+    
+    pub fn pan_law(&self) -> PanLaw {
+        self.config.pan_law
+    }
+    
+    // 正弦を f32::sin で正確に計算する（既定は共有の補間テーブルで、高いポリフォニーでの負荷を抑える）
+    pub fn set_exact_sine(&mut self, exact: bool) {
+        self.config.exact_sine = exact;
+        self.for_each_voice(|voice| voice.set_exact_sine(exact));
+    }
+    
+    pub fn exact_sine(&self) -> bool {
+        self.config.exact_sine
+    }
+    
+    // 内蔵の HRIR セットによるボイスごとのバイノーラル定位（ヘッドホン向け）
+    pub fn set_binaural(&mut self, enabled: bool) {
+        self.config.binaural = enabled;
+        if enabled && self.hrirs.is_none() {
+            self.hrirs = Some(Arc::new(HrirSet::spherical_head(self.config.sample_rate)));
         }
+        let hrirs = self.hrirs.clone().filter(|_| enabled);
+        self.for_each_voice(|voice| voice.set_binaural(hrirs.clone()));
+    }
+    
+    pub fn binaural(&self) -> bool {
+        self.config.binaural
+    }
+    
+    // モノラル互換チェック: マスター出力の左右を足し合わせて両方から出す
+    pub fn set_mono_check(&mut self, enabled: bool) {
+        self.mono_check = enabled;
+    }
+    
+    pub fn mono_check(&self) -> bool {
+        self.mono_check
+    }
+    
+    // 直近の出力をモノラルにしたときの音量の変化（dB、無音なら None）
+    pub fn fold_down_loss_db(&self) -> Option<f32> {
+        self.fold_down.loss_db()
+    }
+    
+    // 出力のピークレベル（左右）
+    pub fn output_meter(&self) -> [f32; 2] {
+        self.output_meter
+    }
+    
+    pub fn voice_count(&self) -> usize {
+        self.voices.len()
+    }
+    
+    // 全ボイスの診断情報（ノート番号順）
+    pub fn voice_info(&self) -> Vec<VoiceInfo> {
+        let mut info: Vec<VoiceInfo> = self.voices.values().map(|voice| voice.info()).collect();
+        info.sort_by_key(|voice| voice.note);
+        info
     }
     
     pub fn is_playing(&self) -> bool {
-        // This needs to be adapted to check if any voice is active
         self.voices.values().any(|v| v.is_active())
     }
 } 
\ No newline at end of file
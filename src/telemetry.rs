@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+const NO_PROGRAM: u32 = u32::MAX;
+
+// Synthesizer の状態のうち、ほかのスレッド（ステータス行、プログラムの読み込みなど）がロックを取らずに読むもの。
+// オーディオのコールバックが1ブロックごとに、制御側は借りた Synthesizer を返すときに書く（Synthesizer::telemetry で受け取る）
+#[derive(Debug)]
+pub struct Telemetry {
+    pub(crate) meter: [AtomicU32; 2], // f32 のビット列
+    pub(crate) playing: AtomicBool,
+    pub(crate) beats: AtomicU64, // 聞こえている拍位置（f64 のビット列）
+    pub(crate) beats_per_bar: AtomicU32,
+    pub(crate) tempo: AtomicU32,
+    pub(crate) voices: AtomicUsize,
+    pub(crate) surround: AtomicBool, // 前後の位置（depth）が効くサラウンドの配置で鳴らしているか
+    pub(crate) dsp_load: AtomicU32,
+    pub(crate) notes: [AtomicU64; 2], // 発音中（リリース前）のノートのビット
+    pub(crate) scenes: AtomicU32,     // 保存済みのシーンのビット
+    program_request: AtomicU32,       // 読み込みを待っているプログラムチェンジ（NO_PROGRAM で無し）
+    pub(crate) edits: AtomicU64,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self {
+            meter: Default::default(),
+            playing: AtomicBool::new(false),
+            beats: AtomicU64::new(0),
+            beats_per_bar: AtomicU32::new(4),
+            tempo: AtomicU32::new(0),
+            voices: AtomicUsize::new(0),
+            surround: AtomicBool::new(false),
+            dsp_load: AtomicU32::new(0),
+            notes: Default::default(),
+            scenes: AtomicU32::new(0),
+            program_request: AtomicU32::new(NO_PROGRAM),
+            edits: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Telemetry {
+    // 出力のピークレベル（左右）
+    pub fn output_meter(&self) -> [f32; 2] {
+        self.meter.each_ref().map(|bits| f32::from_bits(bits.load(Ordering::Relaxed)))
+    }
+    
+    pub fn is_sequencer_playing(&self) -> bool {
+        self.playing.load(Ordering::Relaxed)
+    }
+    
+    pub fn audible_beats(&self) -> f64 {
+        f64::from_bits(self.beats.load(Ordering::Relaxed))
+    }
+    
+    pub fn beats_per_bar(&self) -> u32 {
+        self.beats_per_bar.load(Ordering::Relaxed)
+    }
+    
+    pub fn tempo(&self) -> f32 {
+        f32::from_bits(self.tempo.load(Ordering::Relaxed))
+    }
+    
+    pub fn voice_count(&self) -> usize {
+        self.voices.load(Ordering::Relaxed)
+    }
+    
+    pub fn is_surround(&self) -> bool {
+        self.surround.load(Ordering::Relaxed)
+    }
+    
+    // 品質調整が平滑化したコールバックの負荷
+    pub fn dsp_load(&self) -> f32 {
+        f32::from_bits(self.dsp_load.load(Ordering::Relaxed))
+    }
+    
+    // 発音中のノート番号（昇順）
+    pub fn active_notes(&self) -> Vec<u8> {
+        let words = self.notes.each_ref().map(|word| word.load(Ordering::Relaxed));
+        (0..128u8).filter(|note| words[*note as usize / 64] & (1 << (note % 64)) != 0).collect()
+    }
+    
+    // 音色・テンポなど保存する状態を変えうる操作（パラメータの設定、Command::is_edit、SynthHandle::borrow）の回数。
+    // 自動保存や OSC の送り返しは、前回から変わったときだけ Synthesizer を借りる
+    pub fn edits(&self) -> u64 {
+        self.edits.load(Ordering::Relaxed)
+    }
+    
+    // 借りずにシーンの呼び出し（Command::Scene）を送る前に確かめる
+    pub fn is_scene_stored(&self, slot: usize) -> bool {
+        slot < 32 && self.scenes.load(Ordering::Relaxed) & (1 << slot) != 0
+    }
+    
+    // ProgramLoader はこれを見てから Synthesizer::take_program_request で受け取る
+    pub fn has_program_request(&self) -> bool {
+        self.program_request.load(Ordering::Acquire) != NO_PROGRAM
+    }
+    
+    pub(crate) fn request_program(&self, program: u8) {
+        self.program_request.store(program as u32, Ordering::Release);
+    }
+    
+    pub(crate) fn take_program_request(&self) -> Option<u8> {
+        let program = self.program_request.swap(NO_PROGRAM, Ordering::AcqRel);
+        (program != NO_PROGRAM).then_some(program as u8)
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+
+pub const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+// ルートからの半音の集合（ビット n = n 半音上）と和音の記号。先にあるものほど優先する
+const CHORD_TYPES: [(u16, &str); 22] = [
+    (0b0000_1001_0001, ""),      // 1 3 5
+    (0b0000_1000_1001, "m"),
+    (0b0000_0100_1001, "dim"),
+    (0b0001_0001_0001, "aug"),
+    (0b0000_1010_0001, "sus4"),
+    (0b0000_1000_0101, "sus2"),
+    (0b0000_1000_0001, "5"),
+    (0b0100_1001_0001, "7"),
+    (0b1000_1001_0001, "maj7"),
+    (0b0100_1000_1001, "m7"),
+    (0b1000_1000_1001, "mMaj7"),
+    (0b0100_0100_1001, "m7b5"),
+    (0b0010_0100_1001, "dim7"),
+    (0b0100_1010_0001, "7sus4"),
+    (0b0010_1001_0001, "6"),
+    (0b0010_1000_1001, "m6"),
+    (0b0000_1001_0101, "add9"),
+    (0b0000_1000_1101, "madd9"),
+    (0b0100_1001_0101, "9"),
+    (0b1000_1001_0101, "maj9"),
+    (0b0100_1000_1101, "m9"),
+    (0b0101_0001_0001, "7#5"),
+];
+const PERFECT_FIFTH: u16 = 1 << 7;
+
+// 音名とオクターブ（60 = C4）
+pub fn note_name(note: u8) -> String {
+    format!("{}{}", NOTE_NAMES[note as usize % 12], note as i32 / 12 - 1)
+}
+
+// 判定した和音。ルートと最低音は音名（0 = C〜11 = B）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chord {
+    pub root: u8,
+    pub quality: &'static str,
+    pub bass: u8,
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", NOTE_NAMES[self.root as usize], self.quality)?;
+        if self.bass != self.root {
+            write!(f, "/{}", NOTE_NAMES[self.bass as usize])?;
+        }
+        Ok(())
+    }
+}
+
+// 押さえているノートから和音を判定する（オクターブと重複は無視する）。
+// 最低音をルートとする解釈を優先し、なければ転回形（分数コード）とする。
+// 3音以上なら完全5度を省いた押さえ方（C E B など）も認める
+pub fn detect_chord(notes: &[u8]) -> Option<Chord> {
+    let bass = notes.iter().min()? % 12;
+    let pitch_classes = notes.iter().fold(0u16, |set, note| set | 1 << (note % 12));
+    let relative = |root: u8| (pitch_classes >> root | pitch_classes << (12 - root)) & 0xFFF;
+    let roots = (0..12).map(|offset| (bass + offset) % 12).filter(|root| pitch_classes & 1 << root != 0);
+    let fifth_may_be_omitted = pitch_classes.count_ones() >= 3;
+    for omitted_fifth in [false, true] {
+        if omitted_fifth && !fifth_may_be_omitted {
+            break;
+        }
+        for root in roots.clone() {
+            let mut intervals = relative(root);
+            if omitted_fifth {
+                intervals |= PERFECT_FIFTH;
+            }
+            if let Some((_, quality)) = CHORD_TYPES.iter().find(|(set, _)| *set == intervals) {
+                return Some(Chord { root, quality, bass });
+            }
+        }
+    }
+    None
+}
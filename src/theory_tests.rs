@@ -0,0 +1,24 @@
+// 和音の判定のテスト
+use crate::synth::Synthesizer;
+
+// 押さえているノートから和音を判定する。最低音がルートでなければ分数コードになる
+#[test]
+fn held_notes_are_named_as_chords() {
+    use crate::theory::{detect_chord, note_name};
+    
+    let name = |notes: &[u8]| detect_chord(notes).map(|chord| chord.to_string());
+    assert_eq!(name(&[60, 64, 67, 71]).as_deref(), Some("Cmaj7"));
+    assert_eq!(name(&[54, 57, 60, 64]).as_deref(), Some("F#m7b5"));
+    assert_eq!(name(&[52, 60, 67, 72]).as_deref(), Some("C/E"));
+    assert_eq!(name(&[43, 59, 65]).as_deref(), Some("G7")); // 5度を省いた押さえ方
+    assert_eq!(name(&[57, 60, 64, 67]).as_deref(), Some("Am7"));
+    assert_eq!(name(&[60, 64]), None);
+    assert_eq!(note_name(60), "C4");
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    for note in [62, 65, 69, 72] {
+        synth.note_on(note, 0.8);
+    }
+    synth.note_off(72);
+    assert_eq!(detect_chord(&synth.active_notes()).map(|chord| chord.to_string()).as_deref(), Some("Dm"));
+}
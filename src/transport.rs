@@ -0,0 +1,160 @@
+use std::fmt;
+use std::str::FromStr;
+
+pub const CLOCK_PPQN: u32 = 24; // MIDI クロックの1拍あたりのパルス数
+const CLOCK_TIMEOUT_SECONDS: f32 = 0.5; // これより長くパルスが来なければ同期が外れたとみなす
+
+// テンポをどこから取るか。MidiClock では受け取ったクロックから求めたテンポに、
+// Link では link_listener::LinkTempoListener が受け取ったセッションのテンポに、
+// Host ではプラグインのホストが Synthesizer::set_host_transport で渡すテンポと位置に追従する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TempoSource {
+    #[default]
+    Internal,
+    MidiClock,
+    Link,
+    Host,
+}
+
+impl fmt::Display for TempoSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TempoSource::Internal => "internal",
+            TempoSource::MidiClock => "midi",
+            TempoSource::Link => "link",
+            TempoSource::Host => "host",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for TempoSource {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "internal" => Ok(TempoSource::Internal),
+            "midi" => Ok(TempoSource::MidiClock),
+            "link" => Ok(TempoSource::Link),
+            "host" => Ok(TempoSource::Host),
+            _ => Err(format!("unknown tempo source: {}", s)),
+        }
+    }
+}
+
+// 受け取った MIDI クロック（24 PPQN）の間隔からテンポを推定する。
+// 直近1拍分のパルス間隔の平均を使い、送信側や受信のジッターをならす
+#[derive(Debug, Clone)]
+pub struct MidiClock {
+    intervals: [u64; CLOCK_PPQN as usize],
+    count: usize,
+    next: usize,
+    last_tick: Option<u64>,
+    running: bool,
+    ticks: u64, // Start からのパルス数（Continue では続きから数える）
+}
+
+impl Default for MidiClock {
+    fn default() -> Self {
+        Self {
+            intervals: [0; CLOCK_PPQN as usize],
+            count: 0,
+            next: 0,
+            last_tick: None,
+            running: false,
+            ticks: 0,
+        }
+    }
+}
+
+impl MidiClock {
+    // sample はパルスを受け取ったサンプル位置
+    pub fn tick(&mut self, sample: u64, sample_rate: f32) {
+        if let Some(last) = self.last_tick {
+            let interval = sample.saturating_sub(last);
+            // 長く途切れた後の最初の間隔は平均に入れない
+            if interval as f32 > CLOCK_TIMEOUT_SECONDS * sample_rate {
+                self.count = 0;
+                self.next = 0;
+            } else if interval > 0 {
+                self.intervals[self.next] = interval;
+                self.next = (self.next + 1) % self.intervals.len();
+                self.count = (self.count + 1).min(self.intervals.len());
+            }
+        }
+        self.last_tick = Some(sample);
+        if self.running {
+            self.ticks += 1;
+        }
+    }
+    
+    pub fn start(&mut self) {
+        self.ticks = 0;
+        self.running = true;
+    }
+    
+    pub fn resume(&mut self) {
+        self.running = true;
+    }
+    
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+    
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+    
+    // Start からの拍数
+    pub fn beats(&self) -> f64 {
+        self.ticks as f64 / CLOCK_PPQN as f64
+    }
+    
+    // 推定したテンポ（BPM）。パルスが途切れている、または平均に足りるパルスがまだ無ければ None
+    pub fn tempo(&self, now: u64, sample_rate: f32) -> Option<f32> {
+        let last = self.last_tick?;
+        if self.count < CLOCK_PPQN as usize / 4 || now.saturating_sub(last) as f32 > CLOCK_TIMEOUT_SECONDS * sample_rate {
+            return None;
+        }
+        let average = self.intervals[..self.count].iter().sum::<u64>() as f32 / self.count as f32;
+        Some(60.0 * sample_rate / (average * CLOCK_PPQN as f32))
+    }
+}
+
+// これより長くホストから位置が届かなければ、ホストの外（スタンドアロン）とみなして内部の時計で進める
+pub const HOST_TIMEOUT_SECONDS: f32 = 0.5;
+
+// プラグインのホスト（CLAP / VST3 など）が処理のブロックごとに渡すトランスポート。
+// beat はブロックの先頭での曲の先頭からの位置（4分音符単位）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HostTransport {
+    pub tempo: f32,
+    pub beat: f64,
+    pub bar_start: f64,         // 今の小節の先頭の位置（4分音符単位）
+    pub time_signature: (u8, u8),
+    pub playing: bool,
+}
+
+impl Default for HostTransport {
+    fn default() -> Self {
+        Self { tempo: 120.0, beat: 0.0, bar_start: 0.0, time_signature: (4, 4), playing: false }
+    }
+}
+
+impl HostTransport {
+    // 1小節の長さ（4分音符単位）
+    pub fn bar_length(&self) -> f64 {
+        let (numerator, denominator) = self.time_signature;
+        numerator.max(1) as f64 * 4.0 / denominator.max(1) as f64
+    }
+    
+    // 小節の番号（1 始まり。拍子が途中で変わらない前提で bar_start から求める）
+    pub fn bar(&self) -> i64 {
+        (self.bar_start / self.bar_length()).round() as i64 + 1
+    }
+    
+    // 小節の中での位置（拍子の分母の音符単位、0 始まり）
+    pub fn beat_in_bar(&self) -> f64 {
+        (self.beat - self.bar_start).max(0.0) * self.time_signature.1.max(1) as f64 / 4.0
+    }
+}
@@ -0,0 +1,101 @@
+// テンポの取得元（MIDI クロック・ホストのトランスポート）のテスト
+use crate::event::Event;
+use crate::synth::Synthesizer;
+
+// MIDI クロックを取得元にするとパルスの間隔からテンポを求め、テンポ同期の LFO もそれに従う
+#[test]
+fn midi_clock_drives_tempo_and_transport() {
+    use crate::modulation::Lfo;
+    use crate::transport::TempoSource;
+    
+    let mut synth = Synthesizer::builder().sample_rate(24000.0).build();
+    synth.set_tempo_source(TempoSource::MidiClock);
+    synth.handle_event(&Event::Start);
+    assert!(synth.sequencer().is_playing() && synth.midi_clock().is_running());
+    // 100 BPM: 1パルス 24000 * 60 / 100 / 24 = 600 サンプル
+    for _ in 0..48 {
+        synth.handle_event(&Event::Clock);
+        for _ in 0..600 {
+            synth.next_frame();
+        }
+    }
+    assert!((synth.tempo() - 100.0).abs() < 0.1, "tempo {}", synth.tempo());
+    assert_eq!(synth.midi_clock().beats(), 2.0);
+    
+    let lfo = Lfo { rate: 0.5, sync: true, ..Lfo::default() };
+    assert!((lfo.frequency(synth.tempo()) - 0.8333).abs() < 0.01);
+    
+    // 内部テンポに戻すとクロックを受け取ってもテンポは変わらない
+    synth.handle_event(&Event::Stop);
+    assert!(!synth.sequencer().is_playing());
+    synth.set_tempo_source(TempoSource::Internal);
+    synth.set_tempo(140.0);
+    for _ in 0..24 {
+        synth.handle_event(&Event::Clock);
+        for _ in 0..600 {
+            synth.next_frame();
+        }
+    }
+    assert_eq!(synth.tempo(), 140.0);
+    assert!(synth.midi_clock_tempo().is_some_and(|tempo| (tempo - 100.0).abs() < 0.1));
+}
+
+// ホストのトランスポートを取得元にするとテンポと再生・停止に従い、シーケンサーとメトロノームの位置をホストの位置に合わせる。
+// 届かなくなれば内部の時計で進む
+#[test]
+fn host_transport_drives_tempo_sequencer_and_metronome() {
+    use crate::sequencer::{Step, Track};
+    use crate::transport::{HostTransport, TempoSource};
+    
+    let sample_rate = 24000.0;
+    let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+    let track = synth.sequencer_mut().add_track(Track::new(4, 1));
+    synth.sequencer_mut().track_mut(track).unwrap().set_step(2, Step { note: Some(64), ..Step::default() });
+    synth.set_tempo_source(TempoSource::Host);
+    
+    let mut transport = HostTransport { tempo: 90.0, beat: 2.0, bar_start: 0.0, time_signature: (4, 4), playing: true };
+    synth.set_host_transport(transport);
+    assert_eq!(synth.tempo(), 90.0);
+    assert!(synth.sequencer().is_playing());
+    synth.next_frame();
+    assert_eq!(synth.active_notes(), vec![64]);
+    assert!((synth.metronome().beats() - 2.0).abs() < 0.001);
+    
+    // 同じテンポで進んでいれば位置は移さず、ループで戻れば移す
+    for _ in 0..1000 {
+        synth.next_frame();
+    }
+    let advanced = synth.sequencer().beat();
+    transport.beat = 2.0 + 1000.0 / sample_rate as f64 * 1.5;
+    synth.set_host_transport(transport);
+    assert_eq!(synth.sequencer().beat(), advanced);
+    transport.beat = 0.0;
+    synth.set_host_transport(transport);
+    assert_eq!(synth.sequencer().beat(), 0.0);
+    assert_eq!(synth.metronome().beats(), 0.0);
+    
+    // メトロノームの1小節の拍数は拍子に従う（6/8 は4分音符3拍）
+    transport.time_signature = (3, 4);
+    synth.set_host_transport(transport);
+    assert_eq!(synth.metronome().beats_per_bar(), 3);
+    transport.time_signature = (6, 8);
+    synth.set_host_transport(transport);
+    assert_eq!(synth.metronome().beats_per_bar(), 3);
+    transport.playing = false;
+    synth.set_host_transport(transport);
+    assert!(!synth.sequencer().is_playing());
+    
+    // 届かなくなれば古い位置は返さず、テンポはそのまま
+    for _ in 0..(sample_rate as usize) {
+        synth.next_frame();
+    }
+    assert!(synth.host_transport().is_none());
+    assert_eq!(synth.tempo(), 90.0);
+    
+    // 取得元が内部ならテンポも再生状態も変えない
+    synth.set_tempo_source(TempoSource::Internal);
+    synth.set_host_transport(HostTransport { tempo: 150.0, playing: true, ..HostTransport::default() });
+    assert_eq!(synth.tempo(), 90.0);
+    assert!(!synth.sequencer().is_playing());
+    assert_eq!(synth.host_transport().map(|transport| transport.bar()), Some(1));
+}
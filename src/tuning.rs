@@ -0,0 +1,111 @@
+use std::path::Path;
+
+// 音律: MIDIノート番号から周波数への変換。
+// 基準ノートを音階の主音とし、音階の各度数をセントで持つ（最後の要素が周期、通常 1200）。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuning {
+    pub reference_note: u8,       // 基準ノート（通常 A4 = 69）
+    pub reference_frequency: f32, // 基準周波数（通常 440Hz）
+    pub scale: Vec<f32>,          // 主音からのセント（主音自身は含まない）
+    pub offsets: [f32; 128],      // ノートごとの微調整（セント）
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self::with_reference(69, 440.0)
+    }
+}
+
+impl Tuning {
+    pub fn with_reference(reference_note: u8, reference_frequency: f32) -> Self {
+        Self {
+            reference_note,
+            reference_frequency,
+            scale: edo_scale(12),
+            offsets: [0.0; 128],
+        }
+    }
+    
+    // オクターブを n 等分する平均律（EDO）
+    pub fn edo(divisions: usize) -> Self {
+        Self {
+            scale: edo_scale(divisions),
+            ..Self::default()
+        }
+    }
+    
+    pub fn from_scala(text: &str) -> Result<Self, String> {
+        Ok(Self {
+            scale: parse_scala(text)?,
+            ..Self::default()
+        })
+    }
+    
+    pub fn load_scala(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::from_scala(&text)?)
+    }
+    
+    pub fn set_offset(&mut self, note: u8, cents: f32) {
+        if let Some(offset) = self.offsets.get_mut(note as usize) {
+            *offset = cents;
+        }
+    }
+    
+    pub fn clear_offsets(&mut self) {
+        self.offsets = [0.0; 128];
+    }
+    
+    pub fn frequency(&self, note: u8) -> f32 {
+        let steps = note as i32 - self.reference_note as i32;
+        let size = self.scale.len().max(1) as i32;
+        let period = self.scale.last().copied().unwrap_or(1200.0);
+        let degree = steps.rem_euclid(size) as usize;
+        let mut cents = steps.div_euclid(size) as f32 * period;
+        if degree > 0 {
+            cents += self.scale[degree - 1];
+        }
+        cents += self.offsets.get(note as usize).copied().unwrap_or(0.0);
+        self.reference_frequency * 2.0_f32.powf(cents / 1200.0)
+    }
+}
+
+fn edo_scale(divisions: usize) -> Vec<f32> {
+    let divisions = divisions.max(1);
+    (1..=divisions).map(|step| step as f32 * 1200.0 / divisions as f32).collect()
+}
+
+// Scala (.scl) 形式: '!' はコメント、説明行、音数、各音（"." を含めばセント、そうでなければ比率）
+fn parse_scala(text: &str) -> Result<Vec<f32>, String> {
+    let mut lines = text.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('!'));
+    lines.next().ok_or("missing description line")?;
+    let count = lines.next()
+        .and_then(|line| line.split_whitespace().next())
+        .and_then(|value| value.parse::<usize>().ok())
+        .ok_or("missing note count")?;
+    
+    let scale = lines
+        .filter(|line| !line.is_empty())
+        .take(count)
+        .map(|line| {
+            let value = line.split_whitespace().next().unwrap_or_default();
+            parse_scala_pitch(value).ok_or_else(|| format!("invalid pitch: {}", value))
+        })
+        .collect::<Result<Vec<f32>, String>>()?;
+    
+    if scale.len() != count || count == 0 {
+        return Err(format!("expected {} pitches, found {}", count, scale.len()));
+    }
+    Ok(scale)
+}
+
+fn parse_scala_pitch(value: &str) -> Option<f32> {
+    if value.contains('.') {
+        return value.parse::<f32>().ok();
+    }
+    let (numerator, denominator) = value.split_once('/').unwrap_or((value, "1"));
+    let ratio = numerator.parse::<f32>().ok()? / denominator.parse::<f32>().ok()?;
+    (ratio > 0.0).then(|| 1200.0 * ratio.log2())
+}
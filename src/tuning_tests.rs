@@ -0,0 +1,54 @@
+// Scala (.scl) の読み込みのテスト
+use crate::tuning::Tuning;
+
+fn assert_cents(actual: &[f32], expected: &[f32]) {
+    assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+    for (actual, expected) in actual.iter().zip(expected) {
+        assert!((actual - expected).abs() < 1e-3, "{:?} != {:?}", actual, expected);
+    }
+}
+
+#[test]
+fn reads_cents_and_ratio_lines() {
+    let text = "\
+! meantone.scl
+!
+Quarter-comma meantone (partial)
+ 4
+!
+ 193.157
+ 5/4 major third
+ 3/2
+ 2
+";
+    // "." を含む行はセント、それ以外は比率（整数だけの行は n/1）。ピッチの後の注釈は無視する
+    let tuning = Tuning::from_scala(text).unwrap();
+    assert_cents(&tuning.scale, &[193.157, 386.314, 701.955, 1200.0]);
+    let third = tuning.frequency(70);
+    assert!((third - 440.0 * 2.0_f32.powf(193.157 / 1200.0)).abs() < 1e-2);
+}
+
+#[test]
+fn skips_comments_anywhere() {
+    let text = "!\n!comment\nfive notes\n! between count and pitches\n5\n! first\n240.0\n480.0\n! middle\n720.\n960.0\n1200.0\n! trailing\n";
+    let tuning = Tuning::from_scala(text).unwrap();
+    assert_cents(&tuning.scale, &[240.0, 480.0, 720.0, 960.0, 1200.0]);
+}
+
+#[test]
+fn rejects_a_wrong_degree_count() {
+    let text = "Too few\n3\n100.0\n2/1\n";
+    let error = Tuning::from_scala(text).unwrap_err();
+    assert!(error.contains("expected 3 pitches, found 2"), "{}", error);
+    assert!(Tuning::from_scala("Empty\n0\n").is_err());
+}
+
+#[test]
+fn rejects_bad_input() {
+    assert!(Tuning::from_scala("").unwrap_err().contains("description"));
+    assert!(Tuning::from_scala("Description only\n").unwrap_err().contains("count"));
+    assert!(Tuning::from_scala("Bad count\nseven\n100.0\n").unwrap_err().contains("count"));
+    assert!(Tuning::from_scala("Bad pitch\n2\n100.0\nabc\n").unwrap_err().contains("invalid pitch: abc"));
+    assert!(Tuning::from_scala("Zero ratio\n1\n0/1\n").unwrap_err().contains("invalid pitch"));
+    assert!(Tuning::from_scala("Negative ratio\n1\n-3/2\n").unwrap_err().contains("invalid pitch"));
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+
+// ベロシティ（0.0-1.0）の変換。low〜high の入力を 0.0〜1.0 に広げてから exponent 乗する
+// （exponent > 1 で弱く、< 1 で強く鳴りやすくなる）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VelocityCurve {
+    pub low: f32,
+    pub high: f32,
+    pub exponent: f32,
+}
+
+impl Default for VelocityCurve {
+    fn default() -> Self {
+        Self { low: 0.0, high: 1.0, exponent: 1.0 }
+    }
+}
+
+impl VelocityCurve {
+    pub fn new(low: f32, high: f32, exponent: f32) -> Self {
+        Self { low, high, exponent }
+    }
+    
+    pub fn apply(&self, velocity: f32) -> f32 {
+        if velocity <= 0.0 || self.is_identity() {
+            return velocity;
+        }
+        let range = (self.high - self.low).max(1e-3);
+        let mapped = ((velocity - self.low) / range).clamp(0.0, 1.0).powf(self.exponent.max(0.01));
+        // low より弱い打鍵も無音にはしない（MIDI の最小値 1 に相当）
+        mapped.max(1.0 / 127.0)
+    }
+    
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// 入力機器ごとのベロシティの較正。名前付きのプロファイルを機器名に割り当てる。
+// 機器の変換はシンセサイザー自身のベロシティカーブより前に掛かる
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VelocityProfiles {
+    profiles: BTreeMap<String, VelocityCurve>,
+    devices: BTreeMap<String, String>, // 機器名 → プロファイル名
+}
+
+impl VelocityProfiles {
+    // 同じ名前のプロファイルは置き換える
+    pub fn set_profile(&mut self, name: &str, curve: VelocityCurve) {
+        self.profiles.insert(name.to_string(), curve);
+    }
+    
+    // 削除したプロファイルを使っていた機器の割り当ても外す
+    pub fn remove_profile(&mut self, name: &str) -> bool {
+        self.devices.retain(|_, profile| profile != name);
+        self.profiles.remove(name).is_some()
+    }
+    
+    pub fn profile(&self, name: &str) -> Option<&VelocityCurve> {
+        self.profiles.get(name)
+    }
+    
+    pub fn profiles(&self) -> impl Iterator<Item = (&str, &VelocityCurve)> {
+        self.profiles.iter().map(|(name, curve)| (name.as_str(), curve))
+    }
+    
+    pub fn assign(&mut self, device: &str, profile: &str) -> Result<(), String> {
+        if !self.profiles.contains_key(profile) {
+            return Err(format!("unknown velocity profile: {}", profile));
+        }
+        self.devices.insert(device.to_string(), profile.to_string());
+        Ok(())
+    }
+    
+    pub fn unassign(&mut self, device: &str) -> bool {
+        self.devices.remove(device).is_some()
+    }
+    
+    // (機器名, プロファイル名)
+    pub fn devices(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.devices.iter().map(|(device, profile)| (device.as_str(), profile.as_str()))
+    }
+    
+    pub fn curve_for(&self, device: &str) -> Option<&VelocityCurve> {
+        self.devices.get(device).and_then(|profile| self.profiles.get(profile))
+    }
+    
+    // 割り当てのない機器のベロシティはそのまま通す
+    pub fn apply(&self, device: &str, velocity: f32) -> f32 {
+        self.curve_for(device).map_or(velocity, |curve| curve.apply(velocity))
+    }
+}
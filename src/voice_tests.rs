@@ -0,0 +1,402 @@
+// ボイスの割り当て・演奏操作・ボイスごとのモジュレーションのテスト
+use crate::builder::StealPolicy;
+use crate::event::Event;
+use crate::synth::{EnvelopeStage, Synthesizer};
+
+// サステインペダルを踏んでいる間のノートオフはペダルを離すまで保留される
+#[test]
+fn sustain_pedal_defers_release_until_lifted() {
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    synth.note_on(60, 0.8);
+    synth.note_on(64, 0.8);
+    synth.handle_event(&Event::ControlChange { controller: 64, value: 127 });
+    synth.note_off(60);
+    synth.note_off(60);
+    assert_eq!(synth.sustained_notes(), &[60]);
+    assert_eq!(releasing(&synth), Vec::<u8>::new());
+    
+    // 弾き直したノートは押さえている扱いに戻り、ペダルを離してもリリースされない
+    synth.note_on(60, 0.8);
+    synth.note_off(64);
+    assert_eq!(synth.sustained_notes(), &[64]);
+    synth.handle_event(&Event::ControlChange { controller: 64, value: 0 });
+    assert!(synth.sustained_notes().is_empty());
+    assert_eq!(releasing(&synth), vec![64]);
+}
+
+pub(crate) fn releasing(synth: &Synthesizer) -> Vec<u8> {
+    synth.voice_info().iter()
+        .filter(|voice| voice.stage == EnvelopeStage::Release)
+        .map(|voice| voice.note)
+        .collect()
+}
+
+// パッチの出力トリムと極性はボイスの出力にそのまま掛かり、パッチ差分にも残る
+#[test]
+fn output_trim_and_polarity_scale_voice_output() {
+    use crate::patch::PatchState;
+    use crate::synth::Voice;
+    
+    let reference_patch = PatchState::default();
+    let mut patch = reference_patch.clone();
+    patch.output_trim = 6.0;
+    patch.output_invert = true;
+    let mut reference = Voice::with_patch(48000.0, &reference_patch);
+    let mut trimmed = Voice::with_patch(48000.0, &patch);
+    reference.note_on(57, 0.8);
+    trimmed.note_on(57, 0.8);
+    let gain = -(10.0_f32.powf(6.0 / 20.0));
+    for _ in 0..2000 {
+        let (expected, actual) = (reference.next_frame(), trimmed.next_frame());
+        assert!((actual[0] - expected[0] * gain).abs() < 1e-5);
+        assert!((actual[1] - expected[1] * gain).abs() < 1e-5);
+    }
+    
+    // 範囲外のトリムはクランプされる
+    let mut synth = Synthesizer::builder().sample_rate(48000.0).build();
+    synth.set_output_trim(20.0);
+    assert_eq!(synth.patch().output_trim, 12.0);
+    
+    let delta = patch.to_delta("init").unwrap();
+    assert!(delta.contains("set output.trim 6\n") && delta.contains("set output.invert 1\n"), "{}", delta);
+    let (loaded, warnings) = PatchState::from_delta(&delta).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!((loaded.output_trim, loaded.output_invert), (6.0, true));
+}
+
+// hold は有効にした時点で鳴っているノートだけを固定し、持続時間や note_off を無視する
+#[test]
+fn hold_latches_sounding_notes_until_released() {
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    synth.note_on(60, 0.8);
+    synth.note_on_with_duration(64, 0.8, 0.01);
+    synth.set_hold(true);
+    assert_eq!(synth.held_notes(), &[60, 64]);
+    synth.note_on(67, 0.8);
+    for _ in 0..1000 {
+        synth.next_frame();
+    }
+    synth.note_off(60);
+    synth.note_off(67);
+    assert_eq!(releasing(&synth), vec![67]);
+    
+    synth.set_hold(false);
+    let mut released = releasing(&synth);
+    released.sort_unstable();
+    assert_eq!(released, vec![60, 64, 67]);
+    assert!(synth.held_notes().is_empty());
+}
+
+// オフラインの書き出しは鳴っているノートを引き継ぎ、元のシンセサイザーは進めない
+#[test]
+fn offline_snapshot_renders_sounding_notes() {
+    use crate::render::render_frames;
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    synth.note_on(60, 0.8);
+    synth.note_on(64, 0.8);
+    synth.note_off(64);
+    let mut copy = synth.offline_snapshot();
+    assert_eq!(copy.active_notes(), vec![60]);
+    let frames = render_frames(&mut copy, 0.5);
+    assert_eq!(frames.len(), 11025);
+    assert!(frames[5000..].iter().all(|frame| frame[0].abs() < 1.0) && frames[5000..].iter().any(|frame| frame[0].abs() > 0.01));
+    assert_eq!(synth.voice_info()[0].age_samples, 0);
+}
+
+// ペダルで保留中のノートは hold に移り、hold を解除するまでペダルを離してもリリースされない
+#[test]
+fn hold_takes_over_notes_sustained_by_the_pedal() {
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    synth.note_on(60, 0.8);
+    synth.set_sustain_pedal(true);
+    synth.note_off(60);
+    assert_eq!(synth.pending_release_count(), 1);
+    synth.set_hold(true);
+    assert!(synth.sustained_notes().is_empty());
+    assert_eq!(synth.held_notes(), &[60]);
+    synth.set_sustain_pedal(false);
+    assert_eq!(releasing(&synth), Vec::<u8>::new());
+    synth.set_hold(false);
+    assert_eq!(releasing(&synth), vec![60]);
+    assert_eq!(synth.pending_release_count(), 1);
+}
+
+// モノモードの新しいノートと、ボイスの奪取は、ペダル・hold の保留から外す
+#[test]
+fn mono_mode_and_stealing_drop_deferred_releases() {
+    use crate::builder::VoiceMode;
+    
+    let mut mono = Synthesizer::builder().sample_rate(22050.0).voice_mode(VoiceMode::Mono).build();
+    mono.set_sustain_pedal(true);
+    mono.note_on(60, 0.8);
+    mono.note_off(60);
+    mono.note_on(62, 0.8);
+    assert!(mono.sustained_notes().is_empty());
+    assert_eq!(releasing(&mono), vec![60]);
+    
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).polyphony(2).build();
+    synth.note_on(60, 0.8);
+    synth.next_frame();
+    synth.note_on(62, 0.8);
+    synth.set_hold(true);
+    synth.note_on(64, 0.8);
+    assert_eq!(synth.held_notes(), &[62]);
+    assert_eq!(synth.active_notes(), vec![62, 64]);
+}
+
+// 演奏操作をランダムに重ねても、鍵盤を押さえていないのに発音中のままのボイスは残らない
+#[test]
+fn no_voices_leak_under_random_performance() {
+    use crate::builder::VoiceMode;
+    use crate::random::Rng;
+    
+    for (seed, mode) in [(1, VoiceMode::Poly), (2, VoiceMode::Mono), (3, VoiceMode::Paraphonic(3))] {
+        let mut rng = Rng::new(seed);
+        let mut synth = Synthesizer::builder().sample_rate(22050.0).polyphony(4).voice_mode(mode).build();
+        synth.set_release(0.01);
+        let mut keys_down: Vec<u8> = Vec::new();
+        for _ in 0..2000 {
+            let note = 60 + (rng.next_u64() % 8) as u8;
+            match rng.next_u64() % 10 {
+                0..=3 => {
+                    synth.note_on(note, 0.8);
+                    if !keys_down.contains(&note) {
+                        keys_down.push(note);
+                    }
+                }
+                4..=7 => {
+                    synth.note_off(note);
+                    keys_down.retain(|key| *key != note);
+                }
+                8 => synth.set_sustain_pedal(!synth.sustain_pedal()),
+                _ => synth.set_hold(!synth.hold()),
+            }
+            for _ in 0..(rng.next_u64() % 64) {
+                synth.next_frame();
+            }
+            
+            // 保留・固定されていない発音中のボイスは、すべて押さえている鍵盤のもの
+            let deferred: Vec<u8> = synth.sustained_notes().iter().chain(synth.held_notes()).copied().collect();
+            for note in synth.active_notes().into_iter().filter(|note| !deferred.contains(note)) {
+                assert!(keys_down.contains(&note), "note {} sounds without a key", note);
+            }
+            assert!(synth.pending_release_count() <= synth.voice_count());
+        }
+        
+        for note in keys_down.drain(..) {
+            synth.note_off(note);
+        }
+        synth.set_sustain_pedal(false);
+        synth.set_hold(false);
+        synth.next_frame();
+        assert!(synth.active_notes().is_empty());
+        assert!(synth.sustained_notes().is_empty() && synth.held_notes().is_empty());
+        // 残っているボイスはすべてリリース待ちとして数えられる
+        assert_eq!(synth.pending_release_count(), synth.voice_count());
+    }
+}
+
+// テンポ同期のエンベロープは拍で長さが決まり、鳴っている途中でもテンポの変更に追従する
+#[test]
+fn tempo_synced_envelope_follows_the_tempo() {
+    use crate::modulation::{parse_division, Lfo};
+    use crate::synth::Envelope;
+    
+    assert_eq!(parse_division("1/4"), Some(1.0));
+    assert_eq!(parse_division("1/8."), Some(0.75));
+    assert!(parse_division("1/8t").is_some_and(|beats| (beats - 1.0 / 3.0).abs() < 1e-6));
+    assert_eq!(parse_division("2/1"), Some(8.0));
+    assert_eq!(parse_division("0.5"), None);
+    
+    let sample_rate = 8000.0;
+    let stage_after = |synth: &mut Synthesizer, seconds: f32| {
+        for _ in 0..(seconds * sample_rate) as usize {
+            synth.next_frame();
+        }
+        synth.voice_info()[0].stage
+    };
+    let mut synth = Synthesizer::builder().sample_rate(sample_rate).build();
+    synth.set_tempo(120.0);
+    synth.set_envelope(Envelope { attack: 1.0, tempo_sync: true, ..Envelope::default() });
+    synth.note_on(60, 0.8);
+    assert_eq!(stage_after(&mut synth, 0.45), EnvelopeStage::Attack);
+    assert_ne!(stage_after(&mut synth, 0.1), EnvelopeStage::Attack);
+    
+    // 半分のテンポでは2倍の長さ（途中で変えても残りが伸びる）
+    synth.note_on(60, 0.8);
+    assert_eq!(stage_after(&mut synth, 0.25), EnvelopeStage::Attack);
+    synth.set_tempo(60.0);
+    assert_eq!(stage_after(&mut synth, 0.7), EnvelopeStage::Attack);
+    assert_ne!(stage_after(&mut synth, 0.1), EnvelopeStage::Attack);
+    
+    // 単位を切り替えても今のテンポでの長さは変わらない
+    let seconds = synth.patch().envelope.with_tempo_sync(false, synth.tempo());
+    assert!(!seconds.tempo_sync && (seconds.attack - 1.0).abs() < 1e-6);
+    assert!((seconds.release_seconds(60.0) - synth.patch().envelope.release_seconds(60.0)).abs() < 1e-6);
+    
+    let lfo = Lfo { fade: 2.0, fade_sync: true, ..Lfo::default() };
+    assert_eq!(lfo.fade_seconds(120.0), 1.0);
+}
+
+// 使い回すボイスは reset で発音前の状態に戻り、新しく作ったボイスと同じ音を出す
+// （モジュレーションで動かした値・FM のフィードバック・フィルターの状態を次のノートへ持ち越さない）
+#[test]
+fn reset_voice_sounds_like_a_new_one() {
+    use crate::modulation::{ModDestination, ModRouting, ModSource};
+    use crate::patch::PatchState;
+    use crate::synth::Voice;
+    
+    let mut plain = PatchState::base_preset("fm").unwrap();
+    plain.cutoff = 0.4;
+    plain.resonance = 0.9;
+    plain.stack_density = 1;
+    for operator in &mut plain.operators {
+        operator.feedback = 0.7;
+    }
+    let mut modulated = plain.clone();
+    for (source, destination) in [
+        (ModSource::Lfo, ModDestination::Cutoff),
+        (ModSource::Velocity, ModDestination::Pitch),
+        (ModSource::Velocity, ModDestination::Blend),
+        (ModSource::Velocity, ModDestination::Brightness),
+        (ModSource::Velocity, ModDestination::Pan),
+    ] {
+        modulated.mod_routings.push(ModRouting::new(source, destination, 0.8));
+    }
+    
+    let mut used = Voice::with_patch(48000.0, &modulated);
+    used.note_on(36, 1.0);
+    for _ in 0..4800 {
+        used.next_frame();
+    }
+    used.note_off(0.5);
+    for _ in 0..480 {
+        used.next_frame();
+    }
+    // プールから取り出すときと同じ順に、モジュレーションの無い音色で次のノートを鳴らす
+    used.reset();
+    used.apply_patch(&plain);
+    used.note_on(60, 0.7);
+    let mut fresh = Voice::with_patch(48000.0, &plain);
+    fresh.note_on(60, 0.7);
+    for i in 0..4800 {
+        let (expected, actual) = (fresh.next_frame(), used.next_frame());
+        for channel in 0..2 {
+            assert!((actual[channel] - expected[channel]).abs() < 1e-6, "sample {} channel {}: {} vs {}", i, channel, actual[channel], expected[channel]);
+        }
+    }
+}
+
+// グライドを有効にすると、新しいボイスは直前にリリースしたボイス（nearest なら押さえている最も近いボイス）の音高から滑る
+#[test]
+fn poly_glide_starts_from_the_previous_voice() {
+    let glide = |synth: &Synthesizer, note: u8| synth.voice_info().iter().find(|voice| voice.note == note).map(|voice| voice.glide);
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    synth.note_on(60, 0.8);
+    synth.note_off(60);
+    synth.note_on(64, 0.8);
+    assert_eq!(glide(&synth, 64), Some(0.0));
+    
+    synth.set_glide(0.1, false);
+    synth.note_off(64);
+    synth.note_on(67, 0.8);
+    assert!(glide(&synth, 67).is_some_and(|semitones| (semitones + 3.0).abs() < 1e-3));
+    for _ in 0..(22050.0 * 0.05) as usize {
+        synth.next_frame();
+    }
+    assert!(glide(&synth, 67).is_some_and(|semitones| (semitones + 1.5).abs() < 0.1));
+    for _ in 0..(22050.0 * 0.06) as usize {
+        synth.next_frame();
+    }
+    assert_eq!(glide(&synth, 67), Some(0.0));
+    
+    // 和音では押さえているうち最も近いボイスから
+    synth.set_glide(0.1, true);
+    synth.note_on(72, 0.8);
+    synth.note_on(48, 0.8);
+    assert!(glide(&synth, 72).is_some_and(|semitones| (semitones + 5.0).abs() < 1e-3));
+    assert!(glide(&synth, 48).is_some_and(|semitones| (semitones - 19.0).abs() < 1e-3));
+    assert_eq!(synth.patch().to_delta("init").unwrap().lines().filter(|line| line.starts_with("set glide.")).count(), 2);
+}
+
+// 同時発音数を超えると奪い方に従ってボイスを奪う。同時発音数は演奏中に減らせて、超えている分はその場で奪う
+#[test]
+fn voice_stealing_follows_the_policy() {
+    let play = |policy: StealPolicy, chord: [(u8, f32); 3], note: u8| {
+        let mut synth = Synthesizer::builder().sample_rate(22050.0).polyphony(3).steal_policy(policy).build();
+        for (held, velocity) in chord {
+            synth.note_on(held, velocity);
+            for _ in 0..64 {
+                synth.next_frame();
+            }
+        }
+        synth.note_on(note, 0.8);
+        synth.active_notes()
+    };
+    let chord = [(60, 0.8), (64, 0.2), (67, 0.8)];
+    assert_eq!(play(StealPolicy::Oldest, chord, 72), vec![64, 67, 72]);
+    assert_eq!(play(StealPolicy::Quietest, chord, 72), vec![60, 67, 72]);
+    assert_eq!(play(StealPolicy::SameNote, chord, 79), vec![60, 64, 79]);
+    assert_eq!(play(StealPolicy::SameNote, chord, 74), vec![64, 67, 74]);
+    
+    // リリース中のボイスはどの奪い方でも先に奪う
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).polyphony(3).steal_policy(StealPolicy::Quietest).build();
+    for (held, velocity) in chord {
+        synth.note_on(held, velocity);
+    }
+    synth.next_frame();
+    synth.note_off(67);
+    synth.note_on(72, 0.8);
+    assert_eq!(synth.active_notes(), vec![60, 64, 72]);
+    assert_eq!(synth.voice_count(), 3);
+    
+    synth.set_polyphony(2);
+    assert_eq!(synth.polyphony(), 2);
+    assert_eq!(synth.active_notes(), vec![60, 64]); // 弾いたばかりの 72 が最も小さい
+    synth.note_on(76, 0.8);
+    assert_eq!(synth.voice_count(), 2);
+    
+    // 奪い方に従って、鳴っているボイスを新しい同時発音数まで減らす
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).polyphony(4).steal_policy(StealPolicy::Oldest).build();
+    for note in [60, 64, 67, 71] {
+        synth.note_on(note, 0.8);
+        synth.next_frame();
+    }
+    synth.set_polyphony(2);
+    assert_eq!(synth.active_notes(), vec![67, 71]);
+    for _ in 0..2205 {
+        synth.next_frame();
+    }
+    assert_eq!(synth.voice_count(), 2);
+}
+
+// ノートオフの後もリリースの間は鳴り続けて徐々に小さくなり、エンベロープが Idle になってから回収される
+#[test]
+fn release_tail_sounds_until_the_envelope_is_idle() {
+    let mut synth = Synthesizer::builder().sample_rate(22050.0).build();
+    let peak = |block: &[[f32; 2]]| block.iter().fold(0.0_f32, |peak, frame| peak.max(frame[0].abs()));
+    let mut block = vec![[0.0; 2]; 2205];
+    synth.note_on(60, 0.8);
+    synth.render_block(&mut block);
+    let held = peak(&block);
+    
+    // 既定のリリースは 0.2 秒なので、0.1 秒ずつ2回に分けて鳴る
+    synth.note_off(60);
+    synth.render_block(&mut block);
+    let early = peak(&block);
+    synth.render_block(&mut block);
+    let late = peak(&block);
+    assert!(held > early && early > late && late > 0.0, "{} {} {}", held, early, late);
+    assert_eq!(releasing(&synth), vec![60]);
+    
+    synth.render_block(&mut block);
+    assert!(peak(&block) < 1e-4);
+    assert_eq!(synth.voice_count(), 0);
+    
+    // 持続時間を指定したノートも、時間が来たらリリースに入る
+    synth.note_on_with_duration(64, 0.8, 0.05);
+    synth.render_block(&mut block);
+    assert_eq!(releasing(&synth), vec![64]);
+    assert!(peak(&block[1500..]) > 0.0);
+}
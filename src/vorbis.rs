@@ -0,0 +1,665 @@
+use std::collections::BinaryHeap;
+use std::cmp::Reverse;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use crate::ogg::OggWriter;
+
+pub const DEFAULT_QUALITY: u32 = 5;
+pub const MAX_QUALITY: u32 = 10;
+
+const CHANNELS: usize = 2;
+const SHORT_BLOCK_EXPONENT: u32 = 8; // 識別ヘッダーに必要なだけで使わない
+const BLOCK_EXPONENT: u32 = 11; // すべて 2048 サンプルの長いブロックで符号化する
+const BLOCK_SIZE: usize = 1 << BLOCK_EXPONENT;
+const HALF: usize = BLOCK_SIZE / 2; // 係数の数と、1パケットで進むサンプル数
+const FLOOR_MULTIPLIER: u32 = 2;
+const FLOOR_RANGE: u32 = 128; // multiplier 2 のときの Y の範囲
+const FLOOR_Y_BITS: u32 = 7; // 両端の Y のビット数（ilog(FLOOR_RANGE - 1)）
+const FLOOR_X_BITS: u32 = 10; // X は 0〜1024
+const FLOOR_PARTITIONS: usize = 9;
+const FLOOR_CLASS_DIMENSIONS: usize = 7; // 両端のほかに 9 × 7 = 63 点
+const FLOOR_MIN: f32 = 1.064_986_3e-7; // 逆 dB 表の最初の値（表はここから 1.0 までの等比数列）
+const RESIDUE_PARTITION: usize = 32;
+const STREAM_SERIAL: u32 = 0x5359_4E54; // 論理ストリームは1本だけなので固定
+const SILENCE: f32 = 1e-6; // 係数がすべてこれより小さいチャンネルは床を 0 にして残差を送らない
+
+const COARSE_STEP: i32 = 16;
+const MAX_RESIDUE: i32 = 4 * COARSE_STEP + 8;
+const RESIDUE_PASSES: usize = 2;
+
+// 残差の区画の分類。区画の値の最大と、段ごとに使う符号帳（分類 0 は全部 0 で何も送らない）。
+// 最後の分類は 16 刻みの粗い値と ±8 の残りの2段で送る
+const RESIDUE_CLASSES: [(i32, [Option<usize>; RESIDUE_PASSES]); 5] = [
+    (0, [None, None]),
+    (1, [Some(2), None]),
+    (4, [Some(3), None]),
+    (15, [Some(4), None]),
+    (MAX_RESIDUE, [Some(5), Some(4)]),
+];
+const CLASS_BOOK: usize = 1;
+const FLOOR_BOOK: usize = 0;
+
+// ステレオの Ogg Vorbis を少しずつ書き出す（長いブロックだけ、床は 1 型の固定の 65 点、残差は 1 型の簡易なエンコーダー）。
+// quality（0〜10）で残差の細かさが変わる
+pub struct VorbisWriter {
+    ogg: OggWriter<BufWriter<File>>,
+    encoder: Encoder,
+    pending: Vec<[f32; 2]>, // 次のブロックの先頭からのサンプル（最初は前半が無音）
+    packets: u64,
+    frames: u64,
+}
+
+impl VorbisWriter {
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32, quality: u32) -> io::Result<Self> {
+        if quality > MAX_QUALITY {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unsupported Vorbis quality: {} (use 0 to {})", quality, MAX_QUALITY)));
+        }
+        let encoder = Encoder::new(quality);
+        let mut ogg = OggWriter::new(BufWriter::new(File::create(path)?), STREAM_SERIAL);
+        ogg.write_packet(&identification_header(sample_rate), 0)?;
+        ogg.flush()?;
+        ogg.write_packet(&comment_header(), 0)?;
+        ogg.write_packet(&encoder.setup_header(), 0)?;
+        ogg.flush()?;
+        
+        let mut pending = Vec::with_capacity(BLOCK_SIZE * 2);
+        pending.resize(HALF, [0.0; 2]);
+        Ok(Self { ogg, encoder, pending, packets: 0, frames: 0 })
+    }
+    
+    pub fn write(&mut self, frames: &[[f32; 2]]) -> io::Result<()> {
+        self.frames += frames.len() as u64;
+        self.pending.extend_from_slice(frames);
+        while self.pending.len() >= BLOCK_SIZE {
+            self.encode_pending(self.packets * HALF as u64)?;
+        }
+        Ok(())
+    }
+    
+    // 残りを無音で埋めて書き出し、最後のページの位置で長さを切り詰める。書き出したフレーム数を返す
+    pub fn finish(mut self) -> io::Result<u64> {
+        let packets = self.frames.div_ceil(HALF as u64);
+        while self.packets <= packets {
+            self.pending.resize(BLOCK_SIZE.max(self.pending.len()), [0.0; 2]);
+            let granule = (self.packets * HALF as u64).min(self.frames);
+            self.encode_pending(granule)?;
+        }
+        self.ogg.finish()?;
+        Ok(self.frames)
+    }
+    
+    // パケット k は前のパケットとの重なりから (k - 1) × 1024 〜 k × 1024 のサンプルを復元する（最初のパケットは何も出さない）
+    fn encode_pending(&mut self, granule: u64) -> io::Result<()> {
+        let packet = self.encoder.encode_block(&self.pending[..BLOCK_SIZE]);
+        self.ogg.write_packet(&packet, granule)?;
+        // 何も出さない最初のパケットは単独のページにして、短いファイルでも最後のページで長さを切り詰められるようにする
+        if self.packets == 0 {
+            self.ogg.flush()?;
+        }
+        self.pending.drain(..HALF);
+        self.packets += 1;
+        Ok(())
+    }
+}
+
+fn identification_header(sample_rate: u32) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    bits.put(1, 8);
+    bits.put_bytes(b"vorbis");
+    bits.put(0, 32); // 版
+    bits.put(CHANNELS as u32, 8);
+    bits.put(sample_rate, 32);
+    bits.put(0, 32); // 最大・公称・最小のビットレート（指定しない）
+    bits.put(0, 32);
+    bits.put(0, 32);
+    bits.put(SHORT_BLOCK_EXPONENT, 4);
+    bits.put(BLOCK_EXPONENT, 4);
+    bits.put(1, 1); // フレーミングビット
+    bits.finish()
+}
+
+fn comment_header() -> Vec<u8> {
+    let vendor = concat!("synthesizer ", env!("CARGO_PKG_VERSION"));
+    let mut bits = BitWriter::default();
+    bits.put(3, 8);
+    bits.put_bytes(b"vorbis");
+    bits.put(vendor.len() as u32, 32);
+    bits.put_bytes(vendor.as_bytes());
+    bits.put(0, 32); // ユーザーコメントの数
+    bits.put(1, 1);
+    bits.finish()
+}
+
+// ハフマン符号の長さと符号語。lookup は VQ の値の最小値・刻み・個数
+struct Codebook {
+    dimensions: usize,
+    lengths: Vec<u8>,
+    codewords: Vec<u32>,
+    lookup: Option<(i32, i32, u32)>,
+}
+
+impl Codebook {
+    fn new(dimensions: usize, weights: &[f64], lookup: Option<(i32, i32, u32)>) -> Self {
+        let lengths = huffman_lengths(weights);
+        let codewords = codewords(&lengths);
+        Self { dimensions, lengths, codewords, lookup }
+    }
+    
+    // step 刻みの ±range × step の値を dimensions 個ずつまとめる VQ の符号帳。0 に近い組ほど短い符号にする
+    fn vector(dimensions: usize, range: i32, step: i32, decay: f64) -> Self {
+        let values = (2 * range + 1) as u32;
+        let weights: Vec<f64> = (0..values.pow(dimensions as u32))
+            .map(|entry| {
+                (0..dimensions)
+                    .map(|dimension| {
+                        let value = (entry / values.pow(dimension as u32) % values) as i32 - range;
+                        (-decay * value.abs() as f64).exp()
+                    })
+                    .product()
+            })
+            .collect();
+        Self::new(dimensions, &weights, Some((-range * step, step, values)))
+    }
+    
+    fn put_entry(&self, bits: &mut BitWriter, entry: usize) {
+        // 符号語は上位ビットから読まれる
+        let (codeword, length) = (self.codewords[entry], self.lengths[entry] as u32);
+        for shift in (0..length).rev() {
+            bits.put((codeword >> shift) & 1, 1);
+        }
+    }
+    
+    // values（符号帳の値のどれか）を1つの項目の番号にする。最初の次元が最下位の桁
+    fn put_vector(&self, bits: &mut BitWriter, values: &[i32]) {
+        let (minimum, step, count) = self.lookup.expect("vector codebook");
+        let entry = values.iter().rev().fold(0, |entry, value| entry * count + ((value - minimum) / step) as u32);
+        self.put_entry(bits, entry as usize);
+    }
+    
+    fn write_header(&self, bits: &mut BitWriter) {
+        bits.put(0x56_4342, 24);
+        bits.put(self.dimensions as u32, 16);
+        bits.put(self.lengths.len() as u32, 24);
+        bits.put(0, 1); // 長さの順に並べない
+        bits.put(0, 1); // すべての項目を使う
+        for length in &self.lengths {
+            bits.put(*length as u32 - 1, 5);
+        }
+        match self.lookup {
+            None => bits.put(0, 4),
+            Some((minimum, step, count)) => {
+                bits.put(1, 4);
+                bits.put_float(minimum);
+                bits.put_float(step);
+                let value_bits = ilog(count - 1);
+                bits.put(value_bits - 1, 4);
+                bits.put(0, 1); // 前の次元の値を足さない
+                for value in 0..count {
+                    bits.put(value, value_bits);
+                }
+            }
+        }
+    }
+}
+
+struct Encoder {
+    codebooks: Vec<Codebook>,
+    posts: Vec<u32>, // 床の点の X（両端の 0 と 1024 が先頭）
+    sorted: Vec<usize>, // X の小さい順の点の番号
+    neighbours: Vec<(usize, usize)>, // それより前の点のうち、X がすぐ下とすぐ上の点
+    window: Vec<f32>,
+    twiddles: Vec<[f32; 2]>,
+    target: f32, // 床に対する残差の最大（品質 0 で 4、10 で 60。大きいほど細かく量子化する）
+}
+
+impl Encoder {
+    fn new(quality: u32) -> Self {
+        let codebooks = vec![
+            Codebook::new(1, &[1.0; FLOOR_RANGE as usize], None),
+            Codebook::new(2, &[1.0; RESIDUE_CLASSES.len() * RESIDUE_CLASSES.len()], None),
+            Codebook::vector(4, 1, 1, 1.2),
+            Codebook::vector(2, 4, 1, 0.6),
+            Codebook::vector(2, 15, 1, 0.25),
+            Codebook::vector(2, 4, COARSE_STEP, 0.8),
+        ];
+        // 両端のほかは低い周波数ほど細かく置く
+        let count = FLOOR_PARTITIONS * FLOOR_CLASS_DIMENSIONS;
+        let mut posts = vec![0, HALF as u32];
+        let mut previous = 0;
+        for index in 0..count {
+            let x = (2.0 * 500f32.powf(index as f32 / (count - 1) as f32)).round() as u32;
+            previous = x.max(previous + 1);
+            posts.push(previous);
+        }
+        let mut sorted: Vec<usize> = (0..posts.len()).collect();
+        sorted.sort_by_key(|index| posts[*index]);
+        let neighbours = (0..posts.len())
+            .map(|index| {
+                let below = (0..index).filter(|other| posts[*other] < posts[index]).max_by_key(|other| posts[*other]);
+                let above = (0..index).filter(|other| posts[*other] > posts[index]).min_by_key(|other| posts[*other]);
+                (below.unwrap_or(0), above.unwrap_or(0))
+            })
+            .collect();
+        let window = (0..BLOCK_SIZE)
+            .map(|i| {
+                let slope = ((i as f32 + 0.5) / BLOCK_SIZE as f32 * PI).sin();
+                (PI / 2.0 * slope * slope).sin()
+            })
+            .collect();
+        let twiddles = (0..HALF / 4)
+            .map(|k| {
+                let angle = -2.0 * PI * k as f32 / (HALF / 2) as f32;
+                [angle.cos(), angle.sin()]
+            })
+            .collect();
+        Self { codebooks, posts, sorted, neighbours, window, twiddles, target: 4.0 * 15f32.powf(quality as f32 / MAX_QUALITY as f32) }
+    }
+    
+    fn setup_header(&self) -> Vec<u8> {
+        let mut bits = BitWriter::default();
+        bits.put(5, 8);
+        bits.put_bytes(b"vorbis");
+        bits.put(self.codebooks.len() as u32 - 1, 8);
+        for codebook in &self.codebooks {
+            codebook.write_header(&mut bits);
+        }
+        bits.put(0, 6); // 時間領域の変換（予約で 0 が1つ）
+        bits.put(0, 16);
+        
+        bits.put(0, 6); // 床は1つ（1 型）
+        bits.put(1, 16);
+        bits.put(FLOOR_PARTITIONS as u32, 5);
+        for _ in 0..FLOOR_PARTITIONS {
+            bits.put(0, 4); // すべて分類 0
+        }
+        bits.put(FLOOR_CLASS_DIMENSIONS as u32 - 1, 3);
+        bits.put(0, 2); // 下位分類なし
+        bits.put(FLOOR_BOOK as u32 + 1, 8);
+        bits.put(FLOOR_MULTIPLIER - 1, 2);
+        bits.put(FLOOR_X_BITS, 4);
+        for x in &self.posts[2..] {
+            bits.put(*x, FLOOR_X_BITS);
+        }
+        
+        bits.put(0, 6); // 残差は1つ（1 型）
+        bits.put(1, 16);
+        bits.put(0, 24);
+        bits.put(HALF as u32, 24);
+        bits.put(RESIDUE_PARTITION as u32 - 1, 24);
+        bits.put(RESIDUE_CLASSES.len() as u32 - 1, 6);
+        bits.put(CLASS_BOOK as u32, 8);
+        for (_, books) in RESIDUE_CLASSES {
+            let cascade = books.iter().enumerate().fold(0, |cascade, (pass, book)| cascade | (book.is_some() as u32) << pass);
+            bits.put(cascade, 3);
+            bits.put(0, 1);
+        }
+        for book in RESIDUE_CLASSES.iter().flat_map(|(_, books)| books).flatten() {
+            bits.put(*book as u32, 8);
+        }
+        
+        bits.put(0, 6); // 対応づけは1つ。チャンネルの結合はせず、両方が床 0 と残差 0 を使う
+        bits.put(0, 16);
+        bits.put(0, 1);
+        bits.put(0, 1);
+        bits.put(0, 2);
+        bits.put(0, 8);
+        bits.put(0, 8);
+        bits.put(0, 8);
+        
+        bits.put(0, 6); // モードは長いブロックの1つ
+        bits.put(1, 1);
+        bits.put(0, 16);
+        bits.put(0, 16);
+        bits.put(0, 8);
+        bits.put(1, 1);
+        bits.finish()
+    }
+    
+    fn encode_block(&self, block: &[[f32; 2]]) -> Vec<u8> {
+        let mut bits = BitWriter::default();
+        bits.put(0, 1); // 音声のパケット
+        bits.put(1, 1); // 前後のブロックも長い
+        bits.put(1, 1);
+        
+        // 床を先に全チャンネル分書き、残差をその後にまとめて書く
+        let mut residues = Vec::with_capacity(CHANNELS);
+        for channel in 0..CHANNELS {
+            let coefficients = self.mdct(block.iter().map(|frame| frame[channel]));
+            let residue = self.encode_floor(&mut bits, &coefficients).map(|floor| {
+                coefficients
+                    .iter()
+                    .zip(&floor)
+                    .map(|(coefficient, floor)| ((coefficient / floor).round() as i32).clamp(-MAX_RESIDUE, MAX_RESIDUE))
+                    .collect::<Vec<_>>()
+            });
+            residues.push(residue);
+        }
+        self.encode_residue(&mut bits, &residues);
+        bits.finish()
+    }
+    
+    // 窓を掛けた 2048 サンプルを 1024 個の係数にする。
+    // 復号側の逆変換（係数をそのまま足す DCT-IV と折り返し）と窓の重ね合わせで元に戻るよう 2 / 1024 倍する
+    fn mdct(&self, samples: impl Iterator<Item = f32>) -> Vec<f32> {
+        let windowed: Vec<f32> = samples.zip(&self.window).map(|(sample, window)| sample * window).collect();
+        let quarter = BLOCK_SIZE / 4;
+        let folded: Vec<f32> = (0..HALF)
+            .map(|m| {
+                if m < quarter {
+                    -windowed[m + 3 * quarter] - windowed[3 * quarter - 1 - m]
+                } else {
+                    windowed[m - quarter] - windowed[3 * quarter - 1 - m]
+                }
+            })
+            .collect();
+        let mut coefficients = dct_iv(&folded, &self.twiddles);
+        for coefficient in &mut coefficients {
+            *coefficient *= 2.0 / HALF as f32;
+        }
+        coefficients
+    }
+    
+    // 床の点ごとに、両隣の点までの係数の最大を target で割った高さを置く（点の間の直線は常に係数 / target 以上になる）。
+    // 書き出した床の曲線を返す（無音なら None）
+    fn encode_floor(&self, bits: &mut BitWriter, coefficients: &[f32]) -> Option<Vec<f32>> {
+        if coefficients.iter().all(|coefficient| coefficient.abs() < SILENCE) {
+            bits.put(0, 1);
+            return None;
+        }
+        bits.put(1, 1);
+        
+        let mut desired = vec![0; self.posts.len()];
+        for (position, index) in self.sorted.iter().enumerate() {
+            let low = self.posts[self.sorted[position.saturating_sub(1)]] as usize;
+            let high = self.sorted.get(position + 1).map_or(HALF, |next| self.posts[*next] as usize).min(HALF);
+            let peak = coefficients[low..high.max(low + 1).min(HALF)].iter().fold(0.0f32, |peak, value| peak.max(value.abs()));
+            let amplitude = peak / self.target;
+            desired[*index] = (0..FLOOR_RANGE).find(|y| floor_amplitude(y * FLOOR_MULTIPLIER) >= amplitude).unwrap_or(FLOOR_RANGE - 1);
+        }
+        
+        // 両端はそのまま、ほかの点は両隣から予測した値との差を符号化する
+        let mut final_y = desired.clone();
+        bits.put(final_y[0], FLOOR_Y_BITS);
+        bits.put(final_y[1], FLOOR_Y_BITS);
+        for index in 2..self.posts.len() {
+            let (low, high) = self.neighbours[index];
+            let predicted = render_point(self.posts[low], final_y[low], self.posts[high], final_y[high], self.posts[index]);
+            // 予測と同じ値は「使わない点」になってしまうので1段上げる
+            let mut y = desired[index];
+            if y == predicted {
+                y = if y + 1 < FLOOR_RANGE { y + 1 } else { y - 1 };
+            }
+            final_y[index] = y;
+            let (high_room, low_room) = (FLOOR_RANGE - predicted, predicted);
+            let room = 2 * high_room.min(low_room);
+            let value = if y < predicted && 2 * (predicted - y) - 1 < room {
+                2 * (predicted - y) - 1
+            } else if y > predicted && 2 * (y - predicted) < room {
+                2 * (y - predicted)
+            } else if high_room > low_room {
+                y - predicted + low_room
+            } else {
+                predicted - y + high_room - 1
+            };
+            self.codebooks[FLOOR_BOOK].put_entry(bits, value as usize);
+        }
+        
+        // すべての点を使うので、X の順に点を結んだ折れ線になる
+        let mut curve = Vec::with_capacity(HALF);
+        for pair in self.sorted.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            render_line(self.posts[from], final_y[from] * FLOOR_MULTIPLIER, self.posts[to], final_y[to] * FLOOR_MULTIPLIER, &mut curve);
+        }
+        Some(curve.into_iter().map(floor_amplitude).collect())
+    }
+    
+    // 区画ごとに値の大きさで分類する。最初の段で分類を2区画ずつまとめて書き、段ごとに全区画の値を書く（1 型）
+    fn encode_residue(&self, bits: &mut BitWriter, residues: &[Option<Vec<i32>>]) {
+        let partitions = HALF / RESIDUE_PARTITION;
+        let classes: Vec<Option<Vec<usize>>> = residues
+            .iter()
+            .map(|residue| {
+                residue.as_ref().map(|residue| {
+                    residue
+                        .chunks(RESIDUE_PARTITION)
+                        .map(|partition| {
+                            let peak = partition.iter().map(|value| value.abs()).max().unwrap_or(0);
+                            RESIDUE_CLASSES.iter().position(|(range, _)| peak <= *range).unwrap_or(RESIDUE_CLASSES.len() - 1)
+                        })
+                        .collect()
+                })
+            })
+            .collect();
+        let per_codeword = self.codebooks[CLASS_BOOK].dimensions;
+        for pass in 0..RESIDUE_PASSES {
+            for start in (0..partitions).step_by(per_codeword) {
+                if pass == 0 {
+                    for classes in classes.iter().flatten() {
+                        let entry = classes[start..start + per_codeword].iter().fold(0, |entry, class| entry * RESIDUE_CLASSES.len() + class);
+                        self.codebooks[CLASS_BOOK].put_entry(bits, entry);
+                    }
+                }
+                for partition in start..start + per_codeword {
+                    for (residue, classes) in residues.iter().zip(&classes) {
+                        let (Some(residue), Some(classes)) = (residue, classes) else {
+                            continue;
+                        };
+                        let books = RESIDUE_CLASSES[classes[partition]].1;
+                        let Some(book) = books[pass] else {
+                            continue;
+                        };
+                        let cascaded = books[1].is_some();
+                        let codebook = &self.codebooks[book];
+                        let values = &residue[partition * RESIDUE_PARTITION..(partition + 1) * RESIDUE_PARTITION];
+                        for chunk in values.chunks(codebook.dimensions) {
+                            let mut vector = [0; 4];
+                            for (slot, value) in vector.iter_mut().zip(chunk) {
+                                // 2段の分類では、最初の段が粗い値、次の段がその残り
+                                let coarse = ((*value as f32 / COARSE_STEP as f32).round() as i32).clamp(-4, 4) * COARSE_STEP;
+                                *slot = match (cascaded, pass) {
+                                    (false, _) => *value,
+                                    (true, 0) => coarse,
+                                    (true, _) => value - coarse,
+                                };
+                            }
+                            codebook.put_vector(bits, &vector[..chunk.len()]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 床の高さ（0〜255）を振幅にする
+fn floor_amplitude(index: u32) -> f32 {
+    (FLOOR_MIN.ln() * (1.0 - index as f32 / 255.0)).exp()
+}
+
+// (x0, y0) と (x1, y1) を結ぶ直線の x での高さ（仕様どおりの整数演算）
+fn render_point(x0: u32, y0: u32, x1: u32, y1: u32, x: u32) -> u32 {
+    let dy = y1 as i32 - y0 as i32;
+    let offset = (dy.abs() * (x - x0) as i32 / (x1 - x0) as i32) as u32;
+    if dy < 0 {
+        y0 - offset
+    } else {
+        y0 + offset
+    }
+}
+
+// x0 から x1 の手前までの直線を curve に足す（復号側と同じ整数の刻み）
+fn render_line(x0: u32, y0: u32, x1: u32, y1: u32, curve: &mut Vec<u32>) {
+    let dy = y1 as i32 - y0 as i32;
+    let dx = (x1 - x0) as i32;
+    let base = dy / dx;
+    let step = if dy < 0 { base - 1 } else { base + 1 };
+    let remainder = dy.abs() - base.abs() * dx;
+    let (mut y, mut error) = (y0 as i32, 0);
+    curve.push(y as u32);
+    for _ in x0 + 1..x1 {
+        error += remainder;
+        if error >= dx {
+            error -= dx;
+            y += step;
+        } else {
+            y += base;
+        }
+        curve.push(y as u32);
+    }
+}
+
+// 大きさ N の DCT-IV を N / 2 点の複素 FFT で求める
+fn dct_iv(input: &[f32], twiddles: &[[f32; 2]]) -> Vec<f32> {
+    let size = input.len();
+    let mut data: Vec<[f32; 2]> = (0..size / 2)
+        .map(|n| {
+            let angle = -PI * (n as f32 + 0.25) / size as f32;
+            complex_multiply([input[2 * n], input[size - 1 - 2 * n]], [angle.cos(), angle.sin()])
+        })
+        .collect();
+    fft(&mut data, twiddles);
+    let mut output = vec![0.0; size];
+    for (k, value) in data.into_iter().enumerate() {
+        let angle = -PI * k as f32 / size as f32;
+        let [re, im] = complex_multiply(value, [angle.cos(), angle.sin()]);
+        output[2 * k] = re;
+        output[size - 1 - 2 * k] = -im;
+    }
+    output
+}
+
+// 基数 2 の FFT（twiddles は e^(-2πik/N) の前半）
+fn fft(data: &mut [[f32; 2]], twiddles: &[[f32; 2]]) {
+    let size = data.len();
+    let mut j = 0;
+    for i in 1..size {
+        let mut bit = size >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+    let mut length = 2;
+    while length <= size {
+        let stride = size / length;
+        for start in (0..size).step_by(length) {
+            for k in 0..length / 2 {
+                let odd = complex_multiply(data[start + k + length / 2], twiddles[k * stride]);
+                let even = data[start + k];
+                data[start + k] = [even[0] + odd[0], even[1] + odd[1]];
+                data[start + k + length / 2] = [even[0] - odd[0], even[1] - odd[1]];
+            }
+        }
+        length <<= 1;
+    }
+}
+
+fn complex_multiply(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+// 重みからハフマン符号の長さを求める（木が完全になるので復号側で未使用の符号が残らない）
+fn huffman_lengths(weights: &[f64]) -> Vec<u8> {
+    let total: f64 = weights.iter().sum();
+    let mut heap: BinaryHeap<Reverse<(u64, usize)>> = weights
+        .iter()
+        .enumerate()
+        .map(|(index, weight)| Reverse((((weight / total) * 1e12) as u64 + 1, index)))
+        .collect();
+    let mut parents = vec![usize::MAX; weights.len()];
+    while heap.len() > 1 {
+        let (Some(Reverse((a, first))), Some(Reverse((b, second)))) = (heap.pop(), heap.pop()) else {
+            break;
+        };
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[first] = node;
+        parents[second] = node;
+        heap.push(Reverse((a + b, node)));
+    }
+    (0..weights.len())
+        .map(|leaf| {
+            let (mut depth, mut node) = (0, leaf);
+            while parents[node] != usize::MAX {
+                node = parents[node];
+                depth += 1;
+            }
+            depth.max(1)
+        })
+        .collect()
+}
+
+// 長さから符号語を決める。項目の順に、その長さで使える最も小さい符号語を割り当てる（仕様と同じ方法）
+fn codewords(lengths: &[u8]) -> Vec<u32> {
+    let mut marker = [0u64; 33];
+    lengths
+        .iter()
+        .map(|length| {
+            let length = *length as usize;
+            let codeword = marker[length];
+            for j in (1..=length).rev() {
+                if marker[j] & 1 != 0 {
+                    marker[j] = if j == 1 { marker[1] + 1 } else { marker[j - 1] << 1 };
+                    break;
+                }
+                marker[j] += 1;
+            }
+            let mut branch = codeword;
+            for j in length + 1..33 {
+                if marker[j] >> 1 != branch {
+                    break;
+                }
+                branch = marker[j];
+                marker[j] = marker[j - 1] << 1;
+            }
+            codeword as u32
+        })
+        .collect()
+}
+
+fn ilog(value: u32) -> u32 {
+    32 - value.leading_zeros()
+}
+
+// 下位ビットから詰めて書く
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    used: u32, // 最後のバイトで使ったビット数（0 は次のビットで新しいバイト）
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, bits: u32) {
+        for index in 0..bits {
+            if self.used == 0 {
+                self.bytes.push(0);
+            }
+            let last = self.bytes.len() - 1;
+            self.bytes[last] |= (((value >> index) & 1) as u8) << self.used;
+            self.used = (self.used + 1) % 8;
+        }
+    }
+    
+    fn put_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.put(*byte as u32, 8);
+        }
+    }
+    
+    // 整数を Vorbis の浮動小数点の形式で書く（仮数 21 ビット、指数は 788 で 2^0）
+    fn put_float(&mut self, value: i32) {
+        let sign = if value < 0 { 1 << 31 } else { 0 };
+        self.put(sign | 788 << 21 | value.unsigned_abs(), 32);
+    }
+    
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
@@ -0,0 +1,65 @@
+// Ogg Vorbis の書き出しのテスト（別の実装の復号器で読み戻す）
+use crate::export::{write_audio, ExportFormat};
+
+fn decode(path: &std::path::Path) -> (u32, Vec<[f32; 2]>) {
+    let mut reader = lewton::inside_ogg::OggStreamReader::new(std::fs::File::open(path).unwrap()).unwrap();
+    assert_eq!(reader.ident_hdr.audio_channels, 2);
+    let mut frames = Vec::new();
+    while let Some(packet) = reader.read_dec_packet_generic::<Vec<Vec<f32>>>().unwrap() {
+        frames.extend(packet[0].iter().zip(&packet[1]).map(|(left, right)| [*left, *right]));
+    }
+    (reader.ident_hdr.audio_sample_rate, frames)
+}
+
+fn error_ratio(decoded: &[[f32; 2]], frames: &[[f32; 2]], channel: usize) -> f32 {
+    let error: f32 = decoded.iter().zip(frames).map(|(a, b)| (a[channel] - b[channel]).powi(2)).sum();
+    let signal: f32 = frames.iter().map(|frame| frame[channel].powi(2)).sum();
+    (error / signal).sqrt()
+}
+
+// 和音と減衰する高い音を書き出し、長さが元どおりで、品質を上げるほど誤差が小さくなる（最高の品質では2段の残差も使う）
+#[test]
+fn vorbis_decodes_back_close_to_the_input() {
+    let sample_rate = 44100;
+    let frames: Vec<[f32; 2]> = (0..30_000)
+        .map(|i| {
+            let time = i as f32 / sample_rate as f32;
+            let tone = |frequency: f32| (2.0 * std::f32::consts::PI * frequency * time).sin();
+            let left = 0.3 * tone(220.0) + 0.2 * tone(330.0) + 0.1 * tone(440.0);
+            let right = 0.25 * tone(277.2) + 0.2 * tone(3520.0) * (-time * 4.0).exp();
+            [left, right]
+        })
+        .collect();
+    
+    let mut previous = f32::MAX;
+    for (quality, limit) in [(0, 0.15), (5, 0.04), (10, 0.01)] {
+        let path = std::env::temp_dir().join(format!("synthesizer-vorbis-test-{}-{}.ogg", std::process::id(), quality));
+        write_audio(&path, sample_rate, &frames, ExportFormat::Vorbis { quality }).unwrap();
+        let size = std::fs::metadata(&path).unwrap().len();
+        let (rate, decoded) = decode(&path);
+        std::fs::remove_file(&path).unwrap();
+        
+        assert_eq!(rate, sample_rate);
+        assert_eq!(decoded.len(), frames.len(), "quality {}", quality);
+        let error = error_ratio(&decoded, &frames, 0).max(error_ratio(&decoded, &frames, 1));
+        assert!(error < limit, "quality {}: relative error {}", quality, error);
+        assert!(error < previous, "quality {}: error {} is not below the lower quality ({})", quality, error, previous);
+        assert!(size < frames.len() as u64 * 2, "quality {}: {} bytes is not smaller than 8-bit PCM", quality, size);
+        previous = error;
+    }
+}
+
+// 無音のチャンネルは床を 0 にして送らず、そのまま無音に戻る
+#[test]
+fn silent_vorbis_channel_stays_silent() {
+    let sample_rate = 48000;
+    let frames: Vec<[f32; 2]> = (0..5000).map(|i| [(i as f32 * 0.05).sin() * 0.5, 0.0]).collect();
+    let path = std::env::temp_dir().join(format!("synthesizer-vorbis-silence-{}.ogg", std::process::id()));
+    write_audio(&path, sample_rate, &frames, ExportFormat::Vorbis { quality: 5 }).unwrap();
+    let (_, decoded) = decode(&path);
+    std::fs::remove_file(&path).unwrap();
+    
+    assert_eq!(decoded.len(), frames.len());
+    assert!(decoded.iter().all(|frame| frame[1] == 0.0));
+    assert!(error_ratio(&decoded, &frames, 0) < 0.1);
+}
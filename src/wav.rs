@@ -0,0 +1,74 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const CHANNELS: u16 = 2;
+const BYTES_PER_SAMPLE: u16 = 4;
+const BLOCK_ALIGN: u16 = CHANNELS * BYTES_PER_SAMPLE;
+
+// 32bit float ステレオの WAV ヘッダー（data_size はデータ部のバイト数）
+fn write_header(writer: &mut impl Write, sample_rate: u32, data_size: u32) -> io::Result<()> {
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+    
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&3u16.to_le_bytes())?; // IEEE float
+    writer.write_all(&CHANNELS.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * BLOCK_ALIGN as u32).to_le_bytes())?;
+    writer.write_all(&BLOCK_ALIGN.to_le_bytes())?;
+    writer.write_all(&(BYTES_PER_SAMPLE * 8).to_le_bytes())?;
+    
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())
+}
+
+fn write_frames(writer: &mut impl Write, frames: &[[f32; 2]]) -> io::Result<()> {
+    for frame in frames {
+        for sample in frame {
+            writer.write_all(&sample.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+// ステレオのフレーム列を 32bit float の WAV ファイルとして書き出す
+pub fn write_wav(path: impl AsRef<Path>, sample_rate: u32, frames: &[[f32; 2]]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_header(&mut writer, sample_rate, frames.len() as u32 * BLOCK_ALIGN as u32)?;
+    write_frames(&mut writer, frames)?;
+    writer.flush()
+}
+
+// 長さが決まっていない録音を少しずつ書き出す。finish でヘッダーの長さを書き直す
+pub struct WavWriter {
+    writer: BufWriter<File>,
+    frames: u32,
+}
+
+impl WavWriter {
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_header(&mut writer, sample_rate, 0)?;
+        Ok(Self { writer, frames: 0 })
+    }
+    
+    pub fn write(&mut self, frames: &[[f32; 2]]) -> io::Result<()> {
+        write_frames(&mut self.writer, frames)?;
+        self.frames += frames.len() as u32;
+        Ok(())
+    }
+    
+    // 書き出したフレーム数を返す
+    pub fn finish(mut self) -> io::Result<u32> {
+        let data_size = self.frames * BLOCK_ALIGN as u32;
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_all(&(36 + data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(40))?;
+        self.writer.write_all(&data_size.to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(self.frames)
+    }
+}